@@ -0,0 +1,22 @@
+use fonttools::fea;
+use fonttools_cli::{open_font, read_args};
+use std::fs::File;
+use std::io::Write;
+
+fn main() {
+    let matches = read_args(
+        "ttf-decompile-features",
+        "Writes a font's layout tables out as a feature file",
+    );
+    let mut infont = open_font(&matches);
+    let features = fea::decompile(&mut infont).expect("Could not decompile layout tables");
+    if matches.is_present("OUTPUT") {
+        let mut outfile = File::create(matches.value_of("OUTPUT").unwrap())
+            .expect("Could not open file for writing");
+        outfile
+            .write_all(features.as_bytes())
+            .expect("Could not write feature file");
+    } else {
+        print!("{}", features);
+    }
+}
@@ -0,0 +1,33 @@
+use clap::{App, Arg};
+use fonttools::font;
+use std::fs::File;
+
+fn load(filename: &str) -> font::Font {
+    let file = File::open(filename).expect("Could not open font");
+    font::load(file).expect("Could not parse font")
+}
+
+fn main() {
+    let matches = App::new("ttf-diff")
+        .about("Compares two fonts table by table")
+        .arg(
+            Arg::with_name("OLD")
+                .help("The original font")
+                .required(true),
+        )
+        .arg(
+            Arg::with_name("NEW")
+                .help("The font to compare it with")
+                .required(true),
+        )
+        .get_matches();
+    let mut old = load(matches.value_of("OLD").unwrap());
+    let mut new = load(matches.value_of("NEW").unwrap());
+    let differences = font::diff(&mut old, &mut new).expect("Could not compare fonts");
+    for difference in &differences {
+        println!("{}", difference);
+    }
+    if !differences.is_empty() {
+        std::process::exit(1);
+    }
+}
@@ -0,0 +1,28 @@
+use fonttools::font::Table;
+use fonttools_cli::{open_font, read_args, save_font};
+
+fn main() {
+    env_logger::init();
+    let matches = read_args(
+        "ttf-fix-os2-ranges",
+        "Recalculates the OS/2 Unicode and code page ranges from the cmap table",
+    );
+    let mut infont = open_font(&matches);
+    let mapping = infont
+        .get_table(b"cmap")
+        .expect("Error reading cmap table")
+        .expect("No cmap table found")
+        .cmap_unchecked()
+        .getBestMapping()
+        .cloned()
+        .expect("No Unicode subtable found in cmap table");
+    if let Table::Os2(os2) = infont
+        .get_table(b"OS/2")
+        .expect("Error reading OS/2 table")
+        .expect("No OS/2 table found")
+    {
+        os2.calc_unicode_ranges(&mapping);
+        os2.calc_code_page_ranges(&mapping);
+    }
+    save_font(infont, &matches);
+}
@@ -34,7 +34,7 @@ fn main() {
         .get_matches();
     let mut infont = open_font(&matches);
     let has_cff = infont.tables.contains_key(b"CFF ");
-    let num_glyphs = infont.num_glyphs();
+    let num_glyphs = infont.num_glyphs().expect("Could not read maxp table");
     let mut reversed_map = BTreeMap::new();
 
     if let Table::Cmap(cmap) = infont
@@ -0,0 +1,16 @@
+use fonttools_cli::{open_font, read_args};
+use std::fs::File;
+use std::io;
+
+fn main() {
+    let matches = read_args("ttf-to-woff", "Converts a TTF file to WOFF 1.0");
+    let mut infont = open_font(&matches);
+    if matches.is_present("OUTPUT") {
+        let mut outfile = File::create(matches.value_of("OUTPUT").unwrap())
+            .expect("Could not open file for writing");
+        infont.save_woff(&mut outfile)
+    } else {
+        infont.save_woff(&mut io::stdout())
+    }
+    .expect("Could not write font");
+}
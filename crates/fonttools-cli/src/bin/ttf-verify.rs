@@ -0,0 +1,29 @@
+use fonttools::font;
+use fonttools_cli::read_args;
+use std::fs::File;
+use std::io::{self, Read};
+
+fn main() {
+    let matches = read_args(
+        "ttf-verify",
+        "Checks table checksums and the table directory of a TTF file",
+    );
+    let mut data = vec![];
+    if matches.is_present("INPUT") {
+        let filename = matches.value_of("INPUT").unwrap();
+        File::open(filename)
+            .and_then(|mut f| f.read_to_end(&mut data))
+            .expect("Could not read font");
+    } else {
+        io::stdin()
+            .read_to_end(&mut data)
+            .expect("Could not read font");
+    }
+    let issues = font::verify(&data).expect("Could not parse font");
+    for issue in &issues {
+        println!("{}", issue);
+    }
+    if !issues.is_empty() {
+        std::process::exit(1);
+    }
+}
@@ -0,0 +1,40 @@
+use fonttools::{font, ttx};
+use fonttools_cli::{read_args, save_font};
+use std::fs::File;
+use std::io::{self, Read, Write};
+
+fn main() {
+    let matches = read_args(
+        "ttx",
+        "Dumps a binary font to TTX, or compiles TTX to a binary font",
+    );
+    let mut data = vec![];
+    if matches.is_present("INPUT") {
+        let filename = matches.value_of("INPUT").unwrap();
+        File::open(filename)
+            .and_then(|mut f| f.read_to_end(&mut data))
+            .expect("Could not read input");
+    } else {
+        io::stdin()
+            .read_to_end(&mut data)
+            .expect("Could not read input");
+    }
+
+    let text = String::from_utf8_lossy(&data);
+    let text = text.trim_start_matches('\u{feff}').trim_start();
+    if text.starts_with("<?xml") || text.starts_with("<ttFont") {
+        let font = ttx::compile(text).expect("Could not compile TTX");
+        save_font(font, &matches);
+        return;
+    }
+
+    let mut infont = font::load(data.as_slice()).expect("Could not parse font");
+    let dumped = ttx::dump(&mut infont).expect("Could not dump font");
+    if matches.is_present("OUTPUT") {
+        File::create(matches.value_of("OUTPUT").unwrap())
+            .and_then(|mut f| f.write_all(dumped.as_bytes()))
+    } else {
+        io::stdout().write_all(dumped.as_bytes())
+    }
+    .expect("Could not write TTX");
+}
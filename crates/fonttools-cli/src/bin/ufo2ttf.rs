@@ -8,7 +8,7 @@ use fonttools::hhea;
 use fonttools::hmtx;
 use fonttools::maxp::maxp;
 use fonttools::name::{name, NameRecord, NameRecordID};
-use fonttools::os2::os2;
+use fonttools::os2::{os2, SelectionFlags};
 use fonttools::post::post;
 use fonttools_cli::font_info_data::*;
 use lyon::geom::cubic_bezier::CubicBezierSegment;
@@ -77,6 +77,14 @@ fn compile_head(info: &norad::FontInfo, glyf: &glyf::glyf) -> head {
     }
 
     // mac style
+    let style = style_map_style_name(info);
+    if style.contains("bold") {
+        head_table.macStyle |= 1 << 0;
+    }
+    if style.contains("italic") {
+        head_table.macStyle |= 1 << 1;
+    }
+
     if let Some(lowest_rec_ppm) = info.open_type_head_lowest_rec_ppem {
         head_table.lowestRecPPEM = lowest_rec_ppm as u16;
     }
@@ -101,27 +109,94 @@ fn compile_post(info: &norad::FontInfo, names: &[String]) -> post {
     )
 }
 
-fn compile_cmap(mapping: BTreeMap<u32, u16>) -> cmap::cmap {
+/// Compiles format 4 subtables for the Basic Multilingual Plane, along with
+/// format 12 subtables if any codepoints lie beyond it
+fn compile_cmap(
+    mapping: BTreeMap<u32, u16>,
+    variation_sequences: cmap::VariationSequences,
+) -> cmap::cmap {
+    let bmp: BTreeMap<u32, u16> = mapping.range(..0xFFFF).map(|(&c, &g)| (c, g)).collect();
+    let subtable = |format, platform, encoding, mapping| cmap::CmapSubtable {
+        format,
+        platformID: platform,
+        encodingID: encoding,
+        languageID: 0,
+        mapping,
+    };
+    if bmp.len() == mapping.len() {
+        return cmap::cmap {
+            subtables: vec![subtable(4, 0, 3, bmp.clone()), subtable(4, 3, 1, bmp)],
+            variationSequences: variation_sequences,
+        };
+    }
     cmap::cmap {
         subtables: vec![
-            cmap::CmapSubtable {
-                format: 4,
-                platformID: 0,
-                encodingID: 3,
-                languageID: 0,
-                mapping: mapping.clone(),
-            },
-            cmap::CmapSubtable {
-                format: 4,
-                platformID: 3,
-                encodingID: 1,
-                languageID: 0,
-                mapping,
-            },
+            subtable(4, 0, 3, bmp.clone()),
+            subtable(12, 0, 4, mapping.clone()),
+            subtable(4, 3, 1, bmp),
+            subtable(12, 3, 10, mapping),
         ],
+        variationSequences: variation_sequences,
     }
 }
 
+/// Compiles the Unicode Variation Sequences in the UFO lib
+///
+/// The `public.unicodeVariationSequences` key maps hex variation selectors to
+/// maps of hex base codepoints to glyph names. Sequences whose glyph is the one
+/// the base codepoint is mapped to become default sequences.
+fn compile_variation_sequences(
+    ufo: &Ufo,
+    mapping: &BTreeMap<u32, u16>,
+    name_to_id: &BTreeMap<String, u16>,
+) -> cmap::VariationSequences {
+    let mut sequences = BTreeMap::new();
+    let selectors = match ufo
+        .lib
+        .get("public.unicodeVariationSequences")
+        .and_then(|v| v.as_dictionary())
+    {
+        Some(selectors) => selectors,
+        None => return sequences,
+    };
+    for (selector, bases) in selectors.iter() {
+        let bases = match bases.as_dictionary() {
+            Some(bases) => bases,
+            None => {
+                log::warn!(
+                    "Couldn't read variation sequences for selector {:?}",
+                    selector
+                );
+                continue;
+            }
+        };
+        for (base, glyph) in bases.iter() {
+            let glyph_id = glyph.as_string().and_then(|name| name_to_id.get(name));
+            match (
+                u32::from_str_radix(base, 16),
+                u32::from_str_radix(selector, 16),
+                glyph_id,
+            ) {
+                (Ok(base), Ok(selector), Some(&glyph_id)) => {
+                    let glyph_id = if mapping.get(&base) == Some(&glyph_id) {
+                        None
+                    } else {
+                        Some(glyph_id)
+                    };
+                    sequences.insert((base, selector), glyph_id);
+                }
+                _ => log::warn!(
+                    "Couldn't compile variation sequence {:?} {:?} for glyph {:?}",
+                    base,
+                    selector,
+                    glyph
+                ),
+            }
+        }
+    }
+    sequences
+}
+
 fn compile_hhea(info: &norad::FontInfo, metrics: &[hmtx::Metric], glyf: &glyf::glyf) -> hhea::hhea {
     hhea::hhea {
         majorVersion: 1,
@@ -161,12 +236,7 @@ where
     (offset.into() as f64 * (-angle).to_radians().tan()).round() as i32
 }
 
-fn compile_os2(
-    info: &norad::FontInfo,
-    metrics: &[hmtx::Metric],
-    glyf: &glyf::glyf,
-    mapping: &BTreeMap<u32, u16>,
-) -> os2 {
+fn compile_os2(info: &norad::FontInfo, glyf: &glyf::glyf, mapping: &BTreeMap<u32, u16>) -> os2 {
     let upm = info.units_per_em.map_or(1000.0, |f| f.get());
     let italic_angle = info.italic_angle.map_or(0.0, |f| f.get());
     let xHeight = info.x_height.map_or(upm * 0.5, |f| f.get());
@@ -192,11 +262,10 @@ fn compile_os2(
         .open_type_os2_subscript_x_size
         .unwrap_or((upm * 0.65).round()  as i32)as i16;
 
-    os2 {
+    let mut os2_table = os2 {
         version: 4,
-        xAvgCharWidth: (
-            metrics.iter().map(|m| m.advanceWidth as f32).sum::<f32>() / metrics.iter().filter(|m| m.advanceWidth != 0).count() as f32
-        ).round() as i16,
+        // Set by os2::recalc once the other tables are built
+        xAvgCharWidth: 0,
         usWeightClass: info.open_type_os2_weight_class.unwrap_or(400) as u16,
         usWidthClass: info.open_type_os2_width_class.map_or(5, |f| f as u16),
         fsType: int_list_to_num(&info.open_type_os2_type.as_ref().unwrap_or(&vec![2])) as u16,
@@ -251,18 +320,28 @@ fn compile_os2(
         // sFamilyClass: info.open_type_os2_family_class... (not public)
         sFamilyClass: 0,
         panose: get_panose(info),
-        ulCodePageRange1: Some(0b01100000000000000000000110010011), // XXX
-        ulCodePageRange2: Some(0),                                  // XXX
-        ulUnicodeRange1: 0b10100001000000000000000011111111,        // XXX
-        ulUnicodeRange2: 0,                                         // XXX
-        ulUnicodeRange3: 0,                                         // XXX
-        ulUnicodeRange4: 0,                                         // XXX
-        usFirstCharIndex: *mapping.keys().min().unwrap_or(&0xFFFF) as u16,
-        usLastCharIndex: *mapping.keys().max().unwrap_or(&0xFFFF) as u16,
+        // Set from the mapping below
+        ulCodePageRange1: None,
+        ulCodePageRange2: None,
+        ulUnicodeRange1: 0,
+        ulUnicodeRange2: 0,
+        ulUnicodeRange3: 0,
+        ulUnicodeRange4: 0,
+        usFirstCharIndex: 0,
+        usLastCharIndex: 0,
         usLowerOpticalPointSize: None,
         usUpperOpticalPointSize: None,
-        fsSelection: 0b11000000, // XXX
-    }
+        // The style bits are set from head.macStyle by os2::recalc
+        fsSelection: info
+            .open_type_os2_selection
+            .as_ref()
+            .map_or(SelectionFlags::USE_TYPO_METRICS.bits(), |bits| {
+                bits.iter().fold(0, |flags, bit| flags | 1 << bit)
+            }),
+    };
+    os2_table.calc_unicode_ranges(mapping);
+    os2_table.calc_code_page_ranges(mapping);
+    os2_table
 }
 
 fn compile_name(info: &norad::FontInfo) -> name {
@@ -276,6 +355,7 @@ fn compile_name(info: &norad::FontInfo) -> name {
                 encodingID: record.encoding_id as u16,
                 languageID: record.language_id as u16,
                 string: record.string,
+                languageTag: None,
             })
         }
     }
@@ -578,13 +658,15 @@ fn main() {
         max_component_elements,
         max_component_depth,
     );
-    let os2_table = compile_os2(info, &metrics, &glyf_table, &mapping);
-    let cmap_table = compile_cmap(mapping);
+    let mut os2_table = compile_os2(info, &glyf_table, &mapping);
+    let variation_sequences = compile_variation_sequences(&ufo, &mapping, &name_to_id);
+    let cmap_table = compile_cmap(mapping, variation_sequences);
     let name_table = compile_name(info);
     let mut hhea_table = compile_hhea(info, &metrics, &glyf_table);
     let hmtx_table = hmtx::hmtx { metrics };
     let (hmtx_bytes, num_h_metrics) = hmtx_table.to_bytes();
     hhea_table.numberOfHMetrics = num_h_metrics;
+    os2_table.recalc(&hmtx_table, &cmap_table, &head_table, None, None);
 
     font.tables.insert(*b"head", Table::Head(head_table));
     font.tables.insert(*b"hhea", Table::Hhea(hhea_table));
@@ -624,8 +706,9 @@ fn main() {
     if matches.is_present("OUTPUT") {
         let mut outfile = File::create(matches.value_of("OUTPUT").unwrap())
             .expect("Could not open file for writing");
-        font.save(&mut outfile);
+        font.save(&mut outfile)
     } else {
-        font.save(&mut io::stdout());
-    };
+        font.save(&mut io::stdout())
+    }
+    .expect("Could not write font");
 }
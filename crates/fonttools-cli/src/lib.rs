@@ -34,10 +34,11 @@ pub fn save_font(mut font: Font, matches: &clap::ArgMatches) {
     if matches.is_present("OUTPUT") {
         let mut outfile = File::create(matches.value_of("OUTPUT").unwrap())
             .expect("Could not open file for writing");
-        font.save(&mut outfile);
+        font.save(&mut outfile)
     } else {
-        font.save(&mut io::stdout());
-    };
+        font.save(&mut io::stdout())
+    }
+    .expect("Could not write font");
 }
 
 /* FontInfo things for ufo2ttf */
@@ -1,18 +1,84 @@
-use crate::error::{Error, Result};
+use crate::error::{Error, Location, Result};
+use crate::ser::graph::{Object, ObjectGraph};
 use serde::de::{self, Deserialize, DeserializeSeed, SeqAccess, Visitor};
+use std::borrow::Cow;
 use std::convert::TryInto;
 use std::mem;
 
+/// One step in the path to the field being deserialized
+enum PathSegment {
+    Field(Cow<'static, str>),
+    Index(usize),
+}
+
 pub struct Deserializer<'de> {
     // This string starts with the input data and characters are truncated off
     // the beginning as data is parsed.
     input: &'de [u8],
     ptr: usize,
+    // The offset of the input within the table being read, for error reporting
+    base: usize,
+    path: Vec<PathSegment>,
 }
 
 impl<'de> Deserializer<'de> {
     pub fn from_bytes(input: &'de [u8]) -> Self {
-        Deserializer { input, ptr: 0 }
+        Deserializer {
+            input,
+            ptr: 0,
+            base: 0,
+            path: vec![],
+        }
+    }
+
+    /// Creates a deserializer for data found `base` bytes into a table
+    ///
+    /// Errors will report their offsets relative to the start of the table.
+    pub fn from_bytes_at_offset(input: &'de [u8], base: usize) -> Self {
+        Deserializer {
+            base,
+            ..Deserializer::from_bytes(input)
+        }
+    }
+
+    /// Deserializes a value, reporting any errors as occurring within `field`
+    pub fn read_field<T>(&mut self, field: impl Into<String>, seed: T) -> Result<T::Value>
+    where
+        T: DeserializeSeed<'de>,
+    {
+        self.path.push(PathSegment::Field(Cow::Owned(field.into())));
+        let start = self.ptr;
+        let result = seed
+            .deserialize(&mut *self)
+            .map_err(|e| self.locate(e, start));
+        self.path.pop();
+        result
+    }
+
+    /// The path to the field currently being read, e.g. `instances[3].coordinates`
+    fn path_string(&self) -> String {
+        let mut path = String::new();
+        for segment in &self.path {
+            match segment {
+                PathSegment::Field(name) => {
+                    if !path.is_empty() {
+                        path.push('.');
+                    }
+                    path.push_str(name);
+                }
+                PathSegment::Index(index) => path.push_str(&format!("[{}]", index)),
+            }
+        }
+        path
+    }
+
+    /// Attaches the current field path and the given input position to an error
+    fn locate(&self, error: Error, ptr: usize) -> Error {
+        error.at(Location {
+            table: None,
+            path: self.path_string(),
+            offset: Some(self.base + ptr),
+        })
     }
 }
 
@@ -29,15 +95,57 @@ where
     // }
 }
 
+/// Deserializes a structure found at `offset` bytes from the start of its parent
+///
+/// `remainder` holds the parent's data following its header, which is
+/// `header_len` bytes long.
+pub fn from_bytes_at<T>(remainder: &[u8], offset: usize, header_len: usize) -> Result<T>
+where
+    T: de::DeserializeOwned,
+{
+    let data = offset
+        .checked_sub(header_len)
+        .and_then(|start| remainder.get(start..))
+        .ok_or_else(|| Error::Message(format!("Bad offset {}", offset)))?;
+    T::deserialize(&mut Deserializer::from_bytes_at_offset(data, offset))
+}
+
+/// A record whose offsets are relative to the start of the structure
+/// containing it, rather than to the record itself
+///
+/// The `tables!` macro implements this for structures with offsets, so that
+/// they can be used as the records of a `Records(...)` field.
+pub trait OffsetRecord: Sized {
+    /// The record as stored, with its offsets as plain integers
+    type Raw: de::DeserializeOwned + serde::Serialize;
+
+    /// Follows the offsets of a raw record
+    ///
+    /// `remainder` holds the containing structure's data following its
+    /// header, which is `header_len` bytes long.
+    fn resolve(raw: Self::Raw, remainder: &[u8], header_len: usize) -> Result<Self>;
+
+    /// Appends the record to the object of the structure containing it
+    fn push_to(&self, object: &mut Object, graph: &mut ObjectGraph) -> Result<()>;
+}
+
 impl<'de> Deserializer<'de> {
     fn consume(&mut self, bytes: usize) -> Result<&'de [u8]> {
-        if self.ptr + bytes > self.input.len() {
-            Err(Error::Eof)
-        } else {
-            let subslice = &self.input[self.ptr..self.ptr + bytes];
-            self.ptr += bytes;
-            Ok(subslice)
-        }
+        let end = match self.ptr.checked_add(bytes) {
+            Some(end) if end <= self.input.len() => end,
+            _ => {
+                return Err(self.locate(
+                    Error::UnexpectedEof {
+                        needed: bytes,
+                        available: self.input.len().saturating_sub(self.ptr),
+                    },
+                    self.ptr,
+                ))
+            }
+        };
+        let subslice = &self.input[self.ptr..end];
+        self.ptr = end;
+        Ok(subslice)
     }
 
     fn parse_bool(&mut self) -> Result<bool> {
@@ -79,12 +187,16 @@ impl<'de, 'a> SeqAccess<'de> for Deserializer<'de> {
 impl<'de, 'a> de::Deserializer<'de> for &'a mut Deserializer<'de> {
     type Error = Error;
 
+    fn is_human_readable(&self) -> bool {
+        false
+    }
+
     #[inline]
     fn deserialize_any<V>(self, _visitor: V) -> Result<V::Value>
     where
         V: serde::de::Visitor<'de>,
     {
-        Err(Error::DeserializeAnyNotSupported)
+        Err(self.locate(Error::DeserializeAnyNotSupported, self.ptr))
     }
 
     fn deserialize_bool<V>(self, visitor: V) -> Result<V::Value>
@@ -168,7 +280,7 @@ impl<'de, 'a> de::Deserializer<'de> for &'a mut Deserializer<'de> {
     where
         V: Visitor<'de>,
     {
-        Err(Error::ExpectedNull)
+        Err(self.locate(Error::ExpectedNull, self.ptr))
     }
 
     // Unit struct means a named value containing no data.
@@ -186,18 +298,22 @@ impl<'de, 'a> de::Deserializer<'de> for &'a mut Deserializer<'de> {
         visitor.visit_newtype_struct(self)
     }
 
-    fn deserialize_seq<V>(mut self, visitor: V) -> Result<V::Value>
+    fn deserialize_seq<V>(self, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        visitor.visit_seq(EofChecking::new(&mut self))
+        let start = self.ptr;
+        let result = visitor.visit_seq(EofChecking::new(self, Elements::Unnamed));
+        result.map_err(|e| self.locate(e, start))
     }
 
-    fn deserialize_tuple<V>(self, _len: usize, visitor: V) -> Result<V::Value>
+    fn deserialize_tuple<V>(self, len: usize, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        self.deserialize_seq(visitor)
+        let start = self.ptr;
+        let result = visitor.visit_seq(EofChecking::new(self, Elements::Indexed(len)));
+        result.map_err(|e| self.locate(e, start))
     }
 
     fn deserialize_tuple_struct<V>(
@@ -216,7 +332,7 @@ impl<'de, 'a> de::Deserializer<'de> for &'a mut Deserializer<'de> {
     where
         V: Visitor<'de>,
     {
-        Err(Error::ExpectedMap)
+        Err(self.locate(Error::ExpectedMap, self.ptr))
     }
 
     fn deserialize_struct<V>(
@@ -228,7 +344,9 @@ impl<'de, 'a> de::Deserializer<'de> for &'a mut Deserializer<'de> {
     where
         V: Visitor<'de>,
     {
-        self.deserialize_tuple(fields.len(), visitor)
+        let start = self.ptr;
+        let result = visitor.visit_seq(EofChecking::new(self, Elements::Named(fields)));
+        result.map_err(|e| self.locate(e, start))
     }
 
     fn deserialize_enum<V>(
@@ -244,7 +362,7 @@ impl<'de, 'a> de::Deserializer<'de> for &'a mut Deserializer<'de> {
         //     "Tying to deserialize an enum {:?}, variants {:?}",
         //     name, variants
         // );
-        Err(Error::ExpectedEnum)
+        Err(self.locate(Error::ExpectedEnum, self.ptr))
     }
 
     fn deserialize_identifier<V>(self, _visitor: V) -> Result<V::Value>
@@ -262,13 +380,39 @@ impl<'de, 'a> de::Deserializer<'de> for &'a mut Deserializer<'de> {
     }
 }
 
+/// How the elements of a sequence are described in error locations
+enum Elements {
+    /// Elements of a struct which has been read by hand
+    Unnamed,
+    /// Elements of an array of the given length
+    Indexed(usize),
+    /// The fields of a struct
+    Named(&'static [&'static str]),
+}
+
 struct EofChecking<'a, 'de: 'a> {
     de: &'a mut Deserializer<'de>,
+    elements: Elements,
+    index: usize,
 }
 
 impl<'a, 'de> EofChecking<'a, 'de> {
-    fn new(de: &'a mut Deserializer<'de>) -> Self {
-        EofChecking { de }
+    fn new(de: &'a mut Deserializer<'de>, elements: Elements) -> Self {
+        EofChecking {
+            de,
+            elements,
+            index: 0,
+        }
+    }
+
+    fn segment(&self) -> Option<PathSegment> {
+        match self.elements {
+            Elements::Indexed(len) if self.index < len => Some(PathSegment::Index(self.index)),
+            Elements::Named(fields) => fields
+                .get(self.index)
+                .map(|name| PathSegment::Field(Cow::Borrowed(name))),
+            _ => None,
+        }
     }
 }
 
@@ -279,17 +423,20 @@ impl<'de, 'a> SeqAccess<'de> for EofChecking<'a, 'de> {
     where
         T: DeserializeSeed<'de>,
     {
-        // println!(
-        //     "Is there more? PTR = {:?} len = {:?}",
-        //     self.de.ptr,
-        //     self.de.input.len()
-        // );
         if self.de.ptr >= self.de.input.len() {
-            // println!("No, we hit the end");
             return Ok(None);
         }
-        // println!("Yes, let's go...");
-        seed.deserialize(&mut *self.de).map(Some)
+        let segment = self.segment();
+        self.index += 1;
+        match segment {
+            Some(segment) => {
+                self.de.path.push(segment);
+                let result = seed.deserialize(&mut *self.de);
+                self.de.path.pop();
+                result.map(Some)
+            }
+            None => seed.deserialize(&mut *self.de).map(Some),
+        }
     }
 }
 
@@ -329,48 +476,58 @@ where
             type Value = Vec<T>;
 
             fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
-                write!(formatter, "an array of data")
+                write!(formatter, "{} elements", self.len)
             }
 
-            fn visit_seq<A>(mut self, mut seq: A) -> std::result::Result<Vec<T>, A::Error>
+            fn visit_seq<A>(self, mut seq: A) -> std::result::Result<Vec<T>, A::Error>
             where
                 A: SeqAccess<'de>,
             {
                 let mut res = Vec::new();
-                if self.len > 0 {
-                    while let Some(elem) = seq.next_element()? {
-                        res.push(elem);
-                        self.len -= 1;
-                        if self.len == 0 {
-                            break;
-                        }
+                while res.len() < self.len {
+                    match seq.next_element()? {
+                        Some(elem) => res.push(elem),
+                        None => return Err(de::Error::invalid_length(res.len(), &self)),
                     }
                 }
                 Ok(res)
             }
         }
 
-        deserializer.deserialize_seq(CountedDeserializerVisitor {
-            len: self.len,
-            _phantom: &std::marker::PhantomData,
-        })
+        deserializer.deserialize_tuple(
+            self.len,
+            CountedDeserializerVisitor {
+                len: self.len,
+                _phantom: &std::marker::PhantomData,
+            },
+        )
     }
 }
 
+/// Implements `Deserialize` for a structure using a sequence visitor.
+///
+/// If a `readable:` type is given, human-readable formats are instead
+/// deserialized through it, usually a `#[serde(remote = ...)]` definition
+/// of the structure.
 #[macro_export]
 macro_rules! deserialize_visitor {
-    ($struct_name:ident, $visitor_name:ident, $item:item) => {
-        struct $visitor_name {
-            _phantom: std::marker::PhantomData<$struct_name>,
-        }
+    ($struct_name:ident, $visitor_name:ident, readable: $readable:ident, $item:item) => {
+        $crate::deserialize_visitor!(@visitor $struct_name, $visitor_name, $item);
 
-        impl $visitor_name {
-            fn new() -> Self {
-                $visitor_name {
-                    _phantom: std::marker::PhantomData,
+        impl<'de> Deserialize<'de> for $struct_name {
+            fn deserialize<D>(d: D) -> Result<Self, D::Error>
+            where
+                D: Deserializer<'de>,
+            {
+                if d.is_human_readable() {
+                    return $readable::deserialize(d);
                 }
+                d.deserialize_seq($visitor_name::new())
             }
         }
+    };
+    ($struct_name:ident, $visitor_name:ident, $item:item) => {
+        $crate::deserialize_visitor!(@visitor $struct_name, $visitor_name, $item);
 
         impl<'de> Deserialize<'de> for $struct_name {
             fn deserialize<D>(d: D) -> Result<Self, D::Error>
@@ -380,6 +537,19 @@ macro_rules! deserialize_visitor {
                 d.deserialize_seq($visitor_name::new())
             }
         }
+    };
+    (@visitor $struct_name:ident, $visitor_name:ident, $item:item) => {
+        struct $visitor_name {
+            _phantom: std::marker::PhantomData<$struct_name>,
+        }
+
+        impl $visitor_name {
+            fn new() -> Self {
+                $visitor_name {
+                    _phantom: std::marker::PhantomData,
+                }
+            }
+        }
 
         impl<'de> Visitor<'de> for $visitor_name {
             type Value = $struct_name;
@@ -389,7 +559,6 @@ macro_rules! deserialize_visitor {
             }
             $item
         }
-
     };
 }
 
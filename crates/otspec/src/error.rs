@@ -1,43 +1,188 @@
 use std;
+use std::cell::RefCell;
 use std::fmt::{self, Display};
 
 use serde::{de, ser};
 
 pub type Result<T> = std::result::Result<T, Error>;
 
-// This is a bare-bones implementation. A real library would provide additional
-// information in its error type, for example the line and column at which the
-// error occurred, the byte offset into the input, or the current key being
-// processed.
+/// Where in the input an error occurred
+#[derive(Clone, Debug, PartialEq, Default)]
+pub struct Location {
+    /// The tag of the table being read, if known
+    pub table: Option<[u8; 4]>,
+    /// The path to the field being read, e.g. `instances[3].coordinates`
+    pub path: String,
+    /// The byte offset from the start of the table (or structure) being read
+    pub offset: Option<usize>,
+}
+
+impl Display for Location {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(table) = self.table {
+            formatter.write_str(&String::from_utf8_lossy(&table))?;
+            if !self.path.is_empty() {
+                formatter.write_str(".")?;
+            }
+        }
+        formatter.write_str(&self.path)?;
+        if let Some(offset) = self.offset {
+            if self.table.is_some() || !self.path.is_empty() {
+                formatter.write_str(" ")?;
+            }
+            write!(formatter, "at byte {}", offset)?;
+        }
+        Ok(())
+    }
+}
+
 #[derive(Clone, Debug, PartialEq)]
 pub enum Error {
-    // One or more variants that can be created by data structures through the
-    // `ser::Error` and `de::Error` traits. For example the Serialize impl for
-    // Mutex<T> might return an error because the mutex is poisoned, or the
-    // Deserialize impl for a struct may return an error because a required
-    // field is missing.
+    /// A free-form error raised by a table's (de)serialization code
     Message(String),
-
-    // Zero or more variants that can be created directly by the Serializer and
-    // Deserializer without going through `ser::Error` and `de::Error`. These
-    // are specific to the format, in this case JSON.
-    Eof,
-    Syntax,
-    ExpectedBoolean,
-    ExpectedInteger,
-    ExpectedString,
+    /// The input ended before a value could be read
+    UnexpectedEof {
+        /// The number of bytes needed to read the value
+        needed: usize,
+        /// The number of bytes remaining in the input
+        available: usize,
+    },
+    /// A sequence had a different number of elements than expected
+    InvalidLength {
+        /// A description of what was expected
+        expected: String,
+        /// The number of elements actually found
+        found: usize,
+    },
     ExpectedNull,
-    ExpectedArray,
-    ExpectedArrayComma,
-    ExpectedArrayEnd,
     ExpectedMap,
-    ExpectedMapColon,
-    ExpectedMapComma,
-    ExpectedMapEnd,
     ExpectedEnum,
-    TrailingCharacters,
     DeserializeAnyNotSupported,
     DeserializedInWrongOrder,
+    OffsetOverflow,
+    /// An error annotated with the location in the input at which it occurred
+    Located {
+        location: Location,
+        error: Box<Error>,
+    },
+    /// An error raised while reading a nested structure, on its way back to
+    /// the deserializer of the enclosing structure (see [`Error::nested`])
+    Nested(Box<Error>),
+}
+
+thread_local! {
+    /// The error being passed up by [`Error::nested`], which is only set for
+    /// the duration of its call to `custom`
+    static NESTED: RefCell<Option<Error>> = const { RefCell::new(None) };
+}
+
+/// Joins the path of a structure and a path within it
+fn join_path(outer: &str, inner: &str) -> String {
+    if outer.is_empty() {
+        inner.to_string()
+    } else if inner.is_empty() || inner.starts_with('[') {
+        format!("{}{}", outer, inner)
+    } else {
+        format!("{}.{}", outer, inner)
+    }
+}
+
+impl Error {
+    /// Returns the location at which this error occurred, if known
+    pub fn location(&self) -> Option<&Location> {
+        match self {
+            Error::Located { location, .. } => Some(location),
+            Error::Nested(error) => error.location(),
+            _ => None,
+        }
+    }
+
+    /// Returns the underlying error, without its location
+    pub fn inner(&self) -> &Error {
+        match self {
+            Error::Located { error, .. } | Error::Nested(error) => error.inner(),
+            _ => self,
+        }
+    }
+
+    /// Attaches a location to this error, unless it already has one
+    ///
+    /// The location of a nested error is relative to the structure at the
+    /// given location, and is rebased onto it.
+    pub fn at(self, location: Location) -> Error {
+        match self {
+            Error::Located { .. } => self,
+            Error::Nested(error) => {
+                let error = error.within(&location.path, location.offset.unwrap_or(0));
+                match location.table {
+                    Some(table) => error.in_table(table),
+                    None => error,
+                }
+            }
+            _ => Error::Located {
+                location,
+                error: Box::new(self),
+            },
+        }
+    }
+
+    /// Places an error raised while reading a nested structure within its
+    /// parent, prepending the structure's path to the error's path and adding
+    /// the structure's offset to the error's offset
+    pub fn within(self, path: &str, offset: usize) -> Error {
+        match self {
+            Error::Located {
+                mut location,
+                error,
+            } => {
+                location.path = join_path(path, &location.path);
+                location.offset = Some(location.offset.unwrap_or(0) + offset);
+                Error::Located { location, error }
+            }
+            Error::Nested(error) => Error::Nested(Box::new(error.within(path, offset))),
+            _ => self.at(Location {
+                table: None,
+                path: path.to_string(),
+                offset: Some(offset),
+            }),
+        }
+    }
+
+    /// Passes an error raised by a nested deserializer up through a visitor
+    ///
+    /// Visitors are generic over their error type, so could otherwise only
+    /// return a nested error as a message. When the visitor is driven by this
+    /// crate's deserializer, the error is instead passed through intact, and
+    /// its location (which is relative to the structure being visited) is
+    /// rebased onto the location of that structure.
+    ///
+    /// The error is handed to this crate's `custom` for the duration of the
+    /// call only, so it can never be picked up by an unrelated later call.
+    pub fn nested<E: de::Error>(self) -> E {
+        let message = self.to_string();
+        NESTED.with(|nested| *nested.borrow_mut() = Some(self));
+        let error = E::custom(message);
+        // Other deserializers' errors leave it behind
+        NESTED.with(|nested| nested.borrow_mut().take());
+        error
+    }
+
+    /// Records the tag of the table which was being read when this error occurred
+    pub fn in_table(self, tag: [u8; 4]) -> Error {
+        match self {
+            Error::Located {
+                mut location,
+                error,
+            } => {
+                location.table.get_or_insert(tag);
+                Error::Located { location, error }
+            }
+            _ => self.at(Location {
+                table: Some(tag),
+                ..Default::default()
+            }),
+        }
+    }
 }
 
 impl ser::Error for Error {
@@ -48,7 +193,17 @@ impl ser::Error for Error {
 
 impl de::Error for Error {
     fn custom<T: Display>(msg: T) -> Self {
-        Error::Message(msg.to_string())
+        match NESTED.with(|nested| nested.borrow_mut().take()) {
+            Some(error) => Error::Nested(Box::new(error)),
+            None => Error::Message(msg.to_string()),
+        }
+    }
+
+    fn invalid_length(len: usize, exp: &dyn de::Expected) -> Self {
+        Error::InvalidLength {
+            expected: exp.to_string(),
+            found: len,
+        }
     }
 }
 
@@ -56,10 +211,108 @@ impl Display for Error {
     fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
         match self {
             Error::Message(msg) => formatter.write_str(msg),
-            Error::Eof => formatter.write_str("unexpected end of input"),
-            _ => formatter.write_str("done gone wrong"),
+            Error::UnexpectedEof { needed, available } => write!(
+                formatter,
+                "unexpected end of input: needed {} bytes, found {}",
+                needed, available
+            ),
+            Error::InvalidLength { expected, found } => write!(
+                formatter,
+                "invalid length: expected {}, found {}",
+                expected, found
+            ),
+            Error::ExpectedNull => formatter.write_str("cannot deserialize a unit value"),
+            Error::ExpectedMap => formatter.write_str("cannot deserialize a map"),
+            Error::ExpectedEnum => formatter.write_str("cannot deserialize an enum"),
+            Error::DeserializeAnyNotSupported => {
+                formatter.write_str("cannot deserialize a value without knowing its type")
+            }
+            Error::DeserializedInWrongOrder => {
+                formatter.write_str("table depends on a table which has not been deserialized")
+            }
+            Error::OffsetOverflow => formatter.write_str("offset too large to be serialized"),
+            Error::Located { location, error } => write!(formatter, "{}: {}", location, error),
+            Error::Nested(error) => error.fmt(formatter),
         }
     }
 }
 
 impl std::error::Error for Error {}
+
+#[cfg(test)]
+mod tests {
+    use crate::error::{Error, Location};
+
+    #[test]
+    fn error_display() {
+        let error = Error::UnexpectedEof {
+            needed: 4,
+            available: 2,
+        }
+        .at(Location {
+            table: None,
+            path: "instances[3].coordinates".to_string(),
+            offset: Some(58),
+        })
+        .in_table(*b"fvar");
+        assert_eq!(
+            error.to_string(),
+            "fvar.instances[3].coordinates at byte 58: unexpected end of input: needed 4 bytes, found 2"
+        );
+        assert_eq!(
+            error.inner(),
+            &Error::UnexpectedEof {
+                needed: 4,
+                available: 2
+            }
+        );
+        assert_eq!(
+            Error::DeserializedInWrongOrder
+                .in_table(*b"hmtx")
+                .to_string(),
+            "hmtx: table depends on a table which has not been deserialized"
+        );
+    }
+
+    #[test]
+    fn nested_error() {
+        let error = Error::UnexpectedEof {
+            needed: 2,
+            available: 1,
+        }
+        .at(Location {
+            table: None,
+            path: "[1]".to_string(),
+            offset: Some(6),
+        })
+        .within("coverage", 0);
+        let message = error.to_string();
+
+        // Passed through a visitor driven by this crate's deserializer, the
+        // error keeps its location, rebased onto that of the visited structure
+        let nested: Error = error.clone().nested();
+        let located = nested.at(Location {
+            table: None,
+            path: "subtables[0]".to_string(),
+            offset: Some(32),
+        });
+        assert_eq!(
+            located.location(),
+            Some(&Location {
+                table: None,
+                path: "subtables[0].coverage[1]".to_string(),
+                offset: Some(38),
+            })
+        );
+        assert_eq!(located.inner(), error.inner());
+
+        // Other deserializers only receive the message, and the error is not
+        // attached to a later message, even one with the same text
+        let other: serde::de::value::Error = error.nested();
+        assert_eq!(other.to_string(), message);
+        assert_eq!(
+            <Error as serde::de::Error>::custom(&message),
+            Error::Message(message.clone())
+        );
+    }
+}
@@ -1,6 +1,9 @@
 use crate::error::{Error, Result};
 use serde::{ser, Serialize};
 
+/// Serialization of structures linked by offsets
+pub mod graph;
+
 pub struct Serializer {
     output: Vec<u8>,
 }
@@ -14,6 +17,18 @@ where
     Ok(serializer.output)
 }
 
+/// Serializes a value using a serialization function, such as the
+/// `serialize` function of one of the modules in `otspec::types`
+pub fn to_bytes_with<T, F>(value: &T, f: F) -> Result<Vec<u8>>
+where
+    T: ?Sized,
+    F: FnOnce(&T, &mut Serializer) -> Result<()>,
+{
+    let mut serializer = Serializer { output: vec![] };
+    f(value, &mut serializer)?;
+    Ok(serializer.output)
+}
+
 macro_rules! serialize_number_type {
     ($func:ident, $type:ty) => {
         fn $func(self, v: $type) -> Result<()> {
@@ -39,6 +54,10 @@ impl<'a> ser::Serializer for &'a mut Serializer {
     type SerializeStruct = Self;
     type SerializeStructVariant = Self;
 
+    fn is_human_readable(&self) -> bool {
+        false
+    }
+
     serialize_number_type!(serialize_i8, i8);
     serialize_number_type!(serialize_i16, i16);
     serialize_number_type!(serialize_i32, i32);
@@ -0,0 +1,514 @@
+//! Serialization of structures linked by offsets
+//!
+//! OpenType tables such as `GSUB` and `GPOS` are made up of many subtables
+//! which refer to each other through 16- or 32-bit offsets. Rather than
+//! writing them out linearly, each subtable is turned into an [`Object`] (its
+//! binary data plus a list of links to other objects) and added to an
+//! [`ObjectGraph`]. The graph then decides where each object should go in the
+//! final binary, and fills in the offsets.
+//!
+//! Identical objects are only stored once, so subtables with the same content
+//! are automatically shared. When objects are too far apart to be reached with
+//! a 16-bit offset, the graph first tries to resolve this by moving objects
+//! closer to their parents and duplicating shared objects; overflows which
+//! cannot be resolved that way are reported to the caller, which may be able
+//! to restructure its data (for example by promoting lookups to extension
+//! lookups) and try again.
+
+use crate::error::Result;
+use crate::ser::to_bytes;
+use serde::Serialize;
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap, HashSet, VecDeque};
+
+/// An index identifying an object within an [`ObjectGraph`]
+pub type ObjectId = usize;
+
+/// The width of an offset from one object to another
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum OffsetWidth {
+    /// A 16-bit offset (`Offset16`)
+    Offset16,
+    /// A 32-bit offset (`Offset32`)
+    Offset32,
+}
+
+impl OffsetWidth {
+    fn max(&self) -> usize {
+        match self {
+            OffsetWidth::Offset16 => u16::MAX as usize,
+            OffsetWidth::Offset32 => u32::MAX as usize,
+        }
+    }
+}
+
+/// An offset within an object's data which points to another object
+///
+/// Offsets are measured from the start of the object containing them.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Link {
+    /// The position of the offset within the object's data
+    pub position: usize,
+    /// The width of the offset
+    pub width: OffsetWidth,
+    /// The object being pointed to
+    pub target: ObjectId,
+}
+
+/// A blob of binary data, together with offsets to other objects
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Default)]
+pub struct Object {
+    /// The binary data of this object; offsets are zero until the graph is packed
+    pub data: Vec<u8>,
+    /// Links from this object to other objects
+    pub links: Vec<Link>,
+}
+
+impl Object {
+    /// Creates a new, empty object
+    pub fn new() -> Self {
+        Object::default()
+    }
+
+    /// Appends a serializable value to the object's data
+    pub fn push<T: Serialize>(&mut self, value: &T) -> Result<()> {
+        self.data.extend(to_bytes(value)?);
+        Ok(())
+    }
+
+    /// Appends a 16-bit offset to another object
+    pub fn push_offset16(&mut self, target: ObjectId) {
+        self.push_link(target, OffsetWidth::Offset16)
+    }
+
+    /// Appends a 32-bit offset to another object
+    pub fn push_offset32(&mut self, target: ObjectId) {
+        self.push_link(target, OffsetWidth::Offset32)
+    }
+
+    /// Appends a 16-bit offset to another object, or a NULL offset if there is none
+    pub fn push_optional_offset16(&mut self, target: Option<ObjectId>) {
+        match target {
+            Some(target) => self.push_offset16(target),
+            None => self.data.extend(&[0, 0]),
+        }
+    }
+
+    /// Appends a 32-bit offset to another object, or a NULL offset if there is none
+    pub fn push_optional_offset32(&mut self, target: Option<ObjectId>) {
+        match target {
+            Some(target) => self.push_offset32(target),
+            None => self.data.extend(&[0, 0, 0, 0]),
+        }
+    }
+
+    fn push_link(&mut self, target: ObjectId, width: OffsetWidth) {
+        self.links.push(Link {
+            position: self.data.len(),
+            width,
+            target,
+        });
+        match width {
+            OffsetWidth::Offset16 => self.data.extend(&[0; 2]),
+            OffsetWidth::Offset32 => self.data.extend(&[0; 4]),
+        }
+    }
+}
+
+/// A structure which can be added to an object graph for serialization
+pub trait ToObject {
+    /// Adds this structure (and any structures it refers to) to the graph,
+    /// returning the identifier of its object
+    fn to_object(&self, graph: &mut ObjectGraph) -> Result<ObjectId>;
+}
+
+/// A link which could not be expressed within the width of its offset
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Overflow {
+    /// The object containing the offset
+    pub parent: ObjectId,
+    /// The object being pointed to
+    pub child: ObjectId,
+    /// The distance between the two objects in the packed binary
+    pub distance: usize,
+}
+
+/// A set of objects linked by offsets
+///
+/// Objects must be added children first, so that links always point to
+/// objects which are already in the graph; this also guarantees that the
+/// graph has no cycles.
+#[derive(Debug, Default)]
+pub struct ObjectGraph {
+    objects: Vec<Object>,
+    index: HashMap<Object, ObjectId>,
+}
+
+impl ObjectGraph {
+    /// Creates a new, empty graph
+    pub fn new() -> Self {
+        ObjectGraph::default()
+    }
+
+    /// Adds an object to the graph, returning its identifier
+    ///
+    /// If an identical object has already been added, its identifier is
+    /// returned instead and the object is shared.
+    pub fn add(&mut self, object: Object) -> ObjectId {
+        if let Some(id) = self.index.get(&object) {
+            return *id;
+        }
+        for link in &object.links {
+            assert!(
+                link.target < self.objects.len(),
+                "Objects must be added to the graph after their children"
+            );
+        }
+        let id = self.objects.len();
+        self.objects.push(object.clone());
+        self.index.insert(object, id);
+        id
+    }
+
+    /// Returns the object with a given identifier
+    pub fn get(&self, id: ObjectId) -> &Object {
+        &self.objects[id]
+    }
+
+    /// Returns the number of (distinct) objects in the graph
+    pub fn len(&self) -> usize {
+        self.objects.len()
+    }
+
+    /// Returns true if the graph contains no objects
+    pub fn is_empty(&self) -> bool {
+        self.objects.is_empty()
+    }
+
+    /// Lays out all objects reachable from `root` and returns the binary data
+    ///
+    /// Objects are ordered so that every object comes after all the objects
+    /// which point to it (offsets are unsigned), preferring to keep objects
+    /// close to the root. If any offset cannot be expressed in its width,
+    /// overflowing children are moved up to sit directly after their parent,
+    /// and shared children are duplicated so that each parent can have a copy
+    /// nearby. If overflows still remain, they are returned as an error, with
+    /// object identifiers referring to objects in this graph.
+    pub fn pack(&self, root: ObjectId) -> std::result::Result<Vec<u8>, Vec<Overflow>> {
+        let mut packer = Packer {
+            objects: self.objects.clone(),
+            origin: (0..self.objects.len()).collect(),
+            raised: HashSet::new(),
+        };
+        let mut overflows = vec![];
+        for _ in 0..=2 * self.objects.len() {
+            let order = packer.order(root);
+            let positions = packer.positions(&order);
+            overflows = packer.overflows(&order, &positions);
+            if overflows.is_empty() {
+                return Ok(packer.write(&order, &positions));
+            }
+            if !packer.resolve(&order, &overflows) {
+                break;
+            }
+        }
+        let mut reported: Vec<Overflow> = overflows
+            .into_iter()
+            .map(|o| Overflow {
+                parent: packer.origin[o.parent],
+                child: packer.origin[o.child],
+                distance: o.distance,
+            })
+            .collect();
+        reported.dedup();
+        Err(reported)
+    }
+}
+
+/// Working state used while packing a graph
+struct Packer {
+    objects: Vec<Object>,
+    /// The object in the original graph each object was copied from
+    origin: Vec<ObjectId>,
+    /// Objects which should be placed as soon as their parents are placed
+    raised: HashSet<ObjectId>,
+}
+
+impl Packer {
+    /// Returns the objects reachable from the root, each with its number of incoming links
+    fn incoming(&self, root: ObjectId) -> HashMap<ObjectId, usize> {
+        let mut incoming = HashMap::new();
+        incoming.insert(root, 0);
+        let mut queue = VecDeque::new();
+        queue.push_back(root);
+        while let Some(id) = queue.pop_front() {
+            for link in &self.objects[id].links {
+                let count = incoming.entry(link.target).or_insert_with(|| {
+                    queue.push_back(link.target);
+                    0
+                });
+                *count += 1;
+            }
+        }
+        incoming
+    }
+
+    /// Sorts objects topologically, always choosing the available object
+    /// closest to the root
+    ///
+    /// "Closeness" is measured in bytes along the shortest path from the root.
+    /// Each 32-bit offset starts a new "space": objects reached through it
+    /// can afford to be far from their parents, so they are placed after the
+    /// objects of earlier spaces, and kept together so that the 16-bit
+    /// offsets within the space remain small. Raised objects are treated as
+    /// being as close as their closest parent, so they are placed immediately
+    /// after it.
+    fn order(&self, root: ObjectId) -> Vec<ObjectId> {
+        // First pass: any topological order, to compute spaces and distances
+        let topological = self.kahn(root, |_| (0, 0, 0));
+        let mut space: HashMap<ObjectId, usize> = HashMap::new();
+        let mut distance: HashMap<ObjectId, u64> = HashMap::new();
+        let mut parent_distance: HashMap<ObjectId, u64> = HashMap::new();
+        space.insert(root, 0);
+        distance.insert(root, 0);
+        parent_distance.insert(root, 0);
+        let mut spaces = 0;
+        for id in &topological {
+            let (here_space, here) = (space[id], distance[id]);
+            for link in &self.objects[*id].links {
+                let size = self.objects[link.target].data.len() as u64;
+                let (s, d) = match link.width {
+                    OffsetWidth::Offset16 => (here_space, here + size),
+                    OffsetWidth::Offset32 => {
+                        spaces += 1;
+                        (spaces, size)
+                    }
+                };
+                let entry = space.entry(link.target).or_insert(usize::MAX);
+                if s < *entry {
+                    *entry = s;
+                    distance.insert(link.target, d);
+                    parent_distance.insert(link.target, here);
+                } else if s == *entry {
+                    let entry = distance.get_mut(&link.target).unwrap();
+                    *entry = (*entry).min(d);
+                    let p = parent_distance.get_mut(&link.target).unwrap();
+                    *p = (*p).min(here);
+                }
+            }
+        }
+        // Second pass: order by space, then distance
+        self.kahn(root, |id| {
+            if self.raised.contains(&id) {
+                (space[&id], parent_distance[&id], 0)
+            } else {
+                (space[&id], distance[&id], 1)
+            }
+        })
+    }
+
+    fn kahn<F: Fn(ObjectId) -> (usize, u64, u8)>(
+        &self,
+        root: ObjectId,
+        priority: F,
+    ) -> Vec<ObjectId> {
+        let mut incoming = self.incoming(root);
+        let mut order = Vec::with_capacity(incoming.len());
+        let mut heap = BinaryHeap::new();
+        heap.push(Reverse((priority(root), root)));
+        while let Some(Reverse((_, id))) = heap.pop() {
+            order.push(id);
+            for link in &self.objects[id].links {
+                let count = incoming.get_mut(&link.target).unwrap();
+                *count -= 1;
+                if *count == 0 {
+                    heap.push(Reverse((priority(link.target), link.target)));
+                }
+            }
+        }
+        order
+    }
+
+    fn positions(&self, order: &[ObjectId]) -> HashMap<ObjectId, usize> {
+        let mut positions = HashMap::new();
+        let mut position = 0;
+        for id in order {
+            positions.insert(*id, position);
+            position += self.objects[*id].data.len();
+        }
+        positions
+    }
+
+    fn overflows(&self, order: &[ObjectId], positions: &HashMap<ObjectId, usize>) -> Vec<Overflow> {
+        let mut overflows = vec![];
+        for id in order {
+            for link in &self.objects[*id].links {
+                let distance = positions[&link.target] - positions[id];
+                if distance > link.width.max() {
+                    overflows.push(Overflow {
+                        parent: *id,
+                        child: link.target,
+                        distance,
+                    });
+                }
+            }
+        }
+        overflows
+    }
+
+    /// Attempts to resolve overflows by moving or duplicating children
+    ///
+    /// A child with a single parent is raised so that it is placed directly
+    /// after its parent; a shared child is duplicated, giving the overflowing
+    /// parent its own copy. Returns false if no further changes can be made.
+    fn resolve(&mut self, order: &[ObjectId], overflows: &[Overflow]) -> bool {
+        let mut parents: HashMap<ObjectId, usize> = HashMap::new();
+        for id in order {
+            for link in &self.objects[*id].links {
+                *parents.entry(link.target).or_insert(0) += 1;
+            }
+        }
+        let mut progress = false;
+        for overflow in overflows {
+            if parents[&overflow.child] < 2 {
+                progress |= self.raised.insert(overflow.child);
+                continue;
+            }
+            let copy = self.objects.len();
+            self.objects.push(self.objects[overflow.child].clone());
+            self.origin.push(self.origin[overflow.child]);
+            for link in self.objects[overflow.parent].links.iter_mut() {
+                if link.target == overflow.child {
+                    link.target = copy;
+                }
+            }
+            *parents.get_mut(&overflow.child).unwrap() -= 1;
+            progress = true;
+        }
+        progress
+    }
+
+    fn write(&self, order: &[ObjectId], positions: &HashMap<ObjectId, usize>) -> Vec<u8> {
+        let mut output =
+            Vec::with_capacity(order.iter().map(|id| self.objects[*id].data.len()).sum());
+        for id in order {
+            let start = output.len();
+            output.extend(&self.objects[*id].data);
+            for link in &self.objects[*id].links {
+                let offset = positions[&link.target] - positions[id];
+                let at = start + link.position;
+                match link.width {
+                    OffsetWidth::Offset16 => {
+                        output[at..at + 2].copy_from_slice(&(offset as u16).to_be_bytes())
+                    }
+                    OffsetWidth::Offset32 => {
+                        output[at..at + 4].copy_from_slice(&(offset as u32).to_be_bytes())
+                    }
+                }
+            }
+        }
+        output
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ser::graph::{Object, ObjectGraph, OffsetWidth, Overflow};
+
+    fn blob(bytes: &[u8]) -> Object {
+        Object {
+            data: bytes.to_vec(),
+            links: vec![],
+        }
+    }
+
+    #[test]
+    fn graph_pack_offsets() {
+        let mut graph = ObjectGraph::new();
+        let leaf = graph.add(blob(&[0xAA, 0xBB]));
+        let mut middle = Object::new();
+        middle.push(&1_u16).unwrap();
+        middle.push_offset16(leaf);
+        let middle = graph.add(middle);
+        let mut root = Object::new();
+        root.push_offset16(middle);
+        root.push_offset32(leaf);
+        let root = graph.add(root);
+        assert_eq!(
+            graph.pack(root).unwrap(),
+            vec![0x00, 0x06, 0x00, 0x00, 0x00, 0x0A, 0x00, 0x01, 0x00, 0x04, 0xAA, 0xBB]
+        );
+    }
+
+    #[test]
+    fn graph_shares_identical_objects() {
+        let mut graph = ObjectGraph::new();
+        let a = graph.add(blob(&[1, 2, 3]));
+        let b = graph.add(blob(&[1, 2, 3]));
+        assert_eq!(a, b);
+        let mut root = Object::new();
+        root.push_offset16(a);
+        root.push_offset16(b);
+        let root = graph.add(root);
+        assert_eq!(graph.len(), 2);
+        assert_eq!(graph.pack(root).unwrap(), vec![0, 4, 0, 4, 1, 2, 3]);
+    }
+
+    #[test]
+    fn graph_reports_overflow() {
+        let mut graph = ObjectGraph::new();
+        let small = graph.add(blob(&[1]));
+        let big = graph.add(blob(&[0; 70000]));
+        let mut root = Object::new();
+        root.push_offset16(big);
+        root.push_offset16(small);
+        let root = graph.add(root);
+        // The smaller child is placed first, so the large one is in reach...
+        assert!(graph.pack(root).is_ok());
+
+        let mut graph = ObjectGraph::new();
+        let small = graph.add(blob(&[1]));
+        let big1 = graph.add(blob(&[0; 70000]));
+        let big2 = graph.add(blob(&[1; 70000]));
+        let mut root = Object::new();
+        root.push_offset16(big1);
+        root.push_offset16(big2);
+        root.push_offset16(small);
+        let root = graph.add(root);
+        // ...but two large children cannot both be reached.
+        let overflows = graph.pack(root).unwrap_err();
+        assert_eq!(overflows.len(), 1);
+        assert_eq!(overflows[0].parent, root);
+        assert_eq!(
+            overflows[0],
+            Overflow {
+                parent: root,
+                child: big2,
+                distance: 70007
+            }
+        );
+    }
+
+    #[test]
+    fn graph_duplicates_shared_objects() {
+        // Two parents share a child; the second parent is too far away from
+        // the child unless it gets its own copy.
+        let mut graph = ObjectGraph::new();
+        let shared = graph.add(blob(&[0xFF; 4]));
+        let mut first = Object::new();
+        first.push_offset16(shared);
+        first.data.extend(vec![0; 40000]);
+        let first = graph.add(first);
+        let mut second = Object::new();
+        second.push_offset16(shared);
+        second.data.extend(vec![1; 40000]);
+        let second = graph.add(second);
+        let mut root = Object::new();
+        root.push_offset32(first);
+        root.push_offset32(second);
+        let root = graph.add(root);
+        let packed = graph.pack(root).unwrap();
+        assert_eq!(packed.len(), 8 + 2 * 40002 + 2 * 4);
+        assert_eq!(graph.get(root).links[0].width, OffsetWidth::Offset32);
+    }
+}
@@ -25,7 +25,7 @@ fn ot_round(value: f32) -> i32 {
 pub mod Fixed {
     use crate::types::ot_round;
     use crate::types::I32Visitor;
-    use serde::{Deserializer, Serializer};
+    use serde::{Deserialize, Deserializer, Serializer};
 
     pub fn unpack(v: i32) -> f32 {
         (v as f32) / 65536.0
@@ -38,6 +38,9 @@ pub mod Fixed {
     where
         S: Serializer,
     {
+        if serializer.is_human_readable() {
+            return serializer.serialize_f32(*v);
+        }
         serializer.serialize_i32(pack(*v))
     }
 
@@ -45,6 +48,9 @@ pub mod Fixed {
     where
         D: Deserializer<'de>,
     {
+        if deserializer.is_human_readable() {
+            return f32::deserialize(deserializer);
+        }
         Ok(unpack(deserializer.deserialize_i32(I32Visitor)?))
     }
 }
@@ -54,12 +60,15 @@ pub mod Version16Dot16 {
 
     use crate::types::I32Visitor;
     use fixed::types::U16F16;
-    use serde::{Deserializer, Serializer};
+    use serde::{de, Deserialize, Deserializer, Serializer};
 
     pub fn serialize<S>(v: &U16F16, serializer: S) -> Result<S::Ok, S::Error>
     where
         S: Serializer,
     {
+        if serializer.is_human_readable() {
+            return serializer.serialize_f32(v.to_num());
+        }
         let major = v.floor().to_num::<u8>();
         let minor = (v.frac().to_num::<f32>() * 160.0) as u8;
         serializer.serialize_bytes(&[0, major, minor, 0])
@@ -69,6 +78,11 @@ pub mod Version16Dot16 {
     where
         D: Deserializer<'de>,
     {
+        if deserializer.is_human_readable() {
+            let version = f32::deserialize(deserializer)?;
+            return U16F16::checked_from_num(version)
+                .ok_or_else(|| de::Error::custom(format!("Version {} is out of range", version)));
+        }
         let orig = deserializer.deserialize_i32(I32Visitor)?.to_be_bytes();
         let major = orig[1] as f32;
         let minor = orig[2] as f32 / 160.0;
@@ -81,7 +95,7 @@ pub mod F2DOT14 {
     use crate::types::I16Visitor;
 
     use serde::ser::SerializeSeq;
-    use serde::{Deserializer, Serializer};
+    use serde::{Deserialize, Deserializer, Serializer};
     use std::convert::TryInto;
 
     pub fn unpack(v: i16) -> f32 {
@@ -95,6 +109,9 @@ pub mod F2DOT14 {
     where
         S: Serializer,
     {
+        if serializer.is_human_readable() {
+            return serializer.serialize_f32(*v);
+        }
         serializer.serialize_i16(pack(*v))
     }
 
@@ -112,6 +129,9 @@ pub mod F2DOT14 {
     where
         D: Deserializer<'de>,
     {
+        if deserializer.is_human_readable() {
+            return f32::deserialize(deserializer);
+        }
         Ok(unpack(deserializer.deserialize_i16(I16Visitor)?))
     }
 }
@@ -151,12 +171,18 @@ impl<'de> Visitor<'de> for I16Visitor {
 pub mod LONGDATETIME {
     use chrono::Duration;
     use chrono::NaiveDate;
-    use serde::{Deserialize, Deserializer, Serializer};
+    use serde::{de, Deserialize, Deserializer, Serializer};
+
+    /// The format used for dates in human-readable formats
+    const FORMAT: &str = "%Y-%m-%dT%H:%M:%S";
 
     pub fn serialize<S>(v: &chrono::NaiveDateTime, serializer: S) -> Result<S::Ok, S::Error>
     where
         S: Serializer,
     {
+        if serializer.is_human_readable() {
+            return serializer.collect_str(&v.format(FORMAT));
+        }
         let now = v.timestamp();
         let epoch = NaiveDate::from_ymd(1904, 1, 1).and_hms(0, 0, 0).timestamp();
         serializer.serialize_i64(now - epoch)
@@ -166,23 +192,34 @@ pub mod LONGDATETIME {
     where
         D: Deserializer<'de>,
     {
+        if d.is_human_readable() {
+            let date = String::deserialize(d)?;
+            return chrono::NaiveDateTime::parse_from_str(&date, FORMAT)
+                .map_err(|e| de::Error::custom(format!("Invalid date '{}': {}", date, e)));
+        }
         let diff = i64::deserialize(d)?;
         let epoch = NaiveDate::from_ymd(1904, 1, 1).and_hms(0, 0, 0);
-        let res = epoch + Duration::seconds(diff);
-        Ok(res)
+        // Duration::seconds panics if the duration doesn't fit in i64 milliseconds
+        diff.checked_mul(1000)
+            .and_then(|_| epoch.checked_add_signed(Duration::seconds(diff)))
+            .ok_or_else(|| de::Error::custom(format!("Date {} is out of range", diff)))
     }
 }
 pub mod Counted {
     use serde::de::{SeqAccess, Visitor};
     use serde::ser::SerializeSeq;
     use serde::Serialize;
-    use serde::{Deserializer, Serializer};
+    use serde::{Deserialize, Deserializer, Serializer};
 
     pub fn serialize<S, T>(v: &[T], serializer: S) -> Result<S::Ok, S::Error>
     where
         S: Serializer,
         T: Serialize,
     {
+        // Human-readable formats carry their own lengths
+        if serializer.is_human_readable() {
+            return v.serialize(serializer);
+        }
         let mut my_seq = serializer.serialize_seq(Some(v.len()))?;
         my_seq.serialize_element(&(v.len() as u16));
         for k in v {
@@ -194,6 +231,9 @@ pub mod Counted {
     where
         D: Deserializer<'de>,
     {
+        if d.is_human_readable() {
+            return Vec::<T>::deserialize(d);
+        }
         d.deserialize_seq(SeqVisitor::new())
     }
 
@@ -224,7 +264,10 @@ pub mod Counted {
         type Value = Vec<T>;
 
         fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
-            write!(formatter, "A sequence of {:?} values", self.len)
+            match self.len {
+                Some(len) => write!(formatter, "{} elements", len),
+                None => formatter.write_str("a counted sequence"),
+            }
         }
 
         fn visit_seq<A: SeqAccess<'de>>(mut self, mut seq: A) -> Result<Self::Value, A::Error> {
@@ -235,15 +278,46 @@ pub mod Counted {
                     })? as usize);
             }
             let expected = self.len.unwrap();
-            let mut result = Vec::with_capacity(expected);
-            for i in 0..expected {
-                let next = seq
-                    .next_element::<T>()?
-                    .ok_or_else(|| serde::de::Error::invalid_length(i, &self))?;
-                result.push(next)
+            if expected == 0 {
+                return Ok(vec![]);
             }
-            Ok(result)
+            // Read the elements as a nested array, so that errors report their index
+            seq.next_element_seed(crate::de::CountedDeserializer::with_len(expected))?
+                .ok_or_else(|| serde::de::Error::invalid_length(0, &self))
+        }
+    }
+}
+
+/// Serializes a `Tag` as its bytes, or as a string in human-readable formats
+pub mod tag {
+    use crate::types::Tag;
+    use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+
+    pub fn serialize<S>(v: &Tag, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        if serializer.is_human_readable() {
+            return serializer.serialize_str(&String::from_utf8_lossy(v));
+        }
+        v.serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D>(d: D) -> Result<Tag, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        if !d.is_human_readable() {
+            return <Tag>::deserialize(d);
+        }
+        let string = String::deserialize(d)?;
+        if string.is_empty() || string.len() > 4 {
+            return Err(de::Error::custom(format!("Invalid tag '{}'", string)));
         }
+        // Short tags are padded with spaces
+        let mut tag = *b"    ";
+        tag[..string.len()].copy_from_slice(string.as_bytes());
+        Ok(tag)
     }
 }
 
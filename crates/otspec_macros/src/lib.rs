@@ -1,124 +1,649 @@
-#![feature(proc_macro_diagnostic)]
-#![feature(proc_macro_quote)]
-
-use proc_macro::Delimiter;
 use proc_macro::TokenStream;
-use proc_macro::TokenTree;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::{format_ident, quote};
+use syn::parse::{Parse, ParseStream};
+use syn::{braced, parenthesized, parse_macro_input, Ident, LitStr};
+
+/// The Rust type and serde helper module used for special OpenType types
+fn special_type(t: &str) -> Option<TokenStream2> {
+    match t {
+        /* We don't use types from the fixed crate here because fixed-point
+        arithmetic is an artefact of the storage format of OpenType, and
+        not something we want to foist on the user. It's more ergonomic
+        for them to be able to manipulate plain f32s. */
+        "Fixed" => Some(quote!(f32)),
+        "F2DOT14" => Some(quote!(f32)),
+        /* But we *do* use fixed point here, because we want to be able to
+        compare fractional version numbers for equality without having to
+        do epsilon dances. */
+        "Version16Dot16" => Some(quote!(U16F16)),
+        "LONGDATETIME" => Some(quote!(chrono::NaiveDateTime)),
+        _ => None,
+    }
+}
 
-fn expect_group(item: Option<TokenTree>, delimiter: Delimiter) -> TokenStream {
-    match item {
-        Some(TokenTree::Group(i)) => {
-            if i.delimiter() == delimiter {
-                return i.stream();
+/// The width in bytes of an offset type, if `t` is one
+fn offset_width(t: &Ident) -> Option<usize> {
+    match t.to_string().as_str() {
+        "Offset16" => Some(2),
+        "Offset32" => Some(4),
+        _ => None,
+    }
+}
+
+enum FieldType {
+    /// A type which serializes as itself
+    Plain(TokenStream2),
+    /// A type serialized with a helper module: (module, Rust type)
+    With(Ident, TokenStream2),
+    /// An offset (of the given width) to a child structure
+    Offset(usize, Ident),
+    /// A count followed by an array of offsets (of the given width) to child structures
+    CountedOffset(usize, Ident),
+    /// A count followed by an array of records, whose offsets are relative to
+    /// the start of the structure containing them
+    Records(Ident),
+}
+
+struct Field {
+    name: Ident,
+    field_type: FieldType,
+}
+
+struct Table {
+    name: Ident,
+    fields: Vec<Field>,
+}
+
+struct Tables(Vec<Table>);
+
+/// Parses the parenthesized argument of a field type, such as `(uint16)` in
+/// `Counted(uint16)`, returning the argument and its own argument, if any
+fn parse_argument(input: ParseStream) -> syn::Result<(Ident, Option<Ident>)> {
+    let content;
+    parenthesized!(content in input);
+    let t: Ident = content.parse()?;
+    let inner = if content.peek(syn::token::Paren) {
+        let inner_content;
+        parenthesized!(inner_content in content);
+        Some(inner_content.parse()?)
+    } else {
+        None
+    };
+    if !content.is_empty() {
+        return Err(content.error("Unexpected tokens in type argument"));
+    }
+    Ok((t, inner))
+}
+
+impl Parse for Field {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let t: Ident = input.parse()?;
+        let field_type = if t == "Maybe" {
+            let (subtype, inner) = parse_argument(input)?;
+            if inner.is_some() {
+                return Err(syn::Error::new(
+                    subtype.span(),
+                    "Maybe fields cannot contain offsets",
+                ));
+            }
+            FieldType::Plain(quote!(Option<#subtype>))
+        } else if t == "Counted" {
+            let (subtype, inner) = parse_argument(input)?;
+            match (offset_width(&subtype), inner) {
+                (Some(width), Some(target)) => FieldType::CountedOffset(width, target),
+                (Some(_), None) => {
+                    return Err(syn::Error::new(
+                        subtype.span(),
+                        "Expected the type the offsets point to, e.g. Offset16(Coverage)",
+                    ))
+                }
+                (None, Some(_)) => {
+                    return Err(syn::Error::new(
+                        subtype.span(),
+                        "Only offsets can take a type argument",
+                    ))
+                }
+                (None, None) => FieldType::With(format_ident!("Counted"), quote!(Vec<#subtype>)),
+            }
+        } else if t == "Records" {
+            let (record, inner) = parse_argument(input)?;
+            if inner.is_some() {
+                return Err(syn::Error::new(
+                    record.span(),
+                    "Records must be of a single type",
+                ));
+            }
+            FieldType::Records(record)
+        } else if let Some(width) = offset_width(&t) {
+            if input.peek(syn::token::Paren) {
+                let (target, inner) = parse_argument(input)?;
+                if inner.is_some() {
+                    return Err(syn::Error::new(
+                        target.span(),
+                        "Offsets must point to a single type",
+                    ));
+                }
+                FieldType::Offset(width, target)
+            } else {
+                // A bare offset is just an integer
+                let bits = format_ident!("u{}", width * 8);
+                FieldType::Plain(quote!(#bits))
             }
-            let err = i.span().error(format!(
-                "Expected an {:?}, saw {:?} ",
-                delimiter,
-                i.delimiter()
-            ));
-            err.emit();
-            panic!("Syntax error");
+        } else if t == "Tag" {
+            // Tags are byte arrays, but read better as strings
+            FieldType::With(format_ident!("tag"), quote!(#t))
+        } else if let Some(nonspecial_type) = special_type(&t.to_string()) {
+            FieldType::With(t, nonspecial_type)
+        } else {
+            FieldType::Plain(quote!(#t))
+        };
+        let name: Ident = input.parse()?;
+        Ok(Field { name, field_type })
+    }
+}
+
+impl Parse for Table {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let name: Ident = input.parse()?;
+        let content;
+        braced!(content in input);
+        let mut fields = vec![];
+        while !content.is_empty() {
+            fields.push(content.parse()?);
+        }
+        Ok(Table { name, fields })
+    }
+}
+
+impl Parse for Tables {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let mut tables = vec![];
+        while !input.is_empty() {
+            tables.push(input.parse()?);
+        }
+        Ok(Tables(tables))
+    }
+}
+
+impl Field {
+    fn rust_type(&self) -> TokenStream2 {
+        match &self.field_type {
+            FieldType::Plain(t) => t.clone(),
+            FieldType::With(_, t) => t.clone(),
+            FieldType::Offset(_, t) => quote!(Option<#t>),
+            FieldType::CountedOffset(_, t) => quote!(Vec<#t>),
+            FieldType::Records(t) => quote!(Vec<#t>),
+        }
+    }
+
+    fn is_offset(&self) -> bool {
+        matches!(
+            self.field_type,
+            FieldType::Offset(_, _) | FieldType::CountedOffset(_, _) | FieldType::Records(_)
+        )
+    }
+
+    /// The declaration of this field in the raw form of a record, which
+    /// keeps its offsets as plain integers
+    fn raw_declaration(&self) -> TokenStream2 {
+        let name = &self.name;
+        match &self.field_type {
+            FieldType::Offset(width, _) => {
+                let bits = format_ident!("u{}", width * 8);
+                quote!(pub #name: #bits,)
+            }
+            FieldType::CountedOffset(width, _) => {
+                let bits = format_ident!("u{}", width * 8);
+                quote! {
+                    #[serde(with = "Counted")]
+                    pub #name: Vec<#bits>,
+                }
+            }
+            _ => self.declaration(),
         }
-        None => {
-            panic!("Expected delimiter, found end of macro")
+    }
+
+    /// Code to take this field out of the raw form of a record, under the
+    /// names used by `follow_offsets`
+    fn take_raw(&self) -> TokenStream2 {
+        let name = &self.name;
+        match &self.field_type {
+            FieldType::Offset(_, _) => {
+                let offset = format_ident!("{}_offset", name);
+                quote!(let #offset = raw.#name;)
+            }
+            FieldType::CountedOffset(_, _) => {
+                let offsets = format_ident!("{}_offsets", name);
+                quote!(let #offsets = raw.#name;)
+            }
+            _ => quote!(let #name = raw.#name;),
         }
-        Some(i) => {
-            let err = i.span().error("Expected an ident");
-            err.emit();
-            panic!("Syntax error");
+    }
+
+    fn declaration(&self) -> TokenStream2 {
+        let name = &self.name;
+        let rust_type = self.rust_type();
+        match &self.field_type {
+            FieldType::With(module, _) => {
+                let module = LitStr::new(&module.to_string(), module.span());
+                quote! {
+                    #[serde(with = #module)]
+                    pub #name: #rust_type,
+                }
+            }
+            _ => quote!(pub #name: #rust_type,),
+        }
+    }
+
+    /// Code to read this field within a visitor, keeping track of the header length
+    fn deserialize(&self) -> TokenStream2 {
+        let name = &self.name;
+        let missing = format!("Expecting {}", name);
+        let missing = quote!(.ok_or_else(|| serde::de::Error::custom(#missing))?);
+        match &self.field_type {
+            FieldType::Plain(t) => quote! {
+                let #name: #t = seq.next_element()? #missing;
+                header_len += otspec::ser::to_bytes(&#name)
+                    .map_err(serde::de::Error::custom)?
+                    .len();
+            },
+            FieldType::With(module, t) => {
+                let wrapper = format_ident!("__{}", name);
+                let module_str = LitStr::new(&module.to_string(), module.span());
+                quote! {
+                    #[allow(non_camel_case_types)]
+                    #[derive(serde::Deserialize)]
+                    struct #wrapper(#[serde(with = #module_str)] #t);
+                    let #name: #t = seq.next_element::<#wrapper>()? #missing .0;
+                    header_len += otspec::ser::to_bytes_with(&#name, |v, s| #module::serialize(v, s))
+                        .map_err(serde::de::Error::custom)?
+                        .len();
+                }
+            }
+            FieldType::Offset(width, _) => {
+                let offset = format_ident!("{}_offset", name);
+                let bits = format_ident!("u{}", width * 8);
+                quote! {
+                    let #offset: #bits = seq.next_element()? #missing;
+                    header_len += #width;
+                }
+            }
+            FieldType::CountedOffset(width, _) => {
+                let count = format_ident!("{}_count", name);
+                let offsets = format_ident!("{}_offsets", name);
+                let bits = format_ident!("u{}", width * 8);
+                quote! {
+                    let #count: u16 = seq.next_element()? #missing;
+                    let #offsets: Vec<#bits> = seq
+                        .next_element_seed(otspec::de::CountedDeserializer::with_len(#count as usize))?
+                        #missing;
+                    header_len += 2 + #width * #offsets.len();
+                }
+            }
+            FieldType::Records(t) => {
+                let count = format_ident!("{}_count", name);
+                let raws = format_ident!("{}_raws", name);
+                quote! {
+                    let #count: u16 = seq.next_element()? #missing;
+                    let #raws: Vec<<#t as otspec::de::OffsetRecord>::Raw> = seq
+                        .next_element_seed(otspec::de::CountedDeserializer::with_len(#count as usize))?
+                        #missing;
+                    header_len += 2;
+                    for raw in &#raws {
+                        header_len += otspec::ser::to_bytes(raw)
+                            .map_err(serde::de::Error::custom)?
+                            .len();
+                    }
+                }
+            }
+        }
+    }
+
+    /// Code to follow this field's offsets, once the remainder has been read
+    ///
+    /// `nest` converts the located errors this produces into the error type
+    /// of the surrounding function.
+    fn follow_offsets(&self, nest: &TokenStream2) -> TokenStream2 {
+        let name = &self.name;
+        let path = name.to_string();
+        match &self.field_type {
+            FieldType::Offset(_, _) => {
+                let offset = format_ident!("{}_offset", name);
+                quote! {
+                    let #name = if #offset == 0 {
+                        None
+                    } else {
+                        Some(
+                            otspec::de::from_bytes_at(&remainder, #offset as usize, header_len)
+                                .map_err(|e: otspec::error::Error| e.within(#path, 0) #nest)?,
+                        )
+                    };
+                }
+            }
+            FieldType::CountedOffset(_, _) => {
+                let offsets = format_ident!("{}_offsets", name);
+                let null = format!("NULL offset in {}", name);
+                quote! {
+                    let mut #name = Vec::with_capacity(#offsets.len());
+                    for (i, offset) in #offsets.into_iter().enumerate() {
+                        if offset == 0 {
+                            return Err(serde::de::Error::custom(#null));
+                        }
+                        #name.push(
+                            otspec::de::from_bytes_at(&remainder, offset as usize, header_len)
+                                .map_err(|e: otspec::error::Error| {
+                                    e.within(&format!("{}[{}]", #path, i), 0) #nest
+                                })?,
+                        );
+                    }
+                }
+            }
+            FieldType::Records(t) => {
+                let raws = format_ident!("{}_raws", name);
+                quote! {
+                    let mut #name = Vec::with_capacity(#raws.len());
+                    for (i, raw) in #raws.into_iter().enumerate() {
+                        #name.push(
+                            <#t as otspec::de::OffsetRecord>::resolve(raw, &remainder, header_len)
+                                .map_err(|e| e.within(&format!("{}[{}]", #path, i), 0) #nest)?,
+                        );
+                    }
+                }
+            }
+            _ => quote!(),
+        }
+    }
+
+    /// Code to add this field to an object being built for an object graph
+    fn to_object(&self) -> TokenStream2 {
+        let name = &self.name;
+        match &self.field_type {
+            FieldType::Plain(_) => quote!(object.push(&self.#name)?;),
+            FieldType::With(module, _) => quote! {
+                object.data.extend(otspec::ser::to_bytes_with(&self.#name, |v, s| #module::serialize(v, s))?);
+            },
+            FieldType::Offset(width, _) => {
+                let push = format_ident!("push_optional_offset{}", width * 8);
+                quote! {
+                    let #name = self.#name
+                        .as_ref()
+                        .map(|child| otspec::ser::graph::ToObject::to_object(child, graph))
+                        .transpose()?;
+                    object.#push(#name);
+                }
+            }
+            FieldType::CountedOffset(width, _) => {
+                let push = format_ident!("push_offset{}", width * 8);
+                quote! {
+                    object.push(&(self.#name.len() as u16))?;
+                    for child in &self.#name {
+                        let id = otspec::ser::graph::ToObject::to_object(child, graph)?;
+                        object.#push(id);
+                    }
+                }
+            }
+            FieldType::Records(_) => quote! {
+                object.push(&(self.#name.len() as u16))?;
+                for record in &self.#name {
+                    otspec::de::OffsetRecord::push_to(record, &mut object, graph)?;
+                }
+            },
+        }
+    }
+}
+
+fn to_object_impl(table_name: &Ident, body: TokenStream2) -> TokenStream2 {
+    quote! {
+        impl otspec::ser::graph::ToObject for #table_name {
+            #[allow(non_snake_case)]
+            fn to_object(
+                &self,
+                graph: &mut otspec::ser::graph::ObjectGraph,
+            ) -> otspec::error::Result<otspec::ser::graph::ObjectId> {
+                let mut object = otspec::ser::graph::Object::new();
+                #body
+                Ok(graph.add(object))
+            }
         }
     }
 }
 
-fn expect_ident(item: Option<TokenTree>) -> String {
-    match item {
-        Some(TokenTree::Ident(i)) => i.to_string(),
-        None => {
-            panic!("Expected identifier, found end of macro")
+/// Structures with offsets to children need to know where they start, so
+/// they get hand-rolled implementations
+fn table_with_offsets(table: &Table) -> TokenStream2 {
+    let table_name = &table.name;
+    let declarations = table.fields.iter().map(|f| {
+        let name = &f.name;
+        let rust_type = f.rust_type();
+        quote!(pub #name: #rust_type,)
+    });
+    let reads = table.fields.iter().map(|f| f.deserialize());
+    let follows = table
+        .fields
+        .iter()
+        .map(|f| f.follow_offsets(&quote!(.nested())));
+    let names = table.fields.iter().map(|f| &f.name);
+    let expecting = format!("A {} structure", table_name);
+    let body: TokenStream2 = table.fields.iter().map(|f| f.to_object()).collect();
+    let to_object = to_object_impl(table_name, body);
+    let offset_record = offset_record(table);
+    quote! {
+        /// Low-level structure used for serializing/deserializing table
+        #[allow(missing_docs)]
+        #[derive(Debug, PartialEq, Clone)]
+        pub struct #table_name {
+            #(#declarations)*
         }
-        Some(i) => {
-            let err = i.span().error("Expected an ident");
-            err.emit();
-            panic!("Syntax error");
+
+        impl<'de> serde::Deserialize<'de> for #table_name {
+            fn deserialize<D>(d: D) -> Result<Self, D::Error>
+            where
+                D: serde::Deserializer<'de>,
+            {
+                struct TableVisitor;
+
+                impl<'de> serde::de::Visitor<'de> for TableVisitor {
+                    type Value = #table_name;
+
+                    fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+                        write!(formatter, #expecting)
+                    }
+
+                    #[allow(non_snake_case)]
+                    fn visit_seq<A: serde::de::SeqAccess<'de>>(
+                        self,
+                        mut seq: A,
+                    ) -> Result<Self::Value, A::Error> {
+                        let mut header_len = 0_usize;
+                        #(#reads)*
+                        let remainder: Vec<u8> = seq.next_element()?.unwrap_or_default();
+                        #(#follows)*
+                        Ok(#table_name { #(#names),* })
+                    }
+                }
+
+                d.deserialize_seq(TableVisitor)
+            }
+        }
+
+        impl serde::Serialize for #table_name {
+            fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+            where
+                S: serde::Serializer,
+            {
+                let mut graph = otspec::ser::graph::ObjectGraph::new();
+                let root = otspec::ser::graph::ToObject::to_object(self, &mut graph)
+                    .map_err(serde::ser::Error::custom)?;
+                let binary = graph
+                    .pack(root)
+                    .map_err(|_| serde::ser::Error::custom(otspec::error::Error::OffsetOverflow))?;
+                serializer.serialize_bytes(&binary)
+            }
         }
+
+        #to_object
+
+        #offset_record
     }
 }
 
-fn special_type(t: &str) -> Option<String> {
-    match t {
-        /* We don't use types from the fixed crate here because fixed-point
-        arithmetic is an artefact of the storage format of OpenType, and
-        not something we want to foist on the user. It's more ergonomic
-        for them to be able to manipulate plain f32s. */
-        "Fixed" => Some("f32".to_string()),
-        "F2DOT14" => Some("f32".to_string()),
-        /* But we *do* use fixed point here, because we want to be able to
-        compare fractional version numbers for equality without having to
-        do epsilon dances. */
-        "Version16Dot16" => Some("U16F16".to_string()),
-        "Offset16" => Some("u16".to_string()),
-        "Offset32" => Some("u32".to_string()),
-        "LONGDATETIME" => Some("chrono::NaiveDateTime".to_string()),
-        _ => None,
+/// Structures with offsets can also be records within another structure,
+/// in which case their offsets are relative to that structure instead
+fn offset_record(table: &Table) -> TokenStream2 {
+    if table
+        .fields
+        .iter()
+        .any(|f| matches!(f.field_type, FieldType::Records(_)))
+    {
+        // Records within records would need their own raw forms
+        return quote!();
+    }
+    let table_name = &table.name;
+    let raw_name = format_ident!("__{}Raw", table_name);
+    let raw_declarations = table.fields.iter().map(|f| f.raw_declaration());
+    let take_raw = table.fields.iter().map(|f| f.take_raw());
+    let follows = table.fields.iter().map(|f| f.follow_offsets(&quote!()));
+    let names = table.fields.iter().map(|f| &f.name);
+    let pushes = table.fields.iter().map(|f| f.to_object());
+    quote! {
+        #[doc(hidden)]
+        #[allow(non_camel_case_types, non_snake_case, missing_docs)]
+        #[derive(serde::Serialize, serde::Deserialize)]
+        pub struct #raw_name {
+            #(#raw_declarations)*
+        }
+
+        impl otspec::de::OffsetRecord for #table_name {
+            type Raw = #raw_name;
+
+            #[allow(non_snake_case)]
+            fn resolve(
+                raw: Self::Raw,
+                remainder: &[u8],
+                header_len: usize,
+            ) -> otspec::error::Result<Self> {
+                #(#take_raw)*
+                #(#follows)*
+                Ok(#table_name { #(#names),* })
+            }
+
+            #[allow(non_snake_case)]
+            fn push_to(
+                &self,
+                object: &mut otspec::ser::graph::Object,
+                graph: &mut otspec::ser::graph::ObjectGraph,
+            ) -> otspec::error::Result<()> {
+                #(#pushes)*
+                Ok(())
+            }
+        }
+    }
+}
+
+fn plain_table(table: &Table) -> TokenStream2 {
+    let table_name = &table.name;
+    let declarations = table.fields.iter().map(|f| f.declaration());
+    let to_object = to_object_impl(table_name, quote!(object.push(self)?;));
+    quote! {
+        /// Low-level structure used for serializing/deserializing table
+        #[allow(missing_docs)]
+        #[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+        pub struct #table_name {
+            #(#declarations)*
+        }
+
+        #to_object
     }
 }
 
 #[proc_macro]
 pub fn tables(item: TokenStream) -> TokenStream {
-    let mut output = TokenStream::new();
-    let mut iter = item.into_iter();
-    let mut out_s = String::new();
-
-    loop {
-        // First parse table name
-        let maybe_table_name = iter.next();
-        if maybe_table_name.is_none() {
-            break;
-        }
-
-        let table_name = expect_ident(maybe_table_name);
-        out_s.push_str(&format!(
-            "/// Low-level structure used for serializing/deserializing table\n#[allow(missing_docs)]\n#[derive(Serialize, Deserialize, Debug, PartialEq)]\npub struct {} {{",
-            table_name,
-        ));
-
-        let mut table_def = expect_group(iter.next(), Delimiter::Brace).into_iter();
-
-        loop {
-            let maybe_t = table_def.next();
-            if maybe_t.is_none() {
-                break;
-            }
-            let t = expect_ident(maybe_t);
-            if t == "Maybe" {
-                let subtype = expect_group(table_def.next(), Delimiter::Parenthesis)
-                    .into_iter()
-                    .next()
-                    .unwrap()
-                    .to_string();
-                let name = expect_ident(table_def.next());
-                out_s.push_str(&format!("pub {} : Option<{}>,\n", name, subtype))
-            } else if t == "Counted" {
-                let subtype = expect_group(table_def.next(), Delimiter::Parenthesis)
-                    .into_iter()
-                    .next()
-                    .unwrap()
-                    .to_string();
-                out_s.push_str("#[serde(with = \"Counted\")]\n");
-                let name = expect_ident(table_def.next());
-                out_s.push_str(&format!("pub {} : Vec<{}>,\n", name, subtype))
-            } else if let Some(nonspecial_type) = special_type(&t) {
-                out_s.push_str(&format!("#[serde(with = \"{}\")]\n", t));
-                let name = expect_ident(table_def.next());
-                out_s.push_str(&format!("pub {} : {},\n", name, nonspecial_type))
+    let tables = parse_macro_input!(item as Tables);
+    let output: TokenStream2 = tables
+        .0
+        .iter()
+        .map(|table| {
+            if table.fields.iter().any(|f| f.is_offset()) {
+                table_with_offsets(table)
             } else {
-                let name = expect_ident(table_def.next());
-                out_s.push_str(&format!("pub {} : {},\n", name, t))
+                plain_table(table)
             }
-        }
-        out_s.push('}');
+        })
+        .collect();
+    output.into()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{FieldType, Tables};
+
+    fn parse(input: &str) -> syn::Result<Tables> {
+        syn::parse_str(input)
+    }
+
+    #[test]
+    fn tables_parse() {
+        let tables = parse(
+            "Header {
+                uint16 version
+                Fixed revision
+                Maybe(uint16) extra
+                Counted(Record) records
+                Offset16 rawOffset
+                Offset16(Coverage) coverage
+                Counted(Offset32(Coverage)) coverages
+                Records(Record) entries
+            }
+            Record { Tag tag }",
+        )
+        .unwrap();
+        assert_eq!(tables.0.len(), 2);
+        assert_eq!(tables.0[0].name, "Header");
+        let fields = &tables.0[0].fields;
+        assert_eq!(fields.len(), 8);
+        assert!(matches!(&fields[0].field_type, FieldType::Plain(t) if t.to_string() == "uint16"));
+        assert!(
+            matches!(&fields[1].field_type, FieldType::With(m, t) if m == "Fixed" && t.to_string() == "f32")
+        );
+        assert_eq!(fields[2].rust_type().to_string(), "Option < uint16 >");
+        assert!(matches!(&fields[3].field_type, FieldType::With(m, _) if m == "Counted"));
+        assert_eq!(fields[3].rust_type().to_string(), "Vec < Record >");
+        assert!(matches!(&fields[4].field_type, FieldType::Plain(t) if t.to_string() == "u16"));
+        assert!(matches!(&fields[5].field_type, FieldType::Offset(2, t) if t == "Coverage"));
+        assert!(matches!(&fields[6].field_type, FieldType::CountedOffset(4, t) if t == "Coverage"));
+        assert!(matches!(&fields[7].field_type, FieldType::Records(t) if t == "Record"));
+        assert_eq!(fields[7].rust_type().to_string(), "Vec < Record >");
+        assert!(fields[5].is_offset() && fields[7].is_offset() && !fields[4].is_offset());
+        let record = &tables.0[1].fields;
+        assert!(
+            matches!(&record[0].field_type, FieldType::With(m, t) if m == "tag" && t.to_string() == "Tag")
+        );
+    }
+
+    #[test]
+    fn tables_parse_errors() {
+        let err = |input| parse(input).err().unwrap().to_string();
+        assert_eq!(
+            err("T { Counted(Offset16) x }"),
+            "Expected the type the offsets point to, e.g. Offset16(Coverage)"
+        );
+        assert_eq!(
+            err("T { Maybe(Offset16(Coverage)) x }"),
+            "Maybe fields cannot contain offsets"
+        );
+        assert_eq!(
+            err("T { Counted(uint16(Coverage)) x }"),
+            "Only offsets can take a type argument"
+        );
+        assert_eq!(
+            err("T { Records(Offset16(Coverage)) x }"),
+            "Records must be of a single type"
+        );
+        assert_eq!(
+            err("T { uint16 }"),
+            "unexpected end of input, expected identifier"
+        );
+        assert_eq!(err("T ( uint16 x )"), "expected curly braces");
     }
-    let ts1: TokenStream = out_s.parse().unwrap();
-    output.extend(ts1);
-    output
 }
@@ -0,0 +1,189 @@
+#![allow(non_snake_case, non_camel_case_types)]
+use otspec::types::*;
+use otspec_macros::tables;
+use serde::{Deserialize, Serialize};
+
+tables!(
+    Header {
+        uint16 version
+        Fixed revision
+        F2DOT14 coord
+        Version16Dot16 subversion
+        LONGDATETIME created
+        Tag tag
+        Offset16 rawOffset
+        Counted(Record) records
+    }
+    Record {
+        int16 value
+        uint32 flags
+    }
+    Parent {
+        uint16 format
+        Offset16(Record) record
+        Offset16(Record) missing
+        Counted(Offset32(Record)) children
+    }
+    Directory {
+        uint16 version
+        Records(Entry) entries
+    }
+    Entry {
+        Tag tag
+        Offset16(Record) record
+    }
+);
+
+/// What `tables!` should generate for `Header`, written out by hand
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct ExpectedHeader {
+    pub version: uint16,
+    #[serde(with = "Fixed")]
+    pub revision: f32,
+    #[serde(with = "F2DOT14")]
+    pub coord: f32,
+    #[serde(with = "Version16Dot16")]
+    pub subversion: U16F16,
+    #[serde(with = "LONGDATETIME")]
+    pub created: chrono::NaiveDateTime,
+    pub tag: Tag,
+    pub rawOffset: u16,
+    #[serde(with = "Counted")]
+    pub records: Vec<ExpectedRecord>,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct ExpectedRecord {
+    pub value: int16,
+    pub flags: uint32,
+}
+
+fn header() -> Header {
+    Header {
+        version: 1,
+        revision: 2.5,
+        coord: -0.5,
+        subversion: U16F16::from_num(1.5),
+        created: chrono::NaiveDate::from_ymd_opt(2021, 4, 1)
+            .and_then(|d| d.and_hms_opt(12, 0, 0))
+            .unwrap(),
+        tag: *b"test",
+        rawOffset: 0x1234,
+        records: vec![
+            Record {
+                value: -2,
+                flags: 0xdeadbeef,
+            },
+            Record { value: 7, flags: 0 },
+        ],
+    }
+}
+
+fn expected_header() -> ExpectedHeader {
+    let h = header();
+    ExpectedHeader {
+        version: h.version,
+        revision: h.revision,
+        coord: h.coord,
+        subversion: h.subversion,
+        created: h.created,
+        tag: h.tag,
+        rawOffset: h.rawOffset,
+        records: h
+            .records
+            .iter()
+            .map(|r| ExpectedRecord {
+                value: r.value,
+                flags: r.flags,
+            })
+            .collect(),
+    }
+}
+
+#[test]
+fn plain_table_matches_derived() {
+    let generated = otspec::ser::to_bytes(&header()).unwrap();
+    let expected = otspec::ser::to_bytes(&expected_header()).unwrap();
+    assert_eq!(generated, expected);
+
+    let deserialized: Header = otspec::de::from_bytes(&expected).unwrap();
+    assert_eq!(deserialized, header());
+}
+
+#[test]
+fn plain_table_to_object() {
+    let mut graph = otspec::ser::graph::ObjectGraph::new();
+    let root = otspec::ser::graph::ToObject::to_object(&header(), &mut graph).unwrap();
+    let packed = graph.pack(root).unwrap();
+    assert_eq!(packed, otspec::ser::to_bytes(&header()).unwrap());
+}
+
+#[test]
+fn offsets_round_trip() {
+    let parent = Parent {
+        format: 1,
+        record: Some(Record { value: 1, flags: 2 }),
+        missing: None,
+        children: vec![Record { value: 3, flags: 4 }, Record { value: 1, flags: 2 }],
+    };
+    let binary = otspec::ser::to_bytes(&parent).unwrap();
+    assert_eq!(
+        binary,
+        vec![
+            0x00, 0x01, // format
+            0x00, 0x10, // record
+            0x00, 0x00, // missing
+            0x00, 0x02, // children count
+            0x00, 0x00, 0x00, 0x16, // children[0]
+            0x00, 0x00, 0x00, 0x10, // children[1], shared with record
+            0x00, 0x01, 0x00, 0x00, 0x00, 0x02, // Record { 1, 2 }
+            0x00, 0x03, 0x00, 0x00, 0x00, 0x04, // Record { 3, 4 }
+        ]
+    );
+    let deserialized: Parent = otspec::de::from_bytes(&binary).unwrap();
+    assert_eq!(deserialized, parent);
+}
+
+#[test]
+fn null_counted_offset_is_an_error() {
+    let binary = vec![
+        0x00, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00,
+    ];
+    assert!(otspec::de::from_bytes::<Parent>(&binary).is_err());
+}
+
+#[test]
+fn record_offsets_are_relative_to_their_parent() {
+    let directory = Directory {
+        version: 1,
+        entries: vec![
+            Entry {
+                tag: *b"abcd",
+                record: Some(Record { value: 1, flags: 2 }),
+            },
+            Entry {
+                tag: *b"efgh",
+                record: Some(Record { value: 3, flags: 4 }),
+            },
+        ],
+    };
+    let binary = otspec::ser::to_bytes(&directory).unwrap();
+    assert_eq!(
+        binary,
+        vec![
+            0x00, 0x01, // version
+            0x00, 0x02, // entries count
+            b'a', b'b', b'c', b'd', 0x00, 0x10, // entries[0]
+            b'e', b'f', b'g', b'h', 0x00, 0x16, // entries[1]
+            0x00, 0x01, 0x00, 0x00, 0x00, 0x02, // Record { 1, 2 }
+            0x00, 0x03, 0x00, 0x00, 0x00, 0x04, // Record { 3, 4 }
+        ]
+    );
+    let deserialized: Directory = otspec::de::from_bytes(&binary).unwrap();
+    assert_eq!(deserialized, directory);
+
+    let mut bad = binary;
+    bad[15] = 0x40;
+    let error = otspec::de::from_bytes::<Directory>(&bad).unwrap_err();
+    assert!(error.to_string().contains("entries[1].record"), "{}", error);
+}
@@ -0,0 +1,6 @@
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let _ = otspec::de::from_bytes::<fonttools::gasp::gasp>(data);
+});
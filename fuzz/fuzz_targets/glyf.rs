@@ -0,0 +1,24 @@
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+
+// The first two bytes are a glyph count, followed by that many 32-bit
+// offsets (as found in `loca`, with 0xFFFFFFFF marking an empty glyph) and then
+// the glyf table itself
+fuzz_target!(|data: &[u8]| {
+    if data.len() < 2 {
+        return;
+    }
+    let count = u16::from_be_bytes([data[0], data[1]]) as usize;
+    if data.len() < 2 + count * 4 {
+        return;
+    }
+    let (offsets, glyf) = data[2..].split_at(count * 4);
+    let loca = offsets
+        .chunks(4)
+        .map(|x| match u32::from_be_bytes([x[0], x[1], x[2], x[3]]) {
+            u32::MAX => None,
+            offset => Some(offset),
+        })
+        .collect();
+    let _ = fonttools::glyf::from_bytes(glyf, loca);
+});
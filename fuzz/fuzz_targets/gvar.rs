@@ -0,0 +1,33 @@
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+
+// The first two bytes are a glyph count, followed by that many 16-bit point
+// counts (of a single contour in each glyph, to which the four phantom points
+// are added as glyf does) and then the gvar table itself
+fuzz_target!(|data: &[u8]| {
+    if data.len() < 2 {
+        return;
+    }
+    let count = u16::from_be_bytes([data[0], data[1]]) as usize;
+    if data.len() < 2 + count * 2 {
+        return;
+    }
+    let (counts, gvar) = data[2..].split_at(count * 2);
+    let coords_and_ends = counts
+        .chunks(2)
+        .map(|x| {
+            let points = u16::from_be_bytes([x[0], x[1]]) as i16;
+            let mut coords: Vec<(i16, i16)> = (0..points).map(|i| (i, i)).collect();
+            let mut ends = vec![];
+            if points > 0 {
+                ends.push(coords.len() - 1);
+            }
+            for _ in 0..4 {
+                coords.push((0, 0));
+                ends.push(coords.len() - 1);
+            }
+            (coords, ends)
+        })
+        .collect();
+    let _ = fonttools::gvar::from_bytes(gvar, coords_and_ends);
+});
@@ -0,0 +1,11 @@
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+
+// The first two bytes are the number of long metrics, as found in `hhea`
+fuzz_target!(|data: &[u8]| {
+    if data.len() < 2 {
+        return;
+    }
+    let number_of_h_metrics = u16::from_be_bytes([data[0], data[1]]);
+    let _ = fonttools::hmtx::from_bytes(&data[2..], number_of_h_metrics);
+});
@@ -0,0 +1,8 @@
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(mut font) = fonttools::font::load(data) {
+        let _ = font.fully_deserialize();
+    }
+});
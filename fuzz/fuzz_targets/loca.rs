@@ -0,0 +1,11 @@
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+
+// The first byte selects the offset size (as `head.indexToLocFormat` does),
+// followed by the loca table itself
+fuzz_target!(|data: &[u8]| {
+    if data.is_empty() {
+        return;
+    }
+    let _ = fonttools::loca::from_bytes(&data[1..], data[0] & 1 == 1);
+});
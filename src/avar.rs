@@ -23,6 +23,8 @@ tables!(
 );
 
 impl SegmentMap {
+    /// Creates a segment map from pairs of normalized coordinates, mapping
+    /// the first of each pair to the second
     pub fn new(items: Vec<(f32, f32)>) -> Self {
         let maps = items
             .iter()
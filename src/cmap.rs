@@ -1,6 +1,7 @@
 use crate::font::get_search_range;
 use otspec::de::CountedDeserializer;
 use otspec::ser;
+use otspec::ser::graph::{Object, ObjectGraph, ObjectId, ToObject};
 use otspec::types::*;
 use otspec::{deserialize_visitor, read_field, read_field_counted};
 use otspec_macros::tables;
@@ -10,22 +11,39 @@ use serde::ser::SerializeSeq;
 use serde::Deserializer;
 use serde::Serializer;
 use serde::{Deserialize, Serialize};
-use std::collections::hash_map::DefaultHasher;
-use std::collections::{BTreeMap, HashSet};
-use std::convert::TryInto;
-use std::hash::{Hash, Hasher};
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+use std::convert::{TryFrom, TryInto};
 
 tables!(
 
 EncodingRecord {
         uint16 platformID
         uint16 encodingID
-        uint32 subtableOffset
+        Offset32(SubtableData) subtable
 }
 
 CmapHeader {
     uint16  version
-    Counted(EncodingRecord) encodingRecords
+    Records(EncodingRecord) encodingRecords
+}
+
+SequentialMapGroup {
+    uint32 startCharCode
+    uint32 endCharCode
+    uint32 startGlyphID
+}
+
+SubHeader {
+    uint16 firstCode
+    uint16 entryCount
+    int16 idDelta
+    uint16 idRangeOffset
+}
+
+ConstantMapGroup {
+    uint32 startCharCode
+    uint32 endCharCode
+    uint32 glyphID
 }
 );
 
@@ -38,17 +56,29 @@ struct cmap0 {
 }
 
 impl cmap0 {
-    fn from_mapping(_languageID: uint16, _map: &BTreeMap<uint32, uint16>) -> Self {
-        unimplemented!();
-        // Self {
-        //     format: 0,
-        //     length: 0,
-        //     language: languageID,
-        //     glyphIdArray: Vec::new(),
-        // }
+    fn from_mapping(languageID: uint16, map: &BTreeMap<uint32, uint16>) -> Result<Self, String> {
+        let mut glyphIdArray = vec![0; 256];
+        for (&code, &glyph) in map {
+            let glyph = u8::try_from(glyph)
+                .map_err(|_| format!("Glyph ID {} is too large for a cmap0 table", glyph))?;
+            *glyphIdArray.get_mut(code as usize).ok_or_else(|| {
+                format!("Character code {} is too large for a cmap0 table", code)
+            })? = glyph;
+        }
+        Ok(Self {
+            format: 0,
+            length: 262,
+            language: languageID,
+            glyphIdArray,
+        })
     }
     fn to_mapping(&self) -> BTreeMap<uint32, uint16> {
-        BTreeMap::new()
+        self.glyphIdArray
+            .iter()
+            .enumerate()
+            .filter(|(_, &glyph)| glyph != 0)
+            .map(|(code, &glyph)| (code as uint32, glyph.into()))
+            .collect()
     }
 }
 
@@ -59,7 +89,7 @@ deserialize_visitor!(
         let format = read_field!(seq, uint16, "a cmap0 table format");
         let length = read_field!(seq, uint16, "a cmap0 table length");
         let language = read_field!(seq, uint16, "a cmap0 table language");
-        let glyphIdArray = read_field_counted!(seq, length, "a cmap0 glyph array");
+        let glyphIdArray = read_field_counted!(seq, 256, "a cmap0 glyph array");
         Ok(cmap0 {
             format,
             length,
@@ -69,6 +99,93 @@ deserialize_visitor!(
     }
 );
 
+/// A format 2 (high-byte mapping) subtable, which is only read
+#[derive(Debug, PartialEq)]
+struct cmap2 {
+    format: uint16,
+    length: uint16,
+    language: uint16,
+    subHeaderKeys: Vec<uint16>,
+    subHeaders: Vec<SubHeader>,
+    glyphIdArray: Vec<uint16>,
+}
+
+impl cmap2 {
+    fn to_mapping(&self) -> BTreeMap<uint32, uint16> {
+        let mut map = BTreeMap::new();
+        // Offsets are in bytes from the start of the subheaders
+        let glyph_array_start = 8 * self.subHeaders.len();
+        for (high_byte, &key) in self.subHeaderKeys.iter().enumerate() {
+            let index = key as usize / 8;
+            let subheader = match self.subHeaders.get(index) {
+                Some(subheader) => subheader,
+                None => continue,
+            };
+            let first = subheader.firstCode as usize;
+            let low_bytes = first..first + subheader.entryCount as usize;
+            // Subheader 0 maps single-byte codes; the others map two-byte
+            // codes starting with this high byte
+            let codes: Vec<(uint32, usize)> = if index == 0 {
+                vec![(high_byte as uint32, high_byte)]
+            } else {
+                low_bytes
+                    .clone()
+                    .map(|low| (((high_byte << 8) | low) as uint32, low))
+                    .collect()
+            };
+            for (code, low_byte) in codes {
+                if !low_bytes.contains(&low_byte) {
+                    continue;
+                }
+                // idRangeOffset counts from its own position in the subheader
+                let glyph =
+                    (8 * index + 6 + subheader.idRangeOffset as usize + 2 * (low_byte - first))
+                        .checked_sub(glyph_array_start)
+                        .and_then(|offset| self.glyphIdArray.get(offset / 2));
+                match glyph {
+                    Some(0) | None => {}
+                    Some(&glyph) => {
+                        map.insert(code, glyph.wrapping_add(subheader.idDelta as uint16));
+                    }
+                }
+            }
+        }
+        map
+    }
+}
+
+deserialize_visitor!(
+    cmap2,
+    Cmap2Visitor,
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+        let format = read_field!(seq, uint16, "a cmap2 table format");
+        let length = read_field!(seq, uint16, "a cmap2 table length");
+        let language = read_field!(seq, uint16, "a cmap2 table language");
+        let subHeaderKeys: Vec<uint16> =
+            read_field_counted!(seq, 256, "a cmap2 subheader key array");
+        let numSubHeaders = subHeaderKeys.iter().max().unwrap_or(&0) / 8 + 1;
+        let subHeaders: Vec<SubHeader> =
+            read_field_counted!(seq, numSubHeaders, "a cmap2 subheader array");
+        let lenSoFar = 6 + 2 * 256 + 8 * numSubHeaders as usize;
+        let glyphIdArrayLen = (length as usize).checked_sub(lenSoFar).ok_or_else(|| {
+            serde::de::Error::custom(format!(
+                "cmap2 length {} too short for {} subheaders",
+                length, numSubHeaders
+            ))
+        })? / 2;
+        let glyphIdArray: Vec<uint16> =
+            read_field_counted!(seq, glyphIdArrayLen, "a cmap2 glyph array");
+        Ok(cmap2 {
+            format,
+            length,
+            language,
+            subHeaderKeys,
+            subHeaders,
+            glyphIdArray,
+        })
+    }
+);
+
 #[derive(Debug, PartialEq, Serialize)]
 pub struct cmap4 {
     format: uint16,
@@ -86,163 +203,140 @@ pub struct cmap4 {
     glyphIdArray: Vec<uint16>,
 }
 
-fn is_contiguous_list(l: &[u16]) -> bool {
-    for ab in l.windows(2) {
-        if let [a, b] = ab {
-            if *b != *a + 1 {
-                return false;
-            }
-        }
-    }
-    true
-}
-
-fn split_range(
-    start_code: u16,
-    end_code: u16,
-    map: &BTreeMap<uint32, uint16>,
-) -> (Vec<u16>, Vec<u16>) {
-    if start_code == end_code {
-        return (vec![], vec![end_code]);
-    }
-    let mut last_id = map[&(start_code as u32)];
-    let mut last_code = start_code;
-    let mut in_order = None;
-    let mut ordered_begin = None;
-    let mut subranges = Vec::new();
-    for code in (start_code + 1)..(end_code + 1) {
-        let glyph_id = *map.get(&(code as u32)).unwrap_or(&0);
-        if glyph_id > 0 && glyph_id - 1 == last_id {
-            if in_order.is_none() || in_order == Some(0) {
-                in_order = Some(1);
-                ordered_begin = Some(last_code);
-            }
-        } else {
-            if in_order == Some(1) {
-                in_order = Some(0);
-                subranges.push((ordered_begin, last_code));
-                ordered_begin = None;
-            }
+/// A segment of a cmap4 table, before its glyph array offset is known
+struct Segment {
+    start: uint16,
+    end: uint16,
+    /// The idDelta, or `None` if the segment needs a glyph array
+    delta: Option<uint16>,
+}
+
+/// Finds the segments which give the smallest cmap4 table for a mapping
+///
+/// The mapping is split into runs of consecutive codes mapped to consecutive
+/// glyphs, each of which could be a segment with an idDelta. Runs can be
+/// merged into a segment with a glyph array, which saves the eight bytes of a
+/// segment but costs two bytes for each code the segment spans, mapped or not.
+fn optimal_segments(map: &BTreeMap<uint32, uint16>) -> Vec<Segment> {
+    let mut runs: Vec<Segment> = vec![];
+    for (&code, &glyph) in map {
+        let code = code as uint16;
+        let delta = glyph.wrapping_sub(code);
+        match runs.last_mut() {
+            Some(run) if run.end + 1 == code && run.delta == Some(delta) => run.end = code,
+            _ => runs.push(Segment {
+                start: code,
+                end: code,
+                delta: Some(delta),
+            }),
         }
-        last_id = glyph_id;
-        last_code = code;
-    }
-    if in_order == Some(1) {
-        subranges.push((ordered_begin, last_code));
     }
-    assert_eq!(last_code, end_code);
 
-    let mut new_ranges: Vec<(u32, u32)> = Vec::new();
-    for (b, e) in subranges {
-        let b = b.unwrap();
-
-        if b == start_code && e == end_code {
-            break;
+    // cost[i] is the size of the best segments for the first i runs, and
+    // first[i] is the first run of the last of those segments
+    let mut cost = vec![0_usize; runs.len() + 1];
+    let mut first = vec![0; runs.len() + 1];
+    // The best cost of the runs before a merged segment, less two bytes for
+    // each code before the segment's start, and the first run of that segment
+    let mut best_merge: Option<(isize, usize)> = None;
+    for (i, run) in runs.iter().enumerate() {
+        cost[i + 1] = cost[i] + 8;
+        first[i + 1] = i;
+        if let Some((merge_cost, merge_first)) = best_merge {
+            let merged = (merge_cost + 8 + 2 * (run.end as isize + 1)) as usize;
+            if merged < cost[i + 1] {
+                cost[i + 1] = merged;
+                first[i + 1] = merge_first;
+            }
         }
-        let threshold = if b == start_code || e == end_code {
-            4
-        } else {
-            8
-        };
-        if (e - b + 1) > threshold {
-            new_ranges.push((b.into(), e.into()));
+        let candidate = cost[i] as isize - 2 * run.start as isize;
+        match best_merge {
+            Some((merge_cost, _)) if merge_cost <= candidate => {}
+            _ => best_merge = Some((candidate, i)),
         }
     }
 
-    if new_ranges.is_empty() {
-        return (vec![], vec![end_code]);
-    }
-
-    if new_ranges[0].0 != (start_code as u32) {
-        new_ranges.insert(0, (start_code.into(), new_ranges[0].0 - 1))
-    }
-    if new_ranges.last().unwrap().1 != (end_code as u32) {
-        new_ranges.push((new_ranges.last().unwrap().1 + 1, end_code.into()));
-    }
-    let mut i = 1;
-    while i < new_ranges.len() {
-        if new_ranges[i - 1].1 + 1 != new_ranges[i].0 {
-            new_ranges.insert(i, (new_ranges[i - 1].1 + 1, new_ranges[i].0 - 1));
-            i += 1;
-        }
-        i += 1;
-    }
-    let mut start: Vec<u16> = Vec::new();
-    let mut end: Vec<u16> = Vec::new();
-    for (b, e) in new_ranges {
-        start.push(b as u16);
-        end.push(e as u16);
+    let mut segments = vec![];
+    let mut i = runs.len();
+    while i > 0 {
+        let start = first[i];
+        segments.push(Segment {
+            start: runs[start].start,
+            end: runs[i - 1].end,
+            delta: if start == i - 1 {
+                runs[start].delta
+            } else {
+                None
+            },
+        });
+        i = start;
     }
-    start.drain(0..1);
-    assert_eq!(start.len() + 1, end.len());
-    (start, end)
+    segments.reverse();
+    segments
 }
 
 impl cmap4 {
-    pub fn from_mapping(languageID: uint16, map: &BTreeMap<uint32, uint16>) -> Self {
-        let mut char_codes: Vec<uint32> = map.keys().cloned().collect();
-        char_codes.sort_unstable();
-        let mut last_code = char_codes[0];
-        let mut startCode: Vec<u16> = vec![last_code.try_into().unwrap()];
-        let mut endCode: Vec<u16> = Vec::new();
-        for char_code in &char_codes[1..] {
-            if *char_code == last_code + 1 {
-                last_code = *char_code;
-                continue;
-            }
-            let (mut start, mut end) = split_range(
-                *startCode.last().unwrap(),
-                last_code.try_into().unwrap(),
-                map,
-            );
-            // println!("Split_range called, returned {:?} {:?}", start, end);
-            startCode.append(&mut start);
-            endCode.append(&mut end);
-            startCode.push((*char_code).try_into().unwrap());
-            last_code = *char_code;
-        }
-        let (mut start, mut end) = split_range(
-            *startCode.last().unwrap(),
-            last_code.try_into().unwrap(),
-            map,
-        );
-        startCode.append(&mut start);
-        endCode.append(&mut end);
-        startCode.push(0xffff);
-        endCode.push(0xffff);
-        // println!("Start code array: {:?} ", startCode);
-        // println!("End code array: {:?}", endCode);
-        let mut idDelta: Vec<i16> = Vec::new();
-        let mut idRangeOffsets = Vec::new();
-        let mut glyphIndexArray = Vec::new();
-        for i in 0..(endCode.len() - 1) {
-            let mut indices: Vec<u16> = Vec::new();
-            for char_code in startCode[i]..endCode[i] + 1 {
-                let gid = *map.get(&(char_code as u32)).unwrap_or(&0);
-                indices.push(gid);
-            }
-            if is_contiguous_list(&indices) {
-                // println!("Contiguous list {:?}", indices);
-                idDelta.push((indices[0] as i16 - startCode[i] as i16) as i16);
-                idRangeOffsets.push(0);
-            } else {
-                // println!("Non contiguous list {:?}", indices);
-                idDelta.push(0);
-                idRangeOffsets.push(2 * (endCode.len() + glyphIndexArray.len() - i) as u16);
-                glyphIndexArray.append(&mut indices);
+    /// Builds the smallest cmap4 table for a mapping
+    ///
+    /// Codes from 0xFFFF, and tables too large for their 16-bit offsets, are
+    /// errors.
+    pub fn from_mapping(
+        languageID: uint16,
+        map: &BTreeMap<uint32, uint16>,
+    ) -> Result<Self, String> {
+        if let Some(code) = map.keys().find(|&&code| code >= 0xFFFF) {
+            return Err(format!(
+                "Character code {} is too large for a cmap4 table",
+                code
+            ));
+        }
+        let mut segments = optimal_segments(map);
+        // The final segment maps 0xFFFF to glyph 0
+        segments.push(Segment {
+            start: 0xFFFF,
+            end: 0xFFFF,
+            delta: Some(1),
+        });
+        let segCount = segments.len();
+        let mut startCode = Vec::with_capacity(segCount);
+        let mut endCode = Vec::with_capacity(segCount);
+        let mut idDelta = Vec::with_capacity(segCount);
+        let mut idRangeOffsets = Vec::with_capacity(segCount);
+        let mut glyphIdArray: Vec<uint16> = vec![];
+        for (i, segment) in segments.iter().enumerate() {
+            startCode.push(segment.start);
+            endCode.push(segment.end);
+            match segment.delta {
+                Some(delta) => {
+                    idDelta.push(delta as int16);
+                    idRangeOffsets.push(0);
+                }
+                None => {
+                    idDelta.push(0);
+                    // The offset is from the segment's own idRangeOffset
+                    let offset = 2 * (segCount - i + glyphIdArray.len());
+                    idRangeOffsets.push(offset as uint16);
+                    glyphIdArray.extend(
+                        (segment.start..=segment.end)
+                            .map(|code| *map.get(&(code as uint32)).unwrap_or(&0)),
+                    );
+                }
             }
         }
-        // println!("ID Delta array: {:?}", idDelta);
-        // println!("ID Range Offset array: {:?}", idRangeOffsets);
-        idDelta.push(1);
-        idRangeOffsets.push(0);
-        let segcount = endCode.len() as u16;
-        let (searchRange, entrySelector, rangeShift) = get_search_range(segcount, 2);
-        Self {
+        let length = 16 + 8 * segCount + 2 * glyphIdArray.len();
+        if length > 0xFFFF {
+            return Err(format!(
+                "cmap4 table of {} bytes is too large; use a format 12 table",
+                length
+            ));
+        }
+        let segCount = segCount as uint16;
+        let (searchRange, entrySelector, rangeShift) = get_search_range(segCount, 2);
+        Ok(Self {
             format: 4,
-            length: (glyphIndexArray.len() * 2 + 16 + 2 * 4 * segcount as usize) as u16,
+            length: length as uint16,
             language: languageID,
-            segCountX2: segcount * 2,
+            segCountX2: segCount * 2,
             searchRange,
             entrySelector,
             rangeShift,
@@ -251,46 +345,32 @@ impl cmap4 {
             startCode,
             idDelta,
             idRangeOffsets,
-            glyphIdArray: glyphIndexArray,
-        }
+            glyphIdArray,
+        })
     }
 
     fn to_mapping(&self) -> BTreeMap<uint32, uint16> {
         let mut map = BTreeMap::new();
-        for i in 0..(self.startCode.len() - 1) {
-            let start = self.startCode[i];
-            let end = self.endCode[i];
-            let delta = self.idDelta[i];
-            let range_offset = self.idRangeOffsets[i];
-            if end == 0xffff {
-                break;
-            }
-            let range_char_codes = start..(1 + end);
-            for char_code in range_char_codes {
-                if range_offset == 0 {
-                    map.insert(char_code as u32, (char_code as i16 + delta) as u16);
+        let segCount = self.idRangeOffsets.len();
+        for (i, (&start, &end)) in self.startCode.iter().zip(&self.endCode).enumerate() {
+            let delta = self.idDelta.get(i).copied().unwrap_or(0) as uint16;
+            let range_offset = self.idRangeOffsets.get(i).copied().unwrap_or(0) as usize;
+            for char_code in start..=end {
+                let glyph_id = if range_offset == 0 {
+                    char_code.wrapping_add(delta)
                 } else {
-                    // println!("Range offset/2: {:?}", range_offset / 2);
-                    // println!("start: {:?}", start);
-                    // println!("i: {:?}", i);
-                    // println!("Range of idRangeOffsets: {:?}", self.idRangeOffsets.len());
-                    let partial = (range_offset / 2) as i16
-                        - (start as i16 + i as i16 - self.idRangeOffsets.len() as i16) as i16;
-                    // println!("Partial: {:?}", partial);
-                    let index = (char_code as i16 + partial) as usize;
-                    // println!("Index: {:?}", index);
-                    // println!("GlyphIdArray: {:?}", self.glyphIdArray.len());
-                    // XXX
-                    if index >= self.glyphIdArray.len() {
-                        break;
-                    }
-                    assert!(index < self.glyphIdArray.len());
-                    if self.glyphIdArray[index] != 0 {
-                        let glyph_id = self.glyphIdArray[index] as i16 + delta;
-                        map.insert(char_code as u32, glyph_id as u16);
-                    } else {
-                        map.insert(char_code as u32, 0);
+                    // The offset is in bytes from this segment's idRangeOffset
+                    // to its glyphs in the array which follows
+                    match (range_offset / 2 + (char_code - start) as usize + i)
+                        .checked_sub(segCount)
+                        .and_then(|index| self.glyphIdArray.get(index))
+                    {
+                        Some(0) | None => continue,
+                        Some(glyph_id) => glyph_id.wrapping_add(delta),
                     }
+                };
+                if glyph_id != 0 {
+                    map.insert(char_code as uint32, glyph_id);
                 }
             }
         }
@@ -315,11 +395,17 @@ deserialize_visitor!(
         let idRangeOffsets: Vec<uint16> =
             read_field_counted!(seq, segcount, "a cmap4 table idRangeOffsets");
 
-        let lenSoFar = 16 + (segcount * 2 * 4);
+        let lenSoFar = 16 + (segcount as usize * 2 * 4);
+        let glyphIdArrayLen = (length as usize).checked_sub(lenSoFar).ok_or_else(|| {
+            serde::de::Error::custom(format!(
+                "cmap4 length {} too short for {} segments",
+                length, segcount
+            ))
+        })? / 2;
 
         // This one is optional, hence unwrap_or_default
         let glyphIdArray: Vec<u16> = seq
-            .next_element_seed(CountedDeserializer::with_len((length - lenSoFar) as usize))?
+            .next_element_seed(CountedDeserializer::with_len(glyphIdArrayLen))?
             .unwrap_or_default();
 
         Ok(cmap4 {
@@ -340,7 +426,529 @@ deserialize_visitor!(
     }
 );
 
-#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[derive(Debug, PartialEq, Serialize)]
+struct cmap6 {
+    format: uint16,
+    length: uint16,
+    language: uint16,
+    firstCode: uint16,
+    entryCount: uint16,
+    glyphIdArray: Vec<uint16>,
+}
+
+impl cmap6 {
+    /// The most codes a subtable can span while its length fits in 16 bits
+    const MAX_ENTRIES: uint32 = (0xFFFF - 10) / 2;
+
+    /// Whether a mapping can be written as a cmap6 table
+    fn fits(map: &BTreeMap<uint32, uint16>) -> bool {
+        match (map.keys().next(), map.keys().next_back()) {
+            (Some(&first), Some(&last)) => last <= 0xFFFF && last - first < Self::MAX_ENTRIES,
+            _ => true,
+        }
+    }
+
+    fn from_mapping(languageID: uint16, map: &BTreeMap<uint32, uint16>) -> Result<Self, String> {
+        let firstCode = *map.keys().next().unwrap_or(&0);
+        let lastCode = *map.keys().next_back().unwrap_or(&0);
+        if lastCode > 0xFFFF {
+            return Err(format!(
+                "Character code {} is too large for a cmap6 table",
+                lastCode
+            ));
+        }
+        if !Self::fits(map) {
+            return Err(format!(
+                "Character codes {} to {} span too many entries for a cmap6 table",
+                firstCode, lastCode
+            ));
+        }
+        let mut glyphIdArray = vec![0; map.len().min(1) * (lastCode - firstCode + 1) as usize];
+        for (&code, &glyph) in map {
+            glyphIdArray[(code - firstCode) as usize] = glyph;
+        }
+        Ok(Self {
+            format: 6,
+            length: 10 + 2 * glyphIdArray.len() as uint16,
+            language: languageID,
+            firstCode: firstCode as uint16,
+            entryCount: glyphIdArray.len() as uint16,
+            glyphIdArray,
+        })
+    }
+    fn to_mapping(&self) -> BTreeMap<uint32, uint16> {
+        self.glyphIdArray
+            .iter()
+            .enumerate()
+            .filter(|(_, &glyph)| glyph != 0)
+            .map(|(index, &glyph)| (self.firstCode as uint32 + index as uint32, glyph))
+            .collect()
+    }
+}
+
+deserialize_visitor!(
+    cmap6,
+    Cmap6Visitor,
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+        let format = read_field!(seq, uint16, "a cmap6 table format");
+        let length = read_field!(seq, uint16, "a cmap6 table length");
+        let language = read_field!(seq, uint16, "a cmap6 table language");
+        let firstCode = read_field!(seq, uint16, "a cmap6 table first code");
+        let entryCount = read_field!(seq, uint16, "a cmap6 table entry count");
+        let glyphIdArray = read_field_counted!(seq, entryCount, "a cmap6 glyph array");
+        Ok(cmap6 {
+            format,
+            length,
+            language,
+            firstCode,
+            entryCount,
+            glyphIdArray,
+        })
+    }
+);
+
+/// Checks that the groups of a cmap8, cmap12 or cmap13 table are in order, and map
+/// valid codepoints to valid glyph IDs
+fn check_groups<E: serde::de::Error>(
+    groups: impl Iterator<Item = (uint32, uint32, uint32)>,
+    format: uint16,
+) -> Result<(), E> {
+    let mut next_code = 0;
+    for (start, end, last_glyph) in groups {
+        if start < next_code || end < start || end > 0x10FFFF || last_glyph > 0xFFFF {
+            return Err(E::custom(format!(
+                "Invalid cmap{} group mapping {}-{}",
+                format, start, end
+            )));
+        }
+        next_code = end + 1;
+    }
+    Ok(())
+}
+
+/// Maps the codes in a list of sequential groups to glyph IDs
+fn sequential_mapping(groups: &[SequentialMapGroup]) -> BTreeMap<uint32, uint16> {
+    let mut map = BTreeMap::new();
+    for group in groups {
+        for code in group.startCharCode..=group.endCharCode {
+            let glyph = group.startGlyphID + (code - group.startCharCode);
+            map.insert(code, glyph as uint16);
+        }
+    }
+    map
+}
+
+/// Checks sequential groups, whose last glyph is found from the first
+fn check_sequential_groups<E: serde::de::Error>(
+    groups: &[SequentialMapGroup],
+    format: uint16,
+) -> Result<(), E> {
+    check_groups(
+        groups.iter().map(|g| {
+            (
+                g.startCharCode,
+                g.endCharCode,
+                g.startGlyphID
+                    .saturating_add(g.endCharCode.saturating_sub(g.startCharCode)),
+            )
+        }),
+        format,
+    )
+}
+
+/// A format 8 (mixed 16-bit and 32-bit coverage) subtable, which is only read
+#[derive(Debug, PartialEq)]
+struct cmap8 {
+    format: uint16,
+    reserved: uint16,
+    length: uint32,
+    language: uint32,
+    is32: Vec<u8>,
+    numGroups: uint32,
+    groups: Vec<SequentialMapGroup>,
+}
+
+deserialize_visitor!(
+    cmap8,
+    Cmap8Visitor,
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+        let format = read_field!(seq, uint16, "a cmap8 table format");
+        let reserved = read_field!(seq, uint16, "a cmap8 table reserved field");
+        let length = read_field!(seq, uint32, "a cmap8 table length");
+        let language = read_field!(seq, uint32, "a cmap8 table language");
+        let is32 = read_field_counted!(seq, 8192, "a cmap8 is32 array");
+        let numGroups = read_field!(seq, uint32, "a cmap8 table group count");
+        let groups: Vec<SequentialMapGroup> =
+            read_field_counted!(seq, numGroups, "a cmap8 table group array");
+        check_sequential_groups(&groups, 8)?;
+        Ok(cmap8 {
+            format,
+            reserved,
+            length,
+            language,
+            is32,
+            numGroups,
+            groups,
+        })
+    }
+);
+
+/// A format 10 (trimmed array) subtable, which is only read
+#[derive(Debug, PartialEq)]
+struct cmap10 {
+    format: uint16,
+    reserved: uint16,
+    length: uint32,
+    language: uint32,
+    startCharCode: uint32,
+    numChars: uint32,
+    glyphIdArray: Vec<uint16>,
+}
+
+impl cmap10 {
+    fn to_mapping(&self) -> BTreeMap<uint32, uint16> {
+        self.glyphIdArray
+            .iter()
+            .enumerate()
+            .filter(|(_, &glyph)| glyph != 0)
+            .map(|(index, &glyph)| (self.startCharCode.wrapping_add(index as uint32), glyph))
+            .collect()
+    }
+}
+
+deserialize_visitor!(
+    cmap10,
+    Cmap10Visitor,
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+        let format = read_field!(seq, uint16, "a cmap10 table format");
+        let reserved = read_field!(seq, uint16, "a cmap10 table reserved field");
+        let length = read_field!(seq, uint32, "a cmap10 table length");
+        let language = read_field!(seq, uint32, "a cmap10 table language");
+        let startCharCode = read_field!(seq, uint32, "a cmap10 table start code");
+        let numChars = read_field!(seq, uint32, "a cmap10 table character count");
+        if !matches!(startCharCode.checked_add(numChars), Some(end) if end <= 0x110000) {
+            return Err(serde::de::Error::custom(format!(
+                "Invalid cmap10 range of {} characters from {}",
+                numChars, startCharCode
+            )));
+        }
+        let glyphIdArray = read_field_counted!(seq, numChars, "a cmap10 glyph array");
+        Ok(cmap10 {
+            format,
+            reserved,
+            length,
+            language,
+            startCharCode,
+            numChars,
+            glyphIdArray,
+        })
+    }
+);
+
+#[derive(Debug, PartialEq, Serialize)]
+struct cmap12 {
+    format: uint16,
+    reserved: uint16,
+    length: uint32,
+    language: uint32,
+    numGroups: uint32,
+    groups: Vec<SequentialMapGroup>,
+}
+
+impl cmap12 {
+    fn from_mapping(languageID: uint16, map: &BTreeMap<uint32, uint16>) -> Self {
+        let mut groups: Vec<SequentialMapGroup> = vec![];
+        for (&code, &glyph) in map {
+            match groups.last_mut() {
+                Some(last)
+                    if last.endCharCode + 1 == code
+                        && last.startGlyphID + (code - last.startCharCode) == glyph as uint32 =>
+                {
+                    last.endCharCode = code
+                }
+                _ => groups.push(SequentialMapGroup {
+                    startCharCode: code,
+                    endCharCode: code,
+                    startGlyphID: glyph as uint32,
+                }),
+            }
+        }
+        Self {
+            format: 12,
+            reserved: 0,
+            length: 16 + 12 * groups.len() as uint32,
+            language: languageID as uint32,
+            numGroups: groups.len() as uint32,
+            groups,
+        }
+    }
+    fn to_mapping(&self) -> BTreeMap<uint32, uint16> {
+        sequential_mapping(&self.groups)
+    }
+}
+
+deserialize_visitor!(
+    cmap12,
+    Cmap12Visitor,
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+        let format = read_field!(seq, uint16, "a cmap12 table format");
+        let reserved = read_field!(seq, uint16, "a cmap12 table reserved field");
+        let length = read_field!(seq, uint32, "a cmap12 table length");
+        let language = read_field!(seq, uint32, "a cmap12 table language");
+        let numGroups = read_field!(seq, uint32, "a cmap12 table group count");
+        let groups: Vec<SequentialMapGroup> =
+            read_field_counted!(seq, numGroups, "a cmap12 table group array");
+        check_sequential_groups(&groups, 12)?;
+        Ok(cmap12 {
+            format,
+            reserved,
+            length,
+            language,
+            numGroups,
+            groups,
+        })
+    }
+);
+
+#[derive(Debug, PartialEq, Serialize)]
+struct cmap13 {
+    format: uint16,
+    reserved: uint16,
+    length: uint32,
+    language: uint32,
+    numGroups: uint32,
+    groups: Vec<ConstantMapGroup>,
+}
+
+impl cmap13 {
+    fn from_mapping(languageID: uint16, map: &BTreeMap<uint32, uint16>) -> Self {
+        let mut groups: Vec<ConstantMapGroup> = vec![];
+        for (&code, &glyph) in map {
+            match groups.last_mut() {
+                Some(last) if last.endCharCode + 1 == code && last.glyphID == glyph as uint32 => {
+                    last.endCharCode = code
+                }
+                _ => groups.push(ConstantMapGroup {
+                    startCharCode: code,
+                    endCharCode: code,
+                    glyphID: glyph as uint32,
+                }),
+            }
+        }
+        Self {
+            format: 13,
+            reserved: 0,
+            length: 16 + 12 * groups.len() as uint32,
+            language: languageID as uint32,
+            numGroups: groups.len() as uint32,
+            groups,
+        }
+    }
+    fn to_mapping(&self) -> BTreeMap<uint32, uint16> {
+        let mut map = BTreeMap::new();
+        for group in &self.groups {
+            for code in group.startCharCode..=group.endCharCode {
+                map.insert(code, group.glyphID as uint16);
+            }
+        }
+        map
+    }
+}
+
+deserialize_visitor!(
+    cmap13,
+    Cmap13Visitor,
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+        let format = read_field!(seq, uint16, "a cmap13 table format");
+        let reserved = read_field!(seq, uint16, "a cmap13 table reserved field");
+        let length = read_field!(seq, uint32, "a cmap13 table length");
+        let language = read_field!(seq, uint32, "a cmap13 table language");
+        let numGroups = read_field!(seq, uint32, "a cmap13 table group count");
+        let groups: Vec<ConstantMapGroup> =
+            read_field_counted!(seq, numGroups, "a cmap13 table group array");
+        check_groups(
+            groups
+                .iter()
+                .map(|g| (g.startCharCode, g.endCharCode, g.glyphID)),
+            13,
+        )?;
+        Ok(cmap13 {
+            format,
+            reserved,
+            length,
+            language,
+            numGroups,
+            groups,
+        })
+    }
+);
+
+/// Unicode Variation Sequences, as a map from (base codepoint, variation
+/// selector) pairs to glyph IDs
+///
+/// Default sequences, which use the glyph that the base codepoint is mapped to
+/// in the Unicode subtables, have no glyph ID.
+pub type VariationSequences = BTreeMap<(uint32, uint32), Option<uint16>>;
+
+/// Reads the variation sequences from a cmap14 table
+///
+/// The table's 24-bit fields have no otspec type, so it is read by hand.
+fn cmap14_to_sequences(data: &[u8]) -> otspec::error::Result<VariationSequences> {
+    let truncated = || otspec::error::Error::Message("Truncated cmap14 table".to_string());
+    let uint24_at = |offset: usize| {
+        data.get(offset..offset + 3)
+            .map(|b| u32::from_be_bytes([0, b[0], b[1], b[2]]))
+            .ok_or_else(truncated)
+    };
+    let uint32_at = |offset: usize| {
+        data.get(offset..offset + 4)
+            .map(|b| u32::from_be_bytes(b.try_into().unwrap()))
+            .ok_or_else(truncated)
+    };
+    let mut sequences = BTreeMap::new();
+    let numVarSelectorRecords = uint32_at(6)?;
+    for i in 0..numVarSelectorRecords as usize {
+        let record = 10 + 11 * i;
+        let selector = uint24_at(record)?;
+        let defaultUVSOffset = uint32_at(record + 3)? as usize;
+        let nonDefaultUVSOffset = uint32_at(record + 7)? as usize;
+        if defaultUVSOffset != 0 {
+            let numUnicodeValueRanges = uint32_at(defaultUVSOffset)?;
+            for range in 0..numUnicodeValueRanges as usize {
+                let range = defaultUVSOffset + 4 + 4 * range;
+                let start = uint24_at(range)?;
+                let additionalCount = *data.get(range + 3).ok_or_else(truncated)?;
+                for base in start..=start + additionalCount as uint32 {
+                    sequences.insert((base, selector), None);
+                }
+            }
+        }
+        if nonDefaultUVSOffset != 0 {
+            let numUVSMappings = uint32_at(nonDefaultUVSOffset)?;
+            for mapping in 0..numUVSMappings as usize {
+                let mapping = nonDefaultUVSOffset + 4 + 5 * mapping;
+                let glyph = data
+                    .get(mapping + 3..mapping + 5)
+                    .map(|b| u16::from_be_bytes([b[0], b[1]]))
+                    .ok_or_else(truncated)?;
+                sequences.insert((uint24_at(mapping)?, selector), Some(glyph));
+            }
+        }
+    }
+    Ok(sequences)
+}
+
+/// Writes variation sequences as a cmap14 table
+fn cmap14_from_sequences(sequences: &VariationSequences) -> Result<Vec<u8>, String> {
+    // Default and non-default sequences, grouped by selector
+    let mut defaults: BTreeMap<uint32, Vec<uint32>> = BTreeMap::new();
+    let mut mappings: BTreeMap<uint32, Vec<(uint32, uint16)>> = BTreeMap::new();
+    for (&(base, selector), &glyph) in sequences {
+        if base > 0x10FFFF || selector > 0x10FFFF {
+            return Err(format!(
+                "Invalid variation sequence U+{:04X} U+{:04X}",
+                base, selector
+            ));
+        }
+        match glyph {
+            None => defaults.entry(selector).or_default().push(base),
+            Some(glyph) => mappings.entry(selector).or_default().push((base, glyph)),
+        }
+    }
+    let selectors: BTreeSet<uint32> = defaults.keys().chain(mappings.keys()).copied().collect();
+    let header_len = 10 + 11 * selectors.len();
+    let mut records = Vec::with_capacity(header_len);
+    let mut uvs_tables: Vec<u8> = Vec::new();
+    for selector in &selectors {
+        records.extend_from_slice(&selector.to_be_bytes()[1..]);
+        if let Some(defaults) = defaults.get(selector) {
+            records.extend_from_slice(&((header_len + uvs_tables.len()) as uint32).to_be_bytes());
+            let mut ranges: Vec<(uint32, u8)> = Vec::new();
+            for &base in defaults {
+                match ranges.last_mut() {
+                    Some((start, additionalCount))
+                        if *additionalCount < 255
+                            && *start + *additionalCount as uint32 + 1 == base =>
+                    {
+                        *additionalCount += 1
+                    }
+                    _ => ranges.push((base, 0)),
+                }
+            }
+            uvs_tables.extend_from_slice(&(ranges.len() as uint32).to_be_bytes());
+            for (start, additionalCount) in ranges {
+                uvs_tables.extend_from_slice(&start.to_be_bytes()[1..]);
+                uvs_tables.push(additionalCount);
+            }
+        } else {
+            records.extend_from_slice(&0_u32.to_be_bytes());
+        }
+        if let Some(mappings) = mappings.get(selector) {
+            records.extend_from_slice(&((header_len + uvs_tables.len()) as uint32).to_be_bytes());
+            uvs_tables.extend_from_slice(&(mappings.len() as uint32).to_be_bytes());
+            for (base, glyph) in mappings {
+                uvs_tables.extend_from_slice(&base.to_be_bytes()[1..]);
+                uvs_tables.extend_from_slice(&glyph.to_be_bytes());
+            }
+        } else {
+            records.extend_from_slice(&0_u32.to_be_bytes());
+        }
+    }
+    let mut output = Vec::with_capacity(header_len + uvs_tables.len());
+    output.extend_from_slice(&14_u16.to_be_bytes());
+    output.extend_from_slice(&((header_len + uvs_tables.len()) as uint32).to_be_bytes());
+    output.extend_from_slice(&(selectors.len() as uint32).to_be_bytes());
+    output.extend(records);
+    output.extend(uvs_tables);
+    Ok(output)
+}
+
+/// Variation sequences in human-readable formats, as a list of records
+mod variation_sequences {
+    use super::VariationSequences;
+    use otspec::types::*;
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    #[derive(Serialize, Deserialize)]
+    struct VariationSequence {
+        base: uint32,
+        selector: uint32,
+        glyph: Option<uint16>,
+    }
+
+    pub fn serialize<S>(sequences: &VariationSequences, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.collect_seq(sequences.iter().map(|(&(base, selector), &glyph)| {
+            VariationSequence {
+                base,
+                selector,
+                glyph,
+            }
+        }))
+    }
+
+    pub fn deserialize<'de, D>(d: D) -> Result<VariationSequences, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        Ok(Vec::<VariationSequence>::deserialize(d)?
+            .into_iter()
+            .map(|s| ((s.base, s.selector), s.glyph))
+            .collect())
+    }
+}
+
+/// A cmap subtable, as a mapping from codepoints to glyph IDs
+///
+/// Subtables are only deserialized as part of a cmap table; the derived
+/// `Deserialize` is for human-readable formats.
+///
+/// The legacy formats 2, 8 and 10 can be read, but are written as format 4,
+/// or as format 12 if they map codes beyond U+FFFF. Format 6 mappings which
+/// span too many codes for a format 6 subtable are written the same way.
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Deserialize)]
 pub struct CmapSubtable {
     pub format: uint16,
     pub platformID: uint16,
@@ -349,6 +957,17 @@ pub struct CmapSubtable {
     pub mapping: BTreeMap<uint32, uint16>,
 }
 
+/// A cmap subtable in human-readable formats
+#[derive(Serialize)]
+#[serde(remote = "CmapSubtable")]
+struct CmapSubtableDef {
+    format: uint16,
+    platformID: uint16,
+    encodingID: uint16,
+    languageID: uint16,
+    mapping: BTreeMap<uint32, uint16>,
+}
+
 impl CmapSubtable {
     pub fn is_unicode(&self) -> bool {
         self.platformID == 0
@@ -365,19 +984,181 @@ impl Serialize for CmapSubtable {
     where
         S: Serializer,
     {
+        if serializer.is_human_readable() {
+            return CmapSubtableDef::serialize(self, serializer);
+        }
+        let binary = mapping_to_bytes(self.format, self.languageID, &self.mapping)
+            .map_err(serde::ser::Error::custom)?;
         let mut seq = serializer.serialize_seq(None)?;
-        match self.format {
-            0 => seq.serialize_element(&cmap0::from_mapping(self.languageID, &self.mapping)),
-            4 => seq.serialize_element(&cmap4::from_mapping(self.languageID, &self.mapping)),
-            _ => unimplemented!(),
-        }?;
+        seq.serialize_element(&binary)?;
         seq.end()
     }
 }
 
+/// Writes a mapping as a subtable of the given format
+fn mapping_to_bytes(
+    format: uint16,
+    languageID: uint16,
+    mapping: &BTreeMap<uint32, uint16>,
+) -> otspec::error::Result<Vec<u8>> {
+    let message = otspec::error::Error::Message;
+    match format {
+        0 => ser::to_bytes(&cmap0::from_mapping(languageID, mapping).map_err(message)?),
+        6 if cmap6::fits(mapping) => {
+            ser::to_bytes(&cmap6::from_mapping(languageID, mapping).map_err(message)?)
+        }
+        // Legacy formats, and format 6 mappings spanning too many codes, are
+        // rewritten as format 4, or as format 12 if they map codes beyond the
+        // Basic Multilingual Plane
+        2 | 6 | 8 | 10 if mapping.keys().all(|&code| code < 0xFFFF) => {
+            ser::to_bytes(&cmap4::from_mapping(languageID, mapping).map_err(message)?)
+        }
+        6 | 8 | 10 => ser::to_bytes(&cmap12::from_mapping(languageID, mapping)),
+        4 => ser::to_bytes(&cmap4::from_mapping(languageID, mapping).map_err(message)?),
+        12 => ser::to_bytes(&cmap12::from_mapping(languageID, mapping)),
+        13 => ser::to_bytes(&cmap13::from_mapping(languageID, mapping)),
+        format => Err(message(format!(
+            "Cannot serialize cmap subtable format {}",
+            format
+        ))),
+    }
+}
+
+/// A subtable as stored in the binary cmap table, which doesn't record the
+/// platform and encoding it is used for
+#[derive(Debug, PartialEq, Clone)]
+pub enum SubtableData {
+    /// A mapping from codepoints to glyph IDs
+    #[allow(missing_docs)]
+    Mapping {
+        format: uint16,
+        languageID: uint16,
+        mapping: BTreeMap<uint32, uint16>,
+    },
+    /// Unicode variation sequences, from a format 14 subtable
+    VariationSequences(VariationSequences),
+    /// A subtable in a format which can't be read
+    Unknown(uint16),
+}
+
+deserialize_visitor!(
+    SubtableData,
+    SubtableDataVisitor,
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+        let data = read_field!(seq, Vec<u8>, "a cmap subtable");
+        let invalid = |e: otspec::error::Error| e.nested();
+        let format = data
+            .get(0..2)
+            .map(|b| u16::from_be_bytes([b[0], b[1]]))
+            .ok_or_else(|| serde::de::Error::custom("Truncated cmap subtable"))?;
+        Ok(match format {
+            0 => {
+                let subtable: cmap0 = otspec::de::from_bytes(&data).map_err(invalid)?;
+                SubtableData::Mapping {
+                    format,
+                    languageID: subtable.language,
+                    mapping: subtable.to_mapping(),
+                }
+            }
+            2 => {
+                let subtable: cmap2 = otspec::de::from_bytes(&data).map_err(invalid)?;
+                SubtableData::Mapping {
+                    format,
+                    languageID: subtable.language,
+                    mapping: subtable.to_mapping(),
+                }
+            }
+            4 => {
+                let subtable: cmap4 = otspec::de::from_bytes(&data).map_err(invalid)?;
+                SubtableData::Mapping {
+                    format,
+                    languageID: subtable.language,
+                    mapping: subtable.to_mapping(),
+                }
+            }
+            6 => {
+                let subtable: cmap6 = otspec::de::from_bytes(&data).map_err(invalid)?;
+                SubtableData::Mapping {
+                    format,
+                    languageID: subtable.language,
+                    mapping: subtable.to_mapping(),
+                }
+            }
+            8 => {
+                let subtable: cmap8 = otspec::de::from_bytes(&data).map_err(invalid)?;
+                SubtableData::Mapping {
+                    format,
+                    languageID: subtable.language as uint16,
+                    mapping: sequential_mapping(&subtable.groups),
+                }
+            }
+            10 => {
+                let subtable: cmap10 = otspec::de::from_bytes(&data).map_err(invalid)?;
+                SubtableData::Mapping {
+                    format,
+                    languageID: subtable.language as uint16,
+                    mapping: subtable.to_mapping(),
+                }
+            }
+            12 => {
+                let subtable: cmap12 = otspec::de::from_bytes(&data).map_err(invalid)?;
+                SubtableData::Mapping {
+                    format,
+                    languageID: subtable.language as uint16,
+                    mapping: subtable.to_mapping(),
+                }
+            }
+            13 => {
+                let subtable: cmap13 = otspec::de::from_bytes(&data).map_err(invalid)?;
+                SubtableData::Mapping {
+                    format,
+                    languageID: subtable.language as uint16,
+                    mapping: subtable.to_mapping(),
+                }
+            }
+            14 => SubtableData::VariationSequences(cmap14_to_sequences(&data).map_err(invalid)?),
+            format => SubtableData::Unknown(format),
+        })
+    }
+);
+
+impl ToObject for SubtableData {
+    fn to_object(&self, graph: &mut ObjectGraph) -> otspec::error::Result<ObjectId> {
+        let mut object = Object::new();
+        object.data = match self {
+            SubtableData::Mapping {
+                format,
+                languageID,
+                mapping,
+            } => mapping_to_bytes(*format, *languageID, mapping)?,
+            SubtableData::VariationSequences(sequences) => {
+                cmap14_from_sequences(sequences).map_err(otspec::error::Error::Message)?
+            }
+            SubtableData::Unknown(format) => {
+                return Err(otspec::error::Error::Message(format!(
+                    "Cannot serialize cmap subtable format {}",
+                    format
+                )))
+            }
+        };
+        Ok(graph.add(object))
+    }
+}
+
 #[derive(Debug, PartialEq)]
 pub struct cmap {
     pub subtables: Vec<CmapSubtable>,
+    /// The Unicode Variation Sequences, stored in a format 14 subtable
+    pub variationSequences: VariationSequences,
+}
+
+/// The cmap table in human-readable formats
+#[derive(Serialize, Deserialize)]
+#[serde(remote = "cmap")]
+struct CmapDef {
+    subtables: Vec<CmapSubtable>,
+    #[serde(default, with = "variation_sequences")]
+    variationSequences: VariationSequences,
 }
 
 impl Serialize for cmap {
@@ -385,73 +1166,85 @@ impl Serialize for cmap {
     where
         S: Serializer,
     {
-        let mut offsets: BTreeMap<u64, uint32> = BTreeMap::new();
-        let mut output: Vec<u8> = Vec::new();
-        let mut encoding_records: Vec<EncodingRecord> = Vec::new();
-        let offset_base = (4 + self.subtables.len() * 8) as u32;
-        for st in &self.subtables {
-            let mut hash = DefaultHasher::new();
-            st.mapping.hash(&mut hash);
-            let hash_value = hash.finish();
-            if !offsets.contains_key(&hash_value) {
-                let offset = offset_base + output.len() as u32;
-                output.extend(ser::to_bytes(&st).unwrap());
-                offsets.insert(hash_value, offset);
-            }
-            encoding_records.push(EncodingRecord {
+        if serializer.is_human_readable() {
+            return CmapDef::serialize(self, serializer);
+        }
+        let mut encoding_records: Vec<EncodingRecord> = self
+            .subtables
+            .iter()
+            .map(|st| EncodingRecord {
                 platformID: st.platformID,
                 encodingID: st.encodingID,
-                subtableOffset: *offsets.get(&hash_value).unwrap(),
+                // Identical subtables are shared by the object graph
+                subtable: Some(SubtableData::Mapping {
+                    format: st.format,
+                    languageID: st.languageID,
+                    mapping: st.mapping.clone(),
+                }),
+            })
+            .collect();
+        if !self.variationSequences.is_empty() {
+            encoding_records.push(EncodingRecord {
+                platformID: 0,
+                encodingID: 5,
+                subtable: Some(SubtableData::VariationSequences(
+                    self.variationSequences.clone(),
+                )),
             });
+            // Encoding records must be sorted by platform and encoding
+            encoding_records.sort_by_key(|er| (er.platformID, er.encodingID));
         }
-        let header = CmapHeader {
+        CmapHeader {
             version: 0,
             encodingRecords: encoding_records,
-        };
-        let mut seq = serializer.serialize_seq(None)?;
-        seq.serialize_element(&header)?;
-        seq.serialize_element(&output)?;
-        seq.end()
+        }
+        .serialize(serializer)
     }
 }
 
 deserialize_visitor!(
     cmap,
     CmapVisitor,
+    readable: CmapDef,
     fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
         let core = read_field!(seq, CmapHeader, "a cmap table");
-        let remainder = read_field!(seq, Vec<u8>, "a cmap table");
-        let offset_base = (4 + core.encodingRecords.len() * 8) as u32;
         let mut subtables = Vec::with_capacity(core.encodingRecords.len());
-        for er in &core.encodingRecords {
-            let subtable_bytes = &remainder[(er.subtableOffset - offset_base) as usize..];
-            match subtable_bytes[0..2] {
-                [0x0, 0x0] => {
-                    let subtable: cmap0 = otspec::de::from_bytes(&subtable_bytes).unwrap();
-                    subtables.push(CmapSubtable {
-                        format: 0,
-                        platformID: er.platformID,
-                        encodingID: er.encodingID,
-                        languageID: subtable.language,
-                        mapping: subtable.to_mapping(),
-                    });
+        let mut variationSequences = BTreeMap::new();
+        for er in core.encodingRecords {
+            match er.subtable {
+                Some(SubtableData::Mapping {
+                    format,
+                    languageID,
+                    mapping,
+                }) => subtables.push(CmapSubtable {
+                    format,
+                    platformID: er.platformID,
+                    encodingID: er.encodingID,
+                    languageID,
+                    mapping,
+                }),
+                Some(SubtableData::VariationSequences(sequences))
+                    if er.platformID == 0 && er.encodingID == 5 =>
+                {
+                    variationSequences.extend(sequences)
                 }
-                [0x0, 0x04] => {
-                    let subtable: cmap4 = otspec::de::from_bytes(&subtable_bytes).unwrap();
-                    subtables.push(CmapSubtable {
-                        format: 4,
-                        platformID: er.platformID,
-                        encodingID: er.encodingID,
-                        languageID: subtable.language,
-                        mapping: subtable.to_mapping(),
-                    });
+                Some(SubtableData::VariationSequences(_)) => {
+                    log::warn!(
+                        "Variation sequences for platform {} encoding {}, dropping them",
+                        er.platformID,
+                        er.encodingID
+                    );
                 }
-                _ => {
-                    println!("Unknown format",);
+                Some(SubtableData::Unknown(format)) => {
+                    log::warn!("Unknown cmap subtable format {}, dropping it", format);
                 }
+                None => return Err(serde::de::Error::custom("NULL cmap subtable offset")),
             }
         }
-        Ok(cmap { subtables })
+        Ok(cmap {
+            subtables,
+            variationSequences,
+        })
     }
 );
 
@@ -487,6 +1280,16 @@ impl cmap {
         None
     }
 
+    /// Returns the glyph for a Unicode Variation Sequence, if the font has one
+    ///
+    /// Default sequences use the glyph which the base codepoint is mapped to.
+    pub fn getVariationGlyph(&self, base: uint32, selector: uint32) -> Option<uint16> {
+        match self.variationSequences.get(&(base, selector))? {
+            Some(glyph) => Some(*glyph),
+            None => self.getBestMapping()?.get(&base).copied(),
+        }
+    }
+
     pub fn reversed(&self) -> BTreeMap<u16, HashSet<u32>> {
         let mut res = BTreeMap::new();
         for subtable in &self.subtables {
@@ -506,6 +1309,9 @@ impl cmap {
 #[cfg(test)]
 mod tests {
     use crate::cmap;
+    use proptest::collection::vec;
+    use proptest::prelude::*;
+    use std::collections::BTreeMap;
     use std::iter::FromIterator;
 
     macro_rules! btreemap {
@@ -532,6 +1338,7 @@ mod tests {
                     mapping: btreemap!( 32 => 1, 160 => 1, 65 => 2 ),
                 },
             ],
+            variationSequences: BTreeMap::new(),
         };
         let binary_cmap = vec![
             0x00, 0x00, 0x00, 0x02, 0x00, 0x00, 0x00, 0x03, 0x00, 0x00, 0x00, 0x14, 0x00, 0x03,
@@ -563,6 +1370,7 @@ mod tests {
                     mapping: btreemap!( 32 => 1, 160 => 1, 65 => 2 ),
                 },
             ],
+            variationSequences: BTreeMap::new(),
         };
         let expected = vec![
             0x00, 0x00, 0x00, 0x02, 0x00, 0x00, 0x00, 0x03, 0x00, 0x00, 0x00, 0x14, 0x00, 0x03,
@@ -596,6 +1404,7 @@ mod tests {
                 languageID: 0,
                 mapping: bt,
             }],
+            variationSequences: BTreeMap::new(),
         };
         let deserialized: cmap::cmap = otspec::de::from_bytes(&binary_cmap).unwrap();
         let serialized = otspec::ser::to_bytes(&deserialized).unwrap();
@@ -621,8 +1430,410 @@ mod tests {
                     mapping: btreemap!( 32 => 1, 160 => 1, 65 => 2 ),
                 },
             ],
+            variationSequences: BTreeMap::new(),
         };
         let revmap = fcmap.reversed();
         assert!(revmap.get(&2).unwrap().contains(&65));
     }
+
+    #[test]
+    fn cmap_serde_format12() {
+        let fcmap = cmap::cmap {
+            subtables: vec![cmap::CmapSubtable {
+                format: 12,
+                platformID: 3,
+                encodingID: 10,
+                languageID: 0,
+                mapping: btreemap!( 0x41 => 1, 0x42 => 2, 0x43 => 3, 0x1F600 => 4, 0x1F601 => 5, 0x20000 => 7 ),
+            }],
+            variationSequences: BTreeMap::new(),
+        };
+        let binary_cmap = vec![
+            0x00, 0x00, 0x00, 0x01, 0x00, 0x03, 0x00, 0x0a, 0x00, 0x00, 0x00, 0x0c, 0x00, 0x0c,
+            0x00, 0x00, 0x00, 0x00, 0x00, 0x34, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x03,
+            0x00, 0x00, 0x00, 0x41, 0x00, 0x00, 0x00, 0x43, 0x00, 0x00, 0x00, 0x01, 0x00, 0x01,
+            0xf6, 0x00, 0x00, 0x01, 0xf6, 0x01, 0x00, 0x00, 0x00, 0x04, 0x00, 0x02, 0x00, 0x00,
+            0x00, 0x02, 0x00, 0x00, 0x00, 0x00, 0x00, 0x07,
+        ];
+        let deserialized: cmap::cmap = otspec::de::from_bytes(&binary_cmap).unwrap();
+        assert_eq!(deserialized, fcmap);
+        let serialized = otspec::ser::to_bytes(&fcmap).unwrap();
+        assert_eq!(serialized, binary_cmap);
+
+        // Groups must be in order
+        let mut unordered = binary_cmap.clone();
+        unordered[52..64].copy_from_slice(&[0, 0, 0, 0x30, 0, 0, 0, 0x30, 0, 0, 0, 7]);
+        assert!(otspec::de::from_bytes::<cmap::cmap>(&unordered).is_err());
+    }
+
+    #[test]
+    fn cmap_serde_format13() {
+        let fcmap = cmap::cmap {
+            subtables: vec![cmap::CmapSubtable {
+                format: 13,
+                platformID: 3,
+                encodingID: 10,
+                languageID: 0,
+                mapping: btreemap!( 0x10000 => 1, 0x10001 => 1, 0x10002 => 1, 0x10003 => 2 ),
+            }],
+            variationSequences: BTreeMap::new(),
+        };
+        let binary_cmap = vec![
+            0x00, 0x00, 0x00, 0x01, 0x00, 0x03, 0x00, 0x0a, 0x00, 0x00, 0x00, 0x0c, 0x00, 0x0d,
+            0x00, 0x00, 0x00, 0x00, 0x00, 0x28, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x02,
+            0x00, 0x01, 0x00, 0x00, 0x00, 0x01, 0x00, 0x02, 0x00, 0x00, 0x00, 0x01, 0x00, 0x01,
+            0x00, 0x03, 0x00, 0x01, 0x00, 0x03, 0x00, 0x00, 0x00, 0x02,
+        ];
+        let deserialized: cmap::cmap = otspec::de::from_bytes(&binary_cmap).unwrap();
+        assert_eq!(deserialized, fcmap);
+        let serialized = otspec::ser::to_bytes(&fcmap).unwrap();
+        assert_eq!(serialized, binary_cmap);
+    }
+
+    #[test]
+    fn cmap_serde_format0() {
+        let mut fcmap = cmap::cmap {
+            subtables: vec![cmap::CmapSubtable {
+                format: 0,
+                platformID: 1,
+                encodingID: 0,
+                languageID: 0,
+                mapping: btreemap!( 0x41 => 1, 0xC9 => 2 ),
+            }],
+            variationSequences: BTreeMap::new(),
+        };
+        let serialized = otspec::ser::to_bytes(&fcmap).unwrap();
+        assert_eq!(serialized.len(), 12 + 262);
+        assert_eq!(&serialized[12..18], &[0x00, 0x00, 0x01, 0x06, 0x00, 0x00]);
+        assert_eq!(serialized[18 + 0x41], 1);
+        assert_eq!(serialized[18 + 0xC9], 2);
+        let deserialized: cmap::cmap = otspec::de::from_bytes(&serialized).unwrap();
+        assert_eq!(deserialized, fcmap);
+
+        fcmap.subtables[0].mapping.insert(0x42, 256);
+        assert!(otspec::ser::to_bytes(&fcmap).is_err());
+    }
+
+    #[test]
+    fn cmap_ser_errors() {
+        let mut fcmap = cmap::cmap {
+            subtables: vec![cmap::CmapSubtable {
+                format: 4,
+                platformID: 3,
+                encodingID: 1,
+                languageID: 0,
+                mapping: btreemap!( 0x41 => 1, 0x1F600 => 2 ),
+            }],
+            variationSequences: BTreeMap::new(),
+        };
+        assert!(otspec::ser::to_bytes(&fcmap).is_err());
+        fcmap.subtables[0].format = 99;
+        assert!(otspec::ser::to_bytes(&fcmap).is_err());
+    }
+
+    #[test]
+    fn cmap4_sparse_mapping() {
+        // A glyph array spanning the gaps is smaller than three segments
+        let mapping = btreemap!( 0x41 => 1, 0x43 => 2, 0x45 => 3 );
+        let subtable = cmap::cmap4::from_mapping(0, &mapping).unwrap();
+        assert_eq!(subtable.startCode, vec![0x41, 0xFFFF]);
+        assert_eq!(subtable.endCode, vec![0x45, 0xFFFF]);
+        assert_eq!(subtable.idRangeOffsets, vec![4, 0]);
+        assert_eq!(subtable.glyphIdArray, vec![1, 0, 2, 0, 3]);
+        assert_eq!(subtable.length, 42);
+        assert_eq!(subtable.to_mapping(), mapping);
+
+        // Long runs of consecutive glyphs keep their own segments
+        let mut mapping: BTreeMap<u32, u16> =
+            (0x100..0x120).map(|c| (c, c as u16 - 0xF0)).collect();
+        mapping.insert(0x122, 7);
+        let subtable = cmap::cmap4::from_mapping(0, &mapping).unwrap();
+        assert_eq!(subtable.startCode, vec![0x100, 0x122, 0xFFFF]);
+        assert_eq!(subtable.idDelta, vec![-0xF0, 7 - 0x122, 1]);
+        assert_eq!(subtable.to_mapping(), mapping);
+
+        assert!(cmap::cmap4::from_mapping(0, &btreemap!( 0xFFFF => 1 )).is_err());
+        // Unrelated glyphs for every other code do not fit in a cmap4 table
+        let mapping = (0..30000).map(|i| (2 * i, (i % 7 + 1) as u16)).collect();
+        assert!(cmap::cmap4::from_mapping(0, &mapping).is_err());
+        assert_eq!(
+            cmap::cmap4::from_mapping(0, &BTreeMap::new())
+                .unwrap()
+                .to_mapping(),
+            BTreeMap::new()
+        );
+    }
+
+    /// A mapping which mixes runs of consecutive glyphs with sparse codes,
+    /// all within a window of random size so that the runs may overlap
+    fn mapping() -> impl Strategy<Value = BTreeMap<u32, u16>> {
+        let run = (
+            0..0xFFFF_u32,
+            1..=0xFFFE_u16,
+            vec(1..=0xFFFE_u16, 0..40),
+            any::<bool>(),
+        );
+        (1..=0xFFFF_u32)
+            .prop_flat_map(move |window| {
+                (0..=0xFFFF - window, Just(window), vec(run.clone(), 0..50))
+            })
+            .prop_map(|(first, window, runs)| {
+                let mut mapping = BTreeMap::new();
+                for (start, glyph, glyphs, consecutive) in runs {
+                    let start = first + start % window;
+                    for (i, code) in (start..0xFFFF).take(glyphs.len()).enumerate() {
+                        let glyph = if consecutive {
+                            glyph.wrapping_add(i as u16).max(1)
+                        } else {
+                            glyphs[i]
+                        };
+                        mapping.insert(code, glyph);
+                    }
+                }
+                mapping
+            })
+    }
+
+    /// A mapping with few enough runs to try every way of merging them
+    fn few_runs() -> impl Strategy<Value = BTreeMap<u32, u16>> {
+        (0..0xFF00_u32, vec((0..6_u32, 1..=4_u16, 1..=12_u32), 0..10)).prop_map(
+            |(mut code, runs)| {
+                let mut mapping = BTreeMap::new();
+                for (gap, glyph, length) in runs {
+                    code += gap;
+                    for i in 0..length {
+                        mapping.insert(code + i, glyph + i as u16);
+                    }
+                    code += length;
+                }
+                mapping
+            },
+        )
+    }
+
+    /// The size of the smallest cmap4 table, by trying every way of merging
+    /// runs of consecutive glyphs into segments
+    fn smallest_cmap4(mapping: &BTreeMap<u32, u16>) -> usize {
+        let mut runs: Vec<(u32, u32, u16)> = vec![];
+        for (&code, &glyph) in mapping {
+            let delta = glyph.wrapping_sub(code as u16);
+            match runs.last_mut() {
+                Some(run) if run.1 + 1 == code && run.2 == delta => run.1 = code,
+                _ => runs.push((code, code, delta)),
+            }
+        }
+        let mut best = usize::MAX;
+        for splits in 0..1_u32 << runs.len().saturating_sub(1) {
+            let (mut size, mut first) = (24, 0);
+            for last in 0..runs.len() {
+                if last + 1 < runs.len() && splits & (1 << last) == 0 {
+                    continue;
+                }
+                size += 8;
+                if last > first {
+                    size += 2 * (runs[last].1 - runs[first].0 + 1) as usize;
+                }
+                first = last + 1;
+            }
+            best = best.min(size);
+        }
+        best
+    }
+
+    proptest! {
+        #![proptest_config(ProptestConfig::with_cases(500))]
+
+        #[test]
+        fn cmap4_roundtrip_property(mapping in mapping()) {
+            let subtable = cmap::cmap4::from_mapping(0, &mapping).unwrap();
+            prop_assert_eq!(subtable.to_mapping(), mapping.clone());
+            let binary = otspec::ser::to_bytes(&subtable).unwrap();
+            prop_assert_eq!(binary.len(), subtable.length as usize);
+            let deserialized: cmap::cmap4 = otspec::de::from_bytes(&binary).unwrap();
+            prop_assert_eq!(deserialized.to_mapping(), mapping);
+        }
+
+        #[test]
+        fn cmap4_smallest_property(mapping in few_runs()) {
+            let subtable = cmap::cmap4::from_mapping(0, &mapping).unwrap();
+            prop_assert_eq!(subtable.to_mapping(), mapping.clone());
+            prop_assert_eq!(subtable.length as usize, smallest_cmap4(&mapping));
+        }
+    }
+
+    /// Wraps a subtable in a cmap table with a single encoding record
+    fn wrap_subtable(platformID: u16, encodingID: u16, subtable: &[u16]) -> Vec<u8> {
+        let mut binary = vec![0, 0, 0, 1];
+        binary.extend(&platformID.to_be_bytes());
+        binary.extend(&encodingID.to_be_bytes());
+        binary.extend(&[0, 0, 0, 12]);
+        binary.extend(
+            subtable
+                .iter()
+                .flat_map(|value| value.to_be_bytes().to_vec()),
+        );
+        binary
+    }
+
+    /// Reads a cmap table, and reads it back after saving it
+    fn legacy_roundtrip(binary: &[u8]) -> (cmap::cmap, cmap::cmap) {
+        let deserialized: cmap::cmap = otspec::de::from_bytes(binary).unwrap();
+        let serialized = otspec::ser::to_bytes(&deserialized).unwrap();
+        (deserialized, otspec::de::from_bytes(&serialized).unwrap())
+    }
+
+    #[test]
+    fn cmap_de_format2() {
+        let mut subtable = vec![2, 542, 0];
+        let mut subHeaderKeys = vec![0; 256];
+        subHeaderKeys[0x81] = 8;
+        subtable.extend(subHeaderKeys);
+        // Single bytes 0x41-0x42, and 0x81 followed by 0x40-0x41
+        subtable.extend(&[0x41, 2, 0, 10, 0x40, 2, 10, 6]);
+        subtable.extend(&[1, 2, 3, 0]);
+        let (deserialized, reloaded) = legacy_roundtrip(&wrap_subtable(3, 2, &subtable));
+        assert_eq!(deserialized.subtables[0].format, 2);
+        assert_eq!(
+            deserialized.subtables[0].mapping,
+            btreemap!( 0x41 => 1, 0x42 => 2, 0x8140 => 13 )
+        );
+        assert_eq!(reloaded.subtables[0].format, 4);
+        assert_eq!(
+            reloaded.subtables[0].mapping,
+            deserialized.subtables[0].mapping
+        );
+    }
+
+    #[test]
+    fn cmap_serde_format6() {
+        let binary_cmap = wrap_subtable(1, 0, &[6, 16, 0, 0x20, 3, 1, 0, 2]);
+        let deserialized: cmap::cmap = otspec::de::from_bytes(&binary_cmap).unwrap();
+        assert_eq!(deserialized.subtables[0].format, 6);
+        assert_eq!(
+            deserialized.subtables[0].mapping,
+            btreemap!( 0x20 => 1, 0x22 => 2 )
+        );
+        assert_eq!(otspec::ser::to_bytes(&deserialized).unwrap(), binary_cmap);
+    }
+
+    #[test]
+    fn cmap_ser_format6_too_wide() {
+        let subtable = |mapping| cmap::CmapSubtable {
+            format: 6,
+            platformID: 1,
+            encodingID: 0,
+            languageID: 0,
+            mapping,
+        };
+        let reload = |mapping| {
+            let fcmap = cmap::cmap {
+                subtables: vec![subtable(mapping)],
+                variationSequences: BTreeMap::new(),
+            };
+            let binary = otspec::ser::to_bytes(&fcmap).unwrap();
+            let reloaded: cmap::cmap = otspec::de::from_bytes(&binary).unwrap();
+            assert_eq!(reloaded.subtables[0].mapping, fcmap.subtables[0].mapping);
+            reloaded.subtables[0].format
+        };
+        assert!(cmap::cmap6::from_mapping(0, &btreemap!( 0x20 => 1, 0xFFFE => 2 )).is_err());
+        assert_eq!(reload(btreemap!( 0x20 => 1, 0xFFFE => 2 )), 4);
+        assert_eq!(reload(btreemap!( 0x20 => 1, 0xFFFF => 2 )), 12);
+        assert_eq!(reload(btreemap!( 0x20 => 1, 0x8019 => 2 )), 6);
+        assert_eq!(reload(btreemap!( 0x20 => 1, 0x801A => 2 )), 4);
+        assert_eq!(reload(btreemap!( 0xFFF0 => 1, 0xFFFF => 2 )), 6);
+    }
+
+    #[test]
+    fn cmap_de_format8() {
+        let mut subtable = vec![8, 0, 0, 8220, 0, 0];
+        subtable.extend(vec![0; 4096]);
+        subtable.extend(&[0, 1, 0, 0x41, 0, 0x42, 0, 5]);
+        let (deserialized, reloaded) = legacy_roundtrip(&wrap_subtable(3, 1, &subtable));
+        assert_eq!(deserialized.subtables[0].format, 8);
+        assert_eq!(
+            deserialized.subtables[0].mapping,
+            btreemap!( 0x41 => 5, 0x42 => 6 )
+        );
+        assert_eq!(reloaded.subtables[0].format, 4);
+        assert_eq!(
+            reloaded.subtables[0].mapping,
+            deserialized.subtables[0].mapping
+        );
+    }
+
+    #[test]
+    fn cmap_de_format10() {
+        let subtable = [10, 0, 0, 24, 0, 0, 1, 0, 0, 2, 3, 4];
+        let (deserialized, reloaded) = legacy_roundtrip(&wrap_subtable(3, 10, &subtable));
+        assert_eq!(deserialized.subtables[0].format, 10);
+        assert_eq!(
+            deserialized.subtables[0].mapping,
+            btreemap!( 0x10000 => 3, 0x10001 => 4 )
+        );
+        // Codes beyond the BMP cannot be written as format 4
+        assert_eq!(reloaded.subtables[0].format, 12);
+        assert_eq!(
+            reloaded.subtables[0].mapping,
+            deserialized.subtables[0].mapping
+        );
+        assert_eq!(
+            reloaded.getBestMapping(),
+            Some(&deserialized.subtables[0].mapping)
+        );
+    }
+
+    #[test]
+    fn cmap_serde_format14() {
+        let fcmap = cmap::cmap {
+            subtables: vec![],
+            variationSequences: btreemap!(
+                (0x4E08, 0xE0100) => None,
+                (0x4E09, 0xE0100) => None,
+                (0x4E08, 0xE0101) => Some(2),
+            ),
+        };
+        let binary_cmap = vec![
+            0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x05, 0x00, 0x00, 0x00, 0x0c, 0x00, 0x0e,
+            0x00, 0x00, 0x00, 0x31, 0x00, 0x00, 0x00, 0x02, 0x0e, 0x01, 0x00, 0x00, 0x00, 0x00,
+            0x20, 0x00, 0x00, 0x00, 0x00, 0x0e, 0x01, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+            0x00, 0x28, 0x00, 0x00, 0x00, 0x01, 0x00, 0x4e, 0x08, 0x01, 0x00, 0x00, 0x00, 0x01,
+            0x00, 0x4e, 0x08, 0x00, 0x02,
+        ];
+        let serialized = otspec::ser::to_bytes(&fcmap).unwrap();
+        assert_eq!(serialized, binary_cmap);
+        let deserialized: cmap::cmap = otspec::de::from_bytes(&binary_cmap).unwrap();
+        assert_eq!(deserialized, fcmap);
+
+        // A truncated table is an error
+        let truncated: Result<cmap::cmap, _> = otspec::de::from_bytes(&binary_cmap[..58]);
+        assert!(truncated.is_err());
+    }
+
+    #[test]
+    fn cmap_variation_glyph() {
+        let fcmap = cmap::cmap {
+            subtables: vec![cmap::CmapSubtable {
+                format: 4,
+                platformID: 3,
+                encodingID: 1,
+                languageID: 0,
+                mapping: btreemap!( 0x4E08 => 1 ),
+            }],
+            variationSequences: btreemap!(
+                (0x4E08, 0xE0100) => None,
+                (0x4E09, 0xE0100) => None,
+                (0x4E08, 0xE0101) => Some(2),
+            ),
+        };
+        assert_eq!(fcmap.getVariationGlyph(0x4E08, 0xE0100), Some(1));
+        assert_eq!(fcmap.getVariationGlyph(0x4E08, 0xE0101), Some(2));
+        assert_eq!(fcmap.getVariationGlyph(0x4E08, 0xE0102), None);
+        assert_eq!(fcmap.getVariationGlyph(0x4E09, 0xE0100), None);
+
+        // The format 14 subtable's encoding record is sorted before 3/1
+        let serialized = otspec::ser::to_bytes(&fcmap).unwrap();
+        assert_eq!(serialized[4..8], [0x00, 0x00, 0x00, 0x05]);
+        let deserialized: cmap::cmap = otspec::de::from_bytes(&serialized).unwrap();
+        assert_eq!(deserialized, fcmap);
+    }
 }
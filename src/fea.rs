@@ -0,0 +1,1150 @@
+use crate::font::{Font, Table};
+use crate::gdef::{gdef, GlyphClass};
+use crate::gpos::{gpos, Anchor, Positioning, ValueRecord};
+use crate::gsub::{gsub, Substitution};
+use crate::layout::{
+    ChainedSequenceContext, ClassDef, Coverage, LayoutTable, Lookup, LookupFlags, ScriptList,
+    SequenceContext, SequenceLookupRecord,
+};
+use otspec::types::*;
+use serde::de::DeserializeOwned;
+use std::collections::BTreeSet;
+
+/// Decompiles the font's layout tables to OpenType feature file syntax
+///
+/// The `GDEF`, `GSUB` and `GPOS` tables are read (if present) and written out
+/// as a feature file which, when compiled against the same glyph order, should
+/// produce equivalent layout behaviour. Glyph names are taken from the `post`
+/// table where available; glyphs without names are written as `glyphXXXXX`.
+/// An error is returned if a lookup has subtables of a type or format which
+/// is not currently supported, such as reverse chaining substitutions, or
+/// positioning with device tables, rather than writing a feature file which
+/// behaves differently. Feature variations
+/// are not read; a warning is logged if they are dropped.
+pub fn decompile(font: &mut Font) -> otspec::error::Result<String> {
+    let names = match font.get_table(b"post")? {
+        Some(Table::Post(post)) => post.glyphnames.clone().unwrap_or_default(),
+        _ => vec![],
+    };
+    let num_glyphs = match font.get_table(b"maxp")? {
+        Some(Table::Maxp(maxp)) => maxp.num_glyphs() as usize,
+        _ => names.len(),
+    };
+    let gdef: Option<gdef> = layout_table(font, b"GDEF")?;
+    let gsub: Option<gsub> = layout_table(font, b"GSUB")?;
+    let gpos: Option<gpos> = layout_table(font, b"GPOS")?;
+
+    let mut writer = FeaWriter {
+        names,
+        num_glyphs,
+        lines: vec![],
+    };
+    let script_lists: Vec<&ScriptList> = gsub
+        .iter()
+        .map(|t| &t.scripts)
+        .chain(gpos.iter().map(|t| &t.scripts))
+        .collect();
+    writer.languagesystems(&script_lists);
+    if let Some(gdef) = &gdef {
+        writer.gdef(gdef);
+    }
+    if let Some(gpos) = &gpos {
+        writer.mark_classes(gpos);
+    }
+    if let Some(gsub) = &gsub {
+        writer.layout_table(gsub)?;
+    }
+    if let Some(gpos) = &gpos {
+        writer.layout_table(gpos)?;
+    }
+    Ok(writer.lines.join("\n"))
+}
+
+fn layout_table<T: DeserializeOwned>(
+    font: &mut Font,
+    tag: &Tag,
+) -> otspec::error::Result<Option<T>> {
+    match font.get_table(tag)? {
+        Some(Table::Unknown(binary)) => Ok(Some(otspec::de::from_bytes(binary)?)),
+        _ => Ok(None),
+    }
+}
+
+fn tag_string(tag: &Tag) -> String {
+    String::from_utf8_lossy(tag).trim_end().to_string()
+}
+
+/// Returns script tags with `DFLT` first, as the feature file syntax requires
+fn sorted_scripts(scripts: &ScriptList) -> Vec<&Tag> {
+    let mut tags: Vec<&Tag> = scripts.scripts.keys().collect();
+    tags.sort_by_key(|t| (*t != b"DFLT", **t));
+    tags
+}
+
+fn anchor(anchor: &Option<Anchor>) -> String {
+    match anchor {
+        None => "<anchor NULL>".to_string(),
+        Some(Anchor {
+            xCoordinate,
+            yCoordinate,
+            anchorPoint: Some(point),
+            ..
+        }) => format!(
+            "<anchor {} {} contourpoint {}>",
+            xCoordinate, yCoordinate, point
+        ),
+        Some(a) => format!("<anchor {} {}>", a.xCoordinate, a.yCoordinate),
+    }
+}
+
+fn value_record(vr: &ValueRecord) -> String {
+    if vr.xPlacement.is_none() && vr.yPlacement.is_none() && vr.yAdvance.is_none() {
+        return format!("{:}", vr.xAdvance.unwrap_or(0));
+    }
+    format!(
+        "<{} {} {} {}>",
+        vr.xPlacement.unwrap_or(0),
+        vr.yPlacement.unwrap_or(0),
+        vr.xAdvance.unwrap_or(0),
+        vr.yAdvance.unwrap_or(0)
+    )
+}
+
+fn mark_class_name(lookup: usize, subtable: usize, class: usize) -> String {
+    format!("@MC_{:}_{:}_{:}", lookup, subtable, class)
+}
+
+struct FeaWriter {
+    names: Vec<String>,
+    /// The number of glyphs in the font, needed to list the glyphs of class zero
+    num_glyphs: usize,
+    lines: Vec<String>,
+}
+
+impl FeaWriter {
+    fn glyph(&self, gid: uint16) -> String {
+        self.names
+            .get(gid as usize)
+            .cloned()
+            .unwrap_or_else(|| format!("glyph{:05}", gid))
+    }
+
+    fn glyph_class(&self, glyphs: &[uint16]) -> String {
+        let names: Vec<String> = glyphs.iter().map(|g| self.glyph(*g)).collect();
+        format!("[{:}]", names.join(" "))
+    }
+
+    /// Returns the glyphs in a class, including class zero (every glyph not
+    /// assigned to another class)
+    fn class_glyphs(&self, classDef: &ClassDef, class: uint16) -> Vec<uint16> {
+        if class != 0 {
+            return classDef.glyphs_in_class(class);
+        }
+        (0..self.num_glyphs)
+            .map(|g| g as uint16)
+            .filter(|g| !classDef.classes.contains_key(g))
+            .collect()
+    }
+
+    fn glyph_or_class(&self, glyphs: &[uint16]) -> String {
+        if glyphs.len() == 1 {
+            self.glyph(glyphs[0])
+        } else {
+            self.glyph_class(glyphs)
+        }
+    }
+
+    fn languagesystems(&mut self, script_lists: &[&ScriptList]) {
+        let mut seen = BTreeSet::new();
+        let mut systems = vec![];
+        for scripts in script_lists {
+            for script_tag in sorted_scripts(scripts) {
+                let script = &scripts.scripts[script_tag];
+                let mut languages = vec![];
+                if script.defaultLangSys.is_some() {
+                    languages.push("dflt".to_string());
+                }
+                languages.extend(script.langSys.keys().map(tag_string));
+                for language in languages {
+                    let system = (tag_string(script_tag), language);
+                    if seen.insert(system.clone()) {
+                        systems.push(system);
+                    }
+                }
+            }
+        }
+        systems.sort_by_key(|(s, _)| s != "DFLT");
+        for (script, language) in &systems {
+            self.lines
+                .push(format!("languagesystem {:} {:};", script, language));
+        }
+        if !systems.is_empty() {
+            self.lines.push("".to_string());
+        }
+    }
+
+    fn gdef(&mut self, gdef: &gdef) {
+        if gdef.glyphClassDef.is_some() {
+            let classes: Vec<String> = [
+                GlyphClass::Base,
+                GlyphClass::Ligature,
+                GlyphClass::Mark,
+                GlyphClass::Component,
+            ]
+            .iter()
+            .map(|c| {
+                let glyphs = gdef.glyphs_in_class(*c);
+                if glyphs.is_empty() {
+                    "".to_string()
+                } else {
+                    self.glyph_class(&glyphs)
+                }
+            })
+            .collect();
+            self.lines.push("table GDEF {".to_string());
+            self.lines
+                .push(format!("    GlyphClassDef {:};", classes.join(", ")));
+            self.lines.push("} GDEF;".to_string());
+            self.lines.push("".to_string());
+        }
+        if let Some(markAttachClassDef) = &gdef.markAttachClassDef {
+            let classes: BTreeSet<uint16> = markAttachClassDef.classes.values().copied().collect();
+            for class in classes {
+                let glyphs = markAttachClassDef.glyphs_in_class(class);
+                self.lines.push(format!(
+                    "@GDEF_MarkAttachClass_{:} = {:};",
+                    class,
+                    self.glyph_class(&glyphs)
+                ));
+            }
+        }
+        for (i, set) in gdef.markGlyphSets.iter().enumerate() {
+            self.lines.push(format!(
+                "@GDEF_MarkFilteringSet_{:} = {:};",
+                i,
+                self.glyph_class(&set.glyphs)
+            ));
+        }
+        if gdef.markAttachClassDef.is_some() || !gdef.markGlyphSets.is_empty() {
+            self.lines.push("".to_string());
+        }
+    }
+
+    fn mark_classes(&mut self, gpos: &gpos) {
+        let mut any = false;
+        for (i, lookup) in gpos.lookups.iter().enumerate() {
+            for (j, subtable) in lookup.subtables.iter().enumerate() {
+                let marks = match subtable {
+                    Positioning::MarkToBase(p) | Positioning::MarkToMark(p) => &p.marks,
+                    Positioning::MarkToLigature(p) => &p.marks,
+                    _ => continue,
+                };
+                for (glyph, (class, mark_anchor)) in marks {
+                    self.lines.push(format!(
+                        "markClass {:} {:} {:};",
+                        self.glyph(*glyph),
+                        anchor(&Some(*mark_anchor)),
+                        mark_class_name(i, j, *class as usize)
+                    ));
+                    any = true;
+                }
+            }
+        }
+        if any {
+            self.lines.push("".to_string());
+        }
+    }
+
+    fn lookup_flags<T>(&self, lookup: &Lookup<T>) -> Option<String> {
+        let flags = lookup.lookupFlag;
+        let mut parts = vec![];
+        for (flag, name) in &[
+            (LookupFlags::RIGHT_TO_LEFT, "RightToLeft"),
+            (LookupFlags::IGNORE_BASE_GLYPHS, "IgnoreBaseGlyphs"),
+            (LookupFlags::IGNORE_LIGATURES, "IgnoreLigatures"),
+            (LookupFlags::IGNORE_MARKS, "IgnoreMarks"),
+        ] {
+            if flags.contains(*flag) {
+                parts.push(name.to_string());
+            }
+        }
+        if flags.mark_attachment_type() > 0 {
+            parts.push(format!(
+                "MarkAttachmentType @GDEF_MarkAttachClass_{:}",
+                flags.mark_attachment_type()
+            ));
+        }
+        if let Some(set) = lookup.markFilteringSet {
+            parts.push(format!(
+                "UseMarkFilteringSet @GDEF_MarkFilteringSet_{:}",
+                set
+            ));
+        }
+        if parts.is_empty() {
+            None
+        } else {
+            Some(format!("lookupflag {:};", parts.join(" ")))
+        }
+    }
+
+    fn layout_table<T: FeaSubtable>(
+        &mut self,
+        table: &LayoutTable<T>,
+    ) -> otspec::error::Result<()> {
+        let mut done = vec![false; table.lookups.len()];
+        for i in 0..table.lookups.len() {
+            self.lookup_and_dependencies(&table.lookups, i, &mut done)?;
+        }
+        self.features(table);
+        Ok(())
+    }
+
+    /// Writes a lookup, after first writing any lookups it refers to
+    ///
+    /// Feature files can only refer to lookups which have already been
+    /// defined, so lookups called from contextual rules must come first.
+    fn lookup_and_dependencies<T: FeaSubtable>(
+        &mut self,
+        lookups: &[Lookup<T>],
+        index: usize,
+        done: &mut Vec<bool>,
+    ) -> otspec::error::Result<()> {
+        if done[index] {
+            return Ok(());
+        }
+        done[index] = true;
+        for subtable in &lookups[index].subtables {
+            for record in subtable.lookup_records() {
+                let dependency = record.lookupListIndex as usize;
+                if dependency < lookups.len() {
+                    self.lookup_and_dependencies(lookups, dependency, done)?;
+                }
+            }
+        }
+        self.lookup(index, &lookups[index])
+    }
+
+    fn lookup<T: FeaSubtable>(
+        &mut self,
+        index: usize,
+        lookup: &Lookup<T>,
+    ) -> otspec::error::Result<()> {
+        let name = lookup_name::<T>(index);
+        self.lines.push(format!("lookup {:} {{", name));
+        if let Some(flags) = self.lookup_flags(lookup) {
+            self.lines.push(format!("    {:}", flags));
+        }
+        let mut seen = BTreeSet::new();
+        for (j, subtable) in lookup.subtables.iter().enumerate() {
+            if j > 0 && subtable.needs_break() {
+                self.lines.push("    subtable;".to_string());
+            }
+            let rules = subtable.rules(self, lookup.lookupType, index, j, &mut seen)?;
+            for rule in rules {
+                self.lines.push(format!("    {:}", rule));
+            }
+        }
+        self.lines.push(format!("}} {:};", name));
+        self.lines.push("".to_string());
+        Ok(())
+    }
+
+    fn features<T: FeaSubtable>(&mut self, table: &LayoutTable<T>) {
+        let mut feature_tags: Vec<Tag> = vec![];
+        for feature in &table.features.features {
+            if !feature_tags.contains(&feature.featureTag) {
+                feature_tags.push(feature.featureTag);
+            }
+        }
+        for feature_tag in feature_tags {
+            let mut body = vec![];
+            for script_tag in sorted_scripts(&table.scripts) {
+                let script = &table.scripts.scripts[script_tag];
+                let mut script_written = false;
+                let languages = script
+                    .defaultLangSys
+                    .iter()
+                    .map(|l| (None, l))
+                    .chain(script.langSys.iter().map(|(t, l)| (Some(t), l)));
+                for (language_tag, langsys) in languages {
+                    let mut indices: Vec<(uint16, bool)> = langsys
+                        .requiredFeatureIndex
+                        .iter()
+                        .map(|i| (*i, true))
+                        .collect();
+                    indices.extend(langsys.featureIndices.iter().map(|i| (*i, false)));
+                    let mut statements = vec![];
+                    let mut required = false;
+                    for (index, is_required) in indices {
+                        let feature = match table.features.features.get(index as usize) {
+                            Some(f) if f.featureTag == feature_tag => f,
+                            _ => continue,
+                        };
+                        required |= is_required;
+                        for lookup in &feature.lookupListIndices {
+                            statements.push(format!(
+                                "    lookup {:};",
+                                lookup_name::<T>(*lookup as usize)
+                            ));
+                        }
+                    }
+                    if statements.is_empty() && !required {
+                        continue;
+                    }
+                    if !script_written {
+                        body.push(format!("    script {:};", tag_string(script_tag)));
+                        script_written = true;
+                    }
+                    let required = if required { " required" } else { "" };
+                    match language_tag {
+                        None => body.push(format!("    language dflt{:};", required)),
+                        Some(tag) => body.push(format!(
+                            "    language {:} exclude_dflt{:};",
+                            tag_string(tag),
+                            required
+                        )),
+                    }
+                    body.extend(statements);
+                }
+            }
+            if body.is_empty() {
+                continue;
+            }
+            let tag = tag_string(&feature_tag);
+            self.lines.push(format!("feature {:} {{", tag));
+            self.lines.extend(body);
+            self.lines.push(format!("}} {:};", tag));
+            self.lines.push("".to_string());
+        }
+    }
+
+    fn chained_rule<T: FeaSubtable>(&self, context: &ChainedSequenceContext) -> String {
+        let glyphs = |coverages: &[Coverage]| -> Vec<Vec<uint16>> {
+            coverages.iter().map(|c| c.glyphs.clone()).collect()
+        };
+        self.contextual_rule::<T>(
+            &glyphs(&context.backtrackCoverages),
+            &glyphs(&context.inputCoverages),
+            &glyphs(&context.lookaheadCoverages),
+            &context.seqLookupRecords,
+        )
+    }
+
+    /// Writes the rules of a glyph-based or class-based contextual subtable
+    ///
+    /// Rules which can never match, because one of their classes is empty,
+    /// are left out.
+    fn sequence_rules<T: FeaSubtable>(&self, context: &SequenceContext) -> Vec<String> {
+        let mut rules = vec![];
+        for (set, set_rules) in context.ruleSets.iter().enumerate() {
+            let first = context.first_glyphs(set);
+            for rule in set_rules {
+                let sequence =
+                    |values: &[uint16], classDef: Option<&ClassDef>| -> Vec<Vec<uint16>> {
+                        values
+                            .iter()
+                            .map(|&value| match classDef {
+                                Some(classDef) => self.class_glyphs(classDef, value),
+                                None => vec![value],
+                            })
+                            .collect()
+                    };
+                let classDefs = context.classDefs.as_ref();
+                let backtrack = sequence(&rule.backtrack, classDefs.map(|c| &c.backtrack));
+                let mut input = vec![first.clone()];
+                input.extend(sequence(&rule.input, classDefs.map(|c| &c.input)));
+                let lookahead = sequence(&rule.lookahead, classDefs.map(|c| &c.lookahead));
+                if backtrack
+                    .iter()
+                    .chain(&input)
+                    .chain(&lookahead)
+                    .any(|glyphs| glyphs.is_empty())
+                {
+                    continue;
+                }
+                rules.push(self.contextual_rule::<T>(
+                    &backtrack,
+                    &input,
+                    &lookahead,
+                    &rule.seqLookupRecords,
+                ));
+            }
+        }
+        rules
+    }
+
+    /// Writes a contextual rule, given the glyphs matched at each position
+    /// (the backtrack sequence in reverse order, nearest glyph first)
+    fn contextual_rule<T: FeaSubtable>(
+        &self,
+        backtrack: &[Vec<uint16>],
+        input: &[Vec<uint16>],
+        lookahead: &[Vec<uint16>],
+        seqLookupRecords: &[SequenceLookupRecord],
+    ) -> String {
+        let mut parts = vec![];
+        for glyphs in backtrack.iter().rev() {
+            parts.push(self.glyph_or_class(glyphs));
+        }
+        for (i, glyphs) in input.iter().enumerate() {
+            let mut part = format!("{:}'", self.glyph_or_class(glyphs));
+            for record in seqLookupRecords {
+                if record.sequenceIndex as usize == i {
+                    part.push_str(&format!(
+                        " lookup {:}",
+                        lookup_name::<T>(record.lookupListIndex as usize)
+                    ));
+                }
+            }
+            parts.push(part);
+        }
+        for glyphs in lookahead {
+            parts.push(self.glyph_or_class(glyphs));
+        }
+        let ignore = if seqLookupRecords.is_empty() {
+            "ignore "
+        } else {
+            ""
+        };
+        format!("{:}{:} {:};", ignore, T::KEYWORD, parts.join(" "))
+    }
+}
+
+fn lookup_name<T: FeaSubtable>(index: usize) -> String {
+    format!("{:}_{:}", T::TABLE, index)
+}
+
+fn unsupported<T: FeaSubtable>(lookupType: uint16, format: uint16) -> otspec::error::Error {
+    otspec::error::Error::Message(format!(
+        "Cannot decompile unsupported {:} subtable (lookup type {:}, format {:})",
+        T::TABLE,
+        lookupType,
+        format
+    ))
+}
+
+/// A lookup subtable which can be written as feature file rules
+trait FeaSubtable: Sized {
+    /// The tag of the table containing these subtables
+    const TABLE: &'static str;
+    /// The keyword used to introduce rules (`sub` or `pos`)
+    const KEYWORD: &'static str;
+
+    /// Returns the lookups called by this subtable, if it is contextual
+    fn lookup_records(&self) -> Vec<&SequenceLookupRecord>;
+
+    /// Whether this subtable must be kept apart from the previous one with
+    /// an explicit `subtable;` statement
+    ///
+    /// For other subtable types, the subtables of a lookup are merged, and
+    /// rules for inputs already handled by an earlier subtable are dropped,
+    /// since they could never have been reached.
+    fn needs_break(&self) -> bool;
+
+    /// Returns the feature file rules for this subtable
+    fn rules(
+        &self,
+        writer: &FeaWriter,
+        lookupType: uint16,
+        lookup_index: usize,
+        subtable_index: usize,
+        seen: &mut BTreeSet<Vec<uint16>>,
+    ) -> otspec::error::Result<Vec<String>>;
+}
+
+impl FeaSubtable for Substitution {
+    const TABLE: &'static str = "GSUB";
+    const KEYWORD: &'static str = "sub";
+
+    fn lookup_records(&self) -> Vec<&SequenceLookupRecord> {
+        match self {
+            Substitution::ContextRules(c) | Substitution::ChainedContextRules(c) => {
+                rule_lookup_records(c)
+            }
+            Substitution::Context(c) | Substitution::ChainedContext(c) => {
+                c.seqLookupRecords.iter().collect()
+            }
+            _ => vec![],
+        }
+    }
+
+    fn needs_break(&self) -> bool {
+        matches!(
+            self,
+            Substitution::ContextRules(_)
+                | Substitution::Context(_)
+                | Substitution::ChainedContextRules(_)
+                | Substitution::ChainedContext(_)
+        )
+    }
+
+    fn rules(
+        &self,
+        writer: &FeaWriter,
+        lookupType: uint16,
+        _lookup_index: usize,
+        _subtable_index: usize,
+        seen: &mut BTreeSet<Vec<uint16>>,
+    ) -> otspec::error::Result<Vec<String>> {
+        let mut rules = vec![];
+        match self {
+            Substitution::Single(s) => {
+                for (from, to) in &s.mapping {
+                    if seen.insert(vec![*from]) {
+                        rules.push(format!(
+                            "sub {:} by {:};",
+                            writer.glyph(*from),
+                            writer.glyph(*to)
+                        ));
+                    }
+                }
+            }
+            Substitution::Multiple(s) => {
+                for (from, to) in &s.mapping {
+                    if seen.insert(vec![*from]) {
+                        let to = if to.is_empty() {
+                            "NULL".to_string()
+                        } else {
+                            to.iter()
+                                .map(|g| writer.glyph(*g))
+                                .collect::<Vec<_>>()
+                                .join(" ")
+                        };
+                        rules.push(format!("sub {:} by {:};", writer.glyph(*from), to));
+                    }
+                }
+            }
+            Substitution::Alternate(s) => {
+                for (from, to) in &s.mapping {
+                    if seen.insert(vec![*from]) {
+                        rules.push(format!(
+                            "sub {:} from {:};",
+                            writer.glyph(*from),
+                            writer.glyph_class(to)
+                        ));
+                    }
+                }
+            }
+            Substitution::Ligature(s) => {
+                for (from, to) in &s.ligatures {
+                    if seen.insert(from.clone()) {
+                        let from: Vec<String> = from.iter().map(|g| writer.glyph(*g)).collect();
+                        rules.push(format!(
+                            "sub {:} by {:};",
+                            from.join(" "),
+                            writer.glyph(*to)
+                        ));
+                    }
+                }
+            }
+            Substitution::ContextRules(c) | Substitution::ChainedContextRules(c) => {
+                rules.extend(writer.sequence_rules::<Self>(c))
+            }
+            Substitution::Context(c) | Substitution::ChainedContext(c) => {
+                rules.push(writer.chained_rule::<Self>(c))
+            }
+            Substitution::Unsupported { format } => {
+                return Err(unsupported::<Self>(lookupType, *format))
+            }
+        }
+        Ok(rules)
+    }
+}
+
+impl FeaSubtable for Positioning {
+    const TABLE: &'static str = "GPOS";
+    const KEYWORD: &'static str = "pos";
+
+    fn lookup_records(&self) -> Vec<&SequenceLookupRecord> {
+        match self {
+            Positioning::ContextRules(c) | Positioning::ChainedContextRules(c) => {
+                rule_lookup_records(c)
+            }
+            Positioning::Context(c) | Positioning::ChainedContext(c) => {
+                c.seqLookupRecords.iter().collect()
+            }
+            _ => vec![],
+        }
+    }
+
+    fn needs_break(&self) -> bool {
+        matches!(
+            self,
+            Positioning::PairGlyphs(_)
+                | Positioning::PairClasses(_)
+                | Positioning::ContextRules(_)
+                | Positioning::Context(_)
+                | Positioning::ChainedContextRules(_)
+                | Positioning::ChainedContext(_)
+        )
+    }
+
+    fn rules(
+        &self,
+        writer: &FeaWriter,
+        lookupType: uint16,
+        lookup_index: usize,
+        subtable_index: usize,
+        seen: &mut BTreeSet<Vec<uint16>>,
+    ) -> otspec::error::Result<Vec<String>> {
+        if self.has_devices() {
+            return Err(otspec::error::Error::Message(format!(
+                "Cannot decompile GPOS device tables (lookup {:}, subtable {:})",
+                lookup_index, subtable_index
+            )));
+        }
+        let mut rules = vec![];
+        let mark_class = |class: usize| mark_class_name(lookup_index, subtable_index, class);
+        let anchors_to_marks = |anchors: &[Option<Anchor>]| -> Vec<String> {
+            anchors
+                .iter()
+                .enumerate()
+                .filter(|(_, a)| a.is_some())
+                .map(|(class, a)| format!("{:} mark {:}", anchor(a), mark_class(class)))
+                .collect()
+        };
+        match self {
+            Positioning::Single(s) => {
+                for (glyph, vr) in &s.mapping {
+                    if seen.insert(vec![*glyph]) {
+                        rules.push(format!(
+                            "pos {:} {:};",
+                            writer.glyph(*glyph),
+                            value_record(vr)
+                        ));
+                    }
+                }
+            }
+            Positioning::PairGlyphs(p) => {
+                for ((first, second), (vr1, vr2)) in &p.mapping {
+                    rules.push(pair_rule(
+                        writer.glyph(*first),
+                        writer.glyph(*second),
+                        vr1,
+                        vr2,
+                    ));
+                }
+            }
+            Positioning::PairClasses(p) => {
+                let covered: BTreeSet<uint16> = p.coverage.glyphs.iter().copied().collect();
+                for (class1, records) in p.class1Records.iter().enumerate() {
+                    let firsts: Vec<uint16> = if class1 == 0 {
+                        p.coverage
+                            .glyphs
+                            .iter()
+                            .filter(|g| !p.classDef1.classes.contains_key(g))
+                            .copied()
+                            .collect()
+                    } else {
+                        p.classDef1
+                            .glyphs_in_class(class1 as uint16)
+                            .into_iter()
+                            .filter(|g| covered.contains(g))
+                            .collect()
+                    };
+                    if firsts.is_empty() {
+                        continue;
+                    }
+                    for (class2, (vr1, vr2)) in records.iter().enumerate() {
+                        let seconds = writer.class_glyphs(&p.classDef2, class2 as uint16);
+                        if seconds.is_empty() || (vr1.is_empty() && vr2.is_empty()) {
+                            continue;
+                        }
+                        rules.push(pair_rule(
+                            writer.glyph_class(&firsts),
+                            writer.glyph_class(&seconds),
+                            vr1,
+                            vr2,
+                        ));
+                    }
+                }
+            }
+            Positioning::Cursive(c) => {
+                for (glyph, (entry, exit)) in &c.mapping {
+                    rules.push(format!(
+                        "pos cursive {:} {:} {:};",
+                        writer.glyph(*glyph),
+                        anchor(entry),
+                        anchor(exit)
+                    ));
+                }
+            }
+            Positioning::MarkToBase(m) | Positioning::MarkToMark(m) => {
+                let keyword = if lookupType == 6 { "mark" } else { "base" };
+                for (glyph, anchors) in &m.bases {
+                    let marks = anchors_to_marks(anchors);
+                    if !marks.is_empty() {
+                        rules.push(format!(
+                            "pos {:} {:} {:};",
+                            keyword,
+                            writer.glyph(*glyph),
+                            marks.join(" ")
+                        ));
+                    }
+                }
+            }
+            Positioning::MarkToLigature(m) => {
+                for (glyph, components) in &m.ligatures {
+                    let components: Vec<String> = components
+                        .iter()
+                        .map(|anchors| {
+                            let marks = anchors_to_marks(anchors);
+                            if marks.is_empty() {
+                                anchor(&None)
+                            } else {
+                                marks.join(" ")
+                            }
+                        })
+                        .collect();
+                    rules.push(format!(
+                        "pos ligature {:} {:};",
+                        writer.glyph(*glyph),
+                        components.join(" ligComponent ")
+                    ));
+                }
+            }
+            Positioning::ContextRules(c) | Positioning::ChainedContextRules(c) => {
+                rules.extend(writer.sequence_rules::<Self>(c))
+            }
+            Positioning::Context(c) | Positioning::ChainedContext(c) => {
+                rules.push(writer.chained_rule::<Self>(c))
+            }
+            Positioning::Unsupported { format } => {
+                return Err(unsupported::<Self>(lookupType, *format))
+            }
+        }
+        Ok(rules)
+    }
+}
+
+fn rule_lookup_records(context: &SequenceContext) -> Vec<&SequenceLookupRecord> {
+    context
+        .ruleSets
+        .iter()
+        .flatten()
+        .flat_map(|rule| &rule.seqLookupRecords)
+        .collect()
+}
+
+fn pair_rule(first: String, second: String, vr1: &ValueRecord, vr2: &ValueRecord) -> String {
+    if vr2.is_empty() {
+        format!("pos {:} {:} {:};", first, second, value_record(vr1))
+    } else {
+        let vr1 = if vr1.is_empty() {
+            "<NULL>".to_string()
+        } else {
+            value_record(vr1)
+        };
+        format!(
+            "pos {:} {:} {:} {:};",
+            first,
+            vr1,
+            second,
+            value_record(vr2)
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::fea;
+    use crate::font::{Font, SfntVersion, Table};
+    use crate::gpos::{gpos, PairPosFormat2, Positioning, ValueRecord};
+    use crate::gsub::{gsub, SingleSubst, Substitution};
+    use crate::layout::{
+        ClassDef, Coverage, Lookup, LookupFlags, SequenceClassDefs, SequenceContext,
+        SequenceLookupRecord, SequenceRule,
+    };
+    use crate::post::post;
+    use std::iter::FromIterator;
+
+    fn font_with_names(names: &[&str]) -> Font {
+        let mut font = Font::new(SfntVersion::TrueType);
+        font.tables.insert(
+            *b"post",
+            Table::Post(post::new(
+                2.0,
+                0.0,
+                0,
+                0,
+                false,
+                Some(names.iter().map(|n| n.to_string()).collect()),
+            )),
+        );
+        font
+    }
+
+    const BINARY_GPOS: [u8; 242] = [
+        0x00, 0x01, 0x00, 0x00, 0x00, 0x0A, 0x00, 0x40, 0x00, 0x5C, 0x00, 0x02, 0x44, 0x46, 0x4C,
+        0x54, 0x00, 0x0E, 0x6C, 0x61, 0x74, 0x6E, 0x00, 0x1A, 0x00, 0x04, 0x00, 0x00, 0x00, 0x00,
+        0xFF, 0xFF, 0x00, 0x01, 0x00, 0x00, 0x00, 0x0A, 0x00, 0x01, 0x54, 0x52, 0x4B, 0x20, 0x00,
+        0x14, 0x00, 0x00, 0xFF, 0xFF, 0x00, 0x02, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x01,
+        0x00, 0x01, 0x00, 0x00, 0x00, 0x02, 0x6B, 0x65, 0x72, 0x6E, 0x00, 0x0E, 0x6D, 0x61, 0x72,
+        0x6B, 0x00, 0x16, 0x00, 0x00, 0x00, 0x02, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x01,
+        0x00, 0x02, 0x00, 0x03, 0x00, 0x08, 0x00, 0x28, 0x00, 0x60, 0x00, 0x02, 0x00, 0x00, 0x00,
+        0x01, 0x00, 0x08, 0x00, 0x01, 0x00, 0x0C, 0x00, 0x04, 0x00, 0x00, 0x00, 0x01, 0x00, 0x12,
+        0x00, 0x01, 0x00, 0x01, 0x00, 0x01, 0x00, 0x01, 0x00, 0x02, 0xFF, 0xCE, 0x00, 0x02, 0x00,
+        0x00, 0x00, 0x01, 0x00, 0x08, 0x00, 0x02, 0x00, 0x18, 0x00, 0x04, 0x00, 0x00, 0x00, 0x20,
+        0x00, 0x28, 0x00, 0x02, 0x00, 0x02, 0x00, 0x00, 0xFF, 0xF6, 0x00, 0x00, 0xFF, 0xEC, 0x00,
+        0x01, 0x00, 0x02, 0x00, 0x01, 0x00, 0x03, 0x00, 0x01, 0x00, 0x03, 0x00, 0x01, 0x00, 0x01,
+        0x00, 0x01, 0x00, 0x04, 0x00, 0x01, 0x00, 0x01, 0x00, 0x04, 0x00, 0x00, 0x00, 0x01, 0x00,
+        0x08, 0x00, 0x01, 0x00, 0x0C, 0x00, 0x12, 0x00, 0x01, 0x00, 0x18, 0x00, 0x24, 0x00, 0x01,
+        0x00, 0x01, 0x00, 0x07, 0x00, 0x01, 0x00, 0x01, 0x00, 0x01, 0x00, 0x01, 0x00, 0x00, 0x00,
+        0x06, 0x00, 0x01, 0x00, 0xFA, 0x01, 0xF4, 0x00, 0x01, 0x00, 0x04, 0x00, 0x01, 0x00, 0xFA,
+        0x01, 0xC2,
+    ];
+
+    const EXPECTED_FEA: &str = r#"languagesystem DFLT dflt;
+languagesystem latn dflt;
+languagesystem latn TRK;
+
+markClass acutecomb <anchor 250 500> @MC_2_0_0;
+
+lookup GSUB_1 {
+    sub a by b;
+} GSUB_1;
+
+lookup GSUB_0 {
+    sub f a' lookup GSUB_1;
+} GSUB_0;
+
+lookup GSUB_2 {
+    lookupflag IgnoreMarks;
+    sub f f i by ffi;
+    sub f i by fi;
+} GSUB_2;
+
+feature calt {
+    script DFLT;
+    language dflt;
+    lookup GSUB_0;
+} calt;
+
+feature liga {
+    script DFLT;
+    language dflt;
+    lookup GSUB_2;
+} liga;
+
+lookup GPOS_0 {
+    pos a b -50;
+} GPOS_0;
+
+lookup GPOS_1 {
+    pos [a] [i] -10;
+    pos [f] [i] -20;
+} GPOS_1;
+
+lookup GPOS_2 {
+    pos base a <anchor 250 450> mark @MC_2_0_0;
+} GPOS_2;
+
+feature kern {
+    script DFLT;
+    language dflt;
+    lookup GPOS_0;
+    lookup GPOS_1;
+    script latn;
+    language dflt;
+    lookup GPOS_0;
+    lookup GPOS_1;
+    language TRK exclude_dflt;
+    lookup GPOS_0;
+    lookup GPOS_1;
+} kern;
+
+feature mark {
+    script latn;
+    language dflt;
+    lookup GPOS_2;
+    language TRK exclude_dflt required;
+    lookup GPOS_2;
+} mark;
+"#;
+
+    #[test]
+    fn fea_decompile() {
+        let mut font = font_with_names(&[".notdef", "a", "b", "f", "i", "fi", "ffi", "acutecomb"]);
+        let binary_gsub = [
+            0x00, 0x01, 0x00, 0x00, 0x00, 0x0A, 0x00, 0x20, 0x00, 0x3A, 0x00, 0x01, 0x44, 0x46,
+            0x4C, 0x54, 0x00, 0x08, 0x00, 0x04, 0x00, 0x00, 0x00, 0x00, 0xFF, 0xFF, 0x00, 0x02,
+            0x00, 0x00, 0x00, 0x01, 0x00, 0x02, 0x63, 0x61, 0x6C, 0x74, 0x00, 0x0E, 0x6C, 0x69,
+            0x67, 0x61, 0x00, 0x14, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x01,
+            0x00, 0x02, 0x00, 0x03, 0x00, 0x08, 0x00, 0x2E, 0x00, 0x42, 0x00, 0x06, 0x00, 0x00,
+            0x00, 0x01, 0x00, 0x08, 0x00, 0x03, 0x00, 0x01, 0x00, 0x12, 0x00, 0x01, 0x00, 0x18,
+            0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x01, 0x00, 0x01, 0x00, 0x01, 0x00, 0x03,
+            0x00, 0x01, 0x00, 0x01, 0x00, 0x01, 0x00, 0x01, 0x00, 0x00, 0x00, 0x01, 0x00, 0x08,
+            0x00, 0x01, 0x00, 0x06, 0x00, 0x01, 0x00, 0x01, 0x00, 0x01, 0x00, 0x01, 0x00, 0x04,
+            0x00, 0x08, 0x00, 0x01, 0x00, 0x08, 0x00, 0x01, 0x00, 0x08, 0x00, 0x01, 0x00, 0x0E,
+            0x00, 0x01, 0x00, 0x01, 0x00, 0x03, 0x00, 0x02, 0x00, 0x06, 0x00, 0x0E, 0x00, 0x06,
+            0x00, 0x03, 0x00, 0x03, 0x00, 0x04, 0x00, 0x05, 0x00, 0x02, 0x00, 0x04,
+        ];
+        font.tables
+            .insert(*b"GSUB", Table::Unknown(binary_gsub.to_vec()));
+        font.tables
+            .insert(*b"GPOS", Table::Unknown(BINARY_GPOS.to_vec()));
+        let fea = fea::decompile(&mut font).unwrap();
+        assert_eq!(fea, EXPECTED_FEA);
+    }
+
+    #[test]
+    fn fea_decompile_context_rules() {
+        let mut font = font_with_names(&[".notdef", "a", "b", "f", "i"]);
+        let lookup = |lookupType, subtable| Lookup {
+            lookupType,
+            lookupFlag: LookupFlags::empty(),
+            subtables: vec![subtable],
+            markFilteringSet: None,
+        };
+        let classes = SequenceContext {
+            coverage: Coverage { glyphs: vec![1, 3] },
+            classDefs: Some(SequenceClassDefs {
+                input: ClassDef {
+                    classes: FromIterator::from_iter(vec![(1, 1), (3, 1), (4, 2)]),
+                },
+                ..Default::default()
+            }),
+            ruleSets: vec![
+                vec![],
+                vec![SequenceRule {
+                    input: vec![2, 0],
+                    seqLookupRecords: vec![SequenceLookupRecord {
+                        sequenceIndex: 0,
+                        lookupListIndex: 0,
+                    }],
+                    ..Default::default()
+                }],
+            ],
+        };
+        let glyphs = SequenceContext {
+            coverage: Coverage { glyphs: vec![4] },
+            classDefs: None,
+            ruleSets: vec![vec![SequenceRule {
+                backtrack: vec![3],
+                input: vec![],
+                lookahead: vec![],
+                seqLookupRecords: vec![],
+            }]],
+        };
+        let table = gsub {
+            lookups: vec![
+                lookup(
+                    1,
+                    Substitution::Single(SingleSubst {
+                        mapping: FromIterator::from_iter(vec![(1, 2)]),
+                    }),
+                ),
+                lookup(5, Substitution::ContextRules(classes)),
+                lookup(6, Substitution::ChainedContextRules(glyphs)),
+            ],
+            ..Default::default()
+        };
+        font.tables
+            .insert(*b"GSUB", Table::Unknown(table.to_bytes().unwrap()));
+        let fea = fea::decompile(&mut font).unwrap();
+        assert_eq!(
+            fea,
+            r#"lookup GSUB_0 {
+    sub a by b;
+} GSUB_0;
+
+lookup GSUB_1 {
+    sub [a f]' lookup GSUB_0 i' [.notdef b]';
+} GSUB_1;
+
+lookup GSUB_2 {
+    ignore sub f i';
+} GSUB_2;
+"#
+        );
+    }
+
+    #[test]
+    fn fea_decompile_pair_class_zero() {
+        let mut font = font_with_names(&[".notdef", "a", "b", "f", "i"]);
+        let kern = |xAdvance| ValueRecord {
+            xAdvance: Some(xAdvance),
+            ..Default::default()
+        };
+        let pairs = PairPosFormat2 {
+            coverage: Coverage { glyphs: vec![1, 3] },
+            classDef1: ClassDef {
+                classes: FromIterator::from_iter(vec![(3, 1)]),
+            },
+            classDef2: ClassDef {
+                classes: FromIterator::from_iter(vec![(4, 1)]),
+            },
+            class1Records: vec![
+                vec![
+                    (kern(-30), ValueRecord::default()),
+                    (kern(-10), ValueRecord::default()),
+                ],
+                vec![
+                    (kern(0), ValueRecord::default()),
+                    (kern(-20), ValueRecord::default()),
+                ],
+            ],
+        };
+        let table = gpos {
+            lookups: vec![Lookup {
+                lookupType: 2,
+                lookupFlag: LookupFlags::empty(),
+                subtables: vec![Positioning::PairClasses(pairs)],
+                markFilteringSet: None,
+            }],
+            ..Default::default()
+        };
+        font.tables
+            .insert(*b"GPOS", Table::Unknown(table.to_bytes().unwrap()));
+        let fea = fea::decompile(&mut font).unwrap();
+        // Class zero of the second glyphs holds every glyph not in another class
+        assert_eq!(
+            fea,
+            r#"lookup GPOS_0 {
+    pos [a] [.notdef a b f] -30;
+    pos [a] [i] -10;
+    pos [f] [i] -20;
+} GPOS_0;
+"#
+        );
+    }
+
+    #[test]
+    fn fea_decompile_devices() {
+        let mut font = font_with_names(&[".notdef", "a"]);
+        let binary_gpos = [
+            0x00, 0x01, 0x00, 0x00, 0x00, 0x0A, 0x00, 0x0C, 0x00, 0x0E, // header
+            0x00, 0x00, // script list
+            0x00, 0x00, // feature list
+            0x00, 0x01, 0x00, 0x04, // lookup list
+            0x00, 0x01, 0x00, 0x00, 0x00, 0x01, 0x00, 0x08, // single adjustment lookup
+            0x00, 0x01, 0x00, 0x0A, 0x00, 0x11, 0x00, 0x0A, 0x00, 0x10, // subtable
+            0x00, 0x01, 0x00, 0x01, 0x00, 0x01, // coverage
+            0x00, 0x0B, 0x00, 0x0B, 0x00, 0x01, 0x10, 0x00, // device table
+        ];
+        font.tables
+            .insert(*b"GPOS", Table::Unknown(binary_gpos.to_vec()));
+        let error = fea::decompile(&mut font).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Cannot decompile GPOS device tables (lookup 0, subtable 0)"
+        );
+    }
+
+    #[test]
+    fn fea_decompile_unsupported() {
+        let mut font = font_with_names(&[".notdef", "a"]);
+        let binary_gsub = [
+            0x00, 0x01, 0x00, 0x00, 0x00, 0x0A, 0x00, 0x0C, 0x00, 0x0E, // header
+            0x00, 0x00, // script list
+            0x00, 0x00, // feature list
+            0x00, 0x01, 0x00, 0x04, // lookup list
+            0x00, 0x08, 0x00, 0x00, 0x00, 0x01, 0x00, 0x08, // reverse chaining lookup
+            0x00, 0x01, 0x00, 0x00, // subtable
+        ];
+        font.tables
+            .insert(*b"GSUB", Table::Unknown(binary_gsub.to_vec()));
+        let error = fea::decompile(&mut font).unwrap_err();
+        assert!(error.to_string().contains("(lookup type 8, format 1)"));
+    }
+}
@@ -12,9 +12,13 @@ use crate::maxp::maxp;
 use crate::name::name;
 use crate::os2::os2;
 use crate::post::post;
+use crate::woff::{self, Woff};
+use crate::woff2;
+use otspec::de::CountedDeserializer;
 use otspec::error::Error as OTSpecError;
 use otspec::types::*;
-use otspec::{deserialize_visitor, read_field};
+use otspec::{deserialize_visitor, read_field, read_field_counted, read_remainder};
+use serde::de::DeserializeSeed;
 use serde::de::SeqAccess;
 use serde::de::Visitor;
 use serde::{Deserialize, Deserializer};
@@ -22,9 +26,67 @@ use serde::{Serialize, Serializer};
 use std::cmp;
 use std::collections::BTreeMap;
 use std::convert::{TryFrom, TryInto};
-use std::io::Write;
+use std::fmt;
+use std::io::{Read, Write};
 use std::num::Wrapping;
 
+mod collection;
+mod diff;
+mod font_ref;
+mod human_readable;
+mod verify;
+pub(crate) use collection::read_collection;
+pub use collection::{load_collection, FontCollection};
+pub use diff::{diff, Difference, NameLanguage, NameRecordKey};
+pub use font_ref::FontRef;
+use human_readable::FontDef;
+pub use verify::{verify, VerificationIssue};
+
+/// Errors which can occur when loading, compiling or saving a font
+#[derive(Debug)]
+pub enum FontError {
+    /// A table could not be serialized or deserialized
+    Table(OTSpecError),
+    /// A table needed by the operation was not present in the font
+    MissingTable(Tag),
+    /// Reading or writing the font data failed
+    Io(std::io::Error),
+}
+
+impl fmt::Display for FontError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FontError::Table(e) => write!(f, "{}", e),
+            FontError::MissingTable(tag) => {
+                write!(f, "font has no {} table", String::from_utf8_lossy(tag))
+            }
+            FontError::Io(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for FontError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            FontError::Table(e) => Some(e),
+            FontError::MissingTable(_) => None,
+            FontError::Io(e) => Some(e),
+        }
+    }
+}
+
+impl From<OTSpecError> for FontError {
+    fn from(e: OTSpecError) -> Self {
+        FontError::Table(e)
+    }
+}
+
+impl From<std::io::Error> for FontError {
+    fn from(e: std::io::Error) -> Self {
+        FontError::Io(e)
+    }
+}
+
 /// A generic container for a font table, either known (deserialized) or unknown (binary)
 #[derive(Debug, Serialize, PartialEq)]
 #[serde(untagged)]
@@ -101,23 +163,38 @@ impl TryFrom<u32> for SfntVersion {
 
 /// Low-level structure used for serializing/deserializing entries in the table directory
 #[derive(Serialize, Deserialize, Debug)]
-struct TableRecord {
-    tag: Tag,
-    checksum: uint32,
-    offset: uint32,
-    length: uint32,
+pub(crate) struct TableRecord {
+    pub(crate) tag: Tag,
+    pub(crate) checksum: uint32,
+    pub(crate) offset: uint32,
+    pub(crate) length: uint32,
 }
 /// The header of the font's table directory
 #[derive(Deserialize)]
-struct TableHeader {
-    sfntVersion: u32,
-    numTables: u16,
+pub(crate) struct TableHeader {
+    pub(crate) sfntVersion: u32,
+    pub(crate) numTables: u16,
     _searchRange: u16,
     _entrySelector: u16,
     _rangeShift: u16,
 }
 
+/// Reads the header and table records at the start of a binary font
+pub(crate) fn read_table_directory(
+    data: &[u8],
+) -> otspec::error::Result<(TableHeader, Vec<TableRecord>)> {
+    let mut de = otspec::de::Deserializer::from_bytes(data);
+    let header = TableHeader::deserialize(&mut de)?;
+    let records = CountedDeserializer::with_len(header.numTables as usize).deserialize(&mut de)?;
+    Ok((header, records))
+}
+
 /// An OpenType font object
+///
+/// Fonts serialize to binary with `otspec`. Human-readable formats such as
+/// JSON instead get a map of the font's tables: call `fully_deserialize`
+/// first for every known table to be written out in full, rather than as
+/// hex-encoded binary data.
 #[derive(Debug)]
 pub struct Font {
     /// Font version (TrueType/OpenType)
@@ -238,6 +315,11 @@ impl Font {
         }
     }
 
+    /// Font version (TrueType/OpenType)
+    pub fn sfnt_version(&self) -> SfntVersion {
+        self.sfntVersion
+    }
+
     fn _table_needs_deserializing(&self, table: &Table) -> bool {
         // Also check here for known tables we can't deserialize.
         if let Table::Unknown(_binary) = table {
@@ -266,7 +348,9 @@ impl Font {
     /// is done and the appropriate Table enum entry is returned. If not, then
     /// a Table::Unknown is returned with the binary table data as a Vec<u8>.
     ///
-    /// Returns an Err if the table could not be correctly deserialized.
+    /// Returns an Err if the table could not be correctly deserialized. The
+    /// error records the table's tag, and where possible the path to the field
+    /// and the byte offset within the table at which deserialization failed.
     /// Returns Ok(None) if the table was not present within the font.
     /// Returns Ok(Some(Table)) if the table was present.
     pub fn get_table<'a>(&'a mut self, tag: &Tag) -> otspec::error::Result<Option<&'a mut Table>> {
@@ -281,7 +365,9 @@ impl Font {
         // println!("It was {:?}", table);
         if let Table::Unknown(binary) = table {
             // println!("Was binary, deserializing");
-            let newtable = self._deserialize(tag, binary)?;
+            let newtable = self
+                ._deserialize(tag, binary)
+                .map_err(|e| e.in_table(*tag))?;
             // println!("Inserting new table {:?}", newtable);
             self.tables.insert(*tag, newtable);
         }
@@ -293,43 +379,62 @@ impl Font {
     /// This is done in the correct order (as some tables can only be deserialized
     /// after certain others have been processed), so is a helpful way of getting
     /// the font into a useful state before working on it.
-    pub fn fully_deserialize(&mut self) {
+    ///
+    /// Returns an Err if any table could not be deserialized.
+    pub fn fully_deserialize(&mut self) -> Result<(), FontError> {
         // Order is important
-        self.get_table(b"head").unwrap();
-        self.get_table(b"maxp").unwrap();
+        self.get_table(b"head")?;
+        self.get_table(b"maxp")?;
         if self.tables.contains_key(b"glyf") {
-            self.get_table(b"loca").unwrap();
-            self.get_table(b"glyf").unwrap();
+            self.get_table(b"loca")?;
+            self.get_table(b"glyf")?;
         }
         let keys: Vec<Tag> = self.tables.keys().copied().collect();
         for t in keys {
-            self.get_table(&t).unwrap();
+            self.get_table(&t)?;
         }
+        Ok(())
     }
 
     /// Writes the font to the given file handle
-    pub fn save<T>(&mut self, file: &mut T)
+    pub fn save<T>(&mut self, file: &mut T) -> Result<(), FontError>
     where
         T: Write,
     {
-        self.compile_glyf_loca_maxp();
-        let serialized = ser::to_bytes(&self).unwrap();
-        file.write_all(&serialized).unwrap();
+        self.compile_glyf_loca_maxp()?;
+        let serialized = ser::to_bytes(&self)?;
+        file.write_all(&serialized)?;
+        Ok(())
+    }
+
+    /// Save a font as a WOFF 1.0 web font
+    ///
+    /// The WOFF version is taken from `head.fontRevision`. To set it
+    /// yourself, or to add metadata, use [`woff::encode`].
+    pub fn save_woff<T>(&mut self, file: &mut T) -> Result<(), FontError>
+    where
+        T: Write,
+    {
+        self.compile_glyf_loca_maxp()?;
+        let woff = Woff::new(ser::to_bytes(&self)?);
+        file.write_all(&woff::encode(&woff)?)?;
+        Ok(())
     }
 
     /// Total number of glyphs in the font, from the maxp table.
     ///
     /// Deserializes the maxp table if this is not already done.
-    pub fn num_glyphs(&mut self) -> u16 {
-        if self._numGlyphs.is_none() {
-            let maxp = self
-                .get_table(b"maxp")
-                .expect("Error deserializing maxp")
-                .expect("No maxp?")
-                .maxp_unchecked();
-            self._numGlyphs = Some(maxp.num_glyphs())
-        }
-        self._numGlyphs.unwrap()
+    pub fn num_glyphs(&mut self) -> Result<u16, FontError> {
+        if let Some(num_glyphs) = self._numGlyphs {
+            return Ok(num_glyphs);
+        }
+        let maxp = self
+            .get_table(b"maxp")?
+            .ok_or(FontError::MissingTable(*b"maxp"))?
+            .maxp_unchecked();
+        let num_glyphs = maxp.num_glyphs();
+        self._numGlyphs = Some(num_glyphs);
+        Ok(num_glyphs)
     }
 
     /// Compiles all dependent tables to binary.
@@ -338,19 +443,25 @@ impl Font {
     /// other tables to be passed in to the deserializer. We handle this by
     /// manually compiling those tables to binary here and replacing them with
     /// Table::Unknown. This is automatically called on `.save`.
-    pub fn compile_glyf_loca_maxp(&mut self) {
+    pub fn compile_glyf_loca_maxp(&mut self) -> Result<(), FontError> {
         let mut glyf_output: Vec<u8> = vec![];
         let mut loca_indices: Vec<u32> = vec![];
         let mut locaIs32bit = false;
-        let maybe_glyf = self.get_table(b"glyf").unwrap();
-        if maybe_glyf.is_none() {
-            println!("Warning: no glyf table");
-            return;
-        }
-        let glyf = maybe_glyf.unwrap().glyf_unchecked();
+        // glyf may already have been compiled, and can only be read after loca
+        self.get_table(b"loca")?;
+        let glyf = match self.get_table(b"glyf")? {
+            Some(glyf) => glyf.glyf_unchecked(),
+            None => {
+                log::warn!("No glyf table");
+                return Ok(());
+            }
+        };
         let glyf_count = glyf.glyphs.len();
         for g in &glyf.glyphs {
-            let cur_len: u32 = glyf_output.len().try_into().unwrap();
+            let cur_len: u32 = glyf_output
+                .len()
+                .try_into()
+                .map_err(|_| OTSpecError::OffsetOverflow.in_table(*b"loca"))?;
             if cur_len * 2 > (u16::MAX as u32) {
                 locaIs32bit = true;
             }
@@ -358,53 +469,65 @@ impl Font {
             if g.is_empty() {
                 continue;
             }
-            glyf_output.extend(otspec::ser::to_bytes(&g).unwrap());
+            glyf_output.extend(otspec::ser::to_bytes(&g).map_err(|e| e.in_table(*b"glyf"))?);
             // Add multiple-of-four padding
-            while glyf_output.len() % 4 != 0 {
-                glyf_output.push(0);
-            }
-        }
-        loca_indices.push(glyf_output.len().try_into().unwrap());
-
-        let maxp_table = self.get_table(b"maxp").unwrap().unwrap();
+            pad(&mut glyf_output);
+        }
+        loca_indices.push(
+            glyf_output
+                .len()
+                .try_into()
+                .map_err(|_| OTSpecError::OffsetOverflow.in_table(*b"loca"))?,
+        );
+
+        let maxp_table = self
+            .get_table(b"maxp")?
+            .ok_or(FontError::MissingTable(*b"maxp"))?;
         if let Table::Maxp(maxp) = maxp_table {
             maxp.set_num_glyphs(glyf_count as u16);
         }
+        self._numGlyphs = Some(glyf_count as u16);
 
-        let head_table = self.get_table(b"head").unwrap().unwrap();
+        let head_table = self
+            .get_table(b"head")?
+            .ok_or(FontError::MissingTable(*b"head"))?;
         if let Table::Head(head) = head_table {
             head.indexToLocFormat = if locaIs32bit { 1 } else { 0 };
         }
 
         self.tables.insert(*b"glyf", Table::Unknown(glyf_output));
-        let loca_output: Vec<u8>;
-        if locaIs32bit {
-            loca_output = otspec::ser::to_bytes(&loca_indices).unwrap();
+        let loca_output: Vec<u8> = if locaIs32bit {
+            otspec::ser::to_bytes(&loca_indices)?
         } else {
             let converted: Vec<u16> = loca_indices.iter().map(|x| (*x / 2_u32) as u16).collect();
-            loca_output = otspec::ser::to_bytes(&converted).unwrap();
-        }
+            otspec::ser::to_bytes(&converted)?
+        };
         self.tables.insert(*b"loca", Table::Unknown(loca_output));
+        Ok(())
     }
 }
 
-use std::error::Error;
-use std::io::Read;
-
 /// Loads a binary font from the given filehandle.
-pub fn load<T>(mut file: T) -> Result<Font, Box<dyn Error>>
+///
+/// The `head` and `loca` tables are deserialized straight away; other tables
+/// are deserialized when they are first requested with [`Font::get_table`].
+/// To avoid copying the font data, see [`FontRef`]. WOFF 1.0 and WOFF2
+/// files are recognised by their signature and decoded.
+pub fn load<T>(mut file: T) -> Result<Font, FontError>
 where
     T: Read,
 {
     let mut buffer = Vec::new();
     file.read_to_end(&mut buffer)?;
-    otspec::de::from_bytes(&buffer)
-        .map_err(|e| e.into())
-        .map(|mut f: Font| {
-            let _ = f.get_table(b"head");
-            let _ = f.get_table(b"loca");
-            f
-        })
+    if buffer.starts_with(b"wOFF") {
+        buffer = woff::decode(&buffer)?.sfnt;
+    } else if buffer.starts_with(b"wOF2") {
+        buffer = woff2::decode(&buffer)?.sfnt;
+    }
+    let mut font = FontRef::new(&buffer)?;
+    font.get_table(b"head")?;
+    font.get_table(b"loca")?;
+    Ok(font.into_font())
 }
 
 impl PartialEq for Font {
@@ -421,7 +544,7 @@ impl PartialEq for Font {
     }
 }
 
-fn checksum(x: &[u8]) -> u32 {
+pub(crate) fn checksum(x: &[u8]) -> u32 {
     let mut sum = Wrapping(0u32);
     for slice in x.chunks(4) {
         if slice.len() == 4 {
@@ -438,6 +561,36 @@ fn checksum(x: &[u8]) -> u32 {
     sum.0
 }
 
+/// Converts a length or offset to a `uint32`, failing if it overflows
+pub(crate) fn to_uint32(value: usize) -> Result<uint32, FontError> {
+    uint32::try_from(value).map_err(|_| OTSpecError::OffsetOverflow.into())
+}
+
+/// Pads a vector to a four-byte boundary
+pub(crate) fn pad(output: &mut Vec<u8>) {
+    output.resize((output.len() + 3) & !3, 0);
+}
+
+/// Returns a block of the file, checking that it lies within it
+pub(crate) fn block<'a>(
+    data: &'a [u8],
+    offset: uint32,
+    length: uint32,
+    what: &str,
+) -> Result<&'a [u8], FontError> {
+    let start = offset as usize;
+    start
+        .checked_add(length as usize)
+        .and_then(|end| data.get(start..end))
+        .ok_or_else(|| {
+            OTSpecError::Message(format!(
+                "{} at offset {} with length {} lies outside the file",
+                what, offset, length
+            ))
+            .into()
+        })
+}
+
 pub fn get_search_range(n: u16, itemsize: u16) -> (u16, u16, u16) {
     let mut max_pow2: u16 = 0;
     while 1u16 << (max_pow2 + 1) <= n {
@@ -448,53 +601,84 @@ pub fn get_search_range(n: u16, itemsize: u16) -> (u16, u16, u16) {
     (search_range, max_pow2, range_shift)
 }
 
+/// Writes the header and table records at the start of a binary font
+pub(crate) fn write_table_directory(
+    sfntVersion: u32,
+    records: &[TableRecord],
+) -> otspec::error::Result<Vec<u8>> {
+    let numTables: u16 = records
+        .len()
+        .try_into()
+        .map_err(|_| OTSpecError::Message("Too many tables in font".to_string()))?;
+    let (searchRange, max_pow2, range_shift) = get_search_range(numTables, 16);
+    let mut output: Vec<u8> = vec![];
+    output.extend(&sfntVersion.to_be_bytes());
+    output.extend(&numTables.to_be_bytes());
+    output.extend(&searchRange.to_be_bytes());
+    output.extend(&max_pow2.to_be_bytes());
+    output.extend(&range_shift.to_be_bytes());
+    for record in records {
+        output.extend(otspec::ser::to_bytes(record)?);
+    }
+    Ok(output)
+}
+
+impl Font {
+    /// Serializes each table in the font, in tag order.
+    ///
+    /// The `checkSumAdjustment` field of the `head` table is set to zero, to
+    /// be filled in once the table's position in the output is known.
+    fn compile_tables(&self) -> otspec::error::Result<Vec<(Tag, Vec<u8>)>> {
+        let mut tables = vec![];
+        for (tag, value) in self.tables.iter() {
+            let mut bytes = otspec::ser::to_bytes(&value).map_err(|e| e.in_table(*tag))?;
+            if tag == b"head" && bytes.len() >= 12 {
+                bytes[8..12].copy_from_slice(&[0, 0, 0, 0]);
+            }
+            tables.push((*tag, bytes));
+        }
+        Ok(tables)
+    }
+}
+
 impl Serialize for Font {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
     where
         S: Serializer,
     {
-        let lenu16: u16 = self.tables.len().try_into().unwrap();
-        let (searchRange, max_pow2, range_shift) = get_search_range(lenu16, 16);
-        // let mut seq = serializer.serialize_seq(None)?;
-        let mut output: Vec<u8> = vec![];
+        if serializer.is_human_readable() {
+            return FontDef::serialize(self, serializer);
+        }
+        let tables = self.compile_tables().map_err(serde::ser::Error::custom)?;
+        let mut records = vec![];
         let mut output_tables: Vec<u8> = vec![];
-        output.extend(&(self.sfntVersion as u32).to_be_bytes());
-        output.extend(&lenu16.to_be_bytes());
-        output.extend(&searchRange.to_be_bytes());
-        output.extend(&max_pow2.to_be_bytes());
-        output.extend(&range_shift.to_be_bytes());
-        let mut pos = 16 * self.tables.len() + 12;
+        let mut pos = 16 * tables.len() + 12;
         let mut head_pos: Option<usize> = None;
-        for (tag, value) in self.tables.iter() {
-            let mut bytes = otspec::ser::to_bytes(&value).unwrap();
-            if tag == b"head" {
+        for (tag, mut bytes) in tables {
+            if &tag == b"head" && bytes.len() >= 12 {
                 head_pos = Some(pos);
-                bytes[8] = 0;
-                bytes[9] = 0;
-                bytes[10] = 0;
-                bytes[11] = 0;
             }
             let orig_len = bytes.len();
             let orig_checksum = checksum(&bytes);
             while (bytes.len() % 4) != 0 {
                 bytes.push(0);
             }
-            output.extend(tag);
-            output.extend(&(orig_checksum as u32).to_be_bytes());
-            output.extend(&(pos as u32).to_be_bytes());
-            output.extend(&(orig_len as u32).to_be_bytes());
+            records.push(TableRecord {
+                tag,
+                checksum: orig_checksum,
+                offset: pos as u32,
+                length: orig_len as u32,
+            });
             pos += bytes.len();
             output_tables.extend(bytes);
         }
+        let mut output = write_table_directory(self.sfntVersion as u32, &records)
+            .map_err(serde::ser::Error::custom)?;
         output.extend(output_tables);
         // Compute full checksum and update head here.
         let full_checksum = (Wrapping(0xB1B0AFBA) - Wrapping(checksum(&output))).0;
-        let checksum_be = full_checksum.to_be_bytes();
         if let Some(head_pos) = head_pos {
-            output[head_pos + 8] = checksum_be[0];
-            output[head_pos + 9] = checksum_be[1];
-            output[head_pos + 10] = checksum_be[2];
-            output[head_pos + 11] = checksum_be[3];
+            output[head_pos + 8..head_pos + 12].copy_from_slice(&full_checksum.to_be_bytes());
         }
         serializer.serialize_bytes(&output)
     }
@@ -503,32 +687,30 @@ impl Serialize for Font {
 deserialize_visitor!(
     Font,
     FontVisitor,
+    readable: FontDef,
     fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
         let header = read_field!(seq, TableHeader, "table header");
         let version = TryInto::<SfntVersion>::try_into(header.sfntVersion)
             .map_err(|_| serde::de::Error::custom("Font must begin with a valid version"))?;
 
         let mut result = Font::new(version);
-        let mut table_records = Vec::with_capacity(header.numTables as usize);
-        for i in 0..(header.numTables as usize) {
-            let next = seq
-                .next_element::<TableRecord>()?
-                .ok_or_else(|| serde::de::Error::invalid_length(i, &self))?;
-            table_records.push(next)
-        }
-        let pos = (16 * table_records.len() + 12) as u32;
-        let max_offset = table_records
-            .iter()
-            .map(|x| (x.length + x.offset))
-            .max()
-            .ok_or_else(|| serde::de::Error::custom("No tables?"))?;
-        let remainder: Vec<u8> = (0..(max_offset - pos))
-            .filter_map(|_| seq.next_element::<u8>().unwrap())
-            .collect();
+        let mut table_records: Vec<TableRecord> =
+            read_field_counted!(seq, header.numTables, "table records");
+        let pos = 16 * table_records.len() + 12;
+        let remainder = read_remainder!(seq, "font tables");
         table_records.sort_by_key(|tr| tr.offset);
         for tr in table_records {
-            let start = (tr.offset - pos) as usize;
-            let this_table = &remainder[start..start + tr.length as usize];
+            let this_table = (tr.offset as usize)
+                .checked_sub(pos)
+                .and_then(|start| remainder.get(start..start.checked_add(tr.length as usize)?))
+                .ok_or_else(|| {
+                    serde::de::Error::custom(format!(
+                        "Table {} at offset {} with length {} lies outside the font",
+                        String::from_utf8_lossy(&tr.tag),
+                        tr.offset,
+                        tr.length
+                    ))
+                })?;
             let table = Table::Unknown(this_table.into()); // Deserialize on read
             result.tables.insert(tr.tag, table);
         }
@@ -537,7 +719,7 @@ deserialize_visitor!(
 );
 
 #[cfg(test)]
-mod tests {
+pub(crate) mod tests {
 
     use crate::font;
     use crate::head::head;
@@ -642,31 +824,32 @@ mod tests {
         let serialized = ser::to_bytes(&font).unwrap();
         assert_eq!(serialized, binary_font);
         let mut deserialized: font::Font = otspec::de::from_bytes(&binary_font).unwrap();
-        deserialized.fully_deserialize();
+        deserialized.fully_deserialize().unwrap();
         assert_eq!(deserialized, font);
     }
 
+    /// A font with head, hhea, loca and maxp tables
+    pub(crate) const LOCA_FONT: &[u8] = &[
+        0x00, 0x01, 0x00, 0x00, 0x00, 0x04, 0x00, 0x40, 0x00, 0x02, 0x00, 0x00, 0x68, 0x65, 0x61,
+        0x64, 0x18, 0x6b, 0x5d, 0xde, 0x00, 0x00, 0x00, 0x4c, 0x00, 0x00, 0x00, 0x36, 0x68, 0x68,
+        0x65, 0x61, 0x06, 0x23, 0x07, 0x4b, 0x00, 0x00, 0x00, 0x84, 0x00, 0x00, 0x00, 0x24, 0x6c,
+        0x6f, 0x63, 0x61, 0x00, 0x5e, 0x00, 0x4c, 0x00, 0x00, 0x00, 0xc8, 0x00, 0x00, 0x00, 0x0e,
+        0x6d, 0x61, 0x78, 0x70, 0x04, 0x65, 0x00, 0x64, 0x00, 0x00, 0x00, 0xa8, 0x00, 0x00, 0x00,
+        0x20, 0x00, 0x01, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0xc0, 0x68, 0x3e, 0x6a, 0x5f, 0x0f,
+        0x3c, 0xf5, 0x00, 0x03, 0x03, 0xe8, 0x00, 0x00, 0x00, 0x00, 0xda, 0x56, 0x58, 0xaa, 0x00,
+        0x00, 0x00, 0x00, 0xdc, 0xa5, 0xc0, 0x69, 0x00, 0x09, 0x00, 0x00, 0x02, 0x50, 0x03, 0xe8,
+        0x00, 0x00, 0x00, 0x06, 0x00, 0x02, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x01, 0x00,
+        0x00, 0x02, 0xc1, 0xff, 0x4c, 0x00, 0x00, 0x05, 0x1f, 0xfe, 0x82, 0xfe, 0x82, 0x04, 0xdd,
+        0x00, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x04, 0x5d, 0x00, 0x01, 0x00, 0x00, 0x04, 0x5d, 0x00, 0x62, 0x00, 0x07, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x02, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x15, 0x00, 0x15, 0x00, 0x15, 0x00, 0x15,
+        0x00, 0x22, 0x00, 0x34, 0x00, 0x00,
+    ];
+
     #[test]
     fn test_de_loca() {
-        let binary_font = vec![
-            0x00, 0x01, 0x00, 0x00, 0x00, 0x04, 0x00, 0x40, 0x00, 0x02, 0x00, 0x00, 0x68, 0x65,
-            0x61, 0x64, 0x18, 0x6b, 0x5d, 0xde, 0x00, 0x00, 0x00, 0x4c, 0x00, 0x00, 0x00, 0x36,
-            0x68, 0x68, 0x65, 0x61, 0x06, 0x23, 0x07, 0x4b, 0x00, 0x00, 0x00, 0x84, 0x00, 0x00,
-            0x00, 0x24, 0x6c, 0x6f, 0x63, 0x61, 0x00, 0x5e, 0x00, 0x4c, 0x00, 0x00, 0x00, 0xc8,
-            0x00, 0x00, 0x00, 0x0e, 0x6d, 0x61, 0x78, 0x70, 0x04, 0x65, 0x00, 0x64, 0x00, 0x00,
-            0x00, 0xa8, 0x00, 0x00, 0x00, 0x20, 0x00, 0x01, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00,
-            0xc0, 0x68, 0x3e, 0x6a, 0x5f, 0x0f, 0x3c, 0xf5, 0x00, 0x03, 0x03, 0xe8, 0x00, 0x00,
-            0x00, 0x00, 0xda, 0x56, 0x58, 0xaa, 0x00, 0x00, 0x00, 0x00, 0xdc, 0xa5, 0xc0, 0x69,
-            0x00, 0x09, 0x00, 0x00, 0x02, 0x50, 0x03, 0xe8, 0x00, 0x00, 0x00, 0x06, 0x00, 0x02,
-            0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x02, 0xc1, 0xff, 0x4c,
-            0x00, 0x00, 0x05, 0x1f, 0xfe, 0x82, 0xfe, 0x82, 0x04, 0xdd, 0x00, 0x01, 0x00, 0x00,
-            0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x04, 0x5d,
-            0x00, 0x01, 0x00, 0x00, 0x04, 0x5d, 0x00, 0x62, 0x00, 0x07, 0x00, 0x00, 0x00, 0x00,
-            0x00, 0x02, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
-            0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x15, 0x00, 0x15, 0x00, 0x15, 0x00, 0x15,
-            0x00, 0x22, 0x00, 0x34, 0x00, 0x00,
-        ];
-        let mut deserialized: font::Font = otspec::de::from_bytes(&binary_font).unwrap();
+        let mut deserialized: font::Font = otspec::de::from_bytes(LOCA_FONT).unwrap();
         let head = deserialized.get_table(b"head").unwrap().unwrap();
         if let crate::font::Table::Head(head) = head {
             assert_eq!(head.indexToLocFormat, 0);
@@ -682,6 +865,128 @@ mod tests {
         )
     }
 
+    #[test]
+    fn get_table_error_location() {
+        let mut font = font::Font::new(font::SfntVersion::TrueType);
+        // A head table truncated halfway through its creation date
+        let binary_head = vec![
+            0x00, 0x01, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x5f, 0x0f,
+            0x3c, 0xf5, 0x00, 0x03, 0x03, 0xe8, 0x00, 0x00, 0x00, 0x00,
+        ];
+        font.tables
+            .insert(*b"head", font::Table::Unknown(binary_head));
+        let error = font.get_table(b"head").unwrap_err();
+        let location = error.location().unwrap();
+        assert_eq!(location.table, Some(*b"head"));
+        assert_eq!(location.path, "created");
+        assert_eq!(location.offset, Some(20));
+        assert_eq!(
+            error.inner(),
+            &otspec::error::Error::UnexpectedEof {
+                needed: 8,
+                available: 4
+            }
+        );
+        assert_eq!(
+            error.to_string(),
+            "head.created at byte 20: unexpected end of input: needed 8 bytes, found 4"
+        );
+    }
+
+    #[test]
+    fn get_table_nested_error_location() {
+        let mut font = font::Font::new(font::SfntVersion::TrueType);
+        // An fvar table truncated halfway through its instance's coordinates
+        let binary_fvar = vec![
+            0x00, 0x01, 0x00, 0x00, 0x00, 0x10, 0x00, 0x02, 0x00, 0x01, 0x00, 0x14, 0x00, 0x01,
+            0x00, 0x08, 0x77, 0x67, 0x68, 0x74, 0x00, 0xc8, 0x00, 0x00, 0x00, 0xc8, 0x00, 0x00,
+            0x03, 0xe8, 0x00, 0x00, 0x00, 0x00, 0x01, 0x00, 0x01, 0x01, 0x00, 0x00, 0x00, 0xc8,
+        ];
+        font.tables
+            .insert(*b"fvar", font::Table::Unknown(binary_fvar));
+        let error = font.get_table(b"fvar").unwrap_err();
+        let location = error.location().unwrap();
+        assert_eq!(location.table, Some(*b"fvar"));
+        assert_eq!(location.path, "instances[0].coordinates[0]");
+        assert_eq!(location.offset, Some(40));
+        assert_eq!(
+            error.to_string(),
+            "fvar.instances[0].coordinates[0] at byte 40: unexpected end of input: needed 4 bytes, found 2"
+        );
+    }
+
+    #[test]
+    fn font_errors() {
+        let mut font = font::Font::new(font::SfntVersion::TrueType);
+        assert!(matches!(
+            font.num_glyphs(),
+            Err(font::FontError::MissingTable(tag)) if &tag == b"maxp"
+        ));
+
+        font.tables.insert(
+            *b"glyf",
+            font::Table::Glyf(crate::glyf::glyf { glyphs: vec![] }),
+        );
+        let mut output = vec![];
+        let error = font.save(&mut output).unwrap_err();
+        assert_eq!(error.to_string(), "font has no maxp table");
+        assert!(output.is_empty());
+
+        // A truncated table directory is an error, not a panic
+        assert!(font::load(&[0x00, 0x01, 0x00, 0x00, 0x00][..]).is_err());
+    }
+
+    #[test]
+    fn load_malformed() {
+        // Truncated or corrupted fonts may fail to load, but must not panic
+        for len in 0..LOCA_FONT.len() {
+            if let Ok(mut font) = font::load(&LOCA_FONT[..len]) {
+                let _ = font.fully_deserialize();
+            }
+        }
+        for i in 0..LOCA_FONT.len() {
+            for corruption in &[0x01, 0x80, 0xff] {
+                let mut binary_font = LOCA_FONT.to_vec();
+                binary_font[i] ^= corruption;
+                if let Ok(mut font) = font::load(&binary_font[..]) {
+                    let _ = font.fully_deserialize();
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn font_json() {
+        let font = crate::ttx::tests::test_font();
+        let json = serde_json::to_string(&font).unwrap();
+        let loaded: font::Font = serde_json::from_str(&json).unwrap();
+        assert_eq!(loaded, font);
+
+        let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+        let tables = &value["tables"];
+        assert_eq!(value["sfntVersion"], "TrueType");
+        assert_eq!(tables["head"]["created"], "2020-01-28T21:31:22");
+        assert_eq!(tables["fvar"]["axes"][0]["axisTag"], "wght");
+        assert_eq!(tables["fvar"]["axes"][0]["defaultValue"], 400.0);
+        assert_eq!(tables["name"]["records"][0]["string"], "Test & Sons");
+        assert_eq!(tables["cmap"]["subtables"][0]["mapping"]["65"], 1);
+        assert_eq!(tables["zzzz"], "0102030405");
+    }
+
+    #[test]
+    fn font_json_errors() {
+        let error = |json: &str| {
+            serde_json::from_str::<font::Font>(json)
+                .unwrap_err()
+                .to_string()
+        };
+        let font = |tables: &str| format!("{{\"sfntVersion\":\"TrueType\",\"tables\":{}}}", tables);
+        assert!(error(&font("{\"zzzz\":{}}")).contains("must be given as hex"));
+        assert!(error(&font("{\"zzzz\":\"123\"}")).contains("Invalid hex data"));
+        assert!(error(&font("{\"toolong\":\"\"}")).contains("Invalid tag"));
+        assert!(error(&font("{\"head\":{}}")).contains("missing field `majorVersion`"));
+    }
+
     // #[test]
     // fn test_load() {
     //     let f = font::load("data/test1.ttf").unwrap();
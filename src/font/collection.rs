@@ -0,0 +1,287 @@
+use crate::font::{checksum, pad, write_table_directory, Font, FontError, FontRef, TableRecord};
+use crate::{woff, woff2};
+use otspec::de::CountedDeserializer;
+use otspec::error::Error as OTSpecError;
+use otspec::types::*;
+use serde::de::DeserializeSeed;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::convert::TryInto;
+use std::io::{Read, Write};
+use std::num::Wrapping;
+
+#[derive(Deserialize, Debug)]
+#[allow(non_snake_case)]
+struct CollectionHeader {
+    ttcTag: Tag,
+    majorVersion: uint16,
+    _minorVersion: uint16,
+    numFonts: uint32,
+}
+
+/// A TrueType/OpenType font collection (`.ttc`/`.otc` file)
+///
+/// A collection stores several fonts in one file. Tables which are
+/// identical in several fonts (typically `glyf` or `CFF `) are only stored
+/// once when the collection is saved.
+#[derive(Debug, Default, PartialEq)]
+pub struct FontCollection {
+    /// The fonts in the collection
+    pub fonts: Vec<Font>,
+    /// The binary data of the collection's `DSIG` table, if any
+    ///
+    /// Collections with a `DSIG` table are written as version 2.0 of the
+    /// collection header, and those without as version 1.0.
+    pub dsig: Option<Vec<u8>>,
+}
+
+/// Reads the members of a font collection without copying their tables
+///
+/// Returns the fonts in the collection, along with the data of its `DSIG`
+/// table if the header has one.
+pub(crate) fn read_collection(data: &[u8]) -> Result<(Vec<FontRef<'_>>, Option<&[u8]>), FontError> {
+    let mut de = otspec::de::Deserializer::from_bytes(data);
+    let header = CollectionHeader::deserialize(&mut de)?;
+    if &header.ttcTag != b"ttcf" {
+        return Err(OTSpecError::Message("Font collection must begin with 'ttcf'".into()).into());
+    }
+    let offsets: Vec<uint32> =
+        CountedDeserializer::with_len(header.numFonts as usize).deserialize(&mut de)?;
+    let mut dsig = None;
+    if header.majorVersion >= 2 {
+        let dsig_tag = Tag::deserialize(&mut de)?;
+        let dsig_length = uint32::deserialize(&mut de)? as usize;
+        let dsig_offset = uint32::deserialize(&mut de)? as usize;
+        if &dsig_tag == b"DSIG" && dsig_length > 0 {
+            let table = dsig_offset
+                .checked_add(dsig_length)
+                .and_then(|end| data.get(dsig_offset..end))
+                .ok_or_else(|| {
+                    OTSpecError::Message(format!(
+                        "DSIG at offset {} with length {} lies outside the font collection",
+                        dsig_offset, dsig_length
+                    ))
+                })?;
+            dsig = Some(table);
+        }
+    }
+    let fonts = offsets
+        .iter()
+        .map(|offset| FontRef::from_directory(data, *offset as usize))
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok((fonts, dsig))
+}
+
+/// Load a font collection from its binary representation
+///
+/// As with [`crate::font::load`], the tables needed to interpret `glyf` data
+/// are deserialized; the others are kept as `Table::Unknown` until they are
+/// requested. WOFF and WOFF2 files are decoded first. A file holding a single
+/// font is returned as a collection with one member.
+pub fn load_collection<T>(mut file: T) -> Result<FontCollection, FontError>
+where
+    T: Read,
+{
+    let mut buffer = Vec::new();
+    file.read_to_end(&mut buffer)?;
+    if buffer.starts_with(b"wOFF") {
+        buffer = woff::decode(&buffer)?.sfnt;
+    } else if buffer.starts_with(b"wOF2") {
+        buffer = woff2::decode(&buffer)?.sfnt;
+    }
+    let (refs, dsig) = if buffer.starts_with(b"ttcf") {
+        read_collection(&buffer)?
+    } else {
+        (vec![FontRef::new(&buffer)?], None)
+    };
+    let mut fonts = vec![];
+    for mut font in refs {
+        font.get_table(b"head")?;
+        font.get_table(b"loca")?;
+        fonts.push(font.into_font());
+    }
+    Ok(FontCollection {
+        fonts,
+        dsig: dsig.map(|d| d.to_vec()),
+    })
+}
+
+impl FontCollection {
+    /// Create a new, empty font collection
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Save a font collection to a file-like object
+    ///
+    /// As with [`Font::save`], the `glyf`, `loca` and `maxp` tables of each
+    /// font are recompiled first. Tables with identical binary data are
+    /// written once and shared between fonts, except for `head`, since its
+    /// `checkSumAdjustment` is specific to each font.
+    pub fn save<T>(&mut self, file: &mut T) -> Result<(), FontError>
+    where
+        T: Write,
+    {
+        let mut compiled = vec![];
+        for font in self.fonts.iter_mut() {
+            font.compile_glyf_loca_maxp()?;
+            compiled.push((font.sfntVersion as u32, font.compile_tables()?));
+        }
+        let offset = |pos: usize| -> Result<uint32, FontError> {
+            pos.try_into()
+                .map_err(|_| OTSpecError::OffsetOverflow.into())
+        };
+
+        let header_len = 12 + 4 * compiled.len() + if self.dsig.is_some() { 12 } else { 0 };
+        let directories_len: usize = compiled
+            .iter()
+            .map(|(_, tables)| 12 + 16 * tables.len())
+            .sum();
+        let data_start = header_len + directories_len;
+
+        // Lay out the table data, storing identical tables once
+        let mut data: Vec<u8> = vec![];
+        let mut shared: HashMap<&[u8], uint32> = HashMap::new();
+        let mut members = vec![];
+        for (version, tables) in &compiled {
+            let mut records = vec![];
+            let mut head_pos = None;
+            for (tag, bytes) in tables {
+                let is_head = tag == b"head" && bytes.len() >= 12;
+                let table_offset = match shared.get(bytes.as_slice()) {
+                    Some(table_offset) if !is_head => *table_offset,
+                    _ => {
+                        if is_head {
+                            head_pos = Some(data.len());
+                        }
+                        let table_offset = offset(data_start + data.len())?;
+                        data.extend(bytes);
+                        pad(&mut data);
+                        if !is_head {
+                            shared.insert(bytes, table_offset);
+                        }
+                        table_offset
+                    }
+                };
+                records.push(TableRecord {
+                    tag: *tag,
+                    checksum: checksum(bytes),
+                    offset: table_offset,
+                    length: offset(bytes.len())?,
+                });
+            }
+            members.push((*version, records, head_pos));
+        }
+
+        let mut output: Vec<u8> = vec![];
+        output.extend(b"ttcf");
+        let major_version: uint16 = if self.dsig.is_some() { 2 } else { 1 };
+        output.extend(&major_version.to_be_bytes());
+        output.extend(&0_u16.to_be_bytes());
+        output.extend(&offset(compiled.len())?.to_be_bytes());
+        let mut directory_offset = header_len;
+        for (_, tables) in &compiled {
+            output.extend(&offset(directory_offset)?.to_be_bytes());
+            directory_offset += 12 + 16 * tables.len();
+        }
+        if let Some(dsig) = &self.dsig {
+            let dsig_offset = data_start + data.len();
+            output.extend(b"DSIG");
+            output.extend(&offset(dsig.len())?.to_be_bytes());
+            output.extend(&offset(dsig_offset)?.to_be_bytes());
+        }
+
+        for (version, records, head_pos) in members {
+            let directory = write_table_directory(version, &records)?;
+            // The sum of the checksums of the directory and of each table is
+            // the checksum the font would have as a standalone file.
+            let font_checksum = records
+                .iter()
+                .fold(Wrapping(checksum(&directory)), |acc, record| {
+                    acc + Wrapping(record.checksum)
+                });
+            if let Some(head_pos) = head_pos {
+                let adjustment = (Wrapping(0xB1B0AFBA) - font_checksum).0;
+                data[head_pos + 8..head_pos + 12].copy_from_slice(&adjustment.to_be_bytes());
+            }
+            output.extend(directory);
+        }
+        output.extend(data);
+        if let Some(dsig) = &self.dsig {
+            output.extend(dsig);
+        }
+        file.write_all(&output)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::font::{self, load_collection, FontCollection, FontRef, Table};
+
+    fn collection() -> FontCollection {
+        // Keep the tables in binary form, and leave out loca, which can't be
+        // compiled without a glyf table
+        let mut first = FontRef::new(font::tests::LOCA_FONT).unwrap().into_font();
+        first.tables.remove(b"loca");
+        let mut second = FontRef::new(font::tests::LOCA_FONT).unwrap().into_font();
+        second.tables.remove(b"loca");
+        if let Some(Table::Unknown(hhea)) = second.tables.get_mut(b"hhea") {
+            hhea[4..6].copy_from_slice(&800_i16.to_be_bytes());
+        }
+        let mut collection = FontCollection::new();
+        collection.fonts.push(first);
+        collection.fonts.push(second);
+        collection
+    }
+
+    #[test]
+    fn collection_roundtrip() {
+        let mut collection = collection();
+        let mut binary = vec![];
+        collection.save(&mut binary).unwrap();
+        assert_eq!(&binary[0..12], b"ttcf\x00\x01\x00\x00\x00\x00\x00\x02");
+
+        // The fonts share their maxp table, but not head (which is the same
+        // apart from checkSumAdjustment) or hhea
+        let first = FontRef::from_directory(&binary, 20).unwrap();
+        let second = FontRef::from_directory(&binary, 20 + 12 + 16 * 3).unwrap();
+        for (tag, shared) in &[(b"head", false), (b"hhea", false), (b"maxp", true)] {
+            let a = first.table_data(tag).unwrap();
+            let b = second.table_data(tag).unwrap();
+            assert_eq!(a.as_ptr() == b.as_ptr(), *shared);
+        }
+        assert_eq!(binary.len(), 20 + 2 * (12 + 16 * 3) + 2 * (56 + 36) + 32);
+
+        let mut loaded = load_collection(binary.as_slice()).unwrap();
+        assert_eq!(loaded.fonts.len(), 2);
+        assert_eq!(loaded.dsig, None);
+        for (font, original) in loaded.fonts.iter().zip(&collection.fonts) {
+            assert_eq!(font.tables[b"hhea"], original.tables[b"hhea"]);
+            assert_eq!(font.tables[b"maxp"], original.tables[b"maxp"]);
+        }
+        let hhea = loaded.fonts[1].get_table(b"hhea").unwrap().unwrap();
+        assert_eq!(hhea.hhea_unchecked().ascender, 800);
+    }
+
+    #[test]
+    fn collection_dsig() {
+        let mut collection = collection();
+        collection.dsig = Some(vec![0, 0, 0, 1, 0, 0, 0, 0]);
+        let mut binary = vec![];
+        collection.save(&mut binary).unwrap();
+        assert_eq!(&binary[4..6], &[0, 2]);
+        let loaded = load_collection(binary.as_slice()).unwrap();
+        assert_eq!(loaded.dsig, Some(vec![0, 0, 0, 1, 0, 0, 0, 0]));
+
+        // A collection can't be loaded with font::load
+        let error = font::load(binary.as_slice()).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Font is a collection; use font::load_collection to read it"
+        );
+        // ...but a single font can be loaded as a collection
+        let single = load_collection(font::tests::LOCA_FONT).unwrap();
+        assert_eq!(single.fonts.len(), 1);
+    }
+}
@@ -0,0 +1,965 @@
+use crate::avar::avar;
+use crate::cmap::cmap;
+use crate::font::{Font, FontError, Table};
+use crate::fvar::{fvar, InstanceRecord, VariationAxisRecord};
+use crate::glyf::{glyf, Glyph};
+use crate::gvar::gvar;
+use crate::hmtx::hmtx;
+use crate::maxp::{maxp, MaxpVariant};
+use crate::name::name;
+use crate::os2::Panose;
+use crate::post::post;
+use crate::ttx::GlyphOrder;
+use otspec::types::*;
+use std::cmp;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+
+/// Identifies a name record: (platformID, encodingID, language, nameID)
+pub type NameRecordKey = (uint16, uint16, NameLanguage, uint16);
+
+/// The language of a name record, for a [`NameRecordKey`]
+///
+/// Records with language tags are identified by their tag, as their language
+/// IDs only number the tags of their own table.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum NameLanguage {
+    /// A platform-specific language ID
+    ID(uint16),
+    /// A BCP-47 language tag
+    Tag(String),
+}
+
+impl fmt::Display for NameLanguage {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            NameLanguage::ID(id) => write!(f, "{:#x}", id),
+            NameLanguage::Tag(tag) => write!(f, "{}", tag),
+        }
+    }
+}
+
+/// A difference between two fonts, found by [`diff`]
+///
+/// Glyphs are identified by their name in the new font, or in the old font
+/// if they have been removed. They are matched up by name when both fonts
+/// have glyph names in their `post` tables, and by glyph ID otherwise.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Difference {
+    /// A table is only in the new font
+    TableAdded(Tag),
+    /// A table is only in the old font
+    TableRemoved(Tag),
+    /// A field of a table has changed
+    Field {
+        /// The table's tag
+        tag: Tag,
+        /// The name of the field
+        field: String,
+        /// The value in the old font
+        old: String,
+        /// The value in the new font
+        new: String,
+    },
+    /// A codepoint is only mapped in the new font
+    CmapAdded {
+        /// The codepoint
+        codepoint: uint32,
+        /// The glyph it maps to
+        glyph: String,
+    },
+    /// A codepoint is only mapped in the old font
+    CmapRemoved {
+        /// The codepoint
+        codepoint: uint32,
+        /// The glyph it mapped to
+        glyph: String,
+    },
+    /// A codepoint maps to a different glyph
+    CmapChanged {
+        /// The codepoint
+        codepoint: uint32,
+        /// The glyph it maps to in the old font
+        old: String,
+        /// The glyph it maps to in the new font
+        new: String,
+    },
+    /// A glyph is only in the new font
+    GlyphAdded(String),
+    /// A glyph is only in the old font
+    GlyphRemoved(String),
+    /// A glyph's contours or components have changed
+    GlyphOutline(String),
+    /// A glyph's TrueType instructions have changed
+    GlyphInstructions(String),
+    /// A glyph's horizontal metrics have changed
+    GlyphMetrics {
+        /// The glyph
+        glyph: String,
+        /// The old (advance width, left side bearing)
+        old: (uint16, int16),
+        /// The new (advance width, left side bearing)
+        new: (uint16, int16),
+    },
+    /// A glyph's variations in the `gvar` table have changed
+    GlyphVariations(String),
+    /// A name record is only in the new font
+    NameAdded {
+        /// The record's IDs
+        key: NameRecordKey,
+        /// The record's string
+        string: String,
+    },
+    /// A name record is only in the old font
+    NameRemoved {
+        /// The record's IDs
+        key: NameRecordKey,
+        /// The record's string
+        string: String,
+    },
+    /// A name record's string has changed
+    NameChanged {
+        /// The record's IDs
+        key: NameRecordKey,
+        /// The old string
+        old: String,
+        /// The new string
+        new: String,
+    },
+    /// A table which is not compared structurally has different binary data
+    Binary {
+        /// The table's tag
+        tag: Tag,
+        /// The length of the old table
+        old_length: usize,
+        /// The length of the new table
+        new_length: usize,
+    },
+}
+
+impl fmt::Display for Difference {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let tag = |t: &Tag| String::from_utf8_lossy(t).to_string();
+        let record = |(platform, encoding, language, id): &NameRecordKey| {
+            format!("name {}/{}/{} ID {}", platform, encoding, language, id)
+        };
+        match self {
+            Difference::TableAdded(t) => write!(f, "{} table added", tag(t)),
+            Difference::TableRemoved(t) => write!(f, "{} table removed", tag(t)),
+            Difference::Field {
+                tag: t,
+                field,
+                old,
+                new,
+            } => write!(f, "{}.{}: {} -> {}", tag(t), field, old, new),
+            Difference::CmapAdded { codepoint, glyph } => {
+                write!(f, "cmap U+{:04X} added, mapped to {}", codepoint, glyph)
+            }
+            Difference::CmapRemoved { codepoint, glyph } => {
+                write!(
+                    f,
+                    "cmap U+{:04X} removed, was mapped to {}",
+                    codepoint, glyph
+                )
+            }
+            Difference::CmapChanged {
+                codepoint,
+                old,
+                new,
+            } => write!(f, "cmap U+{:04X}: {} -> {}", codepoint, old, new),
+            Difference::GlyphAdded(glyph) => write!(f, "glyph {} added", glyph),
+            Difference::GlyphRemoved(glyph) => write!(f, "glyph {} removed", glyph),
+            Difference::GlyphOutline(glyph) => write!(f, "glyph {} outline changed", glyph),
+            Difference::GlyphInstructions(glyph) => {
+                write!(f, "glyph {} instructions changed", glyph)
+            }
+            Difference::GlyphMetrics { glyph, old, new } => write!(
+                f,
+                "glyph {} metrics: advance {}, lsb {} -> advance {}, lsb {}",
+                glyph, old.0, old.1, new.0, new.1
+            ),
+            Difference::GlyphVariations(glyph) => {
+                write!(f, "glyph {} variations changed", glyph)
+            }
+            Difference::NameAdded { key, string } => {
+                write!(f, "{} added: {:?}", record(key), string)
+            }
+            Difference::NameRemoved { key, string } => {
+                write!(f, "{} removed: {:?}", record(key), string)
+            }
+            Difference::NameChanged { key, old, new } => {
+                write!(f, "{}: {:?} -> {:?}", record(key), old, new)
+            }
+            Difference::Binary {
+                tag: t,
+                old_length,
+                new_length,
+            } => write!(
+                f,
+                "{} table data differs ({} -> {} bytes)",
+                tag(t),
+                old_length,
+                new_length
+            ),
+        }
+    }
+}
+
+/// Formats the value of a table field for a [`Difference::Field`]
+trait FieldValue {
+    fn describe(&self) -> String;
+}
+
+macro_rules! display_field_value {
+    ($($t:ty),*) => {
+        $(impl FieldValue for $t {
+            fn describe(&self) -> String {
+                self.to_string()
+            }
+        })*
+    };
+}
+
+display_field_value!(
+    u8,
+    u16,
+    i16,
+    u32,
+    f32,
+    U16F16,
+    chrono::NaiveDateTime,
+    String
+);
+
+impl FieldValue for Tag {
+    fn describe(&self) -> String {
+        format!("'{}'", String::from_utf8_lossy(self))
+    }
+}
+
+impl FieldValue for Panose {
+    fn describe(&self) -> String {
+        otspec::ser::to_bytes(self)
+            .unwrap_or_default()
+            .iter()
+            .map(|b| b.to_string())
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+}
+
+impl<T: FieldValue> FieldValue for Option<T> {
+    fn describe(&self) -> String {
+        match self {
+            Some(value) => value.describe(),
+            None => "(none)".to_string(),
+        }
+    }
+}
+
+/// Records a [`Difference::Field`] for each of the named fields which differ
+///
+/// With `@prefixed`, the field names are given a prefix, such as the record
+/// they belong to.
+macro_rules! compare_fields {
+    (@prefixed $prefix:expr, $diffs:expr, $tag:expr, $old:expr, $new:expr, $($field:ident),* $(,)?) => {{
+        $(
+            if $old.$field != $new.$field {
+                $diffs.push(Difference::Field {
+                    tag: *$tag,
+                    field: format!("{}{}", $prefix, stringify!($field)),
+                    old: $old.$field.describe(),
+                    new: $new.$field.describe(),
+                });
+            }
+        )*
+    }};
+    ($diffs:expr, $tag:expr, $old:expr, $new:expr, $($field:ident),* $(,)?) => {
+        compare_fields!(@prefixed "", $diffs, $tag, $old, $new, $($field),*)
+    };
+}
+
+/// The glyph names of the two fonts being compared
+struct GlyphNames {
+    old: GlyphOrder,
+    new: GlyphOrder,
+}
+
+impl GlyphNames {
+    /// The name of a glyph, preferring the new font's name
+    fn name(&self, gid: usize) -> String {
+        if gid < self.new.len() || gid >= self.old.len() {
+            self.new.name(gid as u16)
+        } else {
+            self.old.name(gid as u16)
+        }
+    }
+
+    /// Matches up the glyphs of a table in the two fonts, which has
+    /// `old_count` and `new_count` glyphs
+    ///
+    /// Returns the name of each glyph with its IDs in the old and new fonts.
+    /// Glyphs are matched by name if both fonts name them in their `post`
+    /// tables, and by ID otherwise.
+    fn pairs(
+        &self,
+        old_count: usize,
+        new_count: usize,
+    ) -> Vec<(String, Option<usize>, Option<usize>)> {
+        let present = |gid: usize, count: usize| Some(gid).filter(|&gid| gid < count);
+        if !(self.old.from_post && self.new.from_post) {
+            return (0..cmp::max(old_count, new_count))
+                .map(|gid| {
+                    (
+                        self.name(gid),
+                        present(gid, old_count),
+                        present(gid, new_count),
+                    )
+                })
+                .collect();
+        }
+        let mut pairs: Vec<_> = (0..new_count)
+            .map(|gid| {
+                let name = self.new.name(gid as u16);
+                let old_gid = self
+                    .old
+                    .get(&name)
+                    .and_then(|old_gid| present(old_gid as usize, old_count));
+                (name, old_gid, Some(gid))
+            })
+            .collect();
+        for gid in 0..old_count {
+            let name = self.old.name(gid as u16);
+            let new_gid = self.new.get(&name).map(|new_gid| new_gid as usize);
+            if new_gid
+                .and_then(|new_gid| present(new_gid, new_count))
+                .is_none()
+            {
+                pairs.push((name, Some(gid), None));
+            }
+        }
+        pairs
+    }
+}
+
+fn field(tag: &Tag, field: String, old: String, new: String) -> Difference {
+    Difference::Field {
+        tag: *tag,
+        field,
+        old,
+        new,
+    }
+}
+
+fn diff_maxp(diffs: &mut Vec<Difference>, old: &maxp, new: &maxp) {
+    compare_fields!(diffs, b"maxp", old, new, version);
+    if old.num_glyphs() != new.num_glyphs() {
+        diffs.push(field(
+            b"maxp",
+            "numGlyphs".to_string(),
+            old.num_glyphs().to_string(),
+            new.num_glyphs().to_string(),
+        ));
+    }
+    if let (MaxpVariant::Maxp10(old), MaxpVariant::Maxp10(new)) = (&old.table, &new.table) {
+        compare_fields!(
+            diffs,
+            b"maxp",
+            old,
+            new,
+            maxPoints,
+            maxContours,
+            maxCompositePoints,
+            maxCompositeContours,
+            maxZones,
+            maxTwilightPoints,
+            maxStorage,
+            maxFunctionDefs,
+            maxInstructionDefs,
+            maxStackElements,
+            maxSizeOfInstructions,
+            maxComponentElements,
+            maxComponentDepth,
+        );
+    }
+}
+
+fn diff_post(diffs: &mut Vec<Difference>, old: &post, new: &post) {
+    compare_fields!(
+        diffs,
+        b"post",
+        old,
+        new,
+        version,
+        italicAngle,
+        underlinePosition,
+        underlineThickness,
+        isFixedPitch,
+        minMemType42,
+        maxMemType42,
+        minMemType1,
+        maxMemType1,
+    );
+    let no_names = vec![];
+    let old_names = old.glyphnames.as_ref().unwrap_or(&no_names);
+    let new_names = new.glyphnames.as_ref().unwrap_or(&no_names);
+    for gid in 0..cmp::max(old_names.len(), new_names.len()) {
+        let (old_name, new_name) = (old_names.get(gid), new_names.get(gid));
+        if old_name != new_name {
+            diffs.push(field(
+                b"post",
+                format!("glyphnames[{}]", gid),
+                old_name.cloned().describe(),
+                new_name.cloned().describe(),
+            ));
+        }
+    }
+}
+
+fn diff_cmap(diffs: &mut Vec<Difference>, old: &cmap, new: &cmap, names: &GlyphNames) {
+    let encodings = |cmap: &cmap| {
+        cmap.subtables
+            .iter()
+            .map(|st| format!("{}/{} format {}", st.platformID, st.encodingID, st.format))
+            .collect::<Vec<_>>()
+            .join(", ")
+    };
+    if encodings(old) != encodings(new) {
+        diffs.push(field(
+            b"cmap",
+            "subtables".to_string(),
+            encodings(old),
+            encodings(new),
+        ));
+    }
+
+    let empty = BTreeMap::new();
+    let old_map = old.getBestMapping().unwrap_or(&empty);
+    let new_map = new.getBestMapping().unwrap_or(&empty);
+    let codepoints: BTreeSet<&uint32> = old_map.keys().chain(new_map.keys()).collect();
+    for &codepoint in codepoints {
+        match (old_map.get(&codepoint), new_map.get(&codepoint)) {
+            (Some(&gid), None) => diffs.push(Difference::CmapRemoved {
+                codepoint,
+                glyph: names.old.name(gid),
+            }),
+            (None, Some(&gid)) => diffs.push(Difference::CmapAdded {
+                codepoint,
+                glyph: names.new.name(gid),
+            }),
+            (Some(&old_gid), Some(&new_gid)) if old_gid != new_gid => {
+                diffs.push(Difference::CmapChanged {
+                    codepoint,
+                    old: names.old.name(old_gid),
+                    new: names.new.name(new_gid),
+                })
+            }
+            _ => {}
+        }
+    }
+
+    let sequences: BTreeSet<&(uint32, uint32)> = old
+        .variationSequences
+        .keys()
+        .chain(new.variationSequences.keys())
+        .collect();
+    for &key in sequences {
+        let describe = |glyph: Option<&Option<uint16>>, names: &GlyphOrder| match glyph {
+            None => "(none)".to_string(),
+            Some(None) => "default".to_string(),
+            Some(Some(gid)) => names.name(*gid),
+        };
+        let old_glyph = describe(old.variationSequences.get(&key), &names.old);
+        let new_glyph = describe(new.variationSequences.get(&key), &names.new);
+        if old_glyph != new_glyph {
+            diffs.push(field(
+                b"cmap",
+                format!("U+{:04X} U+{:04X}", key.0, key.1),
+                old_glyph,
+                new_glyph,
+            ));
+        }
+    }
+}
+
+fn diff_name(diffs: &mut Vec<Difference>, old: &name, new: &name) {
+    let records = |name: &name| -> BTreeMap<NameRecordKey, String> {
+        name.records
+            .iter()
+            .map(|r| {
+                let language = match &r.languageTag {
+                    Some(tag) => NameLanguage::Tag(tag.clone()),
+                    None => NameLanguage::ID(r.languageID),
+                };
+                let key = (r.platformID, r.encodingID, language, r.nameID);
+                (key, r.string.clone())
+            })
+            .collect()
+    };
+    let (old, new) = (records(old), records(new));
+    let keys: BTreeSet<&NameRecordKey> = old.keys().chain(new.keys()).collect();
+    for key in keys {
+        let key = key.clone();
+        match (old.get(&key), new.get(&key)) {
+            (Some(string), None) => diffs.push(Difference::NameRemoved {
+                key,
+                string: string.clone(),
+            }),
+            (None, Some(string)) => diffs.push(Difference::NameAdded {
+                key,
+                string: string.clone(),
+            }),
+            (Some(old), Some(new)) if old != new => diffs.push(Difference::NameChanged {
+                key,
+                old: old.clone(),
+                new: new.clone(),
+            }),
+            _ => {}
+        }
+    }
+}
+
+fn diff_glyf(diffs: &mut Vec<Difference>, old: &glyf, new: &glyf, names: &GlyphNames) {
+    let outline_differs = |old: &Glyph, new: &Glyph| {
+        old.contours != new.contours
+            || old.components != new.components
+            || old.overlap != new.overlap
+    };
+    for (name, old_gid, new_gid) in names.pairs(old.glyphs.len(), new.glyphs.len()) {
+        match (old_gid, new_gid) {
+            (Some(_), None) => diffs.push(Difference::GlyphRemoved(name)),
+            (None, Some(_)) => diffs.push(Difference::GlyphAdded(name)),
+            (Some(old_gid), Some(new_gid)) => {
+                let (old, new) = (&old.glyphs[old_gid], &new.glyphs[new_gid]);
+                if outline_differs(old, new) {
+                    diffs.push(Difference::GlyphOutline(name.clone()));
+                }
+                if old.instructions != new.instructions {
+                    diffs.push(Difference::GlyphInstructions(name));
+                }
+            }
+            (None, None) => {}
+        }
+    }
+}
+
+fn diff_hmtx(diffs: &mut Vec<Difference>, old: &hmtx, new: &hmtx, names: &GlyphNames) {
+    // Added and removed glyphs are reported by the glyf table
+    for (name, old_gid, new_gid) in names.pairs(old.metrics.len(), new.metrics.len()) {
+        if let (Some(old_gid), Some(new_gid)) = (old_gid, new_gid) {
+            let (old, new) = (&old.metrics[old_gid], &new.metrics[new_gid]);
+            if old != new {
+                diffs.push(Difference::GlyphMetrics {
+                    glyph: name,
+                    old: (old.advanceWidth, old.lsb),
+                    new: (new.advanceWidth, new.lsb),
+                });
+            }
+        }
+    }
+}
+
+fn diff_gvar(diffs: &mut Vec<Difference>, old: &gvar, new: &gvar, names: &GlyphNames) {
+    for (name, old_gid, new_gid) in names.pairs(old.variations.len(), new.variations.len()) {
+        let old = old_gid.and_then(|gid| old.variations[gid].as_ref());
+        let new = new_gid.and_then(|gid| new.variations[gid].as_ref());
+        if old != new {
+            diffs.push(Difference::GlyphVariations(name));
+        }
+    }
+}
+
+fn diff_fvar(diffs: &mut Vec<Difference>, old: &fvar, new: &fvar) {
+    let tags: BTreeSet<Tag> = old
+        .axes
+        .iter()
+        .chain(&new.axes)
+        .map(|a| a.axisTag)
+        .collect();
+    for axis_tag in tags {
+        let find = |fvar: &fvar| fvar.axes.iter().find(|a| a.axisTag == axis_tag).cloned();
+        let axis_name = String::from_utf8_lossy(&axis_tag).to_string();
+        match (find(old), find(new)) {
+            (Some(old), Some(new)) => compare_fields!(
+                @prefixed format!("axes[{}].", axis_name),
+                diffs,
+                b"fvar",
+                old,
+                new,
+                minValue,
+                defaultValue,
+                maxValue,
+                flags,
+                axisNameID
+            ),
+            (old, new) => {
+                let describe = |axis: Option<VariationAxisRecord>| match axis {
+                    Some(a) => format!("{}:{}:{}", a.minValue, a.defaultValue, a.maxValue),
+                    None => "(none)".to_string(),
+                };
+                diffs.push(field(
+                    b"fvar",
+                    format!("axes[{}]", axis_name),
+                    describe(old),
+                    describe(new),
+                ));
+            }
+        }
+    }
+
+    let describe = |instance: Option<&InstanceRecord>| match instance {
+        Some(i) => format!(
+            "nameID {} at ({})",
+            i.subfamilyNameID,
+            i.coordinates
+                .iter()
+                .map(|c| c.to_string())
+                .collect::<Vec<_>>()
+                .join(", ")
+        ),
+        None => "(none)".to_string(),
+    };
+    for index in 0..cmp::max(old.instances.len(), new.instances.len()) {
+        let (old, new) = (old.instances.get(index), new.instances.get(index));
+        if old != new {
+            diffs.push(field(
+                b"fvar",
+                format!("instances[{}]", index),
+                describe(old),
+                describe(new),
+            ));
+        }
+    }
+}
+
+fn diff_avar(diffs: &mut Vec<Difference>, old: &avar, new: &avar) {
+    let describe = |avar: &avar, index: usize| match avar.axisSegmentMaps.get(index) {
+        Some(map) => map
+            .axisValueMaps
+            .iter()
+            .map(|m| format!("{}:{}", m.fromCoordinate, m.toCoordinate))
+            .collect::<Vec<_>>()
+            .join(", "),
+        None => "(none)".to_string(),
+    };
+    let count = cmp::max(old.axisSegmentMaps.len(), new.axisSegmentMaps.len());
+    for index in 0..count {
+        if old.axisSegmentMaps.get(index) != new.axisSegmentMaps.get(index) {
+            diffs.push(field(
+                b"avar",
+                format!("axisSegmentMaps[{}]", index),
+                describe(old, index),
+                describe(new, index),
+            ));
+        }
+    }
+}
+
+/// The binary data of a table which is not compared structurally
+fn table_bytes(tag: &Tag, table: &Table) -> Result<Vec<u8>, FontError> {
+    match table {
+        Table::Unknown(binary) => Ok(binary.clone()),
+        _ => Ok(otspec::ser::to_bytes(table).map_err(|e| e.in_table(*tag))?),
+    }
+}
+
+fn diff_table(
+    diffs: &mut Vec<Difference>,
+    tag: &Tag,
+    old: &Table,
+    new: &Table,
+    names: &GlyphNames,
+) -> Result<(), FontError> {
+    match (old, new) {
+        (Table::Head(old), Table::Head(new)) => compare_fields!(
+            diffs,
+            tag,
+            old,
+            new,
+            majorVersion,
+            minorVersion,
+            fontRevision,
+            magicNumber,
+            flags,
+            unitsPerEm,
+            created,
+            modified,
+            xMin,
+            yMin,
+            xMax,
+            yMax,
+            macStyle,
+            lowestRecPPEM,
+            fontDirectionHint,
+            indexToLocFormat,
+            glyphDataFormat,
+        ),
+        (Table::Hhea(old), Table::Hhea(new)) => compare_fields!(
+            diffs,
+            tag,
+            old,
+            new,
+            majorVersion,
+            minorVersion,
+            ascender,
+            descender,
+            lineGap,
+            advanceWidthMax,
+            minLeftSideBearing,
+            minRightSideBearing,
+            xMaxExtent,
+            caretSlopeRise,
+            caretSlopeRun,
+            caretOffset,
+            metricDataFormat,
+            numberOfHMetrics,
+        ),
+        (Table::Os2(old), Table::Os2(new)) => compare_fields!(
+            diffs,
+            tag,
+            old,
+            new,
+            version,
+            xAvgCharWidth,
+            usWeightClass,
+            usWidthClass,
+            fsType,
+            ySubscriptXSize,
+            ySubscriptYSize,
+            ySubscriptXOffset,
+            ySubscriptYOffset,
+            ySuperscriptXSize,
+            ySuperscriptYSize,
+            ySuperscriptXOffset,
+            ySuperscriptYOffset,
+            yStrikeoutSize,
+            yStrikeoutPosition,
+            sFamilyClass,
+            panose,
+            ulUnicodeRange1,
+            ulUnicodeRange2,
+            ulUnicodeRange3,
+            ulUnicodeRange4,
+            achVendID,
+            fsSelection,
+            usFirstCharIndex,
+            usLastCharIndex,
+            sTypoAscender,
+            sTypoDescender,
+            sTypoLineGap,
+            usWinAscent,
+            usWinDescent,
+            ulCodePageRange1,
+            ulCodePageRange2,
+            sxHeight,
+            sCapHeight,
+            usDefaultChar,
+            usBreakChar,
+            usMaxContext,
+            usLowerOpticalPointSize,
+            usUpperOpticalPointSize,
+        ),
+        (Table::Maxp(old), Table::Maxp(new)) => diff_maxp(diffs, old, new),
+        (Table::Post(old), Table::Post(new)) => diff_post(diffs, old, new),
+        (Table::Cmap(old), Table::Cmap(new)) => diff_cmap(diffs, old, new, names),
+        (Table::Name(old), Table::Name(new)) => diff_name(diffs, old, new),
+        (Table::Glyf(old), Table::Glyf(new)) => diff_glyf(diffs, old, new, names),
+        (Table::Hmtx(old), Table::Hmtx(new)) => diff_hmtx(diffs, old, new, names),
+        (Table::Gvar(old), Table::Gvar(new)) => diff_gvar(diffs, old, new, names),
+        (Table::Fvar(old), Table::Fvar(new)) => diff_fvar(diffs, old, new),
+        (Table::Avar(old), Table::Avar(new)) => diff_avar(diffs, old, new),
+        // loca follows from the glyf table
+        (Table::Loca(_), Table::Loca(_)) => {}
+        _ => {
+            let (old, new) = (table_bytes(tag, old)?, table_bytes(tag, new)?);
+            if old != new {
+                diffs.push(Difference::Binary {
+                    tag: *tag,
+                    old_length: old.len(),
+                    new_length: new.len(),
+                });
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Compares two fonts table by table.
+///
+/// Both fonts are fully deserialized first. The fields of `head`, `hhea`,
+/// `maxp`, `OS/2` and `post` are compared individually, as are cmap entries,
+/// name records, glyph outlines and metrics, and variation data.
+/// `head.checkSumAdjustment` and the `loca` table are not compared, as they
+/// follow from the rest of the font. Other tables are compared as binary
+/// data.
+///
+/// Returns an Err if a table cannot be deserialized, and otherwise the
+/// differences found, which is empty if the fonts are the same.
+pub fn diff(old: &mut Font, new: &mut Font) -> Result<Vec<Difference>, FontError> {
+    old.fully_deserialize()?;
+    new.fully_deserialize()?;
+    let names = GlyphNames {
+        old: GlyphOrder::from_font(old),
+        new: GlyphOrder::from_font(new),
+    };
+    let mut diffs = vec![];
+    let tags: BTreeSet<Tag> = old
+        .tables
+        .keys()
+        .chain(new.tables.keys())
+        .copied()
+        .collect();
+    for tag in tags {
+        match (old.tables.get(&tag), new.tables.get(&tag)) {
+            (Some(_), None) => diffs.push(Difference::TableRemoved(tag)),
+            (None, Some(_)) => diffs.push(Difference::TableAdded(tag)),
+            (Some(old), Some(new)) if old != new => diff_table(&mut diffs, &tag, old, new, &names)?,
+            _ => {}
+        }
+    }
+    Ok(diffs)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::font::{diff, Table};
+    use crate::name::NameRecord;
+    use crate::ttx::tests::test_font;
+
+    #[test]
+    fn diff_identical() {
+        assert_eq!(diff(&mut test_font(), &mut test_font()).unwrap(), vec![]);
+    }
+
+    #[test]
+    fn diff_changes() {
+        let mut new = test_font();
+        new.tables.remove(b"gasp");
+        new.tables
+            .insert(*b"DSIG", Table::Unknown(vec![0, 0, 0, 1, 0, 0, 0, 0]));
+        new.tables.insert(*b"zzzz", Table::Unknown(vec![1, 2, 3]));
+        for table in new.tables.values_mut() {
+            match table {
+                Table::Cmap(cmap) => {
+                    let mapping = &mut cmap.subtables[0].mapping;
+                    mapping.remove(&0x41);
+                    mapping.insert(0x42, 1);
+                    mapping.insert(0xC5, 2);
+                }
+                Table::Fvar(fvar) => fvar.axes[0].maxValue = 1000.0,
+                Table::Glyf(glyf) => {
+                    glyf.glyphs[1].contours[0][0].x = 25;
+                    glyf.glyphs[3].instructions.clear();
+                }
+                Table::Gvar(gvar) => gvar.variations[2] = None,
+                Table::Head(head) => {
+                    head.fontRevision = 2.5;
+                    // Not compared, as it changes whenever the font does
+                    head.checksumAdjustment += 1;
+                }
+                Table::Hmtx(hmtx) => hmtx.metrics[1].advanceWidth = 1520,
+                Table::Name(name) => {
+                    name.records[0].string = "Test & Daughters".to_string();
+                    name.records.pop();
+                }
+                _ => {}
+            }
+        }
+
+        let differences: Vec<String> = diff(&mut test_font(), &mut new)
+            .unwrap()
+            .iter()
+            .map(|d| d.to_string())
+            .collect();
+        assert_eq!(
+            differences,
+            vec![
+                "DSIG table added",
+                "cmap U+0041 removed, was mapped to A",
+                "cmap U+0042 added, mapped to A",
+                "cmap U+00C5: A#1 -> hyphen",
+                "fvar.axes[wght].maxValue: 900 -> 1000",
+                "gasp table removed",
+                "glyph A outline changed",
+                "glyph A#1 instructions changed",
+                "glyph hyphen variations changed",
+                "head.fontRevision: 1 -> 2.5",
+                "glyph A metrics: advance 1500, lsb 20 -> advance 1520, lsb 20",
+                "name 3/10/0x409 ID 1: \"Test & Sons\" -> \"Test & Daughters\"",
+                "name 3/10/0x409 ID 257 removed: \"Bold\"",
+                "zzzz table data differs (5 -> 3 bytes)",
+            ]
+        );
+    }
+
+    #[test]
+    fn diff_glyphs_by_name() {
+        let mut new = test_font();
+        for table in new.tables.values_mut() {
+            match table {
+                Table::Cmap(cmap) => {
+                    let mapping = &mut cmap.subtables[0].mapping;
+                    mapping.remove(&0x2010);
+                    mapping.insert(0xC5, 2);
+                    cmap.variationSequences.insert((0x41, 0xFE01), Some(2));
+                }
+                Table::Glyf(glyf) => {
+                    glyf.glyphs.remove(2);
+                }
+                Table::Gvar(gvar) => {
+                    gvar.variations.remove(2);
+                }
+                Table::Hmtx(hmtx) => {
+                    hmtx.metrics.remove(2);
+                    hmtx.metrics[2].advanceWidth = 700;
+                }
+                Table::Maxp(maxp) => maxp.set_num_glyphs(3),
+                Table::Post(post) => {
+                    post.glyphnames.as_mut().unwrap().remove(2);
+                }
+                _ => {}
+            }
+        }
+        let differences: Vec<String> = diff(&mut test_font(), &mut new)
+            .unwrap()
+            .iter()
+            .map(|d| d.to_string())
+            .filter(|d| d.starts_with("glyph"))
+            .collect();
+        assert_eq!(
+            differences,
+            vec![
+                "glyph hyphen removed",
+                "glyph hyphen variations changed",
+                "glyph A#1 metrics: advance 600, lsb 30 -> advance 700, lsb 30",
+            ]
+        );
+    }
+
+    #[test]
+    fn diff_names_by_language_tag() {
+        let tagged = |id, string: &str, tag: &str| NameRecord {
+            platformID: 3,
+            encodingID: 1,
+            languageID: id,
+            nameID: 256,
+            string: string.to_string(),
+            languageTag: Some(tag.to_string()),
+        };
+        let mut old = test_font();
+        let mut new = test_font();
+        if let Some(Table::Name(name)) = old.tables.get_mut(b"name") {
+            name.records.push(tagged(0x8000, "Larĝo", "eo"));
+        }
+        if let Some(Table::Name(name)) = new.tables.get_mut(b"name") {
+            name.records.push(tagged(0x8000, "Breite", "de-CH"));
+            name.records.push(tagged(0x8001, "Larĝo", "eo"));
+        }
+        let differences: Vec<String> = diff(&mut old, &mut new)
+            .unwrap()
+            .iter()
+            .map(|d| d.to_string())
+            .collect();
+        assert_eq!(differences, vec!["name 3/1/de-CH ID 256 added: \"Breite\""]);
+    }
+}
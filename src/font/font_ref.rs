@@ -0,0 +1,206 @@
+use crate::font::{read_table_directory, Font, FontError, SfntVersion, Table};
+use otspec::error::Error as OTSpecError;
+use otspec::types::*;
+use std::collections::BTreeMap;
+use std::convert::TryInto;
+
+/// Tables which must be deserialized before the given table can be
+fn dependencies(tag: &Tag) -> &'static [&'static Tag] {
+    match tag {
+        b"loca" => &[b"head"],
+        b"glyf" => &[b"loca"],
+        b"hmtx" => &[b"hhea"],
+        b"gvar" => &[b"glyf"],
+        _ => &[],
+    }
+}
+
+/// A font which borrows its table data from a binary buffer
+///
+/// Creating a `FontRef` only reads the table directory. The table data is
+/// left in the buffer (which may, for example, be a memory-mapped file)
+/// until a table is requested with [`FontRef::get_table`], at which point
+/// that table (and any tables it depends on) is deserialized and kept, so
+/// that it can be modified. Call [`FontRef::into_font`] to turn this into
+/// an owned [`Font`], for example to save it.
+#[derive(Debug)]
+pub struct FontRef<'a> {
+    data: BTreeMap<Tag, &'a [u8]>,
+    font: Font,
+}
+
+impl<'a> FontRef<'a> {
+    /// Reads the table directory of a binary font
+    ///
+    /// Returns an Err if the table directory cannot be read or a table lies
+    /// outside the buffer.
+    pub fn new(data: &'a [u8]) -> Result<Self, FontError> {
+        Self::from_directory(data, 0)
+    }
+
+    /// Reads a table directory found `offset` bytes into the buffer
+    ///
+    /// Table offsets are relative to the start of the buffer, as they are in
+    /// the members of a font collection.
+    pub(crate) fn from_directory(data: &'a [u8], offset: usize) -> Result<Self, FontError> {
+        let directory = data.get(offset..).ok_or_else(|| {
+            OTSpecError::Message(format!(
+                "Table directory at offset {} lies outside the font",
+                offset
+            ))
+        })?;
+        let (header, records) = read_table_directory(directory)?;
+        if &header.sfntVersion.to_be_bytes() == b"ttcf" {
+            return Err(OTSpecError::Message(
+                "Font is a collection; use font::load_collection to read it".into(),
+            )
+            .into());
+        }
+        let version: SfntVersion = header
+            .sfntVersion
+            .try_into()
+            .map_err(|_| OTSpecError::Message("Font must begin with a valid version".into()))?;
+        let mut tables = BTreeMap::new();
+        for tr in records {
+            let start = tr.offset as usize;
+            let table = start
+                .checked_add(tr.length as usize)
+                .and_then(|end| data.get(start..end))
+                .ok_or_else(|| {
+                    OTSpecError::Message(format!(
+                        "Table {} at offset {} with length {} lies outside the font",
+                        String::from_utf8_lossy(&tr.tag),
+                        tr.offset,
+                        tr.length
+                    ))
+                })?;
+            tables.insert(tr.tag, table);
+        }
+        Ok(FontRef {
+            data: tables,
+            font: Font::new(version),
+        })
+    }
+
+    /// Font version (TrueType/OpenType)
+    pub fn sfnt_version(&self) -> SfntVersion {
+        self.font.sfntVersion
+    }
+
+    /// The tags of the tables in the font, in sorted order
+    pub fn tags(&self) -> impl Iterator<Item = &Tag> {
+        self.data.keys()
+    }
+
+    /// Returns the binary data of a table as found in the buffer
+    ///
+    /// This does not reflect any changes made to a table after it has been
+    /// returned by [`FontRef::get_table`].
+    pub fn table_data(&self, tag: &Tag) -> Option<&'a [u8]> {
+        self.data.get(tag).copied()
+    }
+
+    /// Retrieve a table from the font
+    ///
+    /// The table is deserialized the first time it is requested, along with
+    /// any tables needed to deserialize it (e.g. `head` and `loca` for `glyf`).
+    /// Tables not known to this library are returned as a `Table::Unknown`.
+    ///
+    /// Returns an Err if the table could not be correctly deserialized, and
+    /// Ok(None) if the table was not present within the font.
+    pub fn get_table(&mut self, tag: &Tag) -> otspec::error::Result<Option<&mut Table>> {
+        if !self.font.tables.contains_key(tag) {
+            let binary = match self.data.get(tag) {
+                Some(binary) => *binary,
+                None => return Ok(None),
+            };
+            for dependency in dependencies(tag) {
+                self.get_table(dependency)?;
+            }
+            let table = self
+                .font
+                ._deserialize(tag, binary)
+                .map_err(|e| e.in_table(*tag))?;
+            self.font.tables.insert(*tag, table);
+        }
+        Ok(self.font.tables.get_mut(tag))
+    }
+
+    /// Total number of glyphs in the font, from the maxp table.
+    pub fn num_glyphs(&mut self) -> Result<u16, FontError> {
+        self.get_table(b"maxp")?;
+        self.font.num_glyphs()
+    }
+
+    /// Converts this into an owned font
+    ///
+    /// Tables which have been retrieved keep their deserialized form; the
+    /// data of the others is copied out of the buffer.
+    pub fn into_font(self) -> Font {
+        let mut font = self.font;
+        for (tag, binary) in self.data {
+            font.tables
+                .entry(tag)
+                .or_insert_with(|| Table::Unknown(binary.to_vec()));
+        }
+        font
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::font::{self, FontRef, Table};
+
+    #[test]
+    fn font_ref_lazy() {
+        let binary_font = font::tests::LOCA_FONT;
+        let mut font = FontRef::new(binary_font).unwrap();
+        assert_eq!(
+            font.tags().collect::<Vec<_>>(),
+            vec![b"head", b"hhea", b"loca", b"maxp"]
+        );
+
+        // Table data is borrowed from the buffer, not copied
+        let hhea = font.table_data(b"hhea").unwrap();
+        assert_eq!(hhea.len(), 0x24);
+        assert_eq!(hhea.as_ptr(), binary_font[0x84..].as_ptr());
+        assert!(font.table_data(b"glyf").is_none());
+
+        // Nothing is deserialized until it is asked for, then dependencies are too
+        assert!(font.font.tables.is_empty());
+        let loca = font.get_table(b"loca").unwrap().unwrap().loca_unchecked();
+        assert_eq!(
+            loca.indices,
+            vec![Some(0), None, None, None, Some(42), Some(68)]
+        );
+        assert_eq!(
+            font.font.tables.keys().collect::<Vec<_>>(),
+            vec![b"head", b"loca"]
+        );
+        assert_eq!(font.num_glyphs().unwrap(), 1117);
+        assert!(font.get_table(b"glyf").unwrap().is_none());
+
+        if let Some(Table::Hhea(hhea)) = font.get_table(b"hhea").unwrap() {
+            hhea.ascender = 800;
+        }
+        let owned = font.into_font();
+        assert_eq!(owned.tables.len(), 4);
+        assert_eq!(owned.tables[b"hhea"].hhea_unchecked().ascender, 800);
+        assert_eq!(
+            owned.tables[b"head"],
+            font::load(binary_font).unwrap().tables[b"head"]
+        );
+    }
+
+    #[test]
+    fn font_ref_errors() {
+        let mut binary_font = font::tests::LOCA_FONT.to_vec();
+        binary_font.truncate(0x84);
+        let error = FontRef::new(&binary_font).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Table hhea at offset 132 with length 36 lies outside the font"
+        );
+        assert!(FontRef::new(&[0x00, 0x01, 0x00]).is_err());
+    }
+}
@@ -0,0 +1,154 @@
+use crate::font::{Font, SfntVersion, Table};
+use otspec::types::*;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// A font in human-readable formats such as JSON
+///
+/// The tables are written as a map from their tags. Tables which have been
+/// deserialized are written in full; any others are written as a hex string
+/// of their binary data.
+#[derive(Serialize, Deserialize)]
+#[serde(remote = "Font")]
+pub(super) struct FontDef {
+    sfntVersion: SfntVersion,
+    #[serde(with = "tables")]
+    tables: BTreeMap<Tag, Table>,
+    #[serde(skip)]
+    _numGlyphs: Option<u16>,
+}
+
+fn to_hex(data: &[u8]) -> String {
+    data.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+fn from_hex(hex: &str) -> Result<Vec<u8>, String> {
+    if hex.len() % 2 == 1 || !hex.is_ascii() {
+        return Err(format!("Invalid hex data '{}'", hex));
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| {
+            u8::from_str_radix(&hex[i..i + 2], 16)
+                .map_err(|_| format!("Invalid hex data '{}'", &hex[i..i + 2]))
+        })
+        .collect()
+}
+
+mod tables {
+    use super::{from_hex, to_hex};
+    use crate::font::Table;
+    use crate::{
+        avar, cmap, fvar, gasp, glyf, gvar, head, hhea, hmtx, loca, maxp, name, os2, post,
+    };
+    use otspec::types::*;
+    use serde::de::value::MapAccessDeserializer;
+    use serde::de::{self, DeserializeSeed, MapAccess, Visitor};
+    use serde::ser::SerializeMap;
+    use serde::{Deserialize, Deserializer, Serializer};
+    use std::collections::BTreeMap;
+    use std::fmt;
+
+    pub fn serialize<S>(tables: &BTreeMap<Tag, Table>, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let mut map = serializer.serialize_map(Some(tables.len()))?;
+        for (tag, table) in tables {
+            let tag = String::from_utf8_lossy(tag);
+            match table {
+                Table::Unknown(binary) => map.serialize_entry(&tag, &to_hex(binary))?,
+                _ => map.serialize_entry(&tag, table)?,
+            }
+        }
+        map.end()
+    }
+
+    pub fn deserialize<'de, D>(d: D) -> Result<BTreeMap<Tag, Table>, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        d.deserialize_map(TablesVisitor)
+    }
+
+    /// A table's tag, as a map key
+    #[derive(Deserialize)]
+    struct TableTag(#[serde(with = "tag")] Tag);
+
+    struct TablesVisitor;
+
+    impl<'de> Visitor<'de> for TablesVisitor {
+        type Value = BTreeMap<Tag, Table>;
+
+        fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+            formatter.write_str("a map of table tags to tables")
+        }
+
+        fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+            let mut tables = BTreeMap::new();
+            while let Some(TableTag(tag)) = map.next_key()? {
+                let table = map.next_value_seed(TableSeed { tag })?;
+                tables.insert(tag, table);
+            }
+            Ok(tables)
+        }
+    }
+
+    /// Reads a table as the type given by its tag, or as hex-encoded binary
+    struct TableSeed {
+        tag: Tag,
+    }
+
+    impl<'de> DeserializeSeed<'de> for TableSeed {
+        type Value = Table;
+
+        fn deserialize<D>(self, d: D) -> Result<Self::Value, D::Error>
+        where
+            D: Deserializer<'de>,
+        {
+            d.deserialize_any(self)
+        }
+    }
+
+    impl<'de> Visitor<'de> for TableSeed {
+        type Value = Table;
+
+        fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+            write!(
+                formatter,
+                "a {} table, or its binary data as a hex string",
+                String::from_utf8_lossy(&self.tag)
+            )
+        }
+
+        fn visit_str<E: de::Error>(self, hex: &str) -> Result<Self::Value, E> {
+            Ok(Table::Unknown(from_hex(hex).map_err(E::custom)?))
+        }
+
+        fn visit_map<A: MapAccess<'de>>(self, map: A) -> Result<Self::Value, A::Error> {
+            let d = MapAccessDeserializer::new(map);
+            Ok(match &self.tag {
+                b"avar" => Table::Avar(avar::avar::deserialize(d)?),
+                b"cmap" => Table::Cmap(cmap::cmap::deserialize(d)?),
+                b"fvar" => Table::Fvar(fvar::fvar::deserialize(d)?),
+                b"gasp" => Table::Gasp(gasp::gasp::deserialize(d)?),
+                b"glyf" => Table::Glyf(glyf::glyf::deserialize(d)?),
+                b"gvar" => Table::Gvar(gvar::gvar::deserialize(d)?),
+                b"head" => Table::Head(head::head::deserialize(d)?),
+                b"hhea" => Table::Hhea(hhea::hhea::deserialize(d)?),
+                b"hmtx" => Table::Hmtx(hmtx::hmtx::deserialize(d)?),
+                b"loca" => Table::Loca(loca::loca::deserialize(d)?),
+                b"maxp" => Table::Maxp(maxp::maxp::deserialize(d)?),
+                b"name" => Table::Name(name::name::deserialize(d)?),
+                b"OS/2" => Table::Os2(os2::os2::deserialize(d)?),
+                b"post" => Table::Post(post::post::deserialize(d)?),
+                _ => {
+                    return Err(de::Error::custom(format!(
+                        "{} tables must be given as hex-encoded binary data",
+                        String::from_utf8_lossy(&self.tag)
+                    )))
+                }
+            })
+        }
+    }
+}
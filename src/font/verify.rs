@@ -0,0 +1,265 @@
+use crate::font::{checksum, get_search_range, read_table_directory, FontError};
+use otspec::types::*;
+use std::fmt;
+use std::num::Wrapping;
+
+/// A problem with the structure of a font file, found by [`verify`]
+#[derive(Debug, Clone, PartialEq)]
+pub enum VerificationIssue {
+    /// A table's checksum does not match the one in the table directory
+    TableChecksum {
+        /// The table's tag
+        tag: Tag,
+        /// The checksum recorded in the table directory
+        expected: uint32,
+        /// The checksum of the table data
+        found: uint32,
+    },
+    /// The `checkSumAdjustment` field of the `head` table is wrong
+    ChecksumAdjustment {
+        /// The value computed from the font data
+        expected: uint32,
+        /// The value stored in the `head` table
+        found: uint32,
+    },
+    /// A table extends beyond the end of the file
+    TableOutOfBounds {
+        /// The table's tag
+        tag: Tag,
+        /// The table's offset from the start of the file
+        offset: uint32,
+        /// The table's length
+        length: uint32,
+    },
+    /// The data of two tables overlap
+    TablesOverlap(Tag, Tag),
+    /// The table directory is not sorted in ascending order of tag (the
+    /// first tag is found before the second)
+    UnsortedTags(Tag, Tag),
+    /// The `searchRange`, `entrySelector` and `rangeShift` fields of the
+    /// table directory do not match the number of tables
+    SearchRange {
+        /// The values computed from the number of tables
+        expected: (uint16, uint16, uint16),
+        /// The values found in the table directory
+        found: (uint16, uint16, uint16),
+    },
+}
+
+impl fmt::Display for VerificationIssue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let tag = |t: &Tag| String::from_utf8_lossy(t).to_string();
+        match self {
+            VerificationIssue::TableChecksum {
+                tag: t,
+                expected,
+                found,
+            } => write!(
+                f,
+                "{} table checksum is {:#010x}, but the table directory says {:#010x}",
+                tag(t),
+                found,
+                expected
+            ),
+            VerificationIssue::ChecksumAdjustment { expected, found } => write!(
+                f,
+                "head.checkSumAdjustment is {:#010x}, should be {:#010x}",
+                found, expected
+            ),
+            VerificationIssue::TableOutOfBounds {
+                tag: t,
+                offset,
+                length,
+            } => write!(
+                f,
+                "{} table at offset {} with length {} lies outside the font",
+                tag(t),
+                offset,
+                length
+            ),
+            VerificationIssue::TablesOverlap(a, b) => {
+                write!(f, "{} and {} tables overlap", tag(a), tag(b))
+            }
+            VerificationIssue::UnsortedTags(a, b) => write!(
+                f,
+                "table directory is not sorted: {} comes before {}",
+                tag(a),
+                tag(b)
+            ),
+            VerificationIssue::SearchRange { expected, found } => write!(
+                f,
+                "table directory searchRange/entrySelector/rangeShift are {:?}, should be {:?}",
+                found, expected
+            ),
+        }
+    }
+}
+
+/// Checks the structure of a binary font.
+///
+/// This verifies the table checksums and `head.checkSumAdjustment`, that
+/// the tables lie within the file and do not overlap, and that the table
+/// directory is sorted and has correct binary search fields. The tables
+/// themselves are not deserialized.
+///
+/// Returns an Err if the table directory cannot be read at all, and
+/// otherwise a list of the problems found, which is empty for a valid font.
+pub fn verify(data: &[u8]) -> Result<Vec<VerificationIssue>, FontError> {
+    let (header, records) = read_table_directory(data)?;
+    let mut issues = vec![];
+
+    // Entries must fit in a uint16, so don't bother checking huge directories
+    if header.numTables <= 0x0fff {
+        let expected = get_search_range(header.numTables, 16);
+        let found = (
+            header._searchRange,
+            header._entrySelector,
+            header._rangeShift,
+        );
+        if expected != found {
+            issues.push(VerificationIssue::SearchRange { expected, found });
+        }
+    }
+
+    for pair in records.windows(2) {
+        if pair[0].tag >= pair[1].tag {
+            issues.push(VerificationIssue::UnsortedTags(pair[0].tag, pair[1].tag));
+        }
+    }
+
+    let mut in_bounds = vec![];
+    for record in &records {
+        let start = record.offset as usize;
+        let table = start
+            .checked_add(record.length as usize)
+            .and_then(|end| data.get(start..end));
+        match table {
+            Some(table) => in_bounds.push((record, table)),
+            None => issues.push(VerificationIssue::TableOutOfBounds {
+                tag: record.tag,
+                offset: record.offset,
+                length: record.length,
+            }),
+        }
+    }
+
+    in_bounds.sort_by_key(|(record, _)| record.offset);
+    for pair in in_bounds.windows(2) {
+        let (first, second) = (pair[0].0, pair[1].0);
+        if first.offset as usize + first.length as usize > second.offset as usize {
+            issues.push(VerificationIssue::TablesOverlap(first.tag, second.tag));
+        }
+    }
+
+    for (record, table) in &in_bounds {
+        let found = if &record.tag == b"head" && table.len() >= 12 {
+            // The head checksum is computed with checkSumAdjustment set to zero
+            let mut table = table.to_vec();
+            table[8..12].copy_from_slice(&[0, 0, 0, 0]);
+            checksum(&table)
+        } else {
+            checksum(table)
+        };
+        if found != record.checksum {
+            issues.push(VerificationIssue::TableChecksum {
+                tag: record.tag,
+                expected: record.checksum,
+                found,
+            });
+        }
+    }
+
+    let head = in_bounds
+        .iter()
+        .find(|(record, table)| &record.tag == b"head" && table.len() >= 12);
+    if let Some((record, table)) = head {
+        let found = u32::from_be_bytes([table[8], table[9], table[10], table[11]]);
+        let mut font = data.to_vec();
+        let adjustment = record.offset as usize + 8;
+        font[adjustment..adjustment + 4].copy_from_slice(&[0, 0, 0, 0]);
+        let expected = (Wrapping(0xB1B0AFBA) - Wrapping(checksum(&font))).0;
+        if found != expected {
+            issues.push(VerificationIssue::ChecksumAdjustment { expected, found });
+        }
+    }
+
+    Ok(issues)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::font::{self, verify, VerificationIssue};
+
+    fn binary_font() -> Vec<u8> {
+        let mut font = font::Font::new(font::SfntVersion::TrueType);
+        font.tables
+            .insert(*b"head", font::Table::Unknown((0..54).collect()));
+        font.tables
+            .insert(*b"hhea", font::Table::Unknown((100..136).collect()));
+        font.tables
+            .insert(*b"name", font::Table::Unknown(vec![1, 2, 3, 4, 5]));
+        otspec::ser::to_bytes(&font).unwrap()
+    }
+
+    #[test]
+    fn verify_valid() {
+        assert_eq!(verify(&binary_font()).unwrap(), vec![]);
+    }
+
+    #[test]
+    fn verify_checksums() {
+        let mut binary = binary_font();
+        // The last byte of the name table
+        binary[156] = 6;
+        let issues = verify(&binary).unwrap();
+        assert_eq!(issues.len(), 2);
+        assert!(matches!(
+            issues[0],
+            VerificationIssue::TableChecksum { tag, .. } if &tag == b"name"
+        ));
+        assert!(matches!(
+            issues[1],
+            VerificationIssue::ChecksumAdjustment { .. }
+        ));
+    }
+
+    #[test]
+    fn verify_directory() {
+        let mut binary = binary_font();
+        // Swap the head and hhea table records and break the search range
+        let (head, hhea) = (binary[12..28].to_vec(), binary[28..44].to_vec());
+        binary[12..28].copy_from_slice(&hhea);
+        binary[28..44].copy_from_slice(&head);
+        binary[7] = 0x40;
+        // Make head overlap hhea, and name run past the end of the file
+        binary[28 + 15] = 60;
+        binary[44 + 15] = 9;
+        let issues = verify(&binary).unwrap();
+        assert_eq!(
+            issues[..4],
+            [
+                VerificationIssue::SearchRange {
+                    expected: (32, 1, 16),
+                    found: (64, 1, 16)
+                },
+                VerificationIssue::UnsortedTags(*b"hhea", *b"head"),
+                VerificationIssue::TableOutOfBounds {
+                    tag: *b"name",
+                    offset: 152,
+                    length: 9
+                },
+                VerificationIssue::TablesOverlap(*b"head", *b"hhea"),
+            ]
+        );
+        assert!(matches!(
+            issues[4],
+            VerificationIssue::TableChecksum { tag, .. } if &tag == b"head"
+        ));
+        assert!(matches!(
+            issues[5],
+            VerificationIssue::ChecksumAdjustment { .. }
+        ));
+        assert_eq!(issues.len(), 6);
+        assert_eq!(issues[3].to_string(), "head and hhea tables overlap");
+    }
+}
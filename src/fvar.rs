@@ -1,16 +1,16 @@
 #![allow(non_camel_case_types, non_snake_case)]
 
+use crate::layout::data_at;
 use otspec::de::CountedDeserializer;
 use otspec::de::Deserializer as OTDeserializer;
 use otspec::types::*;
-use otspec::{
-    deserialize_visitor, read_field, read_field_counted, read_remainder, stateful_deserializer,
-};
+use otspec::{deserialize_visitor, read_field, read_remainder};
 use otspec_macros::tables;
-use serde::de::{DeserializeSeed, SeqAccess, Visitor};
+use serde::de::{SeqAccess, Visitor};
 use serde::ser::SerializeSeq;
 use serde::Serializer;
 use serde::{Deserialize, Deserializer, Serialize};
+use std::marker::PhantomData;
 
 tables!(
     fvarcore {
@@ -34,7 +34,7 @@ tables!(
 );
 
 /// Struct representing a named instance within the variable font's design space
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
 pub struct InstanceRecord {
     /// The name ID for entries in the 'name' table that provide subfamily names for this instance.
     pub subfamilyNameID: uint16,
@@ -44,47 +44,37 @@ pub struct InstanceRecord {
     pub postscriptNameID: Option<uint16>,
 }
 
-stateful_deserializer!(
-Vec<InstanceRecord>,
-InstanceRecordDeserializer,
-{
-    axisCount: uint16,
-    instanceCount: uint16,
-    has_postscript_name_id: bool
-},
-fn visit_seq<A>(self, mut seq: A) -> std::result::Result<Vec<InstanceRecord>, A::Error>
-        where
-            A: SeqAccess<'de>
-        {
-            let mut res = vec![];
-            for _ in 0..self.instanceCount {
-                let subfamilyNameID =
-                    read_field!(seq, uint16, "instance record family name ID");
-                let _flags = read_field!(seq, uint16, "instance record flags");
-                let coordinates = (read_field_counted!(seq, self.axisCount, "a coordinate")
-                    as Vec<i32>)
-                    .iter()
-                    .map(|x| Fixed::unpack(*x))
-                    .collect();
-                let postscriptNameID = if self.has_postscript_name_id {
-                    Some(read_field!(
-                        seq,
-                        uint16,
-                        "instance record postscript name ID"
-                    ))
-                } else {
-                    None
-                };
-                res.push(InstanceRecord {
-                    subfamilyNameID,
-                    coordinates,
-                    postscriptNameID,
-                });
-                println!("Got a record {:?}", res);
-            }
-            Ok(res)
-        }
-);
+/// Reads `instance_count` instance records from `data`, found `base` bytes into the table
+fn read_instances(
+    data: &[u8],
+    base: usize,
+    axis_count: usize,
+    instance_count: usize,
+    has_postscript_name_id: bool,
+) -> otspec::error::Result<Vec<InstanceRecord>> {
+    let mut de = OTDeserializer::from_bytes_at_offset(data, base);
+    let mut res = Vec::with_capacity(instance_count);
+    for i in 0..instance_count {
+        let field = |name: &str| format!("instances[{}].{}", i, name);
+        let subfamilyNameID = de.read_field(field("subfamilyNameID"), PhantomData::<uint16>)?;
+        let _flags = de.read_field(field("flags"), PhantomData::<uint16>)?;
+        let coordinates: Vec<i32> = de.read_field(
+            field("coordinates"),
+            CountedDeserializer::with_len(axis_count),
+        )?;
+        let postscriptNameID = if has_postscript_name_id {
+            Some(de.read_field(field("postscriptNameID"), PhantomData::<uint16>)?)
+        } else {
+            None
+        };
+        res.push(InstanceRecord {
+            subfamilyNameID,
+            coordinates: coordinates.iter().map(|x| Fixed::unpack(*x)).collect(),
+            postscriptNameID,
+        });
+    }
+    Ok(res)
+}
 
 /// Represents a font's fvar (Font Variations) table
 #[derive(Debug, PartialEq)]
@@ -95,34 +85,48 @@ pub struct fvar {
     pub instances: Vec<InstanceRecord>,
 }
 
+/// The fvar table in human-readable formats
+#[derive(Serialize, Deserialize)]
+#[serde(remote = "fvar")]
+struct FvarDef {
+    axes: Vec<VariationAxisRecord>,
+    instances: Vec<InstanceRecord>,
+}
+
+// The axis and instance arrays share one offset and are sized by header
+// fields, which `tables!` offsets cannot express, so they are read by hand
 deserialize_visitor!(
     fvar,
     FvarVisitor,
+    readable: FvarDef,
     fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
         let core = read_field!(seq, fvarcore, "an fvar table header");
         let remainder = read_remainder!(seq, "an fvar table");
         let offset = core.axesArrayOffset as usize;
         let offset_base: usize = 16;
         let axis_count = core.axisCount as usize;
-        let axis_records = &remainder[offset - offset_base..];
-        let mut de = OTDeserializer::from_bytes(axis_records);
+        let axis_records = data_at(&remainder, offset, offset_base, "the axis records")?;
+        let mut de = OTDeserializer::from_bytes_at_offset(axis_records, offset);
         let cs: CountedDeserializer<VariationAxisRecord> =
             CountedDeserializer::with_len(axis_count);
-        let axes: Vec<VariationAxisRecord> = cs
-            .deserialize(&mut de)
-            .map_err(|_| serde::de::Error::custom("Expecting a VariationAxisRecord"))?;
+        let axes: Vec<VariationAxisRecord> = de
+            .read_field("axes", cs)
+            .map_err(otspec::error::Error::nested)?;
 
-        let instance_records =
-            &remainder[offset - offset_base + (core.axisCount * core.axisSize) as usize..];
-        let mut de2 = otspec::de::Deserializer::from_bytes(instance_records);
-        let cs2 = InstanceRecordDeserializer {
-            axisCount: core.axisCount,
-            instanceCount: core.instanceCount,
-            has_postscript_name_id: core.instanceSize == core.axisCount * 4 + 6,
-        };
-        let instances: Vec<InstanceRecord> = cs2.deserialize(&mut de2).map_err(|e| {
-            serde::de::Error::custom(format!("Expecting a InstanceRecord: {:?}", e))
-        })?;
+        let instance_offset = offset + axis_count * core.axisSize as usize;
+        let instances = read_instances(
+            data_at(
+                &remainder,
+                instance_offset,
+                offset_base,
+                "the instance records",
+            )?,
+            instance_offset,
+            axis_count,
+            core.instanceCount as usize,
+            core.instanceSize as usize == axis_count * 4 + 6,
+        )
+        .map_err(otspec::error::Error::nested)?;
 
         Ok(fvar { axes, instances })
     }
@@ -133,6 +137,9 @@ impl Serialize for fvar {
     where
         S: Serializer,
     {
+        if serializer.is_human_readable() {
+            return FvarDef::serialize(self, serializer);
+        }
         let has_postscript_name_id = self.instances.iter().any(|x| x.postscriptNameID.is_some());
         if has_postscript_name_id && !self.instances.iter().all(|x| x.postscriptNameID.is_some()) {
             return Err(serde::ser::Error::custom(
@@ -308,4 +315,24 @@ mod tests {
         let serialized = otspec::ser::to_bytes(&deserialized).unwrap();
         assert_eq!(serialized, binary_fvar);
     }
+
+    #[test]
+    fn fvar_de_truncated() {
+        let binary_fvar = vec![
+            0x00, 0x01, 0x00, 0x00, 0x00, 0x10, 0x00, 0x02, 0x00, 0x02, 0x00, 0x14, 0x00, 0x04,
+            0x00, 0x0c, 0x77, 0x67, 0x68, 0x74, 0x00, 0xc8, 0x00, 0x00, 0x00, 0xc8, 0x00, 0x00,
+            0x03, 0xe8, 0x00, 0x00, 0x00, 0x00, 0x01, 0x00, 0x69, 0x74, 0x61, 0x6c, 0x00, 0x00,
+            0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x09, 0x00, 0x00, 0x00, 0x00, 0x01, 0x01,
+            0x00, 0x11, 0x00, 0x00, 0x00, 0xc8, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x01, 0x02,
+            0x00, 0x00, 0x01, 0x2c, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x01, 0x03, 0x00, 0x00,
+            0x01, 0x90, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x01, 0x04, 0x00, 0x00, 0x02, 0x58,
+            0x00, 0x00, 0x00, 0x00,
+        ];
+        let error = otspec::de::from_bytes::<fvar::fvar>(&binary_fvar)
+            .unwrap_err()
+            .to_string();
+        assert!(error.contains(
+            "instances[3].coordinates[1] at byte 100: unexpected end of input: needed 4 bytes, found 2"
+        ));
+    }
 }
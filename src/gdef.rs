@@ -0,0 +1,242 @@
+use crate::layout::{deserialize_at, ClassDef, Coverage};
+use otspec::ser::graph::{Object, ObjectGraph, ObjectId, ToObject};
+use otspec::types::*;
+use otspec::{deserialize_visitor, read_field, read_remainder};
+use otspec_macros::tables;
+use serde::de::SeqAccess;
+use serde::de::Visitor;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+/// Glyph class values used in the GDEF glyph class definition table
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum GlyphClass {
+    /// Base glyph (single character, spacing glyph)
+    Base = 1,
+    /// Ligature glyph (multiple character, spacing glyph)
+    Ligature = 2,
+    /// Mark glyph (non-spacing combining glyph)
+    Mark = 3,
+    /// Component glyph (part of single character, spacing glyph)
+    Component = 4,
+}
+
+/// The `GDEF` (Glyph definition) table
+///
+/// The attachment point list, ligature caret list and item variation store
+/// are not currently read. Tables which had them cannot be serialized, so
+/// that they are not silently lost.
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct gdef {
+    /// Assigns glyphs to one of the classes in [`GlyphClass`]
+    pub glyphClassDef: Option<ClassDef>,
+    /// Mark attachment classes, used by the `MARK_ATTACHMENT_TYPE_MASK` lookup flag
+    pub markAttachClassDef: Option<ClassDef>,
+    /// Mark glyph sets, used by the `USE_MARK_FILTERING_SET` lookup flag
+    pub markGlyphSets: Vec<Coverage>,
+    /// Whether the table had an attachment point list
+    pub hasAttachList: bool,
+    /// Whether the table had a ligature caret list
+    pub hasLigCaretList: bool,
+    /// Whether the table had an item variation store
+    pub hasItemVarStore: bool,
+}
+
+impl gdef {
+    /// Returns the glyphs in a given glyph class, in glyph ID order
+    pub fn glyphs_in_class(&self, class: GlyphClass) -> Vec<uint16> {
+        self.glyphClassDef
+            .as_ref()
+            .map(|c| c.glyphs_in_class(class as uint16))
+            .unwrap_or_default()
+    }
+}
+
+impl ToObject for gdef {
+    fn to_object(&self, graph: &mut ObjectGraph) -> otspec::error::Result<ObjectId> {
+        for (present, what) in &[
+            (self.hasAttachList, "attachment point list"),
+            (self.hasLigCaretList, "ligature caret list"),
+            (self.hasItemVarStore, "item variation store"),
+        ] {
+            if *present {
+                return Err(otspec::error::Error::Message(format!(
+                    "Cannot serialize the GDEF {}, which is not currently read",
+                    what
+                )));
+            }
+        }
+        let glyphClassDef = self
+            .glyphClassDef
+            .as_ref()
+            .map(|c| c.to_object(graph))
+            .transpose()?;
+        let markAttachClassDef = self
+            .markAttachClassDef
+            .as_ref()
+            .map(|c| c.to_object(graph))
+            .transpose()?;
+        let mut object = Object::new();
+        object.push(&1_u16)?;
+        object.push(&if self.markGlyphSets.is_empty() {
+            0_u16
+        } else {
+            2_u16
+        })?;
+        object.push_optional_offset16(glyphClassDef);
+        object.push(&0_u16)?;
+        object.push(&0_u16)?;
+        object.push_optional_offset16(markAttachClassDef);
+        if !self.markGlyphSets.is_empty() {
+            let markGlyphSets = MarkGlyphSets {
+                format: 1,
+                coverages: self.markGlyphSets.clone(),
+            };
+            object.push_offset16(markGlyphSets.to_object(graph)?);
+        }
+        Ok(graph.add(object))
+    }
+}
+
+impl Serialize for gdef {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let mut graph = ObjectGraph::new();
+        let root = self
+            .to_object(&mut graph)
+            .map_err(serde::ser::Error::custom)?;
+        let binary = graph
+            .pack(root)
+            .map_err(|_| serde::ser::Error::custom(otspec::error::Error::OffsetOverflow))?;
+        serializer.serialize_bytes(&binary)
+    }
+}
+
+tables!(MarkGlyphSets {
+    uint16 format
+    Counted(Offset32(Coverage)) coverages
+});
+
+deserialize_visitor!(
+    gdef,
+    GdefVisitor,
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+        let majorVersion = read_field!(seq, uint16, "a major version");
+        let minorVersion = read_field!(seq, uint16, "a minor version");
+        if majorVersion != 1 {
+            return Err(serde::de::Error::custom(format!(
+                "Unknown GDEF table version {:}.{:}",
+                majorVersion, minorVersion
+            )));
+        }
+        let glyphClassDefOffset = read_field!(seq, uint16, "a glyph class definition offset");
+        let attachListOffset = read_field!(seq, uint16, "an attachment list offset");
+        let ligCaretListOffset = read_field!(seq, uint16, "a ligature caret list offset");
+        let markAttachClassDefOffset =
+            read_field!(seq, uint16, "a mark attachment class definition offset");
+        let mut header_len = 12;
+        let mut markGlyphSetsDefOffset = 0;
+        let mut itemVarStoreOffset = 0;
+        if minorVersion >= 2 {
+            markGlyphSetsDefOffset = read_field!(seq, uint16, "a mark glyph sets offset");
+            header_len += 2;
+        }
+        if minorVersion >= 3 {
+            itemVarStoreOffset = read_field!(seq, uint32, "an item variation store offset");
+            header_len += 4;
+        }
+        let remainder = read_remainder!(seq, "a GDEF table");
+        let mut table = gdef {
+            hasAttachList: attachListOffset > 0,
+            hasLigCaretList: ligCaretListOffset > 0,
+            hasItemVarStore: itemVarStoreOffset > 0,
+            ..Default::default()
+        };
+        if glyphClassDefOffset > 0 {
+            table.glyphClassDef = Some(deserialize_at(
+                &remainder,
+                glyphClassDefOffset.into(),
+                header_len,
+                "glyphClassDef",
+            )?);
+        }
+        if markAttachClassDefOffset > 0 {
+            table.markAttachClassDef = Some(deserialize_at(
+                &remainder,
+                markAttachClassDefOffset.into(),
+                header_len,
+                "markAttachClassDef",
+            )?);
+        }
+        if markGlyphSetsDefOffset > 0 {
+            let sets: MarkGlyphSets = deserialize_at(
+                &remainder,
+                markGlyphSetsDefOffset.into(),
+                header_len,
+                "markGlyphSetsDef",
+            )?;
+            if sets.format != 1 {
+                return Err(serde::de::Error::custom(format!(
+                    "Unknown mark glyph sets format {:}",
+                    sets.format
+                )));
+            }
+            table.markGlyphSets = sets.coverages;
+        }
+        Ok(table)
+    }
+);
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use crate::gdef::{self, GlyphClass};
+
+    /// Version 1.3, with glyph classes, a ligature caret for glyph 3 and an
+    /// empty item variation store
+    pub(crate) const BINARY_GDEF_1_3: [u8; 62] = [
+        0x00, 0x01, 0x00, 0x03, 0x00, 0x12, 0x00, 0x00, 0x00, 0x1C, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x32, 0x00, 0x02, 0x00, 0x01, 0x00, 0x01, 0x00, 0x02, 0x00, 0x01, 0x00, 0x08,
+        0x00, 0x01, 0x00, 0x0E, 0x00, 0x00, 0x00, 0x01, 0x00, 0x01, 0x00, 0x03, 0x00, 0x01, 0x00,
+        0x04, 0x00, 0x01, 0x01, 0x2C, 0x00, 0x01, 0x00, 0x00, 0x00, 0x08, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00,
+    ];
+
+    #[test]
+    fn gdef_de_unread() {
+        let table: gdef::gdef = otspec::de::from_bytes(&BINARY_GDEF_1_3).unwrap();
+        assert_eq!(table.glyphs_in_class(GlyphClass::Base), vec![1, 2]);
+        assert!(!table.hasAttachList);
+        assert!(table.hasLigCaretList);
+        assert!(table.hasItemVarStore);
+        assert_eq!(
+            otspec::ser::to_bytes(&table).unwrap_err().to_string(),
+            "Cannot serialize the GDEF ligature caret list, which is not currently read"
+        );
+    }
+
+    #[test]
+    fn gdef_de() {
+        /* Version 1.2, with glyph classes and one mark glyph set */
+        let binary_gdef = vec![
+            0x00, 0x01, 0x00, 0x02, 0x00, 0x0E, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x1E,
+            0x00, 0x02, 0x00, 0x02, 0x00, 0x01, 0x00, 0x02, 0x00, 0x01, 0x00, 0x05, 0x00, 0x05,
+            0x00, 0x03, 0x00, 0x01, 0x00, 0x01, 0x00, 0x00, 0x00, 0x08, 0x00, 0x01, 0x00, 0x01,
+            0x00, 0x05,
+        ];
+        let table: gdef::gdef = otspec::de::from_bytes(&binary_gdef).unwrap();
+        assert_eq!(table.glyphs_in_class(GlyphClass::Base), vec![1, 2]);
+        assert_eq!(table.glyphs_in_class(GlyphClass::Mark), vec![5]);
+        assert_eq!(
+            table.glyphs_in_class(GlyphClass::Ligature),
+            Vec::<u16>::new()
+        );
+        assert!(table.markAttachClassDef.is_none());
+        assert_eq!(table.markGlyphSets.len(), 1);
+        assert_eq!(table.markGlyphSets[0].glyphs, vec![5]);
+
+        let serialized = otspec::ser::to_bytes(&table).unwrap();
+        let deserialized: gdef::gdef = otspec::de::from_bytes(&serialized).unwrap();
+        assert_eq!(deserialized, table);
+    }
+}
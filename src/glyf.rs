@@ -22,6 +22,13 @@ pub struct glyf {
     pub glyphs: Vec<Glyph>,
 }
 
+/// The glyf table in human-readable formats
+#[derive(Serialize)]
+#[serde(remote = "glyf")]
+struct GlyfDef {
+    glyphs: Vec<Glyph>,
+}
+
 stateful_deserializer!(
     glyf,
     GlyfDeserializer,
@@ -32,7 +39,7 @@ stateful_deserializer!(
     {
         let mut res = glyf { glyphs: Vec::new() };
         let remainder = read_remainder!(seq, "a glyph table");
-        for item in self.locaOffsets {
+        for (gid, item) in self.locaOffsets.into_iter().enumerate() {
             match item {
                 None => res.glyphs.push(Glyph {
                     contours: vec![],
@@ -45,13 +52,22 @@ stateful_deserializer!(
                     instructions: vec![],
                 }),
                 Some(item) => {
-                    let binary_glyf = &remainder[(item as usize)..];
+                    let binary_glyf = remainder.get(item as usize..).ok_or_else(|| {
+                        serde::de::Error::custom(format!(
+                            "Glyph {} at offset {} lies outside the glyf table",
+                            gid, item
+                        ))
+                    })?;
                     // println!("Reading glyf at item {:?}", item);
                     // println!("Reading binary glyf {:?}", binary_glyf);
-                    let glyph: Glyph =
-                        otspec::de::from_bytes(binary_glyf).map_err(|e| {
-                            serde::de::Error::custom(format!("Expecting a glyph: {:?}", e))
-                        })?;
+                    let mut de =
+                        otspec::de::Deserializer::from_bytes_at_offset(binary_glyf, item as usize);
+                    let glyph: Glyph = de
+                        .read_field(
+                            format!("glyphs[{}]", gid),
+                            std::marker::PhantomData::<Glyph>,
+                        )
+                        .map_err(otspec::error::Error::nested)?;
                     res.glyphs.push(glyph)
                 }
             }
@@ -61,11 +77,16 @@ stateful_deserializer!(
 );
 
 impl Serialize for glyf {
-    fn serialize<S>(&self, _serializer: S) -> Result<S::Ok, S::Error>
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
     where
         S: Serializer,
     {
-        panic!("Don't call this serializer, call the one in Font instead")
+        if serializer.is_human_readable() {
+            return GlyfDef::serialize(self, serializer);
+        }
+        Err(serde::ser::Error::custom(
+            "glyf cannot be serialized directly. Call compile_glyf_loca_maxp on the font instead",
+        ))
     }
 }
 
@@ -393,7 +414,7 @@ mod tests {
             0x09, 0x61, 0x63, 0x75, 0x74, 0x65, 0x63, 0x6f, 0x6d, 0x62,
         ];
         let mut deserialized: font::Font = otspec::de::from_bytes(&binary_font).unwrap();
-        deserialized.fully_deserialize();
+        deserialized.fully_deserialize().unwrap();
         let glyf = deserialized
             .get_table(b"glyf")
             .unwrap()
@@ -4,7 +4,7 @@ use kurbo::Affine;
 use otspec::types::*;
 use otspec::{deserialize_visitor, read_field};
 use serde::de::{SeqAccess, Visitor};
-use serde::{Deserialize, Deserializer, Serialize};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 
 bitflags! {
     /// Flags used when serializing/deserializing the component.
@@ -66,6 +66,48 @@ pub struct Component {
     pub flags: ComponentFlags,
 }
 
+/// Serializes an affine transformation as its six coefficients
+mod affine {
+    use kurbo::Affine;
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    pub fn serialize<S>(v: &Affine, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        v.as_coeffs().serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D>(d: D) -> Result<Affine, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        Ok(Affine::new(<[f64; 6]>::deserialize(d)?))
+    }
+}
+
+/// A component in human-readable formats
+#[derive(Serialize, Deserialize)]
+#[serde(remote = "Component")]
+struct ComponentDef {
+    glyphIndex: uint16,
+    #[serde(with = "affine")]
+    transformation: Affine,
+    matchPoints: Option<(uint16, uint16)>,
+    flags: ComponentFlags,
+}
+
+/// Components are serialized to binary as part of their glyph; this is the
+/// human-readable representation.
+impl Serialize for Component {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        ComponentDef::serialize(self, serializer)
+    }
+}
+
 impl Component {
     /// Recompute the flags prior to serialization. `more` should be true if this
     /// is not the final component in a glyph; `instructions` should be true if
@@ -111,6 +153,7 @@ impl Component {
 deserialize_visitor!(
     Component,
     ComponentVisitor,
+    readable: ComponentDef,
     fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
         let flags = read_field!(seq, ComponentFlags, "a component flag field");
         let glyphIndex = read_field!(seq, uint16, "a component glyph index");
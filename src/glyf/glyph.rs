@@ -44,13 +44,27 @@ pub struct Glyph {
     pub overlap: bool,
 }
 
+/// A glyph in human-readable formats
+#[derive(Serialize, Deserialize)]
+#[serde(remote = "Glyph")]
+struct GlyphDef {
+    xMin: int16,
+    xMax: int16,
+    yMin: int16,
+    yMax: int16,
+    contours: Vec<Vec<Point>>,
+    instructions: Vec<u8>,
+    components: Vec<Component>,
+    overlap: bool,
+}
+
 deserialize_visitor!(
     Glyph,
     GlyphVisitor,
+    readable: GlyphDef,
     fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
         // println!("Reading a glyph");
-        let maybe_num_contours = seq.next_element::<i16>()?;
-        let num_contours = maybe_num_contours.unwrap();
+        let num_contours = read_field!(seq, i16, "a number of contours");
         // println!("Num contours: {:?}", num_contours);
         let core = read_field!(seq, GlyphCore, "a glyph header");
         let mut components: Vec<Component> = vec![];
@@ -85,10 +99,14 @@ deserialize_visitor!(
             }
         } else {
             // println!("Reading {:?} contours", num_contours);
-            let mut end_pts_of_contour: Vec<usize> = (0..num_contours as usize)
-                .filter_map(|_| seq.next_element::<uint16>().unwrap())
-                .map(|x| (1 + x) as usize)
-                .collect();
+            let end_pts: Vec<uint16> = read_field_counted!(seq, num_contours, "contour end points");
+            let mut end_pts_of_contour: Vec<usize> =
+                end_pts.iter().map(|x| *x as usize + 1).collect();
+            if end_pts_of_contour.windows(2).any(|w| w[0] > w[1]) {
+                return Err(serde::de::Error::custom(
+                    "Contour end points are not in increasing order",
+                ));
+            }
             let instructions_count = read_field!(seq, i16, "a count of instruction bytes");
             if instructions_count > 0 {
                 instructions = read_field_counted!(seq, instructions_count, "instructions");
@@ -123,9 +141,9 @@ deserialize_visitor!(
                 if flags[i].contains(SimpleGlyphFlags::X_SHORT_VECTOR) {
                     let coord = read_field!(seq, u8, "an X coordinate") as i16;
                     if flags[i].contains(SimpleGlyphFlags::X_IS_SAME_OR_POSITIVE_X_SHORT_VECTOR) {
-                        last_x += coord;
+                        last_x = last_x.wrapping_add(coord);
                     } else {
-                        last_x -= coord;
+                        last_x = last_x.wrapping_sub(coord);
                     }
                     x_coords.push(last_x);
                     // println!("Read short X coordinate {:?}", coord);
@@ -138,7 +156,7 @@ deserialize_visitor!(
                 } else {
                     let coord = read_field!(seq, i16, "an X coordinate");
                     // println!("Read long X coordinate {:?}", coord);
-                    last_x += coord;
+                    last_x = last_x.wrapping_add(coord);
                     // println!("X is now {:?}", last_x);
                     x_coords.push(last_x);
                 }
@@ -147,9 +165,9 @@ deserialize_visitor!(
                 if flags[i].contains(SimpleGlyphFlags::Y_SHORT_VECTOR) {
                     let coord = read_field!(seq, u8, "a Y coordinate") as i16;
                     if flags[i].contains(SimpleGlyphFlags::Y_IS_SAME_OR_POSITIVE_Y_SHORT_VECTOR) {
-                        last_y += coord;
+                        last_y = last_y.wrapping_add(coord);
                     } else {
-                        last_y -= coord;
+                        last_y = last_y.wrapping_sub(coord);
                     }
                     // println!("Read short Y coordinate {:?}", coord);
                     // println!("Y is now {:?}", last_y);
@@ -161,7 +179,7 @@ deserialize_visitor!(
                     // println!("Y is still {:?}", last_y);
                 } else {
                     let coord = read_field!(seq, i16, "a Y coordinate");
-                    last_y += coord;
+                    last_y = last_y.wrapping_add(coord);
                     // println!("Read long Y coordinate {:?}", coord);
                     // println!("Y is now {:?}", last_y);
                     y_coords.push(last_y);
@@ -347,6 +365,7 @@ impl Glyph {
                 *acc += x;
                 Some(*acc)
             })
+            .map(|end: usize| end.saturating_sub(1))
             .collect();
 
         let mut coords: Vec<(i16, i16)> = self
@@ -358,7 +377,7 @@ impl Glyph {
         for comp in &self.components {
             let [_, _, _, _, translateX, translateY] = comp.transformation.as_coeffs();
             coords.push((translateX as i16, translateY as i16));
-            ends.push(ends.len());
+            ends.push(coords.len() - 1);
         }
 
         // Phantom points
@@ -367,13 +386,13 @@ impl Glyph {
         let top_side_y = 0;
         let bottom_side_y = 0;
         coords.push((left_side_x, 0));
-        ends.push(ends.len());
+        ends.push(coords.len() - 1);
         coords.push((right_side_x, 0));
-        ends.push(ends.len());
+        ends.push(coords.len() - 1);
         coords.push((0, top_side_y));
-        ends.push(ends.len());
+        ends.push(coords.len() - 1);
         coords.push((0, bottom_side_y));
-        ends.push(ends.len());
+        ends.push(coords.len() - 1);
         (coords, ends)
     }
 }
@@ -383,6 +402,9 @@ impl Serialize for Glyph {
     where
         S: Serializer,
     {
+        if serializer.is_human_readable() {
+            return GlyphDef::serialize(self, serializer);
+        }
         let mut seq = serializer.serialize_seq(None)?;
         if self.is_empty() {
             return seq.end();
@@ -1,8 +1,9 @@
 use kurbo::Affine;
 use otspec::types::*;
+use serde::{Deserialize, Serialize};
 
 /// Represents a point inside a glyf::Contour
-#[derive(Debug, PartialEq, Copy, Clone)]
+#[derive(Debug, PartialEq, Copy, Clone, Serialize, Deserialize)]
 pub struct Point {
     /// x-coordinate
     pub x: int16,
@@ -0,0 +1,1523 @@
+use crate::layout::{
+    deserialize_at, deserialize_seed_at, split_map, ChainedSequenceContext, ClassDef, Coverage,
+    LayoutTable, LookupSubtable, SequenceContext,
+};
+use bitflags::bitflags;
+use otspec::ser::graph::{Object, ObjectGraph, ObjectId, ToObject};
+use otspec::types::*;
+use otspec::{
+    deserialize_visitor, read_field, read_field_counted, read_remainder, stateful_deserializer,
+};
+use otspec_macros::tables;
+use serde::de::{DeserializeSeed, SeqAccess, Visitor};
+use serde::{Deserialize, Deserializer, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::ops::Range;
+
+tables!(
+    EntryExitRecord {
+        uint16 entryAnchorOffset
+        uint16 exitAnchorOffset
+    }
+    MarkRecord {
+        uint16 markClass
+        uint16 markAnchorOffset
+    }
+);
+
+bitflags! {
+    /// Flags describing which fields are present in a value record
+    #[derive(Serialize, Deserialize)]
+    pub struct ValueFormat: u16 {
+        /// Horizontal adjustment for placement
+        const X_PLACEMENT = 0x0001;
+        /// Vertical adjustment for placement
+        const Y_PLACEMENT = 0x0002;
+        /// Horizontal adjustment for advance
+        const X_ADVANCE = 0x0004;
+        /// Vertical adjustment for advance
+        const Y_ADVANCE = 0x0008;
+        /// Device table for horizontal placement
+        const X_PLACEMENT_DEVICE = 0x0010;
+        /// Device table for vertical placement
+        const Y_PLACEMENT_DEVICE = 0x0020;
+        /// Device table for horizontal advance
+        const X_ADVANCE_DEVICE = 0x0040;
+        /// Device table for vertical advance
+        const Y_ADVANCE_DEVICE = 0x0080;
+    }
+}
+
+impl ValueFormat {
+    /// The size in bytes of a value record of this format
+    pub fn record_size(&self) -> usize {
+        2 * self.bits().count_ones() as usize
+    }
+
+    /// Returns the smallest format able to hold all of the given records
+    pub fn for_records<'a>(records: impl IntoIterator<Item = &'a ValueRecord>) -> ValueFormat {
+        let mut valueFormat = ValueFormat::empty();
+        for record in records {
+            for (field, flag) in &[
+                (record.xPlacement, ValueFormat::X_PLACEMENT),
+                (record.yPlacement, ValueFormat::Y_PLACEMENT),
+                (record.xAdvance, ValueFormat::X_ADVANCE),
+                (record.yAdvance, ValueFormat::Y_ADVANCE),
+            ] {
+                if field.is_some() {
+                    valueFormat |= *flag;
+                }
+            }
+        }
+        valueFormat
+    }
+}
+
+/// An adjustment to the position of a glyph
+///
+/// Device and variation tables are not currently read. Records which had them
+/// cannot be serialized, so that they are not silently lost.
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub struct ValueRecord {
+    /// Horizontal adjustment for placement
+    pub xPlacement: Option<int16>,
+    /// Vertical adjustment for placement
+    pub yPlacement: Option<int16>,
+    /// Horizontal adjustment for advance
+    pub xAdvance: Option<int16>,
+    /// Vertical adjustment for advance
+    pub yAdvance: Option<int16>,
+    /// Whether the record had device or variation tables
+    pub hasDevices: bool,
+}
+
+impl ValueRecord {
+    /// Returns true if this record does not adjust the glyph's position
+    pub fn is_empty(&self) -> bool {
+        self.xPlacement.unwrap_or(0) == 0
+            && self.yPlacement.unwrap_or(0) == 0
+            && self.xAdvance.unwrap_or(0) == 0
+            && self.yAdvance.unwrap_or(0) == 0
+            && !self.hasDevices
+    }
+}
+
+fn read_value_record<'de, A: SeqAccess<'de>>(
+    seq: &mut A,
+    valueFormat: ValueFormat,
+) -> Result<ValueRecord, A::Error> {
+    let mut record = ValueRecord::default();
+    if valueFormat.contains(ValueFormat::X_PLACEMENT) {
+        record.xPlacement = Some(read_field!(seq, int16, "an X placement"));
+    }
+    if valueFormat.contains(ValueFormat::Y_PLACEMENT) {
+        record.yPlacement = Some(read_field!(seq, int16, "a Y placement"));
+    }
+    if valueFormat.contains(ValueFormat::X_ADVANCE) {
+        record.xAdvance = Some(read_field!(seq, int16, "an X advance"));
+    }
+    if valueFormat.contains(ValueFormat::Y_ADVANCE) {
+        record.yAdvance = Some(read_field!(seq, int16, "a Y advance"));
+    }
+    let devices = (valueFormat
+        & !(ValueFormat::X_PLACEMENT
+            | ValueFormat::Y_PLACEMENT
+            | ValueFormat::X_ADVANCE
+            | ValueFormat::Y_ADVANCE))
+        .bits()
+        .count_ones();
+    for _ in 0..devices {
+        if read_field!(seq, uint16, "a device table offset") != 0 {
+            record.hasDevices = true;
+        }
+    }
+    Ok(record)
+}
+
+fn write_value_record(
+    object: &mut Object,
+    record: &ValueRecord,
+    valueFormat: ValueFormat,
+) -> otspec::error::Result<()> {
+    if record.hasDevices {
+        return Err(otspec::error::Error::Message(
+            "Cannot serialize value record device tables, which are not currently read".into(),
+        ));
+    }
+    for (field, flag) in &[
+        (record.xPlacement, ValueFormat::X_PLACEMENT),
+        (record.yPlacement, ValueFormat::Y_PLACEMENT),
+        (record.xAdvance, ValueFormat::X_ADVANCE),
+        (record.yAdvance, ValueFormat::Y_ADVANCE),
+    ] {
+        if valueFormat.contains(*flag) {
+            object.push(&field.unwrap_or(0))?;
+        }
+    }
+    Ok(())
+}
+
+/// An anchor point, used to attach glyphs to one another
+///
+/// Device and variation tables (format 3 anchors) are not currently read.
+/// Anchors which had them cannot be serialized, so that they are not silently
+/// lost.
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub struct Anchor {
+    /// Horizontal position of the anchor
+    pub xCoordinate: int16,
+    /// Vertical position of the anchor
+    pub yCoordinate: int16,
+    /// Index of a glyph contour point used to position the anchor (format 2 anchors)
+    pub anchorPoint: Option<uint16>,
+    /// Whether the anchor had device or variation tables (format 3 anchors)
+    pub hasDevices: bool,
+}
+
+deserialize_visitor!(
+    Anchor,
+    AnchorVisitor,
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+        let format = read_field!(seq, uint16, "an anchor format");
+        let xCoordinate = read_field!(seq, int16, "an X coordinate");
+        let yCoordinate = read_field!(seq, int16, "a Y coordinate");
+        let mut hasDevices = false;
+        let anchorPoint = match format {
+            1 => None,
+            2 => Some(read_field!(seq, uint16, "an anchor point")),
+            3 => {
+                let xDeviceOffset = read_field!(seq, uint16, "a device table offset");
+                let yDeviceOffset = read_field!(seq, uint16, "a device table offset");
+                hasDevices = xDeviceOffset != 0 || yDeviceOffset != 0;
+                None
+            }
+            _ => {
+                return Err(serde::de::Error::custom(format!(
+                    "Unknown anchor format {:}",
+                    format
+                )))
+            }
+        };
+        Ok(Anchor {
+            xCoordinate,
+            yCoordinate,
+            anchorPoint,
+            hasDevices,
+        })
+    }
+);
+
+impl ToObject for Anchor {
+    fn to_object(&self, graph: &mut ObjectGraph) -> otspec::error::Result<ObjectId> {
+        if self.hasDevices {
+            return Err(otspec::error::Error::Message(
+                "Cannot serialize anchor device tables, which are not currently read".into(),
+            ));
+        }
+        let mut object = Object::new();
+        object.push(&if self.anchorPoint.is_some() {
+            2_u16
+        } else {
+            1_u16
+        })?;
+        object.push(&self.xCoordinate)?;
+        object.push(&self.yCoordinate)?;
+        if let Some(anchorPoint) = self.anchorPoint {
+            object.push(&anchorPoint)?;
+        }
+        Ok(graph.add(object))
+    }
+}
+
+fn optional_anchor_object(
+    anchor: &Option<Anchor>,
+    graph: &mut ObjectGraph,
+) -> otspec::error::Result<Option<ObjectId>> {
+    anchor.as_ref().map(|a| a.to_object(graph)).transpose()
+}
+
+fn optional_anchor<E: serde::de::Error>(
+    remainder: &[u8],
+    offset: uint16,
+    header_len: usize,
+    field: &str,
+) -> Result<Option<Anchor>, E> {
+    if offset == 0 {
+        Ok(None)
+    } else {
+        Ok(Some(deserialize_at(
+            remainder,
+            offset.into(),
+            header_len,
+            field,
+        )?))
+    }
+}
+
+/// A single adjustment subtable (lookup type 1), adjusting the position of one glyph
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct SinglePos {
+    /// A mapping of glyph IDs to adjustments
+    pub mapping: BTreeMap<uint16, ValueRecord>,
+}
+
+deserialize_visitor!(
+    SinglePos,
+    SinglePosVisitor,
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+        let format = read_field!(seq, uint16, "a single adjustment format");
+        let coverageOffset = read_field!(seq, uint16, "a coverage offset");
+        let valueFormat =
+            ValueFormat::from_bits_truncate(read_field!(seq, uint16, "a value format"));
+        let (header_len, records) = match format {
+            1 => (
+                6 + valueFormat.record_size(),
+                vec![read_value_record(&mut seq, valueFormat)?],
+            ),
+            2 => {
+                let count = read_field!(seq, uint16, "a value count");
+                let mut records = Vec::with_capacity(count.into());
+                for _ in 0..count {
+                    records.push(read_value_record(&mut seq, valueFormat)?);
+                }
+                (8 + valueFormat.record_size() * count as usize, records)
+            }
+            _ => {
+                return Err(serde::de::Error::custom(format!(
+                    "Unknown single adjustment format {:}",
+                    format
+                )))
+            }
+        };
+        let remainder = read_remainder!(seq, "a single adjustment subtable");
+        let coverage: Coverage =
+            deserialize_at(&remainder, coverageOffset.into(), header_len, "coverage")?;
+        let mapping = if format == 1 {
+            coverage.glyphs.iter().map(|&g| (g, records[0])).collect()
+        } else {
+            if records.len() != coverage.glyphs.len() {
+                return Err(serde::de::Error::custom(
+                    "Value record count does not match coverage",
+                ));
+            }
+            coverage.glyphs.into_iter().zip(records).collect()
+        };
+        Ok(SinglePos { mapping })
+    }
+);
+
+impl ToObject for SinglePos {
+    fn to_object(&self, graph: &mut ObjectGraph) -> otspec::error::Result<ObjectId> {
+        let coverage = Coverage {
+            glyphs: self.mapping.keys().copied().collect(),
+        }
+        .to_object(graph)?;
+        let valueFormat = ValueFormat::for_records(self.mapping.values());
+        let mut records = self.mapping.values();
+        let first = records.next();
+        let mut object = Object::new();
+        match first {
+            Some(record) if records.all(|r| r == record) => {
+                object.push(&1_u16)?;
+                object.push_offset16(coverage);
+                object.push(&valueFormat)?;
+                write_value_record(&mut object, record, valueFormat)?;
+            }
+            _ => {
+                object.push(&2_u16)?;
+                object.push_offset16(coverage);
+                object.push(&valueFormat)?;
+                object.push(&(self.mapping.len() as uint16))?;
+                for record in self.mapping.values() {
+                    write_value_record(&mut object, record, valueFormat)?;
+                }
+            }
+        }
+        Ok(graph.add(object))
+    }
+}
+
+/// Low-level structure used for deserializing the pairs of a pair adjustment subtable
+pub struct PairSet {
+    /// (second glyph, first value record, second value record) tuples
+    pub records: Vec<(uint16, ValueRecord, ValueRecord)>,
+}
+
+stateful_deserializer!(
+    PairSet,
+    PairSetDeserializer,
+    { valueFormat1: ValueFormat, valueFormat2: ValueFormat },
+    fn visit_seq<A>(self, mut seq: A) -> std::result::Result<PairSet, A::Error>
+    where
+        A: SeqAccess<'de>,
+    {
+        let count = read_field!(seq, uint16, "a pair value count");
+        let mut records = Vec::with_capacity(count.into());
+        for _ in 0..count {
+            let secondGlyph = read_field!(seq, uint16, "a second glyph");
+            let valueRecord1 = read_value_record(&mut seq, self.valueFormat1)?;
+            let valueRecord2 = read_value_record(&mut seq, self.valueFormat2)?;
+            records.push((secondGlyph, valueRecord1, valueRecord2));
+        }
+        Ok(PairSet { records })
+    }
+);
+
+stateful_deserializer!(
+    Vec<Vec<(ValueRecord, ValueRecord)>>,
+    ClassRecordsDeserializer,
+    {
+        valueFormat1: ValueFormat,
+        valueFormat2: ValueFormat,
+        class1Count: uint16,
+        class2Count: uint16
+    },
+    fn visit_seq<A>(
+        self,
+        mut seq: A,
+    ) -> std::result::Result<Vec<Vec<(ValueRecord, ValueRecord)>>, A::Error>
+    where
+        A: SeqAccess<'de>,
+    {
+        let mut class1Records = Vec::with_capacity(self.class1Count.into());
+        for _ in 0..self.class1Count {
+            let mut class2Records = Vec::with_capacity(self.class2Count.into());
+            for _ in 0..self.class2Count {
+                let vr1 = read_value_record(&mut seq, self.valueFormat1)?;
+                let vr2 = read_value_record(&mut seq, self.valueFormat2)?;
+                class2Records.push((vr1, vr2));
+            }
+            class1Records.push(class2Records);
+        }
+        Ok(class1Records)
+    }
+);
+
+/// A pair adjustment subtable for individual glyph pairs (lookup type 2, format 1)
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct PairPosFormat1 {
+    /// A mapping of (first, second) glyph ID pairs to adjustments for each glyph
+    pub mapping: BTreeMap<(uint16, uint16), (ValueRecord, ValueRecord)>,
+}
+
+/// A pair adjustment subtable for pairs of glyph classes (lookup type 2, format 2)
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct PairPosFormat2 {
+    /// The first glyphs of the pairs covered by this subtable
+    pub coverage: Coverage,
+    /// Class definitions for the first glyph of each pair
+    pub classDef1: ClassDef,
+    /// Class definitions for the second glyph of each pair
+    pub classDef2: ClassDef,
+    /// Adjustments for each glyph, indexed by first class and then second class
+    pub class1Records: Vec<Vec<(ValueRecord, ValueRecord)>>,
+}
+
+impl PairPosFormat1 {
+    fn split(&self) -> Option<(Self, Self)> {
+        // Pairs with the same first glyph share a pair set, so split between pair sets
+        let firsts: BTreeSet<uint16> = self.mapping.keys().map(|(first, _)| *first).collect();
+        if firsts.len() < 2 {
+            return None;
+        }
+        let middle = *firsts.iter().nth(firsts.len() / 2)?;
+        let mut first = self.mapping.clone();
+        let second = first.split_off(&(middle, 0));
+        Some((
+            PairPosFormat1 { mapping: first },
+            PairPosFormat1 { mapping: second },
+        ))
+    }
+}
+
+impl ToObject for PairPosFormat1 {
+    fn to_object(&self, graph: &mut ObjectGraph) -> otspec::error::Result<ObjectId> {
+        let mut sets: BTreeMap<uint16, Vec<_>> = BTreeMap::new();
+        for (&(first, second), &(vr1, vr2)) in &self.mapping {
+            sets.entry(first).or_default().push((second, vr1, vr2));
+        }
+        let coverage = Coverage {
+            glyphs: sets.keys().copied().collect(),
+        }
+        .to_object(graph)?;
+        let valueFormat1 = ValueFormat::for_records(self.mapping.values().map(|(vr1, _)| vr1));
+        let valueFormat2 = ValueFormat::for_records(self.mapping.values().map(|(_, vr2)| vr2));
+        let mut object = Object::new();
+        object.push(&1_u16)?;
+        object.push_offset16(coverage);
+        object.push(&valueFormat1)?;
+        object.push(&valueFormat2)?;
+        object.push(&(sets.len() as uint16))?;
+        for records in sets.values() {
+            let mut set = Object::new();
+            set.push(&(records.len() as uint16))?;
+            for (second, vr1, vr2) in records {
+                set.push(second)?;
+                write_value_record(&mut set, vr1, valueFormat1)?;
+                write_value_record(&mut set, vr2, valueFormat2)?;
+            }
+            object.push_offset16(graph.add(set));
+        }
+        Ok(graph.add(object))
+    }
+}
+
+impl PairPosFormat2 {
+    /// Returns a subtable covering only the given first glyphs, with the first
+    /// glyph classes renumbered to those used by the glyphs
+    fn restricted_to(&self, glyphs: &[uint16]) -> Self {
+        let used: BTreeSet<uint16> = glyphs.iter().map(|&g| self.classDef1.get(g)).collect();
+        let mut renumbering = BTreeMap::new();
+        let mut class1Records = vec![self.class1Records.first().cloned().unwrap_or_default()];
+        for &class in used.iter().filter(|&&c| c != 0) {
+            renumbering.insert(class, class1Records.len() as uint16);
+            class1Records.push(
+                self.class1Records
+                    .get(class as usize)
+                    .cloned()
+                    .unwrap_or_default(),
+            );
+        }
+        PairPosFormat2 {
+            coverage: Coverage {
+                glyphs: glyphs.to_vec(),
+            },
+            classDef1: ClassDef {
+                classes: glyphs
+                    .iter()
+                    .filter_map(|&g| renumbering.get(&self.classDef1.get(g)).map(|&c| (g, c)))
+                    .collect(),
+            },
+            classDef2: self.classDef2.clone(),
+            class1Records,
+        }
+    }
+
+    fn split(&self) -> Option<(Self, Self)> {
+        if self.coverage.glyphs.len() < 2 {
+            return None;
+        }
+        let (first, second) = self
+            .coverage
+            .glyphs
+            .split_at(self.coverage.glyphs.len() / 2);
+        Some((self.restricted_to(first), self.restricted_to(second)))
+    }
+}
+
+impl ToObject for PairPosFormat2 {
+    fn to_object(&self, graph: &mut ObjectGraph) -> otspec::error::Result<ObjectId> {
+        let coverage = self.coverage.to_object(graph)?;
+        let classDef1 = self.classDef1.to_object(graph)?;
+        let classDef2 = self.classDef2.to_object(graph)?;
+        let records = self.class1Records.iter().flatten();
+        let valueFormat1 = ValueFormat::for_records(records.clone().map(|(vr1, _)| vr1));
+        let valueFormat2 = ValueFormat::for_records(records.map(|(_, vr2)| vr2));
+        let class2Count = self.class1Records.first().map_or(0, |r| r.len());
+        if self.class1Records.iter().any(|r| r.len() != class2Count) {
+            return Err(otspec::error::Error::Message(
+                "Class pair adjustment rows differ in length".to_string(),
+            ));
+        }
+        let mut object = Object::new();
+        object.push(&2_u16)?;
+        object.push_offset16(coverage);
+        object.push(&valueFormat1)?;
+        object.push(&valueFormat2)?;
+        object.push_offset16(classDef1);
+        object.push_offset16(classDef2);
+        object.push(&(self.class1Records.len() as uint16))?;
+        object.push(&(class2Count as uint16))?;
+        for (vr1, vr2) in self.class1Records.iter().flatten() {
+            write_value_record(&mut object, vr1, valueFormat1)?;
+            write_value_record(&mut object, vr2, valueFormat2)?;
+        }
+        Ok(graph.add(object))
+    }
+}
+
+/// Either format of pair adjustment subtable (lookup type 2)
+enum PairPos {
+    Format1(PairPosFormat1),
+    Format2(PairPosFormat2),
+}
+
+deserialize_visitor!(
+    PairPos,
+    PairPosVisitor,
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+        let format = read_field!(seq, uint16, "a pair adjustment format");
+        let coverageOffset = read_field!(seq, uint16, "a coverage offset");
+        let valueFormat1 =
+            ValueFormat::from_bits_truncate(read_field!(seq, uint16, "a value format"));
+        let valueFormat2 =
+            ValueFormat::from_bits_truncate(read_field!(seq, uint16, "a value format"));
+        match format {
+            1 => {
+                let count = read_field!(seq, uint16, "a pair set count");
+                let offsets: Vec<uint16> = read_field_counted!(seq, count, "pair set offsets");
+                let header_len = 10 + 2 * count as usize;
+                let remainder = read_remainder!(seq, "a pair adjustment subtable");
+                let coverage: Coverage =
+                    deserialize_at(&remainder, coverageOffset.into(), header_len, "coverage")?;
+                if coverage.glyphs.len() != offsets.len() {
+                    return Err(serde::de::Error::custom(
+                        "Pair set count does not match coverage",
+                    ));
+                }
+                let mut mapping = BTreeMap::new();
+                for (i, (first, offset)) in coverage.glyphs.into_iter().zip(offsets).enumerate() {
+                    let pairset = deserialize_seed_at(
+                        &remainder,
+                        offset.into(),
+                        header_len,
+                        &format!("pairSets[{}]", i),
+                        PairSetDeserializer {
+                            valueFormat1,
+                            valueFormat2,
+                        },
+                    )?;
+                    for (second, vr1, vr2) in pairset.records {
+                        mapping.insert((first, second), (vr1, vr2));
+                    }
+                }
+                Ok(PairPos::Format1(PairPosFormat1 { mapping }))
+            }
+            2 => {
+                let classDef1Offset = read_field!(seq, uint16, "a class definition offset");
+                let classDef2Offset = read_field!(seq, uint16, "a class definition offset");
+                let class1Count = read_field!(seq, uint16, "a class count");
+                let class2Count = read_field!(seq, uint16, "a class count");
+                let header_len = 16;
+                let remainder = read_remainder!(seq, "a pair adjustment subtable");
+                // Check the records fit before reading them, as empty value
+                // formats would otherwise allow 65535 x 65535 empty records
+                let records = class1Count as usize * class2Count as usize;
+                let record_size = valueFormat1.record_size() + valueFormat2.record_size();
+                if records > 0 && (record_size == 0 || records * record_size > remainder.len()) {
+                    return Err(serde::de::Error::custom(format!(
+                        "{:} x {:} class records of {:} bytes do not fit in the subtable",
+                        class1Count, class2Count, record_size
+                    )));
+                }
+                let class1Records = deserialize_seed_at(
+                    &remainder,
+                    header_len,
+                    header_len,
+                    "class1Records",
+                    ClassRecordsDeserializer {
+                        valueFormat1,
+                        valueFormat2,
+                        class1Count,
+                        class2Count,
+                    },
+                )?;
+                Ok(PairPos::Format2(PairPosFormat2 {
+                    coverage: deserialize_at(
+                        &remainder,
+                        coverageOffset.into(),
+                        header_len,
+                        "coverage",
+                    )?,
+                    classDef1: deserialize_at(
+                        &remainder,
+                        classDef1Offset.into(),
+                        header_len,
+                        "classDef1",
+                    )?,
+                    classDef2: deserialize_at(
+                        &remainder,
+                        classDef2Offset.into(),
+                        header_len,
+                        "classDef2",
+                    )?,
+                    class1Records,
+                }))
+            }
+            _ => Err(serde::de::Error::custom(format!(
+                "Unknown pair adjustment format {:}",
+                format
+            ))),
+        }
+    }
+);
+
+/// A cursive attachment subtable (lookup type 3)
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct CursivePos {
+    /// A mapping of glyph IDs to their (entry, exit) anchors
+    pub mapping: BTreeMap<uint16, (Option<Anchor>, Option<Anchor>)>,
+}
+
+deserialize_visitor!(
+    CursivePos,
+    CursivePosVisitor,
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+        let format = read_field!(seq, uint16, "a cursive attachment format");
+        if format != 1 {
+            return Err(serde::de::Error::custom(format!(
+                "Unknown cursive attachment format {:}",
+                format
+            )));
+        }
+        let coverageOffset = read_field!(seq, uint16, "a coverage offset");
+        let count = read_field!(seq, uint16, "an entry/exit count");
+        let records: Vec<EntryExitRecord> = read_field_counted!(seq, count, "entry/exit records");
+        let header_len = 6 + 4 * count as usize;
+        let remainder = read_remainder!(seq, "a cursive attachment subtable");
+        let coverage: Coverage =
+            deserialize_at(&remainder, coverageOffset.into(), header_len, "coverage")?;
+        if coverage.glyphs.len() != records.len() {
+            return Err(serde::de::Error::custom(
+                "Entry/exit record count does not match coverage",
+            ));
+        }
+        let mut mapping = BTreeMap::new();
+        for (i, (glyph, record)) in coverage.glyphs.into_iter().zip(records).enumerate() {
+            let field = |name: &str| format!("entryExitRecords[{}].{}", i, name);
+            mapping.insert(
+                glyph,
+                (
+                    optional_anchor(
+                        &remainder,
+                        record.entryAnchorOffset,
+                        header_len,
+                        &field("entryAnchor"),
+                    )?,
+                    optional_anchor(
+                        &remainder,
+                        record.exitAnchorOffset,
+                        header_len,
+                        &field("exitAnchor"),
+                    )?,
+                ),
+            );
+        }
+        Ok(CursivePos { mapping })
+    }
+);
+
+impl ToObject for CursivePos {
+    fn to_object(&self, graph: &mut ObjectGraph) -> otspec::error::Result<ObjectId> {
+        let coverage = Coverage {
+            glyphs: self.mapping.keys().copied().collect(),
+        }
+        .to_object(graph)?;
+        let mut object = Object::new();
+        object.push(&1_u16)?;
+        object.push_offset16(coverage);
+        object.push(&(self.mapping.len() as uint16))?;
+        for (entry, exit) in self.mapping.values() {
+            let entry = optional_anchor_object(entry, graph)?;
+            let exit = optional_anchor_object(exit, graph)?;
+            object.push_optional_offset16(entry);
+            object.push_optional_offset16(exit);
+        }
+        Ok(graph.add(object))
+    }
+}
+
+struct MarkArray {
+    marks: Vec<(uint16, Anchor)>,
+}
+
+deserialize_visitor!(
+    MarkArray,
+    MarkArrayVisitor,
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+        let count = read_field!(seq, uint16, "a mark count");
+        let records: Vec<MarkRecord> = read_field_counted!(seq, count, "mark records");
+        let header_len = 2 + 4 * count as usize;
+        let remainder = read_remainder!(seq, "a mark array");
+        let mut marks = Vec::with_capacity(records.len());
+        for (i, record) in records.into_iter().enumerate() {
+            marks.push((
+                record.markClass,
+                deserialize_at(
+                    &remainder,
+                    record.markAnchorOffset.into(),
+                    header_len,
+                    &format!("marks[{}]", i),
+                )?,
+            ));
+        }
+        Ok(MarkArray { marks })
+    }
+);
+
+fn mark_array_to_object(
+    marks: &BTreeMap<uint16, (uint16, Anchor)>,
+    graph: &mut ObjectGraph,
+) -> otspec::error::Result<ObjectId> {
+    let mut object = Object::new();
+    object.push(&(marks.len() as uint16))?;
+    for (class, anchor) in marks.values() {
+        let anchor = anchor.to_object(graph)?;
+        object.push(class)?;
+        object.push_offset16(anchor);
+    }
+    Ok(graph.add(object))
+}
+
+/// Adds a matrix of anchors to the graph, padding each row to `classCount` entries
+fn anchor_matrix_to_object<'a>(
+    rows: impl ExactSizeIterator<Item = &'a Vec<Option<Anchor>>>,
+    classCount: uint16,
+    graph: &mut ObjectGraph,
+) -> otspec::error::Result<ObjectId> {
+    let mut object = Object::new();
+    object.push(&(rows.len() as uint16))?;
+    for row in rows {
+        for class in 0..classCount as usize {
+            let anchor = optional_anchor_object(row.get(class).unwrap_or(&None), graph)?;
+            object.push_optional_offset16(anchor);
+        }
+    }
+    Ok(graph.add(object))
+}
+
+/// Low-level structure used for deserializing rows of anchors indexed by mark
+/// class, as found in base, mark-to-mark and ligature component arrays
+pub struct AnchorMatrix {
+    /// Rows of anchors, indexed by mark class
+    pub rows: Vec<Vec<Option<Anchor>>>,
+}
+
+stateful_deserializer!(
+    AnchorMatrix,
+    AnchorMatrixDeserializer,
+    { classCount: uint16 },
+    fn visit_seq<A>(self, mut seq: A) -> std::result::Result<AnchorMatrix, A::Error>
+    where
+        A: SeqAccess<'de>,
+    {
+        let count = read_field!(seq, uint16, "a record count");
+        let classCount = self.classCount as usize;
+        let offsets: Vec<uint16> =
+            read_field_counted!(seq, count as usize * classCount, "anchor offsets");
+        let header_len = 2 + 2 * offsets.len();
+        let remainder = read_remainder!(seq, "an anchor array");
+        let mut rows = Vec::with_capacity(count.into());
+        for row in 0..count as usize {
+            let mut anchors = Vec::with_capacity(classCount);
+            for (class, offset) in offsets[row * classCount..(row + 1) * classCount]
+                .iter()
+                .enumerate()
+            {
+                let field = format!("rows[{}][{}]", row, class);
+                anchors.push(optional_anchor(&remainder, *offset, header_len, &field)?);
+            }
+            rows.push(anchors);
+        }
+        Ok(AnchorMatrix { rows })
+    }
+);
+
+/// Low-level structure used for deserializing mark-to-ligature anchors
+pub struct LigatureArray {
+    /// Anchors for each ligature, indexed by component and then mark class
+    pub ligatures: Vec<Vec<Vec<Option<Anchor>>>>,
+}
+
+stateful_deserializer!(
+    LigatureArray,
+    LigatureArrayDeserializer,
+    { classCount: uint16 },
+    fn visit_seq<A>(self, mut seq: A) -> std::result::Result<LigatureArray, A::Error>
+    where
+        A: SeqAccess<'de>,
+    {
+        let count = read_field!(seq, uint16, "a ligature count");
+        let offsets: Vec<uint16> = read_field_counted!(seq, count, "ligature attach offsets");
+        let header_len = 2 + 2 * offsets.len();
+        let remainder = read_remainder!(seq, "a ligature array");
+        let mut ligatures = Vec::with_capacity(offsets.len());
+        for (i, offset) in offsets.into_iter().enumerate() {
+            let components: AnchorMatrix = deserialize_seed_at(
+                &remainder,
+                offset.into(),
+                header_len,
+                &format!("ligatures[{}]", i),
+                AnchorMatrixDeserializer {
+                    classCount: self.classCount,
+                },
+            )?;
+            ligatures.push(components.rows);
+        }
+        Ok(LigatureArray { ligatures })
+    }
+);
+
+/// A mark attachment subtable (lookup types 4 and 6)
+///
+/// This is used both for attaching marks to base glyphs (lookup type 4) and
+/// for attaching marks to other marks (lookup type 6).
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct MarkBasePos {
+    /// A mapping of mark glyph IDs to their (mark class, anchor)
+    pub marks: BTreeMap<uint16, (uint16, Anchor)>,
+    /// A mapping of base glyph IDs to their anchors, indexed by mark class
+    pub bases: BTreeMap<uint16, Vec<Option<Anchor>>>,
+}
+
+/// Returns the marks whose class falls within `classes`, renumbering their
+/// classes to start from zero
+fn marks_in_classes(
+    marks: &BTreeMap<uint16, (uint16, Anchor)>,
+    classes: &Range<uint16>,
+) -> BTreeMap<uint16, (uint16, Anchor)> {
+    marks
+        .iter()
+        .filter(|(_, (class, _))| classes.contains(class))
+        .map(|(&glyph, &(class, anchor))| (glyph, (class - classes.start, anchor)))
+        .collect()
+}
+
+/// Returns the anchors of a row which fall within `classes`
+fn row_in_classes(row: &[Option<Anchor>], classes: &Range<uint16>) -> Vec<Option<Anchor>> {
+    row.iter()
+        .skip(classes.start as usize)
+        .take(classes.len())
+        .copied()
+        .collect()
+}
+
+impl MarkBasePos {
+    /// The number of mark classes used by this subtable
+    pub fn class_count(&self) -> uint16 {
+        let marks = self.marks.values().map(|(class, _)| class + 1);
+        let bases = self.bases.values().map(|row| row.len() as uint16);
+        marks.chain(bases).max().unwrap_or(0)
+    }
+
+    fn restricted_to(&self, classes: Range<uint16>) -> Self {
+        MarkBasePos {
+            marks: marks_in_classes(&self.marks, &classes),
+            bases: self
+                .bases
+                .iter()
+                .map(|(&glyph, row)| (glyph, row_in_classes(row, &classes)))
+                .filter(|(_, row)| row.iter().any(Option::is_some))
+                .collect(),
+        }
+    }
+
+    fn split(&self) -> Option<(Self, Self)> {
+        let classCount = self.class_count();
+        if classCount >= 2 {
+            let middle = classCount / 2;
+            Some((
+                self.restricted_to(0..middle),
+                self.restricted_to(middle..classCount),
+            ))
+        } else {
+            let (first, second) = split_map(&self.bases)?;
+            Some((
+                MarkBasePos {
+                    marks: self.marks.clone(),
+                    bases: first,
+                },
+                MarkBasePos {
+                    marks: self.marks.clone(),
+                    bases: second,
+                },
+            ))
+        }
+    }
+}
+
+impl ToObject for MarkBasePos {
+    fn to_object(&self, graph: &mut ObjectGraph) -> otspec::error::Result<ObjectId> {
+        let classCount = self.class_count();
+        let baseArray = anchor_matrix_to_object(self.bases.values(), classCount, graph)?;
+        mark_attachment_to_object(
+            &self.marks,
+            self.bases.keys().copied().collect(),
+            classCount,
+            baseArray,
+            graph,
+        )
+    }
+}
+
+/// A mark-to-ligature attachment subtable (lookup type 5)
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct MarkLigPos {
+    /// A mapping of mark glyph IDs to their (mark class, anchor)
+    pub marks: BTreeMap<uint16, (uint16, Anchor)>,
+    /// A mapping of ligature glyph IDs to anchors for each component, indexed
+    /// by component and then mark class
+    pub ligatures: BTreeMap<uint16, Vec<Vec<Option<Anchor>>>>,
+}
+
+impl MarkLigPos {
+    /// The number of mark classes used by this subtable
+    pub fn class_count(&self) -> uint16 {
+        let marks = self.marks.values().map(|(class, _)| class + 1);
+        let ligatures = self
+            .ligatures
+            .values()
+            .flatten()
+            .map(|row| row.len() as uint16);
+        marks.chain(ligatures).max().unwrap_or(0)
+    }
+
+    fn restricted_to(&self, classes: Range<uint16>) -> Self {
+        MarkLigPos {
+            marks: marks_in_classes(&self.marks, &classes),
+            ligatures: self
+                .ligatures
+                .iter()
+                .map(|(&glyph, components)| {
+                    let components: Vec<_> = components
+                        .iter()
+                        .map(|row| row_in_classes(row, &classes))
+                        .collect();
+                    (glyph, components)
+                })
+                .filter(|(_, components)| components.iter().flatten().any(Option::is_some))
+                .collect(),
+        }
+    }
+
+    fn split(&self) -> Option<(Self, Self)> {
+        let classCount = self.class_count();
+        if classCount >= 2 {
+            let middle = classCount / 2;
+            Some((
+                self.restricted_to(0..middle),
+                self.restricted_to(middle..classCount),
+            ))
+        } else {
+            let (first, second) = split_map(&self.ligatures)?;
+            Some((
+                MarkLigPos {
+                    marks: self.marks.clone(),
+                    ligatures: first,
+                },
+                MarkLigPos {
+                    marks: self.marks.clone(),
+                    ligatures: second,
+                },
+            ))
+        }
+    }
+}
+
+impl ToObject for MarkLigPos {
+    fn to_object(&self, graph: &mut ObjectGraph) -> otspec::error::Result<ObjectId> {
+        let classCount = self.class_count();
+        let mut ligatureArray = Object::new();
+        ligatureArray.push(&(self.ligatures.len() as uint16))?;
+        for components in self.ligatures.values() {
+            let attach = anchor_matrix_to_object(components.iter(), classCount, graph)?;
+            ligatureArray.push_offset16(attach);
+        }
+        let ligatureArray = graph.add(ligatureArray);
+        mark_attachment_to_object(
+            &self.marks,
+            self.ligatures.keys().copied().collect(),
+            classCount,
+            ligatureArray,
+            graph,
+        )
+    }
+}
+
+/// Adds the parts of a mark attachment subtable shared by all mark lookup types
+fn mark_attachment_to_object(
+    marks: &BTreeMap<uint16, (uint16, Anchor)>,
+    bases: Vec<uint16>,
+    classCount: uint16,
+    baseArray: ObjectId,
+    graph: &mut ObjectGraph,
+) -> otspec::error::Result<ObjectId> {
+    let markCoverage = Coverage {
+        glyphs: marks.keys().copied().collect(),
+    }
+    .to_object(graph)?;
+    let baseCoverage = Coverage { glyphs: bases }.to_object(graph)?;
+    let markArray = mark_array_to_object(marks, graph)?;
+    let mut object = Object::new();
+    object.push(&1_u16)?;
+    object.push_offset16(markCoverage);
+    object.push_offset16(baseCoverage);
+    object.push(&classCount)?;
+    object.push_offset16(markArray);
+    object.push_offset16(baseArray);
+    Ok(graph.add(object))
+}
+
+/// The parts of a mark attachment subtable shared by all mark lookup types
+struct MarkAttachment {
+    marks: BTreeMap<uint16, (uint16, Anchor)>,
+    bases: Vec<uint16>,
+    classCount: uint16,
+    baseArrayOffset: uint16,
+    remainder: Vec<u8>,
+}
+
+const MARK_ATTACHMENT_HEADER_LEN: usize = 12;
+
+deserialize_visitor!(
+    MarkAttachment,
+    MarkAttachmentVisitor,
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+        let format = read_field!(seq, uint16, "a mark attachment format");
+        if format != 1 {
+            return Err(serde::de::Error::custom(format!(
+                "Unknown mark attachment format {:}",
+                format
+            )));
+        }
+        let markCoverageOffset = read_field!(seq, uint16, "a mark coverage offset");
+        let baseCoverageOffset = read_field!(seq, uint16, "a base coverage offset");
+        let classCount = read_field!(seq, uint16, "a mark class count");
+        let markArrayOffset = read_field!(seq, uint16, "a mark array offset");
+        let baseArrayOffset = read_field!(seq, uint16, "a base array offset");
+        let remainder = read_remainder!(seq, "a mark attachment subtable");
+        let header_len = MARK_ATTACHMENT_HEADER_LEN;
+        let markCoverage: Coverage = deserialize_at(
+            &remainder,
+            markCoverageOffset.into(),
+            header_len,
+            "markCoverage",
+        )?;
+        let baseCoverage: Coverage = deserialize_at(
+            &remainder,
+            baseCoverageOffset.into(),
+            header_len,
+            "baseCoverage",
+        )?;
+        let markArray: MarkArray =
+            deserialize_at(&remainder, markArrayOffset.into(), header_len, "markArray")?;
+        if markArray.marks.len() != markCoverage.glyphs.len() {
+            return Err(serde::de::Error::custom(
+                "Mark count does not match coverage",
+            ));
+        }
+        if markArray
+            .marks
+            .iter()
+            .any(|(class, _)| *class >= classCount)
+        {
+            return Err(serde::de::Error::custom("Mark class out of range"));
+        }
+        Ok(MarkAttachment {
+            marks: markCoverage
+                .glyphs
+                .into_iter()
+                .zip(markArray.marks)
+                .collect(),
+            bases: baseCoverage.glyphs,
+            classCount,
+            baseArrayOffset,
+            remainder,
+        })
+    }
+);
+
+impl MarkAttachment {
+    fn into_mark_base<E: serde::de::Error>(self) -> Result<MarkBasePos, E> {
+        let matrix: AnchorMatrix = deserialize_seed_at(
+            &self.remainder,
+            self.baseArrayOffset.into(),
+            MARK_ATTACHMENT_HEADER_LEN,
+            "baseArray",
+            AnchorMatrixDeserializer {
+                classCount: self.classCount,
+            },
+        )?;
+        if matrix.rows.len() != self.bases.len() {
+            return Err(E::custom("Base count does not match coverage"));
+        }
+        Ok(MarkBasePos {
+            marks: self.marks,
+            bases: self.bases.into_iter().zip(matrix.rows).collect(),
+        })
+    }
+
+    fn into_mark_lig<E: serde::de::Error>(self) -> Result<MarkLigPos, E> {
+        let array: LigatureArray = deserialize_seed_at(
+            &self.remainder,
+            self.baseArrayOffset.into(),
+            MARK_ATTACHMENT_HEADER_LEN,
+            "ligatureArray",
+            LigatureArrayDeserializer {
+                classCount: self.classCount,
+            },
+        )?;
+        if array.ligatures.len() != self.bases.len() {
+            return Err(E::custom("Ligature count does not match coverage"));
+        }
+        Ok(MarkLigPos {
+            marks: self.marks,
+            ligatures: self.bases.into_iter().zip(array.ligatures).collect(),
+        })
+    }
+}
+
+/// A subtable of a `GPOS` lookup
+#[derive(Debug, PartialEq, Clone)]
+pub enum Positioning {
+    /// Single adjustment (lookup type 1)
+    Single(SinglePos),
+    /// Pair adjustment of individual glyphs (lookup type 2, format 1)
+    PairGlyphs(PairPosFormat1),
+    /// Pair adjustment of glyph classes (lookup type 2, format 2)
+    PairClasses(PairPosFormat2),
+    /// Cursive attachment (lookup type 3)
+    Cursive(CursivePos),
+    /// Mark-to-base attachment (lookup type 4)
+    MarkToBase(MarkBasePos),
+    /// Mark-to-ligature attachment (lookup type 5)
+    MarkToLigature(MarkLigPos),
+    /// Mark-to-mark attachment (lookup type 6)
+    MarkToMark(MarkBasePos),
+    /// Contextual positioning by glyph or class rules (lookup type 7, formats
+    /// 1 and 2)
+    ContextRules(SequenceContext),
+    /// Contextual positioning by coverages (lookup type 7, format 3)
+    Context(ChainedSequenceContext),
+    /// Chained contextual positioning by glyph or class rules (lookup type 8,
+    /// formats 1 and 2)
+    ChainedContextRules(SequenceContext),
+    /// Chained contextual positioning by coverages (lookup type 8, format 3)
+    ChainedContext(ChainedSequenceContext),
+    /// A subtable of a lookup type or format which is not currently supported
+    Unsupported {
+        /// The subtable's format
+        format: uint16,
+    },
+}
+
+impl LookupSubtable for Positioning {
+    const EXTENSION_TYPE: uint16 = 9;
+
+    fn from_lookup_bytes(lookupType: uint16, data: &[u8]) -> otspec::error::Result<Self> {
+        let format: uint16 = otspec::de::from_bytes(data)?;
+        Ok(match (lookupType, format) {
+            (1, _) => Positioning::Single(otspec::de::from_bytes(data)?),
+            (2, _) => match otspec::de::from_bytes(data)? {
+                PairPos::Format1(p) => Positioning::PairGlyphs(p),
+                PairPos::Format2(p) => Positioning::PairClasses(p),
+            },
+            (3, _) => Positioning::Cursive(otspec::de::from_bytes(data)?),
+            (4, _) => Positioning::MarkToBase(
+                otspec::de::from_bytes::<MarkAttachment>(data)?.into_mark_base()?,
+            ),
+            (5, _) => Positioning::MarkToLigature(
+                otspec::de::from_bytes::<MarkAttachment>(data)?.into_mark_lig()?,
+            ),
+            (6, _) => Positioning::MarkToMark(
+                otspec::de::from_bytes::<MarkAttachment>(data)?.into_mark_base()?,
+            ),
+            (7, 1) | (7, 2) => Positioning::ContextRules(SequenceContext::from_bytes(data, false)?),
+            (7, 3) => Positioning::Context(ChainedSequenceContext::from_context_bytes(data)?),
+            (8, 1) | (8, 2) => {
+                Positioning::ChainedContextRules(SequenceContext::from_bytes(data, true)?)
+            }
+            (8, 3) => Positioning::ChainedContext(otspec::de::from_bytes(data)?),
+            _ => Positioning::Unsupported { format },
+        })
+    }
+
+    fn split(&self) -> Option<(Self, Self)> {
+        match self {
+            Positioning::Single(s) => split_map(&s.mapping).map(|(a, b)| {
+                (
+                    Positioning::Single(SinglePos { mapping: a }),
+                    Positioning::Single(SinglePos { mapping: b }),
+                )
+            }),
+            Positioning::PairGlyphs(p) => p
+                .split()
+                .map(|(a, b)| (Positioning::PairGlyphs(a), Positioning::PairGlyphs(b))),
+            Positioning::PairClasses(p) => p
+                .split()
+                .map(|(a, b)| (Positioning::PairClasses(a), Positioning::PairClasses(b))),
+            Positioning::Cursive(c) => split_map(&c.mapping).map(|(a, b)| {
+                (
+                    Positioning::Cursive(CursivePos { mapping: a }),
+                    Positioning::Cursive(CursivePos { mapping: b }),
+                )
+            }),
+            Positioning::MarkToBase(m) => m
+                .split()
+                .map(|(a, b)| (Positioning::MarkToBase(a), Positioning::MarkToBase(b))),
+            Positioning::MarkToLigature(m) => m.split().map(|(a, b)| {
+                (
+                    Positioning::MarkToLigature(a),
+                    Positioning::MarkToLigature(b),
+                )
+            }),
+            Positioning::MarkToMark(m) => m
+                .split()
+                .map(|(a, b)| (Positioning::MarkToMark(a), Positioning::MarkToMark(b))),
+            Positioning::ContextRules(_)
+            | Positioning::Context(_)
+            | Positioning::ChainedContextRules(_)
+            | Positioning::ChainedContext(_)
+            | Positioning::Unsupported { .. } => None,
+        }
+    }
+}
+
+impl Positioning {
+    /// The number of glyphs the subtable matches from the current glyph
+    /// onwards, as counted for the OS/2 `usMaxContext` field
+    ///
+    /// Attachment subtables are not counted, as in fontTools, and neither are
+    /// unsupported subtables, whose rules are not known.
+    pub fn max_context(&self) -> usize {
+        match self {
+            Positioning::Single(_) => 1,
+            Positioning::PairGlyphs(_) | Positioning::PairClasses(_) => 2,
+            Positioning::ContextRules(c) | Positioning::ChainedContextRules(c) => c.max_context(),
+            Positioning::Context(c) | Positioning::ChainedContext(c) => c.max_context(),
+            Positioning::Cursive(_)
+            | Positioning::MarkToBase(_)
+            | Positioning::MarkToLigature(_)
+            | Positioning::MarkToMark(_)
+            | Positioning::Unsupported { .. } => 0,
+        }
+    }
+
+    /// Returns true if any value record or anchor of the subtable had device
+    /// or variation tables, which are not currently read
+    pub fn has_devices(&self) -> bool {
+        let anchors = |anchors: &[Option<Anchor>]| anchors.iter().flatten().any(|a| a.hasDevices);
+        match self {
+            Positioning::Single(s) => s.mapping.values().any(|vr| vr.hasDevices),
+            Positioning::PairGlyphs(p) => p
+                .mapping
+                .values()
+                .any(|(vr1, vr2)| vr1.hasDevices || vr2.hasDevices),
+            Positioning::PairClasses(p) => p
+                .class1Records
+                .iter()
+                .flatten()
+                .any(|(vr1, vr2)| vr1.hasDevices || vr2.hasDevices),
+            Positioning::Cursive(c) => c
+                .mapping
+                .values()
+                .any(|(entry, exit)| anchors(&[*entry, *exit])),
+            Positioning::MarkToBase(m) | Positioning::MarkToMark(m) => {
+                m.marks.values().any(|(_, a)| a.hasDevices)
+                    || m.bases.values().any(|row| anchors(row))
+            }
+            Positioning::MarkToLigature(m) => {
+                m.marks.values().any(|(_, a)| a.hasDevices)
+                    || m.ligatures.values().flatten().any(|row| anchors(row))
+            }
+            Positioning::ContextRules(_)
+            | Positioning::ChainedContextRules(_)
+            | Positioning::Context(_)
+            | Positioning::ChainedContext(_)
+            | Positioning::Unsupported { .. } => false,
+        }
+    }
+}
+
+impl ToObject for Positioning {
+    fn to_object(&self, graph: &mut ObjectGraph) -> otspec::error::Result<ObjectId> {
+        match self {
+            Positioning::Single(s) => s.to_object(graph),
+            Positioning::PairGlyphs(p) => p.to_object(graph),
+            Positioning::PairClasses(p) => p.to_object(graph),
+            Positioning::Cursive(c) => c.to_object(graph),
+            Positioning::MarkToBase(m) | Positioning::MarkToMark(m) => m.to_object(graph),
+            Positioning::MarkToLigature(m) => m.to_object(graph),
+            Positioning::ContextRules(c) => c.to_object(graph, false),
+            Positioning::Context(c) => c.to_context_object(graph),
+            Positioning::ChainedContextRules(c) => c.to_object(graph, true),
+            Positioning::ChainedContext(c) => c.to_object(graph),
+            Positioning::Unsupported { format } => Err(otspec::error::Error::Message(format!(
+                "Cannot serialize unsupported positioning subtable format {:}",
+                format
+            ))),
+        }
+    }
+}
+
+/// The `GPOS` (Glyph positioning) table
+pub type gpos = LayoutTable<Positioning>;
+
+#[cfg(test)]
+mod tests {
+    use crate::gpos::{self, Anchor, Positioning, ValueRecord};
+    use crate::layout::LookupSubtable;
+    use otspec::ser::graph::{ObjectGraph, ToObject};
+    use std::collections::BTreeMap;
+
+    /* A GPOS table equivalent to the feature file:
+
+        languagesystem DFLT dflt;
+        languagesystem latn dflt;
+        languagesystem latn TRK;
+        markClass acutecomb <anchor 250 500> @TOP;
+        lookup pairs {
+            pos a b -50;
+        } pairs;
+        lookup classes {
+            pos [a] [i] -10;
+            pos [f] [i] -20;
+        } classes;
+        lookup marks {
+            pos base a <anchor 250 450> mark @TOP;
+        } marks;
+        feature kern {
+            lookup pairs; lookup classes;
+        } kern;
+        feature mark {
+            script latn;
+            language dflt;
+            lookup marks;
+            language TRK exclude_dflt required;
+        } mark;
+
+       with glyph order .notdef a b f i fi ffi acutecomb
+    */
+    const BINARY_GPOS: [u8; 242] = [
+        0x00, 0x01, 0x00, 0x00, 0x00, 0x0A, 0x00, 0x40, 0x00, 0x5C, 0x00, 0x02, 0x44, 0x46, 0x4C,
+        0x54, 0x00, 0x0E, 0x6C, 0x61, 0x74, 0x6E, 0x00, 0x1A, 0x00, 0x04, 0x00, 0x00, 0x00, 0x00,
+        0xFF, 0xFF, 0x00, 0x01, 0x00, 0x00, 0x00, 0x0A, 0x00, 0x01, 0x54, 0x52, 0x4B, 0x20, 0x00,
+        0x14, 0x00, 0x00, 0xFF, 0xFF, 0x00, 0x02, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x01,
+        0x00, 0x01, 0x00, 0x00, 0x00, 0x02, 0x6B, 0x65, 0x72, 0x6E, 0x00, 0x0E, 0x6D, 0x61, 0x72,
+        0x6B, 0x00, 0x16, 0x00, 0x00, 0x00, 0x02, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x01,
+        0x00, 0x02, 0x00, 0x03, 0x00, 0x08, 0x00, 0x28, 0x00, 0x60, 0x00, 0x02, 0x00, 0x00, 0x00,
+        0x01, 0x00, 0x08, 0x00, 0x01, 0x00, 0x0C, 0x00, 0x04, 0x00, 0x00, 0x00, 0x01, 0x00, 0x12,
+        0x00, 0x01, 0x00, 0x01, 0x00, 0x01, 0x00, 0x01, 0x00, 0x02, 0xFF, 0xCE, 0x00, 0x02, 0x00,
+        0x00, 0x00, 0x01, 0x00, 0x08, 0x00, 0x02, 0x00, 0x18, 0x00, 0x04, 0x00, 0x00, 0x00, 0x20,
+        0x00, 0x28, 0x00, 0x02, 0x00, 0x02, 0x00, 0x00, 0xFF, 0xF6, 0x00, 0x00, 0xFF, 0xEC, 0x00,
+        0x01, 0x00, 0x02, 0x00, 0x01, 0x00, 0x03, 0x00, 0x01, 0x00, 0x03, 0x00, 0x01, 0x00, 0x01,
+        0x00, 0x01, 0x00, 0x04, 0x00, 0x01, 0x00, 0x01, 0x00, 0x04, 0x00, 0x00, 0x00, 0x01, 0x00,
+        0x08, 0x00, 0x01, 0x00, 0x0C, 0x00, 0x12, 0x00, 0x01, 0x00, 0x18, 0x00, 0x24, 0x00, 0x01,
+        0x00, 0x01, 0x00, 0x07, 0x00, 0x01, 0x00, 0x01, 0x00, 0x01, 0x00, 0x01, 0x00, 0x00, 0x00,
+        0x06, 0x00, 0x01, 0x00, 0xFA, 0x01, 0xF4, 0x00, 0x01, 0x00, 0x04, 0x00, 0x01, 0x00, 0xFA,
+        0x01, 0xC2,
+    ];
+
+    #[test]
+    fn gpos_de() {
+        let table: gpos::gpos = otspec::de::from_bytes(&BINARY_GPOS).unwrap();
+        let latn = table.scripts.scripts.get(b"latn").unwrap();
+        let trk = latn.langSys.get(b"TRK ").unwrap();
+        assert_eq!(trk.requiredFeatureIndex, Some(1));
+        assert_eq!(trk.featureIndices, vec![0]);
+        assert_eq!(table.lookups.len(), 3);
+
+        let kern = ValueRecord {
+            xAdvance: Some(-50),
+            ..Default::default()
+        };
+        let mut pairs = BTreeMap::new();
+        pairs.insert((1, 2), (kern, ValueRecord::default()));
+        assert_eq!(
+            table.lookups[0].subtables,
+            vec![Positioning::PairGlyphs(gpos::PairPosFormat1 {
+                mapping: pairs
+            })]
+        );
+
+        if let Positioning::PairClasses(p) = &table.lookups[1].subtables[0] {
+            assert_eq!(p.coverage.glyphs, vec![1, 3]);
+            assert_eq!(p.classDef1.get(3), 1);
+            assert_eq!(p.classDef2.get(4), 1);
+            assert_eq!(p.class1Records[0][1].0.xAdvance, Some(-10));
+            assert_eq!(p.class1Records[1][1].0.xAdvance, Some(-20));
+        } else {
+            panic!("Expected a class pair subtable");
+        }
+
+        let mut marks = BTreeMap::new();
+        marks.insert(
+            7,
+            (
+                0,
+                Anchor {
+                    xCoordinate: 250,
+                    yCoordinate: 500,
+                    anchorPoint: None,
+                    hasDevices: false,
+                },
+            ),
+        );
+        let mut bases = BTreeMap::new();
+        bases.insert(
+            1,
+            vec![Some(Anchor {
+                xCoordinate: 250,
+                yCoordinate: 450,
+                anchorPoint: None,
+                hasDevices: false,
+            })],
+        );
+        assert_eq!(
+            table.lookups[2].subtables,
+            vec![Positioning::MarkToBase(gpos::MarkBasePos { marks, bases })]
+        );
+    }
+
+    #[test]
+    fn gpos_ser() {
+        let table: gpos::gpos = otspec::de::from_bytes(&BINARY_GPOS).unwrap();
+        let serialized = otspec::ser::to_bytes(&table).unwrap();
+        let deserialized: gpos::gpos = otspec::de::from_bytes(&serialized).unwrap();
+        assert_eq!(deserialized, table);
+    }
+
+    #[test]
+    fn gpos_de_pair_classes_overflow() {
+        // A class pair subtable with empty value formats and 65535 x 65535 classes
+        let binary = [
+            0x00, 0x01, 0x00, 0x00, 0x00, 0x0A, 0x00, 0x0C, 0x00, 0x0E, 0x00, 0x00, 0x00, 0x00,
+            0x00, 0x01, 0x00, 0x04, 0x00, 0x02, 0x00, 0x00, 0x00, 0x01, 0x00, 0x08, 0x00, 0x02,
+            0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0xFF, 0xFF, 0xFF, 0xFF,
+            0x00, 0x01, 0x00, 0x00,
+        ];
+        let error = otspec::de::from_bytes::<gpos::gpos>(&binary).unwrap_err();
+        assert!(error
+            .to_string()
+            .contains("65535 x 65535 class records of 0 bytes do not fit in the subtable"));
+    }
+
+    #[test]
+    fn gpos_de_devices() {
+        // A single adjustment with an X placement and its device table
+        let binary = [
+            0x00, 0x01, 0x00, 0x0A, 0x00, 0x11, 0x00, 0x0A, 0x00, 0x10, 0x00, 0x01, 0x00, 0x01,
+            0x00, 0x05, 0x00, 0x0B, 0x00, 0x0B, 0x00, 0x01, 0x10, 0x00,
+        ];
+        let subtable = Positioning::from_lookup_bytes(1, &binary).unwrap();
+        if let Positioning::Single(s) = &subtable {
+            assert_eq!(s.mapping[&5].xPlacement, Some(10));
+            assert!(s.mapping[&5].hasDevices);
+        } else {
+            panic!("Expected a single adjustment subtable");
+        }
+        let error = subtable.to_object(&mut ObjectGraph::new()).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Cannot serialize value record device tables, which are not currently read"
+        );
+    }
+
+    #[test]
+    fn gpos_split() {
+        let table: gpos::gpos = otspec::de::from_bytes(&BINARY_GPOS).unwrap();
+        let (first, second) = table.lookups[1].subtables[0].split().unwrap();
+        if let (Positioning::PairClasses(first), Positioning::PairClasses(second)) =
+            (&first, &second)
+        {
+            assert_eq!(first.coverage.glyphs, vec![1]);
+            assert_eq!(first.class1Records.len(), 1);
+            assert_eq!(first.class1Records[0][1].0.xAdvance, Some(-10));
+            assert_eq!(second.coverage.glyphs, vec![3]);
+            assert_eq!(second.classDef1.get(3), 1);
+            assert_eq!(second.class1Records[1][1].0.xAdvance, Some(-20));
+        } else {
+            panic!("Expected class pair subtables");
+        }
+    }
+}
@@ -0,0 +1,612 @@
+use crate::layout::{
+    deserialize_at, split_map, ChainedSequenceContext, Coverage, LayoutTable, LookupSubtable,
+    SequenceContext,
+};
+use otspec::ser::graph::{Object, ObjectGraph, ObjectId, ToObject};
+use otspec::types::*;
+use otspec::{deserialize_visitor, read_field, read_field_counted, read_remainder};
+use otspec_macros::tables;
+use serde::de::SeqAccess;
+use serde::de::Visitor;
+use serde::{Deserialize, Deserializer, Serialize};
+use std::collections::BTreeMap;
+
+tables!(
+    Sequence {
+        Counted(uint16) substituteGlyphIDs
+    }
+    LigatureSet {
+        Counted(Offset16(Ligature)) ligatures
+    }
+);
+
+/// A single substitution subtable (lookup type 1), replacing one glyph with another
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct SingleSubst {
+    /// A mapping of input glyph IDs to substitute glyph IDs
+    pub mapping: BTreeMap<uint16, uint16>,
+}
+
+deserialize_visitor!(
+    SingleSubst,
+    SingleSubstVisitor,
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+        let format = read_field!(seq, uint16, "a single substitution format");
+        let coverageOffset = read_field!(seq, uint16, "a coverage offset");
+        let (header_len, substitutes) = match format {
+            1 => {
+                let delta = read_field!(seq, int16, "a delta glyph ID");
+                (6, Err(delta))
+            }
+            2 => {
+                let count = read_field!(seq, uint16, "a glyph count");
+                let glyphs: Vec<uint16> = read_field_counted!(seq, count, "substitute glyphs");
+                (6 + 2 * count as usize, Ok(glyphs))
+            }
+            _ => {
+                return Err(serde::de::Error::custom(format!(
+                    "Unknown single substitution format {:}",
+                    format
+                )))
+            }
+        };
+        let remainder = read_remainder!(seq, "a single substitution subtable");
+        let coverage: Coverage =
+            deserialize_at(&remainder, coverageOffset.into(), header_len, "coverage")?;
+        let mapping = match substitutes {
+            Err(delta) => coverage
+                .glyphs
+                .iter()
+                .map(|&g| (g, (g as i32 + delta as i32).rem_euclid(65536) as uint16))
+                .collect(),
+            Ok(glyphs) => {
+                if glyphs.len() != coverage.glyphs.len() {
+                    return Err(serde::de::Error::custom(
+                        "Substitute glyph count does not match coverage",
+                    ));
+                }
+                coverage.glyphs.into_iter().zip(glyphs).collect()
+            }
+        };
+        Ok(SingleSubst { mapping })
+    }
+);
+
+impl ToObject for SingleSubst {
+    fn to_object(&self, graph: &mut ObjectGraph) -> otspec::error::Result<ObjectId> {
+        let coverage = Coverage {
+            glyphs: self.mapping.keys().copied().collect(),
+        }
+        .to_object(graph)?;
+        let mut deltas = self.mapping.iter().map(|(&a, &b)| b.wrapping_sub(a));
+        let first_delta = deltas.next();
+        let mut object = Object::new();
+        match first_delta {
+            Some(delta) if deltas.all(|d| d == delta) => {
+                object.push(&1_u16)?;
+                object.push_offset16(coverage);
+                object.push(&(delta as int16))?;
+            }
+            _ => {
+                object.push(&2_u16)?;
+                object.push_offset16(coverage);
+                object.push(&(self.mapping.len() as uint16))?;
+                for substitute in self.mapping.values() {
+                    object.push(substitute)?;
+                }
+            }
+        }
+        Ok(graph.add(object))
+    }
+}
+
+/// Reads the coverage and sequences shared by multiple and alternate substitutions
+fn read_sequences<'de, A: SeqAccess<'de>>(
+    mut seq: A,
+    what: &str,
+) -> Result<BTreeMap<uint16, Vec<uint16>>, A::Error> {
+    let format = read_field!(seq, uint16, "a substitution format");
+    if format != 1 {
+        return Err(serde::de::Error::custom(format!(
+            "Unknown {:} format {:}",
+            what, format
+        )));
+    }
+    let coverageOffset = read_field!(seq, uint16, "a coverage offset");
+    let count = read_field!(seq, uint16, "a sequence count");
+    let offsets: Vec<uint16> = read_field_counted!(seq, count, "sequence offsets");
+    let header_len = 6 + 2 * count as usize;
+    let remainder = read_remainder!(seq, what);
+    let coverage: Coverage =
+        deserialize_at(&remainder, coverageOffset.into(), header_len, "coverage")?;
+    if coverage.glyphs.len() != offsets.len() {
+        return Err(serde::de::Error::custom(format!(
+            "Sequence count does not match coverage in {:}",
+            what
+        )));
+    }
+    let mut mapping = BTreeMap::new();
+    for (i, (glyph, offset)) in coverage.glyphs.into_iter().zip(offsets).enumerate() {
+        let field = format!("sequences[{}]", i);
+        let sequence: Sequence = deserialize_at(&remainder, offset.into(), header_len, &field)?;
+        mapping.insert(glyph, sequence.substituteGlyphIDs);
+    }
+    Ok(mapping)
+}
+
+/// Adds the coverage and sequences shared by multiple and alternate substitutions
+fn sequences_to_object(
+    mapping: &BTreeMap<uint16, Vec<uint16>>,
+    graph: &mut ObjectGraph,
+) -> otspec::error::Result<ObjectId> {
+    let coverage = Coverage {
+        glyphs: mapping.keys().copied().collect(),
+    }
+    .to_object(graph)?;
+    let mut object = Object::new();
+    object.push(&1_u16)?;
+    object.push_offset16(coverage);
+    object.push(&(mapping.len() as uint16))?;
+    for glyphs in mapping.values() {
+        let mut sequence = Object::new();
+        sequence.push(&Sequence {
+            substituteGlyphIDs: glyphs.clone(),
+        })?;
+        object.push_offset16(graph.add(sequence));
+    }
+    Ok(graph.add(object))
+}
+
+/// A multiple substitution subtable (lookup type 2), replacing one glyph with several
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct MultipleSubst {
+    /// A mapping of input glyph IDs to sequences of substitute glyph IDs
+    pub mapping: BTreeMap<uint16, Vec<uint16>>,
+}
+
+deserialize_visitor!(
+    MultipleSubst,
+    MultipleSubstVisitor,
+    fn visit_seq<A: SeqAccess<'de>>(self, seq: A) -> Result<Self::Value, A::Error> {
+        Ok(MultipleSubst {
+            mapping: read_sequences(seq, "a multiple substitution subtable")?,
+        })
+    }
+);
+
+impl ToObject for MultipleSubst {
+    fn to_object(&self, graph: &mut ObjectGraph) -> otspec::error::Result<ObjectId> {
+        sequences_to_object(&self.mapping, graph)
+    }
+}
+
+/// An alternate substitution subtable (lookup type 3), offering alternates for a glyph
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct AlternateSubst {
+    /// A mapping of input glyph IDs to their alternate glyph IDs
+    pub mapping: BTreeMap<uint16, Vec<uint16>>,
+}
+
+deserialize_visitor!(
+    AlternateSubst,
+    AlternateSubstVisitor,
+    fn visit_seq<A: SeqAccess<'de>>(self, seq: A) -> Result<Self::Value, A::Error> {
+        Ok(AlternateSubst {
+            mapping: read_sequences(seq, "an alternate substitution subtable")?,
+        })
+    }
+);
+
+impl ToObject for AlternateSubst {
+    fn to_object(&self, graph: &mut ObjectGraph) -> otspec::error::Result<ObjectId> {
+        sequences_to_object(&self.mapping, graph)
+    }
+}
+
+/// A ligature substitution subtable (lookup type 4), replacing several glyphs with one
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct LigatureSubst {
+    /// Ligatures as (input glyph sequence, ligature glyph) pairs, in the order
+    /// in which they are applied
+    pub ligatures: Vec<(Vec<uint16>, uint16)>,
+}
+
+deserialize_visitor!(
+    LigatureSubst,
+    LigatureSubstVisitor,
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+        let format = read_field!(seq, uint16, "a ligature substitution format");
+        if format != 1 {
+            return Err(serde::de::Error::custom(format!(
+                "Unknown ligature substitution format {:}",
+                format
+            )));
+        }
+        let coverageOffset = read_field!(seq, uint16, "a coverage offset");
+        let count = read_field!(seq, uint16, "a ligature set count");
+        let offsets: Vec<uint16> = read_field_counted!(seq, count, "ligature set offsets");
+        let header_len = 6 + 2 * count as usize;
+        let remainder = read_remainder!(seq, "a ligature substitution subtable");
+        let coverage: Coverage =
+            deserialize_at(&remainder, coverageOffset.into(), header_len, "coverage")?;
+        if coverage.glyphs.len() != offsets.len() {
+            return Err(serde::de::Error::custom(
+                "Ligature set count does not match coverage",
+            ));
+        }
+        let mut ligatures = vec![];
+        for (i, (first, offset)) in coverage.glyphs.into_iter().zip(offsets).enumerate() {
+            let field = format!("ligatureSets[{}]", i);
+            let set: LigatureSet = deserialize_at(&remainder, offset.into(), header_len, &field)?;
+            for ligature in set.ligatures {
+                let mut input = vec![first];
+                input.extend(ligature.componentGlyphIDs);
+                ligatures.push((input, ligature.ligatureGlyph));
+            }
+        }
+        Ok(LigatureSubst { ligatures })
+    }
+);
+
+impl LigatureSubst {
+    /// Groups the ligatures by their first glyph, keeping their order within each group
+    fn sets(&self) -> BTreeMap<uint16, Vec<&(Vec<uint16>, uint16)>> {
+        let mut sets: BTreeMap<uint16, Vec<_>> = BTreeMap::new();
+        for ligature in &self.ligatures {
+            if let Some(&first) = ligature.0.first() {
+                sets.entry(first).or_default().push(ligature);
+            }
+        }
+        sets
+    }
+}
+
+impl ToObject for LigatureSubst {
+    fn to_object(&self, graph: &mut ObjectGraph) -> otspec::error::Result<ObjectId> {
+        let sets = self.sets();
+        let coverage = Coverage {
+            glyphs: sets.keys().copied().collect(),
+        }
+        .to_object(graph)?;
+        let mut object = Object::new();
+        object.push(&1_u16)?;
+        object.push_offset16(coverage);
+        object.push(&(sets.len() as uint16))?;
+        for ligatures in sets.values() {
+            let set = LigatureSet {
+                ligatures: ligatures
+                    .iter()
+                    .map(|(input, ligatureGlyph)| Ligature {
+                        ligatureGlyph: *ligatureGlyph,
+                        componentGlyphIDs: input[1..].to_vec(),
+                    })
+                    .collect(),
+            };
+            object.push_offset16(set.to_object(graph)?);
+        }
+        Ok(graph.add(object))
+    }
+}
+
+/// Low-level structure used for serializing/deserializing a ligature
+#[derive(Debug, PartialEq, Clone)]
+pub struct Ligature {
+    /// The glyph ID of the ligature
+    pub ligatureGlyph: uint16,
+    /// The glyph IDs of the components after the first
+    pub componentGlyphIDs: Vec<uint16>,
+}
+
+deserialize_visitor!(
+    Ligature,
+    LigatureVisitor,
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+        let ligatureGlyph = read_field!(seq, uint16, "a ligature glyph");
+        let count = read_field!(seq, uint16, "a component count");
+        if count == 0 {
+            return Err(serde::de::Error::custom("Ligature with no components"));
+        }
+        let componentGlyphIDs = read_field_counted!(seq, count - 1, "component glyphs");
+        Ok(Ligature {
+            ligatureGlyph,
+            componentGlyphIDs,
+        })
+    }
+);
+
+impl ToObject for Ligature {
+    fn to_object(&self, graph: &mut ObjectGraph) -> otspec::error::Result<ObjectId> {
+        let mut object = Object::new();
+        object.push(&self.ligatureGlyph)?;
+        object.push(&(self.componentGlyphIDs.len() as uint16 + 1))?;
+        object.push(&self.componentGlyphIDs)?;
+        Ok(graph.add(object))
+    }
+}
+
+/// A subtable of a `GSUB` lookup
+#[derive(Debug, PartialEq, Clone)]
+pub enum Substitution {
+    /// Single substitution (lookup type 1)
+    Single(SingleSubst),
+    /// Multiple substitution (lookup type 2)
+    Multiple(MultipleSubst),
+    /// Alternate substitution (lookup type 3)
+    Alternate(AlternateSubst),
+    /// Ligature substitution (lookup type 4)
+    Ligature(LigatureSubst),
+    /// Contextual substitution by glyph or class rules (lookup type 5,
+    /// formats 1 and 2)
+    ContextRules(SequenceContext),
+    /// Contextual substitution by coverages (lookup type 5, format 3)
+    Context(ChainedSequenceContext),
+    /// Chained contextual substitution by glyph or class rules (lookup type 6,
+    /// formats 1 and 2)
+    ChainedContextRules(SequenceContext),
+    /// Chained contextual substitution by coverages (lookup type 6, format 3)
+    ChainedContext(ChainedSequenceContext),
+    /// A subtable of a lookup type or format which is not currently supported
+    Unsupported {
+        /// The subtable's format
+        format: uint16,
+    },
+}
+
+impl LookupSubtable for Substitution {
+    const EXTENSION_TYPE: uint16 = 7;
+
+    fn from_lookup_bytes(lookupType: uint16, data: &[u8]) -> otspec::error::Result<Self> {
+        let format: uint16 = otspec::de::from_bytes(data)?;
+        Ok(match (lookupType, format) {
+            (1, _) => Substitution::Single(otspec::de::from_bytes(data)?),
+            (2, _) => Substitution::Multiple(otspec::de::from_bytes(data)?),
+            (3, _) => Substitution::Alternate(otspec::de::from_bytes(data)?),
+            (4, _) => Substitution::Ligature(otspec::de::from_bytes(data)?),
+            (5, 1) | (5, 2) => {
+                Substitution::ContextRules(SequenceContext::from_bytes(data, false)?)
+            }
+            (5, 3) => Substitution::Context(ChainedSequenceContext::from_context_bytes(data)?),
+            (6, 1) | (6, 2) => {
+                Substitution::ChainedContextRules(SequenceContext::from_bytes(data, true)?)
+            }
+            (6, 3) => Substitution::ChainedContext(otspec::de::from_bytes(data)?),
+            _ => Substitution::Unsupported { format },
+        })
+    }
+
+    fn split(&self) -> Option<(Self, Self)> {
+        match self {
+            Substitution::Single(s) => split_map(&s.mapping).map(|(a, b)| {
+                (
+                    Substitution::Single(SingleSubst { mapping: a }),
+                    Substitution::Single(SingleSubst { mapping: b }),
+                )
+            }),
+            Substitution::Multiple(s) => split_map(&s.mapping).map(|(a, b)| {
+                (
+                    Substitution::Multiple(MultipleSubst { mapping: a }),
+                    Substitution::Multiple(MultipleSubst { mapping: b }),
+                )
+            }),
+            Substitution::Alternate(s) => split_map(&s.mapping).map(|(a, b)| {
+                (
+                    Substitution::Alternate(AlternateSubst { mapping: a }),
+                    Substitution::Alternate(AlternateSubst { mapping: b }),
+                )
+            }),
+            Substitution::Ligature(s) => {
+                // Ligatures with the same first glyph must stay together to
+                // keep their order of application
+                let (a, b) = split_map(&s.sets())?;
+                let flatten = |sets: BTreeMap<uint16, Vec<&(Vec<uint16>, uint16)>>| {
+                    Substitution::Ligature(LigatureSubst {
+                        ligatures: sets.into_values().flatten().cloned().collect(),
+                    })
+                };
+                Some((flatten(a), flatten(b)))
+            }
+            Substitution::ContextRules(_)
+            | Substitution::Context(_)
+            | Substitution::ChainedContextRules(_)
+            | Substitution::ChainedContext(_)
+            | Substitution::Unsupported { .. } => None,
+        }
+    }
+}
+
+impl Substitution {
+    /// The number of glyphs the subtable matches from the current glyph
+    /// onwards, as counted for the OS/2 `usMaxContext` field
+    ///
+    /// This is zero for unsupported subtables, whose rules are not known.
+    pub fn max_context(&self) -> usize {
+        match self {
+            Substitution::Single(_) | Substitution::Multiple(_) | Substitution::Alternate(_) => 1,
+            Substitution::Ligature(s) => s
+                .ligatures
+                .iter()
+                .map(|(input, _)| input.len())
+                .max()
+                .unwrap_or(0),
+            Substitution::ContextRules(c) | Substitution::ChainedContextRules(c) => c.max_context(),
+            Substitution::Context(c) | Substitution::ChainedContext(c) => c.max_context(),
+            Substitution::Unsupported { .. } => 0,
+        }
+    }
+}
+
+impl ToObject for Substitution {
+    fn to_object(&self, graph: &mut ObjectGraph) -> otspec::error::Result<ObjectId> {
+        match self {
+            Substitution::Single(s) => s.to_object(graph),
+            Substitution::Multiple(s) => s.to_object(graph),
+            Substitution::Alternate(s) => s.to_object(graph),
+            Substitution::Ligature(s) => s.to_object(graph),
+            Substitution::ContextRules(s) => s.to_object(graph, false),
+            Substitution::Context(s) => s.to_context_object(graph),
+            Substitution::ChainedContextRules(s) => s.to_object(graph, true),
+            Substitution::ChainedContext(s) => s.to_object(graph),
+            Substitution::Unsupported { format } => Err(otspec::error::Error::Message(format!(
+                "Cannot serialize unsupported substitution subtable format {:}",
+                format
+            ))),
+        }
+    }
+}
+
+/// The `GSUB` (Glyph substitution) table
+pub type gsub = LayoutTable<Substitution>;
+
+#[cfg(test)]
+mod tests {
+    use crate::gsub::{self, Substitution};
+    use crate::layout::{Lookup, LookupFlags};
+    use std::collections::BTreeMap;
+
+    /* A GSUB table equivalent to the feature file:
+
+        languagesystem DFLT dflt;
+        lookup single {
+            sub a by b;
+        } single;
+        feature liga {
+            lookup single;
+            sub f f i by ffi;
+            sub f i by fi;
+        } liga;
+
+       with glyph order .notdef a b f i fi ffi
+    */
+    const BINARY_GSUB: [u8; 114] = [
+        0x00, 0x01, 0x00, 0x00, 0x00, 0x0A, 0x00, 0x1E, 0x00, 0x2E, 0x00, 0x01, 0x44, 0x46, 0x4C,
+        0x54, 0x00, 0x08, 0x00, 0x04, 0x00, 0x00, 0x00, 0x00, 0xFF, 0xFF, 0x00, 0x01, 0x00, 0x00,
+        0x00, 0x01, 0x6C, 0x69, 0x67, 0x61, 0x00, 0x08, 0x00, 0x00, 0x00, 0x02, 0x00, 0x00, 0x00,
+        0x01, 0x00, 0x02, 0x00, 0x06, 0x00, 0x1A, 0x00, 0x01, 0x00, 0x00, 0x00, 0x01, 0x00, 0x08,
+        0x00, 0x01, 0x00, 0x06, 0x00, 0x01, 0x00, 0x01, 0x00, 0x01, 0x00, 0x01, 0x00, 0x04, 0x00,
+        0x00, 0x00, 0x01, 0x00, 0x08, 0x00, 0x01, 0x00, 0x08, 0x00, 0x01, 0x00, 0x0E, 0x00, 0x01,
+        0x00, 0x01, 0x00, 0x03, 0x00, 0x02, 0x00, 0x06, 0x00, 0x0E, 0x00, 0x06, 0x00, 0x03, 0x00,
+        0x03, 0x00, 0x04, 0x00, 0x05, 0x00, 0x02, 0x00, 0x04,
+    ];
+
+    #[test]
+    fn gsub_de() {
+        let table: gsub::gsub = otspec::de::from_bytes(&BINARY_GSUB).unwrap();
+        assert_eq!(table.scripts.scripts.len(), 1);
+        let dflt = table.scripts.scripts.get(b"DFLT").unwrap();
+        assert_eq!(
+            dflt.defaultLangSys.as_ref().unwrap().featureIndices,
+            vec![0]
+        );
+        assert_eq!(table.features.features.len(), 1);
+        assert_eq!(&table.features.features[0].featureTag, b"liga");
+        assert_eq!(table.features.features[0].lookupListIndices, vec![0, 1]);
+        assert_eq!(table.lookups.len(), 2);
+        assert_eq!(table.lookups[0].lookupType, 1);
+        assert_eq!(table.lookups[0].lookupFlag, LookupFlags::empty());
+        let mut single = BTreeMap::new();
+        single.insert(1, 2);
+        assert_eq!(
+            table.lookups[0].subtables,
+            vec![Substitution::Single(gsub::SingleSubst { mapping: single })]
+        );
+        assert_eq!(
+            table.lookups[1].subtables,
+            vec![Substitution::Ligature(gsub::LigatureSubst {
+                ligatures: vec![(vec![3, 3, 4], 6), (vec![3, 4], 5)]
+            })]
+        );
+    }
+
+    #[test]
+    fn gsub_ser() {
+        let table: gsub::gsub = otspec::de::from_bytes(&BINARY_GSUB).unwrap();
+        let serialized = otspec::ser::to_bytes(&table).unwrap();
+        let deserialized: gsub::gsub = otspec::de::from_bytes(&serialized).unwrap();
+        assert_eq!(deserialized, table);
+    }
+
+    fn lookup_types(binary: &[u8]) -> Vec<u16> {
+        let read = |pos: usize| u16::from_be_bytes([binary[pos], binary[pos + 1]]) as usize;
+        let lookupList = read(8);
+        (0..read(lookupList))
+            .map(|i| read(lookupList + read(lookupList + 2 + 2 * i)) as u16)
+            .collect()
+    }
+
+    #[test]
+    fn gsub_ser_promotes_extensions() {
+        let mut table = gsub::gsub::default();
+        for i in 0..8 {
+            let mapping = (0..2000)
+                .map(|g| (g, vec![g + i, g + 1, g + 2, g + 3]))
+                .collect();
+            table.lookups.push(Lookup {
+                lookupType: 2,
+                lookupFlag: LookupFlags::empty(),
+                subtables: vec![Substitution::Multiple(gsub::MultipleSubst { mapping })],
+                markFilteringSet: None,
+            });
+        }
+        let serialized = otspec::ser::to_bytes(&table).unwrap();
+        assert!(serialized.len() > 0x10000);
+        assert!(lookup_types(&serialized).contains(&7));
+        let deserialized: gsub::gsub = otspec::de::from_bytes(&serialized).unwrap();
+        assert_eq!(deserialized, table);
+    }
+
+    #[test]
+    fn gsub_ser_splits_subtables() {
+        let mapping: BTreeMap<u16, u16> = (0..40000_u32)
+            .map(|g| (g as u16, ((g * 7) % 40000) as u16))
+            .collect();
+        let mut table = gsub::gsub::default();
+        table.lookups.push(Lookup {
+            lookupType: 1,
+            lookupFlag: LookupFlags::IGNORE_MARKS,
+            subtables: vec![Substitution::Single(gsub::SingleSubst {
+                mapping: mapping.clone(),
+            })],
+            markFilteringSet: None,
+        });
+        let serialized = otspec::ser::to_bytes(&table).unwrap();
+        let deserialized: gsub::gsub = otspec::de::from_bytes(&serialized).unwrap();
+        assert!(deserialized.lookups[0].subtables.len() > 1);
+        let mut merged = BTreeMap::new();
+        for subtable in &deserialized.lookups[0].subtables {
+            if let Substitution::Single(s) = subtable {
+                merged.extend(s.mapping.clone());
+            } else {
+                panic!("Expected single substitution subtables");
+            }
+        }
+        assert_eq!(merged, mapping);
+    }
+
+    #[test]
+    fn gsub_de_error_location() {
+        // A single substitution whose coverage table is cut short
+        let binary_gsub = vec![
+            0x00, 0x01, 0x00, 0x00, 0x00, 0x0A, 0x00, 0x0C, 0x00, 0x0E, // header
+            0x00, 0x00, // script list
+            0x00, 0x00, // feature list
+            0x00, 0x01, 0x00, 0x04, // lookup list
+            0x00, 0x01, 0x00, 0x00, 0x00, 0x01, 0x00, 0x08, // lookup
+            0x00, 0x01, 0x00, 0x06, 0x00, 0x01, // subtable
+            0x00, 0x01, 0x00, 0x02, 0x00, 0x05, 0x00, // coverage
+        ];
+        let error = otspec::de::from_bytes::<gsub::gsub>(&binary_gsub).unwrap_err();
+        let location = error.location().unwrap();
+        assert_eq!(
+            location.path,
+            "lookupList.lookups[0].subtables[0].coverage[1]"
+        );
+        assert_eq!(location.offset, Some(38));
+        assert_eq!(
+            error.inner(),
+            &otspec::error::Error::UnexpectedEof {
+                needed: 2,
+                available: 1
+            }
+        );
+    }
+}
@@ -1,3 +1,4 @@
+use crate::layout::data_at;
 use crate::otvar::*;
 use counter::Counter;
 use otspec::de::CountedDeserializer;
@@ -10,7 +11,6 @@ use serde::de::SeqAccess;
 use serde::de::Visitor;
 use serde::{Deserialize, Serialize, Serializer};
 use std::collections::HashMap;
-use std::convert::TryInto;
 
 tables!( gvarcore {
     uint16  majorVersion
@@ -27,7 +27,7 @@ tables!( gvarcore {
 /// How a glyph's points vary at one region of the design space.
 ///
 /// (This is the user-friendly version of what is serialized as a TupleVariation)
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
 pub struct DeltaSet {
     pub peak: Tuple,
     pub start: Tuple,
@@ -90,47 +90,77 @@ impl DeltaSet {
     }
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
 pub struct GlyphVariationData {
     pub deltasets: Vec<DeltaSet>,
 }
 
-#[derive(Debug, PartialEq)]
+/// The gvar table
+///
+/// The derived `Deserialize` is for human-readable formats; binary tables
+/// need the glyphs' coordinates, and are read with `from_bytes`.
+#[derive(Debug, PartialEq, Deserialize)]
 pub struct gvar {
+    /// The variations of each glyph, or None if the glyph does not vary
+    pub variations: Vec<Option<GlyphVariationData>>,
+}
+
+/// The gvar table in human-readable formats
+#[derive(Serialize)]
+#[serde(remote = "gvar")]
+struct GvarDef {
     variations: Vec<Option<GlyphVariationData>>,
 }
 
+// Glyph variation data are sized by the gaps between their offsets and need
+// each glyph's points to be decoded, so they are not `tables!` offsets
 stateful_deserializer!(
     gvar,
     GvarDeserializer,
     { coords_and_ends: Vec<(Vec<(int16,int16)>,Vec<usize>)> },
     fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
         let core = read_field!(seq, gvarcore, "a gvar table header");
+        let glyph_count = core.glyphCount as usize;
         let dataOffsets: Vec<u32> = if core.flags & 0x1 == 0 {
             // u16 offsets, need doubling
             let u16_and_halved: Vec<u16> =
-                read_field_counted!(seq, core.glyphCount + 1, "a glyphVariationDataOffset");
-            u16_and_halved.iter().map(|x| (x * 2).into()).collect()
+                read_field_counted!(seq, glyph_count + 1, "a glyphVariationDataOffset");
+            u16_and_halved.iter().map(|x| u32::from(*x) * 2).collect()
         } else {
-            read_field_counted!(seq, core.glyphCount + 1, "a glyphVariationDataOffset")
+            read_field_counted!(seq, glyph_count + 1, "a glyphVariationDataOffset")
         };
         // println!("Offsets {:?}", dataOffsets);
         let remainder = read_remainder!(seq, "a gvar table");
         let offset_base: usize =
-            20 + (core.glyphCount as usize + 1) * (if core.flags & 0x1 == 0 { 2 } else { 4 });
+            20 + (glyph_count + 1) * (if core.flags & 0x1 == 0 { 2 } else { 4 });
         // println!("Remainder: {:?}", remainder);
         let axis_count = core.axisCount as usize;
+        if self.coords_and_ends.len() < glyph_count {
+            return Err(serde::de::Error::custom(format!(
+                "gvar table has {} glyphs but glyf table has {}",
+                glyph_count,
+                self.coords_and_ends.len()
+            )));
+        }
 
         /* Shared tuples */
-        let mut shared_tuples: Vec<Tuple> = Vec::with_capacity(core.sharedTupleCount as usize);
-        let mut shared_tuple_start = (core.sharedTuplesOffset as usize) - offset_base;
-        let shared_tuple_end =
-            shared_tuple_start + (core.sharedTupleCount * core.axisCount * 2) as usize;
-        while shared_tuple_start < shared_tuple_end {
-            // println!("Start {:?}", shared_tuple_start);
-            let bytes = &remainder[shared_tuple_start..shared_tuple_start + 2 * axis_count];
+        let mut shared_tuples: Vec<Tuple> = vec![];
+        let shared_tuple_data = if core.sharedTupleCount > 0 {
+            data_at(
+                &remainder,
+                core.sharedTuplesOffset as usize,
+                offset_base,
+                "the shared tuples",
+            )?
+        } else {
+            &[]
+        };
+        for index in 0..core.sharedTupleCount as usize {
+            let bytes = shared_tuple_data
+                .get(index * 2 * axis_count..(index + 1) * 2 * axis_count)
+                .ok_or_else(|| serde::de::Error::custom("Expecting a tuple"))?;
             let mut de = OTDeserializer::from_bytes(bytes);
-            println!("Trying to deserialize shared tuple array {:?}", bytes);
+            log::debug!("Trying to deserialize shared tuple array {:?}", bytes);
             let cs: CountedDeserializer<i16> = CountedDeserializer::with_len(axis_count);
             let tuple: Vec<f32> = cs
                 .deserialize(&mut de)
@@ -138,26 +168,21 @@ stateful_deserializer!(
                 .iter()
                 .map(|i| *i as f32 / 16384.0)
                 .collect();
-            println!("Tuple {:?}", tuple);
-            shared_tuple_start += 2 * axis_count;
+            log::debug!("Tuple {:?}", tuple);
             shared_tuples.push(tuple);
         }
 
         /* Glyph variation data */
         let mut glyphVariations = vec![];
-        for i in 0..(core.glyphCount as usize) {
+        for i in 0..glyph_count {
             // println!("Reading data for glyph {:?}", i);
-            let offset: usize = (dataOffsets[i] + (core.glyphVariationDataArrayOffset)
-                - offset_base as u32)
-                .try_into()
-                .unwrap();
-            let next_offset: usize = (dataOffsets[(i + 1) as usize]
-                + (core.glyphVariationDataArrayOffset)
-                - offset_base as u32)
-                .try_into()
-                .unwrap();
-            let length = next_offset - offset;
-            let bytes = &remainder[offset..];
+            let offset = dataOffsets[i] as usize + core.glyphVariationDataArrayOffset as usize;
+            let next_offset =
+                dataOffsets[i + 1] as usize + core.glyphVariationDataArrayOffset as usize;
+            let length = next_offset.checked_sub(offset).ok_or_else(|| {
+                serde::de::Error::custom(format!("Glyph variation data offsets for glyph {} are not in order", i))
+            })?;
+            let bytes = data_at(&remainder, offset, offset_base, "glyph variation data")?;
             if length == 0 {
                 glyphVariations.push(None);
             } else {
@@ -168,15 +193,22 @@ stateful_deserializer!(
                     point_count: self.coords_and_ends[i].0.len() as u16,
                     is_gvar: true,
                 };
-                let tvs = cs.deserialize(&mut de).unwrap();
+                let tvs = cs.deserialize(&mut de).map_err(|e| {
+                    serde::de::Error::custom(format!(
+                        "Expecting a tuple variation store for glyph {}: {}",
+                        i, e
+                    ))
+                })?;
                 // println!("TVS {:?}", tvs);
                 for tvh in tvs.0 {
                     let deltas = tvh.iup_delta(&self.coords_and_ends[i].0, &self.coords_and_ends[i].1);
                     let index = tvh.0.sharedTupleIndex as usize;
-                    let peak_tuple = tvh
-                        .0
-                        .peakTuple
-                        .unwrap_or_else(|| shared_tuples[index].clone());
+                    let peak_tuple = match tvh.0.peakTuple {
+                        Some(peak_tuple) => peak_tuple,
+                        None => shared_tuples.get(index).cloned().ok_or_else(|| {
+                            serde::de::Error::custom(format!("Bad shared tuple index {}", index))
+                        })?,
+                    };
                     let start_tuple = tvh.0.startTuple.unwrap_or_else(|| peak_tuple.clone());
                     let end_tuple = tvh.0.endTuple.unwrap_or_else(|| peak_tuple.clone());
                     deltasets.push(DeltaSet {
@@ -203,7 +235,7 @@ impl gvar {
         let mut shared_tuple_counter: Counter<Vec<u8>> = Counter::new();
         for var in self.variations.iter().flatten() {
             for ds in &var.deltasets {
-                println!("Peak: {:?}", ds.peak);
+                log::debug!("Peak: {:?}", ds.peak);
                 shared_tuple_counter[&ds
                     .peak
                     .iter()
@@ -239,7 +271,7 @@ impl gvar {
                 out.push(0); // XXX
             }
         }
-        println!("Most common tuples: {:?}", most_common_tuples);
+        log::debug!("Most common tuples: {:?}", most_common_tuples);
         for (a, _) in most_common_tuples {
             out.extend(otspec::ser::to_bytes(&a).unwrap());
         }
@@ -256,16 +288,93 @@ pub fn from_bytes(
     cs.deserialize(&mut deserializer)
 }
 
+/// Subsets a binary gvar table, keeping the variations of the given glyphs
+///
+/// The glyph variation data is copied without being decoded, so the glyphs'
+/// outlines are not needed. Glyphs beyond the end of the table are given no
+/// variations.
+pub(crate) fn subset_bytes(s: &[u8], glyphs: &[uint16]) -> otspec::error::Result<Vec<u8>> {
+    let core: gvarcore = otspec::de::from_bytes(s)?;
+    let long_offsets = core.flags & 0x1 == 1;
+    let data_offset = |glyph: usize| -> Option<usize> {
+        let offset = if long_offsets {
+            let pos = 20 + glyph * 4;
+            u32::from_be_bytes([
+                *s.get(pos)?,
+                *s.get(pos + 1)?,
+                *s.get(pos + 2)?,
+                *s.get(pos + 3)?,
+            ]) as usize
+        } else {
+            let pos = 20 + glyph * 2;
+            u16::from_be_bytes([*s.get(pos)?, *s.get(pos + 1)?]) as usize * 2
+        };
+        Some(offset + core.glyphVariationDataArrayOffset as usize)
+    };
+
+    let shared_tuples_len = core.sharedTupleCount as usize * core.axisCount as usize * 2;
+    let shared_tuples = s
+        .get(core.sharedTuplesOffset as usize..)
+        .and_then(|tuples| tuples.get(..shared_tuples_len))
+        .ok_or_else(|| otspec::error::Error::Message("Bad offset to the shared tuples".into()))?;
+
+    let mut data: Vec<u8> = vec![];
+    let mut offsets = vec![0];
+    for &glyph in glyphs {
+        let glyph = glyph as usize;
+        if glyph < core.glyphCount as usize {
+            let bytes = data_offset(glyph)
+                .zip(data_offset(glyph + 1))
+                .and_then(|(start, end)| s.get(start..end))
+                .ok_or_else(|| {
+                    otspec::error::Error::Message(format!(
+                        "Bad glyph variation data offsets for glyph {}",
+                        glyph
+                    ))
+                })?;
+            data.extend(bytes);
+            // Short offsets can only point to even positions
+            if data.len() % 2 == 1 {
+                data.push(0);
+            }
+        }
+        offsets.push(data.len());
+    }
+
+    let long_offsets = data.len() > 2 * u16::MAX as usize;
+    let sharedTuplesOffset = 20 + offsets.len() * if long_offsets { 4 } else { 2 };
+    let mut out = otspec::ser::to_bytes(&gvarcore {
+        glyphCount: glyphs.len() as uint16,
+        flags: (core.flags & !0x1) | long_offsets as uint16,
+        sharedTuplesOffset: sharedTuplesOffset as u32,
+        glyphVariationDataArrayOffset: (sharedTuplesOffset + shared_tuples.len()) as u32,
+        ..core
+    })?;
+    for offset in offsets {
+        if long_offsets {
+            out.extend(&(offset as u32).to_be_bytes());
+        } else {
+            out.extend(&((offset / 2) as u16).to_be_bytes());
+        }
+    }
+    out.extend(shared_tuples);
+    out.extend(data);
+    Ok(out)
+}
+
 // Serialization plan:
 //  For each glyph, we have: Vec<DeltaSet>. We want TupleVariationStore (Vec<TupleVariation>).
 //      A DeltaSet consists of peak/start/end and (i16,i16) deltas.
 //      Each TupleVariation consists of the TupleVariationHeader and a Vec<Option<Delta>>
 
 impl Serialize for gvar {
-    fn serialize<S>(&self, _serializer: S) -> Result<S::Ok, S::Error>
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
     where
         S: Serializer,
     {
+        if serializer.is_human_readable() {
+            return GvarDef::serialize(self, serializer);
+        }
         panic!("Don't call this serializer, call the one in Font instead")
     }
 }
@@ -468,4 +577,32 @@ mod tests {
         let serialized = deserialized.to_bytes();
         assert_eq!(serialized, binary_gvar);
     }
+
+    #[test]
+    fn gvar_subset_bytes() {
+        let binary_gvar = vec![
+            0x00, 0x01, 0x00, 0x00, 0x00, 0x02, 0x00, 0x02, 0x00, 0x00, 0x00, 0x1e, 0x00, 0x04,
+            0x00, 0x00, 0x00, 0x00, 0x00, 0x26, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x0d,
+            0x00, 0x24, 0x40, 0x00, 0x00, 0x00, 0x00, 0x00, 0x40, 0x00, 0x80, 0x02, 0x00, 0x0c,
+            0x00, 0x06, 0x00, 0x00, 0x00, 0x06, 0x00, 0x01, 0x00, 0x86, 0x02, 0xd2, 0xd2, 0x2e,
+            0x83, 0x02, 0x52, 0xae, 0xf7, 0x83, 0x86, 0x00, 0x80, 0x03, 0x00, 0x14, 0x00, 0x0a,
+            0x20, 0x00, 0x00, 0x07, 0x00, 0x01, 0x00, 0x07, 0x80, 0x00, 0x40, 0x00, 0x40, 0x00,
+            0x00, 0x02, 0x01, 0x01, 0x02, 0x01, 0x26, 0xda, 0x01, 0x83, 0x7d, 0x03, 0x26, 0x26,
+            0xda, 0xda, 0x83, 0x87, 0x03, 0x13, 0x13, 0xed, 0xed, 0x83, 0x87, 0x00,
+        ];
+        assert_eq!(
+            gvar::subset_bytes(&binary_gvar, &[0, 1, 2, 3]).unwrap(),
+            binary_gvar
+        );
+
+        let subset = gvar::subset_bytes(&binary_gvar, &[0, 3, 4]).unwrap();
+        let header = [
+            0x00, 0x01, 0x00, 0x00, 0x00, 0x02, 0x00, 0x02, 0x00, 0x00, 0x00, 0x1c, 0x00, 0x03,
+            0x00, 0x00, 0x00, 0x00, 0x00, 0x24, 0x00, 0x00, 0x00, 0x00, 0x00, 0x17, 0x00, 0x17,
+        ];
+        assert_eq!(&subset[..28], &header);
+        // The shared tuples, then the data of glyph 3
+        assert_eq!(&subset[28..36], &binary_gvar[30..38]);
+        assert_eq!(&subset[36..], &binary_gvar[64..]);
+    }
 }
@@ -1,19 +1,30 @@
 use otspec::types::*;
 use otspec::{read_field, stateful_deserializer};
 use serde::de::{DeserializeSeed, SeqAccess, Visitor};
-use serde::{Serialize, Serializer};
+use serde::{Deserialize, Serialize, Serializer};
 
-#[derive(Debug, PartialEq, Serialize)]
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
 pub struct Metric {
     pub advanceWidth: u16,
     pub lsb: int16,
 }
 
-#[derive(Debug, PartialEq)]
+/// The hmtx table
+///
+/// The derived `Deserialize` is for human-readable formats; binary tables
+/// need the number of long metrics, and are read with `from_bytes`.
+#[derive(Debug, PartialEq, Deserialize)]
 pub struct hmtx {
     pub metrics: Vec<Metric>,
 }
 
+/// The hmtx table in human-readable formats
+#[derive(Serialize)]
+#[serde(remote = "hmtx")]
+struct HmtxDef {
+    metrics: Vec<Metric>,
+}
+
 impl hmtx {
     pub fn to_bytes(&self) -> (Vec<u8>, uint16) {
         let mut end_index_h_metrics = self.metrics.len() - 1;
@@ -54,11 +65,15 @@ stateful_deserializer!(
             res.metrics.push(Metric { advanceWidth, lsb })
         }
         if let Some(otherMetrics) = seq.next_element::<Vec<int16>>()? {
-            let last = res
-                .metrics
-                .last()
-                .expect("Must be one advance width in hmtx!")
-                .advanceWidth;
+            let last = match res.metrics.last() {
+                Some(metric) => metric.advanceWidth,
+                None if otherMetrics.is_empty() => return Ok(res),
+                None => {
+                    return Err(serde::de::Error::custom(
+                        "Must be one advance width in hmtx!",
+                    ))
+                }
+            };
             res.metrics.extend(otherMetrics.iter().map(|x| Metric {
                 lsb: *x,
                 advanceWidth: last,
@@ -69,14 +84,17 @@ stateful_deserializer!(
 );
 
 impl Serialize for hmtx {
-    fn serialize<S>(&self, _serializer: S) -> Result<S::Ok, S::Error>
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
     where
         S: Serializer,
     {
+        if serializer.is_human_readable() {
+            return HmtxDef::serialize(self, serializer);
+        }
         // We'll do this elsewhere
-        panic!(
-            "loca cannot be serialized directly. Call compile_glyf_loca_maxp on the font instead"
-        )
+        Err(serde::ser::Error::custom(
+            "hmtx cannot be serialized directly. Call compile_glyf_loca_maxp on the font instead",
+        ))
     }
 }
 
@@ -0,0 +1,316 @@
+//! OpenType Layout common tables
+
+/// Class definition tables
+mod classdef;
+/// Chained sequence context (contextual lookup) subtables
+mod contextual;
+/// Coverage tables
+mod coverage;
+/// Feature list tables
+mod featurelist;
+/// Lookup list tables and lookup flags
+mod lookup;
+/// Script list tables
+mod scriptlist;
+
+use otspec::ser::graph::{Object, ObjectGraph, ObjectId, ToObject};
+use otspec::types::*;
+use otspec::{read_field, read_remainder};
+use serde::de::{DeserializeOwned, DeserializeSeed, SeqAccess, Visitor};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::marker::PhantomData;
+
+pub use crate::layout::classdef::ClassDef;
+pub use crate::layout::contextual::{
+    ChainedSequenceContext, SequenceClassDefs, SequenceContext, SequenceLookupRecord, SequenceRule,
+};
+pub use crate::layout::coverage::Coverage;
+pub use crate::layout::featurelist::{FeatureList, FeatureRecord};
+pub use crate::layout::lookup::{Lookup, LookupFlags, LookupList, LookupSubtable};
+pub use crate::layout::scriptlist::{LangSys, Script, ScriptList};
+
+/// Returns the data of a structure found at `offset` bytes from the start of its parent.
+///
+/// Visitors only see the bytes after the parent's header, so the length of
+/// the header is subtracted from the offset to locate the structure within
+/// `remainder`. Offsets which point back into the header, or off the end of
+/// the data, are reported as errors rather than panicking.
+pub(crate) fn data_at<'a, E>(
+    remainder: &'a [u8],
+    offset: usize,
+    header_len: usize,
+    what: &str,
+) -> Result<&'a [u8], E>
+where
+    E: serde::de::Error,
+{
+    offset
+        .checked_sub(header_len)
+        .and_then(|start| remainder.get(start..))
+        .ok_or_else(|| E::custom(format!("Bad offset {:} to {:}", offset, what)))
+}
+
+/// Deserializes a structure found at `offset` bytes from the start of its parent.
+///
+/// See [`data_at`] for how the offset is interpreted. Errors are reported at
+/// `field`, the path of the structure within its parent.
+pub(crate) fn deserialize_at<T, E>(
+    remainder: &[u8],
+    offset: usize,
+    header_len: usize,
+    field: &str,
+) -> Result<T, E>
+where
+    T: DeserializeOwned,
+    E: serde::de::Error,
+{
+    let data = data_at(remainder, offset, header_len, field)?;
+    T::deserialize(&mut otspec::de::Deserializer::from_bytes_at_offset(
+        data, offset,
+    ))
+    .map_err(|e| e.within(field, 0).nested())
+}
+
+/// Deserializes a stateful structure found at `offset` bytes from the start of its parent.
+///
+/// See [`deserialize_at`] for how the offset and field are interpreted.
+pub(crate) fn deserialize_seed_at<'a, S, E>(
+    remainder: &'a [u8],
+    offset: usize,
+    header_len: usize,
+    field: &str,
+    seed: S,
+) -> Result<S::Value, E>
+where
+    S: DeserializeSeed<'a>,
+    E: serde::de::Error,
+{
+    let data = data_at(remainder, offset, header_len, field)?;
+    let mut deserializer = otspec::de::Deserializer::from_bytes_at_offset(data, offset);
+    seed.deserialize(&mut deserializer)
+        .map_err(|e| e.within(field, 0).nested())
+}
+
+/// Splits a map into two halves, or returns `None` if it has fewer than two entries
+pub(crate) fn split_map<K: Ord + Clone, V: Clone>(
+    map: &BTreeMap<K, V>,
+) -> Option<(BTreeMap<K, V>, BTreeMap<K, V>)> {
+    if map.len() < 2 {
+        return None;
+    }
+    let mut first: BTreeMap<K, V> = map.clone();
+    let middle = map.keys().nth(map.len() / 2)?.clone();
+    let second = first.split_off(&middle);
+    Some((first, second))
+}
+
+/// The part of a layout table which an object in its graph was created for
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Owner {
+    /// A lookup table (or one of its extension subtables)
+    Lookup(usize),
+    /// The given subtable of the given lookup
+    Subtable(usize, usize),
+}
+
+/// The common structure of the `GSUB` and `GPOS` tables
+///
+/// The feature variations table of version 1.1 tables is not currently read,
+/// and a warning is logged when one is dropped.
+#[derive(Debug, PartialEq, Clone)]
+pub struct LayoutTable<T> {
+    /// The scripts and language systems supported by this table
+    pub scripts: ScriptList,
+    /// The features of this table
+    pub features: FeatureList,
+    /// The lookups of this table, in lookup index order
+    pub lookups: Vec<Lookup<T>>,
+}
+
+impl<T> Default for LayoutTable<T> {
+    fn default() -> Self {
+        LayoutTable {
+            scripts: ScriptList::default(),
+            features: FeatureList::default(),
+            lookups: vec![],
+        }
+    }
+}
+
+struct LayoutTableVisitor<T> {
+    _phantom: PhantomData<T>,
+}
+
+impl<'de, T: LookupSubtable> Visitor<'de> for LayoutTableVisitor<T> {
+    type Value = LayoutTable<T>;
+
+    fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(formatter, "A layout table")
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+        let majorVersion = read_field!(seq, uint16, "a major version");
+        let minorVersion = read_field!(seq, uint16, "a minor version");
+        if majorVersion != 1 {
+            return Err(serde::de::Error::custom(format!(
+                "Unknown layout table version {:}.{:}",
+                majorVersion, minorVersion
+            )));
+        }
+        let scriptListOffset = read_field!(seq, uint16, "a script list offset");
+        let featureListOffset = read_field!(seq, uint16, "a feature list offset");
+        let lookupListOffset = read_field!(seq, uint16, "a lookup list offset");
+        let mut header_len = 10;
+        if minorVersion > 0 {
+            let featureVariationsOffset = read_field!(seq, uint32, "a feature variations offset");
+            if featureVariationsOffset != 0 {
+                log::warn!(
+                    "Dropping the feature variations of a layout table, which are not supported"
+                );
+            }
+            header_len += 4;
+        }
+        let remainder = read_remainder!(seq, "a layout table");
+        let mut table = LayoutTable::default();
+        if scriptListOffset > 0 {
+            table.scripts = deserialize_at(
+                &remainder,
+                scriptListOffset.into(),
+                header_len,
+                "scriptList",
+            )?;
+        }
+        if featureListOffset > 0 {
+            table.features = deserialize_at(
+                &remainder,
+                featureListOffset.into(),
+                header_len,
+                "featureList",
+            )?;
+        }
+        if lookupListOffset > 0 {
+            let lookupList: LookupList<T> = deserialize_at(
+                &remainder,
+                lookupListOffset.into(),
+                header_len,
+                "lookupList",
+            )?;
+            table.lookups = lookupList.lookups;
+        }
+        Ok(table)
+    }
+}
+
+impl<'de, T: LookupSubtable> Deserialize<'de> for LayoutTable<T> {
+    fn deserialize<D>(d: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        d.deserialize_seq(LayoutTableVisitor {
+            _phantom: PhantomData,
+        })
+    }
+}
+
+impl<T: LookupSubtable> LayoutTable<T> {
+    /// Builds the object graph of the table, returning the graph, its root,
+    /// and the lookup or subtable each object belongs to
+    fn to_graph(
+        &self,
+        lookups: &[Lookup<T>],
+        extension: &[bool],
+    ) -> otspec::error::Result<(ObjectGraph, ObjectId, HashMap<ObjectId, Owner>)> {
+        let mut graph = ObjectGraph::new();
+        let mut owners = HashMap::new();
+        let scripts = self.scripts.to_object(&mut graph)?;
+        let features = self.features.to_object(&mut graph)?;
+        let mut lookupList = Object::new();
+        lookupList.push(&(lookups.len() as uint16))?;
+        for (i, lookup) in lookups.iter().enumerate() {
+            let mut subtables = Vec::with_capacity(lookup.subtables.len());
+            for (j, subtable) in lookup.subtables.iter().enumerate() {
+                let start = graph.len();
+                subtables.push(subtable.to_object(&mut graph)?);
+                owners.extend((start..graph.len()).map(|id| (id, Owner::Subtable(i, j))));
+            }
+            let start = graph.len();
+            let id = lookup.to_object_with_subtables(&mut graph, &subtables, extension[i])?;
+            owners.extend((start..graph.len()).map(|id| (id, Owner::Lookup(i))));
+            lookupList.push_offset16(id);
+        }
+        let lookupList = graph.add(lookupList);
+        let mut header = Object::new();
+        header.push(&1_u16)?;
+        header.push(&0_u16)?;
+        header.push_offset16(scripts);
+        header.push_offset16(features);
+        header.push_offset16(lookupList);
+        let root = graph.add(header);
+        Ok((graph, root, owners))
+    }
+
+    /// Serializes the table to binary
+    ///
+    /// If some offsets are too large to be represented, the overflowing
+    /// lookups are promoted to Extension lookups, and overflowing subtables
+    /// are split into smaller subtables, until the table can be written.
+    pub fn to_bytes(&self) -> otspec::error::Result<Vec<u8>> {
+        let mut lookups = self.lookups.clone();
+        let mut extension = vec![false; lookups.len()];
+        loop {
+            let (graph, root, owners) = self.to_graph(&lookups, &extension)?;
+            let overflows = match graph.pack(root) {
+                Ok(binary) => return Ok(binary),
+                Err(overflows) => overflows,
+            };
+            let mut promote = BTreeSet::new();
+            let mut split = BTreeSet::new();
+            for overflow in overflows {
+                let parent = owners.get(&overflow.parent).copied();
+                let child = owners.get(&overflow.child).copied();
+                match (parent, child) {
+                    // Promoting a lookup to an extension moves its subtables
+                    // out of the way of other lookups, so try that first
+                    (_, Some(Owner::Lookup(i)))
+                    | (Some(Owner::Lookup(i)), _)
+                    | (Some(Owner::Subtable(i, _)), _)
+                        if !extension[i] =>
+                    {
+                        promote.insert(i);
+                    }
+                    (Some(Owner::Lookup(_)), Some(Owner::Subtable(i, j)))
+                    | (Some(Owner::Subtable(i, j)), _) => {
+                        split.insert((i, j));
+                    }
+                    _ => {}
+                }
+            }
+            let mut progress = false;
+            for i in promote {
+                extension[i] = true;
+                progress = true;
+            }
+            // Split from the back so that earlier subtable indices stay valid
+            for &(i, j) in split.iter().rev() {
+                if let Some((first, second)) = lookups[i].subtables[j].split() {
+                    lookups[i].subtables.splice(j..=j, vec![first, second]);
+                    progress = true;
+                }
+            }
+            if !progress {
+                return Err(otspec::error::Error::OffsetOverflow);
+            }
+        }
+    }
+}
+
+impl<T: LookupSubtable> Serialize for LayoutTable<T> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let binary = self.to_bytes().map_err(serde::ser::Error::custom)?;
+        serializer.serialize_bytes(&binary)
+    }
+}
@@ -0,0 +1,185 @@
+use otspec::ser::graph::{Object, ObjectGraph, ObjectId, ToObject};
+use otspec::types::*;
+use otspec::{deserialize_visitor, read_field, read_field_counted};
+use otspec_macros::tables;
+use serde::de::SeqAccess;
+use serde::de::Visitor;
+use serde::{Deserialize, Deserializer, Serialize};
+use std::collections::BTreeMap;
+
+tables!(ClassRangeRecord {
+    uint16 startGlyphID
+    uint16 endGlyphID
+    uint16 class
+});
+
+/// A class definition table, assigning glyphs to numbered classes
+///
+/// Glyphs which are not mentioned in the table are in class zero, and are
+/// not stored in the `classes` map.
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct ClassDef {
+    /// A mapping of glyph IDs to class values
+    pub classes: BTreeMap<uint16, uint16>,
+}
+
+impl ClassDef {
+    /// Returns the class of a given glyph
+    pub fn get(&self, glyph: uint16) -> uint16 {
+        *self.classes.get(&glyph).unwrap_or(&0)
+    }
+
+    /// Returns the glyphs in a given (non-zero) class, in glyph ID order
+    pub fn glyphs_in_class(&self, class: uint16) -> Vec<uint16> {
+        self.classes
+            .iter()
+            .filter(|(_, &c)| c == class)
+            .map(|(&g, _)| g)
+            .collect()
+    }
+}
+
+deserialize_visitor!(
+    ClassDef,
+    ClassDefVisitor,
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+        let format = read_field!(seq, uint16, "a class definition format");
+        let mut classes = BTreeMap::new();
+        match format {
+            1 => {
+                let startGlyphID = read_field!(seq, uint16, "a start glyph");
+                let count = read_field!(seq, uint16, "a glyph count");
+                let classValues: Vec<uint16> =
+                    read_field_counted!(seq, count, "a class value array");
+                for (glyph, class) in (startGlyphID..=uint16::MAX).zip(classValues) {
+                    if class != 0 {
+                        classes.insert(glyph, class);
+                    }
+                }
+            }
+            2 => {
+                let count = read_field!(seq, uint16, "a class range count");
+                let ranges: Vec<ClassRangeRecord> =
+                    read_field_counted!(seq, count, "a class range record array");
+                let mut covered = 0;
+                for range in ranges {
+                    if range.class == 0 {
+                        continue;
+                    }
+                    // Ranges should not overlap, so can't cover more glyphs than a font has
+                    covered += range.endGlyphID.saturating_sub(range.startGlyphID) as usize + 1;
+                    if covered > 0x10000 {
+                        return Err(serde::de::Error::custom(
+                            "Class ranges cover more than 65536 glyphs",
+                        ));
+                    }
+                    for glyph in range.startGlyphID..=range.endGlyphID {
+                        classes.insert(glyph, range.class);
+                    }
+                }
+            }
+            _ => {
+                return Err(serde::de::Error::custom(format!(
+                    "Unknown class definition format {:}",
+                    format
+                )))
+            }
+        }
+        Ok(ClassDef { classes })
+    }
+);
+
+impl ToObject for ClassDef {
+    fn to_object(&self, graph: &mut ObjectGraph) -> otspec::error::Result<ObjectId> {
+        let mut ranges: Vec<ClassRangeRecord> = vec![];
+        for (&glyph, &class) in &self.classes {
+            match ranges.last_mut() {
+                Some(last)
+                    if last.class == class && last.endGlyphID.checked_add(1) == Some(glyph) =>
+                {
+                    last.endGlyphID = glyph
+                }
+                _ => ranges.push(ClassRangeRecord {
+                    startGlyphID: glyph,
+                    endGlyphID: glyph,
+                    class,
+                }),
+            }
+        }
+        let mut object = Object::new();
+        let first = self.classes.keys().next();
+        let last = self.classes.keys().next_back();
+        // Use whichever format is smaller; an empty table is written as format 2
+        match (first, last) {
+            (Some(&first), Some(&last)) if (last - first) as usize + 1 < ranges.len() * 3 => {
+                object.push(&1_u16)?;
+                object.push(&first)?;
+                object.push(&(last - first + 1))?;
+                for glyph in first..=last {
+                    object.push(&self.get(glyph))?;
+                }
+            }
+            _ => {
+                object.push(&2_u16)?;
+                object.push(&(ranges.len() as uint16))?;
+                object.push(&ranges)?;
+            }
+        }
+        Ok(graph.add(object))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::layout::ClassDef;
+    use otspec::ser::graph::{ObjectGraph, ToObject};
+
+    #[test]
+    fn classdef_de_format1() {
+        let binary = vec![
+            0x00, 0x01, 0x00, 0x04, 0x00, 0x03, 0x00, 0x01, 0x00, 0x00, 0x00, 0x02,
+        ];
+        let classdef: ClassDef = otspec::de::from_bytes(&binary).unwrap();
+        assert_eq!(classdef.get(4), 1);
+        assert_eq!(classdef.get(5), 0);
+        assert_eq!(classdef.get(6), 2);
+        assert_eq!(classdef.classes.len(), 2);
+    }
+
+    #[test]
+    fn classdef_de_format2() {
+        let binary = vec![
+            0x00, 0x02, 0x00, 0x02, 0x00, 0x01, 0x00, 0x03, 0x00, 0x01, 0x00, 0x08, 0x00, 0x08,
+            0x00, 0x02,
+        ];
+        let classdef: ClassDef = otspec::de::from_bytes(&binary).unwrap();
+        assert_eq!(classdef.glyphs_in_class(1), vec![1, 2, 3]);
+        assert_eq!(classdef.glyphs_in_class(2), vec![8]);
+    }
+
+    #[test]
+    fn classdef_ser() {
+        let mut graph = ObjectGraph::new();
+        let classdef = ClassDef {
+            classes: vec![(4, 1), (6, 2)].into_iter().collect(),
+        };
+        let root = classdef.to_object(&mut graph).unwrap();
+        assert_eq!(
+            graph.pack(root).unwrap(),
+            vec![0x00, 0x01, 0x00, 0x04, 0x00, 0x03, 0x00, 0x01, 0x00, 0x00, 0x00, 0x02]
+        );
+
+        let classdef = ClassDef {
+            classes: vec![(1, 1), (2, 1), (3, 1), (8, 2)].into_iter().collect(),
+        };
+        let root = classdef.to_object(&mut graph).unwrap();
+        let binary = graph.pack(root).unwrap();
+        assert_eq!(
+            binary,
+            vec![
+                0x00, 0x02, 0x00, 0x02, 0x00, 0x01, 0x00, 0x03, 0x00, 0x01, 0x00, 0x08, 0x00, 0x08,
+                0x00, 0x02,
+            ]
+        );
+    }
+}
@@ -0,0 +1,669 @@
+use crate::layout::{deserialize_at, deserialize_seed_at, ClassDef, Coverage};
+use otspec::ser::graph::{Object, ObjectGraph, ObjectId, ToObject};
+use otspec::types::*;
+use otspec::{
+    deserialize_visitor, read_field, read_field_counted, read_remainder, stateful_deserializer,
+};
+use otspec_macros::tables;
+use serde::de::SeqAccess;
+use serde::de::{DeserializeSeed, Visitor};
+use serde::{Deserialize, Deserializer, Serialize};
+
+/// A lookup to be applied at a given position within an input sequence
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Copy)]
+pub struct SequenceLookupRecord {
+    /// Index of the glyph within the input sequence
+    pub sequenceIndex: uint16,
+    /// Index into the lookup list of the lookup to apply
+    pub lookupListIndex: uint16,
+}
+
+tables!(ChainedSequenceContextFormat3 {
+    uint16 format
+    Counted(Offset16(Coverage)) backtrackCoverages
+    Counted(Offset16(Coverage)) inputCoverages
+    Counted(Offset16(Coverage)) lookaheadCoverages
+    Counted(SequenceLookupRecord) seqLookupRecords
+});
+
+/// A coverage-based chained sequence context subtable (format 3)
+///
+/// This is used by both chained contextual substitution (`GSUB` lookup type 6)
+/// and chained contextual positioning (`GPOS` lookup type 8). It also stores
+/// the coverage-based (non-chained) contexts of `GSUB` lookup type 5 and
+/// `GPOS` lookup type 7, whose backtrack and lookahead coverages are empty.
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct ChainedSequenceContext {
+    /// Coverages of the backtrack sequence, in reverse order (nearest glyph first)
+    pub backtrackCoverages: Vec<Coverage>,
+    /// Coverages of the input sequence
+    pub inputCoverages: Vec<Coverage>,
+    /// Coverages of the lookahead sequence
+    pub lookaheadCoverages: Vec<Coverage>,
+    /// Lookups to apply at positions within the input sequence
+    pub seqLookupRecords: Vec<SequenceLookupRecord>,
+}
+
+deserialize_visitor!(
+    ChainedSequenceContext,
+    ChainedSequenceContextVisitor,
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+        let table = read_field!(
+            seq,
+            ChainedSequenceContextFormat3,
+            "a chained sequence context"
+        );
+        if table.format != 3 {
+            return Err(serde::de::Error::custom(format!(
+                "Unsupported chained sequence context format {:}",
+                table.format
+            )));
+        }
+        Ok(ChainedSequenceContext {
+            backtrackCoverages: table.backtrackCoverages,
+            inputCoverages: table.inputCoverages,
+            lookaheadCoverages: table.lookaheadCoverages,
+            seqLookupRecords: table.seqLookupRecords,
+        })
+    }
+);
+
+impl ChainedSequenceContext {
+    /// The number of glyphs matched from the current glyph onwards: the input
+    /// and lookahead sequences
+    pub fn max_context(&self) -> usize {
+        self.inputCoverages.len() + self.lookaheadCoverages.len()
+    }
+
+    /// Deserializes a (non-chained) coverage-based sequence context subtable
+    /// (format 3), which has no backtrack or lookahead coverages
+    pub(crate) fn from_context_bytes(data: &[u8]) -> otspec::error::Result<Self> {
+        Ok(otspec::de::from_bytes::<SequenceContextFormat3>(data)?.0)
+    }
+
+    /// Builds the object of a (non-chained) coverage-based sequence context
+    /// subtable (format 3)
+    pub(crate) fn to_context_object(
+        &self,
+        graph: &mut ObjectGraph,
+    ) -> otspec::error::Result<ObjectId> {
+        if !self.backtrackCoverages.is_empty() || !self.lookaheadCoverages.is_empty() {
+            return Err(otspec::error::Error::Message(
+                "Non-chained contexts cannot have backtrack or lookahead coverages".to_string(),
+            ));
+        }
+        let mut object = Object::new();
+        object.push(&3_u16)?;
+        object.push(&(self.inputCoverages.len() as uint16))?;
+        object.push(&(self.seqLookupRecords.len() as uint16))?;
+        for coverage in &self.inputCoverages {
+            let id = coverage.to_object(graph)?;
+            object.push_offset16(id);
+        }
+        object.push(&self.seqLookupRecords)?;
+        Ok(graph.add(object))
+    }
+}
+
+/// A (non-chained) coverage-based sequence context subtable (format 3)
+struct SequenceContextFormat3(ChainedSequenceContext);
+
+deserialize_visitor!(
+    SequenceContextFormat3,
+    SequenceContextFormat3Visitor,
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+        let format = read_field!(seq, uint16, "a sequence context format");
+        if format != 3 {
+            return Err(serde::de::Error::custom(format!(
+                "Expected sequence context format 3, found {:}",
+                format
+            )));
+        }
+        let glyphCount = read_field!(seq, uint16, "a glyph count");
+        let seqLookupCount = read_field!(seq, uint16, "a sequence lookup count");
+        let offsets: Vec<uint16> = read_field_counted!(seq, glyphCount, "coverage offsets");
+        let seqLookupRecords: Vec<SequenceLookupRecord> =
+            read_field_counted!(seq, seqLookupCount, "sequence lookup records");
+        let header_len = 6 + 2 * glyphCount as usize + 4 * seqLookupCount as usize;
+        let remainder = read_remainder!(seq, "a sequence context subtable");
+        let mut inputCoverages = Vec::with_capacity(offsets.len());
+        for (i, offset) in offsets.into_iter().enumerate() {
+            inputCoverages.push(deserialize_at(
+                &remainder,
+                offset.into(),
+                header_len,
+                &format!("inputCoverages[{}]", i),
+            )?);
+        }
+        Ok(SequenceContextFormat3(ChainedSequenceContext {
+            backtrackCoverages: vec![],
+            inputCoverages,
+            lookaheadCoverages: vec![],
+            seqLookupRecords,
+        }))
+    }
+);
+
+/// A rule of a glyph-based or class-based sequence context subtable
+///
+/// The sequences hold glyph IDs in glyph-based subtables and class values in
+/// class-based subtables. The first element of the input sequence is not
+/// stored, as it is given by the rule set containing the rule.
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct SequenceRule {
+    /// The backtrack sequence, in reverse order (nearest glyph first)
+    pub backtrack: Vec<uint16>,
+    /// The input sequence, after its first element
+    pub input: Vec<uint16>,
+    /// The lookahead sequence
+    pub lookahead: Vec<uint16>,
+    /// Lookups to apply at positions within the input sequence
+    pub seqLookupRecords: Vec<SequenceLookupRecord>,
+}
+
+/// The class definitions of a class-based sequence context subtable
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct SequenceClassDefs {
+    /// Classes of the backtrack sequence (unused in non-chained contexts)
+    pub backtrack: ClassDef,
+    /// Classes of the input sequence
+    pub input: ClassDef,
+    /// Classes of the lookahead sequence (unused in non-chained contexts)
+    pub lookahead: ClassDef,
+}
+
+/// A glyph-based (format 1) or class-based (format 2) sequence context subtable
+///
+/// This is used by both contextual and chained contextual lookups. Rules of
+/// (non-chained) contextual lookups have no backtrack or lookahead sequences.
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct SequenceContext {
+    /// The glyphs which can start a match
+    pub coverage: Coverage,
+    /// Class definitions, if the rules match glyph classes rather than glyphs
+    pub classDefs: Option<SequenceClassDefs>,
+    /// Rule sets, each tried in order. Glyph-based subtables have one set per
+    /// coverage glyph; class-based subtables index the sets by the class of
+    /// the first input glyph.
+    pub ruleSets: Vec<Vec<SequenceRule>>,
+}
+
+impl SequenceContext {
+    /// The number of glyphs matched from the current glyph onwards by the
+    /// longest rule
+    pub fn max_context(&self) -> usize {
+        self.ruleSets
+            .iter()
+            .flatten()
+            .map(|rule| 1 + rule.input.len() + rule.lookahead.len())
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// Returns the glyphs which start a match of the rules in the given set
+    pub fn first_glyphs(&self, set: usize) -> Vec<uint16> {
+        match &self.classDefs {
+            None => self.coverage.glyphs.get(set).into_iter().copied().collect(),
+            Some(classDefs) => self
+                .coverage
+                .glyphs
+                .iter()
+                .filter(|&&g| classDefs.input.get(g) as usize == set)
+                .copied()
+                .collect(),
+        }
+    }
+
+    /// Deserializes a chained or non-chained subtable
+    pub(crate) fn from_bytes(data: &[u8], chained: bool) -> otspec::error::Result<Self> {
+        deserialize_seed_at(data, 0, 0, "", SequenceContextDeserializer { chained })
+    }
+
+    /// Builds the object of a chained or non-chained subtable
+    pub(crate) fn to_object(
+        &self,
+        graph: &mut ObjectGraph,
+        chained: bool,
+    ) -> otspec::error::Result<ObjectId> {
+        let coverage = self.coverage.to_object(graph)?;
+        let mut object = Object::new();
+        match &self.classDefs {
+            None => {
+                if self.ruleSets.len() != self.coverage.glyphs.len() {
+                    return Err(otspec::error::Error::Message(
+                        "Rule set count does not match coverage".to_string(),
+                    ));
+                }
+                object.push(&1_u16)?;
+                object.push_offset16(coverage);
+            }
+            Some(classDefs) => {
+                object.push(&2_u16)?;
+                object.push_offset16(coverage);
+                if chained {
+                    let backtrack = classDefs.backtrack.to_object(graph)?;
+                    object.push_offset16(backtrack);
+                }
+                let input = classDefs.input.to_object(graph)?;
+                object.push_offset16(input);
+                if chained {
+                    let lookahead = classDefs.lookahead.to_object(graph)?;
+                    object.push_offset16(lookahead);
+                }
+            }
+        }
+        object.push(&(self.ruleSets.len() as uint16))?;
+        for rules in &self.ruleSets {
+            if rules.is_empty() {
+                object.push_optional_offset16(None);
+                continue;
+            }
+            let mut set = Object::new();
+            set.push(&(rules.len() as uint16))?;
+            for rule in rules {
+                let id = rule.to_object(graph, chained)?;
+                set.push_offset16(id);
+            }
+            object.push_offset16(graph.add(set));
+        }
+        Ok(graph.add(object))
+    }
+}
+
+impl SequenceRule {
+    fn to_object(&self, graph: &mut ObjectGraph, chained: bool) -> otspec::error::Result<ObjectId> {
+        let mut object = Object::new();
+        let inputCount = self.input.len() as uint16 + 1;
+        if chained {
+            object.push(&(self.backtrack.len() as uint16))?;
+            object.push(&self.backtrack)?;
+            object.push(&inputCount)?;
+            object.push(&self.input)?;
+            object.push(&(self.lookahead.len() as uint16))?;
+            object.push(&self.lookahead)?;
+            object.push(&(self.seqLookupRecords.len() as uint16))?;
+        } else {
+            if !self.backtrack.is_empty() || !self.lookahead.is_empty() {
+                return Err(otspec::error::Error::Message(
+                    "Non-chained rules cannot have backtrack or lookahead sequences".to_string(),
+                ));
+            }
+            object.push(&inputCount)?;
+            object.push(&(self.seqLookupRecords.len() as uint16))?;
+            object.push(&self.input)?;
+        }
+        object.push(&self.seqLookupRecords)?;
+        Ok(graph.add(object))
+    }
+}
+
+/// Reads a class definition table, treating a NULL offset as an empty table
+fn class_def_at<E: serde::de::Error>(
+    remainder: &[u8],
+    offset: uint16,
+    header_len: usize,
+    field: &str,
+) -> Result<ClassDef, E> {
+    if offset == 0 {
+        return Ok(ClassDef::default());
+    }
+    deserialize_at(remainder, offset.into(), header_len, field)
+}
+
+stateful_deserializer!(
+    SequenceContext,
+    SequenceContextDeserializer,
+    { chained: bool },
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+        let format = read_field!(seq, uint16, "a sequence context format");
+        let coverageOffset = read_field!(seq, uint16, "a coverage offset");
+        let (classDefOffsets, mut header_len) = match (format, self.chained) {
+            (1, _) => (None, 6),
+            (2, false) => {
+                let input = read_field!(seq, uint16, "a class definition offset");
+                (Some([0, input, 0]), 8)
+            }
+            (2, true) => {
+                let backtrack = read_field!(seq, uint16, "a class definition offset");
+                let input = read_field!(seq, uint16, "a class definition offset");
+                let lookahead = read_field!(seq, uint16, "a class definition offset");
+                (Some([backtrack, input, lookahead]), 12)
+            }
+            _ => {
+                return Err(serde::de::Error::custom(format!(
+                    "Unexpected sequence context format {:}",
+                    format
+                )))
+            }
+        };
+        let count = read_field!(seq, uint16, "a rule set count");
+        let offsets: Vec<uint16> = read_field_counted!(seq, count, "rule set offsets");
+        header_len += 2 * count as usize;
+        let remainder = read_remainder!(seq, "a sequence context subtable");
+        let coverage: Coverage = deserialize_at(
+            &remainder,
+            coverageOffset.into(),
+            header_len,
+            "coverage",
+        )?;
+        let classDefs = match classDefOffsets {
+            None => {
+                if coverage.glyphs.len() != offsets.len() {
+                    return Err(serde::de::Error::custom(
+                        "Rule set count does not match coverage",
+                    ));
+                }
+                None
+            }
+            Some([backtrack, input, lookahead]) => Some(SequenceClassDefs {
+                backtrack: class_def_at(&remainder, backtrack, header_len, "backtrackClassDef")?,
+                input: class_def_at(&remainder, input, header_len, "inputClassDef")?,
+                lookahead: class_def_at(&remainder, lookahead, header_len, "lookaheadClassDef")?,
+            }),
+        };
+        let mut ruleSets = Vec::with_capacity(offsets.len());
+        for (i, offset) in offsets.into_iter().enumerate() {
+            if offset == 0 {
+                ruleSets.push(vec![]);
+                continue;
+            }
+            ruleSets.push(deserialize_seed_at(
+                &remainder,
+                offset.into(),
+                header_len,
+                &format!("ruleSets[{}]", i),
+                RuleSetDeserializer {
+                    chained: self.chained,
+                },
+            )?);
+        }
+        Ok(SequenceContext {
+            coverage,
+            classDefs,
+            ruleSets,
+        })
+    }
+);
+
+stateful_deserializer!(
+    Vec<SequenceRule>,
+    RuleSetDeserializer,
+    { chained: bool },
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+        let count = read_field!(seq, uint16, "a rule count");
+        let offsets: Vec<uint16> = read_field_counted!(seq, count, "rule offsets");
+        let header_len = 2 + 2 * count as usize;
+        let remainder = read_remainder!(seq, "a rule set");
+        let mut rules = Vec::with_capacity(offsets.len());
+        for (i, offset) in offsets.into_iter().enumerate() {
+            rules.push(deserialize_seed_at(
+                &remainder,
+                offset.into(),
+                header_len,
+                &format!("[{}]", i),
+                RuleDeserializer {
+                    chained: self.chained,
+                },
+            )?);
+        }
+        Ok(rules)
+    }
+);
+
+/// Reads a counted array of a rule
+///
+/// Empty arrays are not read at all, as an ignore rule may end its subtable
+/// with an empty list of lookup records.
+fn read_sequence<'de, A, T>(seq: &mut A, count: uint16, name: &str) -> Result<Vec<T>, A::Error>
+where
+    A: SeqAccess<'de>,
+    T: Deserialize<'de>,
+{
+    if count == 0 {
+        return Ok(vec![]);
+    }
+    let sequence = read_field_counted!(seq, count, name);
+    Ok(sequence)
+}
+
+stateful_deserializer!(
+    SequenceRule,
+    RuleDeserializer,
+    { chained: bool },
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+        let mut rule = SequenceRule::default();
+        let seqLookupCount;
+        if self.chained {
+            let count = read_field!(seq, uint16, "a backtrack count");
+            rule.backtrack = read_sequence(&mut seq, count, "a backtrack sequence")?;
+            let count = read_field!(seq, uint16, "an input count");
+            if count == 0 {
+                return Err(serde::de::Error::custom("Empty input sequence"));
+            }
+            rule.input = read_sequence(&mut seq, count - 1, "an input sequence")?;
+            let count = read_field!(seq, uint16, "a lookahead count");
+            rule.lookahead = read_sequence(&mut seq, count, "a lookahead sequence")?;
+            seqLookupCount = read_field!(seq, uint16, "a sequence lookup count");
+        } else {
+            let count = read_field!(seq, uint16, "an input count");
+            if count == 0 {
+                return Err(serde::de::Error::custom("Empty input sequence"));
+            }
+            seqLookupCount = read_field!(seq, uint16, "a sequence lookup count");
+            rule.input = read_sequence(&mut seq, count - 1, "an input sequence")?;
+        }
+        rule.seqLookupRecords = read_sequence(&mut seq, seqLookupCount, "sequence lookup records")?;
+        Ok(rule)
+    }
+);
+
+impl ToObject for ChainedSequenceContext {
+    fn to_object(&self, graph: &mut ObjectGraph) -> otspec::error::Result<ObjectId> {
+        ChainedSequenceContextFormat3 {
+            format: 3,
+            backtrackCoverages: self.backtrackCoverages.clone(),
+            inputCoverages: self.inputCoverages.clone(),
+            lookaheadCoverages: self.lookaheadCoverages.clone(),
+            seqLookupRecords: self.seqLookupRecords.clone(),
+        }
+        .to_object(graph)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::layout::{
+        ChainedSequenceContext, ClassDef, Coverage, SequenceClassDefs, SequenceContext,
+        SequenceLookupRecord, SequenceRule,
+    };
+    use otspec::ser::graph::{ObjectGraph, ToObject};
+    use std::iter::FromIterator;
+
+    fn record(sequenceIndex: u16, lookupListIndex: u16) -> SequenceLookupRecord {
+        SequenceLookupRecord {
+            sequenceIndex,
+            lookupListIndex,
+        }
+    }
+
+    fn round_trip(context: &SequenceContext, chained: bool) -> Vec<u8> {
+        let mut graph = ObjectGraph::new();
+        let root = context.to_object(&mut graph, chained).unwrap();
+        let binary = graph.pack(root).unwrap();
+        let deserialized = SequenceContext::from_bytes(&binary, chained).unwrap();
+        assert_eq!(&deserialized, context);
+        binary
+    }
+
+    #[test]
+    fn chained_sequence_context_ser() {
+        let context = ChainedSequenceContext {
+            backtrackCoverages: vec![Coverage { glyphs: vec![1, 2] }],
+            inputCoverages: vec![Coverage { glyphs: vec![3] }, Coverage { glyphs: vec![4] }],
+            lookaheadCoverages: vec![],
+            seqLookupRecords: vec![SequenceLookupRecord {
+                sequenceIndex: 1,
+                lookupListIndex: 0,
+            }],
+        };
+        let mut graph = ObjectGraph::new();
+        let root = context.to_object(&mut graph).unwrap();
+        let binary = graph.pack(root).unwrap();
+        assert_eq!(&binary[..4], &[0x00, 0x03, 0x00, 0x01]);
+        let deserialized: ChainedSequenceContext = otspec::de::from_bytes(&binary).unwrap();
+        assert_eq!(deserialized, context);
+    }
+
+    #[test]
+    fn sequence_context_de() {
+        let binary = vec![
+            0x00, 0x01, // format
+            0x00, 0x08, // coverage offset
+            0x00, 0x01, // rule set count
+            0x00, 0x0E, // rule set offset
+            0x00, 0x01, 0x00, 0x01, 0x00, 0x05, // coverage: glyph 5
+            0x00, 0x01, 0x00, 0x04, // rule set: one rule
+            0x00, 0x02, 0x00, 0x01, // glyph count, lookup count
+            0x00, 0x06, // input glyph 6
+            0x00, 0x00, 0x00, 0x03, // lookup 3 at index 0
+        ];
+        let context = SequenceContext::from_bytes(&binary, false).unwrap();
+        assert_eq!(
+            context,
+            SequenceContext {
+                coverage: Coverage { glyphs: vec![5] },
+                classDefs: None,
+                ruleSets: vec![vec![SequenceRule {
+                    input: vec![6],
+                    seqLookupRecords: vec![record(0, 3)],
+                    ..Default::default()
+                }]],
+            }
+        );
+        assert_eq!(context.max_context(), 2);
+        assert_eq!(context.first_glyphs(0), vec![5]);
+        assert!(SequenceContext::from_bytes(&binary[..26], false).is_err());
+    }
+
+    #[test]
+    fn sequence_context_glyphs_ser() {
+        let context = SequenceContext {
+            coverage: Coverage {
+                glyphs: vec![5, 7, 9],
+            },
+            classDefs: None,
+            ruleSets: vec![
+                vec![
+                    SequenceRule {
+                        input: vec![6, 7],
+                        seqLookupRecords: vec![record(0, 3), record(2, 4)],
+                        ..Default::default()
+                    },
+                    SequenceRule {
+                        input: vec![6],
+                        seqLookupRecords: vec![record(1, 3)],
+                        ..Default::default()
+                    },
+                ],
+                vec![],
+                vec![SequenceRule {
+                    input: vec![],
+                    seqLookupRecords: vec![record(0, 1)],
+                    ..Default::default()
+                }],
+            ],
+        };
+        let binary = round_trip(&context, false);
+        assert_eq!(&binary[..2], &[0x00, 0x01]);
+        // The empty rule set is written as a NULL offset
+        assert_eq!(&binary[8..10], &[0x00, 0x00]);
+        assert_eq!(context.max_context(), 3);
+
+        let mut chained = context;
+        chained.ruleSets[1] = vec![SequenceRule {
+            backtrack: vec![1, 2],
+            input: vec![],
+            lookahead: vec![3],
+            // An ignore rule, which may end the subtable
+            seqLookupRecords: vec![],
+        }];
+        round_trip(&chained, true);
+        assert_eq!(chained.max_context(), 3);
+        assert!(chained.to_object(&mut ObjectGraph::new(), false).is_err());
+    }
+
+    #[test]
+    fn sequence_context_classes_ser() {
+        let classDefs = SequenceClassDefs {
+            backtrack: ClassDef {
+                classes: FromIterator::from_iter(vec![(1, 1), (2, 1)]),
+            },
+            input: ClassDef {
+                classes: FromIterator::from_iter(vec![(5, 1), (6, 2), (7, 1)]),
+            },
+            lookahead: ClassDef {
+                classes: FromIterator::from_iter(vec![(3, 1)]),
+            },
+        };
+        let context = SequenceContext {
+            coverage: Coverage {
+                glyphs: vec![5, 6, 7],
+            },
+            classDefs: Some(classDefs.clone()),
+            ruleSets: vec![
+                vec![],
+                vec![SequenceRule {
+                    backtrack: vec![1],
+                    input: vec![2, 0],
+                    lookahead: vec![1],
+                    seqLookupRecords: vec![record(1, 2)],
+                }],
+                vec![],
+            ],
+        };
+        let binary = round_trip(&context, true);
+        assert_eq!(&binary[..2], &[0x00, 0x02]);
+        assert_eq!(context.first_glyphs(1), vec![5, 7]);
+        assert_eq!(context.first_glyphs(2), vec![6]);
+        assert_eq!(context.max_context(), 4);
+
+        // Non-chained subtables only store the input class definitions
+        let context = SequenceContext {
+            classDefs: Some(SequenceClassDefs {
+                input: classDefs.input,
+                ..Default::default()
+            }),
+            ruleSets: vec![
+                vec![],
+                vec![SequenceRule {
+                    input: vec![2, 0],
+                    seqLookupRecords: vec![record(1, 2)],
+                    ..Default::default()
+                }],
+            ],
+            ..context
+        };
+        round_trip(&context, false);
+    }
+
+    #[test]
+    fn sequence_context_format3_ser() {
+        let context = ChainedSequenceContext {
+            inputCoverages: vec![Coverage { glyphs: vec![3] }, Coverage { glyphs: vec![4] }],
+            seqLookupRecords: vec![record(1, 0)],
+            ..Default::default()
+        };
+        let mut graph = ObjectGraph::new();
+        let root = context.to_context_object(&mut graph).unwrap();
+        let binary = graph.pack(root).unwrap();
+        assert_eq!(&binary[..6], &[0x00, 0x03, 0x00, 0x02, 0x00, 0x01]);
+        let deserialized = ChainedSequenceContext::from_context_bytes(&binary).unwrap();
+        assert_eq!(deserialized, context);
+
+        let chained = ChainedSequenceContext {
+            backtrackCoverages: vec![Coverage { glyphs: vec![1] }],
+            ..context
+        };
+        assert!(chained.to_context_object(&mut ObjectGraph::new()).is_err());
+    }
+}
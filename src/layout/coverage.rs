@@ -0,0 +1,151 @@
+use otspec::ser::graph::{Object, ObjectGraph, ObjectId, ToObject};
+use otspec::types::*;
+use otspec::{deserialize_visitor, read_field, read_field_counted};
+use otspec_macros::tables;
+use serde::de::SeqAccess;
+use serde::de::Visitor;
+use serde::{Deserialize, Deserializer, Serialize};
+
+tables!(RangeRecord {
+    uint16 startGlyphID
+    uint16 endGlyphID
+    uint16 startCoverageIndex
+});
+
+/// A coverage table, listing the glyphs that a subtable applies to
+///
+/// Glyphs are stored in coverage index order, so the position of a glyph
+/// within the `glyphs` vector is its coverage index.
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct Coverage {
+    /// The glyph IDs covered by this table
+    pub glyphs: Vec<uint16>,
+}
+
+deserialize_visitor!(
+    Coverage,
+    CoverageVisitor,
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+        let format = read_field!(seq, uint16, "a coverage format");
+        match format {
+            1 => {
+                let count = read_field!(seq, uint16, "a glyph count");
+                let glyphs: Vec<uint16> = read_field_counted!(seq, count, "a glyph array");
+                Ok(Coverage { glyphs })
+            }
+            2 => {
+                let count = read_field!(seq, uint16, "a range count");
+                let ranges: Vec<RangeRecord> =
+                    read_field_counted!(seq, count, "a range record array");
+                let mut glyphs = vec![];
+                for range in ranges {
+                    if range.endGlyphID < range.startGlyphID {
+                        return Err(serde::de::Error::custom(format!(
+                            "Bad coverage range {:}-{:}",
+                            range.startGlyphID, range.endGlyphID
+                        )));
+                    }
+                    // Ranges should not overlap, so can't cover more glyphs than a font has
+                    if glyphs.len() + (range.endGlyphID - range.startGlyphID) as usize >= 0x10000 {
+                        return Err(serde::de::Error::custom(
+                            "Coverage ranges cover more than 65536 glyphs",
+                        ));
+                    }
+                    glyphs.extend(range.startGlyphID..=range.endGlyphID);
+                }
+                Ok(Coverage { glyphs })
+            }
+            _ => Err(serde::de::Error::custom(format!(
+                "Unknown coverage format {:}",
+                format
+            ))),
+        }
+    }
+);
+
+impl Coverage {
+    /// Groups the glyphs into runs of consecutive glyph IDs
+    fn ranges(&self) -> Vec<RangeRecord> {
+        let mut ranges: Vec<RangeRecord> = vec![];
+        for (index, &glyph) in self.glyphs.iter().enumerate() {
+            match ranges.last_mut() {
+                Some(last) if last.endGlyphID.checked_add(1) == Some(glyph) => {
+                    last.endGlyphID = glyph
+                }
+                _ => ranges.push(RangeRecord {
+                    startGlyphID: glyph,
+                    endGlyphID: glyph,
+                    startCoverageIndex: index as uint16,
+                }),
+            }
+        }
+        ranges
+    }
+}
+
+impl ToObject for Coverage {
+    fn to_object(&self, graph: &mut ObjectGraph) -> otspec::error::Result<ObjectId> {
+        let mut object = Object::new();
+        let ranges = self.ranges();
+        // Use whichever format is smaller
+        if ranges.len() * 3 < self.glyphs.len() {
+            object.push(&2_u16)?;
+            object.push(&(ranges.len() as uint16))?;
+            object.push(&ranges)?;
+        } else {
+            object.push(&1_u16)?;
+            object.push(&(self.glyphs.len() as uint16))?;
+            object.push(&self.glyphs)?;
+        }
+        Ok(graph.add(object))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::layout::Coverage;
+    use otspec::ser::graph::{ObjectGraph, ToObject};
+
+    #[test]
+    fn coverage_de_format1() {
+        let binary = vec![0x00, 0x01, 0x00, 0x03, 0x00, 0x05, 0x00, 0x07, 0x00, 0x0A];
+        let coverage: Coverage = otspec::de::from_bytes(&binary).unwrap();
+        assert_eq!(coverage.glyphs, vec![5, 7, 10]);
+    }
+
+    #[test]
+    fn coverage_de_format2() {
+        let binary = vec![
+            0x00, 0x02, 0x00, 0x02, 0x00, 0x04, 0x00, 0x06, 0x00, 0x00, 0x00, 0x0A, 0x00, 0x0A,
+            0x00, 0x03,
+        ];
+        let coverage: Coverage = otspec::de::from_bytes(&binary).unwrap();
+        assert_eq!(coverage.glyphs, vec![4, 5, 6, 10]);
+    }
+
+    #[test]
+    fn coverage_ser() {
+        let coverage = Coverage {
+            glyphs: vec![5, 7, 10],
+        };
+        let mut graph = ObjectGraph::new();
+        let root = coverage.to_object(&mut graph).unwrap();
+        let binary = graph.pack(root).unwrap();
+        assert_eq!(
+            binary,
+            vec![0x00, 0x01, 0x00, 0x03, 0x00, 0x05, 0x00, 0x07, 0x00, 0x0A]
+        );
+
+        let coverage = Coverage {
+            glyphs: (20..40).collect(),
+        };
+        let root = coverage.to_object(&mut graph).unwrap();
+        let binary = graph.pack(root).unwrap();
+        assert_eq!(
+            binary,
+            vec![0x00, 0x02, 0x00, 0x01, 0x00, 0x14, 0x00, 0x27, 0x00, 0x00]
+        );
+        let deserialized: Coverage = otspec::de::from_bytes(&binary).unwrap();
+        assert_eq!(deserialized, coverage);
+    }
+}
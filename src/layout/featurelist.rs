@@ -0,0 +1,85 @@
+use crate::layout::deserialize_at;
+use otspec::ser::graph::{Object, ObjectGraph, ObjectId, ToObject};
+use otspec::types::*;
+use otspec::{deserialize_visitor, read_field, read_field_counted, read_remainder};
+use otspec_macros::tables;
+use serde::de::SeqAccess;
+use serde::de::Visitor;
+use serde::{Deserialize, Deserializer, Serialize};
+
+tables!(
+    FeatureRecordHeader {
+        Tag featureTag
+        uint16 featureOffset
+    }
+    FeatureTable {
+        uint16 featureParamsOffset
+        Counted(uint16) lookupListIndices
+    }
+);
+
+/// A feature, associating a feature tag with a set of lookups
+///
+/// Feature parameters (used by `size`, `ssXX` and `cvXX` features) are not
+/// currently read.
+#[derive(Debug, PartialEq, Clone)]
+pub struct FeatureRecord {
+    /// The feature's tag
+    pub featureTag: Tag,
+    /// Indices into the lookup list of the lookups used by this feature
+    pub lookupListIndices: Vec<uint16>,
+}
+
+/// A feature list table, holding the features of a layout table
+///
+/// The same feature tag may appear multiple times, as different language
+/// systems may use different lookups for the same feature.
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct FeatureList {
+    /// The features, in feature index order
+    pub features: Vec<FeatureRecord>,
+}
+
+deserialize_visitor!(
+    FeatureList,
+    FeatureListVisitor,
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+        let count = read_field!(seq, uint16, "a feature count");
+        let records: Vec<FeatureRecordHeader> =
+            read_field_counted!(seq, count, "a feature record array");
+        let header_len = 2 + 6 * count as usize;
+        let remainder = read_remainder!(seq, "a feature list");
+        let mut features = Vec::with_capacity(records.len());
+        for (i, record) in records.into_iter().enumerate() {
+            let table: FeatureTable = deserialize_at(
+                &remainder,
+                record.featureOffset.into(),
+                header_len,
+                &format!("features[{}]", i),
+            )?;
+            features.push(FeatureRecord {
+                featureTag: record.featureTag,
+                lookupListIndices: table.lookupListIndices,
+            });
+        }
+        Ok(FeatureList { features })
+    }
+);
+
+impl ToObject for FeatureList {
+    fn to_object(&self, graph: &mut ObjectGraph) -> otspec::error::Result<ObjectId> {
+        let mut object = Object::new();
+        object.push(&(self.features.len() as uint16))?;
+        for record in &self.features {
+            let mut feature = Object::new();
+            feature.push(&FeatureTable {
+                featureParamsOffset: 0,
+                lookupListIndices: record.lookupListIndices.clone(),
+            })?;
+            let id = graph.add(feature);
+            object.push(&record.featureTag)?;
+            object.push_offset16(id);
+        }
+        Ok(graph.add(object))
+    }
+}
@@ -0,0 +1,242 @@
+use crate::layout::{data_at, deserialize_at};
+use bitflags::bitflags;
+use otspec::ser::graph::{Object, ObjectGraph, ObjectId, ToObject};
+use otspec::types::*;
+use otspec::{read_field, read_field_counted, read_remainder};
+use otspec_macros::tables;
+use serde::de::SeqAccess;
+use serde::de::Visitor;
+use serde::{Deserialize, Deserializer, Serialize};
+use std::marker::PhantomData;
+
+tables!(ExtensionFormat1 {
+    uint16 format
+    uint16 extensionLookupType
+    uint32 extensionOffset
+});
+
+bitflags! {
+    /// Flags which alter how a lookup is applied
+    #[derive(Serialize, Deserialize)]
+    pub struct LookupFlags: u16 {
+        /// Cursive attachment is applied right to left
+        const RIGHT_TO_LEFT = 0x0001;
+        /// Base glyphs are skipped
+        const IGNORE_BASE_GLYPHS = 0x0002;
+        /// Ligature glyphs are skipped
+        const IGNORE_LIGATURES = 0x0004;
+        /// Mark glyphs are skipped
+        const IGNORE_MARKS = 0x0008;
+        /// The lookup has a mark filtering set
+        const USE_MARK_FILTERING_SET = 0x0010;
+        /// Marks not in this mark attachment class are skipped
+        const MARK_ATTACHMENT_TYPE_MASK = 0xFF00;
+    }
+}
+
+impl LookupFlags {
+    /// Returns the mark attachment class used by this lookup (zero if none)
+    pub fn mark_attachment_type(&self) -> uint16 {
+        (*self & LookupFlags::MARK_ATTACHMENT_TYPE_MASK).bits() >> 8
+    }
+}
+
+/// A type of subtable which can be stored in a lookup
+///
+/// This is implemented by the `GSUB` and `GPOS` subtable enums, which decide
+/// how to parse a subtable based on the type of the lookup containing it.
+pub trait LookupSubtable: Sized + Clone + ToObject {
+    /// The lookup type used for Extension lookups in this table
+    const EXTENSION_TYPE: uint16;
+
+    /// Deserializes a subtable belonging to a lookup of the given type
+    fn from_lookup_bytes(lookupType: uint16, data: &[u8]) -> otspec::error::Result<Self>;
+
+    /// Splits the subtable into two smaller subtables which together have the
+    /// same effect, or returns `None` if it cannot be split any further
+    ///
+    /// This is used to resolve offset overflows when serializing.
+    fn split(&self) -> Option<(Self, Self)>;
+}
+
+/// A lookup, a set of subtables of the same type which are applied together
+///
+/// Extension lookups are transparently unwrapped when deserializing, so
+/// `lookupType` is always the type of the subtables stored within.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Lookup<T> {
+    /// The type of subtables in this lookup
+    pub lookupType: uint16,
+    /// Flags controlling how the lookup is applied
+    pub lookupFlag: LookupFlags,
+    /// The subtables of this lookup
+    pub subtables: Vec<T>,
+    /// Index into the GDEF mark glyph sets, if `USE_MARK_FILTERING_SET` is set
+    pub markFilteringSet: Option<uint16>,
+}
+
+impl<T: LookupSubtable> Lookup<T> {
+    /// Adds the lookup to the graph, given the objects of its subtables
+    ///
+    /// If `extension` is true, the lookup is written as an Extension lookup,
+    /// with each subtable reached through a 32-bit offset.
+    pub(crate) fn to_object_with_subtables(
+        &self,
+        graph: &mut ObjectGraph,
+        subtables: &[ObjectId],
+        extension: bool,
+    ) -> otspec::error::Result<ObjectId> {
+        let mut object = Object::new();
+        let mut lookupFlag = self.lookupFlag;
+        lookupFlag.set(
+            LookupFlags::USE_MARK_FILTERING_SET,
+            self.markFilteringSet.is_some(),
+        );
+        object.push(&if extension {
+            T::EXTENSION_TYPE
+        } else {
+            self.lookupType
+        })?;
+        object.push(&lookupFlag)?;
+        object.push(&(subtables.len() as uint16))?;
+        for &subtable in subtables {
+            if extension {
+                let mut wrapper = Object::new();
+                wrapper.push(&1_u16)?;
+                wrapper.push(&self.lookupType)?;
+                wrapper.push_offset32(subtable);
+                object.push_offset16(graph.add(wrapper));
+            } else {
+                object.push_offset16(subtable);
+            }
+        }
+        if let Some(markFilteringSet) = self.markFilteringSet {
+            object.push(&markFilteringSet)?;
+        }
+        Ok(graph.add(object))
+    }
+}
+
+struct LookupVisitor<T> {
+    _phantom: PhantomData<T>,
+}
+
+impl<'de, T: LookupSubtable> Visitor<'de> for LookupVisitor<T> {
+    type Value = Lookup<T>;
+
+    fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(formatter, "A lookup table")
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+        let mut lookupType = read_field!(seq, uint16, "a lookup type");
+        let lookupFlag = LookupFlags::from_bits_truncate(read_field!(seq, uint16, "a lookup flag"));
+        let count = read_field!(seq, uint16, "a subtable count");
+        let offsets: Vec<uint16> = read_field_counted!(seq, count, "a subtable offset array");
+        let mut header_len = 6 + 2 * count as usize;
+        let markFilteringSet = if lookupFlag.contains(LookupFlags::USE_MARK_FILTERING_SET) {
+            header_len += 2;
+            Some(read_field!(seq, uint16, "a mark filtering set"))
+        } else {
+            None
+        };
+        let remainder = read_remainder!(seq, "a lookup table");
+        let is_extension = lookupType == T::EXTENSION_TYPE;
+        let mut subtables = Vec::with_capacity(offsets.len());
+        for (i, offset) in offsets.iter().enumerate() {
+            let mut data = data_at(
+                &remainder,
+                *offset as usize,
+                header_len,
+                "a lookup subtable",
+            )?;
+            let path = format!("subtables[{}]", i);
+            let mut start = *offset as usize;
+            if is_extension {
+                let extension: ExtensionFormat1 = otspec::de::from_bytes(data)
+                    .map_err(|e: otspec::error::Error| e.within(&path, start).nested())?;
+                if i > 0 && extension.extensionLookupType != lookupType {
+                    return Err(serde::de::Error::custom(
+                        "Extension subtables have differing lookup types",
+                    ));
+                }
+                lookupType = extension.extensionLookupType;
+                data = data_at(
+                    data,
+                    extension.extensionOffset as usize,
+                    0,
+                    "an extension subtable",
+                )?;
+                start += extension.extensionOffset as usize;
+            }
+            subtables.push(
+                T::from_lookup_bytes(lookupType, data)
+                    .map_err(|e| e.within(&path, start).nested())?,
+            );
+        }
+        Ok(Lookup {
+            lookupType,
+            lookupFlag,
+            subtables,
+            markFilteringSet,
+        })
+    }
+}
+
+impl<'de, T: LookupSubtable> Deserialize<'de> for Lookup<T> {
+    fn deserialize<D>(d: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        d.deserialize_seq(LookupVisitor {
+            _phantom: PhantomData,
+        })
+    }
+}
+
+/// A lookup list table, holding all the lookups of a layout table
+#[derive(Debug, PartialEq, Clone)]
+pub struct LookupList<T> {
+    /// The lookups, in lookup index order
+    pub lookups: Vec<Lookup<T>>,
+}
+
+struct LookupListVisitor<T> {
+    _phantom: PhantomData<T>,
+}
+
+impl<'de, T: LookupSubtable> Visitor<'de> for LookupListVisitor<T> {
+    type Value = LookupList<T>;
+
+    fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(formatter, "A lookup list table")
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+        let count = read_field!(seq, uint16, "a lookup count");
+        let offsets: Vec<uint16> = read_field_counted!(seq, count, "a lookup offset array");
+        let header_len = 2 + 2 * count as usize;
+        let remainder = read_remainder!(seq, "a lookup list");
+        let mut lookups = Vec::with_capacity(offsets.len());
+        for (i, offset) in offsets.into_iter().enumerate() {
+            lookups.push(deserialize_at(
+                &remainder,
+                offset as usize,
+                header_len,
+                &format!("lookups[{}]", i),
+            )?);
+        }
+        Ok(LookupList { lookups })
+    }
+}
+
+impl<'de, T: LookupSubtable> Deserialize<'de> for LookupList<T> {
+    fn deserialize<D>(d: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        d.deserialize_seq(LookupListVisitor {
+            _phantom: PhantomData,
+        })
+    }
+}
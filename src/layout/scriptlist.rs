@@ -0,0 +1,170 @@
+use crate::layout::deserialize_at;
+use otspec::ser::graph::{Object, ObjectGraph, ObjectId, ToObject};
+use otspec::types::*;
+use otspec::{deserialize_visitor, read_field, read_field_counted, read_remainder};
+use otspec_macros::tables;
+use serde::de::SeqAccess;
+use serde::de::Visitor;
+use serde::{Deserialize, Deserializer, Serialize};
+use std::collections::BTreeMap;
+
+tables!(
+    ScriptRecord {
+        Tag scriptTag
+        uint16 scriptOffset
+    }
+    LangSysRecord {
+        Tag langSysTag
+        uint16 langSysOffset
+    }
+);
+
+/// A language system table, listing the features used for a script and language
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct LangSys {
+    /// Index of a feature required for this language system, if any
+    pub requiredFeatureIndex: Option<uint16>,
+    /// Indices into the feature list of the features used by this language system
+    pub featureIndices: Vec<uint16>,
+}
+
+deserialize_visitor!(
+    LangSys,
+    LangSysVisitor,
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+        let _lookupOrderOffset = read_field!(seq, uint16, "a reserved offset");
+        let required = read_field!(seq, uint16, "a required feature index");
+        let count = read_field!(seq, uint16, "a feature index count");
+        let featureIndices: Vec<uint16> = read_field_counted!(seq, count, "a feature index array");
+        Ok(LangSys {
+            requiredFeatureIndex: if required == 0xFFFF {
+                None
+            } else {
+                Some(required)
+            },
+            featureIndices,
+        })
+    }
+);
+
+impl ToObject for LangSys {
+    fn to_object(&self, graph: &mut ObjectGraph) -> otspec::error::Result<ObjectId> {
+        let mut object = Object::new();
+        object.push(&0_u16)?;
+        object.push(&self.requiredFeatureIndex.unwrap_or(0xFFFF))?;
+        object.push(&(self.featureIndices.len() as uint16))?;
+        object.push(&self.featureIndices)?;
+        Ok(graph.add(object))
+    }
+}
+
+/// A script table, holding the language systems for a given script
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct Script {
+    /// The language system used when no specific language is requested
+    pub defaultLangSys: Option<LangSys>,
+    /// Language systems for specific languages, keyed by language tag
+    pub langSys: BTreeMap<Tag, LangSys>,
+}
+
+deserialize_visitor!(
+    Script,
+    ScriptVisitor,
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+        let defaultLangSysOffset = read_field!(seq, uint16, "a default language system offset");
+        let count = read_field!(seq, uint16, "a language system count");
+        let records: Vec<LangSysRecord> =
+            read_field_counted!(seq, count, "a language system record array");
+        let header_len = 4 + 6 * count as usize;
+        let remainder = read_remainder!(seq, "a script table");
+        let defaultLangSys = if defaultLangSysOffset > 0 {
+            Some(deserialize_at(
+                &remainder,
+                defaultLangSysOffset.into(),
+                header_len,
+                "defaultLangSys",
+            )?)
+        } else {
+            None
+        };
+        let mut langSys = BTreeMap::new();
+        for (i, record) in records.into_iter().enumerate() {
+            langSys.insert(
+                record.langSysTag,
+                deserialize_at(
+                    &remainder,
+                    record.langSysOffset.into(),
+                    header_len,
+                    &format!("langSys[{}]", i),
+                )?,
+            );
+        }
+        Ok(Script {
+            defaultLangSys,
+            langSys,
+        })
+    }
+);
+
+impl ToObject for Script {
+    fn to_object(&self, graph: &mut ObjectGraph) -> otspec::error::Result<ObjectId> {
+        let defaultLangSys = self
+            .defaultLangSys
+            .as_ref()
+            .map(|langsys| langsys.to_object(graph))
+            .transpose()?;
+        let mut object = Object::new();
+        object.push_optional_offset16(defaultLangSys);
+        object.push(&(self.langSys.len() as uint16))?;
+        for (tag, langsys) in &self.langSys {
+            let id = langsys.to_object(graph)?;
+            object.push(tag)?;
+            object.push_offset16(id);
+        }
+        Ok(graph.add(object))
+    }
+}
+
+/// A script list table, holding the scripts supported by a layout table
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct ScriptList {
+    /// Script tables, keyed by script tag
+    pub scripts: BTreeMap<Tag, Script>,
+}
+
+deserialize_visitor!(
+    ScriptList,
+    ScriptListVisitor,
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+        let count = read_field!(seq, uint16, "a script count");
+        let records: Vec<ScriptRecord> = read_field_counted!(seq, count, "a script record array");
+        let header_len = 2 + 6 * count as usize;
+        let remainder = read_remainder!(seq, "a script list");
+        let mut scripts = BTreeMap::new();
+        for (i, record) in records.into_iter().enumerate() {
+            scripts.insert(
+                record.scriptTag,
+                deserialize_at(
+                    &remainder,
+                    record.scriptOffset.into(),
+                    header_len,
+                    &format!("scripts[{}]", i),
+                )?,
+            );
+        }
+        Ok(ScriptList { scripts })
+    }
+);
+
+impl ToObject for ScriptList {
+    fn to_object(&self, graph: &mut ObjectGraph) -> otspec::error::Result<ObjectId> {
+        let mut object = Object::new();
+        object.push(&(self.scripts.len() as uint16))?;
+        for (tag, script) in &self.scripts {
+            let id = script.to_object(graph)?;
+            object.push(tag)?;
+            object.push_offset16(id);
+        }
+        Ok(graph.add(object))
+    }
+}
@@ -2,17 +2,25 @@
 #![allow(non_camel_case_types, non_snake_case, clippy::upper_case_acronyms)]
 
 /// The `avar` (Axis variations) table
-mod avar;
+pub mod avar;
 /// The `fvar` (Font variations) table
 pub mod cmap;
+/// Decompiling layout tables to OpenType feature file syntax
+pub mod fea;
 /// The main font object. Start here.
 pub mod font;
 /// The `fvar` (Font variations) table
 pub mod fvar;
 /// The `gasp` (Grid-fitting and Scan-conversion Procedure) table
 pub mod gasp;
+/// The `GDEF` (Glyph definition) table
+pub mod gdef;
 /// The `glyf` (Glyf data) table
 pub mod glyf;
+/// The `GPOS` (Glyph positioning) table
+pub mod gpos;
+/// The `GSUB` (Glyph substitution) table
+pub mod gsub;
 /// The `gvar` (Glyph variations) table
 pub mod gvar;
 /// The `head` (Header) table
@@ -21,7 +29,10 @@ pub mod head;
 pub mod hhea;
 /// The `hmtx` (Horizontal metrics) table
 pub mod hmtx;
-mod loca;
+/// OpenType Layout common tables
+pub mod layout;
+/// The `loca` (Index to location) table
+pub mod loca;
 /// The `maxp` (Maximum profile) table
 pub mod maxp;
 /// The `name` (Naming) table
@@ -32,3 +43,11 @@ pub mod os2;
 pub mod otvar;
 /// The `post` (PostScript) table
 pub mod post;
+/// Subsetting fonts to a chosen set of codepoints and glyphs
+pub mod subset;
+/// TTX (fontTools XML) dumping and compiling
+pub mod ttx;
+/// WOFF 1.0 web font encoding and decoding
+pub mod woff;
+/// WOFF2 web font encoding and decoding
+pub mod woff2;
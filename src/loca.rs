@@ -1,12 +1,24 @@
 use otspec::{read_field, stateful_deserializer};
 use serde::de::{DeserializeSeed, SeqAccess, Visitor};
-use serde::{Serialize, Serializer};
+use serde::{Deserialize, Serialize, Serializer};
 
-#[derive(Debug, PartialEq)]
+/// The loca table
+///
+/// The derived `Deserialize` is for human-readable formats; binary tables
+/// need the offset size from `head`, and are read with `from_bytes`.
+#[derive(Debug, PartialEq, Deserialize)]
 pub struct loca {
+    /// The offset of each glyph within the glyf table, or None if it is empty
     pub indices: Vec<Option<u32>>,
 }
 
+/// The loca table in human-readable formats
+#[derive(Serialize)]
+#[serde(remote = "loca")]
+struct LocaDef {
+    indices: Vec<Option<u32>>,
+}
+
 stateful_deserializer!(
     loca,
     LocaDeserializer,
@@ -44,17 +56,22 @@ stateful_deserializer!(
 );
 
 impl Serialize for loca {
-    fn serialize<S>(&self, _serializer: S) -> Result<S::Ok, S::Error>
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
     where
         S: Serializer,
     {
-        panic!(
-            "loca cannot be serialized directly. Call compile_glyf_loca_maxp on the font instead"
-        )
+        if serializer.is_human_readable() {
+            return LocaDef::serialize(self, serializer);
+        }
+        Err(serde::ser::Error::custom(
+            "loca cannot be serialized directly. Call compile_glyf_loca_maxp on the font instead",
+        ))
         // But we still want an impl here to dispatch the Table serializer in Font
     }
 }
 
+/// Deserializes a binary loca table, whose offsets are 32-bit if
+/// `locaIs32Bit` is set (as given by `head.indexToLocFormat`)
 pub fn from_bytes(s: &[u8], locaIs32Bit: bool) -> otspec::error::Result<loca> {
     let mut deserializer = otspec::de::Deserializer::from_bytes(s);
     let cs: LocaDeserializer = LocaDeserializer { locaIs32Bit };
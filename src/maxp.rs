@@ -52,6 +52,16 @@ pub struct maxp {
     pub table: MaxpVariant,
 }
 
+/// A maxp table in human-readable formats, which serialize it as derived
+#[derive(Deserialize)]
+#[serde(remote = "maxp")]
+struct MaxpDef {
+    #[serde(with = "Version16Dot16")]
+    version: U16F16,
+    #[serde(flatten)]
+    table: MaxpVariant,
+}
+
 impl maxp {
     /// Creates a new `maxp` table with version=0.5, given a number of glyphs
     pub fn new05(num_glyphs: u16) -> maxp {
@@ -108,6 +118,7 @@ impl maxp {
 deserialize_visitor!(
     maxp,
     MaxpVisitor,
+    readable: MaxpDef,
     fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
         let version = read_field!(seq, i32, "a maxp version");
         if version == 0x00005000 {
@@ -1,9 +1,10 @@
 #![allow(non_camel_case_types, non_snake_case)]
 
+use encoding::all::whatwg::X_USER_DEFINED;
 use encoding::all::{
     BIG5_2003, GBK, MAC_CYRILLIC, MAC_ROMAN, UTF_16BE, WINDOWS_1252, WINDOWS_31J, WINDOWS_949,
 };
-use encoding::{DecoderTrap, EncoderTrap, EncodingRef};
+use encoding::{DecoderTrap, EncoderTrap, Encoding, EncodingRef};
 use otspec::types::*;
 use otspec::{deserialize_visitor, read_field, read_field_counted, read_remainder};
 use otspec_macros::tables;
@@ -11,39 +12,67 @@ use serde::de::{SeqAccess, Visitor};
 use serde::ser::SerializeSeq;
 use serde::Serializer;
 use serde::{Deserialize, Deserializer, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
 
-fn get_encoding(platform_id: u16, encoding_id: u16) -> EncodingRef {
-    if platform_id == 0 {
-        return UTF_16BE;
-    }
-    if platform_id == 1 {
-        if encoding_id == 7 {
-            return MAC_CYRILLIC;
-        } else {
-            return MAC_ROMAN; // XXX NO THIS IS WRONG.
-        }
-    }
-    if platform_id == 2 {
-        match encoding_id {
-            0 => return WINDOWS_1252,
-            1 => return UTF_16BE,
-            2 => return WINDOWS_1252,
-            _ => unimplemented!(),
-        };
-    }
-    if platform_id == 3 {
-        match encoding_id {
-            0 => return UTF_16BE,
-            1 => return UTF_16BE,
-            2 => return WINDOWS_31J,
-            3 => return GBK,
-            4 => return BIG5_2003,
-            5 => return WINDOWS_949,
-            6 => unimplemented!(),
-            _ => return UTF_16BE,
-        };
+mod encodings;
+mod languages;
+
+use encodings::{
+    MAC_ARABIC, MAC_CENTRAL_EUROPEAN, MAC_CROATIAN, MAC_FARSI, MAC_GREEK, MAC_HEBREW,
+    MAC_ICELANDIC, MAC_ROMANIAN, MAC_TURKISH,
+};
+use languages::{is_language_tag, mac_language_script};
+pub use languages::{mac_language_id, mac_language_tag, windows_language_id, windows_language_tag};
+
+/// Returns the encoding for a Mac script, if one is known
+///
+/// Text in the Roman script is encoded differently for some languages, as is
+/// Persian in the Arabic script.
+fn mac_encoding(script_id: u16, language_id: u16) -> Option<EncodingRef> {
+    match (script_id, language_id) {
+        (0, 15) => Some(MAC_ICELANDIC),
+        (0, 17) => Some(MAC_TURKISH),
+        (0, 18) => Some(MAC_CROATIAN),
+        (0, 24..=26) | (0, 38..=40) => Some(MAC_CENTRAL_EUROPEAN),
+        (0, 37) => Some(MAC_ROMANIAN),
+        (0, _) => Some(MAC_ROMAN),
+        (1, _) => Some(WINDOWS_31J),
+        (2, _) => Some(BIG5_2003),
+        (3, _) => Some(WINDOWS_949),
+        (4, 31) => Some(MAC_FARSI),
+        (4, _) => Some(MAC_ARABIC),
+        (5, _) => Some(MAC_HEBREW),
+        (6, _) => Some(MAC_GREEK),
+        (7, _) => Some(MAC_CYRILLIC),
+        (25, _) => Some(GBK),
+        (29, _) => Some(MAC_CENTRAL_EUROPEAN),
+        _ => None,
     }
-    unimplemented!()
+}
+
+/// Returns the encoding of a name record's string
+///
+/// Strings in encodings which are not known are kept as raw bytes: ASCII
+/// bytes are read as ASCII, and others as the private use characters
+/// U+F780 to U+F7FF, so that they are written back out unchanged.
+fn get_encoding(platform_id: u16, encoding_id: u16, language_id: u16) -> EncodingRef {
+    let encoding: Option<EncodingRef> = match (platform_id, encoding_id) {
+        (0, _) => Some(UTF_16BE),
+        (1, _) => mac_encoding(encoding_id, language_id),
+        (2, 0) => Some(WINDOWS_1252),
+        (2, 1) => Some(UTF_16BE),
+        (2, 2) => Some(WINDOWS_1252),
+        (3, 0) => Some(UTF_16BE),
+        (3, 1) => Some(UTF_16BE),
+        (3, 2) => Some(WINDOWS_31J),
+        (3, 3) => Some(GBK),
+        (3, 4) => Some(BIG5_2003),
+        (3, 5) => Some(WINDOWS_949),
+        (3, 6) => None,
+        (3, _) => Some(UTF_16BE),
+        _ => None,
+    };
+    encoding.unwrap_or(X_USER_DEFINED)
 }
 
 /// Descriptive names of the name table nameID entries
@@ -118,10 +147,14 @@ tables!(
         uint16 length
         uint16 stringOffset
     }
+    LangTagRecord {
+        uint16 length
+        uint16 langTagOffset
+    }
 );
 
 /// A single name record to be placed inside the name table
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
 pub struct NameRecord {
     /// Platform ID (0=Unicode, 1=Macintosh, 3=Windows)
     pub platformID: uint16,
@@ -133,6 +166,12 @@ pub struct NameRecord {
     pub nameID: uint16,
     /// The actual content
     pub string: String,
+    /// BCP-47 tag for the language of a record with a language ID from 0x8000
+    ///
+    /// Such records refer to the table's language-tag records, and are written
+    /// with language IDs numbering their distinct tags.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub languageTag: Option<String>,
 }
 
 impl NameRecord {
@@ -149,6 +188,17 @@ impl NameRecord {
             languageID: 0x409,
             nameID: n.into(),
             string: s.into(),
+            languageTag: None,
+        }
+    }
+
+    /// Returns the BCP-47 tag for the record's language, if it is known
+    pub fn language_tag(&self) -> Option<&str> {
+        match (self.platformID, &self.languageTag) {
+            (_, Some(tag)) => Some(tag),
+            (1, None) => mac_language_tag(self.languageID),
+            (3, None) => windows_language_tag(self.languageID),
+            _ => None,
         }
     }
 }
@@ -159,24 +209,264 @@ pub struct name {
     pub records: Vec<NameRecord>,
 }
 
+impl name {
+    /// Returns the string for a name ID
+    ///
+    /// The records for each of the preferred (platform ID, language ID) pairs
+    /// are tried in turn, then English records for Windows and for the Mac,
+    /// and then any record for the name ID.
+    pub fn get<T>(&self, nameID: T, preferences: &[(uint16, uint16)]) -> Option<&str>
+    where
+        T: Into<u16>,
+    {
+        let nameID = nameID.into();
+        let records: Vec<&NameRecord> =
+            self.records.iter().filter(|r| r.nameID == nameID).collect();
+        preferences
+            .iter()
+            .chain(&[(3, 0x409), (1, 0)])
+            .find_map(|&(platformID, languageID)| {
+                records
+                    .iter()
+                    .find(|r| r.platformID == platformID && r.languageID == languageID)
+            })
+            .or_else(|| records.first())
+            .map(|r| r.string.as_str())
+    }
+
+    /// Sets the English string for a name ID
+    ///
+    /// The English records for the name ID are replaced by a Windows Unicode
+    /// record and, if `mac` is true, a Mac Roman record, so that they agree.
+    pub fn set_name<T, U>(&mut self, nameID: T, string: U, mac: bool)
+    where
+        T: Into<u16>,
+        U: Into<String>,
+    {
+        let (nameID, string) = (nameID.into(), string.into());
+        self.records.retain(|r| {
+            r.nameID != nameID || !matches!((r.platformID, r.languageID), (3, 0x409) | (1, 0))
+        });
+        if mac {
+            self.records.push(NameRecord {
+                platformID: 1,
+                encodingID: 0,
+                languageID: 0,
+                nameID,
+                string: string.clone(),
+                languageTag: None,
+            });
+        }
+        self.records.push(NameRecord {
+            platformID: 3,
+            encodingID: 1,
+            languageID: 0x409,
+            nameID,
+            string,
+            languageTag: None,
+        });
+        self.sort_records();
+    }
+
+    /// Removes all the records for a name ID
+    pub fn remove<T: Into<u16>>(&mut self, nameID: T) {
+        let nameID = nameID.into();
+        self.records.retain(|r| r.nameID != nameID);
+    }
+
+    /// Adds a name in several languages, from a map of BCP-47 tags to strings
+    ///
+    /// A name ID from 256 upwards which already has all the strings is reused;
+    /// otherwise the strings are added under the next unused name ID. Windows
+    /// Unicode records are written for each language, using a language-tag
+    /// record for languages without a Windows language ID, and, if `mac` is
+    /// true, Mac records for each language with a Mac language ID whose
+    /// script's encoding can represent the string. Returns the name ID, or
+    /// `None` if no records could be written.
+    pub fn add_multilingual(
+        &mut self,
+        names: &BTreeMap<String, String>,
+        mac: bool,
+    ) -> Option<uint16> {
+        let mut wanted: Vec<NameRecord> = vec![];
+        for (tag, string) in names {
+            let windows = match windows_language_id(tag) {
+                Some(languageID) => Some((languageID, None)),
+                None if is_language_tag(tag) => {
+                    Some((self.language_tag_id(tag, &wanted), Some(tag.clone())))
+                }
+                None => None,
+            };
+            let mac = mac_language_id(tag).filter(|_| mac).and_then(|languageID| {
+                let script = mac_language_script(languageID)?;
+                mac_encoding(script, languageID)?
+                    .encode(string, EncoderTrap::Strict)
+                    .ok()
+                    .map(|_| (script, languageID))
+            });
+            if windows.is_none() && mac.is_none() {
+                log::warn!("'{}' is not a language tag, skipping its name", tag);
+            }
+            if let Some((languageID, languageTag)) = windows {
+                wanted.push(NameRecord {
+                    platformID: 3,
+                    encodingID: 1,
+                    languageID,
+                    nameID: 0,
+                    string: string.clone(),
+                    languageTag,
+                });
+            }
+            if let Some((encodingID, languageID)) = mac {
+                wanted.push(NameRecord {
+                    platformID: 1,
+                    encodingID,
+                    languageID,
+                    nameID: 0,
+                    string: string.clone(),
+                    languageTag: None,
+                });
+            }
+        }
+        if wanted.is_empty() {
+            return None;
+        }
+
+        let has = |nameID: uint16, w: &NameRecord| {
+            self.records.iter().any(|r| {
+                r.nameID == nameID
+                    && r.platformID == w.platformID
+                    && r.languageID == w.languageID
+                    && r.languageTag == w.languageTag
+                    && r.string == w.string
+            })
+        };
+        let existing: BTreeSet<uint16> = self
+            .records
+            .iter()
+            .map(|r| r.nameID)
+            .filter(|&nameID| nameID >= 256)
+            .collect();
+        if let Some(nameID) = existing
+            .into_iter()
+            .find(|&nameID| wanted.iter().all(|w| has(nameID, w)))
+        {
+            return Some(nameID);
+        }
+
+        let nameID = self
+            .records
+            .iter()
+            .map(|r| r.nameID.saturating_add(1))
+            .max()
+            .unwrap_or(0)
+            .max(256);
+        // Name IDs from 32768 are reserved
+        if nameID > 32767 {
+            log::warn!("No unused name IDs left for a new name");
+            return None;
+        }
+        self.records
+            .extend(wanted.into_iter().map(|r| NameRecord { nameID, ..r }));
+        self.sort_records();
+        Some(nameID)
+    }
+
+    /// Returns the language ID for records with a language tag
+    ///
+    /// The ID of existing records with the tag is reused; otherwise the tag
+    /// gets the ID after the highest in use, among the table's records and
+    /// the records still to be added.
+    fn language_tag_id(&self, tag: &str, pending: &[NameRecord]) -> uint16 {
+        let tagged = || {
+            self.records
+                .iter()
+                .chain(pending)
+                .filter(|r| r.languageTag.is_some())
+        };
+        tagged()
+            .find(|r| r.languageTag.as_deref() == Some(tag))
+            .map(|r| r.languageID)
+            .or_else(|| tagged().map(|r| r.languageID.saturating_add(1)).max())
+            .unwrap_or(0x8000)
+            .max(0x8000)
+    }
+
+    /// Sorts the records into the order the specification requires
+    fn sort_records(&mut self) {
+        self.records
+            .sort_by_key(|r| (r.platformID, r.encodingID, r.languageID, r.nameID));
+    }
+}
+
+/// The name table in human-readable formats, with its strings decoded and the
+/// language tags stored on the records
+#[derive(Serialize, Deserialize)]
+#[serde(remote = "name")]
+struct NameDef {
+    records: Vec<NameRecord>,
+}
+
+// Name records point into the string storage rather than at child tables,
+// so the strings are sliced out by hand
 deserialize_visitor!(
     name,
     NameVisitor,
+    readable: NameDef,
     fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
-        let _version = read_field!(seq, uint16, "a name table version");
+        let version = read_field!(seq, uint16, "a name table version");
         let count = read_field!(seq, uint16, "a count of name records");
-        let _offset = read_field!(seq, uint16, "an offset");
+        let offset = read_field!(seq, uint16, "an offset");
         let internal_records: Vec<NameRecordInternal> =
             read_field_counted!(seq, count, "name records");
+        let lang_tag_records: Vec<LangTagRecord> = if version > 0 {
+            let lang_tag_count = read_field!(seq, uint16, "a count of language-tag records");
+            read_field_counted!(seq, lang_tag_count, "language-tag records")
+        } else {
+            vec![]
+        };
         let remainder = read_remainder!(seq, "a name table string pool");
+        let header_length = 6
+            + 12 * internal_records.len()
+            + if version > 0 {
+                2 + 4 * lang_tag_records.len()
+            } else {
+                0
+            };
+        let string_pool = (offset as usize)
+            .checked_sub(header_length)
+            .and_then(|start| remainder.get(start..))
+            .ok_or_else(|| {
+                serde::de::Error::custom(format!(
+                    "The name table string pool offset {} lies outside the table",
+                    offset
+                ))
+            })?;
+        let get_string = |offset: uint16, length: uint16| {
+            let start = offset as usize;
+            string_pool.get(start..start + length as usize).ok_or_else(|| {
+                serde::de::Error::custom(format!(
+                    "A string at offset {} with length {} lies outside the string pool",
+                    offset, length
+                ))
+            })
+        };
+        let mut lang_tags: Vec<String> = Vec::with_capacity(lang_tag_records.len());
+        for ltr in lang_tag_records {
+            let tag_as_bytes = get_string(ltr.langTagOffset, ltr.length)?;
+            lang_tags.push(
+                UTF_16BE
+                    .decode(tag_as_bytes, DecoderTrap::Replace)
+                    .map_err(serde::de::Error::custom)?,
+            );
+        }
         let mut records: Vec<NameRecord> = Vec::with_capacity(count.into());
         for ir in internal_records {
-            let string_as_bytes =
-                &remainder[ir.stringOffset as usize..(ir.stringOffset + ir.length) as usize];
-            let encoding = get_encoding(ir.platformID, ir.encodingID);
+            let string_as_bytes = get_string(ir.stringOffset, ir.length)?;
+            let encoding = get_encoding(ir.platformID, ir.encodingID, ir.languageID);
             let string: String = encoding
                 .decode(string_as_bytes, DecoderTrap::Replace)
-                .unwrap();
+                .map_err(serde::de::Error::custom)?;
 
             records.push(NameRecord {
                 string,
@@ -184,6 +474,11 @@ deserialize_visitor!(
                 encodingID: ir.encodingID,
                 languageID: ir.languageID,
                 nameID: ir.nameID,
+                languageTag: ir
+                    .languageID
+                    .checked_sub(0x8000)
+                    .and_then(|index| lang_tags.get(index as usize))
+                    .cloned(),
             })
         }
 
@@ -196,28 +491,74 @@ impl Serialize for name {
     where
         S: Serializer,
     {
+        if serializer.is_human_readable() {
+            return NameDef::serialize(self, serializer);
+        }
+        // Tags are numbered in the order of the language IDs they have now
+        let mut tagged: Vec<&NameRecord> = self
+            .records
+            .iter()
+            .filter(|r| r.languageTag.is_some())
+            .collect();
+        tagged.sort_by_key(|r| r.languageID);
+        let mut lang_tags: Vec<&str> = vec![];
+        for tag in tagged.iter().filter_map(|r| r.languageTag.as_deref()) {
+            if !lang_tags.contains(&tag) {
+                lang_tags.push(tag);
+            }
+        }
+
         let mut string_pool: Vec<u8> = Vec::new();
-        let mut seq = serializer.serialize_seq(None)?;
-        let offset = 6 + 12 * self.records.len() as uint16;
-        seq.serialize_element(&(0 as uint16))?;
-        seq.serialize_element(&(self.records.len() as uint16))?;
-        seq.serialize_element(&offset)?;
+        let mut internal_records: Vec<NameRecordInternal> = vec![];
         for record in &self.records {
-            let encoder = get_encoding(record.platformID, record.encodingID);
+            let encoder = get_encoding(record.platformID, record.encodingID, record.languageID);
             let encoded = encoder
                 .encode(&record.string, EncoderTrap::Replace)
-                .unwrap();
-            let nri = NameRecordInternal {
+                .map_err(serde::ser::Error::custom)?;
+            let languageID = match &record.languageTag {
+                Some(tag) => 0x8000 + lang_tags.iter().position(|t| t == tag).unwrap() as uint16,
+                None => record.languageID,
+            };
+            internal_records.push(NameRecordInternal {
                 platformID: record.platformID,
                 encodingID: record.encodingID,
-                languageID: record.languageID,
+                languageID,
                 nameID: record.nameID,
                 length: encoded.len() as uint16,
                 stringOffset: string_pool.len() as uint16,
-            };
-            seq.serialize_element(&nri)?;
+            });
             string_pool.extend(encoded);
         }
+        let mut lang_tag_records: Vec<LangTagRecord> = vec![];
+        for tag in &lang_tags {
+            let encoded = UTF_16BE
+                .encode(tag, EncoderTrap::Replace)
+                .map_err(serde::ser::Error::custom)?;
+            lang_tag_records.push(LangTagRecord {
+                length: encoded.len() as uint16,
+                langTagOffset: string_pool.len() as uint16,
+            });
+            string_pool.extend(encoded);
+        }
+
+        let version: uint16 = if lang_tags.is_empty() { 0 } else { 1 };
+        let mut offset = 6 + 12 * self.records.len() as uint16;
+        if version > 0 {
+            offset += 2 + 4 * lang_tag_records.len() as uint16;
+        }
+        let mut seq = serializer.serialize_seq(None)?;
+        seq.serialize_element(&version)?;
+        seq.serialize_element(&(self.records.len() as uint16))?;
+        seq.serialize_element(&offset)?;
+        for nri in &internal_records {
+            seq.serialize_element(nri)?;
+        }
+        if version > 0 {
+            seq.serialize_element(&(lang_tag_records.len() as uint16))?;
+            for ltr in &lang_tag_records {
+                seq.serialize_element(ltr)?;
+            }
+        }
         seq.serialize_element(&string_pool)?;
         seq.end()
     }
@@ -226,7 +567,8 @@ impl Serialize for name {
 #[cfg(test)]
 mod tests {
     use crate::name;
-    use crate::name::NameRecord;
+    use crate::name::{NameRecord, NameRecordID};
+    use std::collections::BTreeMap;
     #[test]
     fn name_serde() {
         let fname = name::name {
@@ -237,6 +579,7 @@ mod tests {
                     languageID: 0,
                     nameID: 17,
                     string: "Regular".to_string(),
+                    languageTag: None,
                 },
                 NameRecord {
                     platformID: 1,
@@ -244,6 +587,7 @@ mod tests {
                     languageID: 0,
                     nameID: 256,
                     string: "weight".to_string(),
+                    languageTag: None,
                 },
                 NameRecord {
                     platformID: 1,
@@ -251,6 +595,7 @@ mod tests {
                     languageID: 0,
                     nameID: 257,
                     string: "slant".to_string(),
+                    languageTag: None,
                 },
                 NameRecord {
                     platformID: 3,
@@ -258,6 +603,7 @@ mod tests {
                     nameID: 17,
                     languageID: 0x409,
                     string: "Regular".to_string(),
+                    languageTag: None,
                 },
                 NameRecord {
                     platformID: 3,
@@ -265,6 +611,7 @@ mod tests {
                     nameID: 256,
                     languageID: 0x409,
                     string: "weight".to_string(),
+                    languageTag: None,
                 },
                 NameRecord {
                     platformID: 3,
@@ -272,6 +619,7 @@ mod tests {
                     nameID: 257,
                     languageID: 0x409,
                     string: "slant".to_string(),
+                    languageTag: None,
                 },
             ],
         };
@@ -292,4 +640,216 @@ mod tests {
         assert_eq!(deserialized, fname);
         assert_eq!(serialized, binary_name);
     }
+
+    fn record(platformID: u16, languageID: u16, nameID: u16, string: &str) -> NameRecord {
+        NameRecord {
+            platformID,
+            encodingID: if platformID == 3 { 1 } else { 0 },
+            languageID,
+            nameID,
+            string: string.to_string(),
+            languageTag: None,
+        }
+    }
+
+    fn tagged(languageID: u16, nameID: u16, string: &str, tag: &str) -> NameRecord {
+        NameRecord {
+            languageTag: Some(tag.to_string()),
+            ..record(3, languageID, nameID, string)
+        }
+    }
+
+    #[test]
+    fn name_get() {
+        let fname = name::name {
+            records: vec![
+                record(1, 0, 1, "Mac Family"),
+                record(3, 0x407, 1, "Familie"),
+                record(3, 0x409, 1, "Family"),
+                record(3, 0x407, 2, "Fett"),
+            ],
+        };
+        assert_eq!(fname.get(NameRecordID::FontFamilyName, &[]), Some("Family"));
+        assert_eq!(fname.get(1_u16, &[(3, 0x407)]), Some("Familie"));
+        assert_eq!(fname.get(1_u16, &[(1, 0), (3, 0x407)]), Some("Mac Family"));
+        assert_eq!(fname.get(2_u16, &[(3, 0x40C)]), Some("Fett"));
+        assert_eq!(fname.get(3_u16, &[]), None);
+    }
+
+    #[test]
+    fn name_set_and_remove() {
+        let mut fname = name::name {
+            records: vec![
+                record(3, 0x409, 2, "Bold"),
+                NameRecord::windows_unicode(1_u16, "Old"),
+                record(1, 0, 1, "Old"),
+                record(3, 0x407, 1, "Alt"),
+            ],
+        };
+        fname.set_name(NameRecordID::FontFamilyName, "New", true);
+        assert_eq!(
+            fname.records,
+            vec![
+                record(1, 0, 1, "New"),
+                record(3, 0x407, 1, "Alt"),
+                record(3, 0x409, 1, "New"),
+                record(3, 0x409, 2, "Bold"),
+            ]
+        );
+        // Without a Mac record, the old one is removed so the names agree
+        fname.set_name(1_u16, "Newer", false);
+        assert_eq!(fname.get(1_u16, &[(1, 0)]), Some("Newer"));
+        assert_eq!(fname.records.len(), 3);
+
+        fname.remove(1_u16);
+        assert_eq!(fname.records, vec![record(3, 0x409, 2, "Bold")]);
+    }
+
+    #[test]
+    fn name_add_multilingual() {
+        let mut fname = name::name {
+            records: vec![record(3, 0x409, 256, "Weight")],
+        };
+        let mut names = BTreeMap::new();
+        names.insert("en".to_string(), "Width".to_string());
+        names.insert("de-CH".to_string(), "Breite".to_string());
+        names.insert("x-unknown".to_string(), "???".to_string());
+        assert_eq!(fname.add_multilingual(&names, true), Some(257));
+        assert_eq!(
+            fname.records,
+            vec![
+                record(1, 0, 257, "Width"),
+                record(3, 0x409, 256, "Weight"),
+                record(3, 0x409, 257, "Width"),
+                record(3, 0x807, 257, "Breite"),
+                tagged(0x8000, 257, "???", "x-unknown"),
+            ]
+        );
+        // The same names are found again
+        assert_eq!(fname.add_multilingual(&names, false), Some(257));
+        assert_eq!(fname.records.len(), 5);
+
+        names.clear();
+        names.insert("EN".to_string(), "Weight".to_string());
+        assert_eq!(fname.add_multilingual(&names, false), Some(256));
+
+        names.clear();
+        names.insert("not a tag".to_string(), "???".to_string());
+        assert_eq!(fname.add_multilingual(&names, false), None);
+        assert_eq!(name::windows_language_tag(0x0C0A), Some("es"));
+        assert_eq!(name::windows_language_id("es"), Some(0x0C0A));
+    }
+
+    #[test]
+    fn name_mac_encodings() {
+        let mac = |encodingID: u16, languageID: u16, string: &str| NameRecord {
+            platformID: 1,
+            encodingID,
+            languageID,
+            nameID: 1,
+            string: string.to_string(),
+            languageTag: None,
+        };
+        let fname = name::name {
+            records: vec![
+                mac(0, 15, "Þýð"),
+                mac(1, 11, "日本語"),
+                mac(4, 12, "العربية"),
+                mac(5, 10, "עברית"),
+                mac(21, 22, "Thai \u{F7A1}\u{F7B9}"),
+                mac(6, 14, "Ελληνικά"),
+                mac(29, 38, "Čeština"),
+            ],
+        };
+        let binary_name = otspec::ser::to_bytes(&fname).unwrap();
+        let contains = |bytes: &[u8]| binary_name.windows(bytes.len()).any(|w| w == bytes);
+        assert!(contains(&[0xDE, 0xE0, 0xDD]));
+        assert!(contains(&[0x93, 0xFA, 0x96, 0x7B, 0x8C, 0xEA]));
+        assert!(contains(&[0xC7, 0xE4, 0xD9, 0xD1, 0xC8, 0xEA, 0xC9]));
+        assert!(contains(&[0xF2, 0xE1, 0xF8, 0xE9, 0xFA]));
+        // Mac Thai is not known, so its bytes are kept as they are
+        assert!(contains(b"Thai \xA1\xB9"));
+        assert!(contains(&[0xB6, 0xEC, 0xEC, 0xE8, 0xEE, 0xE9, 0xEB, 0xC0]));
+        assert!(contains(&[0x89, b'e', 0xE4, b't', b'i', b'n', b'a']));
+        let deserialized: name::name = otspec::de::from_bytes(&binary_name).unwrap();
+        assert_eq!(deserialized, fname);
+
+        assert_eq!(name::mac_language_tag(38), Some("cs"));
+        assert_eq!(name::mac_language_id("EL"), Some(14));
+        assert_eq!(name::mac_language_id("de-CH"), None);
+
+        let mut fname = name::name { records: vec![] };
+        let mut names = BTreeMap::new();
+        names.insert("cs".to_string(), "Šířka".to_string());
+        names.insert("el".to_string(), "Πλάτος".to_string());
+        names.insert("eo".to_string(), "Larĝo".to_string());
+        assert_eq!(fname.add_multilingual(&names, true), Some(256));
+        assert_eq!(
+            fname.records,
+            vec![
+                mac(6, 14, "Πλάτος"),
+                mac(29, 38, "Šířka"),
+                record(3, 0x405, 256, "Šířka"),
+                record(3, 0x408, 256, "Πλάτος"),
+                tagged(0x8000, 256, "Larĝo", "eo"),
+            ]
+            .into_iter()
+            .map(|r| NameRecord { nameID: 256, ..r })
+            .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn name_language_tags() {
+        #[rustfmt::skip]
+        let binary_name = vec![
+            0x00, 0x01, 0x00, 0x02, 0x00, 0x28,
+            0x00, 0x03, 0x00, 0x01, 0x80, 0x01, 0x00, 0x01, 0x00, 0x02, 0x00, 0x00,
+            0x00, 0x03, 0x00, 0x01, 0x80, 0x00, 0x00, 0x01, 0x00, 0x02, 0x00, 0x02,
+            0x00, 0x02,
+            0x00, 0x04, 0x00, 0x04,
+            0x00, 0x04, 0x00, 0x08,
+            0x00, 0x41, 0x00, 0x42, 0x00, 0x66, 0x00, 0x72, 0x00, 0x78, 0x00, 0x78,
+        ];
+        let fname = name::name {
+            records: vec![tagged(0x8001, 1, "A", "xx"), tagged(0x8000, 1, "B", "fr")],
+        };
+        let deserialized: name::name = otspec::de::from_bytes(&binary_name).unwrap();
+        assert_eq!(deserialized, fname);
+        let serialized = otspec::ser::to_bytes(&deserialized).unwrap();
+        assert_eq!(serialized, binary_name);
+        assert_eq!(fname.records[0].language_tag(), Some("xx"));
+        assert_eq!(record(3, 0x407, 1, "").language_tag(), Some("de"));
+        assert_eq!(record(1, 38, 1, "").language_tag(), Some("cs"));
+
+        // Tags are numbered afresh when written, and version 0 is written
+        // when there are none
+        let mut fname = name::name {
+            records: vec![tagged(0x8005, 1, "A", "xx"), record(3, 0x409, 1, "B")],
+        };
+        let serialized = otspec::ser::to_bytes(&fname).unwrap();
+        assert_eq!(&serialized[..2], &[0x00, 0x01]);
+        let deserialized: name::name = otspec::de::from_bytes(&serialized).unwrap();
+        assert_eq!(deserialized.records[0], tagged(0x8000, 1, "A", "xx"));
+        fname.remove(1_u16);
+        let serialized = otspec::ser::to_bytes(&fname).unwrap();
+        assert_eq!(serialized, vec![0x00, 0x00, 0x00, 0x00, 0x00, 0x06]);
+
+        let mut names = BTreeMap::new();
+        names.insert("fa-IR".to_string(), "قطر".to_string());
+        names.insert("en".to_string(), "Weight".to_string());
+        let mut fname = name::name {
+            records: vec![tagged(0x8000, 256, "A", "xx")],
+        };
+        assert_eq!(fname.add_multilingual(&names, false), Some(257));
+        assert_eq!(
+            fname.records,
+            vec![
+                record(3, 0x409, 257, "Weight"),
+                tagged(0x8000, 256, "A", "xx"),
+                tagged(0x8001, 257, "قطر", "fa-IR"),
+            ]
+        );
+        assert_eq!(fname.add_multilingual(&names, false), Some(257));
+    }
 }
@@ -0,0 +1,334 @@
+//! Macintosh single-byte encodings missing from the `encoding` crate
+//!
+//! The tables give the Unicode value of bytes 0x80 to 0xFF, following the
+//! Apple mapping files (Mac Arabic and Mac Farsi as in Python's codecs); the
+//! language variants are described by the bytes where they differ from their
+//! base encoding. Several Arabic and Hebrew bytes duplicate ASCII characters
+//! with a right-to-left direction: they are read as those characters, which
+//! are written back as ASCII.
+use encoding::codec::singlebyte::SingleByteEncoding;
+
+/// Unassigned bytes
+const NONE: u16 = 0xFFFF;
+
+#[rustfmt::skip]
+const ROMAN: [u16; 128] = [
+    0x00C4, 0x00C5, 0x00C7, 0x00C9, 0x00D1, 0x00D6, 0x00DC, 0x00E1, 0x00E0, 0x00E2, 0x00E4, 0x00E3, 0x00E5, 0x00E7, 0x00E9, 0x00E8,
+    0x00EA, 0x00EB, 0x00ED, 0x00EC, 0x00EE, 0x00EF, 0x00F1, 0x00F3, 0x00F2, 0x00F4, 0x00F6, 0x00F5, 0x00FA, 0x00F9, 0x00FB, 0x00FC,
+    0x2020, 0x00B0, 0x00A2, 0x00A3, 0x00A7, 0x2022, 0x00B6, 0x00DF, 0x00AE, 0x00A9, 0x2122, 0x00B4, 0x00A8, 0x2260, 0x00C6, 0x00D8,
+    0x221E, 0x00B1, 0x2264, 0x2265, 0x00A5, 0x00B5, 0x2202, 0x2211, 0x220F, 0x03C0, 0x222B, 0x00AA, 0x00BA, 0x03A9, 0x00E6, 0x00F8,
+    0x00BF, 0x00A1, 0x00AC, 0x221A, 0x0192, 0x2248, 0x2206, 0x00AB, 0x00BB, 0x2026, 0x00A0, 0x00C0, 0x00C3, 0x00D5, 0x0152, 0x0153,
+    0x2013, 0x2014, 0x201C, 0x201D, 0x2018, 0x2019, 0x00F7, 0x25CA, 0x00FF, 0x0178, 0x2044, 0x20AC, 0x2039, 0x203A, 0xFB01, 0xFB02,
+    0x2021, 0x00B7, 0x201A, 0x201E, 0x2030, 0x00C2, 0x00CA, 0x00C1, 0x00CB, 0x00C8, 0x00CD, 0x00CE, 0x00CF, 0x00CC, 0x00D3, 0x00D4,
+    0xF8FF, 0x00D2, 0x00DA, 0x00DB, 0x00D9, 0x0131, 0x02C6, 0x02DC, 0x00AF, 0x02D8, 0x02D9, 0x02DA, 0x00B8, 0x02DD, 0x02DB, 0x02C7,
+];
+
+#[rustfmt::skip]
+const CENTRAL_EUROPEAN: [u16; 128] = [
+    0x00C4, 0x0100, 0x0101, 0x00C9, 0x0104, 0x00D6, 0x00DC, 0x00E1, 0x0105, 0x010C, 0x00E4, 0x010D, 0x0106, 0x0107, 0x00E9, 0x0179,
+    0x017A, 0x010E, 0x00ED, 0x010F, 0x0112, 0x0113, 0x0116, 0x00F3, 0x0117, 0x00F4, 0x00F6, 0x00F5, 0x00FA, 0x011A, 0x011B, 0x00FC,
+    0x2020, 0x00B0, 0x0118, 0x00A3, 0x00A7, 0x2022, 0x00B6, 0x00DF, 0x00AE, 0x00A9, 0x2122, 0x0119, 0x00A8, 0x2260, 0x0123, 0x012E,
+    0x012F, 0x012A, 0x2264, 0x2265, 0x012B, 0x0136, 0x2202, 0x2211, 0x0142, 0x013B, 0x013C, 0x013D, 0x013E, 0x0139, 0x013A, 0x0145,
+    0x0146, 0x0143, 0x00AC, 0x221A, 0x0144, 0x0147, 0x2206, 0x00AB, 0x00BB, 0x2026, 0x00A0, 0x0148, 0x0150, 0x00D5, 0x0151, 0x014C,
+    0x2013, 0x2014, 0x201C, 0x201D, 0x2018, 0x2019, 0x00F7, 0x25CA, 0x014D, 0x0154, 0x0155, 0x0158, 0x2039, 0x203A, 0x0159, 0x0156,
+    0x0157, 0x0160, 0x201A, 0x201E, 0x0161, 0x015A, 0x015B, 0x00C1, 0x0164, 0x0165, 0x00CD, 0x017D, 0x017E, 0x016A, 0x00D3, 0x00D4,
+    0x016B, 0x016E, 0x00DA, 0x016F, 0x0170, 0x0171, 0x0172, 0x0173, 0x00DD, 0x00FD, 0x0137, 0x017B, 0x0141, 0x017C, 0x0122, 0x02C7,
+];
+
+#[rustfmt::skip]
+const GREEK: [u16; 128] = [
+    0x00C4, 0x00B9, 0x00B2, 0x00C9, 0x00B3, 0x00D6, 0x00DC, 0x0385, 0x00E0, 0x00E2, 0x00E4, 0x0384, 0x00A8, 0x00E7, 0x00E9, 0x00E8,
+    0x00EA, 0x00EB, 0x00A3, 0x2122, 0x00EE, 0x00EF, 0x2022, 0x00BD, 0x2030, 0x00F4, 0x00F6, 0x00A6, 0x20AC, 0x00F9, 0x00FB, 0x00FC,
+    0x2020, 0x0393, 0x0394, 0x0398, 0x039B, 0x039E, 0x03A0, 0x00DF, 0x00AE, 0x00A9, 0x03A3, 0x03AA, 0x00A7, 0x2260, 0x00B0, 0x00B7,
+    0x0391, 0x00B1, 0x2264, 0x2265, 0x00A5, 0x0392, 0x0395, 0x0396, 0x0397, 0x0399, 0x039A, 0x039C, 0x03A6, 0x03AB, 0x03A8, 0x03A9,
+    0x03AC, 0x039D, 0x00AC, 0x039F, 0x03A1, 0x2248, 0x03A4, 0x00AB, 0x00BB, 0x2026, 0x00A0, 0x03A5, 0x03A7, 0x0386, 0x0388, 0x0153,
+    0x2013, 0x2015, 0x201C, 0x201D, 0x2018, 0x2019, 0x00F7, 0x0389, 0x038A, 0x038C, 0x038E, 0x03AD, 0x03AE, 0x03AF, 0x03CC, 0x038F,
+    0x03CD, 0x03B1, 0x03B2, 0x03C8, 0x03B4, 0x03B5, 0x03C6, 0x03B3, 0x03B7, 0x03B9, 0x03BE, 0x03BA, 0x03BB, 0x03BC, 0x03BD, 0x03BF,
+    0x03C0, 0x03CE, 0x03C1, 0x03C3, 0x03C4, 0x03B8, 0x03C9, 0x03C2, 0x03C7, 0x03C5, 0x03B6, 0x03CA, 0x03CB, 0x0390, 0x03B0, 0x00AD,
+];
+
+#[rustfmt::skip]
+const ARABIC: [u16; 128] = [
+    0x00C4, 0x00A0, 0x00C7, 0x00C9, 0x00D1, 0x00D6, 0x00DC, 0x00E1, 0x00E0, 0x00E2, 0x00E4, 0x06BA, 0x00AB, 0x00E7, 0x00E9, 0x00E8,
+    0x00EA, 0x00EB, 0x00ED, 0x2026, 0x00EE, 0x00EF, 0x00F1, 0x00F3, 0x00BB, 0x00F4, 0x00F6, 0x00F7, 0x00FA, 0x00F9, 0x00FB, 0x00FC,
+    0x0020, 0x0021, 0x0022, 0x0023, 0x0024, 0x066A, 0x0026, 0x0027, 0x0028, 0x0029, 0x002A, 0x002B, 0x060C, 0x002D, 0x002E, 0x002F,
+    0x0660, 0x0661, 0x0662, 0x0663, 0x0664, 0x0665, 0x0666, 0x0667, 0x0668, 0x0669, 0x003A, 0x061B, 0x003C, 0x003D, 0x003E, 0x061F,
+    0x274A, 0x0621, 0x0622, 0x0623, 0x0624, 0x0625, 0x0626, 0x0627, 0x0628, 0x0629, 0x062A, 0x062B, 0x062C, 0x062D, 0x062E, 0x062F,
+    0x0630, 0x0631, 0x0632, 0x0633, 0x0634, 0x0635, 0x0636, 0x0637, 0x0638, 0x0639, 0x063A, 0x005B, 0x005C, 0x005D, 0x005E, 0x005F,
+    0x0640, 0x0641, 0x0642, 0x0643, 0x0644, 0x0645, 0x0646, 0x0647, 0x0648, 0x0649, 0x064A, 0x064B, 0x064C, 0x064D, 0x064E, 0x064F,
+    0x0650, 0x0651, 0x0652, 0x067E, 0x0679, 0x0686, 0x06D5, 0x06A4, 0x06AF, 0x0688, 0x0691, 0x007B, 0x007C, 0x007D, 0x0698, 0x06D2,
+];
+
+/// Apple maps three bytes to sequences of characters. The Yiddish yod yod
+/// patah and the qamats qatan are read as their single Unicode characters,
+/// and the lamed holam ligature, which has none, as Apple's private use hint
+/// U+F86A, so that it is written back unchanged.
+#[rustfmt::skip]
+const HEBREW: [u16; 128] = [
+    0x00C4, 0xFB1F, 0x00C7, 0x00C9, 0x00D1, 0x00D6, 0x00DC, 0x00E1, 0x00E0, 0x00E2, 0x00E4, 0x00E3, 0x00E5, 0x00E7, 0x00E9, 0x00E8,
+    0x00EA, 0x00EB, 0x00ED, 0x00EC, 0x00EE, 0x00EF, 0x00F1, 0x00F3, 0x00F2, 0x00F4, 0x00F6, 0x00F5, 0x00FA, 0x00F9, 0x00FB, 0x00FC,
+    0x0020, 0x0021, 0x0022, 0x0023, 0x0024, 0x0025, 0x20AA, 0x0027, 0x0029, 0x0028, 0x002A, 0x002B, 0x002C, 0x002D, 0x002E, 0x002F,
+    0x0030, 0x0031, 0x0032, 0x0033, 0x0034, 0x0035, 0x0036, 0x0037, 0x0038, 0x0039, 0x003A, 0x003B, 0x003C, 0x003D, 0x003E, 0x003F,
+    0xF86A, 0x201E, 0xF89B, 0xF89C, 0xF89D, 0xF89E, 0x05BC, 0xFB4B, 0xFB35, 0x2026, 0x00A0, 0x05B8, 0x05B7, 0x05B5, 0x05B6, 0x05B4,
+    0x2013, 0x2014, 0x201C, 0x201D, 0x2018, 0x2019, 0xFB2A, 0xFB2B, 0x05BF, 0x05B0, 0x05B2, 0x05B1, 0x05BB, 0x05B9, 0x05C7, 0x05B3,
+    0x05D0, 0x05D1, 0x05D2, 0x05D3, 0x05D4, 0x05D5, 0x05D6, 0x05D7, 0x05D8, 0x05D9, 0x05DA, 0x05DB, 0x05DC, 0x05DD, 0x05DE, 0x05DF,
+    0x05E0, 0x05E1, 0x05E2, 0x05E3, 0x05E4, 0x05E5, 0x05E6, 0x05E7, 0x05E8, 0x05E9, 0x05EA, 0x007D, 0x005D, 0x007B, 0x005B, 0x007C,
+];
+
+const CROATIAN: &[(u8, u16)] = &[
+    (0xA9, 0x0160),
+    (0xAE, 0x017D),
+    (0xB4, 0x2206),
+    (0xB9, 0x0161),
+    (0xBE, 0x017E),
+    (0xC6, 0x0106),
+    (0xC8, 0x010C),
+    (0xD0, 0x0110),
+    (0xD8, 0xF8FF),
+    (0xD9, 0x00A9),
+    (0xDE, 0x00C6),
+    (0xDF, 0x00BB),
+    (0xE0, 0x2013),
+    (0xE6, 0x0107),
+    (0xE8, 0x010D),
+    (0xF0, 0x0111),
+    (0xF9, 0x03C0),
+    (0xFA, 0x00CB),
+    (0xFD, 0x00CA),
+    (0xFE, 0x00E6),
+];
+
+/// Persian digits, in place of the Arabic ones
+const FARSI: &[(u8, u16)] = &[
+    (0xB0, 0x06F0),
+    (0xB1, 0x06F1),
+    (0xB2, 0x06F2),
+    (0xB3, 0x06F3),
+    (0xB4, 0x06F4),
+    (0xB5, 0x06F5),
+    (0xB6, 0x06F6),
+    (0xB7, 0x06F7),
+    (0xB8, 0x06F8),
+    (0xB9, 0x06F9),
+];
+
+const ICELANDIC: &[(u8, u16)] = &[
+    (0xA0, 0x00DD),
+    (0xDC, 0x00D0),
+    (0xDD, 0x00F0),
+    (0xDE, 0x00DE),
+    (0xDF, 0x00FE),
+    (0xE0, 0x00FD),
+];
+
+const ROMANIAN: &[(u8, u16)] = &[
+    (0xAE, 0x0102),
+    (0xAF, 0x0218),
+    (0xBE, 0x0103),
+    (0xBF, 0x0219),
+    (0xDE, 0x021A),
+    (0xDF, 0x021B),
+];
+
+const TURKISH: &[(u8, u16)] = &[
+    (0xDA, 0x011E),
+    (0xDB, 0x011F),
+    (0xDC, 0x0130),
+    (0xDD, 0x0131),
+    (0xDE, 0x015E),
+    (0xDF, 0x015F),
+    (0xF5, 0xF8A0),
+];
+
+fn forward(table: &[u16; 128], overrides: &[(u8, u16)], code: u8) -> u16 {
+    overrides
+        .iter()
+        .find(|&&(byte, _)| byte == code)
+        .map(|&(_, unicode)| unicode)
+        .unwrap_or(table[(code - 0x80) as usize])
+}
+
+fn backward(table: &[u16; 128], overrides: &[(u8, u16)], code: u32) -> u8 {
+    if code == NONE as u32 {
+        return 0;
+    }
+    if let Some(&(byte, _)) = overrides
+        .iter()
+        .find(|&&(_, unicode)| unicode as u32 == code)
+    {
+        return byte;
+    }
+    table
+        .iter()
+        .zip(0x80..=0xFF)
+        .find(|&(&unicode, byte)| {
+            unicode as u32 == code && overrides.iter().all(|&(b, _)| b != byte)
+        })
+        .map_or(0, |(_, byte)| byte)
+}
+
+macro_rules! mac_encoding {
+    ($(#[$attr:meta])* $var:ident, $module:ident, $name:expr, $table:expr, $overrides:expr) => {
+        mod $module {
+            pub fn forward(code: u8) -> u16 {
+                super::forward(&$table, $overrides, code)
+            }
+            pub fn backward(code: u32) -> u8 {
+                super::backward(&$table, $overrides, code)
+            }
+        }
+        $(#[$attr])*
+        pub const $var: &SingleByteEncoding = &SingleByteEncoding {
+            name: $name,
+            whatwg_name: None,
+            index_forward: $module::forward,
+            index_backward: $module::backward,
+        };
+    };
+}
+
+mac_encoding!(
+    /// Mac Central European, for Czech, Slovak, Polish, Hungarian and the Baltic languages
+    MAC_CENTRAL_EUROPEAN,
+    central_european,
+    "mac-centraleurope",
+    super::CENTRAL_EUROPEAN,
+    &[]
+);
+mac_encoding!(
+    /// Mac Greek
+    MAC_GREEK,
+    greek,
+    "mac-greek",
+    super::GREEK,
+    &[]
+);
+mac_encoding!(
+    /// Mac Arabic
+    MAC_ARABIC,
+    arabic,
+    "mac-arabic",
+    super::ARABIC,
+    &[]
+);
+mac_encoding!(
+    /// Mac Farsi, a variant of Mac Arabic
+    MAC_FARSI,
+    farsi,
+    "mac-farsi",
+    super::ARABIC,
+    super::FARSI
+);
+mac_encoding!(
+    /// Mac Hebrew
+    MAC_HEBREW,
+    hebrew,
+    "mac-hebrew",
+    super::HEBREW,
+    &[]
+);
+mac_encoding!(
+    /// Mac Croatian, a variant of Mac Roman
+    MAC_CROATIAN,
+    croatian,
+    "mac-croatian",
+    super::ROMAN,
+    super::CROATIAN
+);
+mac_encoding!(
+    /// Mac Icelandic, a variant of Mac Roman
+    MAC_ICELANDIC,
+    icelandic,
+    "mac-iceland",
+    super::ROMAN,
+    super::ICELANDIC
+);
+mac_encoding!(
+    /// Mac Romanian, a variant of Mac Roman
+    MAC_ROMANIAN,
+    romanian,
+    "mac-romanian",
+    super::ROMAN,
+    super::ROMANIAN
+);
+mac_encoding!(
+    /// Mac Turkish, a variant of Mac Roman
+    MAC_TURKISH,
+    turkish,
+    "mac-turkish",
+    super::ROMAN,
+    super::TURKISH
+);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use encoding::{DecoderTrap, EncoderTrap, Encoding};
+
+    #[test]
+    fn mac_encodings_roundtrip() {
+        let bytes: Vec<u8> = (0x80..=0xFF).collect();
+        for encoding in &[
+            MAC_CENTRAL_EUROPEAN,
+            MAC_GREEK,
+            MAC_CROATIAN,
+            MAC_ICELANDIC,
+            MAC_ROMANIAN,
+            MAC_TURKISH,
+        ] {
+            let string = encoding.decode(&bytes, DecoderTrap::Strict).unwrap();
+            assert_eq!(
+                encoding.encode(&string, EncoderTrap::Strict).unwrap(),
+                bytes,
+                "{}",
+                encoding.name()
+            );
+        }
+        assert_eq!(
+            MAC_TURKISH.decode(&[0xDD, 0xF5], DecoderTrap::Strict),
+            Ok("\u{131}\u{F8A0}".to_string())
+        );
+    }
+
+    #[test]
+    fn mac_right_to_left_encodings() {
+        for encoding in &[MAC_ARABIC, MAC_FARSI, MAC_HEBREW] {
+            // Bytes which duplicate ASCII characters are written as ASCII
+            for byte in 0x80..=0xFF {
+                let string = encoding.decode(&[byte], DecoderTrap::Strict).unwrap();
+                let expected = if string.is_ascii() {
+                    string.as_bytes().to_vec()
+                } else {
+                    vec![byte]
+                };
+                assert_eq!(
+                    encoding.encode(&string, EncoderTrap::Strict).unwrap(),
+                    expected,
+                    "{} {:#x}",
+                    encoding.name(),
+                    byte
+                );
+            }
+        }
+        let arabic = [0xC7, 0xE4, 0xD9, 0xD1, 0xC8, 0xEA, 0xC9];
+        assert_eq!(
+            MAC_ARABIC.decode(&arabic, DecoderTrap::Strict),
+            Ok("\u{627}\u{644}\u{639}\u{631}\u{628}\u{64A}\u{629}".to_string())
+        );
+        assert_eq!(
+            MAC_ARABIC.decode(&[0xA0, 0xA5, 0xB0, 0xB9, 0xAC, 0xBF], DecoderTrap::Strict),
+            Ok(" \u{66A}\u{660}\u{669}\u{60C}\u{61F}".to_string())
+        );
+        assert_eq!(
+            MAC_FARSI.decode(&[0xB0, 0xB9], DecoderTrap::Strict),
+            Ok("\u{6F0}\u{6F9}".to_string())
+        );
+        assert_eq!(
+            MAC_HEBREW.decode(
+                &[0xF2, 0xE1, 0xF8, 0xE9, 0xFA, 0x81, 0xDE],
+                DecoderTrap::Strict
+            ),
+            Ok("\u{5E2}\u{5D1}\u{5E8}\u{5D9}\u{5EA}\u{FB1F}\u{5C7}".to_string())
+        );
+    }
+}
@@ -1,5 +1,11 @@
 #![allow(non_camel_case_types, non_snake_case)]
 
+use crate::cmap::cmap;
+use crate::gpos::{gpos, Positioning};
+use crate::gsub::{gsub, Substitution};
+use crate::head::head;
+use crate::hmtx::hmtx;
+use bitflags::bitflags;
 use otspec::types::*;
 use otspec::{deserialize_visitor, read_field};
 use otspec_macros::tables;
@@ -7,6 +13,9 @@ use serde::de::{SeqAccess, Visitor};
 use serde::ser::SerializeSeq;
 use serde::Serializer;
 use serde::{Deserialize, Deserializer, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+
+mod ranges;
 
 tables!(
     Panose {
@@ -155,11 +164,238 @@ pub struct os2 {
     pub usUpperOpticalPointSize: Option<uint16>,
 }
 
+impl os2 {
+    /// Sets the Unicode range bits for the blocks with characters in a cmap
+    /// mapping
+    pub fn calc_unicode_ranges(&mut self, mapping: &BTreeMap<uint32, uint16>) {
+        let codepoints: BTreeSet<uint32> = mapping.keys().copied().collect();
+        let mut fields = [0; 4];
+        ranges::bits_to_fields(&ranges::unicode_range_bits(&codepoints), &mut fields);
+        self.ulUnicodeRange1 = fields[0];
+        self.ulUnicodeRange2 = fields[1];
+        self.ulUnicodeRange3 = fields[2];
+        self.ulUnicodeRange4 = fields[3];
+    }
+
+    /// Sets the code page bits for the code pages a cmap mapping supports
+    ///
+    /// The code pages are guessed from characteristic characters, as fontTools
+    /// does. The table is upgraded to version 1 if it is older, so that the
+    /// code page fields are written.
+    pub fn calc_code_page_ranges(&mut self, mapping: &BTreeMap<uint32, uint16>) {
+        let codepoints: BTreeSet<uint32> = mapping.keys().copied().collect();
+        let mut fields = [0; 2];
+        ranges::bits_to_fields(&ranges::code_page_range_bits(&codepoints), &mut fields);
+        self.ulCodePageRange1 = Some(fields[0]);
+        self.ulCodePageRange2 = Some(fields[1]);
+        self.version = self.version.max(1);
+    }
+
+    /// Recomputes the fields derived from other tables
+    ///
+    /// `xAvgCharWidth` is the weighted average of the lowercase Latin letters
+    /// and space before version 3 (falling back to the average of all
+    /// non-empty advance widths when one of them is missing), and that
+    /// average from version 3. `usFirstCharIndex` and `usLastCharIndex` are
+    /// taken from the cmap, clamped at 0xFFFF; `usMaxContext` from the layout
+    /// tables, for version 2 and later. The style bits of `fsSelection` are
+    /// made to agree with `head.macStyle`, and the bits introduced in version
+    /// 4 are cleared in older tables.
+    pub fn recalc(
+        &mut self,
+        hmtx: &hmtx,
+        cmap: &cmap,
+        head: &head,
+        gsub: Option<&gsub>,
+        gpos: Option<&gpos>,
+    ) {
+        let empty = BTreeMap::new();
+        let mapping = cmap.getBestMapping().unwrap_or(&empty);
+
+        self.xAvgCharWidth = self
+            .weighted_avg_char_width(hmtx, mapping)
+            .unwrap_or_else(|| {
+                let widths: Vec<u32> = hmtx
+                    .metrics
+                    .iter()
+                    .map(|m| m.advanceWidth as u32)
+                    .filter(|&w| w > 0)
+                    .collect();
+                if widths.is_empty() {
+                    0
+                } else {
+                    (widths.iter().sum::<u32>() as f32 / widths.len() as f32).round() as i16
+                }
+            });
+
+        self.usFirstCharIndex = mapping
+            .keys()
+            .next()
+            .map_or(0xFFFF, |&c| c.min(0xFFFF) as u16);
+        self.usLastCharIndex = mapping
+            .keys()
+            .next_back()
+            .map_or(0xFFFF, |&c| c.min(0xFFFF) as u16);
+
+        if self.version >= 2 {
+            let gsub_context = gsub
+                .iter()
+                .flat_map(|t| t.lookups.iter())
+                .flat_map(|l| l.subtables.iter())
+                .map(Substitution::max_context);
+            let gpos_context = gpos
+                .iter()
+                .flat_map(|t| t.lookups.iter())
+                .flat_map(|l| l.subtables.iter())
+                .map(Positioning::max_context);
+            let max_context = gsub_context.chain(gpos_context).max().unwrap_or(0);
+            self.usMaxContext = Some(max_context.min(0xFFFF) as uint16);
+        }
+
+        let mut selection = SelectionFlags::from_bits_truncate(self.fsSelection);
+        selection.set(SelectionFlags::BOLD, head.macStyle & 1 != 0);
+        selection.set(SelectionFlags::ITALIC, head.macStyle & 2 != 0);
+        selection.set(SelectionFlags::REGULAR, head.macStyle & 3 == 0);
+        if self.version < 4 {
+            selection.remove(
+                SelectionFlags::USE_TYPO_METRICS | SelectionFlags::WWS | SelectionFlags::OBLIQUE,
+            );
+        }
+        self.fsSelection = selection.bits();
+    }
+
+    /// The pre-version 3 weighted average width, if every weighted character
+    /// is mapped
+    fn weighted_avg_char_width(
+        &self,
+        hmtx: &hmtx,
+        mapping: &BTreeMap<uint32, uint16>,
+    ) -> Option<int16> {
+        if self.version >= 3 {
+            return None;
+        }
+        let mut total = 0;
+        for &(c, weight) in AVG_CHAR_WEIGHTS {
+            let gid = *mapping.get(&(c as uint32))?;
+            let width = hmtx.metrics.get(gid as usize)?.advanceWidth as u32;
+            total += width * weight;
+        }
+        Some((total as f32 / 1000.0).round() as int16)
+    }
+}
+
+bitflags! {
+    /// Flags in the fsSelection field
+    pub struct SelectionFlags: u16 {
+        /// Italic or oblique glyphs
+        const ITALIC = 0x0001;
+        /// Underscored glyphs
+        const UNDERSCORE = 0x0002;
+        /// Glyphs with foreground and background reversed
+        const NEGATIVE = 0x0004;
+        /// Outline (hollow) glyphs
+        const OUTLINED = 0x0008;
+        /// Overstruck glyphs
+        const STRIKEOUT = 0x0010;
+        /// Emboldened glyphs
+        const BOLD = 0x0020;
+        /// Regular weight glyphs, neither bold nor italic
+        const REGULAR = 0x0040;
+        /// The typographic metrics should be used for line spacing (version >= 4)
+        const USE_TYPO_METRICS = 0x0080;
+        /// The names follow the weight/width/slope model (version >= 4)
+        const WWS = 0x0100;
+        /// Oblique glyphs (version >= 4)
+        const OBLIQUE = 0x0200;
+    }
+}
+
+/// Weights of the lowercase letters and space in xAvgCharWidth before
+/// version 3, in thousandths
+const AVG_CHAR_WEIGHTS: &[(char, u32)] = &[
+    ('a', 64),
+    ('b', 14),
+    ('c', 27),
+    ('d', 35),
+    ('e', 100),
+    ('f', 20),
+    ('g', 14),
+    ('h', 42),
+    ('i', 63),
+    ('j', 3),
+    ('k', 6),
+    ('l', 35),
+    ('m', 20),
+    ('n', 56),
+    ('o', 56),
+    ('p', 17),
+    ('q', 4),
+    ('r', 49),
+    ('s', 56),
+    ('t', 71),
+    ('u', 31),
+    ('v', 10),
+    ('w', 18),
+    ('x', 3),
+    ('y', 18),
+    ('z', 2),
+    (' ', 166),
+];
+
+/// The OS/2 table in human-readable formats
+#[derive(Serialize, Deserialize)]
+#[serde(remote = "os2")]
+struct Os2Def {
+    version: uint16,
+    xAvgCharWidth: int16,
+    usWeightClass: uint16,
+    usWidthClass: uint16,
+    fsType: uint16,
+    ySubscriptXSize: int16,
+    ySubscriptYSize: int16,
+    ySubscriptXOffset: int16,
+    ySubscriptYOffset: int16,
+    ySuperscriptXSize: int16,
+    ySuperscriptYSize: int16,
+    ySuperscriptXOffset: int16,
+    ySuperscriptYOffset: int16,
+    yStrikeoutSize: int16,
+    yStrikeoutPosition: int16,
+    sFamilyClass: int16,
+    panose: Panose,
+    ulUnicodeRange1: uint32,
+    ulUnicodeRange2: uint32,
+    ulUnicodeRange3: uint32,
+    ulUnicodeRange4: uint32,
+    #[serde(with = "tag")]
+    achVendID: Tag,
+    fsSelection: uint16,
+    usFirstCharIndex: uint16,
+    usLastCharIndex: uint16,
+    sTypoAscender: int16,
+    sTypoDescender: int16,
+    sTypoLineGap: int16,
+    usWinAscent: uint16,
+    usWinDescent: uint16,
+    ulCodePageRange1: Option<uint32>,
+    ulCodePageRange2: Option<uint32>,
+    sxHeight: Option<int16>,
+    sCapHeight: Option<int16>,
+    usDefaultChar: Option<uint16>,
+    usBreakChar: Option<uint16>,
+    usMaxContext: Option<uint16>,
+    usLowerOpticalPointSize: Option<uint16>,
+    usUpperOpticalPointSize: Option<uint16>,
+}
+
 impl Serialize for os2 {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
     where
         S: Serializer,
     {
+        if serializer.is_human_readable() {
+            return Os2Def::serialize(self, serializer);
+        }
         let mut seq = serializer.serialize_seq(None)?;
         seq.serialize_element(&os2core {
             version: self.version,
@@ -207,7 +443,7 @@ impl Serialize for os2 {
         if self.version > 0 {
             seq.serialize_element(&os2v1 {
                 ulCodePageRange1: self.ulCodePageRange1.unwrap_or(0),
-                ulCodePageRange2: self.ulCodePageRange1.unwrap_or(0),
+                ulCodePageRange2: self.ulCodePageRange2.unwrap_or(0),
             })?;
         }
         if self.version > 1 {
@@ -233,6 +469,7 @@ impl Serialize for os2 {
 deserialize_visitor!(
     os2,
     Os2Visitor,
+    readable: Os2Def,
     fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
         let core = read_field!(seq, os2core, "an OS/2 table");
         let mut res = os2 {
@@ -297,3 +534,159 @@ deserialize_visitor!(
         Ok(res)
     }
 );
+
+#[cfg(test)]
+mod tests {
+    use crate::cmap::{cmap, CmapSubtable};
+    use crate::gpos::{self, Positioning};
+    use crate::gsub::{self, Substitution};
+    use crate::head::head;
+    use crate::hmtx::{hmtx, Metric};
+    use crate::layout::{ChainedSequenceContext, Coverage, Lookup, LookupFlags};
+    use crate::os2::{os2, SelectionFlags};
+    use std::collections::BTreeMap;
+
+    fn table_version(version: u8) -> os2 {
+        let mut bytes = vec![0; 100];
+        bytes[1] = version;
+        otspec::de::from_bytes(&bytes).unwrap()
+    }
+
+    fn font(mapping: BTreeMap<u32, u16>, widths: &[u16]) -> (hmtx, cmap, head) {
+        let hmtx = hmtx {
+            metrics: widths
+                .iter()
+                .map(|&advanceWidth| Metric {
+                    advanceWidth,
+                    lsb: 0,
+                })
+                .collect(),
+        };
+        let cmap = cmap {
+            subtables: vec![CmapSubtable {
+                format: 12,
+                platformID: 3,
+                encodingID: 10,
+                languageID: 0,
+                mapping,
+            }],
+            variationSequences: BTreeMap::new(),
+        };
+        (hmtx, cmap, head::new(1.0, 1000, 0, 0, 0, 0))
+    }
+
+    fn lookup<T>(lookupType: u16, subtable: T) -> Lookup<T> {
+        Lookup {
+            lookupType,
+            lookupFlag: LookupFlags::empty(),
+            subtables: vec![subtable],
+            markFilteringSet: None,
+        }
+    }
+
+    #[test]
+    fn os2_recalc_avg_char_width() {
+        // Every lowercase letter and space is 500 units wide, the other glyph
+        // 1500
+        let mut mapping: BTreeMap<u32, u16> = ('a'..='z').map(|c| (c as u32, 1)).collect();
+        mapping.insert(0x20, 1);
+        mapping.insert(0x41, 2);
+        let (hmtx, cmap, head) = font(mapping, &[0, 500, 1500]);
+
+        let mut table = table_version(2);
+        table.recalc(&hmtx, &cmap, &head, None, None);
+        assert_eq!(table.xAvgCharWidth, 500);
+
+        let mut table = table_version(3);
+        table.recalc(&hmtx, &cmap, &head, None, None);
+        assert_eq!(table.xAvgCharWidth, 1000);
+
+        // Without space, the old algorithm falls back to the plain average
+        let mut mapping: BTreeMap<u32, u16> = ('a'..='z').map(|c| (c as u32, 1)).collect();
+        mapping.insert(0x41, 2);
+        let (hmtx, cmap, head) = font(mapping, &[0, 500, 1500]);
+        let mut table = table_version(1);
+        table.recalc(&hmtx, &cmap, &head, None, None);
+        assert_eq!(table.xAvgCharWidth, 1000);
+    }
+
+    #[test]
+    fn os2_recalc_char_indices() {
+        let mapping: BTreeMap<u32, u16> = vec![(0x20, 1), (0x1F600, 2)].into_iter().collect();
+        let (hmtx, cmap, head) = font(mapping, &[500, 500, 500]);
+        let mut table = table_version(4);
+        table.recalc(&hmtx, &cmap, &head, None, None);
+        assert_eq!(table.usFirstCharIndex, 0x20);
+        assert_eq!(table.usLastCharIndex, 0xFFFF);
+
+        let (hmtx, cmap, head) = font(BTreeMap::new(), &[500]);
+        table.recalc(&hmtx, &cmap, &head, None, None);
+        assert_eq!(table.usFirstCharIndex, 0xFFFF);
+        assert_eq!(table.usLastCharIndex, 0xFFFF);
+        assert_eq!(table.xAvgCharWidth, 500);
+    }
+
+    #[test]
+    fn os2_recalc_max_context() {
+        let (hmtx, cmap, head) = font(BTreeMap::new(), &[500]);
+        let mut gsub = gsub::gsub::default();
+        gsub.lookups.push(lookup(
+            4,
+            Substitution::Ligature(gsub::LigatureSubst {
+                ligatures: vec![(vec![1, 2, 3], 4)],
+            }),
+        ));
+        let mut gpos = gpos::gpos::default();
+        gpos.lookups.push(lookup(
+            8,
+            Positioning::ChainedContext(ChainedSequenceContext {
+                backtrackCoverages: vec![Coverage::default(); 3],
+                inputCoverages: vec![Coverage::default(); 2],
+                lookaheadCoverages: vec![Coverage::default(); 2],
+                seqLookupRecords: vec![],
+            }),
+        ));
+
+        let mut table = table_version(2);
+        table.recalc(&hmtx, &cmap, &head, Some(&gsub), None);
+        assert_eq!(table.usMaxContext, Some(3));
+        table.recalc(&hmtx, &cmap, &head, Some(&gsub), Some(&gpos));
+        assert_eq!(table.usMaxContext, Some(4));
+        table.recalc(&hmtx, &cmap, &head, None, None);
+        assert_eq!(table.usMaxContext, Some(0));
+
+        let mut table = table_version(1);
+        table.recalc(&hmtx, &cmap, &head, Some(&gsub), Some(&gpos));
+        assert_eq!(table.usMaxContext, None);
+    }
+
+    #[test]
+    fn os2_recalc_selection() {
+        let (hmtx, cmap, mut head) = font(BTreeMap::new(), &[500]);
+        let mut table = table_version(4);
+        table.fsSelection = (SelectionFlags::REGULAR
+            | SelectionFlags::UNDERSCORE
+            | SelectionFlags::USE_TYPO_METRICS
+            | SelectionFlags::WWS)
+            .bits();
+        head.macStyle = 0b11;
+        table.recalc(&hmtx, &cmap, &head, None, None);
+        assert_eq!(
+            table.fsSelection,
+            (SelectionFlags::BOLD
+                | SelectionFlags::ITALIC
+                | SelectionFlags::UNDERSCORE
+                | SelectionFlags::USE_TYPO_METRICS
+                | SelectionFlags::WWS)
+                .bits()
+        );
+
+        head.macStyle = 0;
+        table.version = 3;
+        table.recalc(&hmtx, &cmap, &head, None, None);
+        assert_eq!(
+            table.fsSelection,
+            (SelectionFlags::REGULAR | SelectionFlags::UNDERSCORE).bits()
+        );
+    }
+}
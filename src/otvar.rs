@@ -49,6 +49,7 @@ pub use crate::otvar::tuplevariationstore::{
 #[cfg(test)]
 mod tests {
     use crate::otvar;
+    use otspec::ser::graph::{ObjectGraph, ToObject};
 
     #[test]
     fn otvar_de_ivd() {
@@ -88,4 +89,26 @@ mod tests {
         };
         assert_eq!(deserialized, fivs);
     }
+
+    #[test]
+    fn otvar_ser_ivs() {
+        let ivs = otvar::ItemVariationStore {
+            format: 1,
+            axisCount: 1,
+            variationRegions: vec![vec![otvar::RegionAxisCoordinates {
+                startCoord: 0.0,
+                peakCoord: 1.0,
+                endCoord: 1.0,
+            }]],
+            variationData: vec![otvar::ItemVariationData {
+                regionIndexes: vec![0],
+                deltaValues: vec![vec![-200], vec![-50], vec![100], vec![200]],
+            }],
+        };
+        let mut graph = ObjectGraph::new();
+        let root = ivs.to_object(&mut graph).unwrap();
+        let binary = graph.pack(root).unwrap();
+        let deserialized: otvar::ItemVariationStore = otspec::de::from_bytes(&binary).unwrap();
+        assert_eq!(deserialized, ivs);
+    }
 }
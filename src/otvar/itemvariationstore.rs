@@ -1,10 +1,12 @@
+use otspec::ser::graph::{Object, ObjectGraph, ObjectId, ToObject};
 use otspec::types::*;
-use otspec::{deserialize_visitor, read_field, read_field_counted, read_remainder};
+use otspec::{deserialize_visitor, read_field, read_field_counted};
 use otspec_macros::tables;
 use serde::de::SeqAccess;
 use serde::de::Visitor;
 use serde::Deserializer;
 use serde::{Deserialize, Serialize};
+use std::convert::TryFrom;
 
 tables!(
     RegionAxisCoordinates {
@@ -17,10 +19,14 @@ tables!(
         uint16	shortDeltaCount
         Counted(uint16) regionIndexes
     }
-
+    ItemVariationStoreHeader {
+        uint16 format
+        Offset32(VariationRegionList) variationRegionList
+        Counted(Offset32(ItemVariationData)) variationData
+    }
 );
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Clone)]
 pub struct ItemVariationData {
     pub regionIndexes: Vec<uint16>,
     pub deltaValues: Vec<Vec<int16>>,
@@ -36,7 +42,7 @@ deserialize_visitor!(
         for _ in 0..header.itemCount {
             let mut v: Vec<i16> = Vec::new();
             for col in 0..regionIndexCount {
-                if col <= header.shortDeltaCount as usize {
+                if col < header.shortDeltaCount as usize {
                     v.push(read_field!(seq, i16, "a delta"));
                 } else {
                     v.push(read_field!(seq, i8, "a delta").into());
@@ -51,6 +57,35 @@ deserialize_visitor!(
     }
 );
 
+impl ToObject for ItemVariationData {
+    fn to_object(&self, graph: &mut ObjectGraph) -> otspec::error::Result<ObjectId> {
+        // Columns up to the last one with a value too large for a byte are stored as words
+        let shortDeltaCount = self
+            .deltaValues
+            .iter()
+            .filter_map(|row| row.iter().rposition(|&d| i8::try_from(d).is_err()))
+            .max()
+            .map_or(0, |col| col + 1);
+        let mut object = Object::new();
+        object.push(&ItemVariationDataHeader {
+            itemCount: self.deltaValues.len() as uint16,
+            shortDeltaCount: shortDeltaCount as uint16,
+            regionIndexes: self.regionIndexes.clone(),
+        })?;
+        for row in &self.deltaValues {
+            for (col, &delta) in row.iter().enumerate() {
+                if col < shortDeltaCount {
+                    object.push(&delta)?;
+                } else {
+                    object.push(&(delta as i8))?;
+                }
+            }
+        }
+        Ok(graph.add(object))
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
 pub struct VariationRegionList {
     pub axisCount: uint16,
     pub regionCount: uint16,
@@ -76,6 +111,18 @@ deserialize_visitor!(
     }
 );
 
+impl ToObject for VariationRegionList {
+    fn to_object(&self, graph: &mut ObjectGraph) -> otspec::error::Result<ObjectId> {
+        let mut object = Object::new();
+        object.push(&self.axisCount)?;
+        object.push(&self.regionCount)?;
+        for region in &self.variationRegions {
+            object.push(region)?;
+        }
+        Ok(graph.add(object))
+    }
+}
+
 #[derive(Debug, PartialEq)]
 pub struct ItemVariationStore {
     pub format: uint16,
@@ -88,31 +135,30 @@ deserialize_visitor!(
     ItemVariationStore,
     ItemVariationStoreVisitor,
     fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
-        let format = read_field!(seq, uint16, "a header");
-        let offset = read_field!(seq, uint32, "an offset");
-        let vardatacount = read_field!(seq, uint16, "a count") as usize;
-        let variationDataOffsets: Vec<uint32> =
-            read_field_counted!(seq, vardatacount, "item variation data offsets");
-        let remainder = read_remainder!(seq, "an item variation store");
-        let binary_variation_region_list =
-            &remainder[offset as usize - (8 + 4 * vardatacount as usize)..];
-        let variationRegions: VariationRegionList =
-            otspec::de::from_bytes(binary_variation_region_list).map_err(|e| {
-                serde::de::Error::custom(format!("Expecting a variation region list: {:?}", e))
-            })?;
-        let mut variationData = Vec::with_capacity(vardatacount);
-        for i in 0..vardatacount {
-            let vardata_binary =
-                &remainder[variationDataOffsets[i] as usize - (8 + 4 * vardatacount as usize)..];
-            variationData.push(otspec::de::from_bytes(vardata_binary).map_err(|e| {
-                serde::de::Error::custom(format!("Expecting variation data: {:?}", e))
-            })?);
-        }
+        let header = read_field!(seq, ItemVariationStoreHeader, "an item variation store");
+        let variationRegions = header
+            .variationRegionList
+            .ok_or_else(|| serde::de::Error::custom("Expecting a variation region list"))?;
         Ok(ItemVariationStore {
-            format,
+            format: header.format,
             axisCount: variationRegions.axisCount,
             variationRegions: variationRegions.variationRegions,
-            variationData,
+            variationData: header.variationData,
         })
     }
 );
+
+impl ToObject for ItemVariationStore {
+    fn to_object(&self, graph: &mut ObjectGraph) -> otspec::error::Result<ObjectId> {
+        let header = ItemVariationStoreHeader {
+            format: self.format,
+            variationRegionList: Some(VariationRegionList {
+                axisCount: self.axisCount,
+                regionCount: self.variationRegions.len() as uint16,
+                variationRegions: self.variationRegions.clone(),
+            }),
+            variationData: self.variationData.clone(),
+        };
+        header.to_object(graph)
+    }
+}
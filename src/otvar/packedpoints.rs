@@ -25,7 +25,7 @@ deserialize_visitor!(
         let mut count: u16 = read_field!(seq, u8, "a packed point count (first byte)") as u16;
         if count > 127 {
             let count2: u16 = read_field!(seq, u8, "a packed point count (second byte)") as u16;
-            count = (count & 0xff) << 8 | count2;
+            count = (count & 0x7f) << 8 | count2;
         }
         if count == 0 {
             // All of them
@@ -49,8 +49,8 @@ deserialize_visitor!(
         }
         let cumsum: Vec<u16> = res
             .iter()
-            .scan(0, |acc, &x| {
-                *acc += x;
+            .scan(0_u16, |acc, &x| {
+                *acc = acc.wrapping_add(x);
                 Some(*acc)
             })
             .collect();
@@ -46,7 +46,7 @@ fn iup_segment(
             std::mem::swap(&mut d2, &mut d1);
         }
 
-        let scale = (d2 - d1) as f32 / (x2 - x1) as f32;
+        let scale = (d2 as i32 - d1 as i32) as f32 / (x2 as i32 - x1 as i32) as f32;
 
         for pair in coords {
             let x = if j == 0 { pair.0 } else { pair.1 };
@@ -55,7 +55,7 @@ fn iup_segment(
             } else if x >= x2 {
                 d2
             } else {
-                d1 + ((x - x1) as f32 * scale) as i16
+                d1.wrapping_add(((x as i32 - x1 as i32) as f32 * scale) as i16)
             };
             out_arrays[j].push(d);
         }
@@ -154,8 +154,11 @@ impl TupleVariation {
         let mut newdeltas = vec![];
         let mut start = 0;
         for end in ends {
-            let contour_delta = &deltas[start..end + 1];
-            let contour_orig = &coords[start..end + 1];
+            let (contour_delta, contour_orig) =
+                match (deltas.get(start..=*end), coords.get(start..=*end)) {
+                    (Some(contour_delta), Some(contour_orig)) => (contour_delta, contour_orig),
+                    _ => break,
+                };
             start = end + 1;
             iup_contour(&mut newdeltas, contour_delta, contour_orig);
         }
@@ -197,7 +200,7 @@ stateful_deserializer!(
                 seq.next_element_seed(TupleVariationHeaderDeserializer {
                     axis_count: self.axis_count,
                 })?
-                .unwrap(),
+                .ok_or_else(|| serde::de::Error::custom("Expecting a tuple variation header"))?,
             );
         }
 
@@ -219,26 +222,30 @@ stateful_deserializer!(
                 .contains(TupleIndexFlags::PRIVATE_POINT_NUMBERS)
             {
                 let private_points = read_field!(seq, PackedPoints, "packed points");
-                if private_points.points.is_some() {
-                    points_for_this_header = private_points.points.unwrap().clone().into();
-                } else {
-                    points_for_this_header =  (0..self.point_count).collect();
-                }
+                points_for_this_header = match private_points.points {
+                    Some(pts) => pts.into(),
+                    None => (0..self.point_count).collect(),
+                };
             } else {
                 points_for_this_header = shared_points.clone().into();
             }
+            let mut read_deltas = || -> std::result::Result<Vec<int16>, A::Error> {
+                seq.next_element_seed(PackedDeltasDeserializer { num_points: points_for_this_header.len() })?
+                    .map(|packed| packed.0)
+                    .ok_or_else(|| serde::de::Error::custom("Expecting packed deltas"))
+            };
             let mut deltas:VecDeque<Delta> = if self.is_gvar {
-                let packed_x = seq.next_element_seed(PackedDeltasDeserializer { num_points: points_for_this_header.len() })?.unwrap().0;
-                let packed_y = seq.next_element_seed(PackedDeltasDeserializer { num_points: points_for_this_header.len() })?.unwrap().0;
+                let packed_x = read_deltas()?;
+                let packed_y = read_deltas()?;
                 packed_x.iter().zip(packed_y.iter()).map(|(x,y)| Delta::Delta2D((*x,*y)) ).collect()
             } else {
-                let packed = seq.next_element_seed(PackedDeltasDeserializer { num_points: points_for_this_header.len() })?.unwrap().0;
+                let packed = read_deltas()?;
                 packed.iter().map(|x| Delta::Delta1D(*x) ).collect()
             };
             let mut all_deltas:Vec<Option<Delta>> = vec![];
             for i in 0..self.point_count {
-                if !points_for_this_header.is_empty() && i == points_for_this_header[0] {
-                    all_deltas.push(Some(deltas.pop_front().unwrap()));
+                if points_for_this_header.front() == Some(&i) && !deltas.is_empty() {
+                    all_deltas.push(deltas.pop_front());
                     points_for_this_header.pop_front();
                 } else {
                     all_deltas.push(None);  // IUP needed later
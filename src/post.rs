@@ -10,7 +10,7 @@ use serde::{Deserializer, Serializer};
 /// The list of 258 standard Macintosh glyph names.
 /// Names not in this list will be stored separately in the post table if
 /// version==2
-const APPLE_NAMES: &[&str] = &[
+pub(crate) const APPLE_NAMES: &[&str] = &[
     ".notdef",
     ".null",
     "nonmarkingreturn",
@@ -308,6 +308,23 @@ pub struct post {
     pub glyphnames: Option<Vec<String>>,
 }
 
+/// The post table in human-readable formats
+#[derive(Serialize, Deserialize)]
+#[serde(remote = "post")]
+struct PostDef {
+    #[serde(with = "Version16Dot16")]
+    version: U16F16,
+    italicAngle: f32,
+    underlinePosition: FWORD,
+    underlineThickness: FWORD,
+    isFixedPitch: uint32,
+    minMemType42: uint32,
+    maxMemType42: uint32,
+    minMemType1: uint32,
+    maxMemType1: uint32,
+    glyphnames: Option<Vec<String>>,
+}
+
 impl post {
     /// Creates a new table with a given version.
     /// The glyph names are optional, and only written out if version==2
@@ -346,6 +363,9 @@ impl Serialize for post {
     where
         S: Serializer,
     {
+        if serializer.is_human_readable() {
+            return PostDef::serialize(self, serializer);
+        }
         let core = postcore {
             version: self.version,
             italicAngle: self.italicAngle,
@@ -383,9 +403,12 @@ impl Serialize for post {
         seq.end()
     }
 }
+// The post table has no offsets; which fields follow the header depends on
+// its version, so it is read by hand
 deserialize_visitor!(
     post,
     PostVisitor,
+    readable: PostDef,
     fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
         let core = read_field!(seq, postcore, "a post table");
         let mut glyphnames = None;
@@ -405,14 +428,27 @@ deserialize_visitor!(
                 }
                 let byte_count = byte_count.unwrap() as usize;
                 let name: Vec<u8> = read_field_counted!(seq, byte_count, "glyph name");
-                glyph_name_table.push(String::from_utf8(name).unwrap());
+                glyph_name_table.push(
+                    String::from_utf8(name)
+                        .map_err(|_| serde::de::Error::custom("Glyph name was not valid UTF-8"))?,
+                );
             }
-            for i in 0..numGlyphs {
-                let offset = glyph_offsets[i as usize] as usize;
+            for offset in glyph_offsets {
+                let offset = offset as usize;
                 if offset < 258 {
                     glyphnames_vec.push(String::from(APPLE_NAMES[offset]));
                 } else {
-                    glyphnames_vec.push(glyph_name_table[offset - 258].clone());
+                    glyphnames_vec.push(
+                        glyph_name_table
+                            .get(offset - 258)
+                            .ok_or_else(|| {
+                                serde::de::Error::custom(format!(
+                                    "Glyph name index {} out of range",
+                                    offset
+                                ))
+                            })?
+                            .clone(),
+                    );
                 }
             }
             glyphnames = Some(glyphnames_vec);
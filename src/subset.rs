@@ -0,0 +1,680 @@
+use crate::font::{Font, FontError, Table};
+use crate::gdef::gdef;
+use crate::glyf::glyf;
+use crate::gpos::gpos;
+use crate::gsub::{gsub, Substitution};
+use crate::gvar;
+use crate::hmtx::hmtx;
+use crate::layout::{ClassDef, Coverage};
+use otspec::error::Error as OTSpecError;
+use otspec::types::*;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet};
+
+/// Renumbering the glyphs of the `GSUB`, `GPOS` and `GDEF` tables
+mod layout;
+
+/// Tables which do not refer to glyphs, and so are kept unchanged
+const UNCHANGED_TABLES: [&Tag; 14] = [
+    b"OS/2", b"STAT", b"MVAR", b"avar", b"cvar", b"cvt ", b"fpgm", b"fvar", b"gasp", b"head",
+    b"hhea", b"meta", b"name", b"prep",
+];
+
+/// Tables which are subset
+const SUBSET_TABLES: [&Tag; 12] = [
+    b"GDEF", b"GPOS", b"GSUB", b"cmap", b"glyf", b"gvar", b"hmtx", b"loca", b"maxp", b"post",
+    b"vhea", b"vmtx",
+];
+
+/// The glyphs kept when subsetting, and their new glyph IDs
+///
+/// Glyphs keep their relative order, so lists sorted by glyph ID stay
+/// sorted once renumbered.
+struct GlyphMap {
+    /// The old glyph IDs of the glyphs kept, in order
+    kept: Vec<uint16>,
+    /// The new glyph ID of each glyph, indexed by old glyph ID
+    new_ids: Vec<Option<uint16>>,
+}
+
+impl GlyphMap {
+    fn new(kept: Vec<uint16>, num_glyphs: usize) -> Self {
+        let mut new_ids = vec![None; num_glyphs];
+        for (new_id, &glyph) in kept.iter().enumerate() {
+            new_ids[glyph as usize] = Some(new_id as uint16);
+        }
+        GlyphMap { kept, new_ids }
+    }
+
+    /// Returns the new ID of a glyph, or None if it has been removed
+    fn get(&self, glyph: uint16) -> Option<uint16> {
+        self.new_ids.get(glyph as usize).copied().flatten()
+    }
+
+    /// Returns the new IDs of a sequence of glyphs, or None if any have been removed
+    fn glyphs(&self, glyphs: &[uint16]) -> Option<Vec<uint16>> {
+        glyphs.iter().map(|&glyph| self.get(glyph)).collect()
+    }
+
+    /// Keeps the entries of a per-glyph list which belong to glyphs that are kept
+    fn filter<T>(&self, items: Vec<T>) -> Vec<T> {
+        items
+            .into_iter()
+            .enumerate()
+            .filter(|(glyph, _)| self.get(*glyph as uint16).is_some())
+            .map(|(_, item)| item)
+            .collect()
+    }
+
+    /// Renumbers the keys of a map, dropping the entries of removed glyphs
+    fn keys<V: Clone>(&self, map: &BTreeMap<uint16, V>) -> BTreeMap<uint16, V> {
+        map.iter()
+            .filter_map(|(&glyph, value)| Some((self.get(glyph)?, value.clone())))
+            .collect()
+    }
+
+    fn coverage(&self, coverage: &Coverage) -> Coverage {
+        Coverage {
+            glyphs: coverage
+                .glyphs
+                .iter()
+                .filter_map(|&glyph| self.get(glyph))
+                .collect(),
+        }
+    }
+
+    fn class_def(&self, class_def: &ClassDef) -> ClassDef {
+        ClassDef {
+            classes: self.keys(&class_def.classes),
+        }
+    }
+}
+
+fn layout_table<T: DeserializeOwned>(font: &mut Font, tag: &Tag) -> Result<Option<T>, FontError> {
+    match font.get_table(tag)? {
+        Some(Table::Unknown(binary)) => Ok(Some(
+            otspec::de::from_bytes(binary).map_err(|e| e.in_table(*tag))?,
+        )),
+        _ => Ok(None),
+    }
+}
+
+fn glyf_table(font: &mut Font) -> Result<&mut glyf, FontError> {
+    // glyf can only be read once head and loca have been
+    font.get_table(b"head")?;
+    font.get_table(b"loca")?;
+    match font.get_table(b"glyf")? {
+        Some(Table::Glyf(glyf)) => Ok(glyf),
+        _ => Err(FontError::MissingTable(*b"glyf")),
+    }
+}
+
+/// Adds the glyphs which the given glyphs can be substituted by
+fn gsub_closure(gsub: &gsub, glyphs: &mut BTreeSet<uint16>) {
+    loop {
+        let mut added = vec![];
+        for subtable in gsub.lookups.iter().flat_map(|lookup| &lookup.subtables) {
+            match subtable {
+                Substitution::Single(s) => added.extend(
+                    s.mapping
+                        .iter()
+                        .filter(|(input, _)| glyphs.contains(input))
+                        .map(|(_, &output)| output),
+                ),
+                Substitution::Multiple(s) => added.extend(
+                    s.mapping
+                        .iter()
+                        .filter(|(input, _)| glyphs.contains(input))
+                        .flat_map(|(_, outputs)| outputs.iter().copied()),
+                ),
+                Substitution::Alternate(s) => added.extend(
+                    s.mapping
+                        .iter()
+                        .filter(|(input, _)| glyphs.contains(input))
+                        .flat_map(|(_, outputs)| outputs.iter().copied()),
+                ),
+                Substitution::Ligature(s) => added.extend(
+                    s.ligatures
+                        .iter()
+                        .filter(|(inputs, _)| inputs.iter().all(|g| glyphs.contains(g)))
+                        .map(|(_, ligature)| *ligature),
+                ),
+                // The lookups called by contextual subtables are visited
+                // in their own right
+                Substitution::ContextRules(_)
+                | Substitution::Context(_)
+                | Substitution::ChainedContextRules(_)
+                | Substitution::ChainedContext(_)
+                | Substitution::Unsupported { .. } => {}
+            }
+        }
+        let count = glyphs.len();
+        glyphs.extend(added);
+        if glyphs.len() == count {
+            return;
+        }
+    }
+}
+
+/// Adds the components of composite glyphs, and of their components in turn
+fn component_closure(glyf: &glyf, glyphs: &mut BTreeSet<uint16>) {
+    let mut todo: Vec<uint16> = glyphs.iter().copied().collect();
+    while let Some(glyph) = todo.pop() {
+        if let Some(glyph) = glyf.glyphs.get(glyph as usize) {
+            for component in &glyph.components {
+                if glyphs.insert(component.glyphIndex) {
+                    todo.push(component.glyphIndex);
+                }
+            }
+        }
+    }
+}
+
+fn glyph_closure(
+    font: &mut Font,
+    gsub: Option<&gsub>,
+    unicodes: &BTreeSet<uint32>,
+    glyphs: &BTreeSet<uint16>,
+) -> Result<BTreeSet<uint16>, FontError> {
+    let mut closure: BTreeSet<uint16> = glyphs.clone();
+    // .notdef is always kept
+    closure.insert(0);
+    if let Some(Table::Cmap(cmap)) = font.get_table(b"cmap")? {
+        for subtable in cmap.subtables.iter().filter(|st| st.is_unicode()) {
+            closure.extend(
+                subtable
+                    .mapping
+                    .iter()
+                    .filter(|(codepoint, _)| unicodes.contains(codepoint))
+                    .map(|(_, &glyph)| glyph),
+            );
+        }
+        closure.extend(
+            cmap.variationSequences
+                .iter()
+                .filter(|((base, _), _)| unicodes.contains(base))
+                .filter_map(|(_, &glyph)| glyph),
+        );
+    }
+
+    let glyf = glyf_table(font)?;
+    let num_glyphs = glyf.glyphs.len();
+    if let Some(glyph) = glyphs.iter().find(|&&g| g as usize >= num_glyphs) {
+        return Err(OTSpecError::Message(format!(
+            "Glyph ID {} is out of range, as the font has {} glyphs",
+            glyph, num_glyphs
+        ))
+        .into());
+    }
+    loop {
+        let count = closure.len();
+        if let Some(gsub) = gsub {
+            gsub_closure(gsub, &mut closure);
+        }
+        component_closure(glyf, &mut closure);
+        if closure.len() == count {
+            break;
+        }
+    }
+    closure.retain(|&glyph| (glyph as usize) < num_glyphs);
+    Ok(closure)
+}
+
+/// Returns the glyphs needed to render the given codepoints and glyphs
+///
+/// As well as the glyphs the codepoints are mapped to in `cmap`, this
+/// includes the components of composite glyphs and any glyphs which `GSUB`
+/// can substitute them by. The `.notdef` glyph is always included. Fonts
+/// without a `glyf` table cannot be subset.
+pub fn closure(
+    font: &mut Font,
+    unicodes: &BTreeSet<uint32>,
+    glyphs: &BTreeSet<uint16>,
+) -> Result<BTreeSet<uint16>, FontError> {
+    let gsub: Option<gsub> = layout_table(font, b"GSUB")?;
+    glyph_closure(font, gsub.as_ref(), unicodes, glyphs)
+}
+
+fn subset_glyf(font: &mut Font, map: &GlyphMap) -> Result<(), FontError> {
+    let glyf = glyf_table(font)?;
+    glyf.glyphs = map
+        .filter(std::mem::take(&mut glyf.glyphs))
+        .into_iter()
+        .map(|mut glyph| {
+            glyph.components = std::mem::take(&mut glyph.components)
+                .into_iter()
+                .filter_map(|mut component| {
+                    component.glyphIndex = map.get(component.glyphIndex)?;
+                    Some(component)
+                })
+                .collect();
+            glyph
+        })
+        .collect();
+    Ok(())
+}
+
+/// Subsets a table of metrics, returning its data and count of long metrics
+fn subset_metrics(metrics: hmtx, map: &GlyphMap) -> (Vec<u8>, uint16) {
+    let metrics = hmtx {
+        metrics: map.filter(metrics.metrics),
+    };
+    if metrics.metrics.is_empty() {
+        (vec![], 0)
+    } else {
+        metrics.to_bytes()
+    }
+}
+
+fn subset_hmtx(font: &mut Font, map: &GlyphMap) -> Result<(), FontError> {
+    // hmtx can only be read once hhea has been
+    font.get_table(b"hhea")?;
+    let metrics = match font.get_table(b"hmtx")? {
+        Some(Table::Hmtx(hmtx)) => std::mem::take(&mut hmtx.metrics),
+        _ => return Ok(()),
+    };
+    let (data, number_of_h_metrics) = subset_metrics(hmtx { metrics }, map);
+    font.tables.insert(*b"hmtx", Table::Unknown(data));
+    if let Some(Table::Hhea(hhea)) = font.tables.get_mut(b"hhea") {
+        hhea.numberOfHMetrics = number_of_h_metrics;
+    }
+    Ok(())
+}
+
+/// Subsets the vertical metrics, which are laid out like the horizontal ones
+fn subset_vmtx(font: &mut Font, map: &GlyphMap) -> Result<(), FontError> {
+    let vmtx = match font.tables.get(b"vmtx") {
+        Some(Table::Unknown(vmtx)) => vmtx,
+        _ => return Ok(()),
+    };
+    let number_of_v_metrics = match font.tables.get(b"vhea") {
+        Some(Table::Unknown(vhea)) if vhea.len() >= 36 => {
+            uint16::from_be_bytes([vhea[34], vhea[35]])
+        }
+        Some(_) => return Err(OTSpecError::Message("Bad vhea table".into()).into()),
+        None => return Err(FontError::MissingTable(*b"vhea")),
+    };
+    let metrics =
+        crate::hmtx::from_bytes(vmtx, number_of_v_metrics).map_err(|e| e.in_table(*b"vmtx"))?;
+    let (data, number_of_v_metrics) = subset_metrics(metrics, map);
+    font.tables.insert(*b"vmtx", Table::Unknown(data));
+    if let Some(Table::Unknown(vhea)) = font.tables.get_mut(b"vhea") {
+        vhea[34..36].copy_from_slice(&number_of_v_metrics.to_be_bytes());
+    }
+    Ok(())
+}
+
+/// Subsets the character map
+///
+/// Unicode subtables keep the mappings of the requested codepoints, and of
+/// the requested glyphs. Other subtables keep the mappings of any glyphs
+/// which are kept. Variation sequences are kept in the same way as the
+/// Unicode mappings, with default sequences kept if their base codepoint is.
+fn subset_cmap(
+    font: &mut Font,
+    map: &GlyphMap,
+    unicodes: &BTreeSet<uint32>,
+    glyphs: &BTreeSet<uint16>,
+) -> Result<(), FontError> {
+    if let Some(Table::Cmap(cmap)) = font.get_table(b"cmap")? {
+        for subtable in &mut cmap.subtables {
+            let unicode = subtable.is_unicode();
+            subtable.mapping = subtable
+                .mapping
+                .iter()
+                .filter(|(codepoint, glyph)| {
+                    !unicode || unicodes.contains(codepoint) || glyphs.contains(glyph)
+                })
+                .filter_map(|(&codepoint, &glyph)| Some((codepoint, map.get(glyph)?)))
+                .collect();
+        }
+        let mapped: BTreeSet<uint32> = cmap
+            .subtables
+            .iter()
+            .filter(|st| st.is_unicode())
+            .flat_map(|st| st.mapping.keys().copied())
+            .collect();
+        cmap.variationSequences = cmap
+            .variationSequences
+            .iter()
+            .filter_map(|(&(base, selector), &glyph)| match glyph {
+                None if mapped.contains(&base) => Some(((base, selector), None)),
+                Some(glyph) if unicodes.contains(&base) || glyphs.contains(&glyph) => {
+                    Some(((base, selector), Some(map.get(glyph)?)))
+                }
+                _ => None,
+            })
+            .collect();
+    }
+    Ok(())
+}
+
+fn subset_post(font: &mut Font, map: &GlyphMap) -> Result<(), FontError> {
+    if let Some(Table::Post(post)) = font.get_table(b"post")? {
+        if let Some(names) = post.glyphnames.take() {
+            post.glyphnames = Some(map.filter(names));
+        }
+    }
+    Ok(())
+}
+
+fn subset_gvar(font: &mut Font, map: &GlyphMap) -> Result<(), FontError> {
+    match font.tables.get_mut(b"gvar") {
+        Some(Table::Unknown(binary)) => {
+            *binary = gvar::subset_bytes(binary, &map.kept).map_err(|e| e.in_table(*b"gvar"))?;
+        }
+        Some(Table::Gvar(gvar)) => {
+            gvar.variations = map.filter(std::mem::take(&mut gvar.variations));
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+/// Compiles a subset layout table
+///
+/// Parts of layout tables which are not read, such as device tables, cannot
+/// be serialized, so this fails rather than silently dropping them.
+fn compile_layout_table<T: Serialize>(
+    tag: Tag,
+    table: Option<T>,
+) -> Result<Option<(Tag, Vec<u8>)>, FontError> {
+    match table {
+        Some(table) => {
+            let binary = otspec::ser::to_bytes(&table).map_err(|e| e.in_table(tag))?;
+            Ok(Some((tag, binary)))
+        }
+        None => Ok(None),
+    }
+}
+
+/// Subsets a font to the given codepoints and glyphs
+///
+/// The glyphs kept are those found by [`closure`]. They are renumbered in
+/// their original order, and the `glyf`, `loca`, `hmtx`, `vmtx`, `maxp`,
+/// `post`, `gvar`, `GSUB`, `GPOS` and `GDEF` tables pruned to match. The
+/// `cmap` table keeps the mappings of the requested codepoints and glyphs.
+///
+/// Tables which do not refer to glyphs are kept unchanged; any others are
+/// dropped with a warning. Fails without changing the font if a layout table
+/// has subtables of types or formats which are not currently supported, or
+/// data which is not currently read, such as device tables or a `GDEF`
+/// ligature caret list. The
+/// `glyf` and `loca` tables are left compiled to binary, ready to be saved.
+///
+/// Returns the old glyph IDs of the glyphs kept, in their new order.
+pub fn subset(
+    font: &mut Font,
+    unicodes: &BTreeSet<uint32>,
+    glyphs: &BTreeSet<uint16>,
+) -> Result<Vec<uint16>, FontError> {
+    let mut gsub: Option<gsub> = layout_table(font, b"GSUB")?;
+    let closure = glyph_closure(font, gsub.as_ref(), unicodes, glyphs)?;
+    let num_glyphs = glyf_table(font)?.glyphs.len();
+    let map = GlyphMap::new(closure.into_iter().collect(), num_glyphs);
+
+    // Subset and compile the layout tables first, as they may fail
+    let mut gpos: Option<gpos> = layout_table(font, b"GPOS")?;
+    let mut gdef: Option<gdef> = layout_table(font, b"GDEF")?;
+    if let Some(gsub) = &mut gsub {
+        layout::subset_gsub(gsub, &map)?;
+    }
+    if let Some(gpos) = &mut gpos {
+        layout::subset_gpos(gpos, &map)?;
+    }
+    if let Some(gdef) = &mut gdef {
+        layout::subset_gdef(gdef, &map);
+    }
+    let layout_tables = vec![
+        compile_layout_table(*b"GSUB", gsub)?,
+        compile_layout_table(*b"GPOS", gpos)?,
+        compile_layout_table(*b"GDEF", gdef)?,
+    ];
+
+    let dropped: Vec<Tag> = font
+        .tables
+        .keys()
+        .filter(|tag| !UNCHANGED_TABLES.contains(tag) && !SUBSET_TABLES.contains(tag))
+        .copied()
+        .collect();
+    for tag in dropped {
+        log::warn!(
+            "Dropping the {} table, as it cannot be subset",
+            String::from_utf8_lossy(&tag)
+        );
+        font.tables.remove(&tag);
+    }
+
+    subset_glyf(font, &map)?;
+    subset_hmtx(font, &map)?;
+    subset_vmtx(font, &map)?;
+    subset_cmap(font, &map, unicodes, glyphs)?;
+    subset_post(font, &map)?;
+    subset_gvar(font, &map)?;
+    for (tag, binary) in layout_tables.into_iter().flatten() {
+        font.tables.insert(tag, Table::Unknown(binary));
+    }
+    // Also sets the glyph count in maxp
+    font.compile_glyf_loca_maxp()?;
+    Ok(map.kept)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::font::{self, Font, FontError, SfntVersion, Table};
+    use crate::gdef;
+    use crate::gsub::{gsub, LigatureSubst, SingleSubst, Substitution};
+    use crate::hmtx::Metric;
+    use crate::layout::{Lookup, LookupFlags};
+    use crate::subset;
+    use crate::ttx;
+    use std::collections::BTreeSet;
+
+    /// The TTX test font, with the composite glyph using only the hyphen so
+    /// that "A" can be removed
+    fn test_font() -> Font {
+        let mut font = ttx::tests::test_font();
+        if let Some(Table::Glyf(glyf)) = font.tables.get_mut(b"glyf") {
+            glyf.glyphs[3].components.retain(|c| c.glyphIndex == 2);
+        }
+        font
+    }
+
+    fn set<T: Ord + Copy>(items: &[T]) -> BTreeSet<T> {
+        items.iter().copied().collect()
+    }
+
+    fn lookup(lookupType: u16, subtable: Substitution) -> Lookup<Substitution> {
+        Lookup {
+            lookupType,
+            lookupFlag: LookupFlags::empty(),
+            subtables: vec![subtable],
+            markFilteringSet: None,
+        }
+    }
+
+    #[test]
+    fn subset_codepoints() {
+        let mut font = test_font();
+        let unicodes = set(&[0xC5, 0x10FFFF]);
+        assert_eq!(
+            subset::closure(&mut font, &unicodes, &set(&[])).unwrap(),
+            set(&[0, 2, 3])
+        );
+        let kept = subset::subset(&mut font, &unicodes, &set(&[])).unwrap();
+        assert_eq!(kept, vec![0, 2, 3]);
+        assert_eq!(font.num_glyphs().unwrap(), 3);
+        assert!(!font.tables.contains_key(b"zzzz"));
+        assert!(font.tables.contains_key(b"fvar"));
+        font.fully_deserialize().unwrap();
+
+        let glyf = font.get_table(b"glyf").unwrap().unwrap().glyf_unchecked();
+        assert_eq!(glyf.glyphs.len(), 3);
+        assert_eq!(glyf.glyphs[1].xMax, 300);
+        assert_eq!(glyf.glyphs[2].components.len(), 1);
+        assert_eq!(glyf.glyphs[2].components[0].glyphIndex, 1);
+
+        let hmtx = font.get_table(b"hmtx").unwrap().unwrap().hmtx_unchecked();
+        let metrics: Vec<(u16, i16)> = hmtx
+            .metrics
+            .iter()
+            .map(|Metric { advanceWidth, lsb }| (*advanceWidth, *lsb))
+            .collect();
+        assert_eq!(metrics, vec![(500, 0), (600, -5), (600, 30)]);
+        let hhea = font.get_table(b"hhea").unwrap().unwrap().hhea_unchecked();
+        assert_eq!(hhea.numberOfHMetrics, 2);
+
+        let cmap = font.get_table(b"cmap").unwrap().unwrap().cmap_unchecked();
+        let mapping: Vec<(u32, u16)> = cmap.subtables[0]
+            .mapping
+            .iter()
+            .map(|(&c, &g)| (c, g))
+            .collect();
+        assert_eq!(mapping, vec![(0xC5, 2)]);
+
+        let post = font.get_table(b"post").unwrap().unwrap().post_unchecked();
+        assert_eq!(
+            post.glyphnames,
+            Some(vec![".notdef".to_string(), "hyphen".into(), "A".into()])
+        );
+
+        let gvar = font.get_table(b"gvar").unwrap().unwrap().gvar_unchecked();
+        assert_eq!(gvar.variations.len(), 3);
+        assert!(gvar.variations[1].is_some());
+    }
+
+    #[test]
+    fn subset_glyphs_and_gsub() {
+        let mut font = test_font();
+        // A parsed gvar table cannot be saved
+        font.tables.remove(b"gvar");
+        let mut table = gsub::default();
+        table.lookups.push(lookup(
+            1,
+            Substitution::Single(SingleSubst {
+                mapping: vec![(2, 3), (1, 0)].into_iter().collect(),
+            }),
+        ));
+        table.lookups.push(lookup(
+            4,
+            Substitution::Ligature(LigatureSubst {
+                ligatures: vec![(vec![1, 1], 3), (vec![2, 2], 3)],
+            }),
+        ));
+        let binary = otspec::ser::to_bytes(&table).unwrap();
+        font.tables.insert(*b"GSUB", Table::Unknown(binary));
+
+        // The hyphen can be substituted by the composite glyph
+        let kept = subset::subset(&mut font, &set(&[]), &set(&[2])).unwrap();
+        assert_eq!(kept, vec![0, 2, 3]);
+
+        let mut binary = vec![];
+        font.save(&mut binary).unwrap();
+        let mut font = font::load(binary.as_slice()).unwrap();
+        let cmap = font.get_table(b"cmap").unwrap().unwrap().cmap_unchecked();
+        let mapping: Vec<(u32, u16)> = cmap.subtables[0]
+            .mapping
+            .iter()
+            .map(|(&c, &g)| (c, g))
+            .collect();
+        assert_eq!(mapping, vec![(0x2010, 1)]);
+
+        let table: gsub = match font.get_table(b"GSUB").unwrap() {
+            Some(Table::Unknown(binary)) => otspec::de::from_bytes(binary).unwrap(),
+            _ => panic!("GSUB table not found"),
+        };
+        assert_eq!(
+            table.lookups[0].subtables,
+            vec![Substitution::Single(SingleSubst {
+                mapping: vec![(1, 2)].into_iter().collect(),
+            })]
+        );
+        assert_eq!(
+            table.lookups[1].subtables,
+            vec![Substitution::Ligature(LigatureSubst {
+                ligatures: vec![(vec![1, 1], 2)],
+            })]
+        );
+    }
+
+    #[test]
+    fn subset_variation_sequences() {
+        let mut font = test_font();
+        // U+0041 U+FE01 is mapped to the composite glyph
+        let kept = subset::subset(&mut font, &set(&[0x41]), &set(&[])).unwrap();
+        assert_eq!(kept, vec![0, 1, 2, 3]);
+        let cmap = font.get_table(b"cmap").unwrap().unwrap().cmap_unchecked();
+        assert_eq!(cmap.getVariationGlyph(0x41, 0xFE00), Some(1));
+        assert_eq!(cmap.getVariationGlyph(0x41, 0xFE01), Some(3));
+
+        let mut font = test_font();
+        subset::subset(&mut font, &set(&[0xC5]), &set(&[])).unwrap();
+        let cmap = font.get_table(b"cmap").unwrap().unwrap().cmap_unchecked();
+        assert!(cmap.variationSequences.is_empty());
+    }
+
+    #[test]
+    fn subset_errors() {
+        let mut font = test_font();
+        let error = subset::subset(&mut font, &set(&[]), &set(&[4])).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Glyph ID 4 is out of range, as the font has 4 glyphs"
+        );
+
+        let mut font = Font::new(SfntVersion::OpenType);
+        assert!(matches!(
+            subset::subset(&mut font, &set(&[0x41]), &set(&[])),
+            Err(FontError::MissingTable(tag)) if &tag == b"glyf"
+        ));
+    }
+
+    #[test]
+    fn subset_unsupported_lookup() {
+        let mut font = test_font();
+        let mut table = gsub::default();
+        table.lookups.push(lookup(
+            1,
+            Substitution::Single(SingleSubst {
+                mapping: vec![(2, 3)].into_iter().collect(),
+            }),
+        ));
+        let mut binary = otspec::ser::to_bytes(&table).unwrap();
+        // Make the lookup a reverse chaining contextual one
+        let lookup_list = u16::from_be_bytes([binary[8], binary[9]]) as usize;
+        let offset = u16::from_be_bytes([binary[lookup_list + 2], binary[lookup_list + 3]]);
+        binary[lookup_list + offset as usize + 1] = 8;
+        font.tables.insert(*b"GSUB", Table::Unknown(binary.clone()));
+
+        let error = subset::subset(&mut font, &set(&[]), &set(&[2])).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Cannot subset unsupported GSUB subtable (lookup 0, type 8, format 1)"
+        );
+        // The font is left unchanged
+        assert_eq!(font.num_glyphs().unwrap(), 4);
+        assert!(matches!(
+            font.tables.get(b"GSUB"),
+            Some(Table::Unknown(b)) if *b == binary
+        ));
+    }
+
+    #[test]
+    fn subset_unread_gdef() {
+        let mut font = test_font();
+        let binary = gdef::tests::BINARY_GDEF_1_3.to_vec();
+        font.tables.insert(*b"GDEF", Table::Unknown(binary.clone()));
+
+        let error = subset::subset(&mut font, &set(&[]), &set(&[2])).unwrap_err();
+        assert!(error
+            .to_string()
+            .contains("Cannot serialize the GDEF ligature caret list"));
+        // The font is left unchanged
+        assert_eq!(font.num_glyphs().unwrap(), 4);
+        assert!(matches!(
+            font.tables.get(b"GDEF"),
+            Some(Table::Unknown(b)) if *b == binary
+        ));
+    }
+}
@@ -0,0 +1,195 @@
+use super::GlyphMap;
+use crate::font::FontError;
+use crate::gdef::gdef;
+use crate::gpos::{gpos, Positioning};
+use crate::gsub::{gsub, Substitution};
+use crate::layout::{ChainedSequenceContext, SequenceContext, SequenceRule};
+use otspec::error::Error as OTSpecError;
+use otspec::types::*;
+use std::collections::BTreeMap;
+
+/// Renumbers the coverages of a contextual subtable
+///
+/// Removed glyphs are dropped from the coverages; a rule whose coverage
+/// becomes empty can no longer match.
+fn subset_context(context: &mut ChainedSequenceContext, map: &GlyphMap) {
+    for coverage in context
+        .backtrackCoverages
+        .iter_mut()
+        .chain(&mut context.inputCoverages)
+        .chain(&mut context.lookaheadCoverages)
+    {
+        *coverage = map.coverage(coverage);
+    }
+}
+
+/// Renumbers a glyph-based or class-based contextual subtable
+///
+/// The rule sets of removed glyphs, and glyph-based rules which refer to
+/// removed glyphs, are dropped. Class values are left unchanged.
+fn subset_rules(context: &mut SequenceContext, map: &GlyphMap) {
+    if let Some(classDefs) = &mut context.classDefs {
+        context.coverage = map.coverage(&context.coverage);
+        classDefs.backtrack = map.class_def(&classDefs.backtrack);
+        classDefs.input = map.class_def(&classDefs.input);
+        classDefs.lookahead = map.class_def(&classDefs.lookahead);
+        return;
+    }
+    let ruleSets = std::mem::take(&mut context.ruleSets);
+    let mut glyphs = vec![];
+    for (&glyph, rules) in context.coverage.glyphs.iter().zip(ruleSets) {
+        let glyph = match map.get(glyph) {
+            Some(glyph) => glyph,
+            None => continue,
+        };
+        glyphs.push(glyph);
+        context.ruleSets.push(
+            rules
+                .into_iter()
+                .filter_map(|rule| {
+                    Some(SequenceRule {
+                        backtrack: map.glyphs(&rule.backtrack)?,
+                        input: map.glyphs(&rule.input)?,
+                        lookahead: map.glyphs(&rule.lookahead)?,
+                        seqLookupRecords: rule.seqLookupRecords,
+                    })
+                })
+                .collect(),
+        );
+    }
+    context.coverage.glyphs = glyphs;
+}
+
+/// The error for a subtable of a type or format which cannot be subset
+fn unsupported(table: &str, index: usize, lookupType: uint16, format: uint16) -> FontError {
+    OTSpecError::Message(format!(
+        "Cannot subset unsupported {} subtable (lookup {}, type {}, format {})",
+        table, index, lookupType, format
+    ))
+    .into()
+}
+
+/// Renumbers a mapping of glyphs to sequences of glyphs, dropping the
+/// entries which refer to removed glyphs
+fn subset_sequences(
+    mapping: &BTreeMap<uint16, Vec<uint16>>,
+    map: &GlyphMap,
+) -> BTreeMap<uint16, Vec<uint16>> {
+    mapping
+        .iter()
+        .filter_map(|(&glyph, sequence)| Some((map.get(glyph)?, map.glyphs(sequence)?)))
+        .collect()
+}
+
+/// Renumbers the glyphs of a `GSUB` table, dropping rules which refer to
+/// removed glyphs
+///
+/// Fails without changing the table if it has subtables which cannot be
+/// subset.
+pub(super) fn subset_gsub(table: &mut gsub, map: &GlyphMap) -> Result<(), FontError> {
+    for (index, lookup) in table.lookups.iter().enumerate() {
+        for subtable in &lookup.subtables {
+            if let Substitution::Unsupported { format } = subtable {
+                return Err(unsupported("GSUB", index, lookup.lookupType, *format));
+            }
+        }
+    }
+    for subtable in table
+        .lookups
+        .iter_mut()
+        .flat_map(|lookup| &mut lookup.subtables)
+    {
+        match subtable {
+            Substitution::Single(s) => {
+                s.mapping = s
+                    .mapping
+                    .iter()
+                    .filter_map(|(&input, &output)| Some((map.get(input)?, map.get(output)?)))
+                    .collect()
+            }
+            Substitution::Multiple(s) => s.mapping = subset_sequences(&s.mapping, map),
+            Substitution::Alternate(s) => s.mapping = subset_sequences(&s.mapping, map),
+            Substitution::Ligature(s) => {
+                s.ligatures = s
+                    .ligatures
+                    .iter()
+                    .filter_map(|(inputs, ligature)| {
+                        Some((map.glyphs(inputs)?, map.get(*ligature)?))
+                    })
+                    .collect()
+            }
+            Substitution::ContextRules(c) | Substitution::ChainedContextRules(c) => {
+                subset_rules(c, map)
+            }
+            Substitution::Context(c) | Substitution::ChainedContext(c) => subset_context(c, map),
+            // Rejected above
+            Substitution::Unsupported { .. } => {}
+        }
+    }
+    Ok(())
+}
+
+/// Renumbers the glyphs of a `GPOS` table, dropping rules which refer to
+/// removed glyphs
+///
+/// Fails without changing the table if it has subtables which cannot be
+/// subset.
+pub(super) fn subset_gpos(table: &mut gpos, map: &GlyphMap) -> Result<(), FontError> {
+    for (index, lookup) in table.lookups.iter().enumerate() {
+        for subtable in &lookup.subtables {
+            if let Positioning::Unsupported { format } = subtable {
+                return Err(unsupported("GPOS", index, lookup.lookupType, *format));
+            }
+        }
+    }
+    for subtable in table
+        .lookups
+        .iter_mut()
+        .flat_map(|lookup| &mut lookup.subtables)
+    {
+        match subtable {
+            Positioning::Single(s) => s.mapping = map.keys(&s.mapping),
+            Positioning::PairGlyphs(p) => {
+                p.mapping = p
+                    .mapping
+                    .iter()
+                    .filter_map(|(&(first, second), &values)| {
+                        Some(((map.get(first)?, map.get(second)?), values))
+                    })
+                    .collect()
+            }
+            Positioning::PairClasses(p) => {
+                p.coverage = map.coverage(&p.coverage);
+                p.classDef1 = map.class_def(&p.classDef1);
+                p.classDef2 = map.class_def(&p.classDef2);
+            }
+            Positioning::Cursive(c) => c.mapping = map.keys(&c.mapping),
+            Positioning::MarkToBase(m) | Positioning::MarkToMark(m) => {
+                m.marks = map.keys(&m.marks);
+                m.bases = map.keys(&m.bases);
+            }
+            Positioning::MarkToLigature(m) => {
+                m.marks = map.keys(&m.marks);
+                m.ligatures = map.keys(&m.ligatures);
+            }
+            Positioning::ContextRules(c) | Positioning::ChainedContextRules(c) => {
+                subset_rules(c, map)
+            }
+            Positioning::Context(c) | Positioning::ChainedContext(c) => subset_context(c, map),
+            // Rejected above
+            Positioning::Unsupported { .. } => {}
+        }
+    }
+    Ok(())
+}
+
+/// Renumbers the glyphs of a `GDEF` table
+pub(super) fn subset_gdef(table: &mut gdef, map: &GlyphMap) {
+    table.glyphClassDef = table.glyphClassDef.as_ref().map(|c| map.class_def(c));
+    table.markAttachClassDef = table.markAttachClassDef.as_ref().map(|c| map.class_def(c));
+    table.markGlyphSets = table
+        .markGlyphSets
+        .iter()
+        .map(|c| map.coverage(c))
+        .collect();
+}
@@ -0,0 +1,670 @@
+use crate::font::{Font, FontError, SfntVersion, Table};
+use crate::fvar::VariationAxisRecord;
+use crate::glyf::Glyph;
+use crate::loca::loca;
+use otspec::error::Error as OTSpecError;
+use otspec::types::*;
+use std::collections::{BTreeMap, HashMap};
+use xml::{error, Element, XmlWriter};
+
+mod basic;
+mod cmap;
+mod fvar;
+mod glyf;
+mod instructions;
+mod name;
+mod xml;
+
+/// The order in which fontTools writes the tables of a TrueType font
+const TTF_TABLE_ORDER: &[&Tag] = &[
+    b"head", b"hhea", b"maxp", b"OS/2", b"hmtx", b"LTSH", b"VDMX", b"hdmx", b"cmap", b"fpgm",
+    b"prep", b"cvt ", b"loca", b"glyf", b"kern", b"name", b"post", b"gasp", b"PCLT",
+];
+
+/// The order in which fontTools writes the tables of a CFF-flavoured font
+const OTF_TABLE_ORDER: &[&Tag] = &[
+    b"head", b"hhea", b"maxp", b"OS/2", b"name", b"cmap", b"post", b"CFF ",
+];
+
+/// The names of a font's glyphs, used to refer to glyphs in TTX
+pub(crate) struct GlyphOrder {
+    /// The unique name of each glyph
+    pub(crate) names: Vec<String>,
+    ids: HashMap<String, u16>,
+    /// The original `post` table name of glyphs which had to be renamed to
+    /// make their names unique
+    pub(crate) ps_names: BTreeMap<String, String>,
+    /// Whether the names were read from a font's `post` table, rather than
+    /// made up from its codepoints and glyph IDs
+    pub(crate) from_post: bool,
+}
+
+impl GlyphOrder {
+    /// Creates a glyph order from a list of names, renaming duplicates
+    fn new(names: Vec<String>) -> Self {
+        let mut glyph_order = GlyphOrder {
+            names: Vec::with_capacity(names.len()),
+            ids: HashMap::new(),
+            ps_names: BTreeMap::new(),
+            from_post: false,
+        };
+        for name in names {
+            let mut unique = name.clone();
+            let mut suffix = 1;
+            while glyph_order.ids.contains_key(&unique) {
+                unique = format!("{}#{}", name, suffix);
+                suffix += 1;
+            }
+            if unique != name {
+                glyph_order.ps_names.insert(unique.clone(), name);
+            }
+            glyph_order
+                .ids
+                .insert(unique.clone(), glyph_order.names.len() as u16);
+            glyph_order.names.push(unique);
+        }
+        glyph_order
+    }
+
+    /// Names the glyphs of a font as fontTools does
+    ///
+    /// Names come from the `post` table where it has them. Otherwise glyphs
+    /// are named after the lowest codepoint which maps to them, or their glyph
+    /// ID if they are not encoded.
+    pub(crate) fn from_font(font: &Font) -> Self {
+        let num_glyphs = match font.tables.get(b"maxp") {
+            Some(Table::Maxp(maxp)) => maxp.num_glyphs() as usize,
+            _ => match font.tables.get(b"glyf") {
+                Some(Table::Glyf(glyf)) => glyf.glyphs.len(),
+                _ => 0,
+            },
+        };
+        if let Some(Table::Post(post)) = font.tables.get(b"post") {
+            if let Some(glyphnames) = &post.glyphnames {
+                if post.version == U16F16::from_num(2.0) && glyphnames.len() >= num_glyphs {
+                    return GlyphOrder {
+                        from_post: true,
+                        ..GlyphOrder::new(glyphnames[..num_glyphs].to_vec())
+                    };
+                }
+            }
+        }
+        let mut names: Vec<Option<String>> = vec![None; num_glyphs];
+        if let Some(Table::Cmap(cmap)) = font.tables.get(b"cmap") {
+            if let Some(mapping) = cmap.getBestMapping() {
+                for (&codepoint, &gid) in mapping {
+                    match names.get_mut(gid as usize) {
+                        Some(name) if name.is_none() => {
+                            *name = Some(if codepoint > 0xFFFF {
+                                format!("u{:X}", codepoint)
+                            } else {
+                                format!("uni{:04X}", codepoint)
+                            })
+                        }
+                        _ => {}
+                    }
+                }
+            }
+        }
+        GlyphOrder::new(
+            names
+                .into_iter()
+                .enumerate()
+                .map(|(gid, name)| match (gid, name) {
+                    (0, _) => ".notdef".to_string(),
+                    (_, Some(name)) => name,
+                    (gid, None) => format!("glyph{:05}", gid),
+                })
+                .collect(),
+        )
+    }
+
+    /// The name of a glyph, or a made-up name if it is not in the font
+    pub(crate) fn name(&self, gid: u16) -> String {
+        self.names
+            .get(gid as usize)
+            .cloned()
+            .unwrap_or_else(|| format!("glyph{:05}", gid))
+    }
+
+    /// The ID of a named glyph
+    ///
+    /// As in fontTools, `glyphNNNNN` names the glyph with ID `NNNNN`.
+    pub(crate) fn id(&self, name: &str) -> Result<u16, OTSpecError> {
+        if let Some(&gid) = self.ids.get(name) {
+            return Ok(gid);
+        }
+        name.strip_prefix("glyph")
+            .and_then(|digits| digits.parse::<u16>().ok())
+            .filter(|&gid| (gid as usize) < self.names.len())
+            .ok_or_else(|| error(format!("Unknown glyph name '{}'", name)))
+    }
+
+    /// The ID of the glyph with exactly this name, if there is one
+    pub(crate) fn get(&self, name: &str) -> Option<u16> {
+        self.ids.get(name).copied()
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.names.len()
+    }
+}
+
+/// Converts a table tag to the name of its TTX element
+fn tag_to_xml(tag: &Tag) -> String {
+    if tag == b"OS/2" {
+        return "OS_2".to_string();
+    }
+    let tag = String::from_utf8_lossy(tag);
+    let trimmed = tag.trim_end_matches(' ');
+    let mut chars = trimmed.chars();
+    let is_identifier = matches!(chars.next(), Some(c) if c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_');
+    if is_identifier {
+        return trimmed.to_string();
+    }
+    // Other tags are escaped a character at a time
+    let mut identifier: String = trimmed
+        .chars()
+        .map(|c| match c {
+            'a'..='z' | '0'..='9' => format!("_{}", c),
+            'A'..='Z' => format!("{}_", c),
+            c => format!("{:02x}", c as u32),
+        })
+        .collect();
+    if identifier.starts_with(|c: char| c.is_ascii_digit()) {
+        identifier.insert(0, '_');
+    }
+    identifier
+}
+
+/// Converts the name of a TTX element to a table tag
+fn xml_to_tag(name: &str) -> Result<Tag, OTSpecError> {
+    let invalid = || error(format!("<{}> is not a table tag", name));
+    let mut tag = *b"    ";
+    if name == "OS_2" {
+        return Ok(*b"OS/2");
+    } else if name.len() <= 4 {
+        tag[..name.len()].copy_from_slice(name.as_bytes());
+        return Ok(tag);
+    }
+    let name = if name.len() % 2 == 1 {
+        name.strip_prefix('_').ok_or_else(invalid)?
+    } else {
+        name
+    };
+    let pairs: Vec<&[u8]> = name.as_bytes().chunks(2).collect();
+    if pairs.len() > 4 {
+        return Err(invalid());
+    }
+    for (byte, pair) in tag.iter_mut().zip(pairs) {
+        *byte = match pair {
+            [b'_', c] | [c, b'_'] => *c,
+            hex => std::str::from_utf8(hex)
+                .ok()
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+                .ok_or_else(invalid)?,
+        };
+    }
+    Ok(tag)
+}
+
+/// The order in which fontTools writes tables: known tables first, then the
+/// rest alphabetically, with any signature last
+fn table_order(font: &Font) -> Vec<Tag> {
+    let mut tags: Vec<Tag> = font.tables.keys().copied().collect();
+    let order = if font.tables.contains_key(b"CFF ") {
+        OTF_TABLE_ORDER
+    } else {
+        TTF_TABLE_ORDER
+    };
+    tags.sort_by_key(|tag| {
+        (
+            tag == b"DSIG",
+            order.iter().position(|t| *t == tag).unwrap_or(order.len()),
+            *tag,
+        )
+    });
+    tags
+}
+
+fn write_table(
+    writer: &mut XmlWriter,
+    font: &Font,
+    table: &Table,
+    glyph_order: &GlyphOrder,
+) -> Result<(), FontError> {
+    match table {
+        Table::Head(head) => basic::write_head(writer, head),
+        Table::Hhea(hhea) => basic::write_hhea(writer, hhea),
+        Table::Maxp(maxp) => basic::write_maxp(writer, maxp),
+        Table::Os2(os2) => basic::write_os2(writer, os2),
+        Table::Post(post) => basic::write_post(writer, post, glyph_order),
+        Table::Gasp(gasp) => basic::write_gasp(writer, gasp),
+        Table::Name(name) => name::write(writer, name),
+        Table::Cmap(cmap) => cmap::write(writer, cmap, glyph_order),
+        Table::Glyf(glyf) => glyf::write(writer, glyf, glyph_order),
+        Table::Hmtx(hmtx) => glyf::write_hmtx(writer, hmtx, glyph_order),
+        Table::Loca(_) => writer.comment("The 'loca' table will be calculated by the compiler"),
+        Table::Fvar(fvar) => {
+            let name = match font.tables.get(b"name") {
+                Some(Table::Name(name)) => Some(name),
+                _ => None,
+            };
+            fvar::write_fvar(writer, fvar, name)
+        }
+        Table::Gvar(gvar) => fvar::write_gvar(writer, gvar, axes(font), glyph_order),
+        Table::Avar(avar) => {
+            writer.begin("hexdata", &[]);
+            writer.hex(&otspec::ser::to_bytes(avar)?);
+            writer.end("hexdata");
+        }
+        Table::Unknown(data) => {
+            writer.begin("hexdata", &[]);
+            writer.hex(data);
+            writer.end("hexdata");
+        }
+    }
+    Ok(())
+}
+
+fn axes(font: &Font) -> &[VariationAxisRecord] {
+    match font.tables.get(b"fvar") {
+        Some(Table::Fvar(fvar)) => &fvar.axes,
+        _ => &[],
+    }
+}
+
+/// Dumps a font to TTX, the XML format used by fontTools
+///
+/// Tables which this crate can read are written in the same layout as
+/// fontTools uses; other tables are written as hex data.
+pub fn dump(font: &mut Font) -> Result<String, FontError> {
+    font.fully_deserialize()?;
+    let glyph_order = GlyphOrder::from_font(font);
+    let mut writer = XmlWriter::new();
+    let sfnt_version = match font.sfnt_version() {
+        SfntVersion::OpenType => "OTTO",
+        SfntVersion::TrueType => "\\x00\\x01\\x00\\x00",
+    };
+    writer.begin("ttFont", &[("sfntVersion", &sfnt_version)]);
+
+    writer.blank();
+    writer.begin("GlyphOrder", &[]);
+    writer.comment("The 'id' attribute is only for humans; it is ignored when parsed.");
+    for (gid, name) in glyph_order.names.iter().enumerate() {
+        writer.simple("GlyphID", &[("id", &gid), ("name", name)]);
+    }
+    writer.end("GlyphOrder");
+
+    for tag in table_order(font) {
+        let element = tag_to_xml(&tag);
+        writer.blank();
+        writer.begin(&element, &[]);
+        write_table(&mut writer, font, &font.tables[&tag], &glyph_order).map_err(|e| match e {
+            FontError::Table(e) => FontError::Table(e.in_table(tag)),
+            e => e,
+        })?;
+        writer.end(&element);
+    }
+
+    writer.blank();
+    writer.end("ttFont");
+    Ok(writer.finish())
+}
+
+fn read_table(element: &Element, tag: Tag, glyph_order: &GlyphOrder) -> Result<Table, OTSpecError> {
+    if let Some(hexdata) = element.child("hexdata") {
+        return Ok(Table::Unknown(xml::parse_hex(&hexdata.text)?));
+    }
+    Ok(match &tag {
+        b"head" => Table::Head(basic::read_head(element)?),
+        b"hhea" => Table::Hhea(basic::read_hhea(element)?),
+        b"maxp" => Table::Maxp(basic::read_maxp(element)?),
+        b"OS/2" => Table::Os2(basic::read_os2(element)?),
+        b"post" => Table::Post(basic::read_post(element, glyph_order)?),
+        b"gasp" => Table::Gasp(basic::read_gasp(element)?),
+        b"name" => Table::Name(name::read(element)?),
+        b"cmap" => Table::Cmap(cmap::read(element, glyph_order)?),
+        b"glyf" => Table::Glyf(glyf::read(element, glyph_order)?),
+        b"hmtx" => Table::Hmtx(glyf::read_hmtx(element, glyph_order)?),
+        // The offsets are filled in when the glyf table is compiled
+        b"loca" => Table::Loca(loca { indices: vec![] }),
+        b"fvar" => Table::Fvar(fvar::read_fvar(element)?),
+        _ => {
+            return Err(error(format!(
+                "Don't know how to compile <{}> without <hexdata>",
+                element.name
+            )))
+        }
+    })
+}
+
+/// Recalculates the values which fontTools leaves to the compiler
+fn recalculate(font: &mut Font, glyph_order: &GlyphOrder) {
+    if let Some(Table::Maxp(maxp)) = font.tables.get_mut(b"maxp") {
+        maxp.set_num_glyphs(glyph_order.len() as u16);
+    }
+
+    let bounds = match font.tables.get(b"glyf") {
+        Some(Table::Glyf(glyf)) => glyf
+            .glyphs
+            .iter()
+            .filter(|glyph| !glyph.is_empty())
+            .map(Glyph::bounds_rect)
+            .reduce(|a, b| a.union(b)),
+        _ => None,
+    };
+    if let (Some(bounds), Some(Table::Head(head))) = (bounds, font.tables.get_mut(b"head")) {
+        head.xMin = bounds.min_x() as i16;
+        head.yMin = bounds.min_y() as i16;
+        head.xMax = bounds.max_x() as i16;
+        head.yMax = bounds.max_y() as i16;
+    }
+
+    // hmtx can only be serialized along with its count of long metrics
+    if let Some(Table::Hmtx(hmtx)) = font.tables.get(b"hmtx") {
+        if hmtx.metrics.is_empty() {
+            font.tables.insert(*b"hmtx", Table::Unknown(vec![]));
+        } else {
+            let (data, number_of_h_metrics) = hmtx.to_bytes();
+            font.tables.insert(*b"hmtx", Table::Unknown(data));
+            if let Some(Table::Hhea(hhea)) = font.tables.get_mut(b"hhea") {
+                hhea.numberOfHMetrics = number_of_h_metrics;
+            }
+        }
+    }
+}
+
+/// Compiles a TTX document, as written by fontTools or [`dump`], to a font
+///
+/// Tables given as `<hexdata>` are kept as binary. The `loca` table and the
+/// values which fontTools recalculates on compilation, such as the font's
+/// bounding box and glyph count, are computed from the other tables.
+pub fn compile(ttx: &str) -> Result<Font, FontError> {
+    let root = xml::parse(ttx)?;
+    if root.name != "ttFont" {
+        return Err(error(format!("Expected <ttFont> but found <{}>", root.name)).into());
+    }
+    let mut font = Font::new(match root.attr("sfntVersion") {
+        Some("OTTO") => SfntVersion::OpenType,
+        _ => SfntVersion::TrueType,
+    });
+    let glyph_order = GlyphOrder::new(
+        root.child("GlyphOrder")
+            .ok_or_else(|| error("TTX document has no <GlyphOrder>"))?
+            .children_named("GlyphID")
+            .map(|glyph| glyph.required("name").map(|name| name.to_string()))
+            .collect::<Result<Vec<String>, OTSpecError>>()?,
+    );
+
+    let mut gvar = None;
+    for element in &root.children {
+        if element.name == "GlyphOrder" {
+            continue;
+        }
+        let tag = xml_to_tag(&element.name)?;
+        if &tag == b"gvar" && element.child("hexdata").is_none() {
+            // The glyphs and axes are needed first
+            gvar = Some(element);
+            continue;
+        }
+        let table = read_table(element, tag, &glyph_order).map_err(|e| e.in_table(tag))?;
+        font.tables.insert(tag, table);
+    }
+
+    if let Some(element) = gvar {
+        let glyphs: &[Glyph] = match font.tables.get(b"glyf") {
+            Some(Table::Glyf(glyf)) => &glyf.glyphs,
+            _ => return Err(FontError::MissingTable(*b"glyf")),
+        };
+        let data = fvar::read_gvar(element, axes(&font), glyphs, &glyph_order)
+            .map_err(|e| e.in_table(*b"gvar"))?;
+        font.tables.insert(*b"gvar", Table::Unknown(data));
+    }
+
+    recalculate(&mut font, &glyph_order);
+    Ok(font)
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use crate::cmap::{cmap, CmapSubtable};
+    use crate::font::{self, Font, FontRef, SfntVersion, Table};
+    use crate::fvar::{fvar, InstanceRecord, VariationAxisRecord};
+    use crate::gasp::{gasp, GaspRecord, RangeGaspBehaviorFlags};
+    use crate::gvar::{gvar, DeltaSet, GlyphVariationData};
+    use crate::hmtx::{hmtx, Metric};
+    use crate::name::{name, NameRecord};
+    use crate::post::post;
+    use crate::ttx::{self, tag_to_xml, xml_to_tag};
+    use crate::woff2::glyf::tests::test_glyf;
+    use std::collections::BTreeMap;
+
+    /// A variable font with the test glyphs, one of which has a duplicate
+    /// name in the post table
+    pub(crate) fn test_font() -> Font {
+        let binary = FontRef::new(font::tests::LOCA_FONT).unwrap();
+        let mut font = Font::new(SfntVersion::TrueType);
+        for tag in &[b"head", b"hhea", b"loca", b"maxp"] {
+            let data = binary.table_data(tag).unwrap().to_vec();
+            font.tables.insert(**tag, Table::Unknown(data));
+        }
+        font.fully_deserialize().unwrap();
+        // Give the tables the values which the compiler calculates
+        if let Some(Table::Head(head)) = font.tables.get_mut(b"head") {
+            head.xMin = -5;
+            head.yMin = -320;
+            head.xMax = 1490;
+            head.yMax = 700;
+        }
+        if let Some(Table::Hhea(hhea)) = font.tables.get_mut(b"hhea") {
+            hhea.numberOfHMetrics = 3;
+        }
+        if let Some(Table::Maxp(maxp)) = font.tables.get_mut(b"maxp") {
+            maxp.set_num_glyphs(4);
+        }
+
+        let metrics = [(500, 0), (1500, 20), (600, -5), (600, 30)]
+            .iter()
+            .map(|&(advanceWidth, lsb)| Metric { advanceWidth, lsb })
+            .collect();
+        font.tables.insert(*b"hmtx", Table::Hmtx(hmtx { metrics }));
+        font.tables.insert(*b"glyf", Table::Glyf(test_glyf()));
+        let mut mapping = BTreeMap::new();
+        mapping.insert(0x41, 1);
+        mapping.insert(0xC5, 3);
+        mapping.insert(0x2010, 2);
+        font.tables.insert(
+            *b"cmap",
+            Table::Cmap(cmap {
+                subtables: vec![CmapSubtable {
+                    format: 4,
+                    platformID: 3,
+                    encodingID: 1,
+                    languageID: 0,
+                    mapping,
+                }],
+                variationSequences: [((0x41, 0xFE00), None), ((0x41, 0xFE01), Some(3))]
+                    .iter()
+                    .copied()
+                    .collect(),
+            }),
+        );
+        font.tables.insert(
+            *b"name",
+            Table::Name(name {
+                records: vec![
+                    NameRecord::windows_unicode(1_u16, "Test & Sons"),
+                    NameRecord::windows_unicode(256_u16, "Weight"),
+                    NameRecord::windows_unicode(257_u16, "Bold"),
+                ],
+            }),
+        );
+        let glyphnames = [".notdef", "A", "hyphen", "A"];
+        font.tables.insert(
+            *b"post",
+            Table::Post(post::new(
+                2.0,
+                -12.5,
+                -100,
+                50,
+                false,
+                Some(glyphnames.iter().map(|n| n.to_string()).collect()),
+            )),
+        );
+        font.tables.insert(
+            *b"gasp",
+            Table::Gasp(gasp {
+                version: 1,
+                gaspRanges: vec![GaspRecord {
+                    rangeMaxPPEM: 0xFFFF,
+                    rangeGaspBehavior: RangeGaspBehaviorFlags::GASP_GRIDFIT
+                        | RangeGaspBehaviorFlags::GASP_SYMMETRIC_SMOOTHING,
+                }],
+            }),
+        );
+        font.tables.insert(
+            *b"fvar",
+            Table::Fvar(fvar {
+                axes: vec![VariationAxisRecord {
+                    axisTag: *b"wght",
+                    flags: 0,
+                    minValue: 100.0,
+                    defaultValue: 400.0,
+                    maxValue: 900.0,
+                    axisNameID: 256,
+                }],
+                instances: vec![InstanceRecord {
+                    subfamilyNameID: 257,
+                    coordinates: vec![700.0],
+                    postscriptNameID: None,
+                }],
+            }),
+        );
+        let deltasets = vec![
+            DeltaSet {
+                peak: vec![1.0],
+                start: vec![0.0],
+                end: vec![1.0],
+                deltas: vec![(10, 0), (20, -5), (0, 0), (0, 0), (30, 0), (0, 0), (0, 0)],
+            },
+            DeltaSet {
+                peak: vec![0.5],
+                start: vec![0.25],
+                end: vec![1.0],
+                deltas: vec![(1, 2), (3, 4), (5, 6), (0, 0), (0, 0), (0, 0), (0, 0)],
+            },
+        ];
+        font.tables.insert(
+            *b"gvar",
+            Table::Gvar(gvar {
+                variations: vec![None, None, Some(GlyphVariationData { deltasets }), None],
+            }),
+        );
+        font.tables
+            .insert(*b"zzzz", Table::Unknown(vec![1, 2, 3, 4, 5]));
+        font
+    }
+
+    #[test]
+    fn ttx_tags() {
+        for (tag, element) in &[
+            (b"head", "head"),
+            (b"OS/2", "OS_2"),
+            (b"cvt ", "cvt"),
+            (b"a/b ", "_a2f_b"),
+        ] {
+            assert_eq!(tag_to_xml(tag), *element);
+            assert_eq!(xml_to_tag(element).unwrap(), **tag);
+        }
+    }
+
+    #[test]
+    fn ttx_dump() {
+        let ttx = ttx::dump(&mut test_font()).unwrap();
+        assert!(ttx.starts_with(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+             <ttFont sfntVersion=\"\\x00\\x01\\x00\\x00\">\n\
+             \n  <GlyphOrder>\n"
+        ));
+        assert!(ttx.ends_with("  </zzzz>\n\n</ttFont>\n"));
+        let tables: Vec<&str> = ttx
+            .lines()
+            .filter(|line| line.starts_with("  <") && !line.starts_with("  </"))
+            .collect();
+        assert_eq!(
+            tables,
+            vec![
+                "  <GlyphOrder>",
+                "  <head>",
+                "  <hhea>",
+                "  <maxp>",
+                "  <hmtx>",
+                "  <cmap>",
+                "  <loca>",
+                "  <glyf>",
+                "  <name>",
+                "  <post>",
+                "  <gasp>",
+                "  <fvar>",
+                "  <gvar>",
+                "  <zzzz>",
+            ]
+        );
+        for snippet in &[
+            "    <GlyphID id=\"3\" name=\"A#1\"/>\n",
+            "    <mtx name=\"A#1\" width=\"600\" lsb=\"30\"/>\n",
+            "      <map code=\"0x2010\" name=\"hyphen\"/>\n",
+            "    <cmap_format_14 platformID=\"0\" platEncID=\"5\">\n      \
+             <map uv=\"0x41\" uvs=\"0xfe00\"/>\n      \
+             <map uv=\"0x41\" uvs=\"0xfe01\" name=\"A#1\"/>\n    \
+             </cmap_format_14>\n    <cmap_format_4",
+            "    <TTGlyph name=\".notdef\"/><!-- contains no outline data -->\n",
+            "      <component glyphName=\"A\" x=\"10\" y=\"-20\" flags=\"0x200\"/>\n",
+            "      <component glyphName=\"hyphen\" x=\"400\" y=\"0\" scale=\"0.5\" flags=\"0x0\"/>\n",
+            "    <namerecord nameID=\"1\" platformID=\"3\" platEncID=\"10\" langID=\"0x409\">\n      \
+             Test &amp; Sons\n    </namerecord>\n",
+            "      <psName name=\"A#1\" psName=\"A\"/>\n",
+            "    <gaspRange rangeMaxPPEM=\"65535\" rangeGaspBehavior=\"9\"/>\n",
+            "    <!-- Bold -->\n    <NamedInstance flags=\"0x0\" subfamilyNameID=\"257\">\n",
+            "        <coord axis=\"wght\" min=\"0.25\" value=\"0.5\" max=\"1.0\"/>\n",
+            "        <coord axis=\"wght\" value=\"1.0\"/>\n",
+            "    <hexdata>\n      01020304 05\n    </hexdata>\n",
+        ] {
+            assert!(ttx.contains(snippet), "{} not found in\n{}", snippet, ttx);
+        }
+    }
+
+    #[test]
+    fn ttx_roundtrip() {
+        let ttx = ttx::dump(&mut test_font()).unwrap();
+        assert_eq!(ttx::dump(&mut ttx::compile(&ttx).unwrap()).unwrap(), ttx);
+
+        // Only the checksum changes when the font is saved
+        let without_checksum = |ttx: &str| -> Vec<String> {
+            ttx.lines()
+                .filter(|line| !line.contains("checkSumAdjustment"))
+                .map(|line| line.to_string())
+                .collect()
+        };
+        let mut binary = vec![];
+        ttx::compile(&ttx).unwrap().save(&mut binary).unwrap();
+        let mut loaded = font::load(binary.as_slice()).unwrap();
+        assert_eq!(
+            without_checksum(&ttx::dump(&mut loaded).unwrap()),
+            without_checksum(&ttx)
+        );
+    }
+
+    #[test]
+    fn ttx_compile_errors() {
+        let error = |ttx: &str| ttx::compile(ttx).unwrap_err().to_string();
+        assert!(error("<ttx/>").contains("Expected <ttFont>"));
+        assert!(error("<ttFont></ttFont>").contains("no <GlyphOrder>"));
+        let ttx = "<ttFont><GlyphOrder><GlyphID name=\".notdef\"/></GlyphOrder>\
+                   <hmtx><mtx name=\"A\" width=\"1\" lsb=\"0\"/></hmtx></ttFont>";
+        let message = error(ttx);
+        assert!(message.contains("hmtx") && message.contains("Unknown glyph name 'A'"));
+    }
+}
@@ -0,0 +1,474 @@
+use super::xml::{binary, error, fixed, Element, XmlWriter};
+use super::GlyphOrder;
+use crate::gasp::{gasp, GaspRecord, RangeGaspBehaviorFlags};
+use crate::head::head;
+use crate::hhea::hhea;
+use crate::maxp::{maxp, maxp05, maxp10, MaxpVariant};
+use crate::os2::{os2, Panose};
+use crate::post::{post, APPLE_NAMES};
+use otspec::error::Error as OTSpecError;
+use otspec::types::U16F16;
+
+/// The format in which fontTools writes dates, as Python's `time.asctime`
+const DATE_FORMAT: &str = "%a %b %e %H:%M:%S %Y";
+
+const RECALCULATED: &str = "Most of this table will be recalculated by the compiler";
+
+pub(super) fn write_head(writer: &mut XmlWriter, head: &head) {
+    writer.comment(RECALCULATED);
+    let version = head.majorVersion as f32 + head.minorVersion as f32 / 65536.0;
+    writer.value("tableVersion", &fixed(version, 16));
+    writer.value("fontRevision", &fixed(head.fontRevision, 16));
+    writer.value(
+        "checkSumAdjustment",
+        &format!("{:#x}", head.checksumAdjustment),
+    );
+    writer.value("magicNumber", &format!("{:#x}", head.magicNumber));
+    writer.value("flags", &binary(head.flags.into(), 16));
+    writer.value("unitsPerEm", &head.unitsPerEm);
+    writer.value("created", &head.created.format(DATE_FORMAT));
+    writer.value("modified", &head.modified.format(DATE_FORMAT));
+    writer.value("xMin", &head.xMin);
+    writer.value("yMin", &head.yMin);
+    writer.value("xMax", &head.xMax);
+    writer.value("yMax", &head.yMax);
+    writer.value("macStyle", &binary(head.macStyle.into(), 16));
+    writer.value("lowestRecPPEM", &head.lowestRecPPEM);
+    writer.value("fontDirectionHint", &head.fontDirectionHint);
+    writer.value("indexToLocFormat", &head.indexToLocFormat);
+    writer.value("glyphDataFormat", &head.glyphDataFormat);
+}
+
+fn parse_date(value: &str) -> Result<chrono::NaiveDateTime, OTSpecError> {
+    // Days of the month are padded with a space, which the parser doesn't
+    // expect to find
+    let normalized = value.split_whitespace().collect::<Vec<_>>().join(" ");
+    chrono::NaiveDateTime::parse_from_str(&normalized, "%a %b %d %H:%M:%S %Y")
+        .map_err(|_| error(format!("Invalid date '{}'", value)))
+}
+
+pub(super) fn read_head(element: &Element) -> Result<head, OTSpecError> {
+    let version = element.float_value("tableVersion")?;
+    Ok(head {
+        majorVersion: version.trunc() as u16,
+        minorVersion: (version.fract() * 65536.0).round() as u16,
+        fontRevision: element.float_value("fontRevision")?,
+        checksumAdjustment: element.int_value("checkSumAdjustment")?,
+        magicNumber: element.int_value("magicNumber")?,
+        flags: element.binary_value("flags")?,
+        unitsPerEm: element.int_value("unitsPerEm")?,
+        created: parse_date(element.value("created")?)?,
+        modified: parse_date(element.value("modified")?)?,
+        xMin: element.int_value("xMin")?,
+        yMin: element.int_value("yMin")?,
+        xMax: element.int_value("xMax")?,
+        yMax: element.int_value("yMax")?,
+        macStyle: element.binary_value("macStyle")?,
+        lowestRecPPEM: element.int_value("lowestRecPPEM")?,
+        fontDirectionHint: element.int_value("fontDirectionHint")?,
+        indexToLocFormat: element.int_value("indexToLocFormat")?,
+        glyphDataFormat: element.int_value("glyphDataFormat")?,
+    })
+}
+
+pub(super) fn write_hhea(writer: &mut XmlWriter, hhea: &hhea) {
+    let version = ((hhea.majorVersion as u32) << 16) | hhea.minorVersion as u32;
+    writer.value("tableVersion", &format!("0x{:08x}", version));
+    writer.value("ascent", &hhea.ascender);
+    writer.value("descent", &hhea.descender);
+    writer.value("lineGap", &hhea.lineGap);
+    writer.value("advanceWidthMax", &hhea.advanceWidthMax);
+    writer.value("minLeftSideBearing", &hhea.minLeftSideBearing);
+    writer.value("minRightSideBearing", &hhea.minRightSideBearing);
+    writer.value("xMaxExtent", &hhea.xMaxExtent);
+    writer.value("caretSlopeRise", &hhea.caretSlopeRise);
+    writer.value("caretSlopeRun", &hhea.caretSlopeRun);
+    writer.value("caretOffset", &hhea.caretOffset);
+    writer.value("reserved0", &hhea.reserved0);
+    writer.value("reserved1", &hhea.reserved1);
+    writer.value("reserved2", &hhea.reserved2);
+    writer.value("reserved3", &hhea.reserved3);
+    writer.value("metricDataFormat", &hhea.metricDataFormat);
+    writer.value("numberOfHMetrics", &hhea.numberOfHMetrics);
+}
+
+pub(super) fn read_hhea(element: &Element) -> Result<hhea, OTSpecError> {
+    let version: u32 = element.int_value("tableVersion")?;
+    // Older versions of fontTools call these fields "ascender" and "descender"
+    let ascender = match element.child("ascent") {
+        Some(_) => element.int_value("ascent")?,
+        None => element.int_value("ascender")?,
+    };
+    let descender = match element.child("descent") {
+        Some(_) => element.int_value("descent")?,
+        None => element.int_value("descender")?,
+    };
+    Ok(hhea {
+        majorVersion: (version >> 16) as u16,
+        minorVersion: (version & 0xffff) as u16,
+        ascender,
+        descender,
+        lineGap: element.int_value("lineGap")?,
+        advanceWidthMax: element.int_value("advanceWidthMax")?,
+        minLeftSideBearing: element.int_value("minLeftSideBearing")?,
+        minRightSideBearing: element.int_value("minRightSideBearing")?,
+        xMaxExtent: element.int_value("xMaxExtent")?,
+        caretSlopeRise: element.int_value("caretSlopeRise")?,
+        caretSlopeRun: element.int_value("caretSlopeRun")?,
+        caretOffset: element.int_value("caretOffset")?,
+        reserved0: element.int_value("reserved0")?,
+        reserved1: element.int_value("reserved1")?,
+        reserved2: element.int_value("reserved2")?,
+        reserved3: element.int_value("reserved3")?,
+        metricDataFormat: element.int_value("metricDataFormat")?,
+        numberOfHMetrics: element.int_value("numberOfHMetrics")?,
+    })
+}
+
+pub(super) fn write_maxp(writer: &mut XmlWriter, maxp: &maxp) {
+    writer.comment(RECALCULATED);
+    let version = maxp.version.to_bits();
+    writer.value("tableVersion", &format!("{:#x}", version));
+    match &maxp.table {
+        MaxpVariant::Maxp05(table) => writer.value("numGlyphs", &table.numGlyphs),
+        MaxpVariant::Maxp10(table) => {
+            writer.value("numGlyphs", &table.numGlyphs);
+            writer.value("maxPoints", &table.maxPoints);
+            writer.value("maxContours", &table.maxContours);
+            writer.value("maxCompositePoints", &table.maxCompositePoints);
+            writer.value("maxCompositeContours", &table.maxCompositeContours);
+            writer.value("maxZones", &table.maxZones);
+            writer.value("maxTwilightPoints", &table.maxTwilightPoints);
+            writer.value("maxStorage", &table.maxStorage);
+            writer.value("maxFunctionDefs", &table.maxFunctionDefs);
+            writer.value("maxInstructionDefs", &table.maxInstructionDefs);
+            writer.value("maxStackElements", &table.maxStackElements);
+            writer.value("maxSizeOfInstructions", &table.maxSizeOfInstructions);
+            writer.value("maxComponentElements", &table.maxComponentElements);
+            writer.value("maxComponentDepth", &table.maxComponentDepth);
+        }
+    }
+}
+
+pub(super) fn read_maxp(element: &Element) -> Result<maxp, OTSpecError> {
+    let version: u32 = element.int_value("tableVersion")?;
+    let numGlyphs = element.int_value("numGlyphs")?;
+    let table = match version {
+        0x5000 => MaxpVariant::Maxp05(maxp05 { numGlyphs }),
+        0x10000 => MaxpVariant::Maxp10(maxp10 {
+            numGlyphs,
+            maxPoints: element.int_value("maxPoints")?,
+            maxContours: element.int_value("maxContours")?,
+            maxCompositePoints: element.int_value("maxCompositePoints")?,
+            maxCompositeContours: element.int_value("maxCompositeContours")?,
+            maxZones: element.int_value("maxZones")?,
+            maxTwilightPoints: element.int_value("maxTwilightPoints")?,
+            maxStorage: element.int_value("maxStorage")?,
+            maxFunctionDefs: element.int_value("maxFunctionDefs")?,
+            maxInstructionDefs: element.int_value("maxInstructionDefs")?,
+            maxStackElements: element.int_value("maxStackElements")?,
+            maxSizeOfInstructions: element.int_value("maxSizeOfInstructions")?,
+            maxComponentElements: element.int_value("maxComponentElements")?,
+            maxComponentDepth: element.int_value("maxComponentDepth")?,
+        }),
+        _ => return Err(error(format!("Unknown maxp version {:#x}", version))),
+    };
+    Ok(maxp {
+        version: U16F16::from_bits(version),
+        table,
+    })
+}
+
+/// The names fontTools gives to the ten PANOSE digits
+const PANOSE_FIELDS: [&str; 10] = [
+    "bFamilyType",
+    "bSerifStyle",
+    "bWeight",
+    "bProportion",
+    "bContrast",
+    "bStrokeVariation",
+    "bArmStyle",
+    "bLetterForm",
+    "bMidline",
+    "bXHeight",
+];
+
+pub(super) fn write_os2(writer: &mut XmlWriter, os2: &os2) {
+    writer.comment(
+        "The fields 'usFirstCharIndex' and 'usLastCharIndex'\n\
+         will be recalculated by the compiler",
+    );
+    writer.value("version", &os2.version);
+    writer.value("xAvgCharWidth", &os2.xAvgCharWidth);
+    writer.value("usWeightClass", &os2.usWeightClass);
+    writer.value("usWidthClass", &os2.usWidthClass);
+    writer.value("fsType", &binary(os2.fsType.into(), 16));
+    writer.value("ySubscriptXSize", &os2.ySubscriptXSize);
+    writer.value("ySubscriptYSize", &os2.ySubscriptYSize);
+    writer.value("ySubscriptXOffset", &os2.ySubscriptXOffset);
+    writer.value("ySubscriptYOffset", &os2.ySubscriptYOffset);
+    writer.value("ySuperscriptXSize", &os2.ySuperscriptXSize);
+    writer.value("ySuperscriptYSize", &os2.ySuperscriptYSize);
+    writer.value("ySuperscriptXOffset", &os2.ySuperscriptXOffset);
+    writer.value("ySuperscriptYOffset", &os2.ySuperscriptYOffset);
+    writer.value("yStrikeoutSize", &os2.yStrikeoutSize);
+    writer.value("yStrikeoutPosition", &os2.yStrikeoutPosition);
+    writer.value("sFamilyClass", &os2.sFamilyClass);
+    let panose = &os2.panose;
+    let digits = [
+        panose.panose0,
+        panose.panose1,
+        panose.panose2,
+        panose.panose3,
+        panose.panose4,
+        panose.panose5,
+        panose.panose6,
+        panose.panose7,
+        panose.panose8,
+        panose.panose9,
+    ];
+    writer.begin("panose", &[]);
+    for (field, digit) in PANOSE_FIELDS.iter().zip(digits.iter()) {
+        writer.value(field, digit);
+    }
+    writer.end("panose");
+    writer.value("ulUnicodeRange1", &binary(os2.ulUnicodeRange1, 32));
+    writer.value("ulUnicodeRange2", &binary(os2.ulUnicodeRange2, 32));
+    writer.value("ulUnicodeRange3", &binary(os2.ulUnicodeRange3, 32));
+    writer.value("ulUnicodeRange4", &binary(os2.ulUnicodeRange4, 32));
+    writer.value("achVendID", &String::from_utf8_lossy(&os2.achVendID));
+    writer.value("fsSelection", &binary(os2.fsSelection.into(), 16));
+    writer.value("usFirstCharIndex", &os2.usFirstCharIndex);
+    writer.value("usLastCharIndex", &os2.usLastCharIndex);
+    writer.value("sTypoAscender", &os2.sTypoAscender);
+    writer.value("sTypoDescender", &os2.sTypoDescender);
+    writer.value("sTypoLineGap", &os2.sTypoLineGap);
+    writer.value("usWinAscent", &os2.usWinAscent);
+    writer.value("usWinDescent", &os2.usWinDescent);
+    if os2.version > 0 {
+        writer.value(
+            "ulCodePageRange1",
+            &binary(os2.ulCodePageRange1.unwrap_or(0), 32),
+        );
+        writer.value(
+            "ulCodePageRange2",
+            &binary(os2.ulCodePageRange2.unwrap_or(0), 32),
+        );
+    }
+    if os2.version > 1 {
+        writer.value("sxHeight", &os2.sxHeight.unwrap_or(0));
+        writer.value("sCapHeight", &os2.sCapHeight.unwrap_or(0));
+        writer.value("usDefaultChar", &os2.usDefaultChar.unwrap_or(0));
+        writer.value("usBreakChar", &os2.usBreakChar.unwrap_or(0));
+        writer.value("usMaxContext", &os2.usMaxContext.unwrap_or(0));
+    }
+    if os2.version > 4 {
+        writer.value(
+            "usLowerOpticalPointSize",
+            &os2.usLowerOpticalPointSize.unwrap_or(0),
+        );
+        writer.value(
+            "usUpperOpticalPointSize",
+            &os2.usUpperOpticalPointSize.unwrap_or(0),
+        );
+    }
+}
+
+pub(super) fn read_os2(element: &Element) -> Result<os2, OTSpecError> {
+    let version: u16 = element.int_value("version")?;
+    let panose_element = element
+        .child("panose")
+        .ok_or_else(|| error("<OS_2> element has no <panose>"))?;
+    let mut digits = [0_u8; 10];
+    for (digit, field) in digits.iter_mut().zip(PANOSE_FIELDS.iter()) {
+        *digit = panose_element.int_value(field)?;
+    }
+    let mut vendor = *b"    ";
+    for (byte, c) in vendor.iter_mut().zip(element.value("achVendID")?.bytes()) {
+        *byte = c;
+    }
+    let from_version = |min_version: u16, field: &str| -> Result<Option<i64>, OTSpecError> {
+        if version >= min_version {
+            element.int_value(field).map(Some)
+        } else {
+            Ok(None)
+        }
+    };
+    let narrow = |value: Option<i64>| value.map(|v| v as u16);
+    let code_page = |field: &str| -> Result<Option<u32>, OTSpecError> {
+        if version >= 1 {
+            element.binary_value(field).map(Some)
+        } else {
+            Ok(None)
+        }
+    };
+    Ok(os2 {
+        version,
+        xAvgCharWidth: element.int_value("xAvgCharWidth")?,
+        usWeightClass: element.int_value("usWeightClass")?,
+        usWidthClass: element.int_value("usWidthClass")?,
+        fsType: element.binary_value("fsType")?,
+        ySubscriptXSize: element.int_value("ySubscriptXSize")?,
+        ySubscriptYSize: element.int_value("ySubscriptYSize")?,
+        ySubscriptXOffset: element.int_value("ySubscriptXOffset")?,
+        ySubscriptYOffset: element.int_value("ySubscriptYOffset")?,
+        ySuperscriptXSize: element.int_value("ySuperscriptXSize")?,
+        ySuperscriptYSize: element.int_value("ySuperscriptYSize")?,
+        ySuperscriptXOffset: element.int_value("ySuperscriptXOffset")?,
+        ySuperscriptYOffset: element.int_value("ySuperscriptYOffset")?,
+        yStrikeoutSize: element.int_value("yStrikeoutSize")?,
+        yStrikeoutPosition: element.int_value("yStrikeoutPosition")?,
+        sFamilyClass: element.int_value("sFamilyClass")?,
+        panose: Panose {
+            panose0: digits[0],
+            panose1: digits[1],
+            panose2: digits[2],
+            panose3: digits[3],
+            panose4: digits[4],
+            panose5: digits[5],
+            panose6: digits[6],
+            panose7: digits[7],
+            panose8: digits[8],
+            panose9: digits[9],
+        },
+        ulUnicodeRange1: element.binary_value("ulUnicodeRange1")?,
+        ulUnicodeRange2: element.binary_value("ulUnicodeRange2")?,
+        ulUnicodeRange3: element.binary_value("ulUnicodeRange3")?,
+        ulUnicodeRange4: element.binary_value("ulUnicodeRange4")?,
+        achVendID: vendor,
+        fsSelection: element.binary_value("fsSelection")?,
+        usFirstCharIndex: element.int_value("usFirstCharIndex")?,
+        usLastCharIndex: element.int_value("usLastCharIndex")?,
+        sTypoAscender: element.int_value("sTypoAscender")?,
+        sTypoDescender: element.int_value("sTypoDescender")?,
+        sTypoLineGap: element.int_value("sTypoLineGap")?,
+        usWinAscent: element.int_value("usWinAscent")?,
+        usWinDescent: element.int_value("usWinDescent")?,
+        ulCodePageRange1: code_page("ulCodePageRange1")?,
+        ulCodePageRange2: code_page("ulCodePageRange2")?,
+        sxHeight: from_version(2, "sxHeight")?.map(|v| v as i16),
+        sCapHeight: from_version(2, "sCapHeight")?.map(|v| v as i16),
+        usDefaultChar: narrow(from_version(2, "usDefaultChar")?),
+        usBreakChar: narrow(from_version(2, "usBreakChar")?),
+        usMaxContext: narrow(from_version(2, "usMaxContext")?),
+        usLowerOpticalPointSize: narrow(from_version(5, "usLowerOpticalPointSize")?),
+        usUpperOpticalPointSize: narrow(from_version(5, "usUpperOpticalPointSize")?),
+    })
+}
+
+pub(super) fn write_post(writer: &mut XmlWriter, post: &post, glyph_order: &GlyphOrder) {
+    writer.value("formatType", &fixed(post.version.to_num(), 16));
+    writer.value("italicAngle", &fixed(post.italicAngle, 16));
+    writer.value("underlinePosition", &post.underlinePosition);
+    writer.value("underlineThickness", &post.underlineThickness);
+    writer.value("isFixedPitch", &post.isFixedPitch);
+    writer.value("minMemType42", &post.minMemType42);
+    writer.value("maxMemType42", &post.maxMemType42);
+    writer.value("minMemType1", &post.minMemType1);
+    writer.value("maxMemType1", &post.maxMemType1);
+    let names = match &post.glyphnames {
+        Some(names) if post.version == U16F16::from_num(2.0) => names,
+        _ => return,
+    };
+    writer.begin("psNames", &[]);
+    writer.comment(
+        "This file uses unique glyph names based on the information\n\
+         found in the 'post' table. Since these names might not be unique,\n\
+         we have to invent artificial names in case of clashes. In order to\n\
+         be able to retain the original information, we need a name to\n\
+         ps name mapping for those cases where they differ. That's what\n\
+         you see below.\n",
+    );
+    for (name, ps_name) in &glyph_order.ps_names {
+        writer.simple("psName", &[("name", name), ("psName", ps_name)]);
+    }
+    writer.end("psNames");
+    writer.begin("extraNames", &[]);
+    writer.comment("following are the name that are not taken from the standard Mac glyph order");
+    let mut extra_names: Vec<&String> = vec![];
+    for name in names {
+        if !APPLE_NAMES.contains(&name.as_str()) && !extra_names.contains(&name) {
+            extra_names.push(name);
+        }
+    }
+    for name in extra_names {
+        writer.simple("psName", &[("name", name)]);
+    }
+    writer.end("extraNames");
+}
+
+pub(super) fn read_post(element: &Element, glyph_order: &GlyphOrder) -> Result<post, OTSpecError> {
+    let version = element.float_value("formatType")?;
+    let glyphnames = if (version - 2.0).abs() < f32::EPSILON {
+        let mut ps_names = std::collections::HashMap::new();
+        if let Some(mapping) = element.child("psNames") {
+            for ps_name in mapping.children_named("psName") {
+                ps_names.insert(ps_name.required("name")?, ps_name.required("psName")?);
+            }
+        }
+        Some(
+            glyph_order
+                .names
+                .iter()
+                .map(|name| {
+                    ps_names
+                        .get(name.as_str())
+                        .map_or_else(|| name.clone(), |ps_name| ps_name.to_string())
+                })
+                .collect(),
+        )
+    } else {
+        None
+    };
+    Ok(post {
+        version: U16F16::from_num(version),
+        italicAngle: element.float_value("italicAngle")?,
+        underlinePosition: element.int_value("underlinePosition")?,
+        underlineThickness: element.int_value("underlineThickness")?,
+        isFixedPitch: element.int_value("isFixedPitch")?,
+        minMemType42: element.int_value("minMemType42")?,
+        maxMemType42: element.int_value("maxMemType42")?,
+        minMemType1: element.int_value("minMemType1")?,
+        maxMemType1: element.int_value("maxMemType1")?,
+        glyphnames,
+    })
+}
+
+pub(super) fn write_gasp(writer: &mut XmlWriter, gasp: &gasp) {
+    for range in &gasp.gaspRanges {
+        writer.simple(
+            "gaspRange",
+            &[
+                ("rangeMaxPPEM", &range.rangeMaxPPEM),
+                ("rangeGaspBehavior", &range.rangeGaspBehavior.bits()),
+            ],
+        );
+    }
+}
+
+pub(super) fn read_gasp(element: &Element) -> Result<gasp, OTSpecError> {
+    let mut gaspRanges = vec![];
+    for range in element.children_named("gaspRange") {
+        let behavior: u16 = range.int("rangeGaspBehavior")?;
+        gaspRanges.push(GaspRecord {
+            rangeMaxPPEM: range.int("rangeMaxPPEM")?,
+            rangeGaspBehavior: RangeGaspBehaviorFlags::from_bits(behavior)
+                .ok_or_else(|| error(format!("Invalid rangeGaspBehavior value {}", behavior)))?,
+        });
+    }
+    // The symmetric flags were added in version 1
+    let symmetric = RangeGaspBehaviorFlags::GASP_SYMMETRIC_GRIDFIT
+        | RangeGaspBehaviorFlags::GASP_SYMMETRIC_SMOOTHING;
+    let version = if gaspRanges
+        .iter()
+        .any(|range| range.rangeGaspBehavior.intersects(symmetric))
+    {
+        1
+    } else {
+        0
+    };
+    Ok(gasp {
+        version,
+        gaspRanges,
+    })
+}
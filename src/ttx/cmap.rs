@@ -0,0 +1,115 @@
+use super::xml::{error, Element, XmlWriter};
+use super::GlyphOrder;
+use crate::cmap::{cmap, CmapSubtable};
+use otspec::error::Error as OTSpecError;
+use std::collections::BTreeMap;
+
+fn write_subtable(writer: &mut XmlWriter, subtable: &CmapSubtable, glyph_order: &GlyphOrder) {
+    let element = format!("cmap_format_{}", subtable.format);
+    writer.begin(
+        &element,
+        &[
+            ("platformID", &subtable.platformID),
+            ("platEncID", &subtable.encodingID),
+            ("language", &subtable.languageID),
+        ],
+    );
+    for (code, gid) in &subtable.mapping {
+        writer.simple(
+            "map",
+            &[
+                ("code", &format!("{:#x}", code)),
+                ("name", &glyph_order.name(*gid)),
+            ],
+        );
+    }
+    writer.end(&element);
+}
+
+/// Writes the variation sequences as a format 14 subtable, grouped by selector
+fn write_variation_sequences(writer: &mut XmlWriter, cmap: &cmap, glyph_order: &GlyphOrder) {
+    let mut sequences: Vec<_> = cmap.variationSequences.iter().collect();
+    sequences.sort_by_key(|(&(base, selector), _)| (selector, base));
+    writer.begin("cmap_format_14", &[("platformID", &0), ("platEncID", &5)]);
+    for (&(base, selector), glyph) in sequences {
+        let uv = format!("{:#x}", base);
+        let uvs = format!("{:#x}", selector);
+        match glyph {
+            Some(gid) => writer.simple(
+                "map",
+                &[
+                    ("uv", &uv),
+                    ("uvs", &uvs),
+                    ("name", &glyph_order.name(*gid)),
+                ],
+            ),
+            None => writer.simple("map", &[("uv", &uv), ("uvs", &uvs)]),
+        }
+    }
+    writer.end("cmap_format_14");
+}
+
+pub(super) fn write(writer: &mut XmlWriter, cmap: &cmap, glyph_order: &GlyphOrder) {
+    writer.simple("tableVersion", &[("version", &0)]);
+    // The format 14 subtable goes in its place among the sorted encodings
+    let uvs_position = cmap
+        .subtables
+        .iter()
+        .position(|st| (st.platformID, st.encodingID) > (0, 5))
+        .unwrap_or(cmap.subtables.len());
+    for (index, subtable) in cmap.subtables.iter().enumerate() {
+        if index == uvs_position && !cmap.variationSequences.is_empty() {
+            write_variation_sequences(writer, cmap, glyph_order);
+        }
+        write_subtable(writer, subtable, glyph_order);
+    }
+    if uvs_position == cmap.subtables.len() && !cmap.variationSequences.is_empty() {
+        write_variation_sequences(writer, cmap, glyph_order);
+    }
+}
+
+pub(super) fn read(element: &Element, glyph_order: &GlyphOrder) -> Result<cmap, OTSpecError> {
+    let mut subtables = vec![];
+    let mut variationSequences = BTreeMap::new();
+    for subtable in &element.children {
+        let format = match subtable.name.strip_prefix("cmap_format_") {
+            Some(format) => format,
+            None => continue,
+        };
+        let format: u16 = format
+            .parse()
+            .map_err(|_| error(format!("Unknown cmap subtable <{}>", subtable.name)))?;
+        if format == 14 {
+            for map in subtable.children_named("map") {
+                let glyph = match map.attr("name") {
+                    Some(name) => Some(glyph_order.id(name)?),
+                    None => None,
+                };
+                variationSequences.insert((map.int("uv")?, map.int("uvs")?), glyph);
+            }
+            continue;
+        }
+        // Only these formats can currently be serialized
+        if ![0, 2, 4, 6, 8, 10, 12, 13].contains(&format) {
+            return Err(error(format!(
+                "cmap format {} subtables cannot be compiled",
+                format
+            )));
+        }
+        let mut mapping = BTreeMap::new();
+        for map in subtable.children_named("map") {
+            mapping.insert(map.int("code")?, glyph_order.id(map.required("name")?)?);
+        }
+        subtables.push(CmapSubtable {
+            format,
+            platformID: subtable.int("platformID")?,
+            encodingID: subtable.int("platEncID")?,
+            languageID: subtable.int("language")?,
+            mapping,
+        });
+    }
+    Ok(cmap {
+        subtables,
+        variationSequences,
+    })
+}
@@ -0,0 +1,296 @@
+use super::name::debug_name;
+use super::xml::{error, fixed, parse_float, parse_int, Element, XmlWriter};
+use super::GlyphOrder;
+use crate::fvar::{fvar, InstanceRecord, VariationAxisRecord};
+use crate::glyf::Glyph;
+use crate::gvar::gvar;
+use crate::name::name;
+use crate::otvar::{
+    Delta, TupleIndexFlags, TupleVariation, TupleVariationHeader, TupleVariationStore,
+};
+use otspec::error::Error as OTSpecError;
+use otspec::types::*;
+use std::convert::TryInto;
+
+pub(super) fn write_fvar(writer: &mut XmlWriter, fvar: &fvar, name: Option<&name>) {
+    for axis in &fvar.axes {
+        if let Some(axis_name) = debug_name(name, axis.axisNameID) {
+            writer.blank();
+            writer.comment(axis_name);
+        }
+        writer.begin("Axis", &[]);
+        writer.text_element("AxisTag", &String::from_utf8_lossy(&axis.axisTag));
+        writer.text_element("Flags", &format!("0x{:X}", axis.flags));
+        writer.text_element("MinValue", &fixed(axis.minValue, 16));
+        writer.text_element("DefaultValue", &fixed(axis.defaultValue, 16));
+        writer.text_element("MaxValue", &fixed(axis.maxValue, 16));
+        writer.text_element("AxisNameID", &axis.axisNameID);
+        writer.end("Axis");
+    }
+    for instance in &fvar.instances {
+        if let Some(instance_name) = debug_name(name, instance.subfamilyNameID) {
+            writer.blank();
+            writer.comment(instance_name);
+        }
+        match instance.postscriptNameID {
+            Some(postscript_name_id) => writer.begin(
+                "NamedInstance",
+                &[
+                    ("flags", &"0x0"),
+                    ("postscriptNameID", &postscript_name_id),
+                    ("subfamilyNameID", &instance.subfamilyNameID),
+                ],
+            ),
+            None => writer.begin(
+                "NamedInstance",
+                &[
+                    ("flags", &"0x0"),
+                    ("subfamilyNameID", &instance.subfamilyNameID),
+                ],
+            ),
+        }
+        for (axis, value) in fvar.axes.iter().zip(instance.coordinates.iter()) {
+            writer.simple(
+                "coord",
+                &[
+                    ("axis", &String::from_utf8_lossy(&axis.axisTag)),
+                    ("value", &fixed(*value, 16)),
+                ],
+            );
+        }
+        writer.end("NamedInstance");
+    }
+}
+
+fn parse_tag(value: &str) -> Result<Tag, OTSpecError> {
+    let mut tag = *b"    ";
+    if value.is_empty() || value.len() > 4 {
+        return Err(error(format!("Invalid axis tag '{}'", value)));
+    }
+    for (byte, c) in tag.iter_mut().zip(value.bytes()) {
+        *byte = c;
+    }
+    Ok(tag)
+}
+
+pub(super) fn read_fvar(element: &Element) -> Result<fvar, OTSpecError> {
+    let text = |axis: &Element, name: &str| -> Result<String, OTSpecError> {
+        axis.child(name)
+            .map(|child| child.text.trim().to_string())
+            .ok_or_else(|| error(format!("<Axis> element has no <{}>", name)))
+    };
+    let mut axes = vec![];
+    for axis in element.children_named("Axis") {
+        axes.push(VariationAxisRecord {
+            axisTag: parse_tag(&text(axis, "AxisTag")?)?,
+            flags: parse_int(&text(axis, "Flags")?)?,
+            minValue: parse_float(&text(axis, "MinValue")?)?,
+            defaultValue: parse_float(&text(axis, "DefaultValue")?)?,
+            maxValue: parse_float(&text(axis, "MaxValue")?)?,
+            axisNameID: parse_int(&text(axis, "AxisNameID")?)?,
+        });
+    }
+    let mut instances = vec![];
+    for instance in element.children_named("NamedInstance") {
+        let mut coordinates = vec![];
+        for axis in &axes {
+            let value = instance
+                .children_named("coord")
+                .find(|coord| {
+                    coord.attr("axis").and_then(|a| parse_tag(a).ok()) == Some(axis.axisTag)
+                })
+                .map(|coord| coord.float("value"))
+                .transpose()?
+                .unwrap_or(axis.defaultValue);
+            coordinates.push(value);
+        }
+        instances.push(InstanceRecord {
+            subfamilyNameID: instance.int("subfamilyNameID")?,
+            coordinates,
+            postscriptNameID: match instance.attr("postscriptNameID") {
+                Some(_) => Some(instance.int("postscriptNameID")?),
+                None => None,
+            },
+        });
+    }
+    Ok(fvar { axes, instances })
+}
+
+pub(super) fn write_gvar(
+    writer: &mut XmlWriter,
+    gvar: &gvar,
+    axes: &[VariationAxisRecord],
+    glyph_order: &GlyphOrder,
+) {
+    writer.value("version", &1);
+    writer.value("reserved", &0);
+    let mut variations: Vec<(String, _)> = gvar
+        .variations
+        .iter()
+        .enumerate()
+        .filter_map(|(gid, v)| v.as_ref().map(|v| (glyph_order.name(gid as u16), v)))
+        .collect();
+    variations.sort_by(|a, b| a.0.cmp(&b.0));
+    for (glyph_name, variation) in variations {
+        writer.begin("glyphVariations", &[("glyph", &glyph_name)]);
+        for deltaset in &variation.deltasets {
+            writer.begin("tuple", &[]);
+            let intermediate = deltaset.start != deltaset.peak || deltaset.end != deltaset.peak;
+            for (index, axis) in axes.iter().enumerate() {
+                let peak = deltaset.peak.get(index).copied().unwrap_or(0.0);
+                if peak == 0.0 {
+                    continue;
+                }
+                let tag = String::from_utf8_lossy(&axis.axisTag).into_owned();
+                let start = deltaset.start.get(index).copied().unwrap_or(peak);
+                let end = deltaset.end.get(index).copied().unwrap_or(peak);
+                // fontTools only writes the region when it differs from the
+                // one implied by the peak
+                if intermediate && (start != peak.min(0.0) || end != peak.max(0.0)) {
+                    writer.simple(
+                        "coord",
+                        &[
+                            ("axis", &tag),
+                            ("min", &fixed(start, 14)),
+                            ("value", &fixed(peak, 14)),
+                            ("max", &fixed(end, 14)),
+                        ],
+                    );
+                } else {
+                    writer.simple("coord", &[("axis", &tag), ("value", &fixed(peak, 14))]);
+                }
+            }
+            for (pt, (x, y)) in deltaset.deltas.iter().enumerate() {
+                writer.simple("delta", &[("pt", &pt), ("x", x), ("y", y)]);
+            }
+            writer.end("tuple");
+        }
+        writer.end("glyphVariations");
+    }
+}
+
+/// Parses a `<tuple>` element into a tuple variation of a glyph
+fn read_tuple(
+    tuple: &Element,
+    axes: &[VariationAxisRecord],
+    glyph: &Glyph,
+) -> Result<TupleVariation, OTSpecError> {
+    let mut peak = vec![0.0; axes.len()];
+    let mut start = vec![0.0; axes.len()];
+    let mut end = vec![0.0; axes.len()];
+    let mut intermediate = false;
+    for coord in tuple.children_named("coord") {
+        let tag = parse_tag(coord.required("axis")?)?;
+        let index = axes
+            .iter()
+            .position(|axis| axis.axisTag == tag)
+            .ok_or_else(|| {
+                error(format!(
+                    "Unknown axis '{}' in gvar",
+                    String::from_utf8_lossy(&tag)
+                ))
+            })?;
+        let value = coord.float("value")?;
+        let bound = |name: &str, default: f32| match coord.attr(name) {
+            Some(_) => coord.float(name),
+            None => Ok(default),
+        };
+        peak[index] = value;
+        start[index] = bound("min", value.min(0.0))?;
+        end[index] = bound("max", value.max(0.0))?;
+        intermediate |= start[index] != value.min(0.0) || end[index] != value.max(0.0);
+    }
+    if peak
+        .iter()
+        .chain(&start)
+        .chain(&end)
+        .any(|v| !(-2.0..2.0).contains(v))
+    {
+        return Err(error("gvar coordinates must lie between -2.0 and 2.0"));
+    }
+
+    let (coords, ends) = glyph.gvar_coords_and_ends();
+    let mut deltas: Vec<Option<Delta>> = coords.iter().map(|_| None).collect();
+    for delta in tuple.children_named("delta") {
+        let pt: usize = delta.int("pt")?;
+        let slot = deltas
+            .get_mut(pt)
+            .ok_or_else(|| error(format!("Delta for point {}, which does not exist", pt)))?;
+        *slot = Some(Delta::Delta2D((delta.int("x")?, delta.int("y")?)));
+    }
+
+    let mut flags = TupleIndexFlags::EMBEDDED_PEAK_TUPLE;
+    if intermediate {
+        flags |= TupleIndexFlags::INTERMEDIATE_REGION;
+    }
+    let header = TupleVariationHeader {
+        size: 0,
+        flags,
+        sharedTupleIndex: 0,
+        peakTuple: Some(peak),
+        startTuple: if intermediate { Some(start) } else { None },
+        endTuple: if intermediate { Some(end) } else { None },
+    };
+    // Omitted deltas are inferred, and every point is written out
+    let variation = TupleVariation(header, deltas);
+    let deltas = variation
+        .iup_delta(&coords, &ends)
+        .into_iter()
+        .map(|delta| Some(Delta::Delta2D(delta)))
+        .collect();
+    Ok(TupleVariation(variation.0, deltas))
+}
+
+/// Compiles a `gvar` element to binary, given the font's axes and glyphs
+///
+/// Each tuple carries its own peak, and its deltas for every point.
+pub(super) fn read_gvar(
+    element: &Element,
+    axes: &[VariationAxisRecord],
+    glyphs: &[Glyph],
+    glyph_order: &GlyphOrder,
+) -> Result<Vec<u8>, OTSpecError> {
+    let mut stores: Vec<Option<TupleVariationStore>> = glyphs.iter().map(|_| None).collect();
+    for variations in element.children_named("glyphVariations") {
+        let gid = glyph_order.id(variations.required("glyph")?)? as usize;
+        let glyph = glyphs
+            .get(gid)
+            .ok_or_else(|| error(format!("No glyph for gvar glyph ID {}", gid)))?;
+        let tuples = variations
+            .children_named("tuple")
+            .map(|tuple| read_tuple(tuple, axes, glyph))
+            .collect::<Result<Vec<_>, _>>()?;
+        if !tuples.is_empty() {
+            stores[gid] = Some(TupleVariationStore(tuples));
+        }
+    }
+
+    let glyph_count: u16 = glyphs
+        .len()
+        .try_into()
+        .map_err(|_| error("Too many glyphs for gvar"))?;
+    let offsets_size = 4 * (glyphs.len() + 1);
+    let data_offset = (20 + offsets_size) as u32;
+    let mut offsets: Vec<u8> = vec![];
+    let mut data: Vec<u8> = vec![];
+    for store in &stores {
+        offsets.extend(&(data.len() as u32).to_be_bytes());
+        if let Some(store) = store {
+            data.extend(otspec::ser::to_bytes(store)?);
+        }
+    }
+    offsets.extend(&(data.len() as u32).to_be_bytes());
+
+    let mut output: Vec<u8> = vec![];
+    output.extend(&1_u16.to_be_bytes()); // majorVersion
+    output.extend(&0_u16.to_be_bytes()); // minorVersion
+    output.extend(&(axes.len() as u16).to_be_bytes());
+    output.extend(&0_u16.to_be_bytes()); // sharedTupleCount
+    output.extend(&data_offset.to_be_bytes()); // sharedTuplesOffset
+    output.extend(&glyph_count.to_be_bytes());
+    output.extend(&1_u16.to_be_bytes()); // flags: 32-bit offsets
+    output.extend(&data_offset.to_be_bytes());
+    output.extend(offsets);
+    output.extend(data);
+    Ok(output)
+}
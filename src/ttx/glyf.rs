@@ -0,0 +1,317 @@
+use super::instructions;
+use super::xml::{error, fixed, Element, XmlWriter};
+use super::GlyphOrder;
+use crate::glyf::{glyf, Component, ComponentFlags, Glyph, Point};
+use crate::hmtx::{hmtx, Metric};
+use kurbo::Affine;
+use otspec::error::Error as OTSpecError;
+use std::fmt::Display;
+
+/// The component flags which fontTools keeps; the rest are recomputed on
+/// compilation
+fn preserved_flags() -> ComponentFlags {
+    ComponentFlags::ROUND_XY_TO_GRID
+        | ComponentFlags::USE_MY_METRICS
+        | ComponentFlags::SCALED_COMPONENT_OFFSET
+        | ComponentFlags::UNSCALED_COMPONENT_OFFSET
+        | ComponentFlags::OVERLAP_COMPOUND
+}
+
+fn write_component(writer: &mut XmlWriter, component: &Component, glyph_order: &GlyphOrder) {
+    let [x_scale, scale01, scale10, y_scale, dx, dy] = component.transformation.as_coeffs();
+    let glyph_name = glyph_order.name(component.glyphIndex);
+    let mut attributes: Vec<(&str, String)> = vec![];
+    match component.matchPoints {
+        Some((first, second)) => {
+            attributes.push(("firstPt", first.to_string()));
+            attributes.push(("secondPt", second.to_string()));
+        }
+        None => {
+            attributes.push(("x", (dx as i16).to_string()));
+            attributes.push(("y", (dy as i16).to_string()));
+        }
+    }
+    let scale = |value: f64| fixed(value as f32, 14);
+    if scale01 != 0.0 || scale10 != 0.0 {
+        attributes.push(("scalex", scale(x_scale)));
+        attributes.push(("scale01", scale(scale01)));
+        attributes.push(("scale10", scale(scale10)));
+        attributes.push(("scaley", scale(y_scale)));
+    } else if (x_scale - y_scale).abs() > f64::EPSILON {
+        attributes.push(("scalex", scale(x_scale)));
+        attributes.push(("scaley", scale(y_scale)));
+    } else if (x_scale - 1.0).abs() > f64::EPSILON {
+        attributes.push(("scale", scale(x_scale)));
+    }
+    let flags = (component.flags & preserved_flags()).bits();
+    attributes.push(("flags", format!("{:#x}", flags)));
+
+    let mut all: Vec<(&str, &dyn Display)> = vec![("glyphName", &glyph_name)];
+    all.extend(attributes.iter().map(|(k, v)| (*k, v as &dyn Display)));
+    writer.simple("component", &all);
+}
+
+fn write_glyph(writer: &mut XmlWriter, glyph: &Glyph, name: &str, glyph_order: &GlyphOrder) {
+    if glyph.is_empty() {
+        writer.simple_with_comment("TTGlyph", &[("name", &name)], "contains no outline data");
+        return;
+    }
+    writer.begin(
+        "TTGlyph",
+        &[
+            ("name", &name),
+            ("xMin", &glyph.xMin),
+            ("yMin", &glyph.yMin),
+            ("xMax", &glyph.xMax),
+            ("yMax", &glyph.yMax),
+        ],
+    );
+    for (index, contour) in glyph.contours.iter().enumerate() {
+        writer.begin("contour", &[]);
+        for (pt_index, point) in contour.iter().enumerate() {
+            let on = if point.on_curve { 1 } else { 0 };
+            if glyph.overlap && index == 0 && pt_index == 0 {
+                writer.simple(
+                    "pt",
+                    &[
+                        ("x", &point.x),
+                        ("y", &point.y),
+                        ("on", &on),
+                        ("overlap", &1),
+                    ],
+                );
+            } else {
+                writer.simple("pt", &[("x", &point.x), ("y", &point.y), ("on", &on)]);
+            }
+        }
+        writer.end("contour");
+    }
+    for component in &glyph.components {
+        write_component(writer, component, glyph_order);
+    }
+    if glyph.instructions.is_empty() {
+        // Simple glyphs always have an instruction length, even if it's zero
+        if !glyph.has_components() {
+            writer.simple("instructions", &[]);
+        }
+    } else {
+        writer.begin("instructions", &[]);
+        instructions::write(writer, &glyph.instructions);
+        writer.end("instructions");
+    }
+    writer.end("TTGlyph");
+}
+
+pub(super) fn write(writer: &mut XmlWriter, glyf: &glyf, glyph_order: &GlyphOrder) {
+    writer.blank();
+    writer.comment(
+        "The xMin, yMin, xMax and yMax values\n\
+         will be recalculated by the compiler.",
+    );
+    writer.blank();
+    for (gid, glyph) in glyf.glyphs.iter().enumerate() {
+        write_glyph(writer, glyph, &glyph_order.name(gid as u16), glyph_order);
+        writer.blank();
+    }
+}
+
+fn read_component(element: &Element, glyph_order: &GlyphOrder) -> Result<Component, OTSpecError> {
+    let glyphIndex = glyph_order.id(element.required("glyphName")?)?;
+    let (matchPoints, dx, dy) = if element.attr("firstPt").is_some() {
+        let points = (element.int("firstPt")?, element.int("secondPt")?);
+        (Some(points), 0.0, 0.0)
+    } else {
+        (
+            None,
+            element.int::<i16>("x")? as f64,
+            element.int::<i16>("y")? as f64,
+        )
+    };
+    let scale = |name: &str| -> Result<Option<f64>, OTSpecError> {
+        match element.attr(name) {
+            Some(_) => element.float(name).map(|v| Some(v as f64)),
+            None => Ok(None),
+        }
+    };
+    let (x_scale, scale01, scale10, y_scale) = if let Some(scale) = scale("scale")? {
+        (scale, 0.0, 0.0, scale)
+    } else {
+        (
+            scale("scalex")?.unwrap_or(1.0),
+            scale("scale01")?.unwrap_or(0.0),
+            scale("scale10")?.unwrap_or(0.0),
+            scale("scaley")?.unwrap_or(1.0),
+        )
+    };
+    let flags: u16 = match element.attr("flags") {
+        Some(_) => element.int("flags")?,
+        None => 0,
+    };
+    Ok(Component {
+        glyphIndex,
+        transformation: Affine::new([x_scale, scale01, scale10, y_scale, dx, dy]),
+        matchPoints,
+        flags: ComponentFlags::from_bits_truncate(flags) & preserved_flags(),
+    })
+}
+
+fn read_glyph(element: &Element, glyph_order: &GlyphOrder) -> Result<Glyph, OTSpecError> {
+    let mut glyph = Glyph {
+        xMin: 0,
+        xMax: 0,
+        yMin: 0,
+        yMax: 0,
+        contours: vec![],
+        instructions: vec![],
+        components: vec![],
+        overlap: false,
+    };
+    for child in &element.children {
+        match child.name.as_str() {
+            "contour" => {
+                let mut contour = vec![];
+                for point in child.children_named("pt") {
+                    if point.attr("overlap") == Some("1") {
+                        glyph.overlap = true;
+                    }
+                    contour.push(Point {
+                        x: point.int("x")?,
+                        y: point.int("y")?,
+                        on_curve: point.int::<u8>("on")? & 1 == 1,
+                    });
+                }
+                glyph.contours.push(contour);
+            }
+            "component" => glyph.components.push(read_component(child, glyph_order)?),
+            "instructions" => glyph.instructions = instructions::read(child)?,
+            _ => {}
+        }
+    }
+    if !glyph.contours.is_empty() && !glyph.components.is_empty() {
+        return Err(error(format!(
+            "Glyph {} has both contours and components",
+            element.required("name")?
+        )));
+    }
+    glyph.overlap |= glyph
+        .components
+        .iter()
+        .any(|c| c.flags.contains(ComponentFlags::OVERLAP_COMPOUND));
+    if glyph.is_empty() {
+        return Ok(glyph);
+    }
+    if element.attr("xMin").is_some() {
+        glyph.xMin = element.int("xMin")?;
+        glyph.yMin = element.int("yMin")?;
+        glyph.xMax = element.int("xMax")?;
+        glyph.yMax = element.int("yMax")?;
+    } else if !glyph.has_components() {
+        let points = glyph.contours.iter().flatten();
+        glyph.xMin = points.clone().map(|pt| pt.x).min().unwrap_or(0);
+        glyph.xMax = points.clone().map(|pt| pt.x).max().unwrap_or(0);
+        glyph.yMin = points.clone().map(|pt| pt.y).min().unwrap_or(0);
+        glyph.yMax = points.map(|pt| pt.y).max().unwrap_or(0);
+    }
+    Ok(glyph)
+}
+
+/// Computes the bounds of composite glyphs which were given without them
+fn compute_composite_bounds(glyphs: &mut [Glyph], missing: &[usize]) {
+    // Components may themselves be composites without bounds, so keep
+    // going until nothing changes
+    for _ in 0..missing.len() {
+        let mut changed = false;
+        for &gid in missing {
+            let bounds = glyphs[gid]
+                .components
+                .iter()
+                .filter_map(|c| {
+                    glyphs
+                        .get(c.glyphIndex as usize)
+                        .filter(|g| !g.is_empty())
+                        .map(|g| c.transformation.transform_rect_bbox(g.bounds_rect()))
+                })
+                .reduce(|a, b| a.union(b));
+            if let Some(bounds) = bounds {
+                if bounds != glyphs[gid].bounds_rect() {
+                    glyphs[gid].set_bounds_rect(bounds);
+                    changed = true;
+                }
+            }
+        }
+        if !changed {
+            break;
+        }
+    }
+}
+
+pub(super) fn read(element: &Element, glyph_order: &GlyphOrder) -> Result<glyf, OTSpecError> {
+    let mut glyphs: Vec<Option<Glyph>> = glyph_order.names.iter().map(|_| None).collect();
+    let mut missing_bounds = vec![];
+    for glyph in element.children_named("TTGlyph") {
+        let gid = glyph_order.id(glyph.required("name")?)? as usize;
+        let parsed = read_glyph(glyph, glyph_order)?;
+        if parsed.has_components() && glyph.attr("xMin").is_none() {
+            missing_bounds.push(gid);
+        }
+        glyphs[gid] = Some(parsed);
+    }
+    let mut glyphs = glyphs
+        .into_iter()
+        .enumerate()
+        .map(|(gid, glyph)| {
+            glyph.ok_or_else(|| {
+                error(format!(
+                    "No <TTGlyph> for glyph {}",
+                    glyph_order.name(gid as u16)
+                ))
+            })
+        })
+        .collect::<Result<Vec<Glyph>, OTSpecError>>()?;
+    compute_composite_bounds(&mut glyphs, &missing_bounds);
+    Ok(glyf { glyphs })
+}
+
+pub(super) fn write_hmtx(writer: &mut XmlWriter, hmtx: &hmtx, glyph_order: &GlyphOrder) {
+    let mut metrics: Vec<(String, &Metric)> = hmtx
+        .metrics
+        .iter()
+        .enumerate()
+        .map(|(gid, metric)| (glyph_order.name(gid as u16), metric))
+        .collect();
+    metrics.sort_by(|a, b| a.0.cmp(&b.0));
+    for (name, metric) in metrics {
+        writer.simple(
+            "mtx",
+            &[
+                ("name", &name),
+                ("width", &metric.advanceWidth),
+                ("lsb", &metric.lsb),
+            ],
+        );
+    }
+}
+
+pub(super) fn read_hmtx(element: &Element, glyph_order: &GlyphOrder) -> Result<hmtx, OTSpecError> {
+    let mut metrics: Vec<Option<Metric>> = glyph_order.names.iter().map(|_| None).collect();
+    for mtx in element.children_named("mtx") {
+        let gid = glyph_order.id(mtx.required("name")?)? as usize;
+        metrics[gid] = Some(Metric {
+            advanceWidth: mtx.int("width")?,
+            lsb: mtx.int("lsb")?,
+        });
+    }
+    let metrics = metrics
+        .into_iter()
+        .enumerate()
+        .map(|(gid, metric)| {
+            metric.ok_or_else(|| {
+                error(format!(
+                    "No metrics for glyph {}",
+                    glyph_order.name(gid as u16)
+                ))
+            })
+        })
+        .collect::<Result<Vec<Metric>, OTSpecError>>()?;
+    Ok(hmtx { metrics })
+}
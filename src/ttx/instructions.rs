@@ -0,0 +1,451 @@
+use super::xml::{error, Element, XmlWriter};
+use otspec::error::Error as OTSpecError;
+use std::convert::TryFrom;
+
+/// A TrueType instruction: its first opcode, mnemonic, number of flag bits
+/// encoded in the opcode, and the description fontTools writes alongside it
+type Instruction = (u8, &'static str, u8, &'static str);
+
+const INSTRUCTIONS: &[Instruction] = &[
+    (0x7f, "AA", 0, "AdjustAngle"),
+    (0x64, "ABS", 0, "Absolute"),
+    (0x60, "ADD", 0, "Add"),
+    (0x27, "ALIGNPTS", 0, "AlignPts"),
+    (0x3c, "ALIGNRP", 0, "AlignRelativePt"),
+    (0x5a, "AND", 0, "LogicalAnd"),
+    (0x2b, "CALL", 0, "CallFunction"),
+    (0x67, "CEILING", 0, "Ceil"),
+    (0x25, "CINDEX", 0, "CopyXToTopStack"),
+    (0x22, "CLEAR", 0, "ClearStack"),
+    (0x4f, "DEBUG", 0, "DebugCall"),
+    (0x73, "DELTAC1", 0, "DeltaExceptionC1"),
+    (0x74, "DELTAC2", 0, "DeltaExceptionC2"),
+    (0x75, "DELTAC3", 0, "DeltaExceptionC3"),
+    (0x5d, "DELTAP1", 0, "DeltaExceptionP1"),
+    (0x71, "DELTAP2", 0, "DeltaExceptionP2"),
+    (0x72, "DELTAP3", 0, "DeltaExceptionP3"),
+    (0x24, "DEPTH", 0, "GetDepthStack"),
+    (0x62, "DIV", 0, "Divide"),
+    (0x20, "DUP", 0, "DuplicateTopStack"),
+    (0x59, "EIF", 0, "EndIf"),
+    (0x1b, "ELSE", 0, "Else"),
+    (0x2d, "ENDF", 0, "EndFunctionDefinition"),
+    (0x54, "EQ", 0, "Equal"),
+    (0x57, "EVEN", 0, "Even"),
+    (0x2c, "FDEF", 0, "FunctionDefinition"),
+    (0x4e, "FLIPOFF", 0, "SetAutoFlipOff"),
+    (0x4d, "FLIPON", 0, "SetAutoFlipOn"),
+    (0x80, "FLIPPT", 0, "FlipPoint"),
+    (0x82, "FLIPRGOFF", 0, "FlipRangeOff"),
+    (0x81, "FLIPRGON", 0, "FlipRangeOn"),
+    (0x66, "FLOOR", 0, "Floor"),
+    (0x46, "GC", 1, "GetCoordOnPVector"),
+    (0x88, "GETINFO", 0, "GetInfo"),
+    (0x91, "GETVARIATION", 0, "GetVariation"),
+    (0x0d, "GFV", 0, "GetFVector"),
+    (0x0c, "GPV", 0, "GetPVector"),
+    (0x52, "GT", 0, "GreaterThan"),
+    (0x53, "GTEQ", 0, "GreaterThanOrEqual"),
+    (0x89, "IDEF", 0, "InstructionDefinition"),
+    (0x58, "IF", 0, "If"),
+    (0x8e, "INSTCTRL", 0, "SetInstrExecControl"),
+    (0x39, "IP", 0, "InterpolatePts"),
+    (0x0f, "ISECT", 0, "MovePtToIntersect"),
+    (0x30, "IUP", 1, "InterpolateUntPts"),
+    (0x1c, "JMPR", 0, "Jump"),
+    (0x79, "JROF", 0, "JumpRelativeOnFalse"),
+    (0x78, "JROT", 0, "JumpRelativeOnTrue"),
+    (0x2a, "LOOPCALL", 0, "LoopAndCallFunction"),
+    (0x50, "LT", 0, "LessThan"),
+    (0x51, "LTEQ", 0, "LessThenOrEqual"),
+    (0x8b, "MAX", 0, "Maximum"),
+    (0x49, "MD", 1, "MeasureDistance"),
+    (0x2e, "MDAP", 1, "MoveDirectAbsPt"),
+    (0xc0, "MDRP", 5, "MoveDirectRelPt"),
+    (0x3e, "MIAP", 1, "MoveIndirectAbsPt"),
+    (0x8c, "MIN", 0, "Minimum"),
+    (0x26, "MINDEX", 0, "MoveXToTopStack"),
+    (0xe0, "MIRP", 5, "MoveIndirectRelPt"),
+    (0x4b, "MPPEM", 0, "MeasurePixelPerEm"),
+    (0x4c, "MPS", 0, "MeasurePointSize"),
+    (0x3a, "MSIRP", 1, "MoveStackIndirRelPt"),
+    (0x63, "MUL", 0, "Multiply"),
+    (0x65, "NEG", 0, "Negate"),
+    (0x55, "NEQ", 0, "NotEqual"),
+    (0x5c, "NOT", 0, "LogicalNot"),
+    (0x6c, "NROUND", 2, "NoRound"),
+    (0x56, "ODD", 0, "Odd"),
+    (0x5b, "OR", 0, "LogicalOr"),
+    (0x21, "POP", 0, "PopTopStack"),
+    (0x45, "RCVT", 0, "ReadCVT"),
+    (0x7d, "RDTG", 0, "RoundDownToGrid"),
+    (0x7a, "ROFF", 0, "RoundOff"),
+    (0x8a, "ROLL", 0, "RollTopThreeStack"),
+    (0x68, "ROUND", 2, "Round"),
+    (0x43, "RS", 0, "ReadStore"),
+    (0x3d, "RTDG", 0, "RoundToDoubleGrid"),
+    (0x18, "RTG", 0, "RoundToGrid"),
+    (0x19, "RTHG", 0, "RoundToHalfGrid"),
+    (0x7c, "RUTG", 0, "RoundUpToGrid"),
+    (0x77, "S45ROUND", 0, "SuperRound45Degrees"),
+    (0x7e, "SANGW", 0, "SetAngleWeight"),
+    (0x85, "SCANCTRL", 0, "ScanConversionControl"),
+    (0x8d, "SCANTYPE", 0, "ScanType"),
+    (0x48, "SCFS", 0, "SetCoordFromStackFP"),
+    (0x1d, "SCVTCI", 0, "SetCVTCutIn"),
+    (0x5e, "SDB", 0, "SetDeltaBaseInGState"),
+    (0x86, "SDPVTL", 1, "SetDualPVectorToLine"),
+    (0x5f, "SDS", 0, "SetDeltaShiftInGState"),
+    (0x0b, "SFVFS", 0, "SetFVectorFromStack"),
+    (0x04, "SFVTCA", 1, "SetFVectorToAxis"),
+    (0x08, "SFVTL", 1, "SetFVectorToLine"),
+    (0x0e, "SFVTPV", 0, "SetFVectorToPVector"),
+    (0x34, "SHC", 1, "ShiftContourByLastPt"),
+    (0x32, "SHP", 1, "ShiftPointByLastPoint"),
+    (0x38, "SHPIX", 0, "ShiftZoneByPixel"),
+    (0x36, "SHZ", 1, "ShiftZoneByLastPoint"),
+    (0x17, "SLOOP", 0, "SetLoopVariable"),
+    (0x1a, "SMD", 0, "SetMinimumDistance"),
+    (0x0a, "SPVFS", 0, "SetPVectorFromStack"),
+    (0x02, "SPVTCA", 1, "SetPVectorToAxis"),
+    (0x06, "SPVTL", 1, "SetPVectorToLine"),
+    (0x76, "SROUND", 0, "SuperRound"),
+    (0x10, "SRP0", 0, "SetRefPoint0"),
+    (0x11, "SRP1", 0, "SetRefPoint1"),
+    (0x12, "SRP2", 0, "SetRefPoint2"),
+    (0x1f, "SSW", 0, "SetSingleWidth"),
+    (0x1e, "SSWCI", 0, "SetSingleWidthCutIn"),
+    (0x61, "SUB", 0, "Subtract"),
+    (0x00, "SVTCA", 1, "SetFPVectorToAxis"),
+    (0x23, "SWAP", 0, "SwapTopStack"),
+    (0x13, "SZP0", 0, "SetZonePointer0"),
+    (0x14, "SZP1", 0, "SetZonePointer1"),
+    (0x15, "SZP2", 0, "SetZonePointer2"),
+    (0x16, "SZPS", 0, "SetZonePointerS"),
+    (0x29, "UTP", 0, "UnTouchPt"),
+    (0x70, "WCVTF", 0, "WriteCVTInFUnits"),
+    (0x44, "WCVTP", 0, "WriteCVTInPixels"),
+    (0x42, "WS", 0, "WriteStore"),
+];
+
+const NPUSHB: u8 = 0x40;
+const NPUSHW: u8 = 0x41;
+const PUSHB: u8 = 0xb0;
+const PUSHW: u8 = 0xb8;
+
+/// Push values are written this many to a line
+const VALUES_PER_LINE: usize = 25;
+
+fn lookup(opcode: u8) -> Option<&'static Instruction> {
+    INSTRUCTIONS
+        .iter()
+        .find(|(first, _, bits, _)| opcode >= *first && (opcode - *first) >> *bits == 0)
+}
+
+/// A line of disassembled code, with any values it pushes
+struct Line {
+    text: String,
+    values: Vec<i32>,
+}
+
+/// Disassembles TrueType bytecode into the lines fontTools writes
+///
+/// Returns None if the bytecode ends in the middle of an instruction.
+fn disassemble(bytecode: &[u8]) -> Option<Vec<Line>> {
+    let mut lines = vec![];
+    let mut pos = 0;
+    while pos < bytecode.len() {
+        let opcode = bytecode[pos];
+        pos += 1;
+        let (mnemonic, count, words) = match opcode {
+            NPUSHB | NPUSHW => {
+                let count = *bytecode.get(pos)? as usize;
+                pos += 1;
+                let mnemonic = if opcode == NPUSHB { "NPUSHB" } else { "NPUSHW" };
+                (mnemonic, count, opcode == NPUSHW)
+            }
+            PUSHB..=0xb7 => ("PUSHB", (opcode - PUSHB) as usize + 1, false),
+            PUSHW..=0xbf => ("PUSHW", (opcode - PUSHW) as usize + 1, true),
+            _ => {
+                let text = match lookup(opcode) {
+                    Some((first, mnemonic, 0, description)) if *first == opcode => {
+                        format!("{}[ ]\t/* {} */", mnemonic, description)
+                    }
+                    Some((first, mnemonic, bits, description)) => format!(
+                        "{}[{:0width$b}]\t/* {} */",
+                        mnemonic,
+                        opcode - first,
+                        description,
+                        width = *bits as usize
+                    ),
+                    None => format!("INSTR{}[ ]", opcode),
+                };
+                lines.push(Line {
+                    text,
+                    values: vec![],
+                });
+                continue;
+            }
+        };
+        let size = if words { 2 } else { 1 };
+        let data = bytecode.get(pos..pos + count * size)?;
+        pos += count * size;
+        let values: Vec<i32> = if words {
+            data.chunks(2)
+                .map(|pair| i16::from_be_bytes([pair[0], pair[1]]) as i32)
+                .collect()
+        } else {
+            data.iter().map(|b| *b as i32).collect()
+        };
+        let text = if count == 1 {
+            format!("{}[ ]\t/* 1 value pushed */", mnemonic)
+        } else {
+            format!("{}[ ]\t/* {} values pushed */", mnemonic, count)
+        };
+        lines.push(Line { text, values });
+    }
+    Some(lines)
+}
+
+/// Writes a glyph program as fontTools does
+///
+/// The code is disassembled into an `<assembly>` element, indented within
+/// function definitions and conditionals. If the bytecode can't be
+/// disassembled, it is written in hex in a `<bytecode>` element instead.
+pub(crate) fn write(writer: &mut XmlWriter, bytecode: &[u8]) {
+    let lines = match disassemble(bytecode) {
+        Some(lines) => lines,
+        None => {
+            log::warn!("Could not disassemble instructions, writing bytecode");
+            writer.begin("bytecode", &[]);
+            writer.hex(bytecode);
+            writer.end("bytecode");
+            return;
+        }
+    };
+    writer.begin("assembly", &[]);
+    let mut depth: usize = 0;
+    for line in lines {
+        if line.text.starts_with("ELSE")
+            || line.text.starts_with("ENDF")
+            || line.text.starts_with("EIF")
+        {
+            depth = depth.saturating_sub(1);
+        }
+        let indent = "  ".repeat(depth);
+        writer.line(&format!("{}{}", indent, line.text));
+        for values in line.values.chunks(VALUES_PER_LINE) {
+            let values: Vec<String> = values.iter().map(|v| v.to_string()).collect();
+            writer.line(&format!("{}{}", indent, values.join(" ")));
+        }
+        if line.text.starts_with("FDEF")
+            || line.text.starts_with("IF")
+            || line.text.starts_with("ELSE")
+        {
+            depth += 1;
+        }
+    }
+    writer.end("assembly");
+}
+
+/// Removes `/* ... */` comments from assembly code
+fn strip_comments(text: &str) -> Result<String, OTSpecError> {
+    let mut output = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find("/*") {
+        output.push_str(&rest[..start]);
+        output.push(' ');
+        let end = rest[start..]
+            .find("*/")
+            .ok_or_else(|| error("Unterminated comment in assembly"))?;
+        rest = &rest[start + end + 2..];
+    }
+    output.push_str(rest);
+    Ok(output)
+}
+
+/// Encodes values pushed with the generic `PUSH[ ]` instruction
+fn push(bytecode: &mut Vec<u8>, values: &[i32]) {
+    for chunk in values.chunks(255) {
+        let words = chunk.iter().any(|v| !(0..=255).contains(v));
+        let count = chunk.len() as u8;
+        match (words, chunk.len() <= 8) {
+            (false, true) => bytecode.push(PUSHB + count - 1),
+            (true, true) => bytecode.push(PUSHW + count - 1),
+            (false, false) => bytecode.extend(&[NPUSHB, count]),
+            (true, false) => bytecode.extend(&[NPUSHW, count]),
+        }
+        for value in chunk {
+            if words {
+                bytecode.extend(&(*value as i16).to_be_bytes());
+            } else {
+                bytecode.push(*value as u8);
+            }
+        }
+    }
+}
+
+/// Assembles TrueType code written in fontTools' assembly syntax
+fn assemble(text: &str) -> Result<Vec<u8>, OTSpecError> {
+    let text = strip_comments(text)?;
+    let mut bytecode = vec![];
+    let mut tokens = text.split_whitespace().peekable();
+    while let Some(token) = tokens.next() {
+        // Flags are written in brackets after the mnemonic, and "[ ]" is
+        // split over two tokens
+        let (mnemonic, mut flags) = match token.find('[') {
+            Some(bracket) => (&token[..bracket], token[bracket + 1..].to_string()),
+            None => return Err(error(format!("Expected an instruction, found '{}'", token))),
+        };
+        while !flags.ends_with(']') {
+            let next = tokens
+                .next()
+                .ok_or_else(|| error(format!("Unterminated flags for {}", mnemonic)))?;
+            flags.push_str(next);
+        }
+        flags.pop();
+        let flags = flags.trim();
+
+        if mnemonic.starts_with("PUSH") || mnemonic.starts_with("NPUSH") {
+            let mut values = vec![];
+            while let Some(value) = tokens.peek().and_then(|t| t.parse::<i32>().ok()) {
+                values.push(value);
+                tokens.next();
+            }
+            if values.is_empty() {
+                return Err(error(format!("{} pushes no values", mnemonic)));
+            }
+            let count = values.len();
+            let too_many = || error(format!("Too many values for {}", mnemonic));
+            match mnemonic {
+                "PUSH" => {
+                    push(&mut bytecode, &values);
+                    continue;
+                }
+                "PUSHB" | "PUSHW" if count > 8 => return Err(too_many()),
+                "NPUSHB" | "NPUSHW" if count > 255 => return Err(too_many()),
+                "PUSHB" => bytecode.push(PUSHB + count as u8 - 1),
+                "PUSHW" => bytecode.push(PUSHW + count as u8 - 1),
+                "NPUSHB" => bytecode.extend(&[NPUSHB, count as u8]),
+                "NPUSHW" => bytecode.extend(&[NPUSHW, count as u8]),
+                _ => return Err(error(format!("Unknown instruction {}", mnemonic))),
+            }
+            for value in values {
+                if mnemonic.ends_with('W') {
+                    let word = i16::try_from(value).map_err(|_| {
+                        error(format!("{} is out of range for {}", value, mnemonic))
+                    })?;
+                    bytecode.extend(&word.to_be_bytes());
+                } else {
+                    let byte = u8::try_from(value).map_err(|_| {
+                        error(format!("{} is out of range for {}", value, mnemonic))
+                    })?;
+                    bytecode.push(byte);
+                }
+            }
+            continue;
+        }
+
+        if let Some(opcode) = mnemonic.strip_prefix("INSTR") {
+            bytecode.push(
+                opcode
+                    .parse()
+                    .map_err(|_| error(format!("Unknown instruction {}", mnemonic)))?,
+            );
+            continue;
+        }
+        let (first, _, bits, _) = INSTRUCTIONS
+            .iter()
+            .find(|(_, name, _, _)| *name == mnemonic)
+            .ok_or_else(|| error(format!("Unknown instruction {}", mnemonic)))?;
+        let offset = if flags.is_empty() {
+            0
+        } else {
+            u8::from_str_radix(flags, 2)
+                .ok()
+                .filter(|offset| offset >> bits == 0)
+                .ok_or_else(|| error(format!("Invalid flags '{}' for {}", flags, mnemonic)))?
+        };
+        bytecode.push(first + offset);
+    }
+    Ok(bytecode)
+}
+
+/// Reads a glyph program from an `<instructions>` element
+pub(crate) fn read(element: &Element) -> Result<Vec<u8>, OTSpecError> {
+    if let Some(assembly) = element.child("assembly") {
+        assemble(&assembly.text)
+    } else if let Some(bytecode) = element.child("bytecode") {
+        super::xml::parse_hex(&bytecode.text)
+    } else {
+        Ok(vec![])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{assemble, disassemble};
+
+    #[test]
+    fn ttx_instructions_roundtrip() {
+        let bytecode = vec![
+            0xb1, 0x01, 0x00, // PUSHB[ ] 1 0
+            0x2c, // FDEF
+            0x58, // IF
+            0xb8, 0xff, 0xfe, // PUSHW[ ] -2
+            0x1b, // ELSE
+            0xd5, // MDRP[10101]
+            0x59, // EIF
+            0x2d, // ENDF
+            0x00, // SVTCA[0]
+            0x83, // Unknown opcode
+        ];
+        let lines = disassemble(&bytecode).unwrap();
+        let text: Vec<&str> = lines.iter().map(|l| l.text.as_str()).collect();
+        assert_eq!(
+            text,
+            vec![
+                "PUSHB[ ]\t/* 2 values pushed */",
+                "FDEF[ ]\t/* FunctionDefinition */",
+                "IF[ ]\t/* If */",
+                "PUSHW[ ]\t/* 1 value pushed */",
+                "ELSE[ ]\t/* Else */",
+                "MDRP[10101]\t/* MoveDirectRelPt */",
+                "EIF[ ]\t/* EndIf */",
+                "ENDF[ ]\t/* EndFunctionDefinition */",
+                "SVTCA[0]\t/* SetFPVectorToAxis */",
+                "INSTR131[ ]",
+            ]
+        );
+        assert_eq!(lines[0].values, vec![1, 0]);
+        assert_eq!(lines[3].values, vec![-2]);
+
+        let mut assembly = String::new();
+        for line in &lines {
+            assembly.push_str(&line.text);
+            assembly.push('\n');
+            for value in &line.values {
+                assembly.push_str(&format!("{} ", value));
+            }
+        }
+        assert_eq!(assemble(&assembly).unwrap(), bytecode);
+
+        // Truncated push data can't be disassembled
+        assert!(disassemble(&[0x40, 0x03, 0x01]).is_none());
+    }
+
+    #[test]
+    fn ttx_instructions_generic_push() {
+        assert_eq!(assemble("PUSH[ ] 1 2 3").unwrap(), vec![0xb2, 1, 2, 3]);
+        assert_eq!(
+            assemble("PUSH[ ] 1 -300").unwrap(),
+            vec![0xb9, 0, 1, 0xfe, 0xd4]
+        );
+        let many: Vec<String> = (0..10).map(|v| v.to_string()).collect();
+        let bytecode = assemble(&format!("PUSH[ ] {} IUP[1]", many.join(" "))).unwrap();
+        assert_eq!(&bytecode[0..2], &[0x40, 10]);
+        assert_eq!(bytecode[12], 0x31);
+        assert!(assemble("MDRP[111111]").is_err());
+        assert!(assemble("FROB[ ]").is_err());
+    }
+}
@@ -0,0 +1,43 @@
+use super::xml::{Element, XmlWriter};
+use crate::name::{name, NameRecord};
+use otspec::error::Error as OTSpecError;
+
+pub(super) fn write(writer: &mut XmlWriter, name: &name) {
+    let mut records: Vec<&NameRecord> = name.records.iter().collect();
+    records.sort_by_key(|r| (r.platformID, r.encodingID, r.languageID, r.nameID));
+    for record in records {
+        writer.begin(
+            "namerecord",
+            &[
+                ("nameID", &record.nameID),
+                ("platformID", &record.platformID),
+                ("platEncID", &record.encodingID),
+                ("langID", &format!("{:#x}", record.languageID)),
+            ],
+        );
+        writer.text(&record.string);
+        writer.end("namerecord");
+    }
+}
+
+pub(super) fn read(element: &Element) -> Result<name, OTSpecError> {
+    let mut records = vec![];
+    for record in element.children_named("namerecord") {
+        records.push(NameRecord {
+            platformID: record.int("platformID")?,
+            encodingID: record.int("platEncID")?,
+            languageID: record.int("langID")?,
+            nameID: record.int("nameID")?,
+            string: record.text.trim().to_string(),
+            languageTag: None,
+        });
+    }
+    Ok(name { records })
+}
+
+/// Finds a name to show alongside a name ID, as fontTools does
+///
+/// English names are preferred.
+pub(super) fn debug_name(name: Option<&name>, name_id: u16) -> Option<&str> {
+    name?.get(name_id, &[])
+}
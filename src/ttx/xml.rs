@@ -0,0 +1,364 @@
+use otspec::error::Error as OTSpecError;
+use std::convert::TryFrom;
+use std::fmt::Display;
+use xml::reader::{ParserConfig, XmlEvent};
+
+/// An element of a parsed TTX document
+#[derive(Debug, Default)]
+pub(crate) struct Element {
+    /// The element's name
+    pub(crate) name: String,
+    /// The element's attributes, in document order
+    pub(crate) attributes: Vec<(String, String)>,
+    /// The child elements
+    pub(crate) children: Vec<Element>,
+    /// The character data directly within the element, without comments
+    pub(crate) text: String,
+}
+
+/// Builds an error about the TTX input
+pub(crate) fn error<T: Into<String>>(message: T) -> OTSpecError {
+    OTSpecError::Message(message.into())
+}
+
+impl Element {
+    /// Returns the value of an attribute, if present
+    pub(crate) fn attr(&self, name: &str) -> Option<&str> {
+        self.attributes
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// Returns the value of an attribute, which must be present
+    pub(crate) fn required(&self, name: &str) -> Result<&str, OTSpecError> {
+        self.attr(name).ok_or_else(|| {
+            error(format!(
+                "<{}> element has no '{}' attribute",
+                self.name, name
+            ))
+        })
+    }
+
+    /// Parses an integer attribute, which must be present
+    pub(crate) fn int<T: TryFrom<i64>>(&self, name: &str) -> Result<T, OTSpecError> {
+        parse_int(self.required(name)?)
+    }
+
+    /// Parses a floating point attribute, which must be present
+    pub(crate) fn float(&self, name: &str) -> Result<f32, OTSpecError> {
+        parse_float(self.required(name)?)
+    }
+
+    /// Returns the first child element with the given name
+    pub(crate) fn child(&self, name: &str) -> Option<&Element> {
+        self.children.iter().find(|child| child.name == name)
+    }
+
+    /// Iterates over the child elements with the given name
+    pub(crate) fn children_named<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a Element> {
+        self.children.iter().filter(move |child| child.name == name)
+    }
+
+    /// Returns the `value` attribute of a child element, which must be present
+    ///
+    /// This is how fontTools writes the fields of most tables, as
+    /// `<unitsPerEm value="1000"/>`.
+    pub(crate) fn value(&self, name: &str) -> Result<&str, OTSpecError> {
+        self.child(name)
+            .ok_or_else(|| error(format!("<{}> element has no <{}>", self.name, name)))?
+            .required("value")
+    }
+
+    /// Parses the `value` attribute of a child element as an integer
+    pub(crate) fn int_value<T: TryFrom<i64>>(&self, name: &str) -> Result<T, OTSpecError> {
+        parse_int(self.value(name)?)
+    }
+
+    /// Parses the `value` attribute of a child element as a number written
+    /// in binary
+    pub(crate) fn binary_value<T: TryFrom<i64>>(&self, name: &str) -> Result<T, OTSpecError> {
+        parse_binary(self.value(name)?)
+    }
+
+    /// Parses the `value` attribute of a child element as a floating point
+    /// number
+    pub(crate) fn float_value(&self, name: &str) -> Result<f32, OTSpecError> {
+        parse_float(self.value(name)?)
+    }
+}
+
+/// Parses a TTX document into a tree of elements
+pub(crate) fn parse(input: &str) -> Result<Element, OTSpecError> {
+    let reader = ParserConfig::new()
+        .trim_whitespace(false)
+        .whitespace_to_characters(true)
+        .cdata_to_characters(true)
+        .ignore_comments(true)
+        .coalesce_characters(true)
+        .create_reader(input.as_bytes());
+    let mut stack: Vec<Element> = vec![];
+    for event in reader {
+        match event.map_err(|e| error(format!("Invalid XML: {}", e)))? {
+            XmlEvent::StartElement {
+                name, attributes, ..
+            } => stack.push(Element {
+                name: name.local_name,
+                attributes: attributes
+                    .into_iter()
+                    .map(|attribute| (attribute.name.local_name, attribute.value))
+                    .collect(),
+                ..Default::default()
+            }),
+            XmlEvent::EndElement { .. } => {
+                let element = stack.pop().ok_or_else(|| error("Unbalanced XML"))?;
+                match stack.last_mut() {
+                    Some(parent) => parent.children.push(element),
+                    None => return Ok(element),
+                }
+            }
+            XmlEvent::Characters(text) => {
+                if let Some(element) = stack.last_mut() {
+                    element.text.push_str(&text);
+                }
+            }
+            _ => {}
+        }
+    }
+    Err(error("TTX document has no root element"))
+}
+
+/// Parses an integer written in decimal or, with a `0x` prefix, in hex
+pub(crate) fn parse_int<T: TryFrom<i64>>(value: &str) -> Result<T, OTSpecError> {
+    let trimmed = value.trim();
+    let (negative, digits) = match trimmed.strip_prefix('-') {
+        Some(digits) => (true, digits),
+        None => (false, trimmed),
+    };
+    let parsed = match digits
+        .strip_prefix("0x")
+        .or_else(|| digits.strip_prefix("0X"))
+    {
+        Some(hex) => i64::from_str_radix(hex, 16),
+        None => digits.parse::<i64>(),
+    };
+    parsed
+        .ok()
+        .map(|v| if negative { -v } else { v })
+        .and_then(|v| T::try_from(v).ok())
+        .ok_or_else(|| error(format!("Invalid integer value '{}'", value)))
+}
+
+/// Parses a number written in binary, such as `00000000 00000011`
+pub(crate) fn parse_binary<T: TryFrom<i64>>(value: &str) -> Result<T, OTSpecError> {
+    let digits: String = value.chars().filter(|c| !c.is_whitespace()).collect();
+    i64::from_str_radix(&digits, 2)
+        .ok()
+        .and_then(|v| T::try_from(v).ok())
+        .ok_or_else(|| error(format!("Invalid binary value '{}'", value)))
+}
+
+/// Parses a floating point number
+pub(crate) fn parse_float(value: &str) -> Result<f32, OTSpecError> {
+    value
+        .trim()
+        .parse()
+        .map_err(|_| error(format!("Invalid number '{}'", value)))
+}
+
+/// Formats a number in binary, in groups of eight digits
+pub(crate) fn binary(value: u32, bits: usize) -> String {
+    let digits = format!("{:0width$b}", value, width = bits);
+    digits
+        .as_bytes()
+        .chunks(8)
+        .map(|chunk| String::from_utf8_lossy(chunk).into_owned())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Formats a fixed-point value with the fewest decimal digits which
+/// identify it, as fontTools does
+pub(crate) fn fixed(value: f32, precision_bits: i32) -> String {
+    let scale = 2_f64.powi(precision_bits);
+    let fixed = (value as f64 * scale).round();
+    for digits in 1..10 {
+        let candidate = format!("{:.*}", digits, fixed / scale);
+        if candidate.parse::<f64>().map(|v| (v * scale).round()) == Ok(fixed) {
+            // Drop trailing zeros, but keep one digit after the point
+            let trimmed = candidate.trim_end_matches('0');
+            let trimmed = if trimmed.ends_with('.') {
+                format!("{}0", trimmed)
+            } else {
+                trimmed.to_string()
+            };
+            return if trimmed == "-0.0" {
+                "0.0".to_string()
+            } else {
+                trimmed
+            };
+        }
+    }
+    format!("{}", fixed / scale)
+}
+
+/// Escapes text for use in XML character data or attribute values
+fn escape(text: &str, attribute: bool) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' if attribute => escaped.push_str("&quot;"),
+            '\n' | '\t' if !attribute => escaped.push(c),
+            c if (c as u32) < 0x20 => escaped.push_str(&format!("&#{};", c as u32)),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Writes an indented XML document in the layout used by fontTools
+pub(crate) struct XmlWriter {
+    output: String,
+    indent: usize,
+}
+
+impl XmlWriter {
+    /// Creates a writer, starting with the XML declaration
+    pub(crate) fn new() -> Self {
+        XmlWriter {
+            output: "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n".to_string(),
+            indent: 0,
+        }
+    }
+
+    fn tag(name: &str, attributes: &[(&str, &dyn Display)]) -> String {
+        let mut tag = format!("<{}", name);
+        for (key, value) in attributes {
+            tag.push_str(&format!(
+                " {}=\"{}\"",
+                key,
+                escape(&value.to_string(), true)
+            ));
+        }
+        tag
+    }
+
+    /// Writes a line at the current level of indentation
+    pub(crate) fn line(&mut self, content: &str) {
+        for _ in 0..self.indent {
+            self.output.push_str("  ");
+        }
+        self.output.push_str(content);
+        self.output.push('\n');
+    }
+
+    /// Writes an empty line
+    pub(crate) fn blank(&mut self) {
+        self.output.push('\n');
+    }
+
+    /// Opens an element, indenting its contents
+    pub(crate) fn begin(&mut self, name: &str, attributes: &[(&str, &dyn Display)]) {
+        self.line(&format!("{}>", Self::tag(name, attributes)));
+        self.indent += 1;
+    }
+
+    /// Closes an element opened with [`XmlWriter::begin`]
+    pub(crate) fn end(&mut self, name: &str) {
+        self.indent = self.indent.saturating_sub(1);
+        self.line(&format!("</{}>", name));
+    }
+
+    /// Writes an element with no content
+    pub(crate) fn simple(&mut self, name: &str, attributes: &[(&str, &dyn Display)]) {
+        self.line(&format!("{}/>", Self::tag(name, attributes)));
+    }
+
+    /// Writes an element with no content, followed by a comment
+    pub(crate) fn simple_with_comment(
+        &mut self,
+        name: &str,
+        attributes: &[(&str, &dyn Display)],
+        comment: &str,
+    ) {
+        self.line(&format!(
+            "{}/><!-- {} -->",
+            Self::tag(name, attributes),
+            comment
+        ));
+    }
+
+    /// Writes an element holding a single value, such as `<xMin value="0"/>`
+    pub(crate) fn value(&mut self, name: &str, value: &dyn Display) {
+        self.simple(name, &[("value", value)]);
+    }
+
+    /// Writes an element holding some text, such as `<AxisTag>wght</AxisTag>`
+    pub(crate) fn text_element(&mut self, name: &str, text: &dyn Display) {
+        self.line(&format!(
+            "<{}>{}</{}>",
+            name,
+            escape(&text.to_string(), false),
+            name
+        ));
+    }
+
+    /// Writes a comment, with any further lines of text indented under the
+    /// first
+    pub(crate) fn comment(&mut self, text: &str) {
+        let mut lines = text.split('\n');
+        let mut comment = format!("<!-- {}", lines.next().unwrap_or_default());
+        for line in lines {
+            self.line(&comment);
+            comment = format!("     {}", line);
+        }
+        comment.push_str(" -->");
+        self.line(&comment);
+    }
+
+    /// Writes a line of character data
+    pub(crate) fn text(&mut self, text: &str) {
+        self.line(&escape(text, false));
+    }
+
+    /// Writes binary data as lines of hex digits, sixteen bytes to a line
+    pub(crate) fn hex(&mut self, data: &[u8]) {
+        for line in data.chunks(16) {
+            let groups: Vec<String> = line
+                .chunks(4)
+                .map(|group| group.iter().map(|b| format!("{:02x}", b)).collect())
+                .collect();
+            self.line(&groups.join(" "));
+        }
+    }
+
+    /// Returns the finished document
+    pub(crate) fn finish(self) -> String {
+        self.output
+    }
+}
+
+/// Parses the hex digits written by [`XmlWriter::hex`], ignoring whitespace
+pub(crate) fn parse_hex(text: &str) -> Result<Vec<u8>, OTSpecError> {
+    let digits: Vec<u8> = text.bytes().filter(|b| !b.is_ascii_whitespace()).collect();
+    if digits.len() % 2 == 1 {
+        return Err(error("Hex data has an odd number of digits"));
+    }
+    digits
+        .chunks(2)
+        .map(|pair| {
+            std::str::from_utf8(pair)
+                .ok()
+                .and_then(|pair| u8::from_str_radix(pair, 16).ok())
+                .ok_or_else(|| {
+                    error(format!(
+                        "Invalid hex data '{}'",
+                        String::from_utf8_lossy(pair)
+                    ))
+                })
+        })
+        .collect()
+}
@@ -0,0 +1,378 @@
+use crate::font::{
+    block, pad, read_table_directory, to_uint32, write_table_directory, FontError, TableRecord,
+};
+use flate2::read::ZlibDecoder;
+use flate2::write::ZlibEncoder;
+use flate2::Compression;
+use otspec::de::CountedDeserializer;
+use otspec::error::Error as OTSpecError;
+use otspec::types::*;
+use serde::de::DeserializeSeed;
+use serde::{Deserialize, Serialize};
+use std::io::{self, Read, Write};
+
+/// The header at the start of a WOFF file
+#[derive(Serialize, Deserialize, Debug)]
+struct WoffHeader {
+    signature: Tag,
+    flavor: uint32,
+    length: uint32,
+    numTables: uint16,
+    reserved: uint16,
+    totalSfntSize: uint32,
+    majorVersion: uint16,
+    minorVersion: uint16,
+    metaOffset: uint32,
+    metaLength: uint32,
+    metaOrigLength: uint32,
+    privOffset: uint32,
+    privLength: uint32,
+}
+
+/// An entry in the WOFF table directory
+#[derive(Serialize, Deserialize, Debug)]
+struct WoffTableEntry {
+    tag: Tag,
+    offset: uint32,
+    compLength: uint32,
+    origLength: uint32,
+    origChecksum: uint32,
+}
+
+const HEADER_SIZE: usize = 44;
+const ENTRY_SIZE: usize = 20;
+
+/// The largest font or metadata block we decompress, so that a small file
+/// cannot claim gigabytes of memory
+pub(crate) const MAX_DECOMPRESSED_SIZE: u64 = 256 << 20;
+
+/// Checks that a declared decompressed size is within [`MAX_DECOMPRESSED_SIZE`]
+pub(crate) fn check_decompressed_size(size: u64, what: &str) -> Result<(), FontError> {
+    if size > MAX_DECOMPRESSED_SIZE {
+        return Err(OTSpecError::Message(format!(
+            "{} would decompress to {} bytes, more than the limit of {}",
+            what, size, MAX_DECOMPRESSED_SIZE
+        ))
+        .into());
+    }
+    Ok(())
+}
+
+/// The contents of a WOFF 1.0 or WOFF2 file
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Woff {
+    /// The binary (sfnt) font
+    pub sfnt: Vec<u8>,
+    /// Major version of the WOFF file
+    pub majorVersion: uint16,
+    /// Minor version of the WOFF file
+    pub minorVersion: uint16,
+    /// The extended metadata block, as uncompressed XML
+    pub metadata: Option<Vec<u8>>,
+    /// The private data block
+    pub privateData: Option<Vec<u8>>,
+}
+
+impl Woff {
+    /// Wraps a binary font, taking the WOFF version from its `head.fontRevision`
+    pub fn new(sfnt: Vec<u8>) -> Self {
+        let mut woff = Woff {
+            sfnt,
+            ..Default::default()
+        };
+        if let Ok((_, records)) = read_table_directory(&woff.sfnt) {
+            let head = records.iter().find(|r| &r.tag == b"head").and_then(|r| {
+                let start = r.offset as usize + 4;
+                woff.sfnt.get(start..start + 4)
+            });
+            if let Some(revision) = head.map(|r| [r[0], r[1], r[2], r[3]]) {
+                woff.majorVersion = u16::from_be_bytes([revision[0], revision[1]]);
+                woff.minorVersion = u16::from_be_bytes([revision[2], revision[3]]);
+            }
+        }
+        woff
+    }
+}
+
+/// Decompresses a block, which must decompress to exactly `length` bytes
+fn inflate(data: &[u8], length: uint32, what: &str) -> Result<Vec<u8>, FontError> {
+    check_decompressed_size(length as u64, what)?;
+    let mut output = vec![];
+    ZlibDecoder::new(data)
+        .take(length as u64)
+        .read_to_end(&mut output)
+        .map_err(|e| OTSpecError::Message(format!("Could not decompress {}: {}", what, e)))?;
+    if output.len() != length as usize {
+        return Err(OTSpecError::Message(format!(
+            "{} decompressed to {} bytes, but should be {}",
+            what,
+            output.len(),
+            length
+        ))
+        .into());
+    }
+    Ok(output)
+}
+
+fn deflate(data: &[u8]) -> io::Result<Vec<u8>> {
+    let mut encoder = ZlibEncoder::new(vec![], Compression::default());
+    encoder.write_all(data)?;
+    encoder.finish()
+}
+
+/// Decodes a WOFF 1.0 file
+///
+/// The tables are decompressed and reassembled into a binary font, in the
+/// same order as in the WOFF file. Files whose tables add up to more than the
+/// header's `totalSfntSize` are rejected before anything is decompressed.
+pub fn decode(data: &[u8]) -> Result<Woff, FontError> {
+    let mut de = otspec::de::Deserializer::from_bytes(data);
+    let header = WoffHeader::deserialize(&mut de)?;
+    if &header.signature != b"wOFF" {
+        return Err(OTSpecError::Message("WOFF file must begin with 'wOFF'".into()).into());
+    }
+    let mut entries: Vec<WoffTableEntry> =
+        CountedDeserializer::with_len(header.numTables as usize).deserialize(&mut de)?;
+    entries.sort_by_key(|e| e.offset);
+    check_decompressed_size(header.totalSfntSize as u64, "Font")?;
+    let sfnt_size = entries
+        .iter()
+        .fold(12 + 16 * entries.len() as u64, |size, e| {
+            size + ((e.origLength as u64 + 3) & !3)
+        });
+    if sfnt_size > header.totalSfntSize as u64 {
+        return Err(OTSpecError::Message(format!(
+            "Tables add up to {} bytes, more than the total sfnt size of {}",
+            sfnt_size, header.totalSfntSize
+        ))
+        .into());
+    }
+
+    let mut records = vec![];
+    let mut tables: Vec<u8> = vec![];
+    let start = 12 + 16 * entries.len();
+    for entry in &entries {
+        let what = format!("{} table", String::from_utf8_lossy(&entry.tag));
+        let compressed = block(data, entry.offset, entry.compLength, &what)?;
+        let table = if entry.compLength < entry.origLength {
+            inflate(compressed, entry.origLength, &what)?
+        } else if entry.compLength == entry.origLength {
+            compressed.to_vec()
+        } else {
+            return Err(OTSpecError::Message(format!(
+                "{} has a compressed length greater than its original length",
+                what
+            ))
+            .into());
+        };
+        records.push(TableRecord {
+            tag: entry.tag,
+            checksum: entry.origChecksum,
+            offset: to_uint32(start + tables.len())?,
+            length: entry.origLength,
+        });
+        tables.extend(table);
+        pad(&mut tables);
+    }
+    records.sort_by_key(|r| r.tag);
+    let mut sfnt = write_table_directory(header.flavor, &records)?;
+    sfnt.extend(tables);
+
+    let metadata = if header.metaLength > 0 {
+        let compressed = block(data, header.metaOffset, header.metaLength, "Metadata")?;
+        Some(inflate(compressed, header.metaOrigLength, "metadata")?)
+    } else {
+        None
+    };
+    let privateData = if header.privLength > 0 {
+        Some(block(data, header.privOffset, header.privLength, "Private data")?.to_vec())
+    } else {
+        None
+    };
+    Ok(Woff {
+        sfnt,
+        majorVersion: header.majorVersion,
+        minorVersion: header.minorVersion,
+        metadata,
+        privateData,
+    })
+}
+
+/// Encodes a WOFF 1.0 file
+///
+/// Each table is compressed, unless that would not make it smaller. The
+/// tables are stored in the same order as in the binary font.
+pub fn encode(woff: &Woff) -> Result<Vec<u8>, FontError> {
+    let (sfnt_header, mut records) = read_table_directory(&woff.sfnt)?;
+    records.sort_by_key(|r| r.offset);
+
+    let mut entries = vec![];
+    let mut tables: Vec<u8> = vec![];
+    let start = HEADER_SIZE + ENTRY_SIZE * records.len();
+    let mut totalSfntSize = 12 + 16 * records.len();
+    for record in &records {
+        let what = format!("{} table", String::from_utf8_lossy(&record.tag));
+        let table = block(&woff.sfnt, record.offset, record.length, &what)?;
+        let compressed = deflate(table)?;
+        let stored = if compressed.len() < table.len() {
+            &compressed
+        } else {
+            table
+        };
+        entries.push(WoffTableEntry {
+            tag: record.tag,
+            offset: to_uint32(start + tables.len())?,
+            compLength: to_uint32(stored.len())?,
+            origLength: record.length,
+            origChecksum: record.checksum,
+        });
+        tables.extend(stored);
+        pad(&mut tables);
+        totalSfntSize += (table.len() + 3) & !3;
+    }
+    entries.sort_by_key(|e| e.tag);
+
+    let mut blocks = tables;
+    let (mut metaOffset, mut metaLength, mut metaOrigLength) = (0, 0, 0);
+    if let Some(metadata) = &woff.metadata {
+        let compressed = deflate(metadata)?;
+        metaOffset = to_uint32(start + blocks.len())?;
+        metaLength = to_uint32(compressed.len())?;
+        metaOrigLength = to_uint32(metadata.len())?;
+        blocks.extend(compressed);
+    }
+    let (mut privOffset, mut privLength) = (0, 0);
+    if let Some(private_data) = &woff.privateData {
+        pad(&mut blocks);
+        privOffset = to_uint32(start + blocks.len())?;
+        privLength = to_uint32(private_data.len())?;
+        blocks.extend(private_data);
+    }
+
+    let header = WoffHeader {
+        signature: *b"wOFF",
+        flavor: sfnt_header.sfntVersion,
+        length: to_uint32(start + blocks.len())?,
+        numTables: sfnt_header.numTables,
+        reserved: 0,
+        totalSfntSize: to_uint32(totalSfntSize)?,
+        majorVersion: woff.majorVersion,
+        minorVersion: woff.minorVersion,
+        metaOffset,
+        metaLength,
+        metaOrigLength,
+        privOffset,
+        privLength,
+    };
+    let mut output = otspec::ser::to_bytes(&header)?;
+    for entry in &entries {
+        output.extend(otspec::ser::to_bytes(entry)?);
+    }
+    output.extend(blocks);
+    Ok(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::font::{self, Font, SfntVersion, Table};
+    use crate::woff::{self, Woff};
+
+    fn woff() -> Vec<u8> {
+        woff::encode(&Woff::new(font::tests::LOCA_FONT.to_vec())).unwrap()
+    }
+
+    #[test]
+    fn woff_roundtrip() {
+        let binary = woff();
+        assert_eq!(&binary[0..8], b"wOFF\x00\x01\x00\x00");
+        assert_eq!(
+            u32::from_be_bytes([binary[8], binary[9], binary[10], binary[11]]) as usize,
+            binary.len()
+        );
+        let decoded = woff::decode(&binary).unwrap();
+        assert_eq!(decoded.sfnt, font::tests::LOCA_FONT);
+        assert_eq!((decoded.majorVersion, decoded.minorVersion), (1, 0));
+        assert_eq!(decoded.metadata, None);
+        assert_eq!(decoded.privateData, None);
+
+        assert_eq!(
+            font::load(binary.as_slice()).unwrap(),
+            font::load(font::tests::LOCA_FONT).unwrap()
+        );
+    }
+
+    #[test]
+    fn woff_metadata() {
+        let metadata = br#"<?xml version="1.0" encoding="UTF-8"?>
+<metadata version="1.0"><uniqueid id="com.example.font.1"/></metadata>"#;
+        let mut woff = Woff::new(font::tests::LOCA_FONT.to_vec());
+        woff.metadata = Some(metadata.to_vec());
+        woff.privateData = Some(vec![1, 2, 3]);
+        let binary = woff::encode(&woff).unwrap();
+        let private_offset = u32::from_be_bytes([binary[36], binary[37], binary[38], binary[39]]);
+        assert_eq!(private_offset % 4, 0);
+        assert_eq!(&binary[binary.len() - 3..], &[1, 2, 3]);
+        assert_eq!(woff::decode(&binary).unwrap(), woff);
+    }
+
+    #[test]
+    fn woff_save() {
+        let mut font = Font::new(SfntVersion::TrueType);
+        let head = font::FontRef::new(font::tests::LOCA_FONT)
+            .unwrap()
+            .table_data(b"head")
+            .unwrap()
+            .to_vec();
+        font.tables.insert(*b"head", Table::Unknown(head));
+        font.tables.insert(*b"name", Table::Unknown(vec![0; 400]));
+        let mut binary = vec![];
+        font.save_woff(&mut binary).unwrap();
+        // The empty name table is compressed, the head table isn't
+        assert!(binary.len() < 44 + 2 * 20 + 56 + 400);
+        let decoded = woff::decode(&binary).unwrap();
+        assert_eq!(decoded.sfnt, otspec::ser::to_bytes(&font).unwrap());
+    }
+
+    #[test]
+    fn woff_errors() {
+        let mut binary = woff();
+        assert!(woff::decode(&binary[..binary.len() - 4]).is_err());
+        // Corrupt the zlib header of the first compressed table
+        let compressed = (0..4)
+            .map(|i| &binary[44 + 20 * i..44 + 20 * (i + 1)])
+            .find(|entry| entry[8..12] != entry[12..16])
+            .map(|entry| u32::from_be_bytes([entry[4], entry[5], entry[6], entry[7]]) as usize)
+            .unwrap();
+        binary[compressed] = 0xff;
+        let error = woff::decode(&binary).unwrap_err();
+        assert!(error.to_string().starts_with("Could not decompress"));
+    }
+
+    #[test]
+    fn woff_declared_sizes() {
+        let binary = woff();
+        let set = |binary: &mut Vec<u8>, offset: usize, value: u32| {
+            binary[offset..offset + 4].copy_from_slice(&value.to_be_bytes())
+        };
+        let total = u32::from_be_bytes([binary[16], binary[17], binary[18], binary[19]]);
+
+        let mut shrunk = binary.clone();
+        set(&mut shrunk, 16, total - 4);
+        let error = woff::decode(&shrunk).unwrap_err();
+        assert!(error.to_string().contains("more than the total sfnt size"));
+
+        // A table claiming gigabytes, in a font claiming to be big enough
+        let mut huge = binary.clone();
+        set(&mut huge, 16, 0xFFFF_FFF0);
+        set(&mut huge, 44 + 12, 0x7FFF_FFFF);
+        let error = woff::decode(&huge).unwrap_err();
+        assert!(error.to_string().contains("more than the limit"));
+
+        let mut metadata = binary;
+        set(&mut metadata, 24, 44);
+        set(&mut metadata, 28, 4);
+        set(&mut metadata, 32, 0xFFFF_FFFF);
+        let error = woff::decode(&metadata).unwrap_err();
+        assert!(error.to_string().contains("more than the limit"));
+    }
+}
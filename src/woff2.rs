@@ -0,0 +1,782 @@
+use crate::font::{
+    block, pad, read_collection, to_uint32, Font, FontCollection, FontError, FontRef, SfntVersion,
+    Table,
+};
+use crate::woff::{check_decompressed_size, Woff};
+use otspec::error::Error as OTSpecError;
+use otspec::types::*;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::convert::TryInto;
+use std::io::{Read, Write};
+
+/// The WOFF2 `glyf` and `loca` table transform
+pub(crate) mod glyf;
+/// The WOFF2 `hmtx` table transform
+mod hmtx;
+
+/// The header at the start of a WOFF2 file
+#[derive(Serialize, Deserialize, Debug)]
+struct Woff2Header {
+    signature: Tag,
+    flavor: uint32,
+    length: uint32,
+    numTables: uint16,
+    reserved: uint16,
+    totalSfntSize: uint32,
+    totalCompressedSize: uint32,
+    majorVersion: uint16,
+    minorVersion: uint16,
+    metaOffset: uint32,
+    metaLength: uint32,
+    metaOrigLength: uint32,
+    privOffset: uint32,
+    privLength: uint32,
+}
+
+const HEADER_SIZE: usize = 48;
+
+/// Tags which are stored in the table directory as an index into this list
+const KNOWN_TAGS: [&Tag; 63] = [
+    b"cmap", b"head", b"hhea", b"hmtx", b"maxp", b"name", b"OS/2", b"post", b"cvt ", b"fpgm",
+    b"glyf", b"loca", b"prep", b"CFF ", b"VORG", b"EBDT", b"EBLC", b"gasp", b"hdmx", b"kern",
+    b"LTSH", b"PCLT", b"VDMX", b"vhea", b"vmtx", b"BASE", b"GDEF", b"GPOS", b"GSUB", b"EBSC",
+    b"JSTF", b"MATH", b"CBDT", b"CBLC", b"COLR", b"CPAL", b"SVG ", b"sbix", b"acnt", b"avar",
+    b"bdat", b"bloc", b"bsln", b"cvar", b"fdsc", b"feat", b"fmtx", b"fvar", b"gvar", b"hsty",
+    b"just", b"lcar", b"mort", b"morx", b"opbd", b"prop", b"trak", b"Zapf", b"Silf", b"Glat",
+    b"Gloc", b"Feat", b"Sill",
+];
+
+/// Flags value meaning that the tag follows the flags byte
+const ARBITRARY_TAG: u8 = 63;
+
+/// Brotli quality and window size used when encoding
+const BROTLI_QUALITY: u32 = 11;
+const BROTLI_WINDOW: u32 = 22;
+
+/// Reads big-endian and WOFF2 variable-length values from a buffer
+struct Cursor<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Cursor<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        Cursor { data, pos: 0 }
+    }
+
+    fn bytes(&mut self, length: usize) -> otspec::error::Result<&'a [u8]> {
+        let available = self.data.len() - self.pos;
+        if length > available {
+            return Err(OTSpecError::UnexpectedEof {
+                needed: length,
+                available,
+            });
+        }
+        let bytes = &self.data[self.pos..self.pos + length];
+        self.pos += length;
+        Ok(bytes)
+    }
+
+    fn u8(&mut self) -> otspec::error::Result<u8> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    fn u16(&mut self) -> otspec::error::Result<u16> {
+        let bytes = self.bytes(2)?;
+        Ok(u16::from_be_bytes([bytes[0], bytes[1]]))
+    }
+
+    fn i16(&mut self) -> otspec::error::Result<i16> {
+        Ok(self.u16()? as i16)
+    }
+
+    fn u32(&mut self) -> otspec::error::Result<u32> {
+        let bytes = self.bytes(4)?;
+        Ok(u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+
+    /// Reads a `UIntBase128` value
+    fn uint_base128(&mut self) -> otspec::error::Result<u32> {
+        let mut accumulator: u32 = 0;
+        for i in 0..5 {
+            let byte = self.u8()?;
+            if i == 0 && byte == 0x80 {
+                return Err(OTSpecError::Message(
+                    "UIntBase128 value has leading zeros".into(),
+                ));
+            }
+            if accumulator & 0xFE00_0000 != 0 {
+                return Err(OTSpecError::Message("UIntBase128 value overflows".into()));
+            }
+            accumulator = (accumulator << 7) | (byte & 0x7f) as u32;
+            if byte & 0x80 == 0 {
+                return Ok(accumulator);
+            }
+        }
+        Err(OTSpecError::Message(
+            "UIntBase128 value is longer than five bytes".into(),
+        ))
+    }
+
+    /// Reads a `255UInt16` value
+    fn uint255(&mut self) -> otspec::error::Result<u16> {
+        match self.u8()? {
+            253 => self.u16(),
+            254 => Ok(self.u8()? as u16 + 506),
+            255 => Ok(self.u8()? as u16 + 253),
+            code => Ok(code as u16),
+        }
+    }
+}
+
+/// Writes a `UIntBase128` value
+fn write_base128(output: &mut Vec<u8>, value: u32) {
+    let mut bytes = vec![(value & 0x7f) as u8];
+    let mut rest = value >> 7;
+    while rest > 0 {
+        bytes.push((rest & 0x7f) as u8 | 0x80);
+        rest >>= 7;
+    }
+    output.extend(bytes.iter().rev());
+}
+
+/// Writes a `255UInt16` value
+fn write_uint255(output: &mut Vec<u8>, value: u16) {
+    if value < 253 {
+        output.push(value as u8);
+    } else if value < 506 {
+        output.extend(&[255, (value - 253) as u8]);
+    } else if value < 762 {
+        output.extend(&[254, (value - 506) as u8]);
+    } else {
+        output.push(253);
+        output.extend(&value.to_be_bytes());
+    }
+}
+
+fn tag_name(tag: &Tag) -> String {
+    String::from_utf8_lossy(tag).to_string()
+}
+
+fn brotli_compress(data: &[u8]) -> Result<Vec<u8>, FontError> {
+    let mut output = vec![];
+    {
+        let mut writer =
+            brotli::CompressorWriter::new(&mut output, 4096, BROTLI_QUALITY, BROTLI_WINDOW);
+        writer.write_all(data)?;
+    }
+    Ok(output)
+}
+
+/// Decompresses Brotli data, which must decompress to exactly `length` bytes
+fn brotli_decompress(data: &[u8], length: usize, what: &str) -> Result<Vec<u8>, FontError> {
+    check_decompressed_size(length as u64, what)?;
+    let mut output = vec![];
+    brotli::Decompressor::new(data, 4096)
+        .take(length as u64)
+        .read_to_end(&mut output)
+        .map_err(|e| OTSpecError::Message(format!("Could not decompress {}: {}", what, e)))?;
+    if output.len() != length {
+        return Err(OTSpecError::Message(format!(
+            "{} decompressed to {} bytes, but should be {}",
+            what,
+            output.len(),
+            length
+        ))
+        .into());
+    }
+    Ok(output)
+}
+
+/// An entry in the WOFF2 table directory
+#[derive(Debug)]
+struct TableEntry {
+    tag: Tag,
+    /// The transformation version, from bits 6-7 of the flags
+    transform: u8,
+    origLength: uint32,
+    transformLength: Option<uint32>,
+}
+
+impl TableEntry {
+    /// Whether the table data has been transformed.
+    ///
+    /// For `glyf` and `loca`, version 0 is the transform and version 3 the
+    /// null transform; for other tables it is the other way round.
+    fn is_transformed(&self) -> bool {
+        if &self.tag == b"glyf" || &self.tag == b"loca" {
+            self.transform != 3
+        } else {
+            self.transform != 0
+        }
+    }
+
+    /// The length of the table data in the decompressed stream
+    fn stream_length(&self) -> uint32 {
+        self.transformLength.unwrap_or(self.origLength)
+    }
+
+    fn read(cursor: &mut Cursor) -> otspec::error::Result<Self> {
+        let flags = cursor.u8()?;
+        let tag = match flags & 0x3f {
+            ARBITRARY_TAG => {
+                let bytes = cursor.bytes(4)?;
+                [bytes[0], bytes[1], bytes[2], bytes[3]]
+            }
+            index => *KNOWN_TAGS[index as usize],
+        };
+        let mut entry = TableEntry {
+            tag,
+            transform: flags >> 6,
+            origLength: cursor.uint_base128()?,
+            transformLength: None,
+        };
+        if entry.is_transformed() {
+            entry.transformLength = Some(cursor.uint_base128()?);
+        }
+        Ok(entry)
+    }
+
+    fn write(&self, output: &mut Vec<u8>) {
+        let index = KNOWN_TAGS.iter().position(|t| **t == self.tag);
+        let index = index.map_or(ARBITRARY_TAG, |i| i as u8);
+        output.push(index | (self.transform << 6));
+        if index == ARBITRARY_TAG {
+            output.extend(&self.tag);
+        }
+        write_base128(output, self.origLength);
+        if let Some(length) = self.transformLength {
+            write_base128(output, length);
+        }
+    }
+}
+
+/// Rebuilds a font from the tables of a WOFF2 file
+fn reconstruct_font(flavor: uint32, tables: &[(&TableEntry, &[u8])]) -> Result<Font, FontError> {
+    let version: SfntVersion = flavor
+        .try_into()
+        .map_err(|_| OTSpecError::Message("Font must begin with a valid version".into()))?;
+    let mut font = Font::new(version);
+    let mut x_mins = None;
+    for (entry, data) in tables {
+        let transformed = entry.is_transformed();
+        match &entry.tag {
+            b"glyf" if transformed => {
+                if entry.transform != 0 {
+                    return Err(unknown_transform(entry));
+                }
+                let glyf = glyf::reconstruct(data).map_err(|e| e.in_table(*b"glyf"))?;
+                x_mins = Some(glyf.glyphs.iter().map(|g| g.xMin).collect::<Vec<_>>());
+                font.tables.insert(entry.tag, Table::Glyf(glyf));
+            }
+            b"loca" if transformed => {
+                if entry.transform != 0 {
+                    return Err(unknown_transform(entry));
+                }
+                // Rebuilt from the glyf table when the font is compiled
+            }
+            b"hmtx" if transformed => {}
+            _ if transformed => return Err(unknown_transform(entry)),
+            _ => {
+                font.tables.insert(entry.tag, Table::Unknown(data.to_vec()));
+            }
+        }
+    }
+    if let Some((entry, data)) = tables.iter().find(|(e, _)| &e.tag == b"hmtx") {
+        if entry.is_transformed() {
+            if entry.transform != 1 {
+                return Err(unknown_transform(entry));
+            }
+            let x_mins = x_mins.ok_or_else(|| {
+                OTSpecError::Message("Transformed hmtx table requires a transformed glyf".into())
+            })?;
+            let numberOfHMetrics = match font.tables.get(b"hhea") {
+                Some(Table::Unknown(hhea)) if hhea.len() >= 36 => {
+                    u16::from_be_bytes([hhea[34], hhea[35]])
+                }
+                _ => return Err(FontError::MissingTable(*b"hhea")),
+            };
+            let hmtx = hmtx::reconstruct(data, numberOfHMetrics, &x_mins)
+                .map_err(|e| e.in_table(*b"hmtx"))?;
+            font.tables.insert(*b"hmtx", Table::Unknown(hmtx));
+        }
+    }
+    if font.tables.contains_key(b"glyf") && !font.tables.contains_key(b"loca") {
+        let transformed_loca = tables
+            .iter()
+            .any(|(e, _)| &e.tag == b"loca" && e.is_transformed());
+        if !transformed_loca {
+            return Err(FontError::MissingTable(*b"loca"));
+        }
+    }
+    Ok(font)
+}
+
+fn unknown_transform(entry: &TableEntry) -> FontError {
+    OTSpecError::Message(format!(
+        "Unknown transformation {} for {} table",
+        entry.transform,
+        tag_name(&entry.tag)
+    ))
+    .into()
+}
+
+/// Decodes a WOFF2 file
+///
+/// The tables are decompressed, and the `glyf`, `loca` and `hmtx` tables are
+/// rebuilt from their transformed forms. The result is a binary font (or,
+/// for a WOFF2 collection, a binary font collection) equivalent to, but not
+/// necessarily byte-for-byte identical with, the original.
+pub fn decode(data: &[u8]) -> Result<Woff, FontError> {
+    let mut de = otspec::de::Deserializer::from_bytes(data);
+    let header = Woff2Header::deserialize(&mut de)?;
+    if &header.signature != b"wOF2" {
+        return Err(OTSpecError::Message("WOFF2 file must begin with 'wOF2'".into()).into());
+    }
+    let mut cursor = Cursor::new(data);
+    cursor.bytes(HEADER_SIZE)?;
+    let mut entries = vec![];
+    for _ in 0..header.numTables {
+        entries.push(TableEntry::read(&mut cursor)?);
+    }
+
+    // Each member of a collection refers to tables by their index
+    let mut members: Vec<(uint32, Vec<usize>)> = vec![];
+    let is_collection = &header.flavor.to_be_bytes() == b"ttcf";
+    if is_collection {
+        let _ttc_version = cursor.u32()?;
+        let num_fonts = cursor.uint255()?;
+        for _ in 0..num_fonts {
+            let num_tables = cursor.uint255()?;
+            let flavor = cursor.u32()?;
+            let mut indices = vec![];
+            for _ in 0..num_tables {
+                let index = cursor.uint255()? as usize;
+                if index >= entries.len() {
+                    return Err(OTSpecError::Message(format!(
+                        "Collection refers to table {}, but there are only {}",
+                        index,
+                        entries.len()
+                    ))
+                    .into());
+                }
+                indices.push(index);
+            }
+            members.push((flavor, indices));
+        }
+    } else {
+        members.push((header.flavor, (0..entries.len()).collect()));
+    }
+
+    let compressed = cursor.bytes(header.totalCompressedSize as usize)?;
+    // Neither the tables nor their transformed forms can be larger than the
+    // font they make up
+    check_decompressed_size(header.totalSfntSize as u64, "Font")?;
+    let tables_length: u64 = entries.iter().map(|e| (e.origLength as u64 + 3) & !3).sum();
+    let stream_length: u64 = entries.iter().map(|e| e.stream_length() as u64).sum();
+    if tables_length.max(stream_length) > header.totalSfntSize as u64 {
+        return Err(OTSpecError::Message(format!(
+            "Tables add up to {} bytes, more than the total sfnt size of {}",
+            tables_length.max(stream_length),
+            header.totalSfntSize
+        ))
+        .into());
+    }
+    let stream = brotli_decompress(compressed, stream_length as usize, "font data")?;
+    let mut tables = vec![];
+    let mut pos = 0;
+    for entry in &entries {
+        let end = pos + entry.stream_length() as usize;
+        tables.push((entry, &stream[pos..end]));
+        pos = end;
+    }
+
+    let mut fonts = vec![];
+    for (flavor, indices) in &members {
+        let mut member_tables: Vec<_> = indices.iter().map(|i| tables[*i]).collect();
+        member_tables.sort_by_key(|(entry, _)| entry.tag);
+        fonts.push(reconstruct_font(*flavor, &member_tables)?);
+    }
+    let mut sfnt = vec![];
+    if is_collection {
+        FontCollection { fonts, dsig: None }.save(&mut sfnt)?;
+    } else if let Some(mut font) = fonts.pop() {
+        font.save(&mut sfnt)?;
+    }
+
+    let metadata = if header.metaLength > 0 {
+        let compressed = block(data, header.metaOffset, header.metaLength, "Metadata")?;
+        Some(brotli_decompress(
+            compressed,
+            header.metaOrigLength as usize,
+            "metadata",
+        )?)
+    } else {
+        None
+    };
+    let privateData = if header.privLength > 0 {
+        Some(block(data, header.privOffset, header.privLength, "Private data")?.to_vec())
+    } else {
+        None
+    };
+    Ok(Woff {
+        sfnt,
+        majorVersion: header.majorVersion,
+        minorVersion: header.minorVersion,
+        metadata,
+        privateData,
+    })
+}
+
+/// The tags of a font's tables in the order they are stored: sorted, except
+/// that `loca` immediately follows `glyf`.
+fn table_order(font: &FontRef) -> Vec<Tag> {
+    let mut tags: Vec<Tag> = font.tags().copied().filter(|t| t != b"loca").collect();
+    if font.table_data(b"loca").is_some() {
+        let position = tags
+            .iter()
+            .position(|t| t == b"glyf")
+            .map_or(tags.len(), |p| p + 1);
+        tags.insert(position, *b"loca");
+    }
+    tags
+}
+
+/// Encodes a WOFF2 file
+///
+/// The `glyf`, `loca` and `hmtx` tables are transformed where possible, and
+/// bit 11 of `head.flags` is set to record that the font has been through a
+/// lossless transformation. `woff.sfnt` may also be a font collection, in
+/// which case tables shared between its fonts are stored once.
+pub fn encode(woff: &Woff) -> Result<Vec<u8>, FontError> {
+    let is_collection = woff.sfnt.starts_with(b"ttcf");
+    let mut fonts = if is_collection {
+        read_collection(&woff.sfnt)?.0
+    } else {
+        vec![FontRef::new(&woff.sfnt)?]
+    };
+
+    // Identify each table by its position in the file, so that tables shared
+    // between the fonts of a collection are only stored once
+    let mut entries: Vec<TableEntry> = vec![];
+    let mut stream: Vec<u8> = vec![];
+    let mut positions: BTreeMap<(usize, usize, Tag), usize> = BTreeMap::new();
+    let mut members: Vec<(uint32, Vec<usize>)> = vec![];
+    // The xMin values of the glyphs of each transformed glyf table, by entry
+    let mut glyf_x_mins: BTreeMap<usize, Vec<i16>> = BTreeMap::new();
+    for font in fonts.iter_mut() {
+        let mut indices = vec![];
+        let mut transformed_glyf: Option<Vec<i16>> = None;
+        for tag in table_order(font) {
+            let data = font.table_data(&tag).unwrap_or_default();
+            let key = (data.as_ptr() as usize, data.len(), tag);
+            if let Some(index) = positions.get(&key) {
+                indices.push(*index);
+                if &tag == b"glyf" {
+                    transformed_glyf = glyf_x_mins.get(index).cloned();
+                }
+                continue;
+            }
+            let mut entry = TableEntry {
+                tag,
+                transform: 0,
+                origLength: to_uint32(data.len())?,
+                transformLength: None,
+            };
+            let mut table = data.to_vec();
+            match &tag {
+                b"glyf" => match glyf::transform(font)? {
+                    Some((transformed, x_mins)) => {
+                        entry.transformLength = Some(to_uint32(transformed.len())?);
+                        table = transformed;
+                        transformed_glyf = Some(x_mins);
+                    }
+                    None => entry.transform = 3,
+                },
+                b"loca" => {
+                    if transformed_glyf.is_some() {
+                        entry.transformLength = Some(0);
+                        table = vec![];
+                    } else {
+                        entry.transform = 3;
+                    }
+                }
+                b"hmtx" => {
+                    let transformed = transformed_glyf
+                        .as_ref()
+                        .and_then(|x_mins| hmtx::transform(font, x_mins));
+                    if let Some(transformed) = transformed {
+                        entry.transform = 1;
+                        entry.transformLength = Some(to_uint32(transformed.len())?);
+                        table = transformed;
+                    }
+                }
+                b"head" if table.len() >= 18 => table[16] |= 0x08,
+                _ => {}
+            }
+            if let (b"glyf", Some(x_mins)) = (&tag, &transformed_glyf) {
+                glyf_x_mins.insert(entries.len(), x_mins.clone());
+            }
+            stream.extend(table);
+            positions.insert(key, entries.len());
+            indices.push(entries.len());
+            entries.push(entry);
+        }
+        members.push((font.sfnt_version() as u32, indices));
+    }
+
+    let mut directory: Vec<u8> = vec![];
+    for entry in &entries {
+        entry.write(&mut directory);
+    }
+    if is_collection {
+        let ttc_version: u32 = 0x0001_0000;
+        directory.extend(&ttc_version.to_be_bytes());
+        write_uint255(
+            &mut directory,
+            members
+                .len()
+                .try_into()
+                .map_err(|_| OTSpecError::OffsetOverflow)?,
+        );
+        for (flavor, indices) in &members {
+            write_uint255(&mut directory, indices.len() as u16);
+            directory.extend(&flavor.to_be_bytes());
+            for index in indices {
+                write_uint255(&mut directory, *index as u16);
+            }
+        }
+    }
+
+    let compressed = brotli_compress(&stream)?;
+    let mut blocks = compressed.clone();
+    let start = HEADER_SIZE + directory.len();
+    let (mut metaOffset, mut metaLength, mut metaOrigLength) = (0, 0, 0);
+    if let Some(metadata) = &woff.metadata {
+        pad(&mut blocks);
+        let compressed = brotli_compress(metadata)?;
+        metaOffset = to_uint32(start + blocks.len())?;
+        metaLength = to_uint32(compressed.len())?;
+        metaOrigLength = to_uint32(metadata.len())?;
+        blocks.extend(compressed);
+    }
+    let (mut privOffset, mut privLength) = (0, 0);
+    if let Some(private_data) = &woff.privateData {
+        pad(&mut blocks);
+        privOffset = to_uint32(start + blocks.len())?;
+        privLength = to_uint32(private_data.len())?;
+        blocks.extend(private_data);
+    }
+
+    let header = Woff2Header {
+        signature: *b"wOF2",
+        flavor: if is_collection {
+            u32::from_be_bytes(*b"ttcf")
+        } else {
+            members[0].0
+        },
+        length: to_uint32(start + blocks.len())?,
+        numTables: entries
+            .len()
+            .try_into()
+            .map_err(|_| OTSpecError::Message("Too many tables in font".into()))?,
+        reserved: 0,
+        totalSfntSize: to_uint32(woff.sfnt.len())?,
+        totalCompressedSize: to_uint32(compressed.len())?,
+        majorVersion: woff.majorVersion,
+        minorVersion: woff.minorVersion,
+        metaOffset,
+        metaLength,
+        metaOrigLength,
+        privOffset,
+        privLength,
+    };
+    let mut output = otspec::ser::to_bytes(&header)?;
+    output.extend(directory);
+    output.extend(blocks);
+    Ok(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::font::{self, Font, FontCollection, FontRef, SfntVersion, Table};
+    use crate::woff::Woff;
+    use crate::woff2::glyf::tests::test_glyf;
+    use crate::woff2::{self, write_base128, write_uint255, Cursor, TableEntry};
+
+    /// A font with the test glyphs, whose proportional (but not monospaced)
+    /// left side bearings match the glyphs' xMin
+    fn test_font() -> Font {
+        let binary = FontRef::new(font::tests::LOCA_FONT).unwrap();
+        let mut font = Font::new(SfntVersion::TrueType);
+        for tag in &[b"head", b"hhea", b"maxp"] {
+            let data = binary.table_data(tag).unwrap().to_vec();
+            font.tables.insert(**tag, Table::Unknown(data));
+        }
+        if let Some(Table::Unknown(hhea)) = font.tables.get_mut(b"hhea") {
+            hhea[34..36].copy_from_slice(&2_u16.to_be_bytes());
+        }
+        let hmtx: Vec<u8> = [500, 0, 1500, 20, -5, 77]
+            .iter()
+            .flat_map(|v: &i16| v.to_be_bytes().to_vec())
+            .collect();
+        font.tables.insert(*b"hmtx", Table::Unknown(hmtx));
+        font.tables.insert(*b"glyf", Table::Glyf(test_glyf()));
+        font.tables
+            .insert(*b"zzzz", Table::Unknown(vec![1, 2, 3, 4, 5]));
+        font
+    }
+
+    fn to_sfnt(mut font: Font) -> Vec<u8> {
+        let mut sfnt = vec![];
+        font.save(&mut sfnt).unwrap();
+        sfnt
+    }
+
+    #[test]
+    fn woff2_roundtrip() {
+        let sfnt = to_sfnt(test_font());
+        let binary = woff2::encode(&Woff::new(sfnt.clone())).unwrap();
+        assert_eq!(&binary[0..8], b"wOF2\x00\x01\x00\x00");
+        assert_eq!(
+            u32::from_be_bytes([binary[8], binary[9], binary[10], binary[11]]) as usize,
+            binary.len()
+        );
+
+        let mut cursor = Cursor::new(&binary[48..]);
+        let entries: Vec<TableEntry> = (0..7)
+            .map(|_| TableEntry::read(&mut cursor).unwrap())
+            .collect();
+        let tags: Vec<&[u8; 4]> = entries.iter().map(|e| &e.tag).collect();
+        assert_eq!(
+            tags,
+            vec![b"glyf", b"loca", b"head", b"hhea", b"hmtx", b"maxp", b"zzzz"]
+        );
+        assert!(entries[0].is_transformed() && entries[0].transformLength.is_some());
+        assert_eq!(entries[1].transformLength, Some(0));
+        assert_eq!(entries[4].transform, 1);
+        assert!(!entries[6].is_transformed());
+
+        let decoded = woff2::decode(&binary).unwrap();
+        let mut original = font::load(sfnt.as_slice()).unwrap();
+        let mut roundtripped = font::load(decoded.sfnt.as_slice()).unwrap();
+        assert_eq!(
+            roundtripped.get_table(b"glyf").unwrap(),
+            original.get_table(b"glyf").unwrap()
+        );
+        for tag in &[b"hhea", b"hmtx", b"maxp", b"zzzz"] {
+            assert_eq!(roundtripped.tables[*tag], original.tables[*tag]);
+        }
+        let original_head = original.tables[b"head"].head_unchecked();
+        let head = roundtripped.tables[b"head"].head_unchecked();
+        assert_eq!(head.flags, original_head.flags | 0x0800);
+        assert_eq!(head.unitsPerEm, original_head.unitsPerEm);
+
+        // Encoding the decoded font again gives the same font
+        let again = woff2::decode(&woff2::encode(&decoded).unwrap()).unwrap();
+        assert_eq!(again.sfnt, decoded.sfnt);
+        assert_eq!(
+            font::load(binary.as_slice()).unwrap(),
+            font::load(decoded.sfnt.as_slice()).unwrap()
+        );
+    }
+
+    #[test]
+    fn woff2_collection() {
+        let first = test_font();
+        let mut second = test_font();
+        if let Some(Table::Unknown(hhea)) = second.tables.get_mut(b"hhea") {
+            hhea[4..6].copy_from_slice(&800_i16.to_be_bytes());
+        }
+        let mut collection = FontCollection::new();
+        collection.fonts.push(first);
+        collection.fonts.push(second);
+        let mut sfnt = vec![];
+        collection.save(&mut sfnt).unwrap();
+
+        let binary = woff2::encode(&Woff::new(sfnt)).unwrap();
+        assert_eq!(&binary[4..8], b"ttcf");
+        // Only head and hhea are stored for each font
+        assert_eq!(u16::from_be_bytes([binary[12], binary[13]]), 9);
+
+        let loaded = font::load_collection(binary.as_slice()).unwrap();
+        assert_eq!(loaded.fonts.len(), 2);
+        let (first, second) = (&loaded.fonts[0], &loaded.fonts[1]);
+        assert_eq!(first.tables[b"glyf"], second.tables[b"glyf"]);
+        assert_ne!(first.tables[b"hhea"], second.tables[b"hhea"]);
+        assert_eq!(
+            font::load(binary.as_slice()).unwrap_err().to_string(),
+            "Font is a collection; use font::load_collection to read it"
+        );
+    }
+
+    #[test]
+    fn woff2_metadata() {
+        let mut woff = Woff::new(to_sfnt(test_font()));
+        woff.metadata = Some(br#"<metadata version="1.0"/>"#.to_vec());
+        woff.privateData = Some(vec![9; 5]);
+        let binary = woff2::encode(&woff).unwrap();
+        let decoded = woff2::decode(&binary).unwrap();
+        assert_eq!(decoded.metadata, woff.metadata);
+        assert_eq!(decoded.privateData, woff.privateData);
+        assert_eq!(
+            (decoded.majorVersion, decoded.minorVersion),
+            (woff.majorVersion, woff.minorVersion)
+        );
+
+        assert!(woff2::decode(&binary[..60]).is_err());
+        let mut corrupt = binary;
+        corrupt[12] = 0xff;
+        assert!(woff2::decode(&corrupt).is_err());
+    }
+
+    #[test]
+    fn woff2_declared_sizes() {
+        let mut woff = Woff::new(to_sfnt(test_font()));
+        woff.metadata = Some(br#"<metadata version="1.0"/>"#.to_vec());
+        let binary = woff2::encode(&woff).unwrap();
+        let set = |binary: &mut Vec<u8>, offset: usize, value: u32| {
+            binary[offset..offset + 4].copy_from_slice(&value.to_be_bytes())
+        };
+        let total = u32::from_be_bytes([binary[16], binary[17], binary[18], binary[19]]);
+
+        let mut shrunk = binary.clone();
+        set(&mut shrunk, 16, total / 2);
+        let error = woff2::decode(&shrunk).unwrap_err();
+        assert!(error.to_string().contains("more than the total sfnt size"));
+
+        let mut huge = binary.clone();
+        set(&mut huge, 16, 0xFFFF_FFF0);
+        let error = woff2::decode(&huge).unwrap_err();
+        assert!(error.to_string().contains("more than the limit"));
+
+        let mut metadata = binary;
+        set(&mut metadata, 36, 0xFFFF_FFFF);
+        let error = woff2::decode(&metadata).unwrap_err();
+        assert!(error.to_string().contains("more than the limit"));
+    }
+
+    #[test]
+    fn woff2_variable_length_integers() {
+        for value in &[0, 127, 128, 16383, 16384, u32::MAX] {
+            let mut binary = vec![];
+            write_base128(&mut binary, *value);
+            assert_eq!(Cursor::new(&binary).uint_base128().unwrap(), *value);
+        }
+        assert!(Cursor::new(&[0x80, 0x01]).uint_base128().is_err());
+        assert!(Cursor::new(&[0x90, 0x80, 0x80, 0x80, 0x00])
+            .uint_base128()
+            .is_err());
+        assert!(Cursor::new(&[0x81; 6]).uint_base128().is_err());
+
+        for value in &[0, 252, 253, 505, 506, 761, 762, 65535] {
+            let mut binary = vec![];
+            write_uint255(&mut binary, *value);
+            assert_eq!(Cursor::new(&binary).uint255().unwrap(), *value);
+        }
+        let mut binary = vec![];
+        write_uint255(&mut binary, 300);
+        assert_eq!(binary, vec![255, 47]);
+    }
+}
@@ -0,0 +1,567 @@
+use super::{write_uint255, Cursor};
+use crate::font::{FontError, FontRef};
+use crate::glyf::{glyf, ComponentFlags, Glyph, Point};
+use otspec::error::Error as OTSpecError;
+use std::convert::TryFrom;
+
+/// Size of the transformed glyf header: four uint16 fields and seven stream sizes
+const HEADER_SIZE: usize = 36;
+
+const OVERLAP_SIMPLE_BITMAP: u16 = 0x0001;
+
+/// Size in bytes of a bitmap with one bit per glyph
+fn bitmap_size(num_glyphs: usize) -> usize {
+    (num_glyphs + 7) >> 3
+}
+
+/// Size in bytes of the bitmap recording which glyphs have an explicit
+/// bounding box, which is padded to a multiple of four bytes
+fn bbox_bitmap_size(num_glyphs: usize) -> usize {
+    (bitmap_size(num_glyphs) + 3) & !3
+}
+
+/// A transformed glyf table, and the xMin of each glyph
+type TransformedGlyf = (Vec<u8>, Vec<i16>);
+
+fn bit_is_set(bitmap: &[u8], index: usize) -> bool {
+    bitmap[index >> 3] & (0x80 >> (index & 7)) != 0
+}
+
+fn set_bit(bitmap: &mut [u8], index: usize) {
+    bitmap[index >> 3] |= 0x80 >> (index & 7);
+}
+
+fn with_sign(flag: u8, value: i32) -> i32 {
+    if flag & 1 != 0 {
+        value
+    } else {
+        -value
+    }
+}
+
+/// Reads a point's coordinate deltas, encoded according to its flag byte
+fn read_triplet(flag: u8, glyph_stream: &mut Cursor) -> otspec::error::Result<(i32, i32)> {
+    let flag = flag & 0x7f;
+    Ok(if flag < 10 {
+        let b0 = glyph_stream.u8()? as i32;
+        (0, with_sign(flag, (((flag & 14) as i32) << 7) + b0))
+    } else if flag < 20 {
+        let b0 = glyph_stream.u8()? as i32;
+        (with_sign(flag, ((((flag - 10) & 14) as i32) << 7) + b0), 0)
+    } else if flag < 84 {
+        let b0 = (flag - 20) as i32;
+        let b1 = glyph_stream.u8()? as i32;
+        (
+            with_sign(flag, 1 + (b0 & 0x30) + (b1 >> 4)),
+            with_sign(flag >> 1, 1 + ((b0 & 0x0c) << 2) + (b1 & 0x0f)),
+        )
+    } else if flag < 120 {
+        let b0 = (flag - 84) as i32;
+        let bytes = glyph_stream.bytes(2)?;
+        (
+            with_sign(flag, 1 + ((b0 / 12) << 8) + bytes[0] as i32),
+            with_sign(flag >> 1, 1 + (((b0 % 12) >> 2) << 8) + bytes[1] as i32),
+        )
+    } else if flag < 124 {
+        let bytes = glyph_stream.bytes(3)?;
+        let (b0, b1, b2) = (bytes[0] as i32, bytes[1] as i32, bytes[2] as i32);
+        (
+            with_sign(flag, (b0 << 4) + (b1 >> 4)),
+            with_sign(flag >> 1, ((b1 & 0x0f) << 8) + b2),
+        )
+    } else {
+        let bytes = glyph_stream.bytes(4)?;
+        (
+            with_sign(flag, ((bytes[0] as i32) << 8) + bytes[1] as i32),
+            with_sign(flag >> 1, ((bytes[2] as i32) << 8) + bytes[3] as i32),
+        )
+    })
+}
+
+/// Writes a point's coordinate deltas in the smallest encoding which fits
+fn write_triplet(
+    flags: &mut Vec<u8>,
+    glyph_stream: &mut Vec<u8>,
+    dx: i32,
+    dy: i32,
+    on_curve: bool,
+) {
+    let (abs_x, abs_y) = (dx.abs(), dy.abs());
+    let on_curve_bit = if on_curve { 0 } else { 128 };
+    let x_sign_bit = if dx < 0 { 0 } else { 1 };
+    let y_sign_bit = if dy < 0 { 0 } else { 1 };
+    let xy_sign_bits = x_sign_bit + 2 * y_sign_bit;
+    if dx == 0 && abs_y < 1280 {
+        flags.push((on_curve_bit + ((abs_y & 0xf00) >> 7) + y_sign_bit) as u8);
+        glyph_stream.push((abs_y & 0xff) as u8);
+    } else if dy == 0 && abs_x < 1280 {
+        flags.push((on_curve_bit + 10 + ((abs_x & 0xf00) >> 7) + x_sign_bit) as u8);
+        glyph_stream.push((abs_x & 0xff) as u8);
+    } else if abs_x < 65 && abs_y < 65 {
+        flags.push(
+            (on_curve_bit + 20 + ((abs_x - 1) & 0x30) + (((abs_y - 1) & 0x30) >> 2) + xy_sign_bits)
+                as u8,
+        );
+        glyph_stream.push(((((abs_x - 1) & 0xf) << 4) | ((abs_y - 1) & 0xf)) as u8);
+    } else if abs_x < 769 && abs_y < 769 {
+        flags.push(
+            (on_curve_bit
+                + 84
+                + 12 * (((abs_x - 1) & 0x300) >> 8)
+                + (((abs_y - 1) & 0x300) >> 6)
+                + xy_sign_bits) as u8,
+        );
+        glyph_stream.push(((abs_x - 1) & 0xff) as u8);
+        glyph_stream.push(((abs_y - 1) & 0xff) as u8);
+    } else if abs_x < 4096 && abs_y < 4096 {
+        flags.push((on_curve_bit + 120 + xy_sign_bits) as u8);
+        glyph_stream.push((abs_x >> 4) as u8);
+        glyph_stream.push((((abs_x & 0xf) << 4) | (abs_y >> 8)) as u8);
+        glyph_stream.push((abs_y & 0xff) as u8);
+    } else {
+        flags.push((on_curve_bit + 124 + xy_sign_bits) as u8);
+        glyph_stream.extend(&(abs_x as u16).to_be_bytes());
+        glyph_stream.extend(&(abs_y as u16).to_be_bytes());
+    }
+}
+
+/// The bounding box of a simple glyph's points, as (xMin, yMin, xMax, yMax)
+fn points_bounds<'a>(points: impl Iterator<Item = &'a Point>) -> (i16, i16, i16, i16) {
+    let mut bounds: Option<(i16, i16, i16, i16)> = None;
+    for pt in points {
+        bounds = Some(match bounds {
+            None => (pt.x, pt.y, pt.x, pt.y),
+            Some((x0, y0, x1, y1)) => (x0.min(pt.x), y0.min(pt.y), x1.max(pt.x), y1.max(pt.y)),
+        });
+    }
+    bounds.unwrap_or((0, 0, 0, 0))
+}
+
+fn instruction_length(glyph: &Glyph, gid: usize) -> otspec::error::Result<u16> {
+    u16::try_from(glyph.instructions.len())
+        .map_err(|_| OTSpecError::Message(format!("Glyph {} has too many instructions", gid)))
+}
+
+/// Transforms a glyf table into the WOFF2 format
+fn transform_glyf(glyf: &glyf, index_format: i16) -> otspec::error::Result<Vec<u8>> {
+    let num_glyphs = u16::try_from(glyf.glyphs.len())
+        .map_err(|_| OTSpecError::Message("Too many glyphs".into()))?;
+    let mut n_contours: Vec<u8> = vec![];
+    let mut n_points: Vec<u8> = vec![];
+    let mut flags: Vec<u8> = vec![];
+    let mut glyph_stream: Vec<u8> = vec![];
+    let mut composite: Vec<u8> = vec![];
+    let mut bbox_bitmap = vec![0; bbox_bitmap_size(glyf.glyphs.len())];
+    let mut bboxes: Vec<u8> = vec![];
+    let mut instructions: Vec<u8> = vec![];
+    let mut overlap_bitmap = vec![0; bitmap_size(glyf.glyphs.len())];
+    let mut has_overlap = false;
+
+    for (gid, glyph) in glyf.glyphs.iter().enumerate() {
+        let bbox = (glyph.xMin, glyph.yMin, glyph.xMax, glyph.yMax);
+        if glyph.is_empty() {
+            n_contours.extend(&0_i16.to_be_bytes());
+            continue;
+        }
+        if glyph.has_components() {
+            n_contours.extend(&(-1_i16).to_be_bytes());
+            // The component records are stored as they are in the glyf table,
+            // without the glyph header or the instructions which follow them
+            let binary = otspec::ser::to_bytes(glyph)?;
+            let trailer = if glyph.instructions.is_empty() {
+                0
+            } else {
+                2 + glyph.instructions.len()
+            };
+            composite.extend(&binary[10..binary.len() - trailer]);
+            if !glyph.instructions.is_empty() {
+                write_uint255(&mut glyph_stream, instruction_length(glyph, gid)?);
+                instructions.extend(&glyph.instructions);
+            }
+            // Composite glyphs always have an explicit bounding box
+            set_bit(&mut bbox_bitmap, gid);
+        } else {
+            let num_contours = i16::try_from(glyph.contours.len()).map_err(|_| {
+                OTSpecError::Message(format!("Glyph {} has too many contours", gid))
+            })?;
+            n_contours.extend(&num_contours.to_be_bytes());
+            let (mut last_x, mut last_y) = (0_i32, 0_i32);
+            for contour in &glyph.contours {
+                let count = u16::try_from(contour.len()).map_err(|_| {
+                    OTSpecError::Message(format!("Glyph {} has too many points", gid))
+                })?;
+                write_uint255(&mut n_points, count);
+                for point in contour {
+                    let (x, y) = (point.x as i32, point.y as i32);
+                    write_triplet(
+                        &mut flags,
+                        &mut glyph_stream,
+                        x - last_x,
+                        y - last_y,
+                        point.on_curve,
+                    );
+                    last_x = x;
+                    last_y = y;
+                }
+            }
+            write_uint255(&mut glyph_stream, instruction_length(glyph, gid)?);
+            instructions.extend(&glyph.instructions);
+            if points_bounds(glyph.contours.iter().flatten()) != bbox {
+                set_bit(&mut bbox_bitmap, gid);
+            }
+            if glyph.overlap {
+                set_bit(&mut overlap_bitmap, gid);
+                has_overlap = true;
+            }
+        }
+        if bit_is_set(&bbox_bitmap, gid) {
+            for value in &[bbox.0, bbox.1, bbox.2, bbox.3] {
+                bboxes.extend(&value.to_be_bytes());
+            }
+        }
+    }
+
+    let mut bbox_stream = bbox_bitmap;
+    bbox_stream.extend(bboxes);
+    let streams = [
+        n_contours,
+        n_points,
+        flags,
+        glyph_stream,
+        composite,
+        bbox_stream,
+        instructions,
+    ];
+    let option_flags = if has_overlap {
+        OVERLAP_SIMPLE_BITMAP
+    } else {
+        0
+    };
+    let mut output: Vec<u8> = vec![];
+    output.extend(&0_u16.to_be_bytes());
+    output.extend(&option_flags.to_be_bytes());
+    output.extend(&num_glyphs.to_be_bytes());
+    output.extend(&(index_format as u16).to_be_bytes());
+    for stream in &streams {
+        let size = u32::try_from(stream.len()).map_err(|_| OTSpecError::OffsetOverflow)?;
+        output.extend(&size.to_be_bytes());
+    }
+    for stream in &streams {
+        output.extend(stream);
+    }
+    if has_overlap {
+        output.extend(overlap_bitmap);
+    }
+    Ok(output)
+}
+
+/// Transforms a font's glyf table
+///
+/// Returns Ok(None) if the glyf table could not be read, in which case it
+/// should be stored untransformed.
+pub(super) fn transform(font: &mut FontRef) -> Result<Option<TransformedGlyf>, FontError> {
+    let index_format = match font.table_data(b"head") {
+        Some(head) if head.len() >= 52 => i16::from_be_bytes([head[50], head[51]]),
+        _ => return Ok(None),
+    };
+    let glyf = match font.get_table(b"glyf") {
+        Ok(Some(table)) => table.glyf_unchecked(),
+        Ok(None) => return Ok(None),
+        Err(e) => {
+            log::warn!("Storing glyf table untransformed: {}", e);
+            return Ok(None);
+        }
+    };
+    let transformed = transform_glyf(glyf, index_format).map_err(|e| e.in_table(*b"glyf"))?;
+    let x_mins = glyf.glyphs.iter().map(|g| g.xMin).collect();
+    Ok(Some((transformed, x_mins)))
+}
+
+/// Reads a composite glyph's component records, returning them and whether
+/// they are followed by instructions
+fn read_components<'a>(composite: &mut Cursor<'a>) -> otspec::error::Result<(&'a [u8], bool)> {
+    let start = composite.pos;
+    let mut has_instructions = false;
+    loop {
+        let flags = ComponentFlags::from_bits_truncate(composite.u16()?);
+        let mut length = 2; // glyphIndex
+        length += if flags.contains(ComponentFlags::ARG_1_AND_2_ARE_WORDS) {
+            4
+        } else {
+            2
+        };
+        if flags.contains(ComponentFlags::WE_HAVE_A_SCALE) {
+            length += 2;
+        } else if flags.contains(ComponentFlags::WE_HAVE_AN_X_AND_Y_SCALE) {
+            length += 4;
+        } else if flags.contains(ComponentFlags::WE_HAVE_A_TWO_BY_TWO) {
+            length += 8;
+        }
+        composite.bytes(length)?;
+        if flags.contains(ComponentFlags::WE_HAVE_INSTRUCTIONS) {
+            has_instructions = true;
+        }
+        if !flags.contains(ComponentFlags::MORE_COMPONENTS) {
+            break;
+        }
+    }
+    Ok((&composite.data[start..composite.pos], has_instructions))
+}
+
+/// Rebuilds a glyf table from its WOFF2 transformed form
+pub(super) fn reconstruct(data: &[u8]) -> otspec::error::Result<glyf> {
+    let mut header = Cursor::new(data);
+    let _version = header.u16()?;
+    let option_flags = header.u16()?;
+    let num_glyphs = header.u16()? as usize;
+    let _index_format = header.u16()?;
+    let mut streams = Cursor::new(data);
+    streams.bytes(HEADER_SIZE)?;
+    let mut next_stream = || -> otspec::error::Result<Cursor> {
+        let size = header.u32()? as usize;
+        Ok(Cursor::new(streams.bytes(size)?))
+    };
+    let mut n_contours = next_stream()?;
+    let mut n_points = next_stream()?;
+    let mut flags = next_stream()?;
+    let mut glyph_stream = next_stream()?;
+    let mut composite = next_stream()?;
+    let mut bbox_stream = next_stream()?;
+    let mut instructions = next_stream()?;
+    let bbox_bitmap = bbox_stream.bytes(bbox_bitmap_size(num_glyphs))?;
+    let overlap_bitmap = if option_flags & OVERLAP_SIMPLE_BITMAP != 0 {
+        Some(streams.bytes(bitmap_size(num_glyphs))?)
+    } else {
+        None
+    };
+
+    let mut glyphs = vec![];
+    for gid in 0..num_glyphs {
+        let num_contours = n_contours.i16()?;
+        let has_bbox = bit_is_set(bbox_bitmap, gid);
+        let glyph = if num_contours == 0 {
+            Glyph {
+                xMin: 0,
+                xMax: 0,
+                yMin: 0,
+                yMax: 0,
+                contours: vec![],
+                instructions: vec![],
+                components: vec![],
+                overlap: false,
+            }
+        } else if num_contours == -1 {
+            if !has_bbox {
+                return Err(OTSpecError::Message(format!(
+                    "Composite glyph {} has no bounding box",
+                    gid
+                )));
+            }
+            // Reassemble the binary glyph and read it as usual
+            let mut binary = (-1_i16).to_be_bytes().to_vec();
+            binary.extend(bbox_stream.bytes(8)?);
+            let (components, has_instructions) = read_components(&mut composite)?;
+            binary.extend(components);
+            if has_instructions {
+                let length = glyph_stream.uint255()?;
+                binary.extend(&length.to_be_bytes());
+                binary.extend(instructions.bytes(length as usize)?);
+            }
+            otspec::de::from_bytes(&binary)?
+        } else if num_contours > 0 {
+            let mut contours = vec![];
+            let (mut x, mut y) = (0_i16, 0_i16);
+            for _ in 0..num_contours {
+                let count = n_points.uint255()?;
+                let mut contour = vec![];
+                for _ in 0..count {
+                    let flag = flags.u8()?;
+                    let (dx, dy) = read_triplet(flag, &mut glyph_stream)?;
+                    x = x.wrapping_add(dx as i16);
+                    y = y.wrapping_add(dy as i16);
+                    contour.push(Point {
+                        x,
+                        y,
+                        on_curve: flag & 0x80 == 0,
+                    });
+                }
+                contours.push(contour);
+            }
+            let length = glyph_stream.uint255()?;
+            let glyph_instructions = instructions.bytes(length as usize)?.to_vec();
+            let (xMin, yMin, xMax, yMax) = if has_bbox {
+                (
+                    bbox_stream.i16()?,
+                    bbox_stream.i16()?,
+                    bbox_stream.i16()?,
+                    bbox_stream.i16()?,
+                )
+            } else {
+                points_bounds(contours.iter().flatten())
+            };
+            Glyph {
+                xMin,
+                xMax,
+                yMin,
+                yMax,
+                contours,
+                instructions: glyph_instructions,
+                components: vec![],
+                overlap: matches!(overlap_bitmap, Some(bitmap) if bit_is_set(bitmap, gid)),
+            }
+        } else {
+            return Err(OTSpecError::Message(format!(
+                "Glyph {} has an invalid number of contours ({})",
+                gid, num_contours
+            )));
+        };
+        glyphs.push(glyph);
+    }
+    Ok(glyf { glyphs })
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::{read_triplet, reconstruct, transform_glyf, write_triplet};
+    use crate::glyf::{glyf, Component, ComponentFlags, Glyph, Point};
+    use crate::woff2::Cursor;
+
+    fn point(x: i16, y: i16, on_curve: bool) -> Point {
+        Point { x, y, on_curve }
+    }
+
+    /// An empty glyph, two simple glyphs and a composite glyph, as they would
+    /// be read from a binary font
+    pub(crate) fn test_glyf() -> glyf {
+        let empty = Glyph {
+            xMin: 0,
+            xMax: 0,
+            yMin: 0,
+            yMax: 0,
+            contours: vec![],
+            instructions: vec![],
+            components: vec![],
+            overlap: false,
+        };
+        let simple = Glyph {
+            xMin: 20,
+            xMax: 1480,
+            yMin: -300,
+            yMax: 700,
+            contours: vec![
+                vec![
+                    point(20, 0, true),
+                    point(20, 700, false),
+                    point(1480, 700, false),
+                    point(1480, -300, true),
+                ],
+                vec![
+                    point(100, 100, true),
+                    point(150, 120, true),
+                    point(130, 90, false),
+                ],
+            ],
+            instructions: vec![0xb0, 0x01, 0x2f],
+            components: vec![],
+            overlap: false,
+        };
+        // The bounding box doesn't match the points, so must be stored
+        let bbox = Glyph {
+            xMin: -5,
+            xMax: 300,
+            yMin: 0,
+            yMax: 310,
+            contours: vec![vec![
+                point(0, 0, true),
+                point(300, 300, true),
+                point(0, 300, true),
+            ]],
+            instructions: vec![],
+            components: vec![],
+            overlap: false,
+        };
+        let composite = Glyph {
+            xMin: 30,
+            xMax: 1490,
+            yMin: -320,
+            yMax: 680,
+            contours: vec![],
+            instructions: vec![0x40, 0x01, 0x00],
+            components: vec![
+                Component {
+                    glyphIndex: 1,
+                    transformation: kurbo::Affine::new([1.0, 0.0, 0.0, 1.0, 10.0, -20.0]),
+                    matchPoints: None,
+                    flags: ComponentFlags::ARGS_ARE_XY_VALUES
+                        | ComponentFlags::MORE_COMPONENTS
+                        | ComponentFlags::USE_MY_METRICS,
+                },
+                Component {
+                    glyphIndex: 2,
+                    transformation: kurbo::Affine::new([0.5, 0.0, 0.0, 0.5, 400.0, 0.0]),
+                    matchPoints: None,
+                    flags: ComponentFlags::ARG_1_AND_2_ARE_WORDS
+                        | ComponentFlags::ARGS_ARE_XY_VALUES
+                        | ComponentFlags::WE_HAVE_A_SCALE
+                        | ComponentFlags::WE_HAVE_INSTRUCTIONS,
+                },
+            ],
+            overlap: false,
+        };
+        glyf {
+            glyphs: vec![empty, simple, bbox, composite],
+        }
+    }
+
+    #[test]
+    fn woff2_glyf_transform() {
+        let mut glyf = test_glyf();
+        glyf.glyphs[2].overlap = true;
+        let transformed = transform_glyf(&glyf, 0).unwrap();
+        // Version, option flags (overlap bitmap present), numGlyphs, indexFormat
+        assert_eq!(&transformed[0..8], &[0, 0, 0, 1, 0, 4, 0, 0]);
+        // nContourStream has a 16-bit count for each glyph
+        assert_eq!(&transformed[8..12], &[0, 0, 0, 8]);
+        assert_eq!(reconstruct(&transformed).unwrap(), glyf);
+
+        assert!(reconstruct(&transformed[..transformed.len() - 1]).is_err());
+        // A composite glyph needs an explicit bounding box
+        let mut transformed = transform_glyf(&test_glyf(), 0).unwrap();
+        transformed[36..38].copy_from_slice(&[0xff, 0xff]);
+        assert_eq!(
+            reconstruct(&transformed).unwrap_err().to_string(),
+            "Composite glyph 0 has no bounding box"
+        );
+    }
+
+    #[test]
+    fn woff2_triplets() {
+        let deltas = [
+            (0, 0),
+            (0, -1279),
+            (1279, 0),
+            (-64, 64),
+            (1, -1),
+            (768, -768),
+            (-4095, 4095),
+            (4096, 0),
+            (-32768, 65535),
+        ];
+        for on_curve in &[true, false] {
+            let (mut flags, mut stream) = (vec![], vec![]);
+            for (dx, dy) in &deltas {
+                write_triplet(&mut flags, &mut stream, *dx, *dy, *on_curve);
+            }
+            let mut cursor = Cursor::new(&stream);
+            for (flag, delta) in flags.iter().zip(&deltas) {
+                assert_eq!(flag & 0x80 == 0, *on_curve);
+                assert_eq!(read_triplet(*flag, &mut cursor).unwrap(), *delta);
+            }
+            assert_eq!(cursor.pos, stream.len());
+        }
+        // Short deltas take a single byte
+        let (mut flags, mut stream) = (vec![], vec![]);
+        write_triplet(&mut flags, &mut stream, 3, -7, true);
+        assert_eq!(stream.len(), 1);
+    }
+}
@@ -0,0 +1,101 @@
+use super::Cursor;
+use crate::font::FontRef;
+use otspec::error::Error as OTSpecError;
+
+/// The left side bearings of the proportional glyphs are omitted
+const NO_PROPORTIONAL_LSBS: u8 = 0x01;
+/// The left side bearings of the monospaced glyphs are omitted
+const NO_MONOSPACED_LSBS: u8 = 0x02;
+
+/// Transforms a font's hmtx table, given the xMin of each glyph
+///
+/// Returns None if the transform would not omit any side bearings, or the
+/// hmtx table does not match the number of glyphs.
+pub(super) fn transform(font: &FontRef, x_mins: &[i16]) -> Option<Vec<u8>> {
+    let hhea = font.table_data(b"hhea")?;
+    let hmtx = font.table_data(b"hmtx")?;
+    let num_h_metrics = u16::from_be_bytes([*hhea.get(34)?, *hhea.get(35)?]) as usize;
+    let num_glyphs = x_mins.len();
+    if num_h_metrics == 0
+        || num_h_metrics > num_glyphs
+        || hmtx.len() < 2 * num_glyphs + 2 * num_h_metrics
+    {
+        return None;
+    }
+    let lsb = |gid: usize| {
+        let pos = if gid < num_h_metrics {
+            4 * gid + 2
+        } else {
+            4 * num_h_metrics + 2 * (gid - num_h_metrics)
+        };
+        i16::from_be_bytes([hmtx[pos], hmtx[pos + 1]])
+    };
+    let mut flags = 0;
+    if (0..num_h_metrics).all(|gid| lsb(gid) == x_mins[gid]) {
+        flags |= NO_PROPORTIONAL_LSBS;
+    }
+    if (num_h_metrics..num_glyphs).all(|gid| lsb(gid) == x_mins[gid]) {
+        flags |= NO_MONOSPACED_LSBS;
+    }
+    if flags == 0 {
+        return None;
+    }
+
+    let mut output = vec![flags];
+    for gid in 0..num_h_metrics {
+        output.extend(&hmtx[4 * gid..4 * gid + 2]);
+    }
+    if flags & NO_PROPORTIONAL_LSBS == 0 {
+        for gid in 0..num_h_metrics {
+            output.extend(&lsb(gid).to_be_bytes());
+        }
+    }
+    if flags & NO_MONOSPACED_LSBS == 0 {
+        for gid in num_h_metrics..num_glyphs {
+            output.extend(&lsb(gid).to_be_bytes());
+        }
+    }
+    Some(output)
+}
+
+/// Rebuilds an hmtx table from its WOFF2 transformed form
+pub(super) fn reconstruct(
+    data: &[u8],
+    numberOfHMetrics: u16,
+    x_mins: &[i16],
+) -> otspec::error::Result<Vec<u8>> {
+    let num_h_metrics = numberOfHMetrics as usize;
+    let num_glyphs = x_mins.len();
+    if num_h_metrics == 0 || num_h_metrics > num_glyphs {
+        return Err(OTSpecError::Message(format!(
+            "numberOfHMetrics is {}, but there are {} glyphs",
+            num_h_metrics, num_glyphs
+        )));
+    }
+    let mut cursor = Cursor::new(data);
+    let flags = cursor.u8()?;
+    if flags & (NO_PROPORTIONAL_LSBS | NO_MONOSPACED_LSBS) == 0 {
+        return Err(OTSpecError::Message(
+            "Transformed hmtx table must omit some side bearings".into(),
+        ));
+    }
+    let advances = cursor.bytes(2 * num_h_metrics)?;
+    let mut lsbs = vec![];
+    for (gid, x_min) in x_mins.iter().enumerate() {
+        let omitted = if gid < num_h_metrics {
+            flags & NO_PROPORTIONAL_LSBS != 0
+        } else {
+            flags & NO_MONOSPACED_LSBS != 0
+        };
+        lsbs.push(if omitted { *x_min } else { cursor.i16()? });
+    }
+
+    let mut output = vec![];
+    for (gid, lsb) in lsbs.iter().enumerate() {
+        if gid < num_h_metrics {
+            output.extend(&advances[2 * gid..2 * gid + 2]);
+        }
+        output.extend(&lsb.to_be_bytes());
+    }
+    Ok(output)
+}